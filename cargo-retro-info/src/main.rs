@@ -0,0 +1,141 @@
+//! `cargo retro-info <core library> [--output <file>]`
+//!
+//! Loads a core built with rust-libretro and writes the `.info` file it describes
+//! in `Core::core_info`, see the `core_info` module of rust-libretro.
+use std::{
+    error::Error,
+    ffi::CStr,
+    os::raw::c_char,
+    path::{Path, PathBuf},
+};
+
+/// The same as `rust_libretro::core_info::CORE_INFO_SYMBOL`, the tool doesn’t link
+/// rust-libretro because that expects to be part of a core.
+const CORE_INFO_SYMBOL: &[u8] = b"rust_libretro_core_info\0";
+
+const USAGE: &str = "Usage: cargo retro-info <core library> [--output <file>]";
+
+struct Args {
+    core: PathBuf,
+    output: PathBuf,
+}
+
+impl Args {
+    /// Returns [`None`] if the usage was asked for.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut core = None;
+        let mut output = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--output" => match args.next() {
+                    Some(path) => output = Some(PathBuf::from(path)),
+                    None => return Err(format!("{arg} needs a path\n{USAGE}").into()),
+                },
+                "-h" | "--help" => return Ok(None),
+                _ if core.is_none() => core = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument {arg}\n{USAGE}").into()),
+            }
+        }
+
+        let core = core.ok_or(USAGE)?;
+        let output = output.unwrap_or_else(|| default_output(&core));
+
+        Ok(Some(Self { core, output }))
+    }
+}
+
+/// Returns `<name>_libretro.info` next to the core, the name frontends look for,
+/// e.g. `example_libretro.info` for `libexample.so` or `example_libretro.dll`.
+fn default_output(core: &Path) -> PathBuf {
+    let stem = core
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+
+    let name = stem.strip_prefix("lib").unwrap_or(&stem);
+    let name = name.strip_suffix("_libretro").unwrap_or(name);
+
+    core.with_file_name(format!("{name}_libretro.info"))
+}
+
+/// Returns the `.info` file exported by the core at `path`.
+fn read_core_info(path: &Path) -> Result<String, Box<dyn Error>> {
+    unsafe {
+        let library = libloading::Library::new(path)?;
+        let core_info: libloading::Symbol<unsafe extern "C" fn() -> *const c_char> = library
+            .get(CORE_INFO_SYMBOL)
+            .map_err(|err| format!("{} is not a rust-libretro core: {err}", path.display()))?;
+
+        let info = core_info();
+        if info.is_null() {
+            return Err("The core didn’t return its info".into());
+        }
+
+        Ok(CStr::from_ptr(info).to_string_lossy().into_owned())
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    // Cargo passes the name of the subcommand as the first argument
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("retro-info") {
+        args.next();
+    }
+
+    let Some(args) = Args::parse(args)? else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+
+    let info = read_core_info(&args.core)?;
+    std::fs::write(&args.output, info)?;
+
+    println!("Wrote {}", args.output.display());
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+#[test]
+fn parse_args() {
+    let parse = |args: &[&str]| Args::parse(args.iter().map(|arg| arg.to_string()));
+
+    assert!(matches!(parse(&["--help"]), Ok(None)));
+    assert!(matches!(parse(&["libexample.so", "-h"]), Ok(None)));
+    assert!(parse(&[]).is_err());
+    assert!(parse(&["libexample.so", "--output"]).is_err());
+
+    let args = parse(&["libexample.so", "-o", "example.info"])
+        .unwrap()
+        .unwrap();
+    assert_eq!(args.output, Path::new("example.info"));
+
+    let args = parse(&["target/release/libexample.so"]).unwrap().unwrap();
+    assert_eq!(
+        args.output,
+        Path::new("target/release/example_libretro.info")
+    );
+}
+
+#[test]
+fn default_output_names() {
+    assert_eq!(
+        default_output(Path::new("libexample_core.dylib")),
+        Path::new("example_core_libretro.info")
+    );
+    assert_eq!(
+        default_output(Path::new("example_libretro.dll")),
+        Path::new("example_libretro.info")
+    );
+    assert_eq!(
+        default_output(Path::new("libexample_libretro.so")),
+        Path::new("example_libretro.info")
+    );
+}
@@ -0,0 +1,428 @@
+//! A minimal libretro core layer that only depends on `core` and `alloc`.
+//!
+//! Cores implement [`RawCore`] and export it with [`retro_core!`], which makes this
+//! crate provide the `retro_*` functions of the libretro API.
+//! Unlike `rust-libretro` there are no contexts, logging, paths or core options,
+//! everything besides the audio, video and input callbacks of [`Frontend`] goes
+//! through [`Frontend::environment`].
+//!
+//! Frontends call the core from a single thread, so the instance lives in a
+//! `static mut` without any locking.
+#![no_std]
+#![allow(clippy::missing_safety_doc)]
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/max-m/rust-libretro/master/media/logo.png",
+    html_favicon_url = "https://raw.githubusercontent.com/max-m/rust-libretro/master/media/favicon.png"
+)]
+
+extern crate alloc;
+
+pub use rust_libretro_sys as sys;
+
+use alloc::boxed::Box;
+use core::{
+    ffi::{c_char, c_uint, c_void, CStr},
+    ptr::addr_of_mut,
+};
+use sys::*;
+
+/// The callbacks the frontend passed to the core.
+///
+/// Calls to callbacks the frontend has not set yet are ignored.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Frontend {
+    environment: retro_environment_t,
+    video_refresh: retro_video_refresh_t,
+    audio_sample: retro_audio_sample_t,
+    audio_sample_batch: retro_audio_sample_batch_t,
+    input_poll: retro_input_poll_t,
+    input_state: retro_input_state_t,
+}
+
+impl Frontend {
+    /// Calls the environment callback with a `RETRO_ENVIRONMENT_*` command.
+    ///
+    /// # Safety
+    /// `data` has to point to what the frontend expects for `cmd`.
+    pub unsafe fn environment(&self, cmd: u32, data: *mut c_void) -> bool {
+        match self.environment {
+            Some(callback) => callback(cmd, data),
+            None => false,
+        }
+    }
+
+    /// Sends a frame of `height` lines of `pitch` bytes each, `None` duplicates the last frame.
+    ///
+    /// Panics if `frame` is shorter than `height * pitch`.
+    pub fn video_refresh(&self, frame: Option<&[u8]>, width: u32, height: u32, pitch: usize) {
+        let data = match frame {
+            Some(frame) => {
+                assert!(
+                    frame.len() >= height as usize * pitch,
+                    "The frame is smaller than height * pitch"
+                );
+                frame.as_ptr() as *const c_void
+            }
+            None => core::ptr::null(),
+        };
+
+        if let Some(callback) = self.video_refresh {
+            unsafe { callback(data, width, height, pitch as size_t) }
+        }
+    }
+
+    pub fn audio_sample(&self, left: i16, right: i16) {
+        if let Some(callback) = self.audio_sample {
+            unsafe { callback(left, right) }
+        }
+    }
+
+    /// Sends interleaved stereo samples, returns the number of frames the frontend took.
+    pub fn audio_samples(&self, samples: &[i16]) -> usize {
+        match self.audio_sample_batch {
+            Some(callback) => unsafe {
+                callback(samples.as_ptr(), (samples.len() / 2) as size_t) as usize
+            },
+            None => 0,
+        }
+    }
+
+    pub fn input_poll(&self) {
+        if let Some(callback) = self.input_poll {
+            unsafe { callback() }
+        }
+    }
+
+    pub fn input_state(&self, port: u32, device: u32, index: u32, id: u32) -> i16 {
+        match self.input_state {
+            Some(callback) => unsafe { callback(port, device, index, id) },
+            None => 0,
+        }
+    }
+}
+
+/// The functions of the libretro API, with the raw types of [`sys`].
+///
+/// See `libretro.h` for when the frontend calls them.
+pub trait RawCore {
+    /// The strings have to be nul-terminated and stay valid as long as the core is loaded.
+    fn system_info(&self) -> retro_system_info;
+
+    fn system_av_info(&mut self, frontend: &Frontend) -> retro_system_av_info;
+
+    /// `initial` is `true` for the first call, which happens before [`RawCore::on_init`].
+    fn on_set_environment(&mut self, _initial: bool, _frontend: &Frontend) {}
+
+    fn on_init(&mut self, _frontend: &Frontend) {}
+
+    fn on_deinit(&mut self) {}
+
+    fn on_set_controller_port_device(&mut self, _port: u32, _device: u32) {}
+
+    fn on_reset(&mut self, _frontend: &Frontend) {}
+
+    fn on_run(&mut self, frontend: &Frontend);
+
+    fn serialize_size(&mut self) -> usize {
+        0
+    }
+
+    fn on_serialize(&mut self, _data: &mut [u8]) -> bool {
+        false
+    }
+
+    fn on_unserialize(&mut self, _data: &[u8]) -> bool {
+        false
+    }
+
+    fn on_cheat_reset(&mut self) {}
+
+    fn on_cheat_set(&mut self, _index: u32, _enabled: bool, _code: &CStr) {}
+
+    fn on_load_game(&mut self, game: Option<&retro_game_info>, frontend: &Frontend) -> bool;
+
+    fn on_load_game_special(
+        &mut self,
+        _game_type: u32,
+        _info: &[retro_game_info],
+        _frontend: &Frontend,
+    ) -> bool {
+        false
+    }
+
+    fn on_unload_game(&mut self) {}
+
+    fn region(&mut self) -> u32 {
+        RETRO_REGION_NTSC
+    }
+
+    /// The `RETRO_MEMORY_*` region `id`, it has to stay in place until the game gets unloaded.
+    fn memory(&mut self, _id: u32) -> Option<&mut [u8]> {
+        None
+    }
+}
+
+/// Exports a [`RawCore`]; the expression gets evaluated on the first call of the frontend.
+///
+/// ```ignore
+/// retro_core!(ExampleCore::new());
+/// ```
+#[macro_export]
+macro_rules! retro_core {
+    ( $( $definition:tt )+ ) => {
+        #[doc(hidden)]
+        #[inline(never)]
+        #[no_mangle]
+        pub unsafe extern "Rust" fn __retro_init_nostd_core() {
+            $crate::set_core($($definition)+);
+        }
+    }
+}
+
+struct Instance {
+    core: Box<dyn RawCore>,
+    frontend: Frontend,
+}
+
+static mut INSTANCE: Option<Instance> = None;
+
+#[doc(hidden)]
+pub fn set_core<C: 'static + RawCore>(core: C) {
+    let instance = unsafe { &mut *addr_of_mut!(INSTANCE) };
+
+    if instance.is_some() {
+        panic!("Attempted to set a core after the system was already initialized.");
+    }
+
+    *instance = Some(Instance {
+        core: Box::new(core),
+        frontend: Frontend::default(),
+    });
+}
+
+/// Returns the instance, creating it if the frontend has not called the core before.
+unsafe fn instance() -> &'static mut Instance {
+    let instance = &mut *addr_of_mut!(INSTANCE);
+
+    if instance.is_none() {
+        extern "Rust" {
+            fn __retro_init_nostd_core();
+        }
+        __retro_init_nostd_core();
+    }
+
+    match instance {
+        Some(instance) => instance,
+        None => panic!("No core instance found!"),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(environment: retro_environment_t) {
+    let instance = instance();
+    let initial = instance.frontend.environment.is_none();
+
+    instance.frontend.environment = environment;
+    instance
+        .core
+        .on_set_environment(initial, &instance.frontend);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_video_refresh(callback: retro_video_refresh_t) {
+    instance().frontend.video_refresh = callback;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_audio_sample(callback: retro_audio_sample_t) {
+    instance().frontend.audio_sample = callback;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_audio_sample_batch(callback: retro_audio_sample_batch_t) {
+    instance().frontend.audio_sample_batch = callback;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_input_poll(callback: retro_input_poll_t) {
+    instance().frontend.input_poll = callback;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_input_state(callback: retro_input_state_t) {
+    instance().frontend.input_state = callback;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_init() {
+    let instance = instance();
+    instance.core.on_init(&instance.frontend);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_deinit() {
+    instance().core.on_deinit();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut retro_system_info) {
+    if info.is_null() {
+        panic!("Expected retro_system_info, got NULL pointer instead!");
+    }
+
+    *info = instance().core.system_info();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut retro_system_av_info) {
+    if info.is_null() {
+        panic!("Expected retro_system_av_info, got NULL pointer instead!");
+    }
+
+    let instance = instance();
+    *info = instance.core.system_av_info(&instance.frontend);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_controller_port_device(port: c_uint, device: c_uint) {
+    instance().core.on_set_controller_port_device(port, device);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_reset() {
+    let instance = instance();
+    instance.core.on_reset(&instance.frontend);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    let instance = instance();
+    instance.core.on_run(&instance.frontend);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize_size() -> size_t {
+    instance().core.serialize_size() as size_t
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: size_t) -> bool {
+    if data.is_null() {
+        return false;
+    }
+
+    let data = core::slice::from_raw_parts_mut(data as *mut u8, size as usize);
+    instance().core.on_serialize(data)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: size_t) -> bool {
+    if data.is_null() {
+        return false;
+    }
+
+    let data = core::slice::from_raw_parts(data as *const u8, size as usize);
+    instance().core.on_unserialize(data)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_cheat_reset() {
+    instance().core.on_cheat_reset();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_cheat_set(index: c_uint, enabled: bool, code: *const c_char) {
+    if code.is_null() {
+        return;
+    }
+
+    instance()
+        .core
+        .on_cheat_set(index, enabled, CStr::from_ptr(code));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const retro_game_info) -> bool {
+    let instance = instance();
+    instance
+        .core
+        .on_load_game(game.as_ref(), &instance.frontend)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game_special(
+    game_type: c_uint,
+    info: *const retro_game_info,
+    num_info: size_t,
+) -> bool {
+    let info = if info.is_null() {
+        &[]
+    } else {
+        core::slice::from_raw_parts(info, num_info as usize)
+    };
+
+    let instance = instance();
+    instance
+        .core
+        .on_load_game_special(game_type, info, &instance.frontend)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unload_game() {
+    instance().core.on_unload_game();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_region() -> c_uint {
+    instance().core.region()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match instance().core.memory(id) {
+        Some(memory) => memory.as_mut_ptr() as *mut c_void,
+        None => core::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_memory_size(id: c_uint) -> size_t {
+    match instance().core.memory(id) {
+        Some(memory) => memory.len() as size_t,
+        None => 0,
+    }
+}
+
+#[test]
+fn frontend_callbacks() {
+    unsafe extern "C" fn audio_sample_batch(_data: *const i16, frames: size_t) -> size_t {
+        frames.min(3)
+    }
+
+    unsafe extern "C" fn input_state(
+        port: c_uint,
+        _device: c_uint,
+        _index: c_uint,
+        id: c_uint,
+    ) -> i16 {
+        (port * 16 + id) as i16
+    }
+
+    let mut frontend = Frontend::default();
+    assert_eq!(frontend.audio_samples(&[0; 8]), 0);
+    assert_eq!(frontend.input_state(1, RETRO_DEVICE_JOYPAD, 0, 2), 0);
+    assert!(!unsafe {
+        frontend.environment(RETRO_ENVIRONMENT_GET_CAN_DUPE, core::ptr::null_mut())
+    });
+
+    frontend.audio_sample_batch = Some(audio_sample_batch);
+    frontend.input_state = Some(input_state);
+    assert_eq!(frontend.audio_samples(&[0; 4]), 2);
+    assert_eq!(frontend.audio_samples(&[0; 8]), 3);
+    assert_eq!(frontend.input_state(1, RETRO_DEVICE_JOYPAD, 0, 2), 18);
+}
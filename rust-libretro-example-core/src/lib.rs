@@ -1,7 +1,14 @@
 use rust_libretro::{
-    contexts::*, core::Core, input_descriptors, proc::*, retro_core, sys::*, types::*,
+    audio_state::AudioState,
+    contexts::*,
+    core::Core,
+    core_info::{CoreFeatures, CoreInfo},
+    input_descriptors,
+    proc::*,
+    retro_core,
+    sys::*,
+    types::*,
 };
-use std::ffi::CString;
 
 const INPUT_DESCRIPTORS: &[retro_input_descriptor] = &input_descriptors!(
     { 0, RETRO_DEVICE_JOYPAD, 0, RETRO_DEVICE_ID_JOYPAD_UP, "Up" },
@@ -33,18 +40,12 @@ const INPUT_DESCRIPTORS: &[retro_input_descriptor] = &input_descriptors!(
         { "true" },
         { "unstable", "Turbo (Unstable)" },
     }
-}, {
-    "foo_option_2",
-    "Simple > Toggle Something",
+}, bool "foo_option_2",
     "Toggle Something",
-    "Setting 'Simple > Toggle Something' to 'true' does something.",
-    "Setting 'Toggle Something' to 'true' does something.",
-    "not_so_advanced_settings",
-    {
-        { "false" },
-        { "true" },
-    }
-})]
+    info = "Enabling 'Toggle Something' does something.",
+    category = "not_so_advanced_settings",
+    default = true
+)]
 struct ExampleCore {
     option_1: bool,
     option_2: bool,
@@ -54,6 +55,17 @@ struct ExampleCore {
     even: bool,
 }
 
+/// Writes silence in the audio callback.
+struct Silence;
+
+impl AudioState for Silence {
+    type Message = ();
+
+    fn on_write_audio(&mut self, ctx: &mut AudioContext) {
+        ctx.queue_audio_sample(0, 0);
+    }
+}
+
 retro_core!(ExampleCore {
     option_1: false,
     option_2: true,
@@ -66,26 +78,31 @@ retro_core!(ExampleCore {
 impl Core for ExampleCore {
     fn get_info(&self) -> SystemInfo {
         SystemInfo {
-            library_name: CString::new("Example Core").unwrap(),
-            library_version: CString::new("0.1.0").unwrap(),
-            valid_extensions: CString::new("").unwrap(),
+            library_name: c_str!("Example Core").into(),
+            library_version: c_str!("0.1.0").into(),
+            valid_extensions: c_str!("").into(),
 
             need_fullpath: false,
             block_extract: false,
         }
     }
 
-    fn on_set_environment(&mut self, initial: bool, ctx: &mut SetEnvironmentContext) {
-        if !initial {
-            return;
-        }
+    fn supports_no_game(&self) -> bool {
+        true
+    }
 
-        ctx.set_support_no_game(true);
+    fn core_info(&self) -> CoreInfo {
+        CoreInfo {
+            display_name: "rust-libretro - Example Core".to_owned(),
+            categories: vec!["Demo".to_owned()],
+            features: CoreFeatures::INPUT_DESCRIPTORS | CoreFeatures::CORE_OPTIONS,
+            ..rust_libretro::core_info!(self.get_info())
+        }
     }
 
     fn on_init(&mut self, ctx: &mut InitContext) {
         let gctx: GenericContext = ctx.into();
-        gctx.set_input_descriptors(INPUT_DESCRIPTORS);
+        let _ = gctx.set_input_descriptors(INPUT_DESCRIPTORS);
     }
 
     fn on_get_av_info(&mut self, _ctx: &mut GetAvInfoContext) -> retro_system_av_info {
@@ -109,27 +126,25 @@ impl Core for ExampleCore {
         _info: Option<retro_game_info>,
         ctx: &mut LoadGameContext,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        ctx.set_pixel_format(PixelFormat::XRGB8888);
-        ctx.set_performance_level(0);
-        ctx.enable_frame_time_callback((1000000.0f64 / 60.0).round() as retro_usec_t);
+        ctx.set_pixel_format(PixelFormat::XRGB8888)?;
+        let _ = ctx.set_performance_level(0);
+        let _ = ctx.enable_frame_time_callback((1000000.0f64 / 60.0).round() as retro_usec_t);
 
         let gctx: GenericContext = ctx.into();
-        gctx.enable_audio_callback();
+        let _ = gctx.enable_audio_state(Silence, 1);
 
         Ok(())
     }
 
     fn on_options_changed(&mut self, ctx: &mut OptionsChangedContext) {
         match ctx.get_variable("foo_option_1") {
-            Some("true") => self.option_1 = true,
-            Some("false") => self.option_1 = false,
+            Ok("true") => self.option_1 = true,
+            Ok("false") => self.option_1 = false,
             _ => (),
         }
 
-        match ctx.get_variable("foo_option_2") {
-            Some("true") => self.option_2 = true,
-            Some("false") => self.option_2 = false,
-            _ => (),
+        if let Some(enabled) = ctx.get_bool_variable("foo_option_2") {
+            self.option_2 = enabled;
         }
     }
 
@@ -139,10 +154,11 @@ impl Core for ExampleCore {
 
         self.timer += delta_us.unwrap_or(16_666);
 
-        let input = unsafe { ctx.get_joypad_bitmask(0, 0) };
+        let input = ctx.get_joypad_bitmask(0, 0);
 
         if input.contains(JoypadState::START) && input.contains(JoypadState::SELECT) {
-            return gctx.shutdown();
+            let _ = gctx.shutdown();
+            return;
         }
 
         if !ctx.can_dupe() || self.timer >= 1_000_000 || input.contains(JoypadState::A) {
@@ -172,8 +188,4 @@ impl Core for ExampleCore {
             ctx.dupe_frame();
         }
     }
-
-    fn on_write_audio(&mut self, ctx: &mut AudioContext) {
-        ctx.queue_audio_sample(0, 0);
-    }
 }
@@ -1,5 +1,8 @@
 use bindgen::callbacks::MacroParsingBehavior;
-use std::{env, path::PathBuf};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug)]
 pub struct ParseCallbacks;
@@ -24,20 +27,166 @@ impl bindgen::callbacks::ParseCallbacks for ParseCallbacks {
     }
 }
 
+/// Looks up the sysroot of the active Emscripten SDK.
+fn emscripten_sysroot() -> Option<PathBuf> {
+    println!("cargo:rerun-if-env-changed=EMSCRIPTEN_SYSROOT");
+    println!("cargo:rerun-if-env-changed=EMSCRIPTEN");
+    println!("cargo:rerun-if-env-changed=EMSDK");
+
+    if let Some(sysroot) = env::var_os("EMSCRIPTEN_SYSROOT") {
+        return Some(sysroot.into());
+    }
+
+    let emscripten = env::var_os("EMSCRIPTEN").map(PathBuf::from).or_else(|| {
+        env::var_os("EMSDK").map(|sdk| PathBuf::from(sdk).join("upstream/emscripten"))
+    })?;
+
+    Some(emscripten.join("cache/sysroot")).filter(|sysroot| sysroot.is_dir())
+}
+
+/// Adds the headers of libretro-common that are not part of `libretro.h`.
+///
+/// libretro-common is not vendored, so `LIBRETRO_COMMON_INCLUDE` has to point at
+/// its `include` directory. Without it the headers are left out with a warning,
+/// so that `--all-features` builds keep working.
+fn libretro_common(builder: bindgen::Builder) -> bindgen::Builder {
+    println!("cargo:rerun-if-env-changed=LIBRETRO_COMMON_INCLUDE");
+
+    let include = match env::var_os("LIBRETRO_COMMON_INCLUDE") {
+        Some(include) => PathBuf::from(include),
+        None => {
+            println!(
+                "cargo:warning=The `libretro-common` feature requires LIBRETRO_COMMON_INCLUDE \
+                 to point at libretro-common/include, leaving out libretro_dspfilter.h"
+            );
+            return builder;
+        }
+    };
+
+    builder
+        .header(include.join("libretro_dspfilter.h").to_string_lossy())
+        .clang_arg(format!("-I{}", include.display()))
+        .allowlist_type("dspfilter_.*")
+        .allowlist_var("DSPFILTER_.*")
+}
+
+/// Maps Rust target triples to the ones clang understands.
+fn clang_target(target: &str) -> String {
+    // e.g. `riscv64gc-unknown-linux-gnu`
+    if let Some(rest) = target.strip_prefix("riscv64gc-") {
+        return format!("riscv64-{}", rest);
+    }
+    if let Some(rest) = target.strip_prefix("riscv32imac-") {
+        return format!("riscv32-{}", rest);
+    }
+
+    target.to_owned()
+}
+
+/// bindgen reads `BINDGEN_EXTRA_CLANG_ARGS`, `BINDGEN_EXTRA_CLANG_ARGS_<TARGET>` and
+/// `BINDGEN_EXTRA_CLANG_ARGS_<TARGET_WITH_UNDERSCORES>` itself, but doesn't tell cargo about them.
+fn rerun_if_clang_args_changed(target: &str) {
+    println!("cargo:rerun-if-env-changed=BINDGEN_EXTRA_CLANG_ARGS");
+    println!(
+        "cargo:rerun-if-env-changed=BINDGEN_EXTRA_CLANG_ARGS_{}",
+        target
+    );
+    println!(
+        "cargo:rerun-if-env-changed=BINDGEN_EXTRA_CLANG_ARGS_{}",
+        target.replace('-', "_")
+    );
+}
+
+/// Whether to generate the layout tests, which only pass for the target the bindings are for.
+///
+/// Defaults to native builds, `RUST_LIBRETRO_SYS_LAYOUT_TESTS=0` or `1` overrides it.
+fn layout_tests(target: &str, host: &str) -> bool {
+    println!("cargo:rerun-if-env-changed=RUST_LIBRETRO_SYS_LAYOUT_TESTS");
+
+    match env::var("RUST_LIBRETRO_SYS_LAYOUT_TESTS").as_deref() {
+        Ok("0") | Ok("false") => false,
+        Ok("1") | Ok("true") => true,
+        _ => target == host,
+    }
+}
+
+/// Copies pre-generated bindings for the `bindgen-offline` feature.
+///
+/// They are taken from `RUST_LIBRETRO_SYS_BINDINGS` or `bindings/<TARGET>.rs`.
+/// Returns `false` if there are none, so that bindgen runs after all,
+/// e.g. for `--all-features` builds.
+fn offline_bindings(target: &str, out_path: &Path) -> bool {
+    println!("cargo:rerun-if-env-changed=RUST_LIBRETRO_SYS_BINDINGS");
+
+    let bindings = env::var_os("RUST_LIBRETRO_SYS_BINDINGS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("bindings").join(format!("{}.rs", target)));
+    println!("cargo:rerun-if-changed={}", bindings.display());
+
+    match fs::copy(&bindings, out_path.join("bindings.rs")) {
+        Ok(_) => true,
+        Err(err) => {
+            println!(
+                "cargo:warning=The `bindgen-offline` feature found no pre-generated bindings at {} \
+                 (set RUST_LIBRETRO_SYS_BINDINGS to change the path), running bindgen instead: {}",
+                bindings.display(),
+                err
+            );
+            false
+        }
+    }
+}
+
 fn main() {
     // Tell cargo to invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed=wrapper.h");
 
+    let target = env::var("TARGET").unwrap();
+    let host = env::var("HOST").unwrap();
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    if env::var_os("CARGO_FEATURE_BINDGEN_OFFLINE").is_some()
+        && offline_bindings(&target, &out_path)
+    {
+        return;
+    }
+
     // The bindgen::Builder is the main entry point
     // to bindgen, and lets you build up options for
     // the resulting bindings.
-    let bindings = bindgen::Builder::default()
+    let mut builder = bindgen::Builder::default();
+
+    // Emscripten builds need the headers of the Emscripten sysroot,
+    // otherwise libclang would parse the host's headers instead.
+    // The type sizes and `c_char`'s signedness follow from the target.
+    if target.contains("emscripten") {
+        builder = builder.clang_arg(format!("--target={}", target));
+
+        if let Some(sysroot) = emscripten_sysroot() {
+            builder = builder.clang_arg(format!("--sysroot={}", sysroot.display()));
+        }
+    } else if clang_target(&target) != target {
+        // bindgen passes any other target on as it is
+        builder = builder.clang_arg(format!("--target={}", clang_target(&target)));
+    }
+
+    rerun_if_clang_args_changed(&target);
+    builder = builder.layout_tests(layout_tests(&target, &host));
+
+    if env::var_os("CARGO_FEATURE_LIBRETRO_COMMON").is_some() {
+        builder = libretro_common(builder);
+    }
+
+    let bindings = builder
         .header("wrapper.h")
         .allowlist_type("(retro|RETRO)_.*")
         .allowlist_function("(retro|RETRO)_.*")
         .allowlist_var("(retro|RETRO)_.*")
         .prepend_enum_name(false)
         .impl_debug(true)
+        // Only refer to `core`, so the bindings also work without `std`
+        .use_core()
+        .ctypes_prefix("::core::ffi")
         .clang_arg("-fparse-all-comments")
         .enable_function_attribute_detection()
         .default_enum_style(bindgen::EnumVariation::Rust {
@@ -52,7 +201,6 @@ fn main() {
         .expect("Unable to generate bindings");
 
     // Write the bindings to the $OUT_DIR/bindings.rs file.
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     bindings
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
@@ -0,0 +1,169 @@
+//! Typed versions of the `RETRO_DEVICE_*` and `RETRO_DEVICE_ID_*` constants.
+//!
+//! Every enum converts into the raw constant with [`From`] and back with [`TryFrom`],
+//! which returns the unknown raw value as its error.
+use super::*;
+
+macro_rules! input_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident = $value:ident,
+            )+
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[repr(u32)]
+        pub enum $name {
+            $(
+                $(#[$variant_meta])*
+                #[doc = concat!("[`", stringify!($value), "`]")]
+                $variant = $value,
+            )+
+        }
+
+        impl $name {
+            /// All variants in declaration order.
+            pub const ALL: &'static [Self] = &[$(Self::$variant),+];
+        }
+
+        impl From<$name> for u32 {
+            fn from(value: $name) -> Self {
+                value as u32
+            }
+        }
+
+        impl TryFrom<u32> for $name {
+            type Error = u32;
+
+            fn try_from(value: u32) -> Result<Self, Self::Error> {
+                match value {
+                    $($value => Ok(Self::$variant),)+
+                    _ => Err(value),
+                }
+            }
+        }
+    };
+}
+
+input_enum! {
+    /// The base device types.
+    ///
+    /// Subclassed devices (see [`RETRO_DEVICE_SUBCLASS`](crate::RETRO_DEVICE_SUBCLASS))
+    /// have to be masked with [`RETRO_DEVICE_MASK`] before they can be converted,
+    /// see [`Device::from_subclass`].
+    pub enum Device {
+        None = RETRO_DEVICE_NONE,
+        Joypad = RETRO_DEVICE_JOYPAD,
+        Mouse = RETRO_DEVICE_MOUSE,
+        Keyboard = RETRO_DEVICE_KEYBOARD,
+        Lightgun = RETRO_DEVICE_LIGHTGUN,
+        Analog = RETRO_DEVICE_ANALOG,
+        Pointer = RETRO_DEVICE_POINTER,
+    }
+}
+
+impl Device {
+    /// Returns the base device of a possibly subclassed device.
+    pub fn from_subclass(device: u32) -> Option<Self> {
+        Self::try_from(device & RETRO_DEVICE_MASK).ok()
+    }
+}
+
+input_enum! {
+    /// The buttons of the [`Device::Joypad`], also known as RetroPad.
+    pub enum JoypadButton {
+        B = RETRO_DEVICE_ID_JOYPAD_B,
+        Y = RETRO_DEVICE_ID_JOYPAD_Y,
+        Select = RETRO_DEVICE_ID_JOYPAD_SELECT,
+        Start = RETRO_DEVICE_ID_JOYPAD_START,
+        Up = RETRO_DEVICE_ID_JOYPAD_UP,
+        Down = RETRO_DEVICE_ID_JOYPAD_DOWN,
+        Left = RETRO_DEVICE_ID_JOYPAD_LEFT,
+        Right = RETRO_DEVICE_ID_JOYPAD_RIGHT,
+        A = RETRO_DEVICE_ID_JOYPAD_A,
+        X = RETRO_DEVICE_ID_JOYPAD_X,
+        L = RETRO_DEVICE_ID_JOYPAD_L,
+        R = RETRO_DEVICE_ID_JOYPAD_R,
+        L2 = RETRO_DEVICE_ID_JOYPAD_L2,
+        R2 = RETRO_DEVICE_ID_JOYPAD_R2,
+        L3 = RETRO_DEVICE_ID_JOYPAD_L3,
+        R3 = RETRO_DEVICE_ID_JOYPAD_R3,
+    }
+}
+
+input_enum! {
+    /// The relative axes of the [`Device::Mouse`].
+    pub enum MouseAxis {
+        X = RETRO_DEVICE_ID_MOUSE_X,
+        Y = RETRO_DEVICE_ID_MOUSE_Y,
+    }
+}
+
+input_enum! {
+    /// The buttons and wheels of the [`Device::Mouse`].
+    pub enum MouseButton {
+        Left = RETRO_DEVICE_ID_MOUSE_LEFT,
+        Right = RETRO_DEVICE_ID_MOUSE_RIGHT,
+        WheelUp = RETRO_DEVICE_ID_MOUSE_WHEELUP,
+        WheelDown = RETRO_DEVICE_ID_MOUSE_WHEELDOWN,
+        Middle = RETRO_DEVICE_ID_MOUSE_MIDDLE,
+        HorizontalWheelUp = RETRO_DEVICE_ID_MOUSE_HORIZ_WHEELUP,
+        HorizontalWheelDown = RETRO_DEVICE_ID_MOUSE_HORIZ_WHEELDOWN,
+        Button4 = RETRO_DEVICE_ID_MOUSE_BUTTON_4,
+        Button5 = RETRO_DEVICE_ID_MOUSE_BUTTON_5,
+    }
+}
+
+input_enum! {
+    /// The inputs of the [`Device::Lightgun`].
+    ///
+    /// The deprecated relative inputs are not included.
+    pub enum LightgunInput {
+        ScreenX = RETRO_DEVICE_ID_LIGHTGUN_SCREEN_X,
+        ScreenY = RETRO_DEVICE_ID_LIGHTGUN_SCREEN_Y,
+        IsOffscreen = RETRO_DEVICE_ID_LIGHTGUN_IS_OFFSCREEN,
+        Trigger = RETRO_DEVICE_ID_LIGHTGUN_TRIGGER,
+        Reload = RETRO_DEVICE_ID_LIGHTGUN_RELOAD,
+        AuxA = RETRO_DEVICE_ID_LIGHTGUN_AUX_A,
+        AuxB = RETRO_DEVICE_ID_LIGHTGUN_AUX_B,
+        Start = RETRO_DEVICE_ID_LIGHTGUN_START,
+        Select = RETRO_DEVICE_ID_LIGHTGUN_SELECT,
+        AuxC = RETRO_DEVICE_ID_LIGHTGUN_AUX_C,
+        DpadUp = RETRO_DEVICE_ID_LIGHTGUN_DPAD_UP,
+        DpadDown = RETRO_DEVICE_ID_LIGHTGUN_DPAD_DOWN,
+        DpadLeft = RETRO_DEVICE_ID_LIGHTGUN_DPAD_LEFT,
+        DpadRight = RETRO_DEVICE_ID_LIGHTGUN_DPAD_RIGHT,
+    }
+}
+
+input_enum! {
+    /// The `index` argument for [`Device::Analog`] inputs.
+    pub enum AnalogIndex {
+        Left = RETRO_DEVICE_INDEX_ANALOG_LEFT,
+        Right = RETRO_DEVICE_INDEX_ANALOG_RIGHT,
+        /// Analog values of the [`JoypadButton`]s, with the button as the `id`.
+        Button = RETRO_DEVICE_INDEX_ANALOG_BUTTON,
+    }
+}
+
+input_enum! {
+    /// The axes of an [`AnalogIndex::Left`] or [`AnalogIndex::Right`] stick.
+    pub enum AnalogAxis {
+        X = RETRO_DEVICE_ID_ANALOG_X,
+        Y = RETRO_DEVICE_ID_ANALOG_Y,
+    }
+}
+
+input_enum! {
+    /// The inputs of the [`Device::Pointer`].
+    pub enum PointerInput {
+        X = RETRO_DEVICE_ID_POINTER_X,
+        Y = RETRO_DEVICE_ID_POINTER_Y,
+        Pressed = RETRO_DEVICE_ID_POINTER_PRESSED,
+        Count = RETRO_DEVICE_ID_POINTER_COUNT,
+    }
+}
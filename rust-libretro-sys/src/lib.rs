@@ -1,3 +1,4 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
 #![doc(
@@ -7,6 +8,9 @@
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+pub mod input;
+mod repr;
+
 /// #define RETRO_DEVICE_SUBCLASS(base, id) (((id + 1) << RETRO_DEVICE_TYPE_SHIFT) | base)
 #[macro_export]
 macro_rules! RETRO_DEVICE_SUBCLASS {
@@ -26,9 +30,15 @@ pub const RETRO_DEVICE_ID_LIGHTGUN_TURBO: u32 = 4;
 #[deprecated = "Use `RETRO_DEVICE_ID_LIGHTGUN_START` instead."]
 pub const RETRO_DEVICE_ID_LIGHTGUN_PAUSE: u32 = 5;
 
+/// `bool *` --
+/// Result is set to true if the frontend has already verified that JIT compilation
+/// can be used, mainly for iOS and tvOS. On other platforms the result is true.
+///
+/// Newer than the bundled libretro.h, which is why bindgen does not export it.
+pub const RETRO_ENVIRONMENT_GET_JIT_CAPABLE: u32 = 74;
+
 /// Pass this to [`retro_video_refresh_t`] if rendering to hardware.
 /// Passing NULL to [`retro_video_refresh_t`] is still a frame dupe as normal.
 ///
 /// For some reason bindgen did not export this #define
-pub const RETRO_HW_FRAME_BUFFER_VALID: *mut std::os::raw::c_void =
-    -1_i32 as *mut std::os::raw::c_void;
+pub const RETRO_HW_FRAME_BUFFER_VALID: *mut core::ffi::c_void = -1_i32 as *mut core::ffi::c_void;
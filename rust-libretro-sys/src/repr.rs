@@ -0,0 +1,130 @@
+//! Conversions from the raw integers the frontend passes to the bindgen enums.
+//!
+//! bindgen generates Rust enums, which must never hold a value without a variant,
+//! so integers have to be checked before they can become one.
+use super::*;
+
+macro_rules! impl_from_repr {
+    ($($name:ident { $($variant:ident),+ $(,)? })+) => {$(
+        impl $name {
+            /// All variants, without the `*_LAST` and `*_DUMMY` markers.
+            pub const ALL: &'static [$name] = &[$($name::$variant),+];
+
+            /// Returns the variant with the value `value`.
+            pub const fn from_repr(value: u32) -> Option<Self> {
+                $(
+                    if value == $name::$variant as u32 {
+                        return Some($name::$variant);
+                    }
+                )+
+
+                None
+            }
+        }
+
+        impl TryFrom<u32> for $name {
+            /// The value without a variant.
+            type Error = u32;
+
+            fn try_from(value: u32) -> Result<Self, Self::Error> {
+                Self::from_repr(value).ok_or(value)
+            }
+        }
+    )+};
+}
+
+impl_from_repr! {
+    retro_language {
+        RETRO_LANGUAGE_ENGLISH,
+        RETRO_LANGUAGE_JAPANESE,
+        RETRO_LANGUAGE_FRENCH,
+        RETRO_LANGUAGE_SPANISH,
+        RETRO_LANGUAGE_GERMAN,
+        RETRO_LANGUAGE_ITALIAN,
+        RETRO_LANGUAGE_DUTCH,
+        RETRO_LANGUAGE_PORTUGUESE_BRAZIL,
+        RETRO_LANGUAGE_PORTUGUESE_PORTUGAL,
+        RETRO_LANGUAGE_RUSSIAN,
+        RETRO_LANGUAGE_KOREAN,
+        RETRO_LANGUAGE_CHINESE_TRADITIONAL,
+        RETRO_LANGUAGE_CHINESE_SIMPLIFIED,
+        RETRO_LANGUAGE_ESPERANTO,
+        RETRO_LANGUAGE_POLISH,
+        RETRO_LANGUAGE_VIETNAMESE,
+        RETRO_LANGUAGE_ARABIC,
+        RETRO_LANGUAGE_GREEK,
+        RETRO_LANGUAGE_TURKISH,
+        RETRO_LANGUAGE_SLOVAK,
+        RETRO_LANGUAGE_PERSIAN,
+        RETRO_LANGUAGE_HEBREW,
+        RETRO_LANGUAGE_ASTURIAN,
+        RETRO_LANGUAGE_FINNISH,
+        RETRO_LANGUAGE_INDONESIAN,
+        RETRO_LANGUAGE_SWEDISH,
+        RETRO_LANGUAGE_UKRAINIAN,
+    }
+
+    retro_hw_context_type {
+        RETRO_HW_CONTEXT_NONE,
+        RETRO_HW_CONTEXT_OPENGL,
+        RETRO_HW_CONTEXT_OPENGLES2,
+        RETRO_HW_CONTEXT_OPENGL_CORE,
+        RETRO_HW_CONTEXT_OPENGLES3,
+        RETRO_HW_CONTEXT_OPENGLES_VERSION,
+        RETRO_HW_CONTEXT_VULKAN,
+        RETRO_HW_CONTEXT_DIRECT3D,
+    }
+
+    retro_pixel_format {
+        RETRO_PIXEL_FORMAT_0RGB1555,
+        RETRO_PIXEL_FORMAT_XRGB8888,
+        RETRO_PIXEL_FORMAT_RGB565,
+    }
+}
+
+/// Every variant round-trips and the values are contiguous,
+/// so a variant missing from the lists above fails the test.
+#[test]
+fn from_repr_round_trip() {
+    fn check<T: Copy + PartialEq + core::fmt::Debug>(
+        all: &[T],
+        from_repr: fn(u32) -> Option<T>,
+        to_repr: fn(T) -> u32,
+        end: u32,
+    ) {
+        assert_eq!(all.len() as u32, end);
+
+        for (value, variant) in all.iter().enumerate() {
+            assert_eq!(to_repr(*variant), value as u32);
+            assert_eq!(from_repr(value as u32), Some(*variant));
+        }
+
+        assert_eq!(from_repr(end), None);
+        assert_eq!(from_repr(i32::MAX as u32), None);
+    }
+
+    check(
+        retro_language::ALL,
+        retro_language::from_repr,
+        |language| language as u32,
+        retro_language::RETRO_LANGUAGE_LAST as u32,
+    );
+    check(
+        retro_hw_context_type::ALL,
+        retro_hw_context_type::from_repr,
+        |context| context as u32,
+        retro_hw_context_type::RETRO_HW_CONTEXT_DIRECT3D as u32 + 1,
+    );
+    check(
+        retro_pixel_format::ALL,
+        retro_pixel_format::from_repr,
+        |format| format as u32,
+        retro_pixel_format::RETRO_PIXEL_FORMAT_RGB565 as u32 + 1,
+    );
+
+    assert_eq!(
+        retro_language::try_from(3),
+        Ok(retro_language::RETRO_LANGUAGE_SPANISH)
+    );
+    assert_eq!(retro_pixel_format::try_from(7), Err(7));
+}
@@ -0,0 +1,148 @@
+//! Measures the per-frame overhead `retro_run` adds to a core, run with `cargo bench --bench run`.
+//!
+//! The core does nothing but convert its context a few times, like cores calling helpers
+//! that take a [`GenericContext`] do, so the measured time is spent in the wrapper.
+//!
+//! With `cargo bench --bench run -- --max-ns <ns>` the benchmark fails if a frame takes
+//! longer, which CI uses to catch changes that slow down the hot path.
+use rust_libretro::{
+    contexts::*,
+    core::{Core, CoreOptions},
+    proc::c_str,
+    retro_core,
+    sys::*,
+    types::*,
+};
+use std::{
+    os::raw::{c_uint, c_void},
+    time::{Duration, Instant},
+};
+
+const FRAMES: u32 = 1_000_000;
+
+/// How often the core converts its [`RunContext`] per frame.
+const CONVERSIONS: usize = 4;
+
+struct BenchCore {
+    frame: Vec<u8>,
+}
+
+retro_core!(BenchCore {
+    frame: vec![0; 320 * 240 * 2],
+});
+
+impl CoreOptions for BenchCore {}
+
+impl Core for BenchCore {
+    fn get_info(&self) -> SystemInfo {
+        SystemInfo {
+            library_name: c_str!("Bench Core").into(),
+            library_version: c_str!("0.1.0").into(),
+            valid_extensions: c_str!("").into(),
+
+            need_fullpath: false,
+            block_extract: false,
+        }
+    }
+
+    fn supports_no_game(&self) -> bool {
+        true
+    }
+
+    fn on_get_av_info(&mut self, _ctx: &mut GetAvInfoContext) -> retro_system_av_info {
+        retro_system_av_info {
+            geometry: retro_game_geometry {
+                base_width: 320,
+                base_height: 240,
+                max_width: 320,
+                max_height: 240,
+                aspect_ratio: 0.0,
+            },
+            timing: retro_system_timing {
+                fps: 60.0,
+                sample_rate: 48000.0,
+            },
+        }
+    }
+
+    fn on_run(&mut self, ctx: &mut RunContext, _delta_us: Option<i64>) {
+        for _ in 0..CONVERSIONS {
+            let gctx: GenericContext = ctx.into();
+            std::hint::black_box(&gctx);
+        }
+
+        ctx.draw_frame(&self.frame, 320, 240, 320 * 2);
+    }
+}
+
+unsafe extern "C" fn environment(_cmd: c_uint, _data: *mut c_void) -> bool {
+    false
+}
+
+unsafe extern "C" fn video_refresh(
+    _data: *const c_void,
+    _width: c_uint,
+    _height: c_uint,
+    _pitch: size_t,
+) {
+}
+
+unsafe extern "C" fn input_poll() {}
+
+unsafe extern "C" fn input_state(
+    _port: c_uint,
+    _device: c_uint,
+    _index: c_uint,
+    _id: c_uint,
+) -> i16 {
+    0
+}
+
+/// The limit passed with `--max-ns`, cargo passes `--bench` as well.
+fn max_ns() -> Option<f64> {
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == "--max-ns" {
+            return args.next().and_then(|max| max.parse().ok());
+        }
+    }
+
+    None
+}
+
+fn main() {
+    unsafe {
+        rust_libretro::retro_set_environment(Some(environment));
+        rust_libretro::retro_set_video_refresh(Some(video_refresh));
+        rust_libretro::retro_set_input_poll(Some(input_poll));
+        rust_libretro::retro_set_input_state(Some(input_state));
+
+        rust_libretro::retro_init();
+        assert!(rust_libretro::retro_load_game(std::ptr::null()));
+
+        // Warm up
+        for _ in 0..FRAMES / 10 {
+            rust_libretro::retro_run();
+        }
+
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            rust_libretro::retro_run();
+        }
+        let elapsed: Duration = start.elapsed() / FRAMES;
+        let ns = elapsed.as_secs_f64() * 1e9;
+
+        println!("retro_run with {CONVERSIONS} context conversions: {ns:>8.1} ns/frame");
+
+        rust_libretro::retro_unload_game();
+        rust_libretro::retro_deinit();
+
+        if let Some(max) = max_ns() {
+            if ns > max {
+                eprintln!("retro_run takes longer than {max} ns/frame");
+                std::process::exit(1);
+            }
+        }
+    }
+}
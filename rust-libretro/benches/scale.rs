@@ -0,0 +1,51 @@
+//! Measures the software scalers, run with `cargo bench --bench scale`.
+use rust_libretro::util::scale::{ScaleFilter, Scaler};
+use std::time::{Duration, Instant};
+
+const SRC: (u32, u32) = (640, 480);
+const DST: (u32, u32) = (320, 240);
+const ITERATIONS: u32 = 200;
+
+fn bench(name: &str, scaler: &mut Scaler, src: &[u32], from: (u32, u32), to: (u32, u32)) {
+    // Warm up, which also allocates the output buffer
+    scaler.scale(src, from.0, from.1, to.0, to.1);
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        std::hint::black_box(scaler.scale(src, from.0, from.1, to.0, to.1));
+    }
+    let elapsed: Duration = start.elapsed() / ITERATIONS;
+
+    println!(
+        "{name:<10} {}x{} -> {}x{}: {:>8.3} ms/frame",
+        from.0,
+        from.1,
+        to.0,
+        to.1,
+        elapsed.as_secs_f64() * 1000.0
+    );
+}
+
+fn main() {
+    let src = (0..SRC.0 * SRC.1)
+        .map(|i| i.wrapping_mul(0x9E37_79B9) & 0x00FF_FFFF)
+        .collect::<Vec<_>>();
+
+    for filter in [
+        ScaleFilter::Nearest,
+        ScaleFilter::Bilinear,
+        ScaleFilter::Integer,
+    ] {
+        let mut scaler = Scaler::new(filter);
+        let name = format!("{filter:?}");
+
+        bench(&name, &mut scaler, &src, SRC, DST);
+        bench(
+            &name,
+            &mut scaler,
+            &src[..(DST.0 * DST.1) as usize],
+            DST,
+            SRC,
+        );
+    }
+}
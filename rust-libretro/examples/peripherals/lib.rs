@@ -0,0 +1,289 @@
+//! Exercises the LED, rumble and sensor interfaces.
+//!
+//! - `A`, `B`, `X` and `Y` toggle the LEDs 0 to 3
+//! - `L` and `R` start a decaying pulse on the strong and weak rumble motor
+//! - The bars at the bottom show the accelerometer readings of port 0
+//!
+//! Unsupported interfaces are drawn in gray.
+use rust_libretro::{
+    contexts::*,
+    core::{Core, CoreOptions},
+    proc::c_str,
+    retro_core,
+    sys::*,
+    types::*,
+    util::convert::as_bytes,
+};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+
+const LED_COUNT: usize = 4;
+const LED_BUTTONS: [JoypadState; LED_COUNT] = [
+    JoypadState::A,
+    JoypadState::B,
+    JoypadState::X,
+    JoypadState::Y,
+];
+
+/// The number of frames a rumble pulse lasts
+const PULSE_FRAMES: u32 = 60;
+
+/// Accelerometer values get drawn in a range of ±`SENSOR_RANGE`
+const SENSOR_RANGE: f32 = 2.0;
+
+const BACKGROUND: u32 = 0x00202020;
+const UNSUPPORTED: u32 = 0x00505050;
+const LED_OFF: u32 = 0x00003000;
+const LED_ON: u32 = 0x0000FF00;
+const STRONG_MOTOR: u32 = 0x00FF4040;
+const WEAK_MOTOR: u32 = 0x004080FF;
+const SENSOR: u32 = 0x00FFC000;
+
+/// A linearly decaying rumble effect.
+#[derive(Default)]
+struct RumblePulse {
+    frames_left: u32,
+    last_strength: Option<u16>,
+}
+
+impl RumblePulse {
+    fn start(&mut self) {
+        self.frames_left = PULSE_FRAMES;
+    }
+
+    /// Advances the pulse by one frame and returns the new strength.
+    fn tick(&mut self) -> u16 {
+        let strength = (u16::MAX as u32 * self.frames_left / PULSE_FRAMES) as u16;
+        self.frames_left = self.frames_left.saturating_sub(1);
+        strength
+    }
+}
+
+struct PeripheralsCore {
+    pixels: Vec<u32>,
+
+    has_leds: bool,
+    has_rumble: bool,
+    has_sensors: bool,
+
+    leds: [bool; LED_COUNT],
+    strong: RumblePulse,
+    weak: RumblePulse,
+}
+
+retro_core!(PeripheralsCore {
+    pixels: vec![0; (WIDTH * HEIGHT) as usize],
+
+    has_leds: false,
+    has_rumble: false,
+    has_sensors: false,
+
+    leds: [false; LED_COUNT],
+    strong: RumblePulse::default(),
+    weak: RumblePulse::default(),
+});
+
+impl CoreOptions for PeripheralsCore {}
+impl Core for PeripheralsCore {
+    fn get_info(&self) -> SystemInfo {
+        SystemInfo {
+            library_name: c_str!("PeripheralsTestCore").into(),
+            library_version: c_str!("0.1.0").into(),
+            valid_extensions: c_str!("").into(),
+
+            need_fullpath: false,
+            block_extract: false,
+        }
+    }
+
+    fn supports_no_game(&self) -> bool {
+        true
+    }
+
+    fn on_get_av_info(&mut self, _ctx: &mut GetAvInfoContext) -> retro_system_av_info {
+        retro_system_av_info {
+            geometry: retro_game_geometry {
+                base_width: WIDTH,
+                base_height: HEIGHT,
+                max_width: WIDTH,
+                max_height: HEIGHT,
+                aspect_ratio: 0.0,
+            },
+            timing: retro_system_timing {
+                fps: 60.0,
+                sample_rate: 0.0,
+            },
+        }
+    }
+
+    fn on_load_game(
+        &mut self,
+        _info: Option<retro_game_info>,
+        ctx: &mut LoadGameContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        ctx.set_pixel_format(PixelFormat::XRGB8888)?;
+
+        self.has_leds = unsafe { ctx.enable_led_interface() }.is_ok();
+        self.has_rumble = ctx.enable_rumble_interface().is_ok();
+
+        if unsafe { ctx.enable_sensor_interface() }.is_ok() {
+            let gctx: GenericContext = ctx.into();
+
+            self.has_sensors = unsafe {
+                gctx.set_sensor_state(
+                    0,
+                    retro_sensor_action::RETRO_SENSOR_ACCELEROMETER_ENABLE,
+                    60,
+                )
+            };
+        }
+
+        log::info!(
+            "LEDs: {}, rumble: {}, sensors: {}",
+            self.has_leds,
+            self.has_rumble,
+            self.has_sensors
+        );
+
+        Ok(())
+    }
+
+    fn on_unload_game(&mut self, ctx: &mut UnloadGameContext) {
+        if self.has_rumble {
+            ctx.set_rumble_state(0, retro_rumble_effect::RETRO_RUMBLE_STRONG, 0);
+            ctx.set_rumble_state(0, retro_rumble_effect::RETRO_RUMBLE_WEAK, 0);
+        }
+
+        if self.has_sensors {
+            unsafe {
+                ctx.set_sensor_state(
+                    0,
+                    retro_sensor_action::RETRO_SENSOR_ACCELEROMETER_DISABLE,
+                    0,
+                )
+            };
+        }
+    }
+
+    fn on_run(&mut self, ctx: &mut RunContext, _delta_us: Option<i64>) {
+        let input = ctx.get_joypad_transitions(0);
+        let gctx: GenericContext = ctx.into();
+
+        for (index, button) in LED_BUTTONS.iter().enumerate() {
+            if input.just_pressed(*button) {
+                self.leds[index] = !self.leds[index];
+
+                if self.has_leds {
+                    unsafe { gctx.set_led_state(index as i32, self.leds[index] as i32) };
+                }
+            }
+        }
+
+        if input.just_pressed(JoypadState::L) {
+            self.strong.start();
+        }
+        if input.just_pressed(JoypadState::R) {
+            self.weak.start();
+        }
+
+        let strong = Self::update_rumble(
+            &gctx,
+            &mut self.strong,
+            retro_rumble_effect::RETRO_RUMBLE_STRONG,
+            self.has_rumble,
+        );
+        let weak = Self::update_rumble(
+            &gctx,
+            &mut self.weak,
+            retro_rumble_effect::RETRO_RUMBLE_WEAK,
+            self.has_rumble,
+        );
+
+        let acceleration = [
+            RETRO_SENSOR_ACCELEROMETER_X,
+            RETRO_SENSOR_ACCELEROMETER_Y,
+            RETRO_SENSOR_ACCELEROMETER_Z,
+        ]
+        .map(|id| unsafe { gctx.get_sensor_input(0, id) });
+
+        self.draw(strong, weak, acceleration);
+
+        ctx.draw_frame(as_bytes(&self.pixels), WIDTH, HEIGHT, WIDTH as u64 * 4);
+    }
+}
+
+impl PeripheralsCore {
+    /// Only notifies the frontend when the strength changes.
+    fn update_rumble(
+        ctx: &GenericContext,
+        pulse: &mut RumblePulse,
+        effect: retro_rumble_effect,
+        supported: bool,
+    ) -> u16 {
+        let strength = pulse.tick();
+
+        if supported && pulse.last_strength != Some(strength) {
+            ctx.set_rumble_state(0, effect, strength);
+            pulse.last_strength = Some(strength);
+        }
+
+        strength
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: u32) {
+        for row in y..(y + height).min(HEIGHT) {
+            let start = (row * WIDTH + x) as usize;
+            let end = (row * WIDTH + (x + width).min(WIDTH)) as usize;
+
+            self.pixels[start..end].fill(color);
+        }
+    }
+
+    fn draw(&mut self, strong: u16, weak: u16, acceleration: [f32; 3]) {
+        self.pixels.fill(BACKGROUND);
+
+        for index in 0..LED_COUNT {
+            let color = match (self.has_leds, self.leds[index]) {
+                (false, _) => UNSUPPORTED,
+                (true, false) => LED_OFF,
+                (true, true) => LED_ON,
+            };
+
+            self.fill_rect(40 + index as u32 * 64, 20, 48, 48, color);
+        }
+
+        for (row, (strength, color)) in [(strong, STRONG_MOTOR), (weak, WEAK_MOTOR)]
+            .into_iter()
+            .enumerate()
+        {
+            let y = 100 + row as u32 * 24;
+            let color = if self.has_rumble { color } else { UNSUPPORTED };
+            let width = 240 * strength as u32 / u16::MAX as u32;
+
+            self.fill_rect(40, y, 240, 16, BACKGROUND + 0x00101010);
+            self.fill_rect(40, y, width, 16, color);
+        }
+
+        for (row, value) in acceleration.into_iter().enumerate() {
+            let y = 170 + row as u32 * 20;
+            let center = WIDTH / 2;
+
+            self.fill_rect(40, y, 240, 12, BACKGROUND + 0x00101010);
+
+            if !self.has_sensors {
+                self.fill_rect(40, y, 240, 12, UNSUPPORTED);
+                continue;
+            }
+
+            let offset = ((value / SENSOR_RANGE).clamp(-1.0, 1.0) * 120.0) as i32;
+            let (x, width) = if offset < 0 {
+                ((center as i32 + offset) as u32, offset.unsigned_abs())
+            } else {
+                (center, offset as u32)
+            };
+
+            self.fill_rect(x, y, width.max(1), 12, SENSOR);
+        }
+    }
+}
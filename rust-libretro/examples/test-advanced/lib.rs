@@ -23,9 +23,18 @@ use dasp_sample::Sample;
 use dasp_signal::{self as signal, ConstHz, IntoInterleavedSamples, ScaleAmp, Signal, Sine};
 use num::Integer;
 use num_traits::{cast::AsPrimitive, int::PrimInt};
-use rust_libretro::{contexts::*, core::Core, proc::CoreOptions, retro_core, sys::*, types::*};
+use rust_libretro::{
+    audio_state::{AudioSender, AudioState},
+    contexts::*,
+    core::Core,
+    proc::{c_str, CoreOptions},
+    retro_core,
+    sys::*,
+    types::*,
+    util::hash::crc32,
+};
 use serde::{Deserialize, Serialize, Serializer};
-use std::{char, ffi::CString, fmt::Display};
+use std::{char, fmt::Display};
 
 const FRAMERATE: f64 = 60.0;
 const SAMPLE_RATE: f64 = 30720.0;
@@ -142,26 +151,62 @@ struct AdvancedTestCore {
 
     has_perf: bool,
 
+    /// Receives [`AdvancedTestCore::sound_enable`] whenever it changes.
+    audio: Option<AudioSender<bool>>,
+    audio_sound_enable: bool,
+}
+
+/// Plays a sine wave in the audio callback while the current test asks for sound.
+struct SineWave {
+    sound_enable: bool,
     sine: IntoInterleavedSamples<ScaleAmp<Sine<ConstHz>>>,
 }
 
-impl Default for AdvancedTestCore {
+impl Default for SineWave {
     fn default() -> Self {
         Self {
-            pixel_format: PixelFormat::XRGB8888,
-            active_pixel_format: PixelFormat::XRGB8888,
-
+            sound_enable: false,
             sine: signal::rate(SAMPLE_RATE)
                 .const_hz(FREQUENCY)
                 .sine()
                 .scale_amp(AMPLITUDE)
                 .into_interleaved_samples(),
+        }
+    }
+}
+
+impl AudioState for SineWave {
+    type Message = bool;
+
+    fn on_message(&mut self, sound_enable: bool) {
+        self.sound_enable = sound_enable;
+    }
+
+    fn on_write_audio(&mut self, ctx: &mut AudioContext) {
+        ctx.generate_audio(|samples| {
+            if self.sound_enable {
+                for sample in samples {
+                    *sample = i16::from_sample(self.sine.next_sample());
+                }
+            }
+        });
+    }
+}
+
+impl Default for AdvancedTestCore {
+    fn default() -> Self {
+        Self {
+            pixel_format: PixelFormat::XRGB8888,
+            active_pixel_format: PixelFormat::XRGB8888,
 
             inp_state: [JoypadState::empty(); 2],
             sound_enable: false,
 
             has_perf: false,
 
+            audio: None,
+            audio_sound_enable: false,
+
             state: State::default(),
         }
     }
@@ -766,8 +811,8 @@ impl AdvancedTestCore {
             self.state.test4a[27 * 3 + 2] = self.inp_state[1].bits();
         }
 
-        let crc = crc32(bytemuck::cast_slice(&self.state.test4a), !0);
-        let color = !crc & 0x7F7F7F;
+        let crc = crc32(bytemuck::cast_slice(&self.state.test4a));
+        let color = crc & 0x7F7F7F;
         let r = (color >> 16) as u8;
         let g = (color >> 8) as u8;
         let b = color as u8;
@@ -797,21 +842,17 @@ impl AdvancedTestCore {
 impl Core for AdvancedTestCore {
     fn get_info(&self) -> SystemInfo {
         SystemInfo {
-            library_name: CString::new("AdvancedTestCore").unwrap(),
-            library_version: CString::new("0.1.0").unwrap(),
-            valid_extensions: CString::new("").unwrap(),
+            library_name: c_str!("AdvancedTestCore").into(),
+            library_version: c_str!("0.1.0").into(),
+            valid_extensions: c_str!("").into(),
 
             need_fullpath: false,
             block_extract: false,
         }
     }
 
-    fn on_set_environment(&mut self, initial: bool, ctx: &mut SetEnvironmentContext) {
-        if !initial {
-            return;
-        }
-
-        ctx.set_support_no_game(true);
+    fn supports_no_game(&self) -> bool {
+        true
     }
 
     fn on_get_av_info(&mut self, _ctx: &mut GetAvInfoContext) -> retro_system_av_info {
@@ -832,9 +873,9 @@ impl Core for AdvancedTestCore {
 
     fn on_options_changed(&mut self, ctx: &mut OptionsChangedContext) {
         match ctx.get_variable("test_advanced_pixel_format") {
-            Some("0RGB1555") => self.pixel_format = PixelFormat::XRGB1555,
-            Some("XRGB8888") => self.pixel_format = PixelFormat::XRGB8888,
-            Some("RGB565") => self.pixel_format = PixelFormat::RGB565,
+            Ok("0RGB1555") => self.pixel_format = PixelFormat::XRGB1555,
+            Ok("XRGB8888") => self.pixel_format = PixelFormat::XRGB8888,
+            Ok("RGB565") => self.pixel_format = PixelFormat::RGB565,
             _ => (),
         }
     }
@@ -845,19 +886,22 @@ impl Core for AdvancedTestCore {
         ctx: &mut LoadGameContext,
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.active_pixel_format = self.pixel_format;
-        ctx.set_pixel_format(self.active_pixel_format);
-        ctx.set_performance_level(0);
-        ctx.enable_frame_time_callback((1000000.0f64 / FRAMERATE).round() as retro_usec_t);
+        ctx.set_pixel_format(self.active_pixel_format)?;
+        let _ = ctx.set_performance_level(0);
+        let _ = ctx.enable_frame_time_callback((1000000.0f64 / FRAMERATE).round() as retro_usec_t);
         self.has_perf = ctx.enable_perf_interface().is_ok();
 
         let gctx: GenericContext = ctx.into();
-        gctx.enable_audio_callback();
+        self.audio = gctx.enable_audio_state(SineWave::default(), 16).ok();
 
         Ok(())
     }
 
     fn on_reset(&mut self, _ctx: &mut ResetContext) {
-        core::mem::take(self);
+        // The audio state keeps running, so it still needs the messages
+        let old = core::mem::take(self);
+        self.audio = old.audio;
+        self.audio_sound_enable = old.audio_sound_enable;
     }
 
     #[inline]
@@ -985,30 +1029,22 @@ impl Core for AdvancedTestCore {
 
         self.state.frame = self.state.frame.wrapping_add(1);
 
+        if self.sound_enable != self.audio_sound_enable {
+            if let Some(audio) = &mut self.audio {
+                if audio.send(self.sound_enable).is_ok() {
+                    self.audio_sound_enable = self.sound_enable;
+                }
+            }
+        }
+
         let width = fb.width;
         let height = fb.height;
         let pitch = fb.pitch as u64;
         ctx.draw_frame(data, width, height, pitch);
     }
 
-    fn on_write_audio(&mut self, ctx: &mut AudioContext) {
-        if !self.sound_enable {
-            let samples = vec![0; SAMPLE_RATE as usize * 2 / FRAMERATE as usize];
-            return ctx.batch_audio_samples(&samples);
-        }
-
-        let sample_count = SAMPLE_RATE as usize / FRAMERATE as usize * 2;
-        let mut samples = Vec::with_capacity(sample_count);
-
-        for _ in 0..sample_count {
-            samples.push(i16::from_sample(self.sine.next_sample()));
-        }
-
-        ctx.batch_audio_samples(&samples);
-    }
-
     fn get_serialize_size(&mut self, _ctx: &mut GetSerializeSizeContext) -> size_t {
-        std::mem::size_of::<State>() as u64
+        std::mem::size_of::<State>() as size_t
     }
 
     fn on_serialize(&mut self, slice: &mut [u8], _ctx: &mut SerializeContext) -> bool {
@@ -1049,21 +1085,3 @@ fn format_number<T: Integer + Display>(n: T) -> String {
 
     s
 }
-
-/// Karl Malbrain's compact CRC-32.
-/// See "A compact CCITT crc16 and crc32 C implementation that balances processor cache usage against speed":
-/// http://www.geocities.ws/malbrain/
-fn crc32(data: &[u8], mut crc: u32) -> u32 {
-    const LUT: [u32; 16] = [
-        0x00000000, 0x1db71064, 0x3b6e20c8, 0x26d930ac, 0x76dc4190, 0x6b6b51f4, 0x4db26158,
-        0x5005713c, 0xedb88320, 0xf00f9344, 0xd6d6a3e8, 0xcb61b38c, 0x9b64c2b0, 0x86d3d2d4,
-        0xa00ae278, 0xbdbdf21c,
-    ];
-
-    for byte in data {
-        crc = (crc >> 4) ^ LUT[(crc as usize & 15) ^ (*byte as usize & 15)];
-        crc = (crc >> 4) ^ LUT[(crc as usize & 15) ^ ((*byte >> 4) as usize)];
-    }
-
-    crc
-}
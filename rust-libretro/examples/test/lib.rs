@@ -20,10 +20,15 @@
 
 use libc::c_char;
 use rust_libretro::{
-    contexts::*, core::Core, input_descriptor, input_descriptors, proc::CoreOptions, retro_core,
-    sys::*, types::*,
+    audio_state::{AudioSender, AudioState},
+    contexts::*,
+    core::Core,
+    input_descriptor, input_descriptors,
+    proc::{c_str, CoreOptions},
+    retro_core,
+    sys::*,
+    types::*,
 };
-use std::ffi::CString;
 
 const WIDTH: u32 = 320;
 const HEIGHT: u32 = 240;
@@ -99,6 +104,11 @@ const PORTS: usize = 2;
         { "false" },
     }
 })]
+#[visible_when(
+    "test_analog_mouse",
+    "test_analog_mouse_relative",
+    device = "RETRO_DEVICE_ANALOG"
+)]
 struct TestCore {
     aspect: f32,
     sample_rate: f64,
@@ -109,17 +119,17 @@ struct TestCore {
     last_aspect: f32,
     last_samplerate: f64,
 
-    phase: u32,
+    audio: Option<AudioSender<AudioMessage>>,
 
     x_coord: u16,
     y_coord: u16,
     mouse_rel_x: i16,
     mouse_rel_y: i16,
 
-    old_start: [bool; PORTS],
     old_strength_strong: [u16; PORTS],
-    old_select: [bool; PORTS],
     old_strength_weak: [u16; PORTS],
+
+    lightguns: [Lightgun; PORTS],
 }
 
 retro_core!(TestCore {
@@ -132,20 +142,75 @@ retro_core!(TestCore {
     last_aspect: 0.0,
     last_samplerate: 0.0,
 
-    phase: 0,
+    audio: None,
 
     x_coord: 0,
     y_coord: 0,
     mouse_rel_x: WIDTH as i16 / 2,
     mouse_rel_y: HEIGHT as i16 / 2,
 
-    old_start: [false; PORTS],
     old_strength_strong: [0; PORTS],
-    old_select: [false; PORTS],
     old_strength_weak: [0; PORTS],
+
+    lightguns: [Lightgun::new(); PORTS],
 });
 
+/// Option changes passed to the [`SineWave`].
+enum AudioMessage {
+    Enable(bool),
+    SampleRate(f64),
+}
+
+/// Plays a 300 Hz sine wave in the audio callback.
+struct SineWave {
+    enable: bool,
+    sample_rate: f64,
+    phase: u32,
+}
+
+impl AudioState for SineWave {
+    type Message = AudioMessage;
+
+    fn on_message(&mut self, message: AudioMessage) {
+        match message {
+            AudioMessage::Enable(enable) => self.enable = enable,
+            AudioMessage::SampleRate(sample_rate) => self.sample_rate = sample_rate,
+        }
+    }
+
+    fn on_write_audio(&mut self, ctx: &mut AudioContext) {
+        if !self.enable {
+            return ctx.queue_audio_sample(0, 0);
+        }
+
+        let mut samples = Vec::with_capacity(self.sample_rate as usize * 2);
+        let d = self.sample_rate as f32;
+
+        for _ in 0..self.sample_rate as u64 / 60 {
+            let value = ((0x800 as f32)
+                * (2.0 * std::f32::consts::PI * (self.phase as f32) * 300.0 / d).sin())
+                as i16;
+
+            samples.push(value);
+            samples.push(value);
+
+            self.phase += 1;
+        }
+
+        self.phase %= 100;
+
+        ctx.batch_audio_samples(&samples);
+    }
+}
+
 impl TestCore {
+    /// Passes an option change to the [`SineWave`].
+    fn send_audio_message(&mut self, message: AudioMessage) {
+        if let Some(audio) = &mut self.audio {
+            let _ = audio.send(message);
+        }
+    }
+
     fn get_av_info(&mut self) -> retro_system_av_info {
         self.last_samplerate = self.sample_rate;
         self.last_aspect = self.aspect;
@@ -354,14 +419,13 @@ impl TestCore {
                     0xFFFF
                 };
 
-            let start =
-                ctx.get_input_state(port, RETRO_DEVICE_JOYPAD, 0, RETRO_DEVICE_ID_JOYPAD_START)
-                    != 0;
-            let select =
-                ctx.get_input_state(port, RETRO_DEVICE_JOYPAD, 0, RETRO_DEVICE_ID_JOYPAD_SELECT)
-                    != 0;
+            let joypad = ctx.get_joypad_transitions(port);
+            let toggled = joypad.pressed() | joypad.released();
+
+            let start = joypad.current().contains(JoypadState::START);
+            let select = joypad.current().contains(JoypadState::SELECT);
 
-            if self.old_start[port as usize] != start
+            if toggled.contains(JoypadState::START)
                 || self.old_strength_strong[port as usize] != strength_strong
             {
                 log::info!(
@@ -373,11 +437,10 @@ impl TestCore {
                     retro_rumble_effect::RETRO_RUMBLE_STRONG,
                     if start { strength_strong } else { 0 },
                 );
-                self.old_start[port as usize] = start;
                 self.old_strength_strong[port as usize] = strength_strong;
             }
 
-            if self.old_select[port as usize] != select
+            if toggled.contains(JoypadState::SELECT)
                 || self.old_strength_weak[port as usize] != strength_weak
             {
                 log::info!(
@@ -389,31 +452,17 @@ impl TestCore {
                     retro_rumble_effect::RETRO_RUMBLE_WEAK,
                     if select { strength_weak } else { 0 },
                 );
-                self.old_select[port as usize] = select;
                 self.old_strength_weak[port as usize] = strength_weak;
             }
 
-            let trigger_pressed = ctx.get_input_state(
-                port,
-                RETRO_DEVICE_LIGHTGUN,
-                0,
-                RETRO_DEVICE_ID_LIGHTGUN_TRIGGER,
-            ) != 0;
-            if trigger_pressed {
-                let x = ctx.get_input_state(
-                    port,
-                    RETRO_DEVICE_LIGHTGUN,
-                    0,
-                    RETRO_DEVICE_ID_LIGHTGUN_SCREEN_X,
-                );
-                let y = ctx.get_input_state(
-                    port,
-                    RETRO_DEVICE_LIGHTGUN,
-                    0,
-                    RETRO_DEVICE_ID_LIGHTGUN_SCREEN_Y,
-                );
+            let lightgun = &mut self.lightguns[port as usize];
+            lightgun.update(ctx.get_lightgun_state(port, WIDTH, HEIGHT));
 
+            if lightgun.just_fired() {
+                let LightgunState { x, y, .. } = *lightgun.current();
                 log::info!("Lightgun Trigger Pressed #: {port}    : ({x}, {y}).");
+            } else if lightgun.just_reloaded() {
+                log::info!("Lightgun Reloaded #: {port}");
             }
         }
     }
@@ -515,7 +564,7 @@ impl TestCore {
             },
         ];
 
-        ctx.set_subsystem_info(&[
+        let _ = ctx.set_subsystem_info(&[
             retro_subsystem_info {
                 desc: b"Foo\0".as_ptr() as *const c_char,
                 ident: b"foo\0".as_ptr() as *const c_char,
@@ -574,16 +623,16 @@ impl TestCore {
         ];
 
         let gctx: GenericContext = ctx.into();
-        gctx.set_controller_info(&PORTS);
+        let _ = gctx.set_controller_info(&PORTS);
     }
 }
 
 impl Core for TestCore {
     fn get_info(&self) -> SystemInfo {
         SystemInfo {
-            library_name: CString::new("TestCore").unwrap(),
-            library_version: CString::new("0.1.0").unwrap(),
-            valid_extensions: CString::new("").unwrap(),
+            library_name: c_str!("TestCore").into(),
+            library_version: c_str!("0.1.0").into(),
+            valid_extensions: c_str!("").into(),
 
             need_fullpath: false,
             block_extract: false,
@@ -599,7 +648,17 @@ impl Core for TestCore {
         );
 
         let gctx: GenericContext = ctx.into();
-        gctx.set_input_descriptors(INPUT_DESCRIPTORS);
+        let _ = gctx.set_input_descriptors(INPUT_DESCRIPTORS);
+
+        gctx.on_option_change("test_aspect", |value, core: &mut TestCore| match value {
+            Some("4:3") => core.aspect = 4.0 / 3.0,
+            Some("16:9") => core.aspect = 16.0 / 9.0,
+            _ => (),
+        });
+    }
+
+    fn supports_no_game(&self) -> bool {
+        true
     }
 
     fn on_set_environment(&mut self, initial: bool, ctx: &mut SetEnvironmentContext) {
@@ -607,8 +666,6 @@ impl Core for TestCore {
             return;
         }
 
-        ctx.set_support_no_game(true);
-
         self.set_sub_system_info(ctx);
         self.set_controller_info(ctx);
     }
@@ -622,12 +679,10 @@ impl Core for TestCore {
         _info: Option<retro_game_info>,
         ctx: &mut LoadGameContext,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        if !ctx.set_pixel_format(PixelFormat::XRGB8888) {
-            return Err("XRGB8888 is not supported".into());
-        }
+        ctx.set_pixel_format(PixelFormat::XRGB8888)?;
 
-        ctx.set_performance_level(0);
-        ctx.enable_frame_time_callback((1000000.0f64 / 60.0).round() as retro_usec_t);
+        let _ = ctx.set_performance_level(0);
+        let _ = ctx.enable_frame_time_callback((1000000.0f64 / 60.0).round() as retro_usec_t);
 
         match ctx.enable_rumble_interface() {
             Ok(_) => log::info!("Rumble is supported"),
@@ -635,8 +690,13 @@ impl Core for TestCore {
         }
 
         let gctx: GenericContext = ctx.into();
-        gctx.enable_audio_callback();
-        gctx.enable_keyboard_callback();
+        let audio = SineWave {
+            enable: self.audio_enable,
+            sample_rate: self.sample_rate,
+            phase: 0,
+        };
+        self.audio = gctx.enable_audio_state(audio, 4).ok();
+        let _ = gctx.enable_keyboard_callback();
 
         Ok(())
     }
@@ -672,33 +732,29 @@ impl Core for TestCore {
     }
 
     fn on_options_changed(&mut self, ctx: &mut OptionsChangedContext) {
-        match ctx.get_variable("test_aspect") {
-            Some("4:3") => self.aspect = 4.0 / 3.0,
-            Some("16:9") => self.aspect = 16.0 / 9.0,
-            _ => (),
-        }
-
-        if let Some(value) = ctx.get_variable("test_samplerate") {
-            self.sample_rate = value.parse().unwrap()
+        if let Ok(value) = ctx.get_variable("test_samplerate") {
+            self.sample_rate = value.parse().unwrap();
+            self.send_audio_message(AudioMessage::SampleRate(self.sample_rate));
         }
 
         match ctx.get_variable("test_analog_mouse") {
-            Some("true") => self.analog_mouse = true,
-            Some("false") => self.analog_mouse = false,
+            Ok("true") => self.analog_mouse = true,
+            Ok("false") => self.analog_mouse = false,
             _ => (),
         }
 
         match ctx.get_variable("test_analog_mouse_relative") {
-            Some("true") => self.analog_mouse_relative = true,
-            Some("false") => self.analog_mouse_relative = false,
+            Ok("true") => self.analog_mouse_relative = true,
+            Ok("false") => self.analog_mouse_relative = false,
             _ => (),
         }
 
         match ctx.get_variable("test_audio_enable") {
-            Some("true") => self.audio_enable = true,
-            Some("false") => self.audio_enable = false,
+            Ok("true") => self.audio_enable = true,
+            Ok("false") => self.audio_enable = false,
             _ => (),
         }
+        self.send_audio_message(AudioMessage::Enable(self.audio_enable));
     }
 
     fn on_set_controller_port_device(&mut self, port: u32, device: u32, ctx: &mut GenericContext) {
@@ -813,45 +869,25 @@ impl Core for TestCore {
             }
         }
 
-        ctx.set_input_descriptors(&descriptors);
+        let _ = ctx.update_input_descriptors(port, &descriptors);
     }
 
     #[inline]
     fn on_run(&mut self, ctx: &mut RunContext, _delta_us: Option<i64>) {
         if self.last_samplerate != self.sample_rate {
             log::info!("Changing sample rate to {}", self.sample_rate);
-            ctx.set_system_av_info(self.get_av_info());
+            let _ = ctx.set_system_av_info(self.get_av_info());
         } else if self.last_aspect != self.aspect {
             log::info!("Changing aspect ratio to {}", self.aspect);
-            ctx.set_game_geometry(self.get_av_info().geometry);
+            let _ = ctx.set_game_geometry(self.get_av_info().geometry);
         }
 
         self.update_input(ctx);
         self.render(ctx);
     }
 
-    fn on_write_audio(&mut self, ctx: &mut AudioContext) {
-        if !self.audio_enable {
-            return ctx.queue_audio_sample(0, 0);
-        }
-
-        let mut samples = Vec::with_capacity(self.sample_rate as usize * 2);
-        let d = self.sample_rate as f32;
-
-        for _ in 0..self.sample_rate as u64 / 60 {
-            let value = ((0x800 as f32)
-                * (2.0 * std::f32::consts::PI * (self.phase as f32) * 300.0 / d).sin())
-                as i16;
-
-            samples.push(value);
-            samples.push(value);
-
-            self.phase += 1;
-        }
-
-        self.phase %= 100;
-
-        ctx.batch_audio_samples(&samples);
+    fn on_fastforward_changed(&mut self, fastforwarding: bool, _ctx: &mut GenericContext) {
+        log::info!("Fast-forwarding: {fastforwarding}");
     }
 
     fn on_keyboard_event(
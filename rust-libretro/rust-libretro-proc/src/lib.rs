@@ -14,6 +14,7 @@ use syn::{
     Attribute, DeriveInput, LitByteStr, LitStr, NestedMeta, Token,
 };
 
+mod png;
 mod util;
 use util::*;
 
@@ -178,6 +179,65 @@ impl Parse for CoreOptionV2 {
     }
 }
 
+impl CoreOptionV2 {
+    /// Parses the `bool "key", "desc", info = "…", category = "…", default = false`
+    /// shorthand for a toggle, everything after the description is optional.
+    fn parse_bool(input: ParseStream) -> Result<Self> {
+        let key: LitStr = input.parse()?;
+        input.parse::<Token![,]>()?;
+
+        let desc: LitStr = input.parse()?;
+
+        let mut info = None;
+        let mut category_key = None;
+        let mut default = false;
+
+        // A comma can also separate this option from the next one
+        loop {
+            let fork = input.fork();
+            if fork.parse::<Token![,]>().is_err() {
+                break;
+            }
+
+            let name = match fork.parse::<syn::Ident>() {
+                Ok(name) if name == "info" || name == "category" || name == "default" => name,
+                _ => break,
+            };
+            fork.parse::<Token![=]>()?;
+
+            if name == "default" {
+                default = fork.parse::<syn::LitBool>()?.value;
+            } else if name == "info" {
+                info = Some(fork.parse::<LitStr>()?);
+            } else {
+                category_key = Some(fork.parse::<LitStr>()?);
+            }
+
+            input.advance_to(&fork);
+        }
+
+        let span = key.span();
+        let value = |value: &str, label: &str| CoreOptionValue {
+            value: LitStr::new(value, span),
+            label: Some(LitStr::new(label, span)),
+        };
+
+        Ok(Self {
+            key,
+            desc,
+            desc_categorized: None,
+            info: info.unwrap_or_else(|| LitStr::new("", span)),
+            info_categorized: None,
+            category_key,
+            values: vec![value("disabled", "Disabled"), value("enabled", "Enabled")],
+            default_value: Some(LitStr::new(
+                if default { "enabled" } else { "disabled" },
+                span,
+            )),
+        })
+    }
+}
+
 impl From<CoreOption> for CoreOptionV2 {
     fn from(option: CoreOption) -> Self {
         Self {
@@ -204,6 +264,24 @@ impl Parse for CoreOptions {
         let mut options = Self::default();
 
         while !input.is_empty() {
+            if input.peek(syn::Ident) {
+                let ident: syn::Ident = input.parse()?;
+                if ident != "bool" {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "expected a braced core option or `bool`",
+                    ));
+                }
+
+                options.0.push(CoreOptionV2::parse_bool(&input)?);
+
+                if input.is_empty() {
+                    break;
+                }
+                input.parse::<Token![,]>()?;
+                continue;
+            }
+
             let option;
             braced!(option in input);
 
@@ -230,6 +308,108 @@ impl Parse for CoreOptions {
     }
 }
 
+impl CoreOptions {
+    /// Rejects declarations the frontend would silently misinterpret,
+    /// reporting all of them at once.
+    fn validate(&self, categories: &CoreOptionCategories) -> Result<()> {
+        let max_values = RETRO_NUM_CORE_OPTION_VALUES_MAX as usize - 1;
+        let mut errors: Vec<syn::Error> = Vec::new();
+
+        for (index, category) in categories.0.iter().enumerate() {
+            if categories.0[..index]
+                .iter()
+                .any(|other| other.key.value() == category.key.value())
+            {
+                errors.push(syn::Error::new(
+                    category.key.span(),
+                    format!("duplicate category `{}`", category.key.value()),
+                ));
+            }
+        }
+
+        for (index, option) in self.0.iter().enumerate() {
+            let key = option.key.value();
+
+            if self.0[..index].iter().any(|other| other.key.value() == key) {
+                errors.push(syn::Error::new(
+                    option.key.span(),
+                    format!("duplicate core option `{key}`"),
+                ));
+            }
+
+            if option.values.is_empty() {
+                errors.push(syn::Error::new(
+                    option.key.span(),
+                    format!("core option `{key}` has no values"),
+                ));
+            }
+
+            if option.values.len() > max_values {
+                errors.push(syn::Error::new(
+                    option.values[max_values].value.span(),
+                    format!(
+                        "core option `{key}` has {} values, at most {max_values} are supported",
+                        option.values.len()
+                    ),
+                ));
+            }
+
+            for (index, value) in option.values.iter().enumerate() {
+                if option.values[..index]
+                    .iter()
+                    .any(|other| other.value.value() == value.value.value())
+                {
+                    errors.push(syn::Error::new(
+                        value.value.span(),
+                        format!("duplicate value `{}`", value.value.value()),
+                    ));
+                }
+            }
+
+            // An empty key leaves the option uncategorized
+            if let Some(category_key) = &option.category_key {
+                let category = category_key.value();
+
+                if !category.is_empty()
+                    && !categories
+                        .0
+                        .iter()
+                        .any(|declared| declared.key.value() == category)
+                {
+                    errors.push(syn::Error::new(
+                        category_key.span(),
+                        format!("unknown category `{category}`, declare it with `#[categories]`"),
+                    ));
+                }
+            }
+
+            if let Some(default_value) = &option.default_value {
+                if !option
+                    .values
+                    .iter()
+                    .any(|value| value.value.value() == default_value.value())
+                {
+                    errors.push(syn::Error::new(
+                        default_value.span(),
+                        format!(
+                            "default value `{}` is not a value of `{key}`",
+                            default_value.value()
+                        ),
+                    ));
+                }
+            }
+        }
+
+        match errors.into_iter().reduce(|mut combined, error| {
+            combined.combine(error);
+            combined
+        }) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
 impl Concat<CoreOptions> for Vec<CoreOptions> {
     fn concat(self) -> CoreOptions {
         CoreOptions(self.into_iter().flat_map(|x| x.0).collect::<Vec<_>>())
@@ -300,6 +480,69 @@ impl Concat<CoreOptionCategories> for Vec<CoreOptionCategories> {
     }
 }
 
+/// `#[visible_when("key", device = "RETRO_DEVICE_ANALOG", port = 0)]`, the port is optional.
+/// Several keys can share a rule: `#[visible_when("key1", "key2", device = "...")]`.
+#[derive(Debug)]
+struct VisibleWhen {
+    keys: Vec<LitStr>,
+    device: syn::Ident,
+    port: Option<syn::LitInt>,
+}
+
+impl Parse for VisibleWhen {
+    fn parse(outer: ParseStream) -> Result<Self> {
+        let input;
+        parenthesized!(input in outer);
+
+        let mut keys: Vec<LitStr> = vec![input.parse()?];
+        let mut device = None;
+        let mut port = None;
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+
+            // allow trailing comma
+            if input.is_empty() {
+                break;
+            }
+
+            if input.peek(LitStr) {
+                if device.is_some() || port.is_some() {
+                    return Err(input.error("keys have to come before `device` and `port`"));
+                }
+
+                keys.push(input.parse()?);
+                continue;
+            }
+
+            let name: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            if name == "device" {
+                let value: LitStr = input.parse()?;
+
+                if !value.value().starts_with("RETRO_DEVICE_") {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "expected the name of a `RETRO_DEVICE_*` constant",
+                    ));
+                }
+
+                device = Some(syn::Ident::new(&value.value(), value.span()));
+            } else if name == "port" {
+                port = Some(input.parse()?);
+            } else {
+                return Err(syn::Error::new(name.span(), "expected `device` or `port`"));
+            }
+        }
+
+        let device =
+            device.ok_or_else(|| syn::Error::new(keys[0].span(), "missing `device = \"...\"`"))?;
+
+        Ok(Self { keys, device, port })
+    }
+}
+
 /// Implements the CoreOptions trait by generating a `set_core_options()` implementation
 /// that checks whether the frontend supports “options v2” or “options v1”
 /// and uses `retro_variable`s as fallback.
@@ -311,6 +554,14 @@ impl Concat<CoreOptionCategories> for Vec<CoreOptionCategories> {
 /// - `retro_core_option_v2_definition`
 /// - `retro_core_options_v2`
 ///
+/// The declarations are also made available through `CoreOptions::option_definitions()`
+/// and `CoreOptions::export_definitions()` for tooling.
+///
+/// Duplicate keys or values, options without values or with more than
+/// `RETRO_NUM_CORE_OPTION_VALUES_MAX - 1` of them, default values missing
+/// from the value list and category keys without a `#[categories]` entry
+/// are compile errors.
+///
 /// # Examples
 ///
 /// ```ignore
@@ -338,10 +589,59 @@ impl Concat<CoreOptionCategories> for Vec<CoreOptionCategories> {
 /// struct TestCore;
 /// ```
 ///
+/// Toggles can be declared with the `bool` shorthand, which expands to the values
+/// `disabled` and `enabled`, labeled “Disabled” and “Enabled”. `info`, `category`
+/// and `default` are optional, options are disabled by default.
+/// Read them with `GenericContext::get_bool_variable`.
+///
+/// ```ignore
+/// #[derive(CoreOptions)]
+/// #[options(bool "foo_option_3", "Toggle Something", default = true)]
+/// #[categories({ "video", "Video", "" })]
+/// #[options({
+///     "foo_option_4",
+///     "Frame skip",
+///     "",
+///     {
+///         { "0" },
+///         { "1" },
+///     },
+/// }, bool "foo_option_5", "Show FPS", info = "Displays the frame rate", category = "video")]
+/// struct TestCore;
+/// ```
+///
+/// Options can be hidden depending on the devices connected to the ports, e.g. settings
+/// that only apply to analog controllers. An option with `#[visible_when]` attributes is
+/// shown if any of them matches, see `types::OptionVisibilityRule`. Options sharing a
+/// rule can be listed in the same attribute.
+///
+/// ```ignore
+/// #[derive(CoreOptions)]
+/// #[options({
+///     "foo_analog_deadzone",
+///     "Analog deadzone",
+///     "",
+///     {
+///         { "15" },
+///         { "25" },
+///     },
+/// }, {
+///     "foo_analog_sensitivity",
+///     "Analog sensitivity",
+///     "",
+///     {
+///         { "100" },
+///         { "150" },
+///     },
+/// })]
+/// #[visible_when("foo_analog_deadzone", "foo_analog_sensitivity", device = "RETRO_DEVICE_ANALOG")]
+/// struct TestCore;
+/// ```
+///
 /// **TODO**:
 /// - Add V2 (category support) documentation
 /// - Support `*_intl` variants
-#[proc_macro_derive(CoreOptions, attributes(options, categories))]
+#[proc_macro_derive(CoreOptions, attributes(options, categories, visible_when))]
 pub fn derive_core_options(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -375,6 +675,49 @@ fn impl_derive_core_options(input: DeriveInput) -> TokenStream {
         Err(err) => return TokenStream::from(err.to_compile_error()),
     };
 
+    if let Err(err) = options.validate(&categories) {
+        return TokenStream::from(err.to_compile_error());
+    }
+
+    let visibility_rules = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("visible_when"))
+        .map(|attr| -> Result<Vec<proc_macro2::TokenStream>> {
+            let VisibleWhen { keys, device, port } = parse2(attr.tokens.clone())?;
+
+            let port = match port {
+                Some(port) => quote! { Some(#port) },
+                None => quote! { None },
+            };
+
+            keys.iter()
+                .map(|key| {
+                    if !options
+                        .0
+                        .iter()
+                        .any(|option| option.key.value() == key.value())
+                    {
+                        return Err(syn::Error::new(key.span(), "unknown core option"));
+                    }
+
+                    Ok(quote! {
+                        ::rust_libretro::types::OptionVisibilityRule {
+                            key: #key,
+                            device: ::rust_libretro::sys::#device,
+                            port: #port,
+                        }
+                    })
+                })
+                .collect()
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(|rules| rules.concat());
+
+    let visibility_rules = match visibility_rules {
+        Ok(visibility_rules) => visibility_rules,
+        Err(err) => return TokenStream::from(err.to_compile_error()),
+    };
+
     let option_count = options.0.len();
     let category_count = categories.0.len();
 
@@ -560,20 +903,93 @@ fn impl_derive_core_options(input: DeriveInput) -> TokenStream {
         })
         .collect::<Vec<_>>();
 
+    fn optional_str(value: &Option<LitStr>) -> proc_macro2::TokenStream {
+        match value {
+            Some(value) => quote! { Some(#value) },
+            None => quote! { None },
+        }
+    }
+
+    let option_definitions = options
+        .0
+        .iter()
+        .map(|option| {
+            let CoreOptionV2 {
+                key, desc, info, ..
+            } = option;
+            let desc_categorized = optional_str(&option.desc_categorized);
+            let info_categorized = optional_str(&option.info_categorized);
+            let category_key = optional_str(&option.category_key);
+            let default_value = optional_str(&option.default_value);
+
+            let values = option.values.iter().map(|value| {
+                let label = optional_str(&value.label);
+                let value = &value.value;
+
+                quote! {
+                    ::rust_libretro::types::CoreOptionValueDefinition {
+                        value: #value,
+                        label: #label,
+                    }
+                }
+            });
+
+            quote! {
+                ::rust_libretro::types::CoreOptionDefinition {
+                    key: #key,
+                    desc: #desc,
+                    desc_categorized: #desc_categorized,
+                    info: #info,
+                    info_categorized: #info_categorized,
+                    category_key: #category_key,
+                    values: &[ #(#values),* ],
+                    default_value: #default_value,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let category_definitions = categories
+        .0
+        .iter()
+        .map(|CoreOptionCategory { key, desc, info }| {
+            quote! {
+                ::rust_libretro::types::CoreOptionCategoryDefinition {
+                    key: #key,
+                    desc: #desc,
+                    info: #info,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
     let expanded = quote! {
         impl #impl_generics ::rust_libretro::core::CoreOptions for #name #ty_generics #where_clause {
             fn set_core_options(&self, ctx: &SetEnvironmentContext) -> bool {
-                let gctx: GenericContext = ctx.into();
-
                 // For some reason the call to `supports_set_core_options` only works on the initial call of `on_set_environment`.
                 // On subsequent calls of `on_set_environment` querying `RETRO_ENVIRONMENT_GET_CORE_OPTIONS_VERSION` returns NULL pointers.
                 // But our `retro_set_environment` wrapper makes sure to call us on the initial call of `on_set_environment` only.
-                match gctx.get_core_options_version() {
-                    n if n >= 2 => ctx.set_core_options_v2(&Self::__RETRO_CORE_OPTIONS_V2),
-                    n if n >= 1 => ctx.set_core_options(&Self::__RETRO_CORE_OPTIONS),
-                    _ => ctx.set_variables(&Self::__RETRO_CORE_VARIABLES)
+                match ctx.core_options_version() {
+                    // `Ok(false)` means the options got registered, but the frontend ignores the categories
+                    n if n >= 2 => ctx.set_core_options_v2(&Self::__RETRO_CORE_OPTIONS_V2).is_ok(),
+                    n if n >= 1 => ctx.set_core_options(&Self::__RETRO_CORE_OPTIONS).is_ok(),
+                    _ => ctx.set_variables(&Self::__RETRO_CORE_VARIABLES).is_ok()
                 }
             }
+
+            fn option_definitions() -> ::rust_libretro::types::CoreOptionDefinitions {
+                const DEFINITIONS: ::rust_libretro::types::CoreOptionDefinitions =
+                    ::rust_libretro::types::CoreOptionDefinitions {
+                        categories: &[ #(#category_definitions),* ],
+                        definitions: &[ #(#option_definitions),* ],
+                    };
+
+                DEFINITIONS
+            }
+
+            fn option_visibility_rules(&self) -> &'static [::rust_libretro::types::OptionVisibilityRule] {
+                &[ #(#visibility_rules),* ]
+            }
         }
 
         impl #impl_generics #name #ty_generics #where_clause {
@@ -649,6 +1065,90 @@ fn impl_derive_core_options(input: DeriveInput) -> TokenStream {
     TokenStream::from(expanded)
 }
 
+/// Turns a string literal into a `&'static CStr` at compile time.
+///
+/// Interior NUL bytes are rejected with a compile error, so no check is necessary at runtime.
+///
+/// ```rust
+/// use std::ffi::CStr;
+///
+/// const NAME: &CStr = rust_libretro_proc::c_str!("ExampleCore");
+/// assert_eq!(NAME.to_str(), Ok("ExampleCore"));
+/// ```
+///
+/// ```compile_fail
+/// let name = rust_libretro_proc::c_str!("Example\0Core");
+/// ```
+#[proc_macro]
+pub fn c_str(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    let value = lit.value();
+
+    if value.contains('\0') {
+        return TokenStream::from(
+            syn::Error::new(lit.span(), "C strings must not contain NUL bytes").to_compile_error(),
+        );
+    }
+
+    let bytes = LitByteStr::new(format!("{}\0", value).as_bytes(), lit.span());
+
+    let expanded = quote! {
+        unsafe { ::std::ffi::CStr::from_bytes_with_nul_unchecked(#bytes) }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Embeds a PNG image as a [`StaticImage`](../rust_libretro/types/struct.StaticImage.html)
+/// of `XRGB8888` pixels, decoded at compile time.
+///
+/// The path is relative to the directory of the `Cargo.toml` of the crate using the macro.
+/// Only non-interlaced images with a bit depth of 8 are supported.
+/// The alpha channel, if any, ends up in the top byte of every pixel.
+///
+/// ```ignore
+/// const BODY: StaticImage = include_image_xrgb8888!("img/body.png");
+/// ```
+#[proc_macro]
+pub fn include_image_xrgb8888(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+
+    let path = match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(directory) => std::path::Path::new(&directory).join(lit.value()),
+        None => std::path::PathBuf::from(lit.value()),
+    };
+
+    let image = match std::fs::read(&path) {
+        Ok(data) => {
+            png::decode(&data).map_err(|err| format!("Failed to decode {}: {err}", path.display()))
+        }
+        Err(err) => Err(format!("Failed to read {}: {err}", path.display())),
+    };
+
+    let image = match image {
+        Ok(image) => image,
+        Err(err) => return TokenStream::from(syn::Error::new(lit.span(), err).to_compile_error()),
+    };
+
+    let path = LitStr::new(&path.to_string_lossy(), lit.span());
+    let (width, height, pixels) = (image.width, image.height, image.pixels);
+
+    let expanded = quote! {
+        {
+            // Rebuilds the crate when the image changes
+            const _: &[u8] = include_bytes!(#path);
+
+            ::rust_libretro::types::StaticImage {
+                width: #width,
+                height: #height,
+                pixels: &[#(#pixels),*],
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
 const UNSTABLE_TAG: &str = "<span class='stab unstable'>Unstable</span>";
 
 fn get_unstable_text(feature_name: &str) -> String {
@@ -876,3 +1376,83 @@ pub fn context(args: TokenStream, input: TokenStream) -> TokenStream {
 
     TokenStream::from(ctx_impl)
 }
+
+#[test]
+fn validate_core_options() {
+    let valid: CoreOptions = parse_quote! {({
+        "foo_speed", "Speed", "", { { "1" }, { "2", "Fast" } }, "2"
+    }, {
+        "foo_mode", "Mode", "", { { "a" } },
+    })};
+    assert!(valid.validate(&CoreOptionCategories::default()).is_ok());
+
+    let invalid: CoreOptions = parse_quote! {({
+        "foo_speed", "Speed", "", { { "1" }, { "1" } }, "3"
+    }, {
+        "foo_speed", "Speed", "", {},
+    })};
+    let messages = invalid
+        .validate(&CoreOptionCategories::default())
+        .unwrap_err()
+        .into_iter()
+        .map(|err| err.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            "duplicate value `1`",
+            "default value `3` is not a value of `foo_speed`",
+            "duplicate core option `foo_speed`",
+            "core option `foo_speed` has no values",
+        ]
+    );
+
+    let toggles: CoreOptions = parse_quote! {(
+        bool "foo_fps", "Show FPS", default = true,
+        { "foo_mode", "Mode", "", { { "a" } } },
+        bool "foo_hud", "HUD", info = "Overlay", category = "video",
+    )};
+    assert!(toggles.validate(&CoreOptionCategories::default()).is_err());
+    let fps = &toggles.0[0];
+    assert_eq!(fps.default_value.as_ref().unwrap().value(), "enabled");
+    assert_eq!(
+        fps.values
+            .iter()
+            .map(|value| (value.value.value(), value.label.as_ref().unwrap().value()))
+            .collect::<Vec<_>>(),
+        [
+            ("disabled".to_owned(), "Disabled".to_owned()),
+            ("enabled".to_owned(), "Enabled".to_owned())
+        ]
+    );
+    let hud = &toggles.0[2];
+    assert_eq!(hud.info.value(), "Overlay");
+    assert_eq!(hud.category_key.as_ref().unwrap().value(), "video");
+    assert_eq!(hud.default_value.as_ref().unwrap().value(), "disabled");
+
+    let categories: CoreOptionCategories = parse_quote! {({
+        "video", "Video", ""
+    }, {
+        "video", "Video", ""
+    })};
+    let categorized: CoreOptions = parse_quote! {({
+        "foo_scale", "Video > Scale", "Scale", "", "", "video", { { "1" } }
+    }, {
+        "foo_volume", "Audio > Volume", "Volume", "", "", "audoi", { { "1" } }
+    }, {
+        "foo_misc", "Misc", "Misc", "", "", "", { { "1" } }
+    })};
+    let messages = categorized
+        .validate(&categories)
+        .unwrap_err()
+        .into_iter()
+        .map(|err| err.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            "duplicate category `video`",
+            "unknown category `audoi`, declare it with `#[categories]`",
+        ]
+    );
+}
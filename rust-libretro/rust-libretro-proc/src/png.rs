@@ -0,0 +1,412 @@
+//! A minimal PNG decoder for [`include_image_xrgb8888!`](crate::include_image_xrgb8888).
+//!
+//! Supports non-interlaced images with a bit depth of 8 in every color type.
+//! Ancillary chunks other than `tRNS` get ignored, as do the checksums.
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// A decoded image, one `0xAARRGGBB` value per pixel.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u32>,
+}
+
+pub fn decode(png: &[u8]) -> Result<Image, String> {
+    if !png.starts_with(SIGNATURE) {
+        return Err("Not a PNG file".to_owned());
+    }
+
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+
+    let mut rest = &png[SIGNATURE.len()..];
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let kind = &rest[4..8];
+        let data = rest
+            .get(8..8 + length)
+            .ok_or_else(|| "Truncated chunk".to_owned())?;
+
+        match kind {
+            b"IHDR" if length == 13 => header = Some(data),
+            b"PLTE" => palette = data,
+            b"tRNS" => transparency = data,
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => (),
+        }
+
+        rest = &rest[(12 + length).min(rest.len())..];
+    }
+
+    let header = header.ok_or_else(|| "Missing IHDR chunk".to_owned())?;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+
+    if depth != 8 {
+        return Err(format!("Unsupported bit depth {depth}, expected 8"));
+    } else if interlace != 0 {
+        return Err("Interlaced images are not supported".to_owned());
+    }
+
+    let channels = match color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return Err(format!("Unknown color type {color_type}")),
+    };
+
+    let data = unfilter(&inflate_zlib(&compressed)?, width, height, channels)?;
+
+    let pixels = data
+        .chunks_exact(channels)
+        .map(|pixel| {
+            let [r, g, b, a] = match (color_type, pixel) {
+                (0, &[y]) => [y, y, y, 0xFF],
+                (4, &[y, a]) => [y, y, y, a],
+                (2, &[r, g, b]) => [r, g, b, 0xFF],
+                (6, &[r, g, b, a]) => [r, g, b, a],
+                (_, &[index]) => {
+                    let index = index as usize;
+                    let rgb = palette
+                        .get(index * 3..index * 3 + 3)
+                        .ok_or_else(|| format!("Palette index {index} out of range"))?;
+                    let a = transparency.get(index).copied().unwrap_or(0xFF);
+                    [rgb[0], rgb[1], rgb[2], a]
+                }
+                _ => unreachable!(),
+            };
+
+            Ok(u32::from_be_bytes([a, r, g, b]))
+        })
+        .collect::<Result<_, String>>()?;
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Reverses the per-row filters, returning the tightly packed image data.
+fn unfilter(data: &[u8], width: u32, height: u32, channels: usize) -> Result<Vec<u8>, String> {
+    let stride = width as usize * channels;
+    let height = height as usize;
+
+    if data.len() < (stride + 1) * height {
+        return Err("Truncated image data".to_owned());
+    }
+
+    let mut out = vec![0u8; stride * height];
+
+    for (y, row) in data.chunks_exact(stride + 1).take(height).enumerate() {
+        let (filter, row) = (row[0], &row[1..]);
+        let (previous, current) = out.split_at_mut(y * stride);
+        let above = &previous[y.saturating_sub(1) * stride..];
+        let current = &mut current[..stride];
+
+        for x in 0..stride {
+            let a = if x >= channels {
+                current[x - channels]
+            } else {
+                0
+            };
+            let b = above.get(x).copied().unwrap_or(0);
+            let c = if x >= channels {
+                above.get(x - channels).copied().unwrap_or(0)
+            } else {
+                0
+            };
+
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("Unknown filter type {filter}")),
+            };
+
+            current[x] = row[x].wrapping_add(predicted);
+        }
+    }
+
+    Ok(out)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or_else(|| "Truncated deflate stream".to_owned())?;
+
+            self.buffer |= (byte as u32) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code, decoded bit by bit.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err("Invalid Huffman code".to_owned())
+    }
+}
+
+fn inflate_zlib(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 2 || data[0] & 0x0F != 8 || data[1] & 0x20 != 0 {
+        return Err("Unsupported zlib stream".to_owned());
+    }
+
+    let mut reader = BitReader {
+        data: &data[2..],
+        position: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+
+                let header = reader
+                    .data
+                    .get(reader.position..reader.position + 4)
+                    .ok_or_else(|| "Truncated stored block".to_owned())?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                reader.position += 4;
+
+                let block = reader
+                    .data
+                    .get(reader.position..reader.position + length)
+                    .ok_or_else(|| "Truncated stored block".to_owned())?;
+                out.extend_from_slice(block);
+                reader.position += length;
+            }
+            1 => {
+                let mut lengths = [0u8; 288 + 30];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..288].fill(8);
+                lengths[288..].fill(5);
+
+                let literals = Huffman::new(&lengths[..288]);
+                let distances = Huffman::new(&lengths[288..]);
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            }
+            _ => return Err("Invalid deflate block type".to_owned()),
+        }
+
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn read_dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_lengths.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| "Repeated code length without a previous one".to_owned())?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+
+        lengths.resize(lengths.len() + repeat as usize, value);
+    }
+
+    if lengths.len() != literal_count + distance_count {
+        return Err("Too many code lengths".to_owned());
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err("Invalid length symbol".to_owned());
+                }
+                let length =
+                    LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+
+                let index = distances.decode(reader)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err("Invalid distance symbol".to_owned());
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+
+                if distance > out.len() {
+                    return Err("Distance too far back".to_owned());
+                }
+
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn png_decoding() {
+    let image = decode(include_bytes!("../../src/debug/img/body.png")).unwrap();
+    assert_eq!((image.width, image.height), (416, 282));
+    assert_eq!(image.pixels.len(), 416 * 282);
+    assert_eq!(image.pixels[0], 0);
+    assert_eq!(image.pixels[141 * 416 + 208], 0xFFC4C4C4);
+
+    let sum = image
+        .pixels
+        .iter()
+        .fold(0u32, |sum, &pixel| sum.wrapping_add(pixel));
+    assert_eq!(sum, 0x7D6533D5);
+}
@@ -0,0 +1,219 @@
+//! Controls which calls of the libretro API get trace logged.
+//!
+//! Every call the frontend makes into the core can be logged at [`log::Level::Trace`].
+//! Logging `retro_run` and the other calls made once per frame buries everything else,
+//! so by default they are only logged every 600th time, while lifecycle calls like
+//! `retro_load_game` are always logged. Sampled calls get their number appended:
+//!
+//! ```text
+//! [rust_libretro] retro_run() [call 1201]
+//! ```
+//!
+//! The rates can be changed per [`ApiGroup`] and per entry point, e.g. before asking
+//! a user for a log of a bug with savestates:
+//!
+//! ```
+//! use rust_libretro::api_trace::{self, ApiGroup, ApiTraceConfig, TraceRate};
+//!
+//! api_trace::set_config(
+//!     ApiTraceConfig::new()
+//!         .with_group(ApiGroup::Frame, TraceRate::Never)
+//!         .with_group(ApiGroup::State, TraceRate::Always)
+//!         .with_entry_point("retro_get_memory_data", TraceRate::Every(60)),
+//! );
+//! ```
+//!
+//! Set the config in [`Core::on_set_environment`](crate::core::Core::on_set_environment)
+//! to include the first calls. Requires the `log` feature.
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+/// How often calls of an entry point get logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceRate {
+    Never,
+    Always,
+
+    /// Logs the first call and every n-th call after it, `Every(0)` never logs.
+    Every(u32),
+}
+
+impl TraceRate {
+    /// Whether the call with the zero-based `index` gets logged.
+    fn logs(self, index: u64) -> bool {
+        match self {
+            Self::Never => false,
+            Self::Always => true,
+            Self::Every(n) => index.checked_rem(n as u64) == Some(0),
+        }
+    }
+}
+
+/// The kinds of calls the frontend makes into the core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiGroup {
+    /// Initializing the core and loading games, e.g. `retro_init` or `retro_load_game`.
+    Lifecycle,
+
+    /// Calls made once or more per frame, e.g. `retro_run` or the audio callback.
+    Frame,
+
+    /// Savestates, cheats and the core’s memory, e.g. `retro_serialize`.
+    State,
+
+    /// The callbacks of the interfaces enabled by the core, e.g. the keyboard or disk control.
+    Callback,
+}
+
+/// The rates calls get logged at, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiTraceConfig {
+    lifecycle: TraceRate,
+    frame: TraceRate,
+    state: TraceRate,
+    callback: TraceRate,
+
+    /// Overrides the rate of the group for single entry points.
+    entry_points: Vec<(&'static str, TraceRate)>,
+}
+
+impl ApiTraceConfig {
+    /// Logs every 600th call of the [`ApiGroup::Frame`] and every call of the other groups.
+    pub const fn new() -> Self {
+        Self {
+            lifecycle: TraceRate::Always,
+            frame: TraceRate::Every(600),
+            state: TraceRate::Always,
+            callback: TraceRate::Always,
+
+            entry_points: Vec::new(),
+        }
+    }
+
+    /// Sets the rate of all entry points in `group`.
+    pub fn with_group(mut self, group: ApiGroup, rate: TraceRate) -> Self {
+        *self.group_rate_mut(group) = rate;
+        self
+    }
+
+    /// Sets the rate of the entry point called `name`, e.g. `"retro_run"`,
+    /// regardless of its group.
+    pub fn with_entry_point(mut self, name: &'static str, rate: TraceRate) -> Self {
+        self.entry_points
+            .retain(|(entry_point, _)| *entry_point != name);
+        self.entry_points.push((name, rate));
+        self
+    }
+
+    /// Returns the rate of the entry point called `name` in `group`.
+    pub fn rate(&self, group: ApiGroup, name: &str) -> TraceRate {
+        self.entry_points
+            .iter()
+            .find(|(entry_point, _)| *entry_point == name)
+            .map_or_else(|| self.group_rate(group), |(_, rate)| *rate)
+    }
+
+    fn group_rate(&self, group: ApiGroup) -> TraceRate {
+        match group {
+            ApiGroup::Lifecycle => self.lifecycle,
+            ApiGroup::Frame => self.frame,
+            ApiGroup::State => self.state,
+            ApiGroup::Callback => self.callback,
+        }
+    }
+
+    fn group_rate_mut(&mut self, group: ApiGroup) -> &mut TraceRate {
+        match group {
+            ApiGroup::Lifecycle => &mut self.lifecycle,
+            ApiGroup::Frame => &mut self.frame,
+            ApiGroup::State => &mut self.state,
+            ApiGroup::Callback => &mut self.callback,
+        }
+    }
+}
+
+impl Default for ApiTraceConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static CONFIG: RwLock<ApiTraceConfig> = RwLock::new(ApiTraceConfig::new());
+
+/// Replaces the rates calls get logged at.
+pub fn set_config(config: ApiTraceConfig) {
+    *CONFIG.write().unwrap() = config;
+}
+
+pub fn config() -> ApiTraceConfig {
+    CONFIG.read().unwrap().clone()
+}
+
+/// Appended to the logged calls of sampled entry points.
+pub(crate) struct CallNumber(Option<u64>);
+
+impl fmt::Display for CallNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(number) => write!(f, " [call {number}]"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Counts a call of the entry point `call` is logged for, returns whether to log it.
+///
+/// `call` is the logged message, which starts with the name of the entry point.
+pub(crate) fn sample(group: ApiGroup, call: &str, calls: &AtomicU64) -> Option<CallNumber> {
+    let index = calls.fetch_add(1, Ordering::Relaxed);
+
+    if !log::log_enabled!(log::Level::Trace) {
+        return None;
+    }
+
+    let name = call.split('(').next().unwrap_or(call);
+    match CONFIG.read().unwrap().rate(group, name) {
+        TraceRate::Always => Some(CallNumber(None)),
+        rate if rate.logs(index) => Some(CallNumber(Some(index + 1))),
+        _ => None,
+    }
+}
+
+#[test]
+fn trace_rates() {
+    let config = ApiTraceConfig::new()
+        .with_group(ApiGroup::State, TraceRate::Never)
+        .with_entry_point("retro_get_memory_data", TraceRate::Every(2))
+        .with_entry_point("retro_get_memory_data", TraceRate::Every(3));
+
+    assert_eq!(
+        config.rate(ApiGroup::Lifecycle, "retro_init"),
+        TraceRate::Always
+    );
+    assert_eq!(
+        config.rate(ApiGroup::Frame, "retro_run"),
+        TraceRate::Every(600)
+    );
+    assert_eq!(
+        config.rate(ApiGroup::State, "retro_serialize"),
+        TraceRate::Never
+    );
+    assert_eq!(
+        config.rate(ApiGroup::State, "retro_get_memory_data"),
+        TraceRate::Every(3)
+    );
+
+    let logged: Vec<u64> = (0..7)
+        .filter(|&index| TraceRate::Every(3).logs(index))
+        .collect();
+    assert_eq!(logged, [0, 3, 6]);
+    assert!(!(0..7).any(|index| TraceRate::Every(0).logs(index)));
+
+    assert_eq!(CallNumber(Some(4)).to_string(), " [call 4]");
+    assert_eq!(CallNumber(None).to_string(), "");
+}
@@ -0,0 +1,300 @@
+//! A [`tokio`] runtime for cores that need asynchronous I/O,
+//! e.g. to talk to netplay relays or scraping services.
+//!
+//! The frontend calls into the core from its own thread and expects each frame to
+//! finish in time, so the runtime doesn’t get a thread of its own. Instead
+//! [`retro_run`](crate::retro_run) drives the spawned tasks before every frame until all
+//! of them wait for I/O or timers, for at most [`AsyncRuntime::budget`].
+//! `retro_deinit` drops the runtime along with its tasks.
+//!
+//! # Examples
+//! ```rust,no_run
+//! # use rust_libretro::async_runtime::AsyncRuntime;
+//! # use std::time::Duration;
+//! let runtime = AsyncRuntime::init(Duration::from_millis(2)).unwrap();
+//!
+//! runtime.spawn(async {
+//!     // Talk to the network without blocking the frontend
+//! });
+//! ```
+use once_cell::sync::OnceCell;
+use std::{
+    future::Future,
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    task::{Context, Wake, Waker},
+    time::{Duration, Instant},
+};
+use tokio::{
+    runtime::{Builder, Handle, Runtime},
+    task::JoinHandle,
+};
+
+static RUNTIME: OnceCell<AsyncRuntime> = OnceCell::new();
+
+/// A current-thread [`tokio`] runtime that gets polled once per frame.
+#[derive(Debug)]
+pub struct AsyncRuntime {
+    inner: RwLock<Inner>,
+    budget_us: AtomicU64,
+    active_tasks: Arc<AtomicUsize>,
+    woken_tasks: Arc<AtomicUsize>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// [`None`] once the runtime has been shut down by `retro_deinit`.
+    runtime: Option<Runtime>,
+
+    /// Tasks spawned after the shutdown get cancelled right away.
+    handle: Handle,
+}
+
+impl AsyncRuntime {
+    /// Creates the runtime, which lives until the core gets deinitialized.
+    ///
+    /// Calling this function again only updates the time budget,
+    /// unless the runtime has been shut down in the meantime.
+    pub fn init(budget: Duration) -> Result<&'static AsyncRuntime, Box<dyn std::error::Error>> {
+        let build = || Builder::new_current_thread().enable_all().build();
+
+        let runtime = RUNTIME.get_or_try_init(|| -> Result<_, std::io::Error> {
+            let runtime = build()?;
+
+            Ok(AsyncRuntime {
+                inner: RwLock::new(Inner {
+                    handle: runtime.handle().clone(),
+                    runtime: Some(runtime),
+                }),
+                budget_us: AtomicU64::new(0),
+                active_tasks: Arc::new(AtomicUsize::new(0)),
+                woken_tasks: Arc::new(AtomicUsize::new(0)),
+            })
+        })?;
+
+        {
+            let mut inner = runtime.inner.write().unwrap();
+            if inner.runtime.is_none() {
+                let new_runtime = build()?;
+                inner.handle = new_runtime.handle().clone();
+                inner.runtime = Some(new_runtime);
+            }
+        }
+
+        runtime.set_budget(budget);
+
+        Ok(runtime)
+    }
+
+    /// Returns the runtime if [`AsyncRuntime::init`] has been called.
+    pub fn get() -> Option<&'static AsyncRuntime> {
+        RUNTIME.get()
+    }
+
+    /// The maximum amount of time spent on running tasks per frame.
+    pub fn budget(&self) -> Duration {
+        Duration::from_micros(self.budget_us.load(Ordering::Relaxed))
+    }
+
+    pub fn set_budget(&self, budget: Duration) {
+        self.budget_us
+            .store(budget.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// The number of spawned tasks that have not finished yet.
+    pub fn active_tasks(&self) -> usize {
+        self.active_tasks.load(Ordering::Acquire)
+    }
+
+    /// Spawns a task onto the runtime.
+    ///
+    /// This can be called from any thread and callback.
+    /// The task makes progress whenever the frontend runs a frame.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let guard = TaskGuard::new(Arc::clone(&self.active_tasks));
+        let readiness = Arc::new(Readiness::new(Arc::clone(&self.woken_tasks)));
+
+        self.inner.read().unwrap().handle.spawn(async move {
+            let _guard = guard;
+            let _readiness = ReadinessGuard(Arc::clone(&readiness));
+            let mut future = pin!(future);
+
+            std::future::poll_fn(|cx| {
+                readiness.clear();
+
+                let waker = Waker::from(Arc::new(ReadinessWaker {
+                    readiness: Arc::clone(&readiness),
+                    waker: cx.waker().clone(),
+                }));
+
+                future.as_mut().poll(&mut Context::from_waker(&waker))
+            })
+            .await
+        })
+    }
+
+    /// Runs the spawned tasks until all of them are waiting for I/O or timers,
+    /// or the time budget has been used up.
+    pub(crate) fn poll(&self) {
+        if self.active_tasks() == 0 {
+            return;
+        }
+
+        let inner = self.inner.read().unwrap();
+        let Some(runtime) = &inner.runtime else {
+            return;
+        };
+
+        let deadline = Instant::now() + self.budget();
+
+        runtime.block_on(async {
+            loop {
+                // Lets the scheduler run the woken tasks and poll the I/O and time drivers,
+                // which wakes the tasks whose I/O or timers are ready
+                tokio::task::yield_now().await;
+
+                if self.woken_tasks.load(Ordering::Acquire) == 0 || Instant::now() >= deadline {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Drops all tasks and the runtime, called when the core gets deinitialized.
+    pub(crate) fn shutdown() {
+        let Some(runtime) = RUNTIME.get() else {
+            return;
+        };
+
+        if let Some(runtime) = runtime.inner.write().unwrap().runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Spawns a task onto the [`AsyncRuntime`].
+///
+/// Returns [`None`] if the runtime has not been initialized.
+pub fn spawn<F>(future: F) -> Option<JoinHandle<F::Output>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    AsyncRuntime::get().map(|runtime| runtime.spawn(future))
+}
+
+/// Keeps track of the number of running tasks.
+/// Also counts tasks that get aborted or panic.
+struct TaskGuard(Arc<AtomicUsize>);
+
+impl TaskGuard {
+    fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::AcqRel);
+        Self(counter)
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Whether a task has been woken and waits for the scheduler to poll it,
+/// counted in [`AsyncRuntime::woken_tasks`].
+struct Readiness {
+    woken: AtomicBool,
+    counter: Arc<AtomicUsize>,
+}
+
+impl Readiness {
+    /// Tasks start out woken, as they are waiting for their first poll.
+    fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::AcqRel);
+
+        Self {
+            woken: AtomicBool::new(true),
+            counter,
+        }
+    }
+
+    fn wake(&self) {
+        if !self.woken.swap(true, Ordering::AcqRel) {
+            self.counter.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+
+    fn clear(&self) {
+        if self.woken.swap(false, Ordering::AcqRel) {
+            self.counter.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+/// Stops counting a task once it finished or got dropped.
+struct ReadinessGuard(Arc<Readiness>);
+
+impl Drop for ReadinessGuard {
+    fn drop(&mut self) {
+        // Leaves the flag set, so wakers that outlive the task don’t count it again
+        if self.0.woken.swap(true, Ordering::AcqRel) {
+            self.0.counter.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+/// Marks the task as woken before passing the wake up on to the scheduler.
+struct ReadinessWaker {
+    readiness: Arc<Readiness>,
+    waker: Waker,
+}
+
+impl Wake for ReadinessWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.readiness.wake();
+        self.waker.wake_by_ref();
+    }
+}
+
+#[test]
+fn idle_tasks_return_early() {
+    let runtime = AsyncRuntime::init(Duration::from_secs(5)).unwrap();
+
+    runtime.spawn(std::future::pending::<()>());
+    runtime.spawn(async {
+        for _ in 0..3 {
+            tokio::task::yield_now().await;
+        }
+    });
+
+    let start = Instant::now();
+    runtime.poll();
+
+    // The finished task doesn't count, the pending one doesn't keep polling
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(runtime.active_tasks(), 1);
+    assert_eq!(runtime.woken_tasks.load(Ordering::Acquire), 0);
+
+    AsyncRuntime::shutdown();
+    assert_eq!(runtime.active_tasks(), 0);
+
+    // Tasks spawned after the shutdown get dropped right away
+    runtime.spawn(std::future::pending::<()>());
+    assert_eq!(runtime.active_tasks(), 0);
+
+    AsyncRuntime::init(Duration::from_millis(1)).unwrap();
+    runtime.spawn(std::future::pending::<()>());
+    assert_eq!(runtime.active_tasks(), 1);
+    AsyncRuntime::shutdown();
+}
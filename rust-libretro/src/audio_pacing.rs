@@ -0,0 +1,115 @@
+//! Generating the right amount of audio for every frame.
+//!
+//! At 48000 Hz and 59.94 fps a frame needs 800.8 audio frames, so rounding the same way
+//! every frame slowly drifts away from the sample rate. [`AudioContext::generate_audio`]
+//! carries the fractions over to the next frame instead.
+//!
+//! Once [`GenericContext::enable_audio_buffer_status_callback`] succeeded, it also keeps the
+//! frontend’s audio buffer about half full, by generating up to half a percent more audio
+//! while it is running low or an underrun is likely, and up to half a percent less while
+//! it fills up.
+//!
+//! [`AudioContext::generate_audio`]: crate::contexts::AudioContext::generate_audio
+//! [`GenericContext::enable_audio_buffer_status_callback`]: crate::contexts::GenericContext::enable_audio_buffer_status_callback
+
+/// The last report of the frontend’s audio buffer status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioBufferStatus {
+    /// Whether the frontend plays the audio, `false` e.g. while it is muted or fast-forwarding.
+    pub active: bool,
+
+    /// How full the buffer is, in percent.
+    pub occupancy: u32,
+    pub underrun_likely: bool,
+}
+
+/// Computes how many audio frames to generate per frame, see the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct AudioPacer {
+    /// The fraction of an audio frame left over from the previous frames.
+    remainder: f64,
+    status: Option<AudioBufferStatus>,
+
+    /// Reused by [`AudioContext::generate_audio`](crate::contexts::AudioContext::generate_audio).
+    pub(crate) buffer: Vec<i16>,
+}
+
+impl AudioPacer {
+    /// The occupancy in percent the pacer aims for.
+    const TARGET_OCCUPANCY: f64 = 50.0;
+
+    /// The largest change of the amount of audio, relative to the sample rate.
+    const MAX_ADJUSTMENT: f64 = 0.005;
+
+    pub fn buffer_status(&self) -> Option<AudioBufferStatus> {
+        self.status
+    }
+
+    pub(crate) fn set_buffer_status(&mut self, status: AudioBufferStatus) {
+        self.status = Some(status);
+    }
+
+    /// Returns the number of audio frames to generate for the next frame.
+    pub fn next_frames(&mut self, sample_rate: f64, fps: f64) -> usize {
+        if !(sample_rate > 0.0 && fps > 0.0) {
+            return 0;
+        }
+
+        let mut frames = sample_rate / fps;
+
+        if let Some(status) = self.status.filter(|status| status.active) {
+            let deviation =
+                (Self::TARGET_OCCUPANCY - status.occupancy as f64) / Self::TARGET_OCCUPANCY;
+            let mut adjustment = deviation.clamp(-1.0, 1.0) * Self::MAX_ADJUSTMENT;
+
+            if status.underrun_likely {
+                adjustment = Self::MAX_ADJUSTMENT;
+            }
+
+            frames *= 1.0 + adjustment;
+        }
+
+        let total = frames + self.remainder;
+        let whole = total.floor();
+        self.remainder = total - whole;
+
+        whole as usize
+    }
+}
+
+#[test]
+fn audio_pacer() {
+    let mut pacer = AudioPacer::default();
+    assert_eq!(pacer.next_frames(44100.0, 60.0), 735);
+    assert_eq!(pacer.next_frames(44100.0, 0.0), 0);
+
+    // 800.8 audio frames per frame
+    let frames: usize = (0..5).map(|_| pacer.next_frames(48000.0, 59.94)).sum();
+    assert_eq!(frames, 4004);
+
+    let paced = |occupancy, underrun_likely| {
+        let mut pacer = AudioPacer::default();
+        pacer.set_buffer_status(AudioBufferStatus {
+            active: true,
+            occupancy,
+            underrun_likely,
+        });
+
+        // The adjusted rates aren't exact in binary, so allow for rounding
+        let frames: usize = (0..100).map(|_| pacer.next_frames(48000.0, 60.0)).sum();
+        (frames as f64 / 100.0).round() as usize
+    };
+
+    assert_eq!(paced(0, false), 804);
+    assert_eq!(paced(50, false), 800);
+    assert_eq!(paced(100, false), 796);
+    assert_eq!(paced(90, true), 804);
+
+    let mut pacer = AudioPacer::default();
+    pacer.set_buffer_status(AudioBufferStatus {
+        active: false,
+        occupancy: 100,
+        underrun_likely: false,
+    });
+    assert_eq!(pacer.next_frames(48000.0, 60.0), 800);
+}
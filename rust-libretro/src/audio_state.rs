@@ -0,0 +1,246 @@
+//! Rendering audio in the audio callback on a thread of its own.
+//!
+//! Frontends may call the audio callback on their audio thread while [`Core::on_run`]
+//! is running on the main thread. An [`AudioState`] owns everything the callback needs,
+//! so it never touches the [`Core`]. The core keeps the [`AudioSender`] returned by
+//! [`GenericContext::enable_audio_state`] and passes changes, e.g. of the core options,
+//! as messages, which the audio state receives before it writes the next audio frames:
+//!
+//! ```ignore
+//! struct Tone {
+//!     enabled: bool,
+//!     sine: Sine,
+//! }
+//!
+//! impl AudioState for Tone {
+//!     type Message = bool;
+//!
+//!     fn on_message(&mut self, enabled: bool) {
+//!         self.enabled = enabled;
+//!     }
+//!
+//!     fn on_write_audio(&mut self, ctx: &mut AudioContext) {
+//!         ctx.generate_audio(|samples| {
+//!             if self.enabled {
+//!                 samples.fill_with(|| self.sine.next_sample());
+//!             }
+//!         });
+//!     }
+//! }
+//!
+//! // In `Core::on_load_game`
+//! self.audio = ctx.enable_audio_state(Tone::new(), 16).ok();
+//!
+//! // In `Core::on_options_changed`
+//! if let (Some(audio), Some(enabled)) = (&mut self.audio, ctx.get_bool_variable("tone")) {
+//!     let _ = audio.send(enabled);
+//! }
+//! ```
+//!
+//! [`Core`]: crate::core::Core
+//! [`Core::on_run`]: crate::core::Core::on_run
+//! [`GenericContext::enable_audio_state`]: crate::contexts::GenericContext::enable_audio_state
+use crate::{contexts::AudioContext, core_wrapper::Interfaces, sys::*, util::spsc};
+use std::sync::{Mutex, MutexGuard};
+
+/// The audio rendering part of a core, see the [module documentation](self).
+pub trait AudioState: Send + 'static {
+    /// What the core sends through its [`AudioSender`].
+    type Message: Send + 'static;
+
+    /// Called with every message sent since the last audio callback, before
+    /// [`AudioState::on_write_audio`].
+    fn on_message(&mut self, _message: Self::Message) {
+        // Do nothing
+    }
+
+    /// Called when the frontend needs more audio frames.
+    fn on_write_audio(&mut self, ctx: &mut AudioContext);
+
+    /// Called when the frontend’s audio driver starts or stops, the callback
+    /// only gets called while it is running.
+    fn on_audio_set_state(&mut self, _enabled: bool) {
+        // Do nothing
+    }
+}
+
+/// Sends messages from the [`Core`](crate::core::Core) to its [`AudioState`].
+#[derive(Debug)]
+pub struct AudioSender<M> {
+    producer: spsc::Producer<M>,
+}
+
+impl<M> AudioSender<M> {
+    /// Queues `message` for the next audio callback, returns it back if the queue is full.
+    pub fn send(&mut self, message: M) -> Result<(), M> {
+        self.producer.push(message)
+    }
+
+    /// Whether the [`AudioState`] has been dropped, e.g. because another one got enabled.
+    pub fn is_disconnected(&self) -> bool {
+        self.producer.is_abandoned()
+    }
+}
+
+/// An [`AudioState`] with the receiving end of its messages.
+struct Receiver<S: AudioState> {
+    state: S,
+    messages: spsc::Consumer<S::Message>,
+}
+
+/// Hides the message type of [`Receiver`].
+trait AnyAudioState: Send {
+    fn write_audio(&mut self, ctx: &mut AudioContext);
+    fn set_state(&mut self, enabled: bool);
+}
+
+impl<S: AudioState> AnyAudioState for Receiver<S> {
+    fn write_audio(&mut self, ctx: &mut AudioContext) {
+        for message in self.messages.drain() {
+            self.state.on_message(message);
+        }
+
+        self.state.on_write_audio(ctx);
+    }
+
+    fn set_state(&mut self, enabled: bool) {
+        self.state.on_audio_set_state(enabled);
+    }
+}
+
+/// Everything the audio callbacks need, kept apart from the
+/// [`CoreWrapper`](crate::core_wrapper::CoreWrapper) which belongs to the main thread.
+pub(crate) struct AudioThread {
+    environment_callback: retro_environment_t,
+    interfaces: Option<Interfaces>,
+
+    audio_sample_callback: retro_audio_sample_t,
+    audio_sample_batch_callback: retro_audio_sample_batch_t,
+
+    state: Option<Box<dyn AnyAudioState>>,
+}
+
+static AUDIO_THREAD: Mutex<AudioThread> = Mutex::new(AudioThread {
+    environment_callback: None,
+    interfaces: None,
+    audio_sample_callback: None,
+    audio_sample_batch_callback: None,
+    state: None,
+});
+
+impl AudioThread {
+    pub(crate) fn lock() -> MutexGuard<'static, Self> {
+        // A panicking audio state doesn’t leave the callbacks in an inconsistent state
+        AUDIO_THREAD
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn set_audio_sample_callback(callback: retro_audio_sample_t) {
+        Self::lock().audio_sample_callback = callback;
+    }
+
+    pub(crate) fn set_audio_sample_batch_callback(callback: retro_audio_sample_batch_t) {
+        Self::lock().audio_sample_batch_callback = callback;
+    }
+
+    /// Replaces the audio state, returns the sender for its messages.
+    pub(crate) fn enable<S: AudioState>(
+        state: S,
+        capacity: usize,
+        environment_callback: retro_environment_t,
+        interfaces: &Interfaces,
+    ) -> AudioSender<S::Message> {
+        let (producer, messages) = spsc::channel(capacity);
+
+        let mut thread = Self::lock();
+        thread.environment_callback = environment_callback;
+        thread.interfaces = Some(interfaces.clone());
+        thread.state = Some(Box::new(Receiver { state, messages }));
+
+        AudioSender { producer }
+    }
+
+    /// Drops the audio state, called when the core gets deinitialized.
+    pub(crate) fn disable() {
+        let mut thread = Self::lock();
+        thread.interfaces = None;
+        thread.state = None;
+    }
+
+    /// Runs [`AudioState::on_write_audio`], returns [`false`] if there is no audio state.
+    pub(crate) fn write_audio(&mut self) -> bool {
+        let (Some(interfaces), Some(state)) = (&self.interfaces, &mut self.state) else {
+            return false;
+        };
+
+        let mut ctx = AudioContext {
+            environment_callback: &self.environment_callback,
+            interfaces,
+
+            audio_sample_callback: &self.audio_sample_callback,
+            audio_sample_batch_callback: &self.audio_sample_batch_callback,
+
+            in_audio_callback: true,
+        };
+
+        ctx.flush_queued_audio();
+        state.write_audio(&mut ctx);
+
+        true
+    }
+
+    /// Runs [`AudioState::on_audio_set_state`], returns [`false`] if there is no audio state.
+    pub(crate) fn set_state(&mut self, enabled: bool) -> bool {
+        let (Some(interfaces), Some(state)) = (&self.interfaces, &mut self.state) else {
+            return false;
+        };
+
+        if !enabled {
+            // Nobody is going to pick up audio queued while the frontend is paused
+            interfaces.write().unwrap().queued_audio.clear();
+        }
+
+        state.set_state(enabled);
+
+        true
+    }
+}
+
+#[test]
+fn audio_state_messages() {
+    use std::sync::atomic::*;
+
+    static WRITTEN: AtomicI32 = AtomicI32::new(0);
+
+    struct Volume(i32);
+
+    impl AudioState for Volume {
+        type Message = i32;
+
+        fn on_message(&mut self, volume: i32) {
+            self.0 = volume;
+        }
+
+        fn on_write_audio(&mut self, _ctx: &mut AudioContext) {
+            WRITTEN.store(self.0, Ordering::SeqCst);
+        }
+    }
+
+    let interfaces = Interfaces::default();
+    let mut sender = AudioThread::enable(Volume(0), 2, None, &interfaces);
+
+    assert!(sender.send(5).is_ok());
+    assert!(sender.send(7).is_ok());
+    assert_eq!(sender.send(9), Err(9));
+
+    // The callback runs on another thread and sees the messages in order
+    std::thread::spawn(|| assert!(AudioThread::lock().write_audio()))
+        .join()
+        .unwrap();
+    assert_eq!(WRITTEN.load(Ordering::SeqCst), 7);
+
+    AudioThread::disable();
+    assert!(sender.is_disconnected());
+    assert!(!AudioThread::lock().write_audio());
+}
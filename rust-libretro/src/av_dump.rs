@@ -0,0 +1,230 @@
+//! Dumps the audio and video a core sends to the frontend, to debug AV glitches.
+//!
+//! If the environment variable [`AV_DUMP_VAR`] points at a directory when the frontend
+//! asks for the AV info, every software rendered frame passed to [`RunContext::draw_frame`]
+//! or [`RunContext::draw_framebuffer`] gets appended to `video-<n>.y4m` and the audio
+//! frames to `audio.wav` in that directory:
+//!
+//! ```sh
+//! RUST_LIBRETRO_AV_DUMP=/tmp/dump retroarch -L libcore.so game.rom
+//! ffmpeg -i /tmp/dump/video-0.y4m -i /tmp/dump/audio.wav dump.mkv
+//! ```
+//!
+//! The video is converted to 8 bit YUV 4:4:4, dupes repeat the previous frame.
+//! Y4M can’t change the frame size, so every new size starts a new file.
+//! Hardware rendered frames never reach the CPU and are skipped.
+//! The files use the frame rate and sample rate of the first AV info.
+//!
+//! This module requires the `av-dump` feature.
+//!
+//! [`RunContext::draw_frame`]: crate::contexts::RunContext::draw_frame
+//! [`RunContext::draw_framebuffer`]: crate::contexts::RunContext::draw_framebuffer
+use crate::{sys::retro_system_av_info, types::CapturedFrame};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+/// The environment variable holding the directory to dump into.
+pub const AV_DUMP_VAR: &str = "RUST_LIBRETRO_AV_DUMP";
+
+/// The size of the header written by [`AvDump::write_wav_header`].
+const WAV_HEADER_SIZE: u32 = 44;
+
+#[derive(Debug)]
+struct VideoDump {
+    file: BufWriter<File>,
+    width: u32,
+    height: u32,
+
+    /// The planes of the last frame, written again for dupes.
+    last_frame: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct AudioDump {
+    file: BufWriter<File>,
+    data_size: u32,
+}
+
+/// The files written by the wrapper, see the [module documentation](self).
+#[derive(Debug)]
+pub struct AvDump {
+    directory: PathBuf,
+    fps: f64,
+    sample_rate: u32,
+
+    video: Option<VideoDump>,
+    audio: Option<AudioDump>,
+
+    /// The number of video files started so far.
+    segments: u32,
+}
+
+impl AvDump {
+    /// Returns [`None`] if [`AV_DUMP_VAR`] is not set.
+    pub(crate) fn from_env(av_info: &retro_system_av_info) -> io::Result<Option<Self>> {
+        let directory = match std::env::var_os(AV_DUMP_VAR) {
+            Some(directory) if !directory.is_empty() => PathBuf::from(directory),
+            _ => return Ok(None),
+        };
+
+        std::fs::create_dir_all(&directory)?;
+
+        Ok(Some(Self {
+            directory,
+            fps: av_info.timing.fps,
+            sample_rate: av_info.timing.sample_rate.round() as u32,
+            video: None,
+            audio: None,
+            segments: 0,
+        }))
+    }
+
+    pub(crate) fn write_frame(&mut self, frame: &CapturedFrame) -> io::Result<()> {
+        let rgb = frame
+            .to_rgb8()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+
+        let resized = match &self.video {
+            Some(video) => (video.width, video.height) != (frame.width, frame.height),
+            None => true,
+        };
+
+        if resized {
+            self.start_video(frame.width, frame.height)?;
+        }
+
+        if let Some(video) = self.video.as_mut() {
+            video.last_frame.clear();
+            rgb_to_yuv444(&rgb, &mut video.last_frame);
+
+            video.file.write_all(b"FRAME\n")?;
+            video.file.write_all(&video.last_frame)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn dupe_frame(&mut self) -> io::Result<()> {
+        if let Some(video) = self.video.as_mut() {
+            video.file.write_all(b"FRAME\n")?;
+            video.file.write_all(&video.last_frame)?;
+        }
+
+        Ok(())
+    }
+
+    fn start_video(&mut self, width: u32, height: u32) -> io::Result<()> {
+        let path = self.directory.join(format!("video-{}.y4m", self.segments));
+        self.segments += 1;
+
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(
+            file,
+            "YUV4MPEG2 W{width} H{height} F{}:1000 Ip A1:1 C444",
+            (self.fps * 1000.0).round().max(1.0) as u64
+        )?;
+
+        self.video = Some(VideoDump {
+            file,
+            width,
+            height,
+            last_frame: Vec::new(),
+        });
+
+        Ok(())
+    }
+
+    /// Appends interleaved stereo `samples`.
+    pub(crate) fn write_audio(&mut self, samples: &[i16]) -> io::Result<()> {
+        if self.audio.is_none() {
+            let mut file = BufWriter::new(File::create(self.directory.join("audio.wav"))?);
+            Self::write_wav_header(&mut file, self.sample_rate, 0)?;
+
+            self.audio = Some(AudioDump { file, data_size: 0 });
+        }
+
+        let sample_rate = self.sample_rate;
+        let audio = self.audio.as_mut().unwrap();
+
+        for sample in samples {
+            audio.file.write_all(&sample.to_le_bytes())?;
+        }
+        audio.data_size = audio.data_size.saturating_add(samples.len() as u32 * 2);
+
+        // Keep the header up to date, so the file stays readable if the frontend gets killed
+        audio.file.seek(SeekFrom::Start(0))?;
+        Self::write_wav_header(&mut audio.file, sample_rate, audio.data_size)?;
+        audio.file.seek(SeekFrom::End(0))?;
+
+        Ok(())
+    }
+
+    fn write_wav_header(file: &mut impl Write, sample_rate: u32, data_size: u32) -> io::Result<()> {
+        const CHANNELS: u16 = 2;
+        const BITS_PER_SAMPLE: u16 = 16;
+        const BLOCK_ALIGN: u16 = CHANNELS * BITS_PER_SAMPLE / 8;
+
+        file.write_all(b"RIFF")?;
+        file.write_all(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // PCM
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&CHANNELS.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * BLOCK_ALIGN as u32).to_le_bytes())?;
+        file.write_all(&BLOCK_ALIGN.to_le_bytes())?;
+        file.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&data_size.to_le_bytes())
+    }
+}
+
+/// Runs `write` on the dump, stops dumping if it fails.
+pub(crate) fn with_dump(
+    dump: &mut Option<AvDump>,
+    write: impl FnOnce(&mut AvDump) -> io::Result<()>,
+) {
+    if let Some(av_dump) = dump.as_mut() {
+        if let Err(_err) = write(av_dump) {
+            #[cfg(feature = "log")]
+            log::warn!("Stopped dumping audio and video: {_err}");
+
+            *dump = None;
+        }
+    }
+}
+
+/// Converts packed 8 bit `R, G, B` pixels to the planes of BT.601 limited range YUV 4:4:4.
+fn rgb_to_yuv444(rgb: &[u8], planes: &mut Vec<u8>) {
+    let pixels = rgb.len() / 3;
+    planes.resize(pixels * 3, 0);
+
+    let (luma, chroma) = planes.split_at_mut(pixels);
+    let (cb, cr) = chroma.split_at_mut(pixels);
+
+    for (i, pixel) in rgb.chunks_exact(3).enumerate() {
+        let [r, g, b] = [pixel[0] as i32, pixel[1] as i32, pixel[2] as i32];
+
+        luma[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+        cb[i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+        cr[i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+    }
+}
+
+#[test]
+fn av_dump_formats() {
+    let mut planes = Vec::new();
+    rgb_to_yuv444(&[0, 0, 0, 255, 255, 255, 255, 0, 0], &mut planes);
+    assert_eq!(planes, [16, 235, 82, 128, 128, 90, 128, 128, 240]);
+
+    let mut header = Vec::new();
+    AvDump::write_wav_header(&mut header, 44100, 8).unwrap();
+    assert_eq!(header.len(), WAV_HEADER_SIZE as usize);
+    assert_eq!(&header[4..8], &44u32.to_le_bytes());
+    assert_eq!(&header[28..32], &(44100u32 * 4).to_le_bytes());
+    assert_eq!(&header[40..44], &8u32.to_le_bytes());
+}
@@ -0,0 +1,595 @@
+//! Uniform access to (possibly archived) game content.
+//!
+//! Depending on [`SystemInfo::need_fullpath`] and [`SystemInfo::block_extract`]
+//! the frontend either hands the core the raw content, an already extracted
+//! file or the archive itself. [`ArchiveReader`] hides these differences.
+//!
+//! The multiple entries a subsystem gets loaded with are wrapped by [`SpecialContent`].
+use super::*;
+
+/// Separates the path of an archive from the path of an entry inside of it,
+/// e.g. `/roms/game.zip#game.bin`.
+const ARCHIVE_DELIMITER: char = '#';
+
+/// File extensions the frontend may use in `archive#entry` style paths.
+const ARCHIVE_EXTENSIONS: &[&str] = &[".zip"];
+
+/// Local file header signature of ZIP archives.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// End of central directory signature, found at the start of empty ZIP archives.
+const ZIP_EMPTY_MAGIC: &[u8] = b"PK\x05\x06";
+
+enum ArchiveKind {
+    /// Content that is not archived, exposed as a single entry.
+    Plain { name: String, data: Vec<u8> },
+
+    #[cfg(feature = "zip")]
+    Zip(zip::ZipArchive<std::io::Cursor<Vec<u8>>>),
+}
+
+/// Reads game content regardless of whether it is archived or not.
+///
+/// Plain files are exposed as an archive with a single entry, so cores
+/// only have to deal with [`ArchiveReader::read_entry`].
+///
+/// ZIP archives are only supported if the `zip` feature is enabled.
+pub struct ArchiveReader {
+    kind: ArchiveKind,
+    selected_entry: Option<String>,
+}
+
+impl ArchiveReader {
+    /// Wraps in-memory content.
+    ///
+    /// `name` is used as the entry name for non-archived content.
+    pub fn from_bytes(name: &str, data: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
+        let kind = if is_zip(&data) {
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "zip")] {
+                    ArchiveKind::Zip(zip::ZipArchive::new(std::io::Cursor::new(data))?)
+                } else {
+                    return Err("Reading ZIP archives requires the `zip` feature".into());
+                }
+            }
+        } else {
+            ArchiveKind::Plain {
+                name: name.to_owned(),
+                data,
+            }
+        };
+
+        Ok(Self {
+            kind,
+            selected_entry: None,
+        })
+    }
+
+    /// Opens the file at `path` from the local file system.
+    ///
+    /// Paths of the form `archive.zip#entry` select `entry` as the default entry,
+    /// see [`ArchiveReader::selected_entry`].
+    pub fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (archive_path, entry) = split_archive_path(path);
+
+        let data = std::fs::read(archive_path)?;
+
+        Self::from_bytes(&file_name(archive_path), data).map(|reader| reader.select(entry))
+    }
+
+    /// Opens the file at `path` using the frontend’s VFS interface.
+    ///
+    /// Falls back to [`ArchiveReader::open`] if the VFS interface has not been
+    /// enabled by [`SetEnvironmentContext::enable_vfs_interface`].
+    #[proc::unstable(feature = "env-commands")]
+    pub fn open_vfs(ctx: &GenericContext, path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (archive_path, entry) = split_archive_path(path);
+
+        match read_file_vfs(ctx, archive_path)? {
+            Some(data) => {
+                Self::from_bytes(&file_name(archive_path), data).map(|reader| reader.select(entry))
+            }
+            None => Self::open(path),
+        }
+    }
+
+    /// Creates a reader for the content passed to [`Core::on_load_game`].
+    ///
+    /// If the frontend provided the content in memory, that data gets used.
+    /// Otherwise the content is read from [`retro_game_info::path`],
+    /// going through the VFS interface if it is available.
+    pub fn from_game_info(
+        game: &retro_game_info,
+        ctx: &GenericContext,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = get_str_from_pointer(game.path);
+
+        if !game.data.is_null() {
+            let data =
+                unsafe { std::slice::from_raw_parts(game.data as *const u8, game.size as usize) };
+
+            let (archive_path, entry) = path.map(split_archive_path).unwrap_or(("", None));
+
+            // Frontends that extract `archive#entry` content themselves pass the data of
+            // the entry, along with the path pointing into the archive
+            if !is_zip(data) {
+                let name = entry.map_or_else(|| file_name(archive_path), ToOwned::to_owned);
+
+                return Self::from_bytes(&name, data.to_vec());
+            }
+
+            return Self::from_bytes(&file_name(archive_path), data.to_vec())
+                .map(|reader| reader.select(entry));
+        }
+
+        let path = path.ok_or("The frontend provided neither content data nor a path")?;
+
+        // Falls back to the local file system if the VFS interface is unavailable
+        unsafe { Self::open_vfs(ctx, path) }
+    }
+
+    fn select(mut self, entry: Option<&str>) -> Self {
+        self.selected_entry = entry.map(ToOwned::to_owned);
+        self
+    }
+
+    /// Returns [`true`] if the content is an archive, [`false`] for plain files.
+    pub fn is_archive(&self) -> bool {
+        !matches!(self.kind, ArchiveKind::Plain { .. })
+    }
+
+    /// The entry selected by an `archive#entry` style path, if any.
+    pub fn selected_entry(&self) -> Option<&str> {
+        self.selected_entry.as_deref()
+    }
+
+    /// Lists the names of all files, skipping directory entries.
+    pub fn entries(&self) -> Vec<String> {
+        match &self.kind {
+            ArchiveKind::Plain { name, .. } => vec![name.clone()],
+
+            #[cfg(feature = "zip")]
+            ArchiveKind::Zip(archive) => archive
+                .file_names()
+                .filter(|name| !name.ends_with('/'))
+                .map(ToOwned::to_owned)
+                .collect(),
+        }
+    }
+
+    /// Reads the whole entry called `name`.
+    pub fn read_entry(&mut self, name: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match &mut self.kind {
+            ArchiveKind::Plain {
+                name: entry_name,
+                data,
+            } => {
+                if name == entry_name {
+                    Ok(data.clone())
+                } else {
+                    Err(format!("No such entry: {name}").into())
+                }
+            }
+
+            #[cfg(feature = "zip")]
+            ArchiveKind::Zip(archive) => {
+                use std::io::Read;
+
+                let mut file = archive.by_name(name)?;
+                let mut data = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut data)?;
+
+                Ok(data)
+            }
+        }
+    }
+
+    /// Reads the [selected entry](ArchiveReader::selected_entry),
+    /// or the first entry if none has been selected.
+    pub fn read_default_entry(&mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let name = match &self.selected_entry {
+            Some(name) => name.clone(),
+            None => self
+                .entries()
+                .into_iter()
+                .next()
+                .ok_or("The archive is empty")?,
+        };
+
+        self.read_entry(&name)
+    }
+
+    /// Consumes the reader and returns the raw data of plain content
+    /// without copying it.
+    pub fn into_plain_data(self) -> Option<Vec<u8>> {
+        match self.kind {
+            ArchiveKind::Plain { data, .. } => Some(data),
+
+            #[cfg(feature = "zip")]
+            _ => None,
+        }
+    }
+}
+
+/// A content slot of a subsystem, copied from the [`retro_subsystem_rom_info`]
+/// passed to [`SetEnvironmentContext::set_subsystem_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubsystemRom {
+    /// The description of the slot, e.g. `"Game Boy ROM"`.
+    pub desc: String,
+
+    /// Whether the frontend has to provide content for this slot.
+    pub required: bool,
+}
+
+/// The content passed to [`Core::on_load_game_special`].
+///
+/// Wraps the raw [`retro_game_info`] array, so every entry can be read through an
+/// [`ArchiveReader`]. With [`SpecialContent::with_subsystems`] the entries can also be
+/// looked up by the descriptions of the subsystem’s ROMs, e.g. `"Super Game Boy BIOS"`.
+#[derive(Debug)]
+pub struct SpecialContent<'a> {
+    game_type: std::os::raw::c_uint,
+    games: &'a [retro_game_info],
+    roms: Vec<SubsystemRom>,
+}
+
+impl<'a> SpecialContent<'a> {
+    /// Wraps the arguments of [`Core::on_load_game_special`].
+    ///
+    /// # Safety
+    ///
+    /// `info` must be null or point to `num_info` entries that live for `'a`,
+    /// which is the case for the arguments the frontend passes.
+    pub unsafe fn new(
+        game_type: std::os::raw::c_uint,
+        info: *const retro_game_info,
+        num_info: size_t,
+    ) -> Self {
+        let games = if info.is_null() || num_info == 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(info, num_info as usize)
+        };
+
+        Self {
+            game_type,
+            games,
+            roms: Vec::new(),
+        }
+    }
+
+    /// Looks up the ROMs of the subsystem whose [`retro_subsystem_info::id`]
+    /// matches the game type in `subsystems`, the array that has been passed to
+    /// [`SetEnvironmentContext::set_subsystem_info`].
+    ///
+    /// Fails if the game type is unknown, the frontend passed more entries than the
+    /// subsystem has ROMs, or a required ROM is missing.
+    pub fn with_subsystems(
+        mut self,
+        subsystems: &[retro_subsystem_info],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let subsystem = subsystems
+            .iter()
+            .take_while(|subsystem| !subsystem.ident.is_null())
+            .find(|subsystem| subsystem.id == self.game_type)
+            .ok_or_else(|| format!("Unknown subsystem {}", self.game_type))?;
+
+        let roms = if subsystem.roms.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(subsystem.roms, subsystem.num_roms as usize) }
+        };
+
+        self.roms = roms
+            .iter()
+            .map(|rom| SubsystemRom {
+                desc: get_str_from_pointer(rom.desc)
+                    .unwrap_or_default()
+                    .to_owned(),
+                required: rom.required,
+            })
+            .collect();
+
+        if self.games.len() > self.roms.len() {
+            return Err(format!(
+                "Subsystem {} takes {} ROMs, but got {}",
+                self.game_type,
+                self.roms.len(),
+                self.games.len()
+            )
+            .into());
+        }
+
+        for (index, rom) in self.roms.iter().enumerate() {
+            if rom.required && !self.is_provided(index) {
+                return Err(format!("{} is required", rom.desc).into());
+            }
+        }
+
+        Ok(self)
+    }
+
+    pub fn game_type(&self) -> std::os::raw::c_uint {
+        self.game_type
+    }
+
+    /// The number of entries passed by the frontend.
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    /// The ROMs declared by the subsystem, empty unless [`SpecialContent::with_subsystems`]
+    /// has been used.
+    pub fn roms(&self) -> &[SubsystemRom] {
+        &self.roms
+    }
+
+    pub fn get(&self, index: usize) -> Option<&'a retro_game_info> {
+        self.games.get(index)
+    }
+
+    /// Returns the index of the entry for the ROM described by `desc`.
+    pub fn index_of(&self, desc: &str) -> Option<usize> {
+        self.roms
+            .iter()
+            .take(self.games.len())
+            .position(|rom| rom.desc == desc)
+    }
+
+    /// Returns [`true`] if the frontend provided either data or a path for the entry.
+    pub fn is_provided(&self, index: usize) -> bool {
+        match self.games.get(index) {
+            Some(game) => !game.data.is_null() || !game.path.is_null(),
+            None => false,
+        }
+    }
+
+    /// The path of an entry, if the frontend provided one.
+    pub fn path(&self, index: usize) -> Option<&'a str> {
+        self.games
+            .get(index)
+            .and_then(|game| get_str_from_pointer(game.path))
+    }
+
+    /// Reads an entry, see [`ArchiveReader::from_game_info`].
+    pub fn load(
+        &self,
+        index: usize,
+        ctx: &GenericContext,
+    ) -> Result<ArchiveReader, Box<dyn std::error::Error>> {
+        let game = self
+            .games
+            .get(index)
+            .ok_or_else(|| format!("No content for index {index}"))?;
+
+        ArchiveReader::from_game_info(game, ctx)
+    }
+
+    /// Reads the entry for the ROM described by `desc`.
+    ///
+    /// Returns `Ok(None)` if the frontend did not provide it, which only happens
+    /// for ROMs that are not required.
+    pub fn load_rom(
+        &self,
+        desc: &str,
+        ctx: &GenericContext,
+    ) -> Result<Option<ArchiveReader>, Box<dyn std::error::Error>> {
+        let index = match self.index_of(desc) {
+            Some(index) => index,
+            None if self.roms.iter().any(|rom| rom.desc == desc) => return Ok(None),
+            None => return Err(format!("Unknown subsystem ROM: {desc}").into()),
+        };
+
+        if !self.is_provided(index) {
+            return Ok(None);
+        }
+
+        self.load(index, ctx).map(Some)
+    }
+}
+
+fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(ZIP_MAGIC) || data.starts_with(ZIP_EMPTY_MAGIC)
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Splits `archive.zip#entry` into its archive path and entry name.
+///
+/// Paths that don’t point into an archive are returned unchanged.
+pub fn split_archive_path(path: &str) -> (&str, Option<&str>) {
+    if let Some(index) = path.rfind(ARCHIVE_DELIMITER) {
+        let (archive, entry) = (&path[..index], &path[index + 1..]);
+        let lowercase = archive.to_ascii_lowercase();
+
+        if !entry.is_empty()
+            && ARCHIVE_EXTENSIONS
+                .iter()
+                .any(|extension| lowercase.ends_with(extension))
+        {
+            return (archive, Some(entry));
+        }
+    }
+
+    (path, None)
+}
+
+#[test]
+fn split_archive_paths() {
+    assert_eq!(
+        split_archive_path("/roms/game.bin"),
+        ("/roms/game.bin", None)
+    );
+    assert_eq!(
+        split_archive_path("/roms/game.ZIP#disc 1.bin"),
+        ("/roms/game.ZIP", Some("disc 1.bin"))
+    );
+    assert_eq!(split_archive_path("/roms/#1.bin"), ("/roms/#1.bin", None));
+    assert_eq!(
+        split_archive_path("/roms/game.zip#"),
+        ("/roms/game.zip#", None)
+    );
+}
+
+#[test]
+fn content_from_game_info() {
+    let environment_callback: retro_environment_t = None;
+    let interfaces = core_wrapper::Interfaces::default();
+    let ctx = GenericContext::new(&environment_callback, &interfaces);
+
+    let game = |path: &std::ffi::CStr, data: &[u8]| retro_game_info {
+        path: path.as_ptr(),
+        data: data.as_ptr() as *const _,
+        size: data.len() as _,
+        meta: std::ptr::null(),
+    };
+
+    // Already extracted by the frontend
+    let mut reader =
+        ArchiveReader::from_game_info(&game(c"/roms/game.zip#rom.bin", b"ROM"), &ctx).unwrap();
+    assert!(!reader.is_archive());
+    assert_eq!(reader.entries(), ["rom.bin"]);
+    assert_eq!(reader.read_default_entry().unwrap(), b"ROM");
+
+    let mut reader = ArchiveReader::from_game_info(&game(c"/roms/game.bin", b"ROM"), &ctx).unwrap();
+    assert_eq!(reader.entries(), ["game.bin"]);
+    assert_eq!(reader.read_default_entry().unwrap(), b"ROM");
+
+    // The archive itself, with the entry selected by the path
+    #[cfg(feature = "zip")]
+    {
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, data) in [("other.bin", b"NOPE"), ("rom.bin", b"ROM!")] {
+            writer
+                .start_file(name, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        let archive = writer.finish().unwrap().into_inner();
+
+        let mut reader =
+            ArchiveReader::from_game_info(&game(c"/roms/game.zip#rom.bin", &archive), &ctx)
+                .unwrap();
+        assert!(reader.is_archive());
+        assert_eq!(reader.selected_entry(), Some("rom.bin"));
+        assert_eq!(reader.read_default_entry().unwrap(), b"ROM!");
+    }
+}
+
+#[test]
+fn special_content() {
+    use std::os::raw::c_char;
+
+    let game = |path: *const c_char| retro_game_info {
+        path,
+        data: std::ptr::null(),
+        size: 0,
+        meta: std::ptr::null(),
+    };
+    let rom = |desc: &'static std::ffi::CStr, required| retro_subsystem_rom_info {
+        desc: desc.as_ptr(),
+        valid_extensions: c"gb".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+        required,
+        memory: std::ptr::null(),
+        num_memory: 0,
+    };
+
+    let roms = [rom(c"BIOS", true), rom(c"Cartridge", false)];
+    let subsystems = [
+        retro_subsystem_info {
+            desc: c"Super Game Boy".as_ptr(),
+            ident: c"sgb".as_ptr(),
+            roms: roms.as_ptr(),
+            num_roms: roms.len() as _,
+            id: 1,
+        },
+        retro_subsystem_info {
+            desc: std::ptr::null(),
+            ident: std::ptr::null(),
+            roms: std::ptr::null(),
+            num_roms: 0,
+            id: 0,
+        },
+    ];
+
+    let games = [game(c"/bios.bin".as_ptr()), game(std::ptr::null())];
+    let content = unsafe { SpecialContent::new(1, games.as_ptr(), games.len() as _) }
+        .with_subsystems(&subsystems)
+        .unwrap();
+
+    assert_eq!(content.len(), 2);
+    assert_eq!(content.index_of("BIOS"), Some(0));
+    assert_eq!(content.path(0), Some("/bios.bin"));
+    assert!(!content.is_provided(1));
+    assert!(content.roms()[0].required);
+
+    let missing = [game(std::ptr::null())];
+    let content = unsafe { SpecialContent::new(1, missing.as_ptr(), 1) };
+    assert!(content.with_subsystems(&subsystems).is_err());
+
+    let content = unsafe { SpecialContent::new(2, games.as_ptr(), 2) };
+    assert!(content.with_subsystems(&subsystems).is_err());
+}
+
+/// Reads a whole file through the VFS interface.
+///
+/// Returns `Ok(None)` if the VFS interface is unavailable.
+fn read_file_vfs(
+    ctx: &GenericContext,
+    path: &str,
+) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    let interfaces = ctx.interfaces.read().unwrap();
+
+    let interface = match interfaces.vfs_interface_info.interface {
+        Some(interface) => interface,
+        None => return Ok(None),
+    };
+
+    if let (Some(open), Some(size), Some(read), Some(close)) = (
+        interface.open,
+        interface.size,
+        interface.read,
+        interface.close,
+    ) {
+        let path = CString::new(path)?;
+
+        unsafe {
+            let handle = open(
+                path.as_ptr(),
+                RETRO_VFS_FILE_ACCESS_READ,
+                RETRO_VFS_FILE_ACCESS_HINT_NONE,
+            );
+            if handle.is_null() {
+                return Err(format!("Failed to open {path:?}").into());
+            }
+
+            let length = size(handle);
+            let mut data = vec![0u8; length.max(0) as usize];
+            let read_length = read(handle, data.as_mut_ptr() as *mut _, data.len() as u64);
+
+            close(handle);
+
+            if length < 0 || read_length != length {
+                return Err(format!("Failed to read {path:?}").into());
+            }
+
+            return Ok(Some(data));
+        }
+    }
+
+    Ok(None)
+}
@@ -1,7 +1,9 @@
 //! This module contains abstractions of the libretro environment callbacks.
-use crate::core_wrapper::Interfaces;
+use crate::{
+    core_wrapper::Interfaces,
+    environment::{EnvironmentCallError, EnvironmentCommand},
+};
 use once_cell::unsync::Lazy;
-use std::collections::HashMap;
 
 use super::*;
 
@@ -16,13 +18,13 @@ macro_rules! into_generic {
     ($type:ty, $other:ident, $lifetime:tt) => {
         impl<$lifetime> From<&$type> for $other<$lifetime> {
             fn from(other: &$type) -> $other<$lifetime> {
-                $other::new(other.environment_callback, Arc::clone(&other.interfaces))
+                $other::new(other.environment_callback, other.interfaces)
             }
         }
 
         impl<$lifetime> From<&mut $type> for $other<$lifetime> {
             fn from(other: &mut $type) -> $other<$lifetime> {
-                $other::new(other.environment_callback, Arc::clone(&other.interfaces))
+                $other::new(other.environment_callback, other.interfaces)
             }
         }
     };
@@ -34,11 +36,11 @@ macro_rules! make_context {
         $(#[doc = $doc])?
         pub struct $name<'a> {
             pub(crate) environment_callback: &'a retro_environment_t,
-            pub(crate) interfaces: Interfaces,
+            pub(crate) interfaces: &'a Interfaces,
         }
 
         impl<'a> $name<'a> {
-            pub(crate) fn new(environment_callback: &'a retro_environment_t, interfaces: Interfaces) -> Self {
+            pub(crate) fn new(environment_callback: &'a retro_environment_t, interfaces: &'a Interfaces) -> Self {
                 Self {
                     environment_callback,
                     interfaces
@@ -53,13 +55,13 @@ macro_rules! make_context {
 /// Exposes environment callbacks that are safe to call in every context.
 pub struct GenericContext<'a> {
     pub(crate) environment_callback: &'a retro_environment_t,
-    pub(crate) interfaces: Interfaces,
+    pub(crate) interfaces: &'a Interfaces,
 }
 
 impl<'a> GenericContext<'a> {
     pub(crate) fn new(
         environment_callback: &'a retro_environment_t,
-        interfaces: Interfaces,
+        interfaces: &'a Interfaces,
     ) -> Self {
         Self {
             environment_callback,
@@ -71,26 +73,477 @@ impl<'a> GenericContext<'a> {
         self.environment_callback
     }
 
+    /// Calls the environment callback with a command that has no wrapper yet.
+    ///
+    /// Returns the status returned by the frontend, or an error if the environment
+    /// callback has not been set.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be the type the frontend expects for `command`, e.g. `bool` for
+    /// [`EnvironmentCommand::GetCanDupe`], or a pointer type where libretro expects
+    /// a pointer to one.
+    pub unsafe fn raw_environment_call<T>(
+        &self,
+        command: EnvironmentCommand,
+        data: &mut T,
+    ) -> Result<bool, EnvironmentCallError> {
+        #[cfg(feature = "log")]
+        if command.is_experimental() {
+            log::debug!("Calling experimental environment command {command}");
+        }
+
+        match *self.environment_callback {
+            Some(callback) => {
+                #[cfg(feature = "metrics")]
+                metrics::record_environment_call(command.raw());
+                Ok(callback(command.raw(), data as *mut T as *mut c_void))
+            }
+            None => Err(EnvironmentCallError::NoCallback {
+                command: command.raw(),
+            }),
+        }
+    }
+
     pub unsafe fn interfaces(&self) -> Interfaces {
-        Arc::clone(&self.interfaces)
+        self.interfaces.clone()
+    }
+
+    /// The region matching the frame rate last reported to the frontend, see [`Region::from_fps`].
+    ///
+    /// Follows timing changes made with [`RunContext::try_set_system_av_info`].
+    /// Returns [`None`] before [`Core::on_get_av_info`] has been called.
+    pub fn timing_region(&self) -> Option<Region> {
+        let av_info = self.interfaces.read().unwrap().av_info?;
+
+        Some(Region::from_fps(av_info.timing.fps))
+    }
+
+    /// Displays a message on screen for the given amount of `frames`.
+    ///
+    /// Messages sent through [`environment::set_message`] replace the one currently
+    /// on screen, so messages sent in quick succession are queued and shown one
+    /// after another.
+    ///
+    /// Fails if the frontend failed to display the message.
+    pub fn show_simple_message(&self, text: &str, frames: u32) -> Result<(), EnvironmentCallError> {
+        let mut interfaces = self.interfaces.write().unwrap();
+
+        if let Some((text, frames)) = interfaces.message_queue.push(text, frames) {
+            if let Err(err) = self.set_message(&text, frames) {
+                interfaces.message_queue.clear();
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tells the player why the core has to stop and requests the frontend to shut down.
+    ///
+    /// `error` gets shown as an error notification, using [`GenericContext::set_message_ext`]
+    /// if the frontend supports it. The message and all of its sources get logged.
+    /// Only fails if the shutdown request fails.
+    pub fn shutdown_with_message(
+        &self,
+        error: &dyn std::error::Error,
+    ) -> Result<(), EnvironmentCallError> {
+        #[cfg(feature = "log")]
+        {
+            let mut chain = error.to_string();
+            let mut source = error.source();
+            while let Some(error) = source {
+                chain.push_str(&format!(": {error}"));
+                source = error.source();
+            }
+
+            log::error!("Shutting down: {chain}");
+        }
+
+        let text = error.to_string();
+        let shown = if self.get_message_interface_version() >= 1 {
+            self.set_message_ext(
+                &text,
+                10_000,
+                // Takes precedence over ordinary notifications
+                u32::MAX,
+                retro_log_level::RETRO_LOG_ERROR,
+                retro_message_target::RETRO_MESSAGE_TARGET_ALL,
+                retro_message_type::RETRO_MESSAGE_TYPE_NOTIFICATION,
+                MessageProgress::Indeterminate,
+            )
+        } else {
+            self.set_message(&text, 600)
+        };
+
+        if let Err(_err) = shown {
+            #[cfg(feature = "log")]
+            log::warn!("Failed to show the shutdown reason: {_err}");
+        }
+
+        self.shutdown()
+    }
+
+    /// Returns the username and language set in the frontend.
+    ///
+    /// Unlike [`GenericContext::get_username`] this reports usernames that are not
+    /// valid UTF-8 as an error instead of ignoring them.
+    /// The language falls back to [`Language::English`] if the frontend doesn’t specify one.
+    pub fn get_user_info(&self) -> Result<UserInfo, Box<dyn std::error::Error>> {
+        let ptr: *const c_char = std::ptr::null();
+
+        // const char **
+        let username = match unsafe {
+            environment::get_mut(
+                *self.environment_callback,
+                RETRO_ENVIRONMENT_GET_USERNAME,
+                ptr,
+            )
+        } {
+            Some((ptr, true)) if !ptr.is_null() => {
+                let username = unsafe { CStr::from_ptr(ptr) }.to_str()?;
+                Some(username.to_owned())
+            }
+            _ => None,
+        };
+
+        Ok(UserInfo {
+            username,
+            language: self.get_language().map(Language::from).unwrap_or_default(),
+        })
+    }
+
+    /// Registers the firmware files the core needs, which get checked
+    /// before every game gets loaded, or removes them if `firmware` is [`None`].
+    ///
+    /// See the [`firmware`] module for details.
+    pub fn set_firmware(&self, firmware: Option<firmware::FirmwareSet>) {
+        self.interfaces.write().unwrap().firmware = firmware;
+    }
+
+    /// The path of a firmware file registered with [`GenericContext::set_firmware`],
+    /// by its [`name`](firmware::Firmware::name).
+    ///
+    /// Returns [`None`] for optional files that are unavailable and
+    /// before the check at load time.
+    pub fn get_firmware_path(&self, name: &str) -> Option<PathBuf> {
+        self.interfaces
+            .read()
+            .unwrap()
+            .firmware_paths
+            .get(name)
+            .map(Path::to_path_buf)
+    }
+
+    /// Registers the [`StateMigrations`](savestate::StateMigrations) of the core,
+    /// which adds a version header to every savestate, or removes them if `migrations` is [`None`].
+    ///
+    /// Register them before the frontend asks for the savestate size,
+    /// e.g. in [`Core::on_init`], as it must not grow while a game is loaded.
+    pub fn set_state_migrations(&self, migrations: Option<savestate::StateMigrations>) {
+        self.interfaces.write().unwrap().state_migrations = migrations;
+    }
+
+    /// Registers a [`FrameWatchdog`](timing::FrameWatchdog) that calls [`Core::on_frame_overrun`]
+    /// when frames repeatedly take too long, or removes it if `watchdog` is [`None`].
+    pub fn set_frame_watchdog(&self, watchdog: Option<timing::FrameWatchdog>) {
+        self.interfaces.write().unwrap().frame_watchdog = watchdog;
+    }
+
+    /// Registers a [`SramTracker`](sram::SramTracker) that calls [`Core::on_sram_dirty`]
+    /// when the save RAM changes, or removes it if `tracker` is [`None`].
+    pub fn set_sram_tracker(&self, tracker: Option<sram::SramTracker>) {
+        self.interfaces.write().unwrap().sram_tracker = tracker;
+    }
+
+    /// Registers a resource that lives in the hardware context, see [`hw_resources`].
+    ///
+    /// It gets created right away if the context is currently available.
+    pub fn register_hw_resource<R: hw_resources::HwResource + 'static>(
+        &self,
+        level: hw_resources::HwResourceLevel,
+        resource: R,
+    ) {
+        self.interfaces
+            .write()
+            .unwrap()
+            .hw_resources
+            .register(level, resource);
+    }
+
+    /// Whether the core may use a JIT compiler or has to fall back to an interpreter.
+    ///
+    /// Frontends that don’t know [`RETRO_ENVIRONMENT_GET_JIT_CAPABLE`] are assumed to
+    /// allow JIT compilation, except on iOS and tvOS.
+    pub fn is_jit_capable(&self) -> bool {
+        self.get_jit_capable()
+            .unwrap_or(!cfg!(any(target_os = "ios", target_os = "tvos")))
+    }
+
+    /// Combines the hints the frontend provides into a best-effort guess of its identity,
+    /// for workarounds that only apply to some frontends.
+    ///
+    /// libretro has no way to query the frontend directly, so [`FrontendInfo::kind`] is
+    /// derived from the directories the frontend reports and the interfaces it supports.
+    /// Some frontends only report [`FrontendInfo::core_options_version`] during the
+    /// initial call of [`Core::on_set_environment`].
+    pub fn get_frontend_info(&self) -> FrontendInfo {
+        let paths = [
+            self.get_system_directory(),
+            self.get_save_directory(),
+            self.get_core_assets_directory(),
+        ];
+        let message_interface_version = self.get_message_interface_version();
+
+        FrontendInfo {
+            kind: FrontendKind::guess(paths.into_iter().flatten(), message_interface_version),
+            core_options_version: self.get_core_options_version(),
+            message_interface_version,
+            disk_control_interface_version: self.get_disk_control_interface_version(),
+        }
+    }
+
+    /// Calls `handler` with the value of the core option `key` whenever it changes.
+    ///
+    /// The handler gets called with the current value the next time the options get
+    /// checked, which happens before [`Core::on_load_game`] and each call of [`Core::on_run`],
+    /// and afterwards only if the frontend reports a different value.
+    /// `C` has to be the type of the [`Core`] passed to [`retro_core!`],
+    /// handlers for other types are never called.
+    ///
+    /// ```ignore
+    /// ctx.on_option_change("test_aspect", |value, core: &mut TestCore| {
+    ///     core.aspect_ratio = value.and_then(|value| value.parse().ok()).unwrap_or(4.0 / 3.0);
+    /// });
+    /// ```
+    pub fn on_option_change<C: Core + 'static>(
+        &self,
+        key: &str,
+        handler: impl FnMut(Option<&str>, &mut C) + 'static,
+    ) {
+        self.interfaces
+            .write()
+            .unwrap()
+            .option_handlers
+            .register(key, handler);
+    }
+
+    /// Replaces the values of the core option `key` declared with `#[derive(CoreOptions)]`,
+    /// e.g. to offer the discs of the loaded content. `values` are pairs of value and label.
+    ///
+    /// All options get submitted to the frontend again, using the interface the
+    /// declarations have been registered with. If the declared default value is not
+    /// among `values`, the first one becomes the default.
+    ///
+    /// The frontend shows all options again afterwards. The wrapper reapplies the
+    /// `#[visible_when]` rules before the next frame, options hidden with
+    /// [`GenericContext::set_core_options_display`] have to be hidden again by the core.
+    pub fn set_option_values(
+        &self,
+        key: &str,
+        values: &[(&str, Option<&str>)],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (declared, mut replaced, version) = {
+            let interfaces = self.interfaces.read().unwrap();
+
+            (
+                interfaces.option_definitions,
+                interfaces.option_values.clone(),
+                interfaces.options_version,
+            )
+        };
+
+        if !declared.definitions.iter().any(|option| option.key == key) {
+            return Err(format!("Unknown core option {key}").into());
+        }
+        let version = version.ok_or("The core options have not been registered")?;
+
+        replaced.insert(
+            key.to_owned(),
+            values
+                .iter()
+                .map(|(value, label)| ((*value).to_owned(), label.map(ToOwned::to_owned)))
+                .collect(),
+        );
+        let options = options::RuntimeOptions::new(&declared, &replaced)?;
+
+        let callback = *self.environment_callback;
+        unsafe {
+            match version {
+                n if n >= 2 => {
+                    environment::set_core_options_v2(callback, &options.options_v2()).map(|_| ())
+                }
+                1 => environment::set_core_options(callback, options.definitions()),
+                _ => environment::set_variables(callback, options.variables()),
+            }?;
+        }
+
+        let mut interfaces = self.interfaces.write().unwrap();
+        interfaces.variable_cache.remove(key);
+        interfaces.option_values = replaced;
+        interfaces.runtime_options = Some(options);
+        interfaces.options_resubmitted = true;
+
+        Ok(())
+    }
+
+    /// Like [`GenericContext::get_variable`], but remembers the value until the
+    /// frontend reports changed core options, avoiding repeated calls into the frontend.
+    ///
+    /// Values changed by the core itself via [`GenericContext::set_variable`]
+    /// are only picked up after the next options update.
+    pub fn get_variable_cached(&self, key: &str) -> Option<String> {
+        if let Some(value) = self.interfaces.read().unwrap().variable_cache.get(key) {
+            return value.clone();
+        }
+
+        let value = self.get_variable(key).ok().map(ToOwned::to_owned);
+
+        self.interfaces
+            .write()
+            .unwrap()
+            .variable_cache
+            .insert(key.to_owned(), value.clone());
+
+        value
+    }
+
+    /// Reads a toggle declared with `#[options(bool …)]`, see [`parse_bool_option`].
+    ///
+    /// Returns [`None`] if the option is unknown or its value is not a toggle value.
+    pub fn get_bool_variable(&self, key: &str) -> Option<bool> {
+        self.get_variable(key).ok().and_then(parse_bool_option)
+    }
+
+    /// Exports `function` under the symbol `name` through the
+    /// frontend’s `get_proc_address` interface.
+    ///
+    /// Registered symbols take precedence over [`Core::on_get_proc_address`].
+    /// The wrapper enables the interface in the first call of `retro_set_environment`,
+    /// see [`SetEnvironmentContext::enable_proc_address_interface`].
+    pub fn register_proc(
+        &self,
+        name: &str,
+        function: unsafe extern "C" fn(),
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let name = CString::new(name)?;
+
+        self.interfaces
+            .write()
+            .unwrap()
+            .proc_addresses
+            .insert(name, Some(function));
+
+        Ok(())
+    }
+
+    /// Looks up a function of the hardware context, e.g. `"glFenceSync"`.
+    ///
+    /// Returns [`None`] if the function is unavailable or hardware rendering has
+    /// not been enabled with [`LoadGameContext::enable_hw_render`].
+    pub fn get_hw_proc_address(&self, symbol: &str) -> retro_proc_address_t {
+        let get_proc_address = self
+            .interfaces
+            .read()
+            .unwrap()
+            .hw_render?
+            .get_proc_address?;
+        let symbol = CString::new(symbol).ok()?;
+
+        unsafe { get_proc_address(symbol.as_ptr()) }
+    }
+
+    /// Removes a symbol registered with [`GenericContext::register_proc`].
+    pub fn unregister_proc(&self, name: &str) -> bool {
+        match CString::new(name) {
+            Ok(name) => self
+                .interfaces
+                .write()
+                .unwrap()
+                .proc_addresses
+                .remove(&name)
+                .is_some(),
+            Err(_) => false,
+        }
+    }
+
+    /// The number of input devices (users) provided by the frontend,
+    /// e.g. to size per-port state. Ports at or above this number don’t need to be polled.
+    ///
+    /// Returns [`None`] if the number is unknown, in which case all ports should be considered active.
+    pub fn get_max_users(&self) -> Option<u32> {
+        match self.get_input_max_users() {
+            (users, true) if users > 0 => Some(users),
+            _ => None,
+        }
     }
 
     /// Enables the [`Core::on_keyboard_event`] callback.
-    pub fn enable_keyboard_callback(&self) -> bool {
+    pub fn enable_keyboard_callback(&self) -> Result<(), EnvironmentCallError> {
         self.set_keyboard_callback(retro_keyboard_callback {
             callback: Some(retro_keyboard_callback_fn),
         })
     }
 
+    /// Registers a [`KeyRepeat`](key_repeat::KeyRepeat) that repeats held keys for
+    /// [`Core::on_keyboard_event`], or removes it if `key_repeat` is [`None`].
+    ///
+    /// Requires [`GenericContext::enable_keyboard_callback`].
+    pub fn set_key_repeat(&self, key_repeat: Option<key_repeat::KeyRepeat>) {
+        self.interfaces.write().unwrap().key_repeat = key_repeat;
+    }
+
     /// Enables the [`Core::on_write_audio`] and [`Core::on_audio_set_state`] callbacks.
-    pub fn enable_audio_callback(&self) -> bool {
+    ///
+    /// Once enabled, audio written through an [`AudioContext`] outside of
+    /// [`Core::on_write_audio`], e.g. in [`Core::on_run`], gets queued
+    /// and passed to the frontend at the start of the next audio callback.
+    #[deprecated(
+        note = "the frontend may call `Core::on_write_audio` while `Core::on_run` is running, use `enable_audio_state` instead"
+    )]
+    pub fn enable_audio_callback(&self) -> Result<(), EnvironmentCallError> {
         self.set_audio_callback(retro_audio_callback {
             callback: Some(retro_audio_callback_fn),
             set_state: Some(retro_audio_set_state_callback_fn),
-        })
+        })?;
+
+        self.interfaces.write().unwrap().audio_callback_enabled = true;
+        Ok(())
     }
 
-    pub fn enable_disk_control_interface(&self) -> bool {
+    /// Renders the audio in the audio callback with `state`, which may run on the frontend’s
+    /// audio thread, see [`audio_state`].
+    ///
+    /// Returns the [`AudioSender`](audio_state::AudioSender) that passes up to `capacity`
+    /// messages per audio callback to `state`. Replaces a previously enabled audio state.
+    ///
+    /// Like with [`GenericContext::enable_audio_callback`], audio written outside of the
+    /// audio callback gets queued.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn enable_audio_state<S: audio_state::AudioState>(
+        &self,
+        state: S,
+        capacity: usize,
+    ) -> Result<audio_state::AudioSender<S::Message>, EnvironmentCallError> {
+        self.set_audio_callback(retro_audio_callback {
+            callback: Some(retro_audio_callback_fn),
+            set_state: Some(retro_audio_set_state_callback_fn),
+        })?;
+
+        self.interfaces.write().unwrap().audio_callback_enabled = true;
+
+        Ok(audio_state::AudioThread::enable(
+            state,
+            capacity,
+            *self.environment_callback,
+            self.interfaces,
+        ))
+    }
+
+    pub fn enable_disk_control_interface(&self) -> Result<(), EnvironmentCallError> {
         self.set_disk_control_interface(retro_disk_control_callback {
             set_eject_state: Some(retro_set_eject_state_callback),
             get_eject_state: Some(retro_get_eject_state_callback),
@@ -104,7 +557,7 @@ impl<'a> GenericContext<'a> {
 
     pub fn enable_extended_disk_control_interface(&self) -> Result<(), Box<dyn std::error::Error>> {
         if self.get_disk_control_interface_version() >= 1 {
-            let success = self.set_disk_control_ext_interface(retro_disk_control_ext_callback {
+            self.set_disk_control_ext_interface(retro_disk_control_ext_callback {
                 set_eject_state: Some(retro_set_eject_state_callback),
                 get_eject_state: Some(retro_get_eject_state_callback),
                 get_image_index: Some(retro_get_image_index_callback),
@@ -116,11 +569,7 @@ impl<'a> GenericContext<'a> {
                 set_initial_image: Some(retro_set_initial_image_callback),
                 get_image_path: Some(retro_get_image_path_callback),
                 get_image_label: Some(retro_get_image_label_callback),
-            });
-
-            if !success {
-                return Err("Failed to enable the extended disk control interface.".into());
-            }
+            })?;
         } else {
             return Err("The extended disk control interface is unsupported.".into());
         }
@@ -128,7 +577,7 @@ impl<'a> GenericContext<'a> {
         Ok(())
     }
 
-    pub fn enable_audio_buffer_status_callback(&self) -> bool {
+    pub fn enable_audio_buffer_status_callback(&self) -> Result<(), EnvironmentCallError> {
         let data = retro_audio_buffer_status_callback {
             callback: Some(retro_audio_buffer_status_callback_fn),
         };
@@ -136,6 +585,15 @@ impl<'a> GenericContext<'a> {
         self.set_audio_buffer_status_callback(data)
     }
 
+    /// Whether the camera enabled with [`LoadGameContext::enable_camera_interface`]
+    /// has been initialized and started by the frontend.
+    ///
+    /// [`CameraState::Denied`] explains why a core never receives camera frames.
+    #[proc::unstable(feature = "env-commands")]
+    pub fn camera_state(&self) -> CameraState {
+        self.interfaces.read().unwrap().camera_state
+    }
+
     #[proc::unstable(feature = "env-commands")]
     pub fn set_led_state(&self, led: i32, state: i32) {
         let interfaces = self.interfaces.read().unwrap();
@@ -147,6 +605,34 @@ impl<'a> GenericContext<'a> {
         }
     }
 
+    #[proc::unstable(feature = "env-commands")]
+    pub fn set_sensor_state(&self, port: u32, action: retro_sensor_action, rate: u32) -> bool {
+        let interfaces = self.interfaces.read().unwrap();
+
+        if let Some(interface) = interfaces.sensor_interface {
+            if let Some(set_sensor_state) = interface.set_sensor_state {
+                return unsafe { set_sensor_state(port, action, rate) };
+            }
+        }
+
+        false
+    }
+
+    /// Returns the value of a sensor, e.g. [`RETRO_SENSOR_ACCELEROMETER_X`],
+    /// or `0.0` if the sensor interface is unavailable.
+    #[proc::unstable(feature = "env-commands")]
+    pub fn get_sensor_input(&self, port: u32, id: u32) -> f32 {
+        let interfaces = self.interfaces.read().unwrap();
+
+        if let Some(interface) = interfaces.sensor_interface {
+            if let Some(get_sensor_input) = interface.get_sensor_input {
+                return unsafe { get_sensor_input(port, id) };
+            }
+        }
+
+        0.0
+    }
+
     pub fn set_rumble_state(&self, port: u32, effect: retro_rumble_effect, strength: u16) -> bool {
         let interfaces = self.interfaces.read().unwrap();
 
@@ -159,6 +645,140 @@ impl<'a> GenericContext<'a> {
         false
     }
 
+    /// Requests a rumble effect on behalf of `source`, e.g. `"rumble pak"`.
+    ///
+    /// Unlike [`GenericContext::set_rumble_state`] this does not override the
+    /// requests of other sources, the frontend receives the mix of all of them,
+    /// see [`GenericContext::set_rumble_mix`]. A strength of `0` ends the effect.
+    ///
+    /// Returns [`false`] if the frontend rejected a changed strength.
+    pub fn set_rumble(
+        &self,
+        port: u32,
+        source: &'static str,
+        effect: retro_rumble_effect,
+        strength: u16,
+    ) -> bool {
+        self.interfaces
+            .write()
+            .unwrap()
+            .rumble_mixer
+            .set(port, source, effect, strength);
+
+        self.apply_rumble()
+    }
+
+    /// Ends both rumble effects `source` requested on `port`.
+    pub fn stop_rumble(&self, port: u32, source: &'static str) -> bool {
+        self.interfaces
+            .write()
+            .unwrap()
+            .rumble_mixer
+            .stop(port, source);
+
+        self.apply_rumble()
+    }
+
+    /// Changes how [`GenericContext::set_rumble`] combines the requests of multiple sources.
+    pub fn set_rumble_mix(&self, mix: rumble::RumbleMix) -> bool {
+        self.interfaces.write().unwrap().rumble_mixer.set_mix(mix);
+
+        self.apply_rumble()
+    }
+
+    fn apply_rumble(&self) -> bool {
+        let changes = self.interfaces.write().unwrap().rumble_mixer.take_changes();
+
+        changes
+            .into_iter()
+            .fold(true, |accepted, (port, effect, strength)| {
+                self.set_rumble_state(port, effect, strength) && accepted
+            })
+    }
+
+    /// Asks the player to enter up to `max_len` characters, see [`text_input`].
+    ///
+    /// `callback` receives the text once it has been confirmed, or [`None`] if the request
+    /// has been cancelled. Like with [`GenericContext::on_option_change`], `C` has to be
+    /// the type of the [`Core`] passed to [`retro_core!`].
+    /// Returns `false` without doing anything if another request is still active.
+    ///
+    /// ```ignore
+    /// ctx.request_text_input("NAME", 8, |name, core: &mut TestCore| {
+    ///     if let Some(name) = name {
+    ///         core.high_scores.insert(name, core.score);
+    ///     }
+    /// });
+    /// ```
+    pub fn request_text_input<C: Core + 'static>(
+        &self,
+        prompt: &str,
+        max_len: usize,
+        callback: impl FnOnce(Option<String>, &mut C) + 'static,
+    ) -> bool {
+        let mut interfaces = self.interfaces.write().unwrap();
+
+        if interfaces.text_input.is_some() {
+            return false;
+        }
+
+        interfaces.text_input = Some(text_input::TextInputRequest::new(
+            text_input::TextInput::new(prompt, max_len),
+            callback,
+        ));
+
+        true
+    }
+
+    /// Replaces the input descriptors of `port` and submits them merged with those
+    /// of the other ports, see [`input_descriptor_set`].
+    ///
+    /// `descriptors` end at the first entry with a `NULL` or empty description, so the
+    /// output of [`input_descriptors!`] can be passed as it is.
+    /// Returns `Ok(false)` if the merged table did not change and has not been submitted.
+    ///
+    /// Don’t mix this with [`GenericContext::set_input_descriptors`], which replaces
+    /// the table without the wrapper knowing about it.
+    pub fn update_input_descriptors(
+        &self,
+        port: u32,
+        descriptors: &[retro_input_descriptor],
+    ) -> Result<bool, EnvironmentCallError> {
+        let descriptors = descriptors
+            .iter()
+            .map_while(input_descriptor_set::InputDescriptor::from_raw)
+            .collect();
+
+        let submitted = self
+            .interfaces
+            .write()
+            .unwrap()
+            .input_descriptors
+            .update(port, descriptors);
+
+        let submitted = match submitted {
+            Some(submitted) => submitted,
+            None => return Ok(false),
+        };
+
+        unsafe {
+            environment::set_input_descriptors(*self.environment_callback, submitted.as_raw())?;
+        }
+
+        self.interfaces
+            .write()
+            .unwrap()
+            .input_descriptors
+            .set_submitted(submitted);
+
+        Ok(true)
+    }
+
+    /// Whether a [`GenericContext::request_text_input`] request is waiting for input.
+    pub fn text_input_active(&self) -> bool {
+        self.interfaces.read().unwrap().text_input.is_some()
+    }
+
     pub fn start_perf_counter(&mut self, name: &'static str) {
         let mut interfaces = self.interfaces.write().unwrap();
 
@@ -173,7 +793,7 @@ impl<'a> GenericContext<'a> {
                             let ident = CString::new(name).unwrap();
                             let ptr = ident.as_ptr();
 
-                            PerfCounter {
+                            Box::new(PerfCounter {
                                 ident,
                                 counter: retro_perf_counter {
                                     ident: ptr,
@@ -182,7 +802,7 @@ impl<'a> GenericContext<'a> {
                                     call_cnt: 0,
                                     registered: false,
                                 },
-                            }
+                            })
                         });
 
                     if !counter.counter.registered {
@@ -395,21 +1015,34 @@ impl<'a> GenericContext<'a> {
         false
     }
 
+    /// Summarizes which VFS version and functions the frontend provides.
+    ///
+    /// Everything is reported as unavailable until [`SetEnvironmentContext::enable_vfs_interface`]
+    /// succeeded.
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_get_path(&self, handle: &mut retro_vfs_file_handle) -> Option<CString> {
-        let interfaces = self.interfaces.read().unwrap();
+    pub fn vfs_capabilities(&self) -> VfsCapabilities {
+        self.interfaces
+            .read()
+            .unwrap()
+            .vfs_interface_info
+            .capabilities()
+    }
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(get_path) = interface.get_path {
-                let ptr = unsafe { get_path(handle) };
-                if !ptr.is_null() {
-                    let path = CStr::from_ptr(ptr).to_owned();
-                    return Some(path);
-                }
-            }
+    #[proc::unstable(feature = "env-commands")]
+    pub fn vfs_get_path(&self, handle: &mut retro_vfs_file_handle) -> Result<CString, VfsError> {
+        let interfaces = self.interfaces.read().unwrap();
+        let get_path = interfaces
+            .vfs_interface_info
+            .function("get_path", 1, |vfs| vfs.get_path)?;
+
+        let ptr = unsafe { get_path(handle) };
+        if ptr.is_null() {
+            return Err(VfsError::Failed {
+                function: "get_path",
+            });
         }
 
-        None
+        Ok(unsafe { CStr::from_ptr(ptr) }.to_owned())
     }
 
     #[proc::unstable(feature = "env-commands")]
@@ -418,58 +1051,42 @@ impl<'a> GenericContext<'a> {
         path: &str,
         mode: VfsFileOpenFlags,
         hints: VfsFileOpenHints,
-    ) -> Result<retro_vfs_file_handle, Box<dyn std::error::Error>> {
+    ) -> Result<retro_vfs_file_handle, VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let open = interfaces
+            .vfs_interface_info
+            .function("open", 1, |vfs| vfs.open)?;
+        let path = CString::new(path).map_err(|_| VfsError::InvalidPath)?;
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(open) = interface.open {
-                let path = CString::new(path)?;
-
-                let handle = unsafe { open(path.as_ptr(), mode.bits(), hints.bits()) };
-                if !handle.is_null() {
-                    return Ok(*handle);
-                }
-            }
+        let handle = unsafe { open(path.as_ptr(), mode.bits(), hints.bits()) };
+        if handle.is_null() {
+            return Err(VfsError::Failed { function: "open" });
         }
 
-        Err("Failed to open file".into())
+        Ok(unsafe { *handle })
     }
 
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_close(
-        &self,
-        mut handle: retro_vfs_file_handle,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn vfs_close(&self, mut handle: retro_vfs_file_handle) -> Result<(), VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let close = interfaces
+            .vfs_interface_info
+            .function("close", 1, |vfs| vfs.close)?;
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(close) = interface.close {
-                if unsafe { close(&mut handle) } == 0 {
-                    return Ok(());
-                }
-            }
+        match unsafe { close(&mut handle) } {
+            0 => Ok(()),
+            _ => Err(VfsError::Failed { function: "close" }),
         }
-
-        Err("Failed to close file".into())
     }
 
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_size(
-        &self,
-        handle: &mut retro_vfs_file_handle,
-    ) -> Result<u64, Box<dyn std::error::Error>> {
+    pub fn vfs_size(&self, handle: &mut retro_vfs_file_handle) -> Result<u64, VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let size = interfaces
+            .vfs_interface_info
+            .function("size", 1, |vfs| vfs.size)?;
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(size) = interface.size {
-                let size = unsafe { size(handle) };
-                if size >= 0 {
-                    return Ok(size as u64);
-                }
-            }
-        }
-
-        Err("Failed to get file size".into())
+        u64::try_from(unsafe { size(handle) }).map_err(|_| VfsError::Failed { function: "size" })
     }
 
     #[proc::unstable(feature = "env-commands")]
@@ -477,45 +1094,28 @@ impl<'a> GenericContext<'a> {
         &self,
         handle: &mut retro_vfs_file_handle,
         length: i64, // no idea why the API wants signed values
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let truncate = interfaces
+            .vfs_interface_info
+            .function("truncate", 2, |vfs| vfs.truncate)?;
 
-        if interfaces.vfs_interface_info.supported_version < 2 {
-            return Err(format!(
-                "VFS interface version 2 required, but the frontend only supports version {}",
-                interfaces.vfs_interface_info.supported_version
-            )
-            .into());
-        }
-
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(truncate) = interface.truncate {
-                if unsafe { truncate(handle, length) } == 0 {
-                    return Ok(());
-                }
-            }
+        match unsafe { truncate(handle, length) } {
+            0 => Ok(()),
+            _ => Err(VfsError::Failed {
+                function: "truncate",
+            }),
         }
-
-        Err("Failed to truncate file".into())
     }
 
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_tell(
-        &self,
-        handle: &mut retro_vfs_file_handle,
-    ) -> Result<u64, Box<dyn std::error::Error>> {
+    pub fn vfs_tell(&self, handle: &mut retro_vfs_file_handle) -> Result<u64, VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let tell = interfaces
+            .vfs_interface_info
+            .function("tell", 1, |vfs| vfs.tell)?;
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(tell) = interface.tell {
-                let position = unsafe { tell(handle) };
-                if position >= 0 {
-                    return Ok(position as u64);
-                }
-            }
-        }
-
-        Err("Failed to get cursor position".into())
+        u64::try_from(unsafe { tell(handle) }).map_err(|_| VfsError::Failed { function: "tell" })
     }
 
     #[proc::unstable(feature = "env-commands")]
@@ -524,42 +1124,36 @@ impl<'a> GenericContext<'a> {
         handle: &mut retro_vfs_file_handle,
         offset: i64,
         seek_position: VfsSeekPosition,
-    ) -> Result<u64, Box<dyn std::error::Error>> {
+    ) -> Result<u64, VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let seek = interfaces
+            .vfs_interface_info
+            .function("seek", 1, |vfs| vfs.seek)?;
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(seek) = interface.seek {
-                let position = unsafe { seek(handle, offset, seek_position as i32) };
-                if position >= 0 {
-                    return Ok(position as u64);
-                }
-            }
-        }
-
-        Err("Failed to seek into file".into())
+        u64::try_from(unsafe { seek(handle, offset, seek_position as i32) })
+            .map_err(|_| VfsError::Failed { function: "seek" })
     }
 
+    /// Reads up to `length` bytes, the returned buffer is shorter if the end of the file has been reached.
     #[proc::unstable(feature = "env-commands")]
     pub fn vfs_read(
         &self,
         handle: &mut retro_vfs_file_handle,
         length: usize,
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<u8>, VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let read = interfaces
+            .vfs_interface_info
+            .function("read", 1, |vfs| vfs.read)?;
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(read) = interface.read {
-                let mut buffer = Vec::with_capacity(length);
+        let mut buffer = vec![0u8; length];
+        let read_length = unsafe { read(handle, buffer.as_mut_ptr() as *mut _, length as u64) };
 
-                let read_length =
-                    unsafe { read(handle, buffer.as_mut_ptr() as *mut _, length as u64) };
-                if read_length >= 0 {
-                    return Ok(buffer);
-                }
-            }
-        }
+        let read_length =
+            usize::try_from(read_length).map_err(|_| VfsError::Failed { function: "read" })?;
+        buffer.truncate(read_length);
 
-        Err("Failed to read from file".into())
+        Ok(buffer)
     }
 
     #[proc::unstable(feature = "env-commands")]
@@ -567,139 +1161,95 @@ impl<'a> GenericContext<'a> {
         &self,
         handle: &mut retro_vfs_file_handle,
         buffer: &mut [u8],
-    ) -> Result<u64, Box<dyn std::error::Error>> {
+    ) -> Result<u64, VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let write = interfaces
+            .vfs_interface_info
+            .function("write", 1, |vfs| vfs.write)?;
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(write) = interface.write {
-                let bytes_written =
-                    unsafe { write(handle, buffer.as_mut_ptr() as *mut _, buffer.len() as u64) };
-                if bytes_written >= 0 {
-                    return Ok(bytes_written as u64);
-                }
-            }
-        }
+        let bytes_written =
+            unsafe { write(handle, buffer.as_mut_ptr() as *mut _, buffer.len() as u64) };
 
-        Err("Failed to write to file".into())
+        u64::try_from(bytes_written).map_err(|_| VfsError::Failed { function: "write" })
     }
 
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_flush(
-        &self,
-        handle: &mut retro_vfs_file_handle,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn vfs_flush(&self, handle: &mut retro_vfs_file_handle) -> Result<(), VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let flush = interfaces
+            .vfs_interface_info
+            .function("flush", 1, |vfs| vfs.flush)?;
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(flush) = interface.flush {
-                if unsafe { flush(handle) } == 0 {
-                    return Ok(());
-                }
-            }
+        match unsafe { flush(handle) } {
+            0 => Ok(()),
+            _ => Err(VfsError::Failed { function: "flush" }),
         }
-
-        Err("Failed to flush file".into())
     }
 
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_remove(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn vfs_remove(&self, path: &str) -> Result<(), VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let remove = interfaces
+            .vfs_interface_info
+            .function("remove", 1, |vfs| vfs.remove)?;
+        let path = CString::new(path).map_err(|_| VfsError::InvalidPath)?;
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(remove) = interface.remove {
-                let path = CString::new(path)?;
-
-                if unsafe { remove(path.as_ptr()) } == 0 {
-                    return Ok(());
-                }
-            }
+        match unsafe { remove(path.as_ptr()) } {
+            0 => Ok(()),
+            _ => Err(VfsError::Failed { function: "remove" }),
         }
+    }
+
+    #[proc::unstable(feature = "env-commands")]
+    pub fn vfs_rename(&self, old_path: &str, new_path: &str) -> Result<(), VfsError> {
+        let interfaces = self.interfaces.read().unwrap();
+        let rename = interfaces
+            .vfs_interface_info
+            .function("rename", 1, |vfs| vfs.rename)?;
+        let old_path = CString::new(old_path).map_err(|_| VfsError::InvalidPath)?;
+        let new_path = CString::new(new_path).map_err(|_| VfsError::InvalidPath)?;
 
-        Err("Failed to remove file".into())
+        match unsafe { rename(old_path.as_ptr(), new_path.as_ptr()) } {
+            0 => Ok(()),
+            _ => Err(VfsError::Failed { function: "rename" }),
+        }
     }
 
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_rename(
-        &self,
-        old_path: &str,
-        new_path: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn vfs_stat(&self, path: &str) -> Result<(VfsStat, u64), VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let stat = interfaces
+            .vfs_interface_info
+            .function("stat", 3, |vfs| vfs.stat)?;
+        let path = CString::new(path).map_err(|_| VfsError::InvalidPath)?;
 
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(rename) = interface.rename {
-                let old_path = CString::new(old_path)?;
-                let new_path = CString::new(new_path)?;
+        let (stat, size) = unsafe {
+            let mut size = 0i32;
+            let value = stat(path.as_ptr(), &mut size);
 
-                if unsafe { rename(old_path.as_ptr(), new_path.as_ptr()) } == 0 {
-                    return Ok(());
-                }
-            }
+            (VfsStat::from_bits_unchecked(value), size)
+        };
+
+        if stat.is_empty() || size < 0 {
+            return Err(VfsError::Failed { function: "stat" });
         }
 
-        Err("Failed to rename file".into())
+        Ok((stat, size as u64))
     }
 
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_stat(&self, path: &str) -> Result<(VfsStat, u64), Box<dyn std::error::Error>> {
+    pub fn vfs_mkdir(&self, dir: &str) -> Result<(), VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let mkdir = interfaces
+            .vfs_interface_info
+            .function("mkdir", 3, |vfs| vfs.mkdir)?;
+        let dir = CString::new(dir).map_err(|_| VfsError::InvalidPath)?;
 
-        if interfaces.vfs_interface_info.supported_version < 3 {
-            return Err(format!(
-                "VFS interface version 3 required, but the frontend only supports version {}",
-                interfaces.vfs_interface_info.supported_version
-            )
-            .into());
-        }
-
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(stat) = interface.stat {
-                let path = CString::new(path)?;
-                let (stat, size) = unsafe {
-                    let mut size = 0i32;
-                    let value = stat(path.as_ptr(), &mut size);
-
-                    (VfsStat::from_bits_unchecked(value), size)
-                };
-
-                if stat.is_empty() {
-                    return Err(format!("Invalid stat bitmask: {stat:#?}").into());
-                } else if size < 0 {
-                    return Err(format!("Invalid file size: {size}").into());
-                }
-
-                return Ok((stat, size as u64));
-            }
-        }
-
-        Err("Failed to stat file".into())
-    }
-
-    #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_mkdir(&self, dir: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let interfaces = self.interfaces.read().unwrap();
-
-        if interfaces.vfs_interface_info.supported_version < 3 {
-            return Err(format!(
-                "VFS interface version 3 required, but the frontend only supports version {}",
-                interfaces.vfs_interface_info.supported_version
-            )
-            .into());
+        match unsafe { mkdir(dir.as_ptr()) } {
+            0 => Ok(()),
+            -2 => Err(VfsError::Exists),
+            _ => Err(VfsError::Failed { function: "mkdir" }),
         }
-
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(mkdir) = interface.mkdir {
-                let dir = CString::new(dir)?;
-
-                match unsafe { mkdir(dir.as_ptr()) } {
-                    0 => return Ok(()),
-                    -2 => return Err("Failed to create directory: Exists already".into()),
-                    _ => (),
-                }
-            }
-        }
-
-        Err("Failed to create directory".into())
     }
 
     #[proc::unstable(feature = "env-commands")]
@@ -707,133 +1257,83 @@ impl<'a> GenericContext<'a> {
         &self,
         dir: &str,
         include_hidden: bool,
-    ) -> Result<retro_vfs_dir_handle, Box<dyn std::error::Error>> {
+    ) -> Result<retro_vfs_dir_handle, VfsError> {
         let interfaces = self.interfaces.read().unwrap();
-
-        if interfaces.vfs_interface_info.supported_version < 3 {
-            return Err(format!(
-                "VFS interface version 3 required, but the frontend only supports version {}",
-                interfaces.vfs_interface_info.supported_version
-            )
-            .into());
-        }
-
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(opendir) = interface.opendir {
-                let dir = CString::new(dir)?;
-
-                let handle = unsafe { opendir(dir.as_ptr(), include_hidden) };
-                if !handle.is_null() {
-                    return Ok(*handle);
-                }
-            }
+        let opendir = interfaces
+            .vfs_interface_info
+            .function("opendir", 3, |vfs| vfs.opendir)?;
+        let dir = CString::new(dir).map_err(|_| VfsError::InvalidPath)?;
+
+        let handle = unsafe { opendir(dir.as_ptr(), include_hidden) };
+        if handle.is_null() {
+            return Err(VfsError::Failed {
+                function: "opendir",
+            });
         }
 
-        Err("Failed to open directory".into())
+        Ok(unsafe { *handle })
     }
 
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_readdir(
-        &self,
-        handle: &mut retro_vfs_dir_handle,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn vfs_readdir(&self, handle: &mut retro_vfs_dir_handle) -> Result<(), VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let readdir = interfaces
+            .vfs_interface_info
+            .function("readdir", 3, |vfs| vfs.readdir)?;
 
-        if interfaces.vfs_interface_info.supported_version < 3 {
-            return Err(format!(
-                "VFS interface version 3 required, but the frontend only supports version {}",
-                interfaces.vfs_interface_info.supported_version
-            )
-            .into());
-        }
-
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(readdir) = interface.readdir {
-                if unsafe { readdir(handle) } {
-                    return Ok(());
-                }
-            }
+        if unsafe { readdir(handle) } {
+            Ok(())
+        } else {
+            Err(VfsError::Failed {
+                function: "readdir",
+            })
         }
-
-        Err("Failed to read directory".into())
     }
 
     #[proc::unstable(feature = "env-commands")]
     pub fn vfs_dirent_get_name(
         &self,
         handle: &mut retro_vfs_dir_handle,
-    ) -> Result<CString, Box<dyn std::error::Error>> {
+    ) -> Result<CString, VfsError> {
         let interfaces = self.interfaces.read().unwrap();
-
-        if interfaces.vfs_interface_info.supported_version < 3 {
-            return Err(format!(
-                "VFS interface version 3 required, but the frontend only supports version {}",
-                interfaces.vfs_interface_info.supported_version
-            )
-            .into());
-        }
-
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(dirent_get_name) = interface.dirent_get_name {
-                let ptr = unsafe { dirent_get_name(handle) };
-                if !ptr.is_null() {
-                    let name = CStr::from_ptr(ptr).to_owned();
-                    return Ok(name);
-                }
-            }
+        let dirent_get_name =
+            interfaces
+                .vfs_interface_info
+                .function("dirent_get_name", 3, |vfs| vfs.dirent_get_name)?;
+
+        let ptr = unsafe { dirent_get_name(handle) };
+        if ptr.is_null() {
+            return Err(VfsError::Failed {
+                function: "dirent_get_name",
+            });
         }
 
-        Err("Failed to get entry name".into())
+        Ok(unsafe { CStr::from_ptr(ptr) }.to_owned())
     }
 
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_dirent_is_dir(
-        &self,
-        handle: &mut retro_vfs_dir_handle,
-    ) -> Result<bool, Box<dyn std::error::Error>> {
+    pub fn vfs_dirent_is_dir(&self, handle: &mut retro_vfs_dir_handle) -> Result<bool, VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let dirent_is_dir = interfaces
+            .vfs_interface_info
+            .function("dirent_is_dir", 3, |vfs| vfs.dirent_is_dir)?;
 
-        if interfaces.vfs_interface_info.supported_version < 3 {
-            return Err(format!(
-                "VFS interface version 3 required, but the frontend only supports version {}",
-                interfaces.vfs_interface_info.supported_version
-            )
-            .into());
-        }
-
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(dirent_is_dir) = interface.dirent_is_dir {
-                return Ok(unsafe { dirent_is_dir(handle) });
-            }
-        }
-
-        Err("Failed to check if the entry is a directory".into())
+        Ok(unsafe { dirent_is_dir(handle) })
     }
 
     #[proc::unstable(feature = "env-commands")]
-    pub fn vfs_closedir(
-        &self,
-        mut handle: retro_vfs_dir_handle,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn vfs_closedir(&self, mut handle: retro_vfs_dir_handle) -> Result<(), VfsError> {
         let interfaces = self.interfaces.read().unwrap();
+        let closedir = interfaces
+            .vfs_interface_info
+            .function("closedir", 3, |vfs| vfs.closedir)?;
 
-        if interfaces.vfs_interface_info.supported_version < 3 {
-            return Err(format!(
-                "VFS interface version 3 required, but the frontend only supports version {}",
-                interfaces.vfs_interface_info.supported_version
-            )
-            .into());
-        }
-
-        if let Some(interface) = interfaces.vfs_interface_info.interface {
-            if let Some(closedir) = interface.closedir {
-                if unsafe { closedir(&mut handle) } == 0 {
-                    return Ok(());
-                }
-            }
+        match unsafe { closedir(&mut handle) } {
+            0 => Ok(()),
+            _ => Err(VfsError::Failed {
+                function: "closedir",
+            }),
         }
-
-        Err("Failed to close directory".into())
     }
 }
 
@@ -871,22 +1371,109 @@ pub type GetMemoryDataContext<'a> = GenericContext<'a>;
 pub type GetMemorySizeContext<'a> = GenericContext<'a>;
 
 make_context!(GetAvInfoContext, #[doc = "Functions that are safe to be called in [`Core::on_get_av_info`]"]);
+
+impl GetAvInfoContext<'_> {
+    /// See [`LoadGameContext::set_pixel_format`].
+    pub fn set_pixel_format<F: Into<retro_pixel_format>>(
+        &self,
+        format: F,
+    ) -> Result<(), EnvironmentCallError> {
+        set_pixel_format(*self.environment_callback, self.interfaces, format.into())
+    }
+
+    /// See [`GenericContext::get_target_refresh_rate`].
+    #[proc::unstable(feature = "env-commands")]
+    pub fn get_target_refresh_rate(&self) -> Result<f32, EnvironmentCallError> {
+        GenericContext::from(self).get_target_refresh_rate()
+    }
+
+    /// Picks the frame rate out of `candidates` that fits the frontend’s target
+    /// refresh rate best, see [`util::choose_fps`].
+    ///
+    /// Returns the first candidate if the frontend doesn’t report a refresh rate.
+    #[proc::unstable(feature = "env-commands")]
+    pub fn choose_fps(&self, candidates: &[f64]) -> Option<f64> {
+        let refresh_rate = self.get_target_refresh_rate().map_or(0.0, f64::from);
+
+        util::choose_fps(refresh_rate, candidates)
+    }
+}
+
 make_context!(InitContext, #[doc = "Functions that are safe to be called in [`Core::on_init`]"]);
 make_context!(OptionsChangedContext, #[doc = "Functions that are safe to be called in [`Core::on_options_changed`]"]);
 
+impl OptionsChangedContext<'_> {
+    /// See [`GenericContext::get_variable_cached`].
+    pub fn get_variable_cached(&self, key: &str) -> Option<String> {
+        GenericContext::from(self).get_variable_cached(key)
+    }
+
+    /// See [`GenericContext::get_bool_variable`].
+    pub fn get_bool_variable(&self, key: &str) -> Option<bool> {
+        GenericContext::from(self).get_bool_variable(key)
+    }
+
+    /// See [`GenericContext::set_option_values`].
+    pub fn set_option_values(
+        &self,
+        key: &str,
+        values: &[(&str, Option<&str>)],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        GenericContext::from(self).set_option_values(key, values)
+    }
+}
+
 make_context!(LoadGameSpecialContext, #[doc = "Functions that are safe to be called in [`Core::on_load_game_special`]"]);
 into_generic!(LoadGameSpecialContext<'a>, LoadGameContext, 'a);
 
-make_context!(SetEnvironmentContext, #[doc = "Functions that are safe to be called in [`Core::on_set_environment`]"]);
+make_context!(SetEnvironmentContext, #[doc = "Functions that are safe to be called in [`Core::on_set_environment`] and [`Core::on_before_set_core_options`]"]);
 
 impl<'a> SetEnvironmentContext<'a> {
-    pub fn enable_proc_address_interface(&mut self) -> bool {
+    /// Changes the interface that `#[derive(CoreOptions)]` registers the core options with.
+    ///
+    /// The core options get registered before the initial call of [`Core::on_set_environment`],
+    /// so the mode has to be set in [`Core::on_before_set_core_options`].
+    pub fn set_options_compat_mode(&self, mode: OptionsCompat) {
+        self.interfaces.write().unwrap().options_compat = mode;
+    }
+
+    pub fn options_compat_mode(&self) -> OptionsCompat {
+        self.interfaces.read().unwrap().options_compat
+    }
+
+    /// The core options version reported by the frontend, limited by the
+    /// [`OptionsCompat`] mode.
+    pub fn core_options_version(&self) -> u32 {
+        let version = GenericContext::from(self).get_core_options_version();
+
+        let version = match self.options_compat_mode() {
+            OptionsCompat::Auto => version,
+            OptionsCompat::ForceV1 => version.min(1),
+            OptionsCompat::ForceVariables => 0,
+        };
+
+        // Later calls of `retro_set_environment` don’t get an answer anymore
+        self.interfaces.write().unwrap().options_version = Some(version);
+
+        version
+    }
+
+    pub fn enable_proc_address_interface(&mut self) -> Result<(), EnvironmentCallError> {
         self.set_proc_address_callback(retro_get_proc_address_interface {
             get_proc_address: Some(retro_get_proc_address_callback),
         })
     }
 
-    pub fn enable_options_update_display_callback(&mut self) -> bool {
+    /// See [`GenericContext::register_proc`].
+    pub fn register_proc(
+        &self,
+        name: &str,
+        function: unsafe extern "C" fn(),
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        GenericContext::from(self).register_proc(name, function)
+    }
+
+    pub fn enable_options_update_display_callback(&mut self) -> Result<(), EnvironmentCallError> {
         self.set_core_options_update_display_callback(retro_core_options_update_display_callback {
             callback: Some(retro_core_options_update_display_callback_fn),
         })
@@ -904,7 +1491,7 @@ impl<'a> SetEnvironmentContext<'a> {
             iface: std::ptr::null_mut(),
         });
 
-        if let Some(info) = info {
+        if let Ok(info) = info {
             if !info.iface.is_null() && info.required_interface_version >= min_version {
                 interfaces.vfs_interface_info = VfsInterfaceInfo {
                     supported_version: info.required_interface_version,
@@ -926,13 +1513,13 @@ impl<'a> SetEnvironmentContext<'a> {
 /// For a description of the callbacks see [`CoreWrapper`].
 pub struct LoadGameContext<'a> {
     pub(crate) environment_callback: &'a retro_environment_t,
-    pub(crate) interfaces: Interfaces,
+    pub(crate) interfaces: &'a Interfaces,
 }
 
 impl<'a> LoadGameContext<'a> {
     pub(crate) fn new(
         environment_callback: &'a retro_environment_t,
-        interfaces: Interfaces,
+        interfaces: &'a Interfaces,
     ) -> Self {
         Self {
             environment_callback,
@@ -940,14 +1527,58 @@ impl<'a> LoadGameContext<'a> {
         }
     }
 
+    /// Sets the internal pixel format used by the implementation.
+    /// The default pixel format is [`retro_pixel_format::RETRO_PIXEL_FORMAT_0RGB1555`].
+    /// This pixel format however, is deprecated (see enum [`retro_pixel_format`]).
+    /// If the call fails, the frontend does not support this pixel
+    /// format.
+    pub fn set_pixel_format<F: Into<retro_pixel_format>>(
+        &self,
+        format: F,
+    ) -> Result<(), EnvironmentCallError> {
+        set_pixel_format(*self.environment_callback, self.interfaces, format.into())
+    }
+
+    /// Rotates the screen, e.g. for vertical (TATE) games.
+    ///
+    /// If the frontend refuses the rotation, [`RunContext::draw_frame`] rotates the
+    /// frames itself and the geometry returned by [`Core::on_get_av_info`] gets
+    /// reported rotated, see [`util::rotate`]. In both cases the core keeps rendering
+    /// and reporting its geometry unrotated, except for geometry changes made while
+    /// running, which have to be passed through [`util::rotate::rotate_geometry`]
+    /// in the [`RotationMode::Software`] case.
+    /// Frames passed to [`RunContext::draw_framebuffer`] and hardware rendered frames
+    /// are never rotated.
+    pub fn set_rotation(&self, rotation: Rotation) -> RotationMode {
+        let accepted = GenericContext::from(self).set_rotation(rotation).is_ok();
+
+        self.interfaces.write().unwrap().software_rotation = match rotation {
+            _ if accepted => None,
+            Rotation::None => None,
+            rotation => Some(rotation),
+        };
+
+        if accepted {
+            RotationMode::Frontend
+        } else {
+            RotationMode::Software
+        }
+    }
+
     /// The reference represents the time of one frame.
     /// It is computed as `1000000 / fps`, but the implementation will resolve the
     /// rounding to ensure that framestepping, etc is exact.
-    pub fn enable_frame_time_callback(&self, reference: i64) {
+    ///
+    /// See [`Core::on_run_timed`] for a frame time that tells pauses and
+    /// fast-forwarding apart from real time.
+    pub fn enable_frame_time_callback(&self, reference: i64) -> Result<(), EnvironmentCallError> {
         self.set_frame_time_callback(retro_frame_time_callback {
             callback: Some(retro_frame_time_callback_fn),
             reference,
-        });
+        })?;
+
+        self.interfaces.write().unwrap().frame_time_reference = Some(reference);
+        Ok(())
     }
 
     #[proc::unstable(feature = "env-commands")]
@@ -964,7 +1595,7 @@ impl<'a> LoadGameContext<'a> {
 
         let mut interfaces = self.interfaces.write().unwrap();
 
-        interfaces.camera_interface = self.get_camera_interface(retro_camera_callback {
+        let interface = self.get_camera_interface(retro_camera_callback {
             caps,
             width,
             height,
@@ -986,56 +1617,54 @@ impl<'a> LoadGameContext<'a> {
             deinitialized: Some(retro_camera_deinitialized_callback),
         });
 
-        if interfaces.camera_interface.is_some() {
-            Ok(())
+        interfaces.camera_interface = interface.ok();
+        interfaces.camera_state = if interface.is_ok() {
+            CameraState::Uninitialized
         } else {
-            Err("Failed to enable camera interface".into())
-        }
+            CameraState::Unavailable
+        };
+        interface?;
+
+        Ok(())
     }
 
     #[proc::unstable(feature = "env-commands")]
     pub fn enable_sensor_interface(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let ctx: GenericContext = self.into();
         let mut interfaces = self.interfaces.write().unwrap();
-        interfaces.sensor_interface = ctx.get_sensor_interface();
+        let interface = ctx.get_sensor_interface();
+        interfaces.sensor_interface = interface.ok();
+        interface?;
 
-        if interfaces.sensor_interface.is_some() {
-            Ok(())
-        } else {
-            Err("Failed to enable sensor interface".into())
-        }
+        Ok(())
     }
 
     #[proc::unstable(feature = "env-commands")]
     pub fn enable_led_interface(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let ctx: GenericContext = self.into();
         let mut interfaces = self.interfaces.write().unwrap();
-        interfaces.led_interface = ctx.get_led_interface();
+        let interface = ctx.get_led_interface();
+        interfaces.led_interface = interface.ok();
+        interface?;
 
-        if interfaces.led_interface.is_some() {
-            Ok(())
-        } else {
-            Err("Failed to enable led interface".into())
-        }
+        Ok(())
     }
 
     #[proc::unstable(feature = "env-commands")]
     pub fn enable_midi_interface(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let ctx: GenericContext = self.into();
         let mut interfaces = self.interfaces.write().unwrap();
-        interfaces.midi_interface = ctx.get_midi_interface();
+        let interface = ctx.get_midi_interface();
+        interfaces.midi_interface = interface.ok();
+        interface?;
 
-        if interfaces.midi_interface.is_some() {
-            Ok(())
-        } else {
-            Err("Failed to enable MIDI interface".into())
-        }
+        Ok(())
     }
 
     pub fn enable_location_interface(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let ctx: GenericContext = self.into();
         let mut interfaces = self.interfaces.write().unwrap();
-        interfaces.location_interface = ctx.get_location_callback();
+        interfaces.location_interface = ctx.get_location_callback().ok();
 
         if let Some(mut interface) = interfaces.location_interface {
             interface.initialized = Some(retro_location_lifetime_status_initialized_callback);
@@ -1048,28 +1677,30 @@ impl<'a> LoadGameContext<'a> {
 
     pub fn enable_rumble_interface(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let mut interfaces = self.interfaces.write().unwrap();
-        interfaces.rumble_interface = self.get_rumble_interface();
+        let interface = self.get_rumble_interface();
+        interfaces.rumble_interface = interface.ok();
+        interface?;
 
-        if interfaces.rumble_interface.is_some() {
-            Ok(())
-        } else {
-            Err("Failed to enable rumble interface".into())
-        }
+        Ok(())
     }
 
     pub fn enable_perf_interface(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let ctx: GenericContext = self.into();
         let mut interfaces = self.interfaces.write().unwrap();
-        interfaces.perf_interface = PerfCounters {
-            interface: ctx.get_perf_interface(),
-            counters: HashMap::new(),
-        };
+        let interface = ctx.get_perf_interface();
+        util::set_time_source(
+            interface
+                .as_ref()
+                .ok()
+                .and_then(|interface| interface.get_time_usec),
+        );
+
+        // Counters registered with a previous interface have to be registered again
+        interfaces.perf_interface.unregister_all();
+        interfaces.perf_interface.interface = interface.ok();
+        interface?;
 
-        if interfaces.perf_interface.interface.is_some() {
-            Ok(())
-        } else {
-            Err("Failed to enable perf interface".into())
-        }
+        Ok(())
     }
 
     pub unsafe fn enable_hw_render(
@@ -1079,7 +1710,7 @@ impl<'a> LoadGameContext<'a> {
         version_major: u32,
         version_minor: u32,
         debug_context: bool,
-    ) -> bool {
+    ) -> Result<(), EnvironmentCallError> {
         let data = retro_hw_render_callback {
             context_type,
             bottom_left_origin,
@@ -1099,23 +1730,59 @@ impl<'a> LoadGameContext<'a> {
             get_proc_address: None,
         };
 
-        self.set_hw_render(data)
+        let data = environment::try_get_mut(
+            *self.environment_callback,
+            RETRO_ENVIRONMENT_SET_HW_RENDER,
+            data,
+        )?;
+        self.interfaces.write().unwrap().hw_render = Some(data);
+
+        Ok(())
     }
 }
 into_generic!(LoadGameContext<'a>, 'a);
 
-/// Functions that are safe to be called in [`Core::on_write_audio`].
+/// Functions that are safe to be called in [`AudioState::on_write_audio`](audio_state::AudioState::on_write_audio).
 ///
 /// For a description of the callbacks see [`CoreWrapper`].
 pub struct AudioContext<'a> {
     pub(crate) environment_callback: &'a retro_environment_t,
-    pub(crate) interfaces: Interfaces,
+    pub(crate) interfaces: &'a Interfaces,
 
     pub(crate) audio_sample_batch_callback: &'a retro_audio_sample_batch_t,
     pub(crate) audio_sample_callback: &'a retro_audio_sample_t,
+
+    /// Whether this context has been created for [`Core::on_write_audio`].
+    pub(crate) in_audio_callback: bool,
 }
 
 impl AudioContext<'_> {
+    /// Queues the frames if the audio callback is enabled but not currently running.
+    ///
+    /// Returns [`true`] if the frames have been queued.
+    fn queue_outside_callback(&self, samples: &[i16]) -> bool {
+        if self.in_audio_callback {
+            return false;
+        }
+
+        let mut interfaces = self.interfaces.write().unwrap();
+        if !interfaces.audio_callback_enabled {
+            return false;
+        }
+
+        interfaces.queued_audio.extend_from_slice(samples);
+        true
+    }
+
+    /// Passes the frames queued outside of [`Core::on_write_audio`] to the frontend.
+    pub(crate) fn flush_queued_audio(&self) {
+        let queued = std::mem::take(&mut self.interfaces.write().unwrap().queued_audio);
+
+        if !queued.is_empty() {
+            self.batch_audio_samples(&queued);
+        }
+    }
+
     /// Renders multiple audio frames in one go if [`AudioContext::audio_sample_batch_callback`] has been set.
     ///
     /// One frame is defined as a sample of left and right channels, interleaved.
@@ -1123,8 +1790,15 @@ impl AudioContext<'_> {
     ///
     /// Only one of the audio callbacks must ever be used.
     pub fn batch_audio_samples(&self, samples: &[i16]) {
+        if self.queue_outside_callback(samples) {
+            return;
+        }
+
+        #[cfg(feature = "av-dump")]
+        self.dump_audio(samples);
+
         if let Some(callback) = self.audio_sample_batch_callback {
-            let len = samples.len() as u64;
+            let len = samples.len() as size_t;
 
             unsafe {
                 (callback)(samples.as_ptr(), len / 2);
@@ -1132,18 +1806,94 @@ impl AudioContext<'_> {
         }
     }
 
+    /// Renders mono audio by duplicating every sample into both channels.
+    pub fn batch_mono(&self, samples: &[i16]) {
+        self.batch_converted(samples, 1, util::audio::mono_to_stereo);
+    }
+
+    /// Renders quadraphonic (4.0) audio mixed down to stereo by `downmix`,
+    /// see [`util::audio::QuadDownmix`].
+    pub fn batch_quad(&self, samples: &[i16], downmix: &util::audio::QuadDownmix) {
+        self.batch_converted(samples, 4, |src, dst| downmix.downmix(src, dst));
+    }
+
+    /// Converts `samples` with `channels` channels to stereo in chunks,
+    /// to avoid allocating a buffer.
+    fn batch_converted(
+        &self,
+        samples: &[i16],
+        channels: usize,
+        convert: impl Fn(&[i16], &mut [i16]) -> usize,
+    ) {
+        const CHUNK_FRAMES: usize = 512;
+        let mut stereo = [0; CHUNK_FRAMES * 2];
+
+        for chunk in samples.chunks(CHUNK_FRAMES * channels) {
+            let frames = convert(chunk, &mut stereo);
+
+            if frames > 0 {
+                self.batch_audio_samples(&stereo[..frames * 2]);
+            }
+        }
+    }
+
+    /// Lets `generate` write the interleaved stereo audio for the current frame and
+    /// renders it, see [`crate::audio_pacing`].
+    ///
+    /// The buffer holds as many audio frames as the sample rate and frame rate of the
+    /// current AV info call for, adjusted to the frontend’s audio buffer status if
+    /// [`GenericContext::enable_audio_buffer_status_callback`] succeeded.
+    /// It is meant to be called once per frame.
+    pub fn generate_audio(&self, generate: impl FnOnce(&mut [i16])) {
+        let (frames, mut buffer) = {
+            let mut interfaces = self.interfaces.write().unwrap();
+            let timing = interfaces.av_info.map(|av_info| av_info.timing);
+
+            let frames = timing.map_or(0, |timing| {
+                interfaces
+                    .audio_pacer
+                    .next_frames(timing.sample_rate, timing.fps)
+            });
+
+            (frames, std::mem::take(&mut interfaces.audio_pacer.buffer))
+        };
+
+        buffer.clear();
+        buffer.resize(frames * 2, 0);
+
+        if frames > 0 {
+            generate(&mut buffer);
+            self.batch_audio_samples(&buffer);
+        }
+
+        self.interfaces.write().unwrap().audio_pacer.buffer = buffer;
+    }
+
     /// Renders a single audio frame if [`AudioContext::audio_sample_callback`] has been set.
     /// Should only be used if implementation generates a single sample at a time.
     /// Format is signed 16-bit native endian.
     ///
     /// Only one of the audio callbacks must ever be used.
     pub fn queue_audio_sample(&self, left: i16, right: i16) {
+        if self.queue_outside_callback(&[left, right]) {
+            return;
+        }
+
+        #[cfg(feature = "av-dump")]
+        self.dump_audio(&[left, right]);
+
         if let Some(callback) = self.audio_sample_callback {
             unsafe {
                 (callback)(left, right);
             }
         }
     }
+
+    #[cfg(feature = "av-dump")]
+    fn dump_audio(&self, samples: &[i16]) {
+        let mut interfaces = self.interfaces.write().unwrap();
+        av_dump::with_dump(&mut interfaces.av_dump, |dump| dump.write_audio(samples));
+    }
 }
 
 into_generic!(AudioContext<'a>, 'a);
@@ -1153,7 +1903,7 @@ into_generic!(AudioContext<'a>, 'a);
 /// For a description of the callbacks see [`CoreWrapper`].
 pub struct RunContext<'a> {
     pub(crate) environment_callback: &'a retro_environment_t,
-    pub(crate) interfaces: Interfaces,
+    pub(crate) interfaces: &'a Interfaces,
 
     pub(crate) audio_sample_batch_callback: &'a retro_audio_sample_batch_t,
     pub(crate) audio_sample_callback: &'a retro_audio_sample_t,
@@ -1161,13 +1911,27 @@ pub struct RunContext<'a> {
     pub(crate) input_state_callback: &'a retro_input_state_t,
     pub(crate) video_refresh_callback: &'a retro_video_refresh_t,
 
-    pub(crate) can_dupe: bool,
+    pub(crate) hints: FrameHints,
+
+    /// See [`LoadGameContext::set_rotation`].
+    pub(crate) software_rotation: Option<Rotation>,
     pub(crate) had_frame: &'a mut bool,
     pub(crate) last_width: &'a mut u32,
     pub(crate) last_height: &'a mut u32,
-    pub(crate) last_pitch: &'a mut u64,
+    pub(crate) last_pitch: &'a mut size_t,
+
+    pub(crate) capture_frames: &'a mut bool,
+    pub(crate) captured_frame: &'a mut Option<CapturedFrame>,
+
+    /// Set by [`RunContext::draw_hardware_frame`], see [`Core::on_hw_frame_presented`].
+    pub(crate) hw_frame_presented: &'a mut bool,
+
+    /// See [`RunContext::set_frame_dedup`].
+    pub(crate) frame_dedup: &'a mut Option<FrameDeduplicator>,
 
     pub(crate) supports_bitmasks: bool,
+    pub(crate) joypad_transitions: &'a mut Vec<JoypadTransitions>,
+    pub(crate) port_devices: &'a [u32],
 }
 
 into_generic!(RunContext<'a>, 'a);
@@ -1176,10 +1940,12 @@ impl<'a> From<&mut RunContext<'a>> for AudioContext<'a> {
     fn from(other: &mut RunContext<'a>) -> AudioContext<'a> {
         AudioContext {
             environment_callback: other.environment_callback,
-            interfaces: Arc::clone(&other.interfaces),
+            interfaces: other.interfaces,
 
             audio_sample_batch_callback: other.audio_sample_batch_callback,
             audio_sample_callback: other.audio_sample_callback,
+
+            in_audio_callback: false,
         }
     }
 }
@@ -1187,7 +1953,13 @@ impl<'a> From<&mut RunContext<'a>> for AudioContext<'a> {
 impl<'a> RunContext<'_> {
     #[inline(always)]
     pub fn can_dupe(&self) -> bool {
-        self.can_dupe
+        self.hints.can_dupe
+    }
+
+    /// The frontend’s hints about this frame, fetched without calling into the frontend again.
+    #[inline(always)]
+    pub fn hints(&self) -> FrameHints {
+        self.hints
     }
 
     /// Polls for input if [`RunContext::input_poll_callback`] has been set
@@ -1200,9 +1972,19 @@ impl<'a> RunContext<'_> {
     }
 
     /// Gets the input state for the given player and device if [`RunContext::input_state_callback`] has been set
-    pub fn get_input_state(&self, port: u32, device: u32, index: u32, id: u32) -> i16 {
+    ///
+    /// `device` and `id` accept either the raw `RETRO_DEVICE_*` constants
+    /// or the enums from [`sys::input`], e.g. [`Device::Joypad`](sys::input::Device::Joypad)
+    /// and [`JoypadButton::A`](sys::input::JoypadButton::A).
+    /// `index` stays a plain [`u32`] so that literals keep working, convert an
+    /// [`AnalogIndex`](sys::input::AnalogIndex) with [`Into::into`].
+    pub fn get_input_state<D, I>(&self, port: u32, device: D, index: u32, id: I) -> i16
+    where
+        D: Into<u32>,
+        I: Into<u32>,
+    {
         if let Some(callback) = self.input_state_callback {
-            unsafe { (callback)(port, device, index, id) }
+            unsafe { (callback)(port, device.into(), index, id.into()) }
         } else {
             0
         }
@@ -1305,16 +2087,22 @@ impl<'a> RunContext<'_> {
 
     /// Queries the frontend for the joypad state with the more efficient, but currently experimental,
     /// joypad bitmask feature. Only a single call into the frontend gets made.
-    #[proc::unstable(feature = "env-commands")]
+    ///
+    /// If the frontend does not support input bitmasks, the bitmask gets emulated
+    /// by querying every button separately, see [`Self::get_joypad_state`].
     pub fn get_joypad_bitmask(&self, port: u32, index: u32) -> JoypadState {
         if let Some(callback) = self.input_state_callback {
             if self.supports_bitmasks {
-                return JoypadState::from_bits_truncate((callback)(
-                    port,
-                    RETRO_DEVICE_JOYPAD,
-                    index,
-                    RETRO_DEVICE_ID_JOYPAD_MASK,
-                ) as u16);
+                let mask = unsafe {
+                    (callback)(
+                        port,
+                        RETRO_DEVICE_JOYPAD,
+                        index,
+                        RETRO_DEVICE_ID_JOYPAD_MASK,
+                    )
+                };
+
+                return JoypadState::from_bits_truncate(mask as u16);
             }
 
             // Fallback
@@ -1324,6 +2112,114 @@ impl<'a> RunContext<'_> {
         JoypadState::empty()
     }
 
+    /// Returns the device type the frontend last set for `port` via
+    /// [`Core::on_set_controller_port_device`].
+    ///
+    /// Defaults to [`RETRO_DEVICE_JOYPAD`] for ports that have not been set yet.
+    pub fn device_for_port(&self, port: u32) -> u32 {
+        self.port_devices
+            .get(port as usize)
+            .copied()
+            .unwrap_or(RETRO_DEVICE_JOYPAD)
+    }
+
+    /// Returns the joypad state of `port` along with the buttons that
+    /// have been pressed or released since the previous frame.
+    ///
+    /// The state gets queried once per frame, using [`Self::get_joypad_bitmask`].
+    /// Transitions are relative to the last frame this function got called for `port`.
+    pub fn get_joypad_transitions(&mut self, port: u32) -> JoypadTransitions {
+        let index = port as usize;
+
+        if self.joypad_transitions.len() <= index {
+            self.joypad_transitions
+                .resize_with(index + 1, JoypadTransitions::default);
+        }
+
+        if !self.joypad_transitions[index].polled {
+            let state = self.get_joypad_bitmask(port, 0);
+
+            let transitions = &mut self.joypad_transitions[index];
+            transitions.update(state);
+            transitions.polled = true;
+        }
+
+        self.joypad_transitions[index]
+    }
+
+    /// Reads the joypad and left stick of `port` and passes them through `transformer`.
+    ///
+    /// Call it once per frame and port, as the transformer keeps state between frames.
+    pub fn get_transformed_input(
+        &self,
+        port: u32,
+        transformer: &mut input_transform::InputTransformer,
+    ) -> input_transform::TransformedInput {
+        let buttons = self.get_joypad_bitmask(port, 0);
+        let x = self.get_input_state(
+            port,
+            RETRO_DEVICE_ANALOG,
+            RETRO_DEVICE_INDEX_ANALOG_LEFT,
+            RETRO_DEVICE_ID_ANALOG_X,
+        );
+        let y = self.get_input_state(
+            port,
+            RETRO_DEVICE_ANALOG,
+            RETRO_DEVICE_INDEX_ANALOG_LEFT,
+            RETRO_DEVICE_ID_ANALOG_Y,
+        );
+
+        transformer.transform(buttons, x, y)
+    }
+
+    /// Returns how far `button` of the joypad at `port` is pressed, from `0.0` to `1.0`,
+    /// e.g. for the L2 and R2 triggers.
+    ///
+    /// Frontends without analog buttons report `0.0`, so the digital state
+    /// of the button is used as a fallback.
+    pub fn get_button_pressure<I: Into<u32>>(&self, port: u32, button: I) -> f32 {
+        let button = button.into();
+
+        let pressure = self.get_input_state(
+            port,
+            RETRO_DEVICE_ANALOG,
+            RETRO_DEVICE_INDEX_ANALOG_BUTTON,
+            button,
+        );
+
+        if pressure > 0 {
+            return pressure as f32 / i16::MAX as f32;
+        }
+
+        if self.get_input_state(port, RETRO_DEVICE_JOYPAD, 0, button) != 0 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Reads the lightgun of `port`, with its position scaled
+    /// to a framebuffer of `width` × `height` pixels.
+    ///
+    /// Feed the result into a [`Lightgun`] to detect shots and reloads.
+    pub fn get_lightgun_state(&self, port: u32, width: u32, height: u32) -> LightgunState {
+        let get = |id| self.get_input_state(port, RETRO_DEVICE_LIGHTGUN, 0, id);
+
+        let mut buttons = LightgunButtons::empty();
+        for (bit, id) in LightgunButtons::IDS.into_iter().enumerate() {
+            if get(id) != 0 {
+                buttons |= LightgunButtons::from_bits_truncate(1 << bit);
+            }
+        }
+
+        LightgunState {
+            x: LightgunState::scale_coordinate(get(RETRO_DEVICE_ID_LIGHTGUN_SCREEN_X), width),
+            y: LightgunState::scale_coordinate(get(RETRO_DEVICE_ID_LIGHTGUN_SCREEN_Y), height),
+            offscreen: get(RETRO_DEVICE_ID_LIGHTGUN_IS_OFFSCREEN) != 0,
+            buttons,
+        }
+    }
+
     #[proc::unstable(feature = "env-commands")]
     pub fn get_current_framebuffer(
         &self,
@@ -1344,7 +2240,7 @@ impl<'a> RunContext<'_> {
             memory_flags: 0,
         });
 
-        if let Some(fb) = fb {
+        if let Ok(fb) = fb {
             if !fb.data.is_null() {
                 // TODO: Can we get rid of the raw pointer and PhantomData in an ergonomic way?
                 // When defining `data` as `&'a mut [u8]` it has the same lifetime as `self`,
@@ -1382,7 +2278,7 @@ impl<'a> RunContext<'_> {
                 let data = unsafe { &mut FALLBACK_FRAMEBUFFER };
 
                 let pitch = width as usize * format.bit_per_pixel();
-                let data_len = width as usize * height as usize * pitch;
+                let data_len = height as usize * pitch;
 
                 if data.len() < data_len {
                     data.resize(data_len, 0);
@@ -1404,21 +2300,326 @@ impl<'a> RunContext<'_> {
         }
     }
 
+    /// The AV info as last reported to the frontend.
+    pub fn av_info(&self) -> Option<retro_system_av_info> {
+        self.interfaces.read().unwrap().av_info
+    }
+
+    /// Like [`RunContext::set_system_av_info`], but rejects geometries
+    /// that exceed their own maximum size and reports what changed.
+    pub fn try_set_system_av_info(
+        &self,
+        av_info: retro_system_av_info,
+    ) -> Result<AvInfoDiff, AvInfoError> {
+        let geometry = &av_info.geometry;
+        check_geometry(geometry, geometry.max_width, geometry.max_height)?;
+
+        let diff = match self.av_info() {
+            Some(old) => AvInfoDiff::new(&old, &av_info),
+            None => AvInfoDiff {
+                base_size: true,
+                max_size: true,
+                aspect_ratio: true,
+                fps: true,
+                sample_rate: true,
+            },
+        };
+
+        #[cfg(feature = "log")]
+        if diff.sample_rate {
+            log::warn!(
+                "Changing the sample rate to {} Hz, the frontend will reinitialize its audio driver",
+                av_info.timing.sample_rate
+            );
+        }
+
+        if self.set_system_av_info(av_info).is_err() {
+            return Err(AvInfoError::Rejected);
+        }
+
+        let mut interfaces = self.interfaces.write().unwrap();
+        interfaces.av_info = Some(av_info);
+        interfaces
+            .hw_resources
+            .resize(geometry.base_width, geometry.base_height);
+
+        Ok(diff)
+    }
+
+    /// Like [`RunContext::set_game_geometry`], but rejects geometries that exceed
+    /// the maximum size declared in [`Core::on_get_av_info`] or with
+    /// [`RunContext::try_set_system_av_info`], and reports what changed.
+    ///
+    /// The maximum size of `geometry` is ignored, as the frontend does not allow changing it here.
+    pub fn try_set_game_geometry(
+        &self,
+        geometry: retro_game_geometry,
+    ) -> Result<AvInfoDiff, AvInfoError> {
+        let old = self.av_info();
+
+        let mut new = match old {
+            Some(old) => {
+                check_geometry(&geometry, old.geometry.max_width, old.geometry.max_height)?;
+                old
+            }
+            None => retro_system_av_info {
+                geometry,
+                timing: retro_system_timing {
+                    fps: 0.0,
+                    sample_rate: 0.0,
+                },
+            },
+        };
+
+        new.geometry.base_width = geometry.base_width;
+        new.geometry.base_height = geometry.base_height;
+        new.geometry.aspect_ratio = geometry.aspect_ratio;
+
+        if self.set_game_geometry(geometry).is_err() {
+            return Err(AvInfoError::Rejected);
+        }
+
+        let mut interfaces = self.interfaces.write().unwrap();
+        interfaces.av_info = Some(new);
+        interfaces
+            .hw_resources
+            .resize(geometry.base_width, geometry.base_height);
+
+        Ok(old.map_or_else(
+            || AvInfoDiff {
+                base_size: true,
+                aspect_ratio: true,
+                ..AvInfoDiff::default()
+            },
+            |old| AvInfoDiff::new(&old, &new),
+        ))
+    }
+
     /// Draws a new frame if [`RunContext::video_refresh_callback`] has been set
+    ///
+    /// If the frontend refused [`LoadGameContext::set_rotation`], the frame gets rotated first.
     pub fn draw_frame(&mut self, data: &[u8], width: u32, height: u32, pitch: u64) {
+        if let (Some(rotation), Some(_)) = (self.software_rotation, self.video_refresh_callback) {
+            let mut surface = std::mem::take(&mut self.interfaces.write().unwrap().rotated_surface);
+
+            surface.set_rotation(rotation);
+            self.draw_rotated_frame(&mut surface, data, width, height, pitch);
+
+            self.interfaces.write().unwrap().rotated_surface = surface;
+            return;
+        }
+
+        self.draw_unrotated_frame(data, width, height, pitch);
+    }
+
+    /// Rotates and flips a frame with `surface` and draws it,
+    /// e.g. for cores that rotate the screen regardless of the frontend.
+    ///
+    /// Frames in an unknown pixel format get drawn as they are.
+    pub fn draw_rotated_frame(
+        &mut self,
+        surface: &mut util::rotate::RotatedSurface,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        pitch: u64,
+    ) {
+        // `bit_per_pixel` returns bytes
+        let bytes_per_pixel = self.pixel_format().bit_per_pixel();
+        if bytes_per_pixel == 0 {
+            return self.draw_unrotated_frame(data, width, height, pitch);
+        }
+
+        let frame = surface.rotate(data, width, height, pitch as usize, bytes_per_pixel);
+        self.draw_unrotated_frame(frame.data, frame.width, frame.height, frame.pitch as u64);
+    }
+
+    fn draw_unrotated_frame(&mut self, data: &[u8], width: u32, height: u32, pitch: u64) {
         if let Some(callback) = self.video_refresh_callback {
+            if self.is_duplicate_frame(data, width, height, pitch as usize) {
+                return self.dupe_frame();
+            }
+
             *self.had_frame = true;
             *self.last_width = width;
             *self.last_height = height;
-            *self.last_pitch = pitch;
+            *self.last_pitch = pitch as size_t;
+
+            if *self.capture_frames {
+                let format = self.pixel_format();
+                self.capture_frame(data, width, height, pitch as usize, format);
+            }
+
+            #[cfg(feature = "av-dump")]
+            self.dump_av_frame(data, width, height, pitch as usize, self.pixel_format());
 
-            unsafe { (callback)(data.as_ptr() as *const c_void, width, height, pitch) }
+            unsafe {
+                (callback)(
+                    data.as_ptr() as *const c_void,
+                    width,
+                    height,
+                    *self.last_pitch,
+                )
+            }
         }
     }
 
+    /// Scales an [`XRGB8888`](PixelFormat::XRGB8888) frame to the base size
+    /// of the current geometry and draws it.
+    ///
+    /// Lets software rendered cores offer internal resolution options while keeping
+    /// the reported geometry. Without a known geometry, the frame gets drawn as it is.
+    pub fn draw_scaled_frame(
+        &mut self,
+        scaler: &mut util::scale::Scaler,
+        data: &[u32],
+        width: u32,
+        height: u32,
+    ) {
+        let (dst_width, dst_height) = self
+            .av_info()
+            .map(|av_info| (av_info.geometry.base_width, av_info.geometry.base_height))
+            .unwrap_or((width, height));
+
+        let frame = scaler.scale(data, width, height, dst_width, dst_height);
+        let pitch = dst_width as u64 * std::mem::size_of::<u32>() as u64;
+
+        self.draw_frame(util::convert::as_bytes(frame), dst_width, dst_height, pitch);
+    }
+
+    /// Replaces frames passed to [`RunContext::draw_frame`] that are identical to the
+    /// previous one with [`RunContext::dupe_frame`], so the frontend doesn’t upload them again.
+    ///
+    /// Disabled by default, as every frame gets hashed. Has no effect if the frontend
+    /// can’t dupe frames, see [`RunContext::can_dupe`].
+    pub fn set_frame_dedup(&mut self, enabled: bool) {
+        match (enabled, self.frame_dedup.is_some()) {
+            (true, false) => *self.frame_dedup = Some(FrameDeduplicator::new()),
+            (false, _) => *self.frame_dedup = None,
+            _ => (),
+        }
+    }
+
+    /// The number of frames replaced since [`RunContext::set_frame_dedup`] got enabled.
+    pub fn deduplicated_frames(&self) -> u64 {
+        self.frame_dedup.map_or(0, |dedup| dedup.duplicates())
+    }
+
+    fn is_duplicate_frame(&mut self, data: &[u8], width: u32, height: u32, pitch: usize) -> bool {
+        if !self.hints.can_dupe {
+            return false;
+        }
+
+        let had_frame = *self.had_frame;
+
+        match self.frame_dedup.as_mut() {
+            Some(dedup) => dedup.is_duplicate(data, width, height, pitch) && had_frame,
+            None => false,
+        }
+    }
+
+    /// Makes [`RunContext::set_frame_dedup`] compare the next frame against nothing,
+    /// as the frontend got a frame it can’t compare against.
+    fn reset_frame_dedup(&mut self) {
+        if let Some(dedup) = self.frame_dedup.as_mut() {
+            dedup.reset();
+        }
+    }
+
+    /// Keeps a copy of every software rendered frame passed to [`RunContext::draw_frame`]
+    /// or [`RunContext::draw_framebuffer`], e.g. to attach screenshots to bug reports.
+    ///
+    /// Disabled by default, as copying every frame is not free.
+    pub fn set_frame_capture(&mut self, enabled: bool) {
+        *self.capture_frames = enabled;
+
+        if !enabled {
+            *self.captured_frame = None;
+        }
+    }
+
+    /// The last frame captured since [`RunContext::set_frame_capture`] got enabled.
+    ///
+    /// Returns [`None`] if the last frame has been rendered by the GPU.
+    pub fn captured_frame(&self) -> Option<&CapturedFrame> {
+        self.captured_frame.as_ref()
+    }
+
+    /// Writes the [captured frame](RunContext::captured_frame) as a PNG image to `path`.
+    pub fn dump_frame<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let frame = self
+            .captured_frame()
+            .ok_or("No frame has been captured, see `RunContext::set_frame_capture`")?;
+
+        std::fs::write(path, frame.to_png()?)?;
+
+        Ok(())
+    }
+
+    fn pixel_format(&self) -> PixelFormat {
+        self.interfaces
+            .read()
+            .unwrap()
+            .pixel_format
+            .unwrap_or(PixelFormat::XRGB1555)
+    }
+
+    fn capture_frame(
+        &mut self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        pitch: usize,
+        format: PixelFormat,
+    ) {
+        // The last row does not need to be padded to the full pitch
+        let length = data.len().min(pitch * height as usize);
+
+        let frame = self.captured_frame.get_or_insert_with(|| CapturedFrame {
+            data: Vec::new(),
+            width,
+            height,
+            pitch,
+            format,
+        });
+
+        frame.data.clear();
+        frame.data.extend_from_slice(&data[..length]);
+        frame.width = width;
+        frame.height = height;
+        frame.pitch = pitch;
+        frame.format = format;
+    }
+
+    #[cfg(feature = "av-dump")]
+    fn dump_av_frame(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        pitch: usize,
+        format: PixelFormat,
+    ) {
+        let mut interfaces = self.interfaces.write().unwrap();
+        if interfaces.av_dump.is_none() {
+            return;
+        }
+
+        let length = data.len().min(pitch * height as usize);
+        let frame = CapturedFrame {
+            data: data[..length].to_vec(),
+            width,
+            height,
+            pitch,
+            format,
+        };
+
+        av_dump::with_dump(&mut interfaces.av_dump, |dump| dump.write_frame(&frame));
+    }
+
     /// Duplicates the previous frame
     pub fn dupe_frame(&self) {
-        if !self.can_dupe {
+        if !self.hints.can_dupe {
             eprintln!("[ERROR] This frontend does not support frame duping!");
             return;
         } else if !*self.had_frame {
@@ -1426,6 +2627,11 @@ impl<'a> RunContext<'_> {
             return;
         }
 
+        #[cfg(feature = "av-dump")]
+        av_dump::with_dump(&mut self.interfaces.write().unwrap().av_dump, |dump| {
+            dump.dupe_frame()
+        });
+
         if let Some(callback) = self.video_refresh_callback {
             unsafe {
                 (callback)(
@@ -1440,11 +2646,41 @@ impl<'a> RunContext<'_> {
 
     pub fn draw_framebuffer(&mut self, framebuffer: retro_framebuffer) {
         if let Some(callback) = self.video_refresh_callback {
+            self.reset_frame_dedup();
+
             *self.had_frame = true;
             *self.last_width = framebuffer.width;
             *self.last_height = framebuffer.height;
             *self.last_pitch = framebuffer.pitch;
 
+            if !framebuffer.data.is_null() {
+                let data = unsafe {
+                    std::slice::from_raw_parts(
+                        framebuffer.data as *const u8,
+                        framebuffer.pitch as usize * framebuffer.height as usize,
+                    )
+                };
+
+                if *self.capture_frames {
+                    self.capture_frame(
+                        data,
+                        framebuffer.width,
+                        framebuffer.height,
+                        framebuffer.pitch as usize,
+                        framebuffer.format.into(),
+                    );
+                }
+
+                #[cfg(feature = "av-dump")]
+                self.dump_av_frame(
+                    data,
+                    framebuffer.width,
+                    framebuffer.height,
+                    framebuffer.pitch as usize,
+                    framebuffer.format.into(),
+                );
+            }
+
             unsafe {
                 (callback)(
                     framebuffer.data,
@@ -1456,43 +2692,145 @@ impl<'a> RunContext<'_> {
         }
     }
 
+    /// Passes the buttons pressed on port 0 to an active text input request
+    /// and draws its keyboard onto an XRGB8888 `frame`, see [`text_input`].
+    ///
+    /// `pitch` is the distance between two lines in pixels.
+    /// Returns `false` if there is no active request, the frame is left untouched then.
+    pub fn draw_text_input(
+        &mut self,
+        frame: &mut [u32],
+        width: usize,
+        height: usize,
+        pitch: usize,
+    ) -> bool {
+        if self.interfaces.read().unwrap().text_input.is_none() {
+            return false;
+        }
+
+        let pressed = self.get_joypad_transitions(0).pressed();
+
+        if let Some(request) = self.interfaces.write().unwrap().text_input.as_mut() {
+            request.input.handle_buttons(pressed);
+            request.input.draw(frame, width, height, pitch);
+        }
+
+        true
+    }
+
+    /// Like [`RunContext::draw_hardware_frame`], but waits until the OpenGL commands
+    /// issued so far have completed, see [`gl_sync`].
+    ///
+    /// The frame gets passed to the frontend even if waiting failed.
+    pub fn draw_hardware_frame_synced(
+        &mut self,
+        sync: &mut gl_sync::GlSync,
+        width: u32,
+        height: u32,
+        pitch: u64,
+    ) -> gl_sync::GlSyncStatus {
+        sync.fence();
+        let status = sync.wait();
+
+        #[cfg(feature = "log")]
+        if status != gl_sync::GlSyncStatus::Complete {
+            log::warn!("Presenting a hardware frame that may be incomplete: {status:?}");
+        }
+
+        self.draw_hardware_frame(width, height, pitch);
+        status
+    }
+
+    /// Passes `image` to the frontend’s Vulkan interface and presents it, see [`vulkan`].
+    #[cfg(feature = "unstable-env-commands")]
+    pub fn draw_vulkan_frame(
+        &mut self,
+        interface: &vulkan::VulkanInterface,
+        image: &vulkan::retro_vulkan_image,
+        mode: vulkan::SubmissionMode,
+        width: u32,
+        height: u32,
+    ) -> Result<(), vulkan::VulkanError> {
+        interface.set_image(image, mode, interface.raw()?.queue_index)?;
+        self.draw_hardware_frame(width, height, 0);
+
+        Ok(())
+    }
+
     pub fn draw_hardware_frame(&mut self, width: u32, height: u32, pitch: u64) {
         if let Some(callback) = self.video_refresh_callback {
             *self.had_frame = true;
             *self.last_width = width;
             *self.last_height = height;
-            *self.last_pitch = pitch;
+            *self.last_pitch = pitch as size_t;
+
+            // Hardware rendered frames never reach the CPU
+            *self.captured_frame = None;
+            self.reset_frame_dedup();
+            *self.hw_frame_presented = true;
 
             unsafe {
                 (callback)(
                     RETRO_HW_FRAME_BUFFER_VALID as *const c_void,
                     width,
                     height,
-                    pitch,
+                    *self.last_pitch,
                 )
             }
         }
     }
 
+    /// Starts the camera, see [`GenericContext::camera_state`].
     #[proc::unstable(feature = "env-commands")]
-    pub fn camera_start(&self) {
-        let interfaces = self.interfaces.read().unwrap();
+    pub fn camera_start(&self) -> Result<(), CameraError> {
+        let mut interfaces = self.interfaces.write().unwrap();
 
-        if let Some(interface) = interfaces.camera_interface {
-            if let Some(start) = interface.start {
-                unsafe { start() };
+        let start = interfaces
+            .camera_interface
+            .and_then(|interface| interface.start)
+            .ok_or(CameraError::Unavailable)?;
+
+        if unsafe { start() } {
+            interfaces.camera_state = CameraState::Running;
+            return Ok(());
+        }
+
+        match interfaces.camera_state {
+            CameraState::Uninitialized | CameraState::Deinitialized => {
+                Err(CameraError::NotInitialized)
+            }
+            _ => {
+                interfaces.camera_state = CameraState::Denied;
+                Err(CameraError::Denied)
             }
         }
     }
 
     #[proc::unstable(feature = "env-commands")]
     pub fn camera_stop(&self) {
-        let interfaces = self.interfaces.read().unwrap();
+        let mut interfaces = self.interfaces.write().unwrap();
 
         if let Some(interface) = interfaces.camera_interface {
             if let Some(stop) = interface.stop {
                 unsafe { stop() };
+
+                if interfaces.camera_state == CameraState::Running {
+                    interfaces.camera_state = CameraState::Initialized;
+                }
             }
         }
     }
 }
+
+/// Remembers the pixel format accepted by the frontend for [`RunContext::set_frame_capture`].
+fn set_pixel_format(
+    callback: retro_environment_t,
+    interfaces: &Interfaces,
+    format: retro_pixel_format,
+) -> Result<(), EnvironmentCallError> {
+    unsafe { environment::set_pixel_format(callback, format) }?;
+
+    interfaces.write().unwrap().pixel_format = Some(format.into());
+
+    Ok(())
+}
@@ -1,5 +1,17 @@
 //! Provides the [`Core`] and [`CoreOptions`] traits.
-use crate::*;
+use crate::{core_info::CoreInfo, disk_control::DiskControl, *};
+
+/// Gives the [`CoreWrapper`](crate::core_wrapper::CoreWrapper) access to the concrete type
+/// of a [`Core`], which [`GenericContext::on_option_change`] handlers get passed.
+pub(crate) trait AnyCore: Core {
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
+}
+
+impl<C: Core + 'static> AnyCore for C {
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
 
 /// This trait defines the [`set_core_options`](CoreOptions::set_core_options) function.
 pub trait CoreOptions {
@@ -13,6 +25,29 @@ pub trait CoreOptions {
     fn set_core_options(&self, _ctx: &SetEnvironmentContext) -> bool {
         true
     }
+
+    /// Returns the options and categories declared with `#[derive(CoreOptions)]`.
+    fn option_definitions() -> CoreOptionDefinitions
+    where
+        Self: Sized,
+    {
+        CoreOptionDefinitions::default()
+    }
+
+    /// Serializes the [option definitions](CoreOptions::option_definitions) to JSON,
+    /// so that option documentation can be generated from the same source as the core,
+    /// e.g. by a test or a small binary.
+    fn export_definitions() -> String
+    where
+        Self: Sized,
+    {
+        Self::option_definitions().to_json()
+    }
+
+    /// Returns the rules declared with `#[visible_when]` on `#[derive(CoreOptions)]`.
+    fn option_visibility_rules(&self) -> &'static [OptionVisibilityRule] {
+        &[]
+    }
 }
 
 /// This trait defines the basic functions that every libretro core must implement.
@@ -21,6 +56,24 @@ pub trait Core: CoreOptions {
     /// Returns static info about this core.
     fn get_info(&self) -> SystemInfo;
 
+    /// Whether this core can be started without any content.
+    ///
+    /// This gets communicated to the frontend during the initial call of
+    /// [`Core::on_set_environment`], so there is no need to call
+    /// [`SetEnvironmentContext::set_support_no_game`] manually.
+    /// If [`true`], [`Core::on_load_game`] may be called with [`None`].
+    fn supports_no_game(&self) -> bool {
+        false
+    }
+
+    /// Describes the core in its `.info` file, see [`core_info`](mod@crate::core_info).
+    ///
+    /// [`CoreInfo::supports_no_game`](crate::core_info::CoreInfo::supports_no_game)
+    /// gets overwritten by [`Core::supports_no_game`].
+    fn core_info(&self) -> CoreInfo {
+        CoreInfo::new(&self.get_info())
+    }
+
     /// Called when the frontend needs information about the
     /// audio and video timings and the video geometry.
     fn on_get_av_info(&mut self, _ctx: &mut GetAvInfoContext) -> retro_system_av_info;
@@ -32,6 +85,15 @@ pub trait Core: CoreOptions {
         // Do nothing
     }
 
+    /// Called during the initial call of `retro_set_environment`, before the core options
+    /// get registered and before [`Core::on_set_environment`].
+    ///
+    /// This is the place to choose the interface for the core options with
+    /// [`SetEnvironmentContext::set_options_compat_mode`].
+    fn on_before_set_core_options(&mut self, _ctx: &mut SetEnvironmentContext) {
+        // Do nothing
+    }
+
     /// Called when the libretro API has been initialized.
     fn on_init(&mut self, _ctx: &mut InitContext) {
         // Do nothing
@@ -52,6 +114,16 @@ pub trait Core: CoreOptions {
         // Do nothing
     }
 
+    /// Called before the first [`Core::on_run`] and whenever the number of ports the frontend
+    /// provides changes afterwards, see [`GenericContext::get_max_users`]. Changes are
+    /// checked along with the [`FrameHints`], about once per second.
+    ///
+    /// `max_users` is [`None`] if the frontend doesn’t report it. Use it to resize a
+    /// [`Ports`](crate::ports::Ports) collection.
+    fn on_max_users_changed(&mut self, _max_users: Option<u32>, _ctx: &mut GenericContext) {
+        // Do nothing
+    }
+
     /// Called when the frontend requests resetting the system.
     fn on_reset(&mut self, _ctx: &mut ResetContext) {
         // Do nothing
@@ -61,12 +133,38 @@ pub trait Core: CoreOptions {
     ///
     /// If a frame is not rendered for reasons where a game "dropped" a frame,
     /// this still counts as a frame, and [`Core::on_run`] should explicitly dupe
-    /// a frame if [`environment::can_dupe`] returns [`true`].
+    /// a frame if [`RunContext::can_dupe`] returns [`true`].
     /// In this case, the video callback can take a NULL argument for data.
     fn on_run(&mut self, _ctx: &mut RunContext, _delta_us: Option<i64>) {
         // Do nothing
     }
 
+    /// Called once per frame instead of [`Core::on_run`], with the frame time classified
+    /// into real time, throttled and resumed frames, see [`timing::FrameTime`].
+    ///
+    /// `frame_time` is [`None`] unless [`LoadGameContext::enable_frame_time_callback`]
+    /// succeeded. Calls [`Core::on_run`] with the raw frame time by default.
+    ///
+    /// If [`Core::as_split_run`] returns the core, its [`SplitRunCore`](split_run::SplitRunCore)
+    /// methods get called instead of [`Core::on_run`].
+    fn on_run_timed(&mut self, ctx: &mut RunContext, frame_time: Option<timing::FrameTime>) {
+        let delta_us = frame_time.map(|frame_time| frame_time.usec());
+
+        #[cfg(feature = "unstable-env-commands")]
+        if let Some(core) = self.as_split_run() {
+            return split_run::run(core, ctx, delta_us);
+        }
+
+        self.on_run(ctx, delta_us)
+    }
+
+    /// Returns `Some(self)` if the core implements [`SplitRunCore`](split_run::SplitRunCore),
+    /// to let rendering be skipped if the frontend doesn’t show a frame, see [`split_run`].
+    #[cfg(feature = "unstable-env-commands")]
+    fn as_split_run(&mut self) -> Option<&mut dyn split_run::SplitRunCore> {
+        None
+    }
+
     /// Returns the amount of data the implementation requires to serialize
     /// internal state (save states).
     ///
@@ -95,6 +193,10 @@ pub trait Core: CoreOptions {
 
     /// Called when a game should be loaded.
     /// Return [`true`] to indicate successful loading and [`false`] to indicate load failure.
+    ///
+    /// Any type implementing [`std::error::Error`] converts into the boxed error with `?`,
+    /// so cores are free to use their own error types; the crate doesn’t depend on an
+    /// error handling library. With the `log` feature, the error gets logged if loading fails.
     fn on_load_game(
         &mut self,
         _game: Option<retro_game_info>,
@@ -144,8 +246,21 @@ pub trait Core: CoreOptions {
     /// Can be any of:
     /// - [`RETRO_REGION_NTSC`]
     /// - [`RETRO_REGION_PAL`]
-    fn on_get_region(&mut self, _ctx: &mut GetRegionContext) -> std::os::raw::c_uint {
-        RETRO_REGION_NTSC
+    ///
+    /// Unless [`Core::region_override`] returns a region, the default implementation
+    /// derives it from the frame rate last reported in [`Core::on_get_av_info`] or with
+    /// [`RunContext::try_set_system_av_info`], see [`GenericContext::timing_region`].
+    fn on_get_region(&mut self, ctx: &mut GetRegionContext) -> std::os::raw::c_uint {
+        self.region_override(ctx)
+            .or_else(|| ctx.timing_region())
+            .unwrap_or(Region::NTSC)
+            .into()
+    }
+
+    /// Overrides the region reported by the default [`Core::on_get_region`],
+    /// e.g. for PAL content that runs at 60 Hz.
+    fn region_override(&mut self, _ctx: &mut GetRegionContext) -> Option<Region> {
+        None
     }
 
     /// **TODO:** Documentation
@@ -168,9 +283,33 @@ pub trait Core: CoreOptions {
         0
     }
 
+    /// Called at the start of a frame if the registered [`SramTracker`](crate::sram::SramTracker)
+    /// noticed that the save RAM changed, see [`GenericContext::set_sram_tracker`].
+    fn on_sram_dirty(&mut self, _ctx: &mut GenericContext) {
+        // Do nothing
+    }
+
+    /// Called before [`Core::on_run`] when the frontend starts or stops fast-forwarding,
+    /// e.g. to skip expensive audio processing while fast-forwarding.
+    ///
+    /// The state is checked along with the [`FrameHints`], so this may be called up to a second late.
+    fn on_fastforward_changed(&mut self, _fastforwarding: bool, _ctx: &mut GenericContext) {
+        // Do nothing
+    }
+
+    /// Called after [`Core::on_run`] if it took longer than the frame budget several times
+    /// in a row, see [`GenericContext::set_frame_watchdog`].
+    ///
+    /// `duration` is the wall time of the last frame. Cores may enable frame skipping here.
+    fn on_frame_overrun(&mut self, _duration: std::time::Duration, _ctx: &mut GenericContext) {
+        // Do nothing
+    }
+
     /// Gets called when the core options have been changed.
     ///
     /// Options get checked before [`Core::on_load_game`], [`Core::on_load_game_special`] and before each call of [`Core::on_run`].
+    ///
+    /// To react to individual options, see [`GenericContext::on_option_change`].
     fn on_options_changed(&mut self, _ctx: &mut OptionsChangedContext) {
         // Do nothing
     }
@@ -187,11 +326,16 @@ pub trait Core: CoreOptions {
     }
 
     /// Called when the frontend needs more audio frames
+    ///
+    /// The frontend may call it on its audio thread while [`Core::on_run`] is running,
+    /// render the audio in an [`AudioState`](crate::audio_state::AudioState) instead.
+    #[deprecated(note = "use an `AudioState` instead, see `GenericContext::enable_audio_state`")]
     fn on_write_audio(&mut self, _ctx: &mut AudioContext) {
         // Do nothing
     }
 
     /// **TODO:** Documentation
+    #[deprecated(note = "use an `AudioState` instead, see `GenericContext::enable_audio_state`")]
     fn on_audio_set_state(&mut self, _enabled: bool) {
         // Do nothing
     }
@@ -211,6 +355,12 @@ pub trait Core: CoreOptions {
         // Do nothing
     }
 
+    /// Called after [`Core::on_run`] if it passed a frame to
+    /// [`RunContext::draw_hardware_frame`] or [`RunContext::draw_hardware_frame_synced`].
+    fn on_hw_frame_presented(&mut self, _ctx: &mut GenericContext) {
+        // Do nothing
+    }
+
     /// **TODO:** Documentation
     fn on_get_proc_address(&mut self, _symbol_name: &CStr) -> retro_proc_address_t {
         None
@@ -257,6 +407,14 @@ pub trait Core: CoreOptions {
         // Do nothing
     }
 
+    /// Returns the [`DiskControl`] used to handle the disk control interface.
+    ///
+    /// If this returns [`Some`], the disk control callbacks below are
+    /// not called; the returned [`DiskControl`] handles them instead.
+    fn disk_control(&mut self) -> Option<&mut DiskControl> {
+        None
+    }
+
     /// **TODO:** Documentation
     fn on_set_eject_state(&mut self, _ejected: bool) -> bool {
         false
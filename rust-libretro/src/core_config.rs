@@ -0,0 +1,311 @@
+//! Persisting settings of a core that don’t belong in the core options.
+//!
+//! Core options are managed by the frontend and limited to a list of values per option.
+//! A [`CoreConfig`] stores any [`Serialize`] and [`DeserializeOwned`] type as TOML in
+//! `<save directory>/<name>.toml` instead, e.g. the key bindings of an emulated keyboard
+//! or the last directory opened in a file browser. If the frontend reports no save
+//! directory, the system directory gets used.
+//!
+//! The file is accessed through the frontend’s VFS interface if it has been enabled
+//! (see [`SetEnvironmentContext::enable_vfs_interface`]) and through [`std::fs`] otherwise.
+//!
+//! Load the settings in [`Core::on_init`] and save them in [`Core::on_deinit`]:
+//!
+//! ```ignore
+//! #[derive(Default, Serialize, Deserialize)]
+//! struct Settings { /* … */ }
+//!
+//! fn on_init(&mut self, ctx: &mut InitContext) {
+//!     self.settings = CoreConfig::load_or_default(&ctx.into(), "example");
+//! }
+//!
+//! fn on_deinit(&mut self, ctx: &mut DeinitContext) {
+//!     let _ = self.settings.save(&ctx.into());
+//! }
+//! ```
+//!
+//! This module requires the `core-config` feature.
+//!
+//! [`SetEnvironmentContext::enable_vfs_interface`]: crate::contexts::SetEnvironmentContext::enable_vfs_interface
+//! [`Core::on_init`]: crate::core::Core::on_init
+//! [`Core::on_deinit`]: crate::core::Core::on_deinit
+use crate::contexts::GenericContext;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+};
+
+/// Settings of type `T` stored in a TOML file, see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoreConfig<T> {
+    name: String,
+    value: T,
+}
+
+impl<T: Serialize + DeserializeOwned + Default> CoreConfig<T> {
+    /// Creates the default settings, which get stored in `<name>.toml`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self::with_value(name, T::default())
+    }
+
+    pub fn with_value(name: impl Into<String>, value: T) -> Self {
+        Self {
+            name: name.into(),
+            value,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// The path of the file in the save directory, or the system directory if the
+    /// frontend did not report a save directory.
+    pub fn path(&self, ctx: &GenericContext) -> Result<PathBuf, CoreConfigError> {
+        let directory = ctx
+            .get_save_directory()
+            .or_else(|_| ctx.get_system_directory())
+            .map_err(|_| CoreConfigError::NoDirectory)?;
+
+        Ok(directory.join(format!("{}.toml", self.name)))
+    }
+
+    /// Reads the settings from `<name>.toml`, the default settings are used if the file
+    /// does not exist yet.
+    pub fn load(ctx: &GenericContext, name: impl Into<String>) -> Result<Self, CoreConfigError> {
+        let mut config = Self::new(name);
+        let path = config.path(ctx)?;
+
+        if let Some(data) = read_file(ctx, &path)? {
+            config.value = from_toml(&data)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Like [`CoreConfig::load`], but falls back to the default settings on errors.
+    pub fn load_or_default(ctx: &GenericContext, name: impl Into<String>) -> Self {
+        let name = name.into();
+
+        Self::load(ctx, name.clone()).unwrap_or_else(|_err| {
+            #[cfg(feature = "log")]
+            log::warn!("Failed to load {name}.toml: {_err}");
+
+            Self::new(name)
+        })
+    }
+
+    /// Writes the settings to `<name>.toml`.
+    pub fn save(&self, ctx: &GenericContext) -> Result<(), CoreConfigError> {
+        let path = self.path(ctx)?;
+        let data = toml::to_string_pretty(&self.value).map_err(CoreConfigError::Serialize)?;
+
+        write_file(ctx, &path, data.as_bytes())
+    }
+}
+
+impl<T> Deref for CoreConfig<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for CoreConfig<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+/// Returned by [`CoreConfig::load`] and [`CoreConfig::save`].
+#[derive(Debug)]
+pub enum CoreConfigError {
+    /// The frontend reported neither a save nor a system directory.
+    NoDirectory,
+
+    /// Accessing the file through [`std::fs`] failed.
+    Io(std::io::Error),
+
+    /// Accessing the file through the frontend’s VFS interface failed.
+    #[cfg(feature = "unstable-env-commands")]
+    Vfs(crate::types::VfsError),
+
+    /// The file is not valid TOML or does not match the settings type.
+    Parse(toml::de::Error),
+
+    Serialize(toml::ser::Error),
+}
+
+impl std::fmt::Display for CoreConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoDirectory => write!(f, "Neither the save nor the system directory is known"),
+            Self::Io(err) => write!(f, "{err}"),
+            #[cfg(feature = "unstable-env-commands")]
+            Self::Vfs(err) => write!(f, "{err}"),
+            Self::Parse(err) => write!(f, "Invalid settings: {err}"),
+            Self::Serialize(err) => write!(f, "Failed to serialize the settings: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CoreConfigError {}
+
+fn from_toml<T: DeserializeOwned>(data: &[u8]) -> Result<T, CoreConfigError> {
+    let text = String::from_utf8_lossy(data);
+
+    toml::from_str(&text).map_err(CoreConfigError::Parse)
+}
+
+/// Returns [`None`] if the file does not exist.
+#[cfg_attr(not(feature = "unstable-env-commands"), allow(unused_variables))]
+fn read_file(ctx: &GenericContext, path: &Path) -> Result<Option<Vec<u8>>, CoreConfigError> {
+    #[cfg(feature = "unstable-env-commands")]
+    if unsafe { ctx.vfs_capabilities() }.files {
+        return vfs::read(ctx, path).map_err(CoreConfigError::Vfs);
+    }
+
+    match std::fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(CoreConfigError::Io(err)),
+    }
+}
+
+#[cfg_attr(not(feature = "unstable-env-commands"), allow(unused_variables))]
+fn write_file(ctx: &GenericContext, path: &Path, data: &[u8]) -> Result<(), CoreConfigError> {
+    #[cfg(feature = "unstable-env-commands")]
+    if unsafe { ctx.vfs_capabilities() }.files {
+        return vfs::write(ctx, path, data).map_err(CoreConfigError::Vfs);
+    }
+
+    std::fs::write(path, data).map_err(CoreConfigError::Io)
+}
+
+/// Whole-file access through the raw VFS functions, which hand out
+/// pointers to the handles the frontend owns.
+#[cfg(feature = "unstable-env-commands")]
+mod vfs {
+    use super::*;
+    use crate::types::{VfsError, VfsFileOpenFlags, VfsFileOpenHints};
+    use std::ffi::CString;
+
+    fn c_path(path: &Path) -> Result<CString, VfsError> {
+        CString::new(path.to_string_lossy().as_bytes()).map_err(|_| VfsError::InvalidPath)
+    }
+
+    /// Returns [`None`] if the file can’t be opened, which includes it not existing.
+    pub(super) fn read(ctx: &GenericContext, path: &Path) -> Result<Option<Vec<u8>>, VfsError> {
+        let interfaces = ctx.interfaces.read().unwrap();
+        let info = &interfaces.vfs_interface_info;
+
+        let open = info.function("open", 1, |vfs| vfs.open)?;
+        let close = info.function("close", 1, |vfs| vfs.close)?;
+        let size = info.function("size", 1, |vfs| vfs.size)?;
+        let read = info.function("read", 1, |vfs| vfs.read)?;
+
+        let path = c_path(path)?;
+        let handle = unsafe {
+            open(
+                path.as_ptr(),
+                VfsFileOpenFlags::READ.bits(),
+                VfsFileOpenHints::NONE.bits(),
+            )
+        };
+
+        if handle.is_null() {
+            return Ok(None);
+        }
+
+        let result = match u64::try_from(unsafe { size(handle) }) {
+            Ok(length) => {
+                let mut buffer = vec![0u8; length as usize];
+                let read_length = unsafe { read(handle, buffer.as_mut_ptr() as *mut _, length) };
+
+                match usize::try_from(read_length) {
+                    Ok(read_length) => {
+                        buffer.truncate(read_length);
+                        Ok(Some(buffer))
+                    }
+                    Err(_) => Err(VfsError::Failed { function: "read" }),
+                }
+            }
+            Err(_) => Err(VfsError::Failed { function: "size" }),
+        };
+
+        unsafe { close(handle) };
+
+        result
+    }
+
+    pub(super) fn write(ctx: &GenericContext, path: &Path, data: &[u8]) -> Result<(), VfsError> {
+        let interfaces = ctx.interfaces.read().unwrap();
+        let info = &interfaces.vfs_interface_info;
+
+        let open = info.function("open", 1, |vfs| vfs.open)?;
+        let close = info.function("close", 1, |vfs| vfs.close)?;
+        let write = info.function("write", 1, |vfs| vfs.write)?;
+
+        let path = c_path(path)?;
+        let handle = unsafe {
+            open(
+                path.as_ptr(),
+                VfsFileOpenFlags::WRITE.bits(),
+                VfsFileOpenHints::NONE.bits(),
+            )
+        };
+
+        if handle.is_null() {
+            return Err(VfsError::Failed { function: "open" });
+        }
+
+        let written = unsafe { write(handle, data.as_ptr() as *const _, data.len() as u64) };
+
+        if unsafe { close(handle) } != 0 {
+            return Err(VfsError::Failed { function: "close" });
+        }
+
+        match u64::try_from(written) {
+            Ok(written) if written == data.len() as u64 => Ok(()),
+            _ => Err(VfsError::Failed { function: "write" }),
+        }
+    }
+}
+
+#[test]
+fn core_config_toml() {
+    #[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Settings {
+        #[serde(default)]
+        last_directory: String,
+        #[serde(default)]
+        volume: u8,
+    }
+
+    let settings = Settings {
+        last_directory: "/roms".to_owned(),
+        volume: 7,
+    };
+
+    let text = toml::to_string_pretty(&settings).unwrap();
+    assert_eq!(from_toml::<Settings>(text.as_bytes()).unwrap(), settings);
+
+    let partial: Settings = from_toml(b"volume = 3").unwrap();
+    assert_eq!(partial.volume, 3);
+    assert!(partial.last_directory.is_empty());
+
+    assert!(matches!(
+        from_toml::<Settings>(b"volume = \"loud\""),
+        Err(CoreConfigError::Parse(_))
+    ));
+
+    let config = CoreConfig::with_value("example", settings);
+    assert_eq!(config.name(), "example");
+    assert_eq!(config.volume, 7);
+}
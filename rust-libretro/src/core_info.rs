@@ -0,0 +1,312 @@
+//! Generating the `.info` file frontends like RetroArch show core details from.
+//!
+//! RetroArch reads the name, supported extensions, firmware and features of a core from
+//! a `.info` file shipped next to it, which otherwise has to be kept in sync with the
+//! core by hand. Instead, [`Core::core_info`] returns a [`CoreInfo`], which is derived
+//! from [`Core::get_info`] and [`Core::supports_no_game`], while [`core_info!`] adds the
+//! authors, license and description from the core’s `Cargo.toml`:
+//!
+//! ```ignore
+//! fn core_info(&self) -> CoreInfo {
+//!     CoreInfo {
+//!         display_name: "Sega - Mega Drive (Example)".to_owned(),
+//!         categories: vec!["Emulator".to_owned()],
+//!         system_name: "Mega Drive".to_owned(),
+//!         savestate: Some(SavestateFeatures::Deterministic),
+//!         features: CoreFeatures::CHEATS | CoreFeatures::CORE_OPTIONS,
+//!         firmware: firmware(),
+//!         ..rust_libretro::core_info!(self.get_info())
+//!     }
+//! }
+//! ```
+//!
+//! The `cargo retro-info` command of the `cargo-retro-info` crate loads a built core
+//! and writes its `.info` file, which it gets through the exported [`CORE_INFO_SYMBOL`].
+//!
+//! [`Core::core_info`]: crate::core::Core::core_info
+//! [`Core::get_info`]: crate::core::Core::get_info
+//! [`Core::supports_no_game`]: crate::core::Core::supports_no_game
+//! [`core_info!`]: crate::core_info!
+use crate::{firmware::FirmwareSet, types::SystemInfo};
+use std::fmt;
+
+/// The symbol of the `const char *(*)(void)` function that returns the `.info` file
+/// of a core, see [`CoreInfo`].
+pub const CORE_INFO_SYMBOL: &str = "rust_libretro_core_info";
+
+bitflags::bitflags! {
+    /// The libretro features listed in the `.info` file.
+    #[derive(Default)]
+    pub struct CoreFeatures: u32 {
+        const CHEATS = 1 << 0;
+        const INPUT_DESCRIPTORS = 1 << 1;
+        const MEMORY_DESCRIPTORS = 1 << 2;
+
+        /// The core uses the frontend’s SRAM handling through `retro_get_memory_data`.
+        const LIBRETRO_SAVES = 1 << 3;
+        const CORE_OPTIONS = 1 << 4;
+        const LOAD_SUBSYSTEM = 1 << 5;
+        const HW_RENDER = 1 << 6;
+        const DISK_CONTROL = 1 << 7;
+        const EXPERIMENTAL = 1 << 8;
+    }
+}
+
+/// What savestates are good for, from least to most capable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SavestateFeatures {
+    /// Saving and loading states works.
+    Basic,
+
+    /// States are complete enough for rewinding and run-ahead.
+    Serialized,
+
+    /// Loading a state and replaying the same input always gives the same result,
+    /// required for netplay.
+    Deterministic,
+}
+
+impl SavestateFeatures {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Basic => "basic",
+            Self::Serialized => "serialized",
+            Self::Deterministic => "deterministic",
+        }
+    }
+}
+
+/// The contents of a `.info` file, see the [module documentation](self).
+///
+/// Empty strings and lists get left out of the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoreInfo {
+    /// The name shown in the list of cores, usually `"<Manufacturer> - <System> (<Core>)"`.
+    /// Defaults to [`CoreInfo::core_name`].
+    pub display_name: String,
+    pub authors: Vec<String>,
+
+    /// Separated by pipes, e.g. `"bin|rom"`.
+    pub supported_extensions: String,
+
+    /// The [`SystemInfo::library_name`].
+    pub core_name: String,
+
+    /// E.g. `"Emulator"` or `"Game"`.
+    pub categories: Vec<String>,
+    pub license: String,
+    pub permissions: String,
+
+    /// The [`SystemInfo::library_version`].
+    pub display_version: String,
+
+    pub manufacturer: String,
+    pub system_name: String,
+    pub system_id: String,
+
+    /// The names of the databases the supported content is listed in,
+    /// e.g. `"Sega - Mega Drive - Genesis"`.
+    pub database: Vec<String>,
+
+    pub supports_no_game: bool,
+    pub needs_fullpath: bool,
+
+    /// [`None`] if the core doesn’t support savestates.
+    pub savestate: Option<SavestateFeatures>,
+    pub features: CoreFeatures,
+
+    /// Listed with their MD5 hashes, if any, in the notes.
+    pub firmware: FirmwareSet,
+    pub notes: Vec<String>,
+    pub description: String,
+}
+
+impl CoreInfo {
+    /// Takes the name, version, extensions and `need_fullpath` from `system_info`.
+    pub fn new(system_info: &SystemInfo) -> Self {
+        Self {
+            core_name: system_info.library_name.to_string_lossy().into_owned(),
+            display_version: system_info.library_version.to_string_lossy().into_owned(),
+            supported_extensions: system_info.valid_extensions.to_string_lossy().into_owned(),
+            needs_fullpath: system_info.need_fullpath,
+
+            ..Default::default()
+        }
+    }
+
+    /// Returns the notes, preceded by the MD5 hashes of the firmware files.
+    fn all_notes(&self) -> Vec<String> {
+        let firmware = self
+            .firmware
+            .files()
+            .iter()
+            .filter(|file| !file.md5.is_empty());
+
+        firmware
+            .map(|file| format!("(!) {} (md5): {}", file.path.display(), file.md5.join(", ")))
+            .chain(self.notes.iter().cloned())
+            .collect()
+    }
+}
+
+/// Writes `key = "value"`, values can’t contain quotes.
+fn write_entry(f: &mut fmt::Formatter, key: &str, value: &str) -> fmt::Result {
+    writeln!(f, "{key} = \"{}\"", value.replace('"', "'"))
+}
+
+/// Writes `key = "value"` unless `value` is empty.
+fn write_optional(f: &mut fmt::Formatter, key: &str, value: &str) -> fmt::Result {
+    if value.is_empty() {
+        return Ok(());
+    }
+
+    write_entry(f, key, value)
+}
+
+/// Writes the `.info` file.
+impl fmt::Display for CoreInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bool_str = |value: bool| if value { "true" } else { "false" };
+        let feature = |feature| bool_str(self.features.contains(feature));
+
+        writeln!(f, "# Software Information")?;
+        if self.display_name.is_empty() {
+            write_entry(f, "display_name", &self.core_name)?;
+        } else {
+            write_entry(f, "display_name", &self.display_name)?;
+        }
+        write_optional(f, "authors", &self.authors.join("|"))?;
+        write_entry(f, "supported_extensions", &self.supported_extensions)?;
+        write_entry(f, "corename", &self.core_name)?;
+        write_optional(f, "categories", &self.categories.join("|"))?;
+        write_optional(f, "license", &self.license)?;
+        write_optional(f, "permissions", &self.permissions)?;
+        write_entry(f, "display_version", &self.display_version)?;
+
+        writeln!(f, "\n# Hardware Information")?;
+        write_optional(f, "manufacturer", &self.manufacturer)?;
+        write_optional(f, "systemname", &self.system_name)?;
+        write_optional(f, "systemid", &self.system_id)?;
+
+        writeln!(f, "\n# Libretro Features")?;
+        write_optional(f, "database", &self.database.join("|"))?;
+        write_entry(f, "supports_no_game", bool_str(self.supports_no_game))?;
+        write_entry(f, "savestate", bool_str(self.savestate.is_some()))?;
+        if let Some(savestate) = self.savestate {
+            write_entry(f, "savestate_features", savestate.as_str())?;
+        }
+        write_entry(f, "cheats", feature(CoreFeatures::CHEATS))?;
+        write_entry(
+            f,
+            "input_descriptors",
+            feature(CoreFeatures::INPUT_DESCRIPTORS),
+        )?;
+        write_entry(
+            f,
+            "memory_descriptors",
+            feature(CoreFeatures::MEMORY_DESCRIPTORS),
+        )?;
+        write_entry(f, "libretro_saves", feature(CoreFeatures::LIBRETRO_SAVES))?;
+        write_entry(f, "core_options", feature(CoreFeatures::CORE_OPTIONS))?;
+        if self.features.contains(CoreFeatures::CORE_OPTIONS) {
+            // The options get declared in the version 2 format
+            write_entry(f, "core_options_version", "2.0")?;
+        }
+        write_entry(f, "load_subsystem", feature(CoreFeatures::LOAD_SUBSYSTEM))?;
+        write_entry(f, "hw_render", feature(CoreFeatures::HW_RENDER))?;
+        write_entry(f, "needs_fullpath", bool_str(self.needs_fullpath))?;
+        write_entry(f, "disk_control", feature(CoreFeatures::DISK_CONTROL))?;
+        write_entry(f, "is_experimental", feature(CoreFeatures::EXPERIMENTAL))?;
+
+        let files = self.firmware.files();
+        if !files.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "firmware_count = {}", files.len())?;
+
+            for (index, file) in files.iter().enumerate() {
+                let path = file.path.to_string_lossy();
+
+                write_entry(
+                    f,
+                    &format!("firmware{index}_desc"),
+                    &format!("{path} ({})", file.name),
+                )?;
+                write_entry(f, &format!("firmware{index}_path"), &path)?;
+                write_entry(f, &format!("firmware{index}_opt"), bool_str(file.optional))?;
+            }
+        }
+
+        let notes = self.all_notes();
+        if !notes.is_empty() || !self.description.is_empty() {
+            writeln!(f)?;
+            write_optional(f, "notes", &notes.join("|"))?;
+            write_optional(f, "description", &self.description)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn core_info_file() {
+    use crate::{firmware::Firmware, proc::c_str};
+
+    let system_info = SystemInfo {
+        library_name: c_str!("Example").into(),
+        library_version: c_str!("1.2.3").into(),
+        valid_extensions: c_str!("bin|rom").into(),
+
+        need_fullpath: true,
+        block_extract: false,
+    };
+
+    let info = CoreInfo {
+        authors: vec!["Jane Doe".to_owned(), "John Doe".to_owned()],
+        savestate: Some(SavestateFeatures::Deterministic),
+        features: CoreFeatures::CHEATS | CoreFeatures::CORE_OPTIONS,
+        firmware: FirmwareSet::new()
+            .with(Firmware::new("BIOS", "bios.bin").md5("0123456789ABCDEF0123456789ABCDEF"))
+            .with(Firmware::new("Boot ROM", "boot.rom").optional()),
+        description: "An \"example\" core.".to_owned(),
+        ..CoreInfo::new(&system_info)
+    };
+
+    let expected = r#"# Software Information
+display_name = "Example"
+authors = "Jane Doe|John Doe"
+supported_extensions = "bin|rom"
+corename = "Example"
+display_version = "1.2.3"
+
+# Hardware Information
+
+# Libretro Features
+supports_no_game = "false"
+savestate = "true"
+savestate_features = "deterministic"
+cheats = "true"
+input_descriptors = "false"
+memory_descriptors = "false"
+libretro_saves = "false"
+core_options = "true"
+core_options_version = "2.0"
+load_subsystem = "false"
+hw_render = "false"
+needs_fullpath = "true"
+disk_control = "false"
+is_experimental = "false"
+
+firmware_count = 2
+firmware0_desc = "bios.bin (BIOS)"
+firmware0_path = "bios.bin"
+firmware0_opt = "false"
+firmware1_desc = "boot.rom (Boot ROM)"
+firmware1_path = "boot.rom"
+firmware1_opt = "true"
+
+notes = "(!) bios.bin (md5): 0123456789abcdef0123456789abcdef"
+description = "An 'example' core."
+"#;
+
+    assert_eq!(info.to_string(), expected);
+}
@@ -2,9 +2,41 @@
 //!
 //! It stores runtime information provided by the libretro frontend without interfering with your [`Core`] implementation.
 use crate::*;
-use std::sync::{Arc, RwLock};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, RwLock},
+};
 
-pub type Interfaces = Arc<RwLock<InterfaceList>>;
+/// The [`InterfaceList`] shared by the wrapper, the contexts and the audio callbacks.
+#[derive(Debug, Default, Clone)]
+pub struct Interfaces(Arc<RwLock<InterfaceList>>);
+
+// SAFETY: The [`InterfaceList`] isn’t `Send` or `Sync` because of the raw pointers and
+// closures it stores for the frontend, which are only ever accessed through its lock.
+// Only the audio callbacks use it outside of the thread the frontend calls the core on.
+unsafe impl Send for Interfaces {}
+unsafe impl Sync for Interfaces {}
+
+impl Interfaces {
+    fn new(interfaces: InterfaceList) -> Self {
+        // Made `Send` and `Sync` above
+        #[allow(clippy::arc_with_non_send_sync)]
+        Self(Arc::new(RwLock::new(interfaces)))
+    }
+}
+
+impl Deref for Interfaces {
+    type Target = RwLock<InterfaceList>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// How often the [`FrameHints`] that only change through the frontend’s settings get
+/// fetched, in frames. Once per second at 60 FPS.
+const FRAME_HINTS_INTERVAL: u32 = 60;
 
 #[derive(Debug, Default)]
 #[proc::unstable]
@@ -13,9 +45,104 @@ pub struct InterfaceList {
     pub perf_interface: PerfCounters,
     pub rumble_interface: Option<retro_rumble_interface>,
 
+    /// See [`GenericContext::set_rumble`].
+    pub rumble_mixer: rumble::RumbleMixer,
+    pub message_queue: MessageQueue,
+
+    /// Values returned by [`GenericContext::get_variable_cached`].
+    /// Cleared whenever the frontend reports updated core options.
+    pub variable_cache: HashMap<String, Option<String>>,
+
+    /// Functions registered with [`GenericContext::register_proc`].
+    pub proc_addresses: HashMap<CString, retro_proc_address_t>,
+
+    /// The AV info last reported to the frontend.
+    pub av_info: Option<retro_system_av_info>,
+
+    /// The pixel format last accepted by the frontend, [`None`] if the core uses the default.
+    pub pixel_format: Option<PixelFormat>,
+
+    /// The rotation applied by [`RunContext::draw_frame`] because the frontend
+    /// refused [`LoadGameContext::set_rotation`].
+    pub software_rotation: Option<Rotation>,
+
+    /// Rotates the frames for [`InterfaceList::software_rotation`].
+    pub rotated_surface: util::rotate::RotatedSurface,
+
+    /// See [`SetEnvironmentContext::set_options_compat_mode`].
+    pub options_compat: OptionsCompat,
+
+    /// The version returned by [`SetEnvironmentContext::core_options_version`] when the options got registered.
+    pub options_version: Option<u32>,
+
+    /// The options declared by the core, see [`CoreOptions::option_definitions`].
+    pub option_definitions: CoreOptionDefinitions,
+
+    /// Values set with [`GenericContext::set_option_values`], by option.
+    pub option_values: HashMap<String, options::OptionValues>,
+
+    /// The definitions last submitted by [`GenericContext::set_option_values`], kept alive for the frontend.
+    pub runtime_options: Option<options::RuntimeOptions>,
+
+    /// Set by [`GenericContext::set_option_values`] until the visibility rules have been reapplied.
+    pub options_resubmitted: bool,
+
+    /// See [`GenericContext::set_sram_tracker`].
+    pub sram_tracker: Option<sram::SramTracker>,
+
+    /// The callbacks filled in by the frontend in [`LoadGameContext::enable_hw_render`].
+    pub hw_render: Option<retro_hw_render_callback>,
+
+    /// See [`GenericContext::register_hw_resource`].
+    pub hw_resources: hw_resources::HwResourceManager,
+
+    /// See [`GenericContext::on_option_change`].
+    pub option_handlers: options::OptionHandlers,
+
+    /// See [`GenericContext::update_input_descriptors`].
+    pub input_descriptors: input_descriptor_set::InputDescriptorSet,
+
+    /// See [`GenericContext::request_text_input`].
+    pub text_input: Option<text_input::TextInputRequest>,
+
+    /// See [`GenericContext::set_key_repeat`].
+    pub key_repeat: Option<key_repeat::KeyRepeat>,
+
+    /// See [`GenericContext::set_firmware`].
+    pub firmware: Option<firmware::FirmwareSet>,
+
+    /// See [`GenericContext::get_firmware_path`].
+    pub firmware_paths: firmware::FirmwarePaths,
+
+    /// See [`GenericContext::set_state_migrations`].
+    pub state_migrations: Option<savestate::StateMigrations>,
+
+    /// See [`GenericContext::set_frame_watchdog`].
+    pub frame_watchdog: Option<timing::FrameWatchdog>,
+
+    /// See [`av_dump`].
+    #[cfg(feature = "av-dump")]
+    pub av_dump: Option<av_dump::AvDump>,
+
+    /// Whether the frontend accepted [`GenericContext::enable_audio_state`] or [`GenericContext::enable_audio_callback`].
+    pub audio_callback_enabled: bool,
+
+    /// See [`AudioContext::generate_audio`].
+    pub audio_pacer: audio_pacing::AudioPacer,
+
+    /// Audio frames written outside of [`Core::on_write_audio`] while the audio callback is enabled.
+    /// They get passed to the frontend at the start of the next audio callback.
+    pub queued_audio: Vec<i16>,
+
     #[unstable(feature = "env-commands")]
     pub camera_interface: Option<retro_camera_callback>,
 
+    /// The reference passed to [`LoadGameContext::enable_frame_time_callback`].
+    pub frame_time_reference: Option<i64>,
+
+    /// See [`GenericContext::camera_state`].
+    pub camera_state: CameraState,
+
     #[unstable(feature = "env-commands")]
     pub led_interface: Option<retro_led_interface>,
 
@@ -29,6 +156,97 @@ pub struct InterfaceList {
     pub vfs_interface_info: VfsInterfaceInfo,
 }
 
+/// The per-frame state of the [`InterfaceList`] that [`retro_run`] acts on,
+/// taken with a single lock by [`CoreWrapper::begin_frame`].
+#[derive(Debug, Default)]
+pub(crate) struct FrameSnapshot {
+    /// Whether option handlers have been registered since the last frame.
+    pub options_pending: bool,
+
+    /// See [`InterfaceList::options_resubmitted`].
+    pub options_resubmitted: bool,
+
+    /// The next queued message, see [`GenericContext::show_simple_message`].
+    pub message: Option<(String, u32)>,
+
+    /// The key press synthesized by [`GenericContext::set_key_repeat`].
+    pub key_repeat: Option<key_repeat::KeyEvent>,
+
+    /// Whether the [`SramTracker`](sram::SramTracker) wants the save RAM to be checked.
+    pub sram_due: bool,
+
+    /// Whether a text input request is active, which may finish during the frame.
+    pub text_input: bool,
+
+    /// Whether a [`FrameWatchdog`](timing::FrameWatchdog) has been set.
+    pub watchdog: bool,
+
+    /// See [`InterfaceList::frame_time_reference`].
+    pub frame_time_reference: Option<i64>,
+}
+
+/// Where the core is in its lifecycle, according to the calls made by the frontend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum CoreState {
+    /// Before [`retro_init`] and after [`retro_deinit`].
+    #[default]
+    Uninitialized,
+
+    /// After [`retro_init`] or [`retro_unload_game`].
+    Initialized,
+
+    /// After a successful [`retro_load_game`] or [`retro_load_game_special`].
+    GameLoaded,
+}
+
+/// The API functions whose call order gets validated by [`CoreWrapper::check_call`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ApiCall {
+    Init,
+    Deinit,
+    LoadGame,
+    UnloadGame,
+    Run,
+    Reset,
+    SerializeSize,
+    Serialize,
+    Unserialize,
+    CheatReset,
+    CheatSet,
+    GetMemory,
+}
+
+impl ApiCall {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Init => "retro_init",
+            Self::Deinit => "retro_deinit",
+            Self::LoadGame => "retro_load_game",
+            Self::UnloadGame => "retro_unload_game",
+            Self::Run => "retro_run",
+            Self::Reset => "retro_reset",
+            Self::SerializeSize => "retro_serialize_size",
+            Self::Serialize => "retro_serialize",
+            Self::Unserialize => "retro_unserialize",
+            Self::CheatReset => "retro_cheat_reset",
+            Self::CheatSet => "retro_cheat_set",
+            Self::GetMemory => "retro_get_memory_*",
+        }
+    }
+}
+
+impl CoreState {
+    /// Whether the frontend may make `call` in this state.
+    pub(crate) fn allows(self, call: ApiCall) -> bool {
+        match call {
+            ApiCall::Init => self == Self::Uninitialized,
+            ApiCall::Deinit => self != Self::Uninitialized,
+            ApiCall::LoadGame => self == Self::Initialized,
+            _ => self == Self::GameLoaded,
+        }
+    }
+}
+
 /// Holds the core instance as well as runtime information from the libretro frontend.
 ///
 /// This struct gets used in the code generated by the [`retro_core!()`]-macro.
@@ -71,24 +289,77 @@ pub(crate) struct CoreWrapper {
     /// will still use the higher level [`RETRO_DEVICE_JOYPAD`] to request input.
     pub(crate) input_state_callback: retro_input_state_t,
 
-    pub(crate) can_dupe: bool,
+    /// The hints passed to [`RunContext::hints`], see [`CoreWrapper::update_frame_hints`].
+    pub(crate) hints: FrameHints,
+
+    /// The number of frames until the hints that rarely change get fetched again.
+    pub(crate) hints_refresh_in: u32,
+
+    /// Whether [`CoreWrapper::update_video_enabled`] fetches [`FrameHints::video_enabled`].
+    #[cfg(feature = "unstable-env-commands")]
+    pub(crate) fetch_video_enabled: bool,
+
+    /// [`InterfaceList::software_rotation`], which only changes while loading a game.
+    pub(crate) software_rotation: Option<Rotation>,
+
     pub(crate) had_frame: bool,
     pub(crate) last_width: u32,
     pub(crate) last_height: u32,
-    pub(crate) last_pitch: u64,
+    pub(crate) last_pitch: size_t,
+
+    /// Set by [`RunContext::set_frame_capture`].
+    pub(crate) capture_frames: bool,
+    pub(crate) captured_frame: Option<CapturedFrame>,
+
+    /// Whether the core presented a hardware frame in the current [`retro_run`].
+    pub(crate) hw_frame_presented: bool,
+
+    /// See [`RunContext::set_frame_dedup`].
+    pub(crate) frame_dedup: Option<FrameDeduplicator>,
 
     pub(crate) supports_bitmasks: bool,
+    pub(crate) joypad_transitions: Vec<JoypadTransitions>,
+
+    /// Device types set via [`retro_set_controller_port_device`], indexed by port.
+    pub(crate) port_devices: Vec<u32>,
+
+    /// The visibility last reported for the options with [`OptionVisibilityRule`]s.
+    pub(crate) option_visibility: HashMap<&'static str, bool>,
 
     pub(crate) frame_delta: Option<i64>,
 
+    /// When the last frame with a [`frame_delta`](Self::frame_delta) started,
+    /// see [`timing::FrameTime::classify`].
+    pub(crate) last_run: Option<std::time::Instant>,
+
+    /// The `RETRO_THROTTLE_*` mode last fetched by [`CoreWrapper::update_fastforwarding`],
+    /// if the frontend supports it.
+    pub(crate) throttle_mode: Option<u32>,
+
+    /// Whether [`FrameHints::max_users`] has been reported to [`Core::on_max_users_changed`].
+    pub(crate) max_users_reported: bool,
+
     pub(crate) interfaces: Interfaces,
 
+    /// Guards the [`Core`] against frontends calling the API out of order.
+    pub(crate) state: CoreState,
+
+    /// The last call that passed [`CoreWrapper::check_call`], for logging violations.
+    pub(crate) last_call: Option<ApiCall>,
+
     /// The wrapped [`Core`] implementation.
-    pub(crate) core: Box<dyn Core>,
+    pub(crate) core: Box<dyn AnyCore>,
 }
 
 impl CoreWrapper {
     pub(crate) fn new<C: 'static + Core>(core: C) -> Self {
+        let interfaces = InterfaceList {
+            option_definitions: C::option_definitions(),
+            #[cfg(feature = "metrics")]
+            proc_addresses: metrics::proc_addresses(),
+            ..Default::default()
+        };
+
         Self {
             environment_set: false,
             environment_callback: None,
@@ -102,17 +373,35 @@ impl CoreWrapper {
             input_poll_callback: None,
             input_state_callback: None,
 
-            interfaces: Arc::new(RwLock::new(InterfaceList::default())),
+            interfaces: Interfaces::new(interfaces),
 
-            can_dupe: false,
+            state: CoreState::Uninitialized,
+            last_call: None,
+
+            hints: FrameHints::default(),
+            hints_refresh_in: 0,
+            #[cfg(feature = "unstable-env-commands")]
+            fetch_video_enabled: false,
+            software_rotation: None,
             had_frame: false,
             last_width: 0,
             last_height: 0,
             last_pitch: 0,
 
+            capture_frames: false,
+            captured_frame: None,
+            hw_frame_presented: false,
+            frame_dedup: None,
+
             frame_delta: None,
+            last_run: None,
+            throttle_mode: None,
+            max_users_reported: false,
 
             supports_bitmasks: false,
+            joypad_transitions: Vec::new(),
+            port_devices: Vec::new(),
+            option_visibility: HashMap::new(),
         }
     }
 
@@ -124,11 +413,13 @@ impl CoreWrapper {
     #[inline(always)]
     pub(crate) fn on_set_audio_sample(&mut self, arg1: retro_audio_sample_t) {
         self.audio_sample_callback = arg1;
+        audio_state::AudioThread::set_audio_sample_callback(arg1);
     }
 
     #[inline(always)]
     pub(crate) fn on_set_audio_sample_batch(&mut self, arg1: retro_audio_sample_batch_t) {
         self.audio_sample_batch_callback = arg1;
+        audio_state::AudioThread::set_audio_sample_batch_callback(arg1);
     }
 
     #[inline(always)]
@@ -136,8 +427,358 @@ impl CoreWrapper {
         self.input_poll_callback = arg1;
     }
 
+    /// Returns [`false`] and logs the violation if the frontend must not make `call` right now.
+    ///
+    /// The caller skips the call and returns a safe default in that case,
+    /// so the [`Core`] never sees calls in an order the libretro API does not allow.
+    pub(crate) fn check_call(&mut self, call: ApiCall) -> bool {
+        if !self.state.allows(call) {
+            #[cfg(feature = "log")]
+            log::warn!(
+                "Ignoring {} in state {:?}, the previous call was {}",
+                call.name(),
+                self.state,
+                self.last_call.map_or("none", ApiCall::name)
+            );
+
+            return false;
+        }
+
+        self.last_call = Some(call);
+        true
+    }
+
+    /// Drops cached environment query results that may have become stale.
+    pub(crate) fn invalidate_environment_cache(&mut self) {
+        self.interfaces.write().unwrap().variable_cache.clear();
+    }
+
+    /// Notifies the core about changed core options through [`Core::on_options_changed`]
+    /// and the handlers registered with [`GenericContext::on_option_change`].
+    pub(crate) fn notify_options_changed(&mut self) {
+        self.invalidate_environment_cache();
+
+        let mut ctx = OptionsChangedContext::new(&self.environment_callback, &self.interfaces);
+
+        self.core.on_options_changed(&mut ctx);
+        self.dispatch_option_handlers();
+    }
+
+    /// Calls the option handlers whose option changed since their last call.
+    pub(crate) fn dispatch_option_handlers(&mut self) {
+        // Handlers must not run while the interfaces are locked
+        let mut handlers = std::mem::take(&mut self.interfaces.write().unwrap().option_handlers);
+
+        let ctx = GenericContext::new(&self.environment_callback, &self.interfaces);
+        handlers.dispatch(self.core.as_any_mut(), |key| ctx.get_variable_cached(key));
+
+        self.interfaces.write().unwrap().option_handlers = handlers;
+    }
+
+    /// Passes a key press or release to the active text input request or to [`Core::on_keyboard_event`].
+    pub(crate) fn dispatch_keyboard_event(&mut self, down: bool, event: key_repeat::KeyEvent) {
+        // Keys typed while a text input request is active belong to it
+        if let Some(request) = self.interfaces.write().unwrap().text_input.as_mut() {
+            if down {
+                request.input.handle_key(event.keycode, event.character);
+            }
+
+            return;
+        }
+
+        self.core
+            .on_keyboard_event(down, event.keycode, event.character, event.key_modifiers);
+    }
+
+    /// Calls the callback of a finished [`GenericContext::request_text_input`] request.
+    pub(crate) fn dispatch_text_input(&mut self) {
+        let finished = {
+            let mut interfaces = self.interfaces.write().unwrap();
+
+            match &interfaces.text_input {
+                Some(request) if !request.input.is_active() => interfaces.text_input.take(),
+                _ => None,
+            }
+        };
+
+        // The callback may start another request
+        if let Some(request) = finished {
+            request.finish(self.core.as_any_mut());
+        }
+    }
+
+    /// Shows or hides the options with [`OptionVisibilityRule`]s
+    /// according to the devices in [`CoreWrapper::port_devices`].
+    pub(crate) fn update_option_visibility(&mut self) {
+        let rules = self.core.option_visibility_rules();
+        if rules.is_empty() {
+            return;
+        }
+
+        let mut visibility = HashMap::<&'static str, bool>::new();
+        for rule in rules {
+            *visibility.entry(rule.key).or_default() |= rule.matches(&self.port_devices);
+        }
+
+        let ctx = GenericContext::new(&self.environment_callback, &self.interfaces);
+
+        for (key, visible) in visibility {
+            if self.option_visibility.get(key) == Some(&visible) {
+                continue;
+            }
+
+            let c_key = match CString::new(key) {
+                Ok(c_key) => c_key,
+                Err(_) => continue,
+            };
+
+            match ctx.set_core_options_display(retro_core_option_display {
+                key: c_key.as_ptr(),
+                visible,
+            }) {
+                Ok(()) => {
+                    self.option_visibility.insert(key, visible);
+                }
+                Err(_err) => {
+                    #[cfg(feature = "log")]
+                    log::debug!("Failed to update the visibility of {key}: {_err}");
+                }
+            }
+        }
+    }
+
+    /// Checks the registered [`FirmwareSet`](firmware::FirmwareSet) before a game gets loaded
+    /// and shows a message listing the problems if a required file has been rejected.
+    pub(crate) fn check_firmware(&mut self) -> Result<(), firmware::FirmwareError> {
+        let set = match self.interfaces.read().unwrap().firmware.clone() {
+            Some(set) => set,
+            None => return Ok(()),
+        };
+
+        let ctx = GenericContext::new(&self.environment_callback, &self.interfaces);
+
+        let result = match ctx.get_system_directory() {
+            Ok(directory) => set.check(directory),
+            Err(_) => Err(firmware::FirmwareError {
+                problems: vec![firmware::FirmwareProblem::NoSystemDirectory],
+            }),
+        };
+
+        match result {
+            Ok(paths) => {
+                self.interfaces.write().unwrap().firmware_paths = paths;
+                Ok(())
+            }
+            Err(err) => {
+                let _ = ctx.show_simple_message(&format!("Missing firmware: {err}"), 600);
+                Err(err)
+            }
+        }
+    }
+
+    /// Fetches the [`FrameHints`] every [`FRAME_HINTS_INTERVAL`] frames, including
+    /// the fast-forwarding state through [`CoreWrapper::update_fastforwarding`]
+    /// and the number of ports through [`CoreWrapper::update_max_users`].
+    ///
+    /// The only cadence for values the frontend may change while running,
+    /// fetch them here instead of in [`retro_run`].
+    ///
+    /// [`FrameHints::video_enabled`] is the exception, see [`CoreWrapper::update_video_enabled`].
+    pub(crate) fn update_frame_hints(&mut self) {
+        match self.hints_refresh_in.checked_sub(1) {
+            Some(frames) => self.hints_refresh_in = frames,
+            None => self.refresh_frame_hints(),
+        }
+
+        #[cfg(feature = "unstable-env-commands")]
+        self.update_video_enabled();
+    }
+
+    fn refresh_frame_hints(&mut self) {
+        self.hints_refresh_in = FRAME_HINTS_INTERVAL - 1;
+
+        unsafe {
+            #[allow(deprecated)]
+            let overscan = environment::get_overscan(self.environment_callback);
+
+            self.hints.overscan = overscan;
+            self.hints.can_dupe = environment::can_dupe(self.environment_callback);
+        }
+
+        #[cfg(feature = "unstable-env-commands")]
+        {
+            // Tried again in case the core became a split-run core
+            self.fetch_video_enabled = self.core.as_split_run().is_some();
+        }
+
+        self.update_fastforwarding();
+        self.update_max_users();
+    }
+
+    /// Fetches [`FrameHints::video_enabled`], which changes from frame to frame with
+    /// run-ahead. Only [`SplitRunCore`](split_run::SplitRunCore)s make use of it,
+    /// so other cores and frontends that don’t support it never get asked.
+    #[cfg(feature = "unstable-env-commands")]
+    fn update_video_enabled(&mut self) {
+        if !self.fetch_video_enabled {
+            return;
+        }
+
+        let enable = unsafe {
+            environment::get::<u32>(
+                self.environment_callback,
+                RETRO_ENVIRONMENT_GET_AUDIO_VIDEO_ENABLE,
+            )
+        };
+
+        match enable {
+            Some((bits, true)) => {
+                self.hints.video_enabled = AudioVideoEnable::from_bits_truncate(bits)
+                    .contains(AudioVideoEnable::ENABLE_VIDEO);
+            }
+            _ => {
+                self.fetch_video_enabled = false;
+                self.hints.video_enabled = true;
+            }
+        }
+    }
+
+    /// Calls [`Core::on_fastforward_changed`] if the frontend started or stopped fast-forwarding.
+    ///
+    /// Prefers the throttle state and falls back to [`environment::get_fastforwarding`].
+    fn update_fastforwarding(&mut self) {
+        self.throttle_mode = unsafe { environment::get_throttle_state(self.environment_callback) }
+            .ok()
+            .map(|state| state.mode);
+
+        let fastforwarding = match self.throttle_mode {
+            Some(mode) => mode == RETRO_THROTTLE_FAST_FORWARD,
+            None => unsafe { environment::get_fastforwarding(self.environment_callback) },
+        };
+
+        if fastforwarding == self.hints.fastforwarding {
+            return;
+        }
+
+        self.hints.fastforwarding = fastforwarding;
+
+        let mut ctx = GenericContext::new(&self.environment_callback, &self.interfaces);
+        self.core.on_fastforward_changed(fastforwarding, &mut ctx);
+    }
+
+    /// Calls [`Core::on_max_users_changed`] if the number of ports changed since the last check.
+    fn update_max_users(&mut self) {
+        let mut ctx = GenericContext::new(&self.environment_callback, &self.interfaces);
+        let max_users = ctx.get_max_users();
+
+        if self.max_users_reported && self.hints.max_users == max_users {
+            return;
+        }
+
+        self.max_users_reported = true;
+        self.hints.max_users = max_users;
+        self.core.on_max_users_changed(max_users, &mut ctx);
+    }
+
+    /// Reports the duration of [`Core::on_run`] to the [`FrameWatchdog`](timing::FrameWatchdog).
+    pub(crate) fn update_frame_watchdog(&mut self, elapsed: std::time::Duration) {
+        let overrun = {
+            let mut interfaces = self.interfaces.write().unwrap();
+            let fps = interfaces.av_info.map_or(0.0, |av_info| av_info.timing.fps);
+
+            match interfaces.frame_watchdog.as_mut() {
+                Some(watchdog) => watchdog.record(elapsed, fps),
+                None => false,
+            }
+        };
+
+        if overrun {
+            #[cfg(feature = "log")]
+            log::warn!("Frame took {elapsed:?}, which exceeds the frame budget");
+
+            let mut ctx = GenericContext::new(&self.environment_callback, &self.interfaces);
+            self.core.on_frame_overrun(elapsed, &mut ctx);
+        }
+    }
+
+    /// Takes everything [`retro_run`] needs from the [`InterfaceList`] with a single lock,
+    /// advancing the per-frame counters of the message queue, key repeat and SRAM tracker.
+    pub(crate) fn begin_frame(&mut self) -> FrameSnapshot {
+        let mut interfaces = self.interfaces.write().unwrap();
+        let interfaces = &mut *interfaces;
+
+        FrameSnapshot {
+            options_pending: interfaces.option_handlers.has_pending(),
+            options_resubmitted: std::mem::take(&mut interfaces.options_resubmitted),
+            message: interfaces.message_queue.tick(),
+            key_repeat: interfaces
+                .key_repeat
+                .as_mut()
+                .and_then(|repeat| repeat.tick()),
+            sram_due: interfaces
+                .sram_tracker
+                .as_mut()
+                .is_some_and(|tracker| tracker.tick()),
+            text_input: interfaces.text_input.is_some(),
+            watchdog: interfaces.frame_watchdog.is_some(),
+            frame_time_reference: interfaces.frame_time_reference,
+        }
+    }
+
+    /// Displays the message taken from the queue by [`CoreWrapper::begin_frame`].
+    pub(crate) fn show_queued_message(&mut self, text: &str, frames: u32) {
+        if unsafe { environment::set_message(self.environment_callback, text, frames) }.is_err() {
+            self.interfaces.write().unwrap().message_queue.clear();
+        }
+    }
+
+    /// Checks the save RAM with the registered [`SramTracker`](sram::SramTracker) and
+    /// calls [`Core::on_sram_dirty`] if it changed.
+    ///
+    /// Called when [`FrameSnapshot::sram_due`] is set.
+    pub(crate) fn check_sram(&mut self) {
+        let mut ctx = GenericContext::new(&self.environment_callback, &self.interfaces);
+
+        let data = self.core.get_memory_data(RETRO_MEMORY_SAVE_RAM, &mut ctx);
+        let size = self.core.get_memory_size(RETRO_MEMORY_SAVE_RAM, &mut ctx);
+
+        if data.is_null() || size == 0 {
+            return;
+        }
+
+        let sram = unsafe { std::slice::from_raw_parts(data as *const u8, size as usize) };
+
+        let dirty = match self.interfaces.write().unwrap().sram_tracker.as_mut() {
+            Some(tracker) => tracker.check(sram),
+            None => false,
+        };
+
+        if dirty {
+            self.core.on_sram_dirty(&mut ctx);
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn on_set_input_state(&mut self, arg1: retro_input_state_t) {
         self.input_state_callback = arg1;
     }
 }
+
+#[test]
+fn core_state() {
+    use ApiCall::*;
+
+    assert!(CoreState::Uninitialized.allows(Init));
+    assert!(!CoreState::Uninitialized.allows(Run));
+    assert!(!CoreState::Uninitialized.allows(Deinit));
+
+    assert!(CoreState::Initialized.allows(LoadGame));
+    assert!(!CoreState::Initialized.allows(Init));
+    assert!(!CoreState::Initialized.allows(Serialize));
+    assert!(!CoreState::Initialized.allows(UnloadGame));
+
+    assert!(CoreState::GameLoaded.allows(Run));
+    assert!(CoreState::GameLoaded.allows(Unserialize));
+    assert!(CoreState::GameLoaded.allows(Deinit));
+    assert!(!CoreState::GameLoaded.allows(LoadGame));
+}
@@ -0,0 +1,379 @@
+//! Overlays that help debugging cores.
+//!
+//! A [`ControllerWidget`] draws a gamepad into a corner of the frame, highlighting the
+//! pressed buttons and showing the position of the analog sticks. It shows what the
+//! frontend actually reports, which helps tracking down input mapping issues:
+//!
+//! ```ignore
+//! fn on_run(&mut self, ctx: &mut RunContext, _delta_us: Option<i64>) {
+//!     self.machine.step(ctx);
+//!     self.machine.draw(&mut self.frame);
+//!
+//!     if self.debug_input {
+//!         let state = ControllerState::read(ctx, 0);
+//!         self.widget.draw(&mut self.frame, WIDTH, &state);
+//!     }
+//!
+//!     ctx.draw_frame(bytemuck::cast_slice(&self.frame), WIDTH, HEIGHT, WIDTH as u64 * 4);
+//! }
+//! ```
+//!
+//! Requires the `debug-widgets` feature.
+use crate::{
+    contexts::RunContext,
+    proc::include_image_xrgb8888,
+    sys::*,
+    types::{blend, JoypadState, StaticImage},
+};
+
+const BODY: StaticImage = include_image_xrgb8888!("src/debug/img/body.png");
+const BUMPER: StaticImage = include_image_xrgb8888!("src/debug/img/bumper.png");
+const BUTTON: StaticImage = include_image_xrgb8888!("src/debug/img/button.png");
+const DPAD: StaticImage = include_image_xrgb8888!("src/debug/img/dpad.png");
+const HOME: StaticImage = include_image_xrgb8888!("src/debug/img/home.png");
+const JOYSTICK: StaticImage = include_image_xrgb8888!("src/debug/img/joystick.png");
+const START: StaticImage = include_image_xrgb8888!("src/debug/img/start.png");
+const TRIGGER: StaticImage = include_image_xrgb8888!("src/debug/img/trigger.png");
+
+/// The triggers stick out of the top of the body.
+const BODY_Y: i32 = 5;
+
+/// How far the sticks move at full deflection, in pixels.
+const STICK_RANGE: f32 = 10.0;
+
+/// The input of a port as shown by a [`ControllerWidget`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControllerState {
+    pub buttons: JoypadState,
+
+    /// The X and Y position of the left stick from `-1.0` to `1.0`,
+    /// positive values pointing right and down.
+    pub left_stick: (f32, f32),
+
+    /// See [`ControllerState::left_stick`].
+    pub right_stick: (f32, f32),
+}
+
+impl ControllerState {
+    /// Reads the joypad and analog sticks of `port`.
+    pub fn read(ctx: &RunContext, port: u32) -> Self {
+        let axis = |index: u32, id: u32| {
+            ctx.get_input_state(port, RETRO_DEVICE_ANALOG, index, id) as f32 / 32767.0
+        };
+        let stick = |index: u32| {
+            (
+                axis(index, RETRO_DEVICE_ID_ANALOG_X),
+                axis(index, RETRO_DEVICE_ID_ANALOG_Y),
+            )
+        };
+
+        Self {
+            buttons: ctx.get_joypad_bitmask(port, 0),
+            left_stick: stick(RETRO_DEVICE_INDEX_ANALOG_LEFT),
+            right_stick: stick(RETRO_DEVICE_INDEX_ANALOG_RIGHT),
+        }
+    }
+}
+
+/// The corner of the frame a [`ControllerWidget`] gets drawn into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// How a sprite gets turned before drawing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Turn {
+    None,
+    MirrorX,
+    MirrorY,
+    /// Rotated by 90° clockwise.
+    RotateRight,
+    /// Rotated by 90° clockwise and mirrored horizontally.
+    Transpose,
+}
+
+/// A part of the controller, positioned relative to the top left corner of the widget.
+struct Sprite {
+    image: &'static StaticImage,
+    turn: Turn,
+    x: i32,
+    y: i32,
+    active: bool,
+}
+
+impl Sprite {
+    fn new(image: &'static StaticImage, x: i32, y: i32, active: bool) -> Self {
+        Self {
+            image,
+            turn: Turn::None,
+            x,
+            y: BODY_Y + y,
+            active,
+        }
+    }
+
+    fn turned(self, turn: Turn) -> Self {
+        Self { turn, ..self }
+    }
+
+    /// The width and height after turning the image.
+    fn size(&self) -> (u32, u32) {
+        match self.turn {
+            Turn::None | Turn::MirrorX | Turn::MirrorY => (self.image.width, self.image.height),
+            Turn::RotateRight | Turn::Transpose => (self.image.height, self.image.width),
+        }
+    }
+
+    /// The pixel at `x`, `y` of the turned image.
+    fn get(&self, x: u32, y: u32) -> Option<u32> {
+        let (width, height) = (self.image.width, self.image.height);
+
+        match self.turn {
+            Turn::None => self.image.get(x, y),
+            Turn::MirrorX => self.image.get(width.checked_sub(x + 1)?, y),
+            Turn::MirrorY => self.image.get(x, height.checked_sub(y + 1)?),
+            Turn::RotateRight => self.image.get(y, height.checked_sub(x + 1)?),
+            Turn::Transpose => self.image.get(y, x),
+        }
+    }
+}
+
+/// Draws a gamepad visualizing a [`ControllerState`], see the [module documentation](self).
+///
+/// The widget is 416×287 pixels at a scale of `1.0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControllerWidget {
+    corner: Corner,
+    margin: u32,
+    scale: f32,
+    color: u32,
+}
+
+impl Default for ControllerWidget {
+    fn default() -> Self {
+        Self::new(Corner::default())
+    }
+}
+
+impl ControllerWidget {
+    pub fn new(corner: Corner) -> Self {
+        Self {
+            corner,
+            margin: 4,
+            scale: 1.0,
+            color: 0x00_FF_00,
+        }
+    }
+
+    /// Scales the widget, e.g. `0.5` to fit into the corner of a small frame.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale.max(0.05);
+        self
+    }
+
+    /// The distance to the edges of the frame in pixels, `4` by default.
+    pub fn with_margin(mut self, margin: u32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Tints pressed buttons in an `XRGB8888` color, green by default.
+    pub fn with_color(mut self, color: u32) -> Self {
+        self.color = color & 0x00FF_FFFF;
+        self
+    }
+
+    /// The width and height of the widget in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (
+            self.scaled(BODY.width as i32) as u32,
+            self.scaled(BODY_Y + BODY.height as i32) as u32,
+        )
+    }
+
+    /// Draws the widget into its corner of an `XRGB8888` frame of `frame_width` pixels per row.
+    pub fn draw(&self, frame: &mut [u32], frame_width: u32, state: &ControllerState) {
+        let frame_height = frame.len().checked_div(frame_width as usize).unwrap_or(0) as i32;
+        let (width, height) = self.size();
+        let margin = self.margin as i32;
+
+        let x = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => margin,
+            Corner::TopRight | Corner::BottomRight => frame_width as i32 - width as i32 - margin,
+        };
+        let y = match self.corner {
+            Corner::TopLeft | Corner::TopRight => margin,
+            Corner::BottomLeft | Corner::BottomRight => frame_height - height as i32 - margin,
+        };
+
+        self.draw_at(frame, frame_width, x, y, state);
+    }
+
+    /// Draws the widget with its top left corner at `x`, `y`. Parts outside of the frame get clipped.
+    pub fn draw_at(
+        &self,
+        frame: &mut [u32],
+        frame_width: u32,
+        x: i32,
+        y: i32,
+        state: &ControllerState,
+    ) {
+        if frame_width == 0 {
+            return;
+        }
+
+        for sprite in Self::sprites(state) {
+            self.draw_sprite(frame, frame_width, x, y, &sprite);
+        }
+    }
+
+    fn sprites(state: &ControllerState) -> [Sprite; 18] {
+        let pressed = |button: JoypadState| state.buttons.contains(button);
+        let stick = |x: i32, (dx, dy): (f32, f32)| {
+            let offset = |value: f32| (value.clamp(-1.0, 1.0) * STICK_RANGE).round() as i32;
+            (x + offset(dx), 122 + offset(dy))
+        };
+        let (left_x, left_y) = stick(115, state.left_stick);
+        let (right_x, right_y) = stick(247, state.right_stick);
+
+        [
+            Sprite::new(&TRIGGER, 46, -5, pressed(JoypadState::L2)),
+            Sprite::new(&BUMPER, 56, -1, pressed(JoypadState::L)),
+            Sprite::new(&TRIGGER, 277, -5, pressed(JoypadState::R2)).turned(Turn::MirrorX),
+            Sprite::new(&BUMPER, 283, -1, pressed(JoypadState::R)).turned(Turn::MirrorX),
+            Sprite::new(&BODY, 0, 0, false),
+            Sprite::new(&DPAD, 83, 53, pressed(JoypadState::UP)),
+            Sprite::new(&DPAD, 83, 82, pressed(JoypadState::DOWN)).turned(Turn::MirrorY),
+            Sprite::new(&DPAD, 61, 75, pressed(JoypadState::LEFT)).turned(Turn::Transpose),
+            Sprite::new(&DPAD, 90, 75, pressed(JoypadState::RIGHT)).turned(Turn::RotateRight),
+            Sprite::new(&START, 158, 75, pressed(JoypadState::SELECT)),
+            // libretro has no home button
+            Sprite::new(&HOME, 196, 63, false),
+            Sprite::new(&START, 227, 75, pressed(JoypadState::START)),
+            Sprite::new(&JOYSTICK, left_x, left_y, pressed(JoypadState::L3)),
+            Sprite::new(&JOYSTICK, right_x, right_y, pressed(JoypadState::R3)),
+            Sprite::new(&BUTTON, 349, 67, pressed(JoypadState::A)),
+            Sprite::new(&BUTTON, 321, 95, pressed(JoypadState::B)),
+            Sprite::new(&BUTTON, 321, 39, pressed(JoypadState::X)),
+            Sprite::new(&BUTTON, 293, 67, pressed(JoypadState::Y)),
+        ]
+    }
+
+    fn scaled(&self, value: i32) -> i32 {
+        (value as f32 * self.scale).floor() as i32
+    }
+
+    fn draw_sprite(&self, frame: &mut [u32], frame_width: u32, x: i32, y: i32, sprite: &Sprite) {
+        let frame_width = frame_width as i32;
+        let frame_height = (frame.len() / frame_width as usize) as i32;
+        let (width, height) = sprite.size();
+
+        // Maps every covered pixel of the frame back into the sprite, so that
+        // neighbouring sprites line up without gaps at any scale
+        let left = (x + self.scaled(sprite.x)).max(0);
+        let top = (y + self.scaled(sprite.y)).max(0);
+        let right = (x + self.scaled(sprite.x + width as i32)).min(frame_width);
+        let bottom = (y + self.scaled(sprite.y + height as i32)).min(frame_height);
+
+        for frame_y in top..bottom {
+            let sprite_y = ((frame_y - y) as f32 / self.scale) as i32 - sprite.y;
+
+            for frame_x in left..right {
+                let sprite_x = ((frame_x - x) as f32 / self.scale) as i32 - sprite.x;
+
+                if sprite_x < 0 || sprite_y < 0 {
+                    continue;
+                }
+
+                let mut pixel = match sprite.get(sprite_x as u32, sprite_y as u32) {
+                    Some(pixel) => pixel,
+                    None => continue,
+                };
+
+                if sprite.active {
+                    pixel = tint(pixel, self.color);
+                }
+
+                let target = &mut frame[(frame_y * frame_width + frame_x) as usize];
+                *target = blend(*target, pixel);
+            }
+        }
+    }
+}
+
+/// Colors the gray sprites by blending `color` in with the soft light blend mode,
+/// keeping the alpha value of `pixel`.
+fn tint(pixel: u32, color: u32) -> u32 {
+    fn soft_light(a: u32, b: u32) -> u32 {
+        let a = a as f32 / 255.0;
+        let b = b as f32 / 255.0;
+
+        let d = if a <= 0.25 {
+            ((16.0 * a - 12.0) * a + 4.0) * a
+        } else {
+            a.sqrt()
+        };
+
+        let value = if b <= 0.5 {
+            a - (1.0 - 2.0 * b) * a * (1.0 - a)
+        } else {
+            a + (2.0 * b - 1.0) * (d - a)
+        };
+
+        (value.clamp(0.0, 1.0) * 255.0) as u32
+    }
+
+    [16, 8, 0]
+        .iter()
+        .fold(pixel & 0xFF00_0000, |tinted, shift| {
+            tinted | soft_light((pixel >> shift) & 0xFF, (color >> shift) & 0xFF) << shift
+        })
+}
+
+#[test]
+fn controller_widget() {
+    let widget = ControllerWidget::new(Corner::TopLeft).with_margin(0);
+    assert_eq!(widget.size(), (416, 287));
+    assert_eq!(widget.with_scale(0.5).size(), (208, 143));
+
+    let idle = ControllerState {
+        buttons: JoypadState::empty(),
+        left_stick: (0.0, 0.0),
+        right_stick: (0.0, 0.0),
+    };
+    let pressed = ControllerState {
+        buttons: JoypadState::A,
+        ..idle
+    };
+
+    let draw = |state: &ControllerState| {
+        let mut frame = vec![0u32; 420 * 290];
+        widget.draw(&mut frame, 420, state);
+        frame
+    };
+    let idle_frame = draw(&idle);
+    let pressed_frame = draw(&pressed);
+
+    // Only the A button changes
+    let changed = idle_frame
+        .iter()
+        .zip(&pressed_frame)
+        .enumerate()
+        .filter(|(_, (idle, pressed))| idle != pressed)
+        .map(|(index, _)| ((index % 420) as i32, (index / 420) as i32))
+        .collect::<Vec<_>>();
+    assert!(!changed.is_empty());
+    assert!(changed.iter().all(|&(x, y)| {
+        (349..349 + 28).contains(&x) && (BODY_Y + 67..BODY_Y + 67 + 28).contains(&y)
+    }));
+
+    // Nothing gets drawn outside of the frame
+    let mut small = vec![0u32; 64 * 32];
+    widget.with_scale(0.25).draw(&mut small, 64, &idle);
+    ControllerWidget::new(Corner::BottomRight).draw(&mut small, 64, &pressed);
+}
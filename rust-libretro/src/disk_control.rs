@@ -0,0 +1,207 @@
+//! A managed implementation of the disk control interface.
+//!
+//! Cores that return a [`DiskControl`] from [`Core::disk_control`] don’t need
+//! to implement the individual disk control callbacks of the [`Core`] trait.
+use super::*;
+
+/// A disk image known to [`DiskControl`].
+#[derive(Debug, Clone, Default)]
+pub struct DiskImage {
+    /// Path of the image file. [`None`] for slots that were added by the
+    /// frontend but have not been assigned an image yet.
+    pub path: Option<PathBuf>,
+
+    /// Label shown by the frontend, e.g. “Disk 1” or “Save Disk”.
+    pub label: Option<String>,
+}
+
+impl DiskImage {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: Some(path.into()),
+            label: None,
+        }
+    }
+
+    pub fn with_label<P: Into<PathBuf>, L: Into<String>>(path: P, label: L) -> Self {
+        Self {
+            path: Some(path.into()),
+            label: Some(label.into()),
+        }
+    }
+}
+
+/// Keeps track of the virtual disk tray and the available disk images.
+///
+/// Enable the interface with [`GenericContext::enable_disk_control_interface`]
+/// or [`GenericContext::enable_extended_disk_control_interface`].
+#[derive(Debug, Default)]
+pub struct DiskControl {
+    ejected: bool,
+    image_index: u32,
+    images: Vec<DiskImage>,
+    initial_image: Option<(u32, PathBuf)>,
+}
+
+impl DiskControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an image to the list of disk images.
+    pub fn add_image(&mut self, image: DiskImage) {
+        self.images.push(image);
+    }
+
+    pub fn images(&self) -> &[DiskImage] {
+        &self.images
+    }
+
+    pub fn num_images(&self) -> u32 {
+        self.images.len() as u32
+    }
+
+    pub fn is_ejected(&self) -> bool {
+        self.ejected
+    }
+
+    /// The index of the inserted image.
+    /// Values `>= num_images()` indicate that no disk is inserted.
+    pub fn image_index(&self) -> u32 {
+        self.image_index
+    }
+
+    /// Returns the currently inserted image, if any.
+    pub fn current_image(&self) -> Option<&DiskImage> {
+        if self.ejected {
+            return None;
+        }
+
+        self.images.get(self.image_index as usize)
+    }
+
+    /// The image index and path the frontend asked to insert
+    /// when the content gets loaded, usually the disk that was
+    /// in use last time.
+    ///
+    /// The frontend sets this before calling [`Core::on_load_game`].
+    /// See also [`DiskControl::insert_initial_image`].
+    pub fn initial_image(&self) -> Option<(u32, &Path)> {
+        self.initial_image
+            .as_ref()
+            .map(|(index, path)| (*index, path.as_path()))
+    }
+
+    /// Inserts the [initial image](DiskControl::initial_image) if it is
+    /// valid and still points to the same file, or the first image otherwise.
+    ///
+    /// Call this after registering all images in [`Core::on_load_game`].
+    /// Returns the index of the inserted image.
+    pub fn insert_initial_image(&mut self) -> u32 {
+        self.ejected = false;
+        self.image_index = 0;
+
+        if let Some((index, path)) = &self.initial_image {
+            if let Some(image) = self.images.get(*index as usize) {
+                if image.path.as_deref() == Some(path.as_path()) {
+                    self.image_index = *index;
+                }
+            }
+        }
+
+        self.image_index
+    }
+
+    pub(crate) fn set_eject_state(&mut self, ejected: bool) -> bool {
+        self.ejected = ejected;
+        true
+    }
+
+    pub(crate) fn set_image_index(&mut self, index: u32) -> bool {
+        if !self.ejected {
+            return false;
+        }
+
+        // Indices >= num_images() represent “no disk”
+        self.image_index = index;
+        true
+    }
+
+    pub(crate) unsafe fn replace_image_index(
+        &mut self,
+        index: u32,
+        info: *const retro_game_info,
+    ) -> bool {
+        let index = index as usize;
+
+        if !self.ejected || index >= self.images.len() {
+            return false;
+        }
+
+        if info.is_null() {
+            self.images.remove(index);
+
+            if self.image_index as usize > index {
+                self.image_index -= 1;
+            }
+
+            return true;
+        }
+
+        match get_path_from_pointer((*info).path) {
+            Some(path) => {
+                self.images[index] = DiskImage::new(path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn add_image_index(&mut self) -> bool {
+        self.images.push(DiskImage::default());
+        true
+    }
+
+    pub(crate) fn set_initial_image(&mut self, index: u32, path: &CStr) -> bool {
+        match get_path_from_pointer(path.as_ptr()) {
+            Some(path) => {
+                self.initial_image = Some((index, path.to_owned()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn image_path(&self, index: u32) -> Option<CString> {
+        let path = self.images.get(index as usize)?.path.as_ref()?;
+
+        CString::new(path.to_string_lossy().as_bytes()).ok()
+    }
+
+    pub(crate) fn image_label(&self, index: u32) -> Option<CString> {
+        let image = self.images.get(index as usize)?;
+
+        match &image.label {
+            Some(label) => CString::new(label.as_str()).ok(),
+            None => {
+                let name = image.path.as_ref()?.file_stem()?;
+                CString::new(name.to_string_lossy().as_bytes()).ok()
+            }
+        }
+    }
+}
+
+#[test]
+fn disk_control_initial_image() {
+    let mut disks = DiskControl::new();
+    disks.add_image(DiskImage::new("/roms/disk1.cue"));
+    disks.add_image(DiskImage::new("/roms/disk2.cue"));
+
+    let path = CString::new("/roms/disk2.cue").unwrap();
+    assert!(disks.set_initial_image(1, &path));
+    assert_eq!(disks.insert_initial_image(), 1);
+
+    let path = CString::new("/roms/other.cue").unwrap();
+    assert!(disks.set_initial_image(1, &path));
+    assert_eq!(disks.insert_initial_image(), 0);
+}
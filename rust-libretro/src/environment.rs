@@ -1,8 +1,233 @@
 //! Unsafe functions related to the libretro environment callback.
 //! For safe versions have a look at the [`contexts`] module and
 //! the context types you get in your core callbacks.
+//!
+//! Commands and queries the frontend may not support return an [`EnvironmentCallError`].
+//! Queries for which libretro defines a fallback value (e.g. interface versions
+//! that default to `0`) return that value instead.
 use super::{types::*, *};
 
+/// The reason an environment call failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvironmentCallError {
+    /// The frontend has not provided an environment callback yet.
+    NoCallback { command: u32 },
+
+    /// The frontend does not support the command or rejected the passed data.
+    Unsupported { command: u32 },
+
+    /// The frontend handled the command but returned no usable data,
+    /// e.g. a `NULL` pointer or a string that is not valid UTF-8.
+    Unavailable { command: u32 },
+}
+
+impl EnvironmentCallError {
+    /// The `RETRO_ENVIRONMENT_*` command that failed.
+    pub fn command(&self) -> u32 {
+        match *self {
+            Self::NoCallback { command }
+            | Self::Unsupported { command }
+            | Self::Unavailable { command } => command,
+        }
+    }
+}
+
+impl std::fmt::Display for EnvironmentCallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = command_name(self.command());
+
+        match self {
+            Self::NoCallback { .. } => {
+                write!(f, "{name}: the environment callback has not been set")
+            }
+            Self::Unsupported { .. } => write!(f, "{name}: not supported by the frontend"),
+            Self::Unavailable { .. } => write!(f, "{name}: the frontend returned no data"),
+        }
+    }
+}
+
+impl std::error::Error for EnvironmentCallError {}
+
+/// Returns the name of a `RETRO_ENVIRONMENT_*` command, e.g. for error messages.
+pub fn command_name(command: u32) -> &'static str {
+    EnvironmentCommand::from_raw(command).name()
+}
+
+macro_rules! environment_commands {
+    ($($variant:ident = $name:ident,)*) => {
+        /// The `RETRO_ENVIRONMENT_*` commands, see [`GenericContext::raw_environment_call`].
+        ///
+        /// Commands this crate does not know about yet can be passed as [`EnvironmentCommand::Other`].
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum EnvironmentCommand {
+            $(
+                #[doc = concat!("[`", stringify!($name), "`]")]
+                $variant,
+            )*
+
+            /// Any other command, including the [`RETRO_ENVIRONMENT_EXPERIMENTAL`]
+            /// and [`RETRO_ENVIRONMENT_PRIVATE`] flags.
+            Other(u32),
+        }
+
+        impl EnvironmentCommand {
+            pub fn from_raw(command: u32) -> Self {
+                match command {
+                    $($name => Self::$variant,)*
+                    _ => Self::Other(command),
+                }
+            }
+
+            /// The value passed to the environment callback.
+            pub fn raw(self) -> u32 {
+                match self {
+                    $(Self::$variant => $name,)*
+                    Self::Other(command) => command,
+                }
+            }
+
+            /// The name of the `RETRO_ENVIRONMENT_*` constant.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => stringify!($name),)*
+                    Self::Other(_) => "RETRO_ENVIRONMENT_UNKNOWN",
+                }
+            }
+        }
+    };
+}
+
+// `RETRO_ENVIRONMENT_GET_CONTENT_DIRECTORY` is an alias of `RETRO_ENVIRONMENT_GET_CORE_ASSETS_DIRECTORY`
+environment_commands! {
+    SetRotation = RETRO_ENVIRONMENT_SET_ROTATION,
+    GetOverscan = RETRO_ENVIRONMENT_GET_OVERSCAN,
+    GetCanDupe = RETRO_ENVIRONMENT_GET_CAN_DUPE,
+    SetMessage = RETRO_ENVIRONMENT_SET_MESSAGE,
+    Shutdown = RETRO_ENVIRONMENT_SHUTDOWN,
+    SetPerformanceLevel = RETRO_ENVIRONMENT_SET_PERFORMANCE_LEVEL,
+    GetSystemDirectory = RETRO_ENVIRONMENT_GET_SYSTEM_DIRECTORY,
+    SetPixelFormat = RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+    SetInputDescriptors = RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+    SetKeyboardCallback = RETRO_ENVIRONMENT_SET_KEYBOARD_CALLBACK,
+    SetDiskControlInterface = RETRO_ENVIRONMENT_SET_DISK_CONTROL_INTERFACE,
+    SetHwRender = RETRO_ENVIRONMENT_SET_HW_RENDER,
+    GetVariable = RETRO_ENVIRONMENT_GET_VARIABLE,
+    SetVariables = RETRO_ENVIRONMENT_SET_VARIABLES,
+    GetVariableUpdate = RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+    SetSupportNoGame = RETRO_ENVIRONMENT_SET_SUPPORT_NO_GAME,
+    GetLibretroPath = RETRO_ENVIRONMENT_GET_LIBRETRO_PATH,
+    SetFrameTimeCallback = RETRO_ENVIRONMENT_SET_FRAME_TIME_CALLBACK,
+    SetAudioCallback = RETRO_ENVIRONMENT_SET_AUDIO_CALLBACK,
+    GetRumbleInterface = RETRO_ENVIRONMENT_GET_RUMBLE_INTERFACE,
+    GetInputDeviceCapabilities = RETRO_ENVIRONMENT_GET_INPUT_DEVICE_CAPABILITIES,
+    GetSensorInterface = RETRO_ENVIRONMENT_GET_SENSOR_INTERFACE,
+    GetCameraInterface = RETRO_ENVIRONMENT_GET_CAMERA_INTERFACE,
+    GetLogInterface = RETRO_ENVIRONMENT_GET_LOG_INTERFACE,
+    GetPerfInterface = RETRO_ENVIRONMENT_GET_PERF_INTERFACE,
+    GetLocationInterface = RETRO_ENVIRONMENT_GET_LOCATION_INTERFACE,
+    GetCoreAssetsDirectory = RETRO_ENVIRONMENT_GET_CORE_ASSETS_DIRECTORY,
+    GetSaveDirectory = RETRO_ENVIRONMENT_GET_SAVE_DIRECTORY,
+    SetSystemAvInfo = RETRO_ENVIRONMENT_SET_SYSTEM_AV_INFO,
+    SetProcAddressCallback = RETRO_ENVIRONMENT_SET_PROC_ADDRESS_CALLBACK,
+    SetSubsystemInfo = RETRO_ENVIRONMENT_SET_SUBSYSTEM_INFO,
+    SetControllerInfo = RETRO_ENVIRONMENT_SET_CONTROLLER_INFO,
+    SetMemoryMaps = RETRO_ENVIRONMENT_SET_MEMORY_MAPS,
+    SetGeometry = RETRO_ENVIRONMENT_SET_GEOMETRY,
+    GetUsername = RETRO_ENVIRONMENT_GET_USERNAME,
+    GetLanguage = RETRO_ENVIRONMENT_GET_LANGUAGE,
+    GetCurrentSoftwareFramebuffer = RETRO_ENVIRONMENT_GET_CURRENT_SOFTWARE_FRAMEBUFFER,
+    GetHwRenderInterface = RETRO_ENVIRONMENT_GET_HW_RENDER_INTERFACE,
+    SetSupportAchievements = RETRO_ENVIRONMENT_SET_SUPPORT_ACHIEVEMENTS,
+    SetHwRenderContextNegotiationInterface = RETRO_ENVIRONMENT_SET_HW_RENDER_CONTEXT_NEGOTIATION_INTERFACE,
+    SetSerializationQuirks = RETRO_ENVIRONMENT_SET_SERIALIZATION_QUIRKS,
+    SetHwSharedContext = RETRO_ENVIRONMENT_SET_HW_SHARED_CONTEXT,
+    GetVfsInterface = RETRO_ENVIRONMENT_GET_VFS_INTERFACE,
+    GetLedInterface = RETRO_ENVIRONMENT_GET_LED_INTERFACE,
+    GetAudioVideoEnable = RETRO_ENVIRONMENT_GET_AUDIO_VIDEO_ENABLE,
+    GetMidiInterface = RETRO_ENVIRONMENT_GET_MIDI_INTERFACE,
+    GetFastforwarding = RETRO_ENVIRONMENT_GET_FASTFORWARDING,
+    GetTargetRefreshRate = RETRO_ENVIRONMENT_GET_TARGET_REFRESH_RATE,
+    GetInputBitmasks = RETRO_ENVIRONMENT_GET_INPUT_BITMASKS,
+    GetCoreOptionsVersion = RETRO_ENVIRONMENT_GET_CORE_OPTIONS_VERSION,
+    SetCoreOptions = RETRO_ENVIRONMENT_SET_CORE_OPTIONS,
+    SetCoreOptionsIntl = RETRO_ENVIRONMENT_SET_CORE_OPTIONS_INTL,
+    SetCoreOptionsDisplay = RETRO_ENVIRONMENT_SET_CORE_OPTIONS_DISPLAY,
+    GetPreferredHwRender = RETRO_ENVIRONMENT_GET_PREFERRED_HW_RENDER,
+    GetDiskControlInterfaceVersion = RETRO_ENVIRONMENT_GET_DISK_CONTROL_INTERFACE_VERSION,
+    SetDiskControlExtInterface = RETRO_ENVIRONMENT_SET_DISK_CONTROL_EXT_INTERFACE,
+    GetMessageInterfaceVersion = RETRO_ENVIRONMENT_GET_MESSAGE_INTERFACE_VERSION,
+    SetMessageExt = RETRO_ENVIRONMENT_SET_MESSAGE_EXT,
+    GetInputMaxUsers = RETRO_ENVIRONMENT_GET_INPUT_MAX_USERS,
+    SetAudioBufferStatusCallback = RETRO_ENVIRONMENT_SET_AUDIO_BUFFER_STATUS_CALLBACK,
+    SetMinimumAudioLatency = RETRO_ENVIRONMENT_SET_MINIMUM_AUDIO_LATENCY,
+    SetFastforwardingOverride = RETRO_ENVIRONMENT_SET_FASTFORWARDING_OVERRIDE,
+    SetContentInfoOverride = RETRO_ENVIRONMENT_SET_CONTENT_INFO_OVERRIDE,
+    GetGameInfoExt = RETRO_ENVIRONMENT_GET_GAME_INFO_EXT,
+    SetCoreOptionsV2 = RETRO_ENVIRONMENT_SET_CORE_OPTIONS_V2,
+    SetCoreOptionsV2Intl = RETRO_ENVIRONMENT_SET_CORE_OPTIONS_V2_INTL,
+    SetCoreOptionsUpdateDisplayCallback = RETRO_ENVIRONMENT_SET_CORE_OPTIONS_UPDATE_DISPLAY_CALLBACK,
+    SetVariable = RETRO_ENVIRONMENT_SET_VARIABLE,
+    GetThrottleState = RETRO_ENVIRONMENT_GET_THROTTLE_STATE,
+    GetJitCapable = RETRO_ENVIRONMENT_GET_JIT_CAPABLE,
+}
+
+impl EnvironmentCommand {
+    /// Whether the command is marked with [`RETRO_ENVIRONMENT_EXPERIMENTAL`].
+    ///
+    /// Experimental commands are not considered part of the stable libretro API,
+    /// so their behaviour may change and frontends are more likely to reject them.
+    pub fn is_experimental(self) -> bool {
+        self.raw() & RETRO_ENVIRONMENT_EXPERIMENTAL != 0
+    }
+
+    /// Whether the command is marked with [`RETRO_ENVIRONMENT_PRIVATE`],
+    /// i.e. it is specific to a frontend.
+    pub fn is_private(self) -> bool {
+        self.raw() & RETRO_ENVIRONMENT_PRIVATE != 0
+    }
+}
+
+impl From<EnvironmentCommand> for u32 {
+    fn from(command: EnvironmentCommand) -> Self {
+        command.raw()
+    }
+}
+
+impl std::fmt::Display for EnvironmentCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Other(command) => write!(f, "RETRO_ENVIRONMENT_UNKNOWN ({command:#x})"),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+#[test]
+fn environment_commands() {
+    let command = EnvironmentCommand::from_raw(RETRO_ENVIRONMENT_GET_VFS_INTERFACE);
+    assert_eq!(command, EnvironmentCommand::GetVfsInterface);
+    assert_eq!(command.raw(), RETRO_ENVIRONMENT_GET_VFS_INTERFACE);
+    assert!(command.is_experimental());
+    assert!(!EnvironmentCommand::SetRotation.is_experimental());
+
+    assert_eq!(
+        EnvironmentCommand::from_raw(RETRO_ENVIRONMENT_GET_CONTENT_DIRECTORY),
+        EnvironmentCommand::GetCoreAssetsDirectory
+    );
+
+    let private = RETRO_ENVIRONMENT_PRIVATE | 1;
+    assert_eq!(
+        EnvironmentCommand::from_raw(private),
+        EnvironmentCommand::Other(private)
+    );
+    assert!(EnvironmentCommand::Other(private).is_private());
+    assert_eq!(command_name(private), "RETRO_ENVIRONMENT_UNKNOWN");
+    assert_eq!(
+        command_name(RETRO_ENVIRONMENT_SET_ROTATION),
+        "RETRO_ENVIRONMENT_SET_ROTATION"
+    );
+}
+
 /// Gets a value from an environment callback.
 ///
 /// The first value of the return type is the queried data,
@@ -25,6 +250,8 @@ pub unsafe fn get_mut<T>(callback: retro_environment_t, id: u32, mut data: T) ->
             panic!("Expected environment callback, got NULL pointer instead!");
         }
 
+        #[cfg(feature = "metrics")]
+        metrics::record_environment_call(id);
         let status = (callback)(id, (&mut data as *mut _) as *mut c_void);
 
         Some((data, status))
@@ -66,6 +293,8 @@ pub unsafe fn set_ptr<T>(callback: retro_environment_t, id: u32, ptr: *const T)
             panic!("Expected environment callback, got NULL pointer instead!");
         }
 
+        #[cfg(feature = "metrics")]
+        metrics::record_environment_call(id);
         let status = (callback)(id, ptr as *mut c_void);
 
         return Some(status);
@@ -74,20 +303,84 @@ pub unsafe fn set_ptr<T>(callback: retro_environment_t, id: u32, ptr: *const T)
     None
 }
 
+/// Like [`get`], but fails unless the frontend handled the command.
+pub unsafe fn try_get<T: Default>(
+    callback: retro_environment_t,
+    id: u32,
+) -> Result<T, EnvironmentCallError> {
+    try_get_mut(callback, id, Default::default())
+}
+
+/// Like [`get_unchecked`], but fails unless the frontend handled the command.
+pub unsafe fn try_get_unchecked<T>(
+    callback: retro_environment_t,
+    id: u32,
+) -> Result<T, EnvironmentCallError> {
+    try_get_mut(callback, id, std::mem::MaybeUninit::zeroed().assume_init())
+}
+
+/// Like [`get_mut`], but fails unless the frontend handled the command.
+pub unsafe fn try_get_mut<T>(
+    callback: retro_environment_t,
+    id: u32,
+    data: T,
+) -> Result<T, EnvironmentCallError> {
+    match get_mut(callback, id, data) {
+        Some((data, true)) => Ok(data),
+        Some((_, false)) => Err(EnvironmentCallError::Unsupported { command: id }),
+        None => Err(EnvironmentCallError::NoCallback { command: id }),
+    }
+}
+
+/// Like [`get_path`], but fails unless the frontend handled the command
+/// and returned a valid path.
+pub unsafe fn try_get_path<'a>(
+    callback: retro_environment_t,
+    id: u32,
+) -> Result<&'a Path, EnvironmentCallError> {
+    let ptr: *const c_char = try_get_mut(callback, id, std::ptr::null())?;
+
+    get_path_from_pointer(ptr).ok_or(EnvironmentCallError::Unavailable { command: id })
+}
+
+/// Like [`set`], but fails unless the frontend accepted the value.
+pub unsafe fn try_set<T: std::fmt::Debug>(
+    callback: retro_environment_t,
+    id: u32,
+    value: T,
+) -> Result<(), EnvironmentCallError> {
+    try_set_ptr(callback, id, &value as *const _)
+}
+
+/// Like [`set_ptr`], but fails unless the frontend accepted the value.
+pub unsafe fn try_set_ptr<T>(
+    callback: retro_environment_t,
+    id: u32,
+    ptr: *const T,
+) -> Result<(), EnvironmentCallError> {
+    match set_ptr(callback, id, ptr) {
+        Some(true) => Ok(()),
+        Some(false) => Err(EnvironmentCallError::Unsupported { command: id }),
+        None => Err(EnvironmentCallError::NoCallback { command: id }),
+    }
+}
+
 /* ========================================================================== *\
  *                    Environment callback implementations                    *
 \* ========================================================================== */
 
 /// Sets screen rotation of graphics.
 #[proc::context(GenericContext)]
-pub unsafe fn set_rotation(callback: retro_environment_t, rotation: Rotation) -> bool {
+pub unsafe fn set_rotation(
+    callback: retro_environment_t,
+    rotation: Rotation,
+) -> Result<(), EnvironmentCallError> {
     // const unsigned *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_ROTATION,
         rotation.get_env_value(),
     )
-    .unwrap_or(false)
 }
 
 /// Boolean value whether or not the implementation should use overscan,
@@ -96,6 +389,7 @@ pub unsafe fn set_rotation(callback: retro_environment_t, rotation: Rotation) ->
     note = "This function is considered deprecated in favor of using core options to manage overscan in a more nuanced, core-specific way"
 )]
 #[proc::context(GenericContext)]
+#[must_use]
 pub unsafe fn get_overscan(callback: retro_environment_t) -> bool {
     // bool *
     get(callback, RETRO_ENVIRONMENT_GET_OVERSCAN)
@@ -106,6 +400,7 @@ pub unsafe fn get_overscan(callback: retro_environment_t) -> bool {
 /// Boolean value whether or not frontend supports frame duping,
 /// passing NULL to video frame callback.
 #[proc::context(GenericContext)]
+#[must_use]
 pub unsafe fn can_dupe(callback: retro_environment_t) -> bool {
     // bool *
     get(callback, RETRO_ENVIRONMENT_GET_CAN_DUPE)
@@ -119,11 +414,15 @@ pub unsafe fn can_dupe(callback: retro_environment_t) -> bool {
 /// logged via [`RETRO_ENVIRONMENT_GET_LOG_INTERFACE`] (or as a
 /// fallback, stderr).
 #[proc::context(GenericContext)]
-pub unsafe fn set_message(callback: retro_environment_t, message: &str, frames: u32) -> bool {
+pub unsafe fn set_message(
+    callback: retro_environment_t,
+    message: &str,
+    frames: u32,
+) -> Result<(), EnvironmentCallError> {
     let msg = CString::new(message).unwrap();
 
     // const struct retro_message *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_MESSAGE,
         retro_message {
@@ -131,20 +430,19 @@ pub unsafe fn set_message(callback: retro_environment_t, message: &str, frames:
             frames,
         },
     )
-    .unwrap_or(false)
 }
 
 /// Requests the frontend to shutdown.
 /// Should only be used if game has a specific
 /// way to shutdown the game from a menu item or similar.
 #[proc::context(GenericContext)]
-pub unsafe fn shutdown(callback: retro_environment_t) {
+pub unsafe fn shutdown(callback: retro_environment_t) -> Result<(), EnvironmentCallError> {
     // N/A (NULL)
-    set_ptr(
+    try_set_ptr(
         callback,
         RETRO_ENVIRONMENT_SHUTDOWN,
         std::ptr::null() as *const c_void,
-    );
+    )
 }
 
 /// Gives a hint to the frontend how demanding this implementation
@@ -161,21 +459,23 @@ pub unsafe fn shutdown(callback: retro_environment_t) {
 /// as certain games an implementation can play might be
 /// particularly demanding.
 #[proc::context(LoadGameContext)]
-pub unsafe fn set_performance_level(callback: retro_environment_t, level: u8) -> bool {
+pub unsafe fn set_performance_level(
+    callback: retro_environment_t,
+    level: u8,
+) -> Result<(), EnvironmentCallError> {
     // const unsigned *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_PERFORMANCE_LEVEL,
         level as u32,
     )
-    .unwrap_or(false)
 }
 
 /// Returns the "system" directory of the frontend.
 /// This directory can be used to store system specific
 /// content such as BIOSes, configuration data, etc.
-/// The returned value can be `NULL`.
-/// If so, no such directory is defined,
+/// Fails with [`EnvironmentCallError::Unavailable`] if
+/// no such directory is defined,
 /// and it's up to the implementation to find a suitable directory.
 ///
 /// **NOTE**: Some cores used this folder also for "save" data such as
@@ -183,24 +483,27 @@ pub unsafe fn set_performance_level(callback: retro_environment_t, level: u8) ->
 /// This is now discouraged, and if possible, cores should try to
 /// use the new [`get_save_directory()`].
 #[proc::context(GenericContext)]
-pub unsafe fn get_system_directory<'a>(callback: retro_environment_t) -> Option<&'a Path> {
+pub unsafe fn get_system_directory<'a>(
+    callback: retro_environment_t,
+) -> Result<&'a Path, EnvironmentCallError> {
     // const char **
-    get_path(callback, RETRO_ENVIRONMENT_GET_SYSTEM_DIRECTORY)
+    try_get_path(callback, RETRO_ENVIRONMENT_GET_SYSTEM_DIRECTORY)
 }
 
 /// Sets the internal pixel format used by the implementation.
 /// The default pixel format is [`retro_pixel_format::RETRO_PIXEL_FORMAT_0RGB1555`].
 /// This pixel format however, is deprecated (see enum [`retro_pixel_format`]).
-/// If the call returns `false`, the frontend does not support this pixel
+/// If the call fails, the frontend does not support this pixel
 /// format.
-#[proc::context(LoadGameContext)]
-#[proc::context(GetAvInfoContext)]
+///
+/// Prefer [`LoadGameContext::set_pixel_format`], which also remembers the
+/// pixel format for [`RunContext::set_frame_capture`].
 pub unsafe fn set_pixel_format<F: Into<retro_pixel_format>>(
     callback: retro_environment_t,
     format: F,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const enum retro_pixel_format *
-    set(callback, RETRO_ENVIRONMENT_SET_PIXEL_FORMAT, format.into()).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_PIXEL_FORMAT, format.into())
 }
 
 /// Sets an array of retro_input_descriptors.
@@ -213,14 +516,13 @@ pub unsafe fn set_pixel_format<F: Into<retro_pixel_format>>(
 pub unsafe fn set_input_descriptors(
     callback: retro_environment_t,
     descriptors: &[retro_input_descriptor],
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_input_descriptor *
-    set_ptr(
+    try_set_ptr(
         callback,
         RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
         descriptors.as_ptr(),
     )
-    .unwrap_or(false)
 }
 
 /// Sets a callback function used to notify core about keyboard events.
@@ -228,9 +530,9 @@ pub unsafe fn set_input_descriptors(
 pub unsafe fn set_keyboard_callback(
     callback: retro_environment_t,
     data: retro_keyboard_callback,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_keyboard_callback *
-    set(callback, RETRO_ENVIRONMENT_SET_KEYBOARD_CALLBACK, data).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_KEYBOARD_CALLBACK, data)
 }
 
 /// Sets an interface which frontend can use to eject and insert
@@ -241,9 +543,9 @@ pub unsafe fn set_keyboard_callback(
 pub unsafe fn set_disk_control_interface(
     callback: retro_environment_t,
     data: retro_disk_control_callback,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_disk_control_callback *
-    set(callback, RETRO_ENVIRONMENT_SET_DISK_CONTROL_INTERFACE, data).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_DISK_CONTROL_INTERFACE, data)
 }
 
 /// Sets an interface to let a libretro core render with
@@ -256,9 +558,12 @@ pub unsafe fn set_disk_control_interface(
 /// If HW rendering is used, call either
 /// [`RunContext::draw_hardware_frame`] or [`RunContext::dupe_frame`].
 #[proc::context(LoadGameContext)]
-pub unsafe fn set_hw_render(callback: retro_environment_t, data: retro_hw_render_callback) -> bool {
+pub unsafe fn set_hw_render(
+    callback: retro_environment_t,
+    data: retro_hw_render_callback,
+) -> Result<(), EnvironmentCallError> {
     // struct retro_hw_render_callback *
-    set(callback, RETRO_ENVIRONMENT_SET_HW_RENDER, data).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_HW_RENDER, data)
 }
 
 /// Interface to acquire user-defined information from environment
@@ -267,12 +572,19 @@ pub unsafe fn set_hw_render(callback: retro_environment_t, data: retro_hw_render
 /// The `key` should be set to a key which has already been set by
 /// [`set_variables`] or [`set_core_options`].
 ///
-/// Returns [`None`] if the variable could not be found.
+/// Fails with [`EnvironmentCallError::Unavailable`] if the variable could not be found.
 #[proc::context(GenericContext)]
 #[proc::context(OptionsChangedContext)]
 #[allow(clippy::needless_lifetimes)]
-pub unsafe fn get_variable<'a>(callback: retro_environment_t, key: &'a str) -> Option<&'a str> {
-    let key = CString::new(key).unwrap();
+pub unsafe fn get_variable<'a>(
+    callback: retro_environment_t,
+    key: &'a str,
+) -> Result<&'a str, EnvironmentCallError> {
+    let unavailable = EnvironmentCallError::Unavailable {
+        command: RETRO_ENVIRONMENT_GET_VARIABLE,
+    };
+
+    let key = CString::new(key).map_err(|_| unavailable)?;
 
     let var = retro_variable {
         key: key.as_ptr(),
@@ -280,13 +592,9 @@ pub unsafe fn get_variable<'a>(callback: retro_environment_t, key: &'a str) -> O
     };
 
     // struct retro_variable *
-    if let Some((var, _)) = get_mut(callback, RETRO_ENVIRONMENT_GET_VARIABLE, var) {
-        if !var.value.is_null() {
-            return get_str_from_pointer(var.value as *const c_char);
-        }
-    }
+    let var = try_get_mut(callback, RETRO_ENVIRONMENT_GET_VARIABLE, var)?;
 
-    None
+    get_str_from_pointer(var.value as *const c_char).ok_or(unavailable)
 }
 
 /// Allows an implementation to signal the environment
@@ -341,19 +649,22 @@ pub unsafe fn get_variable<'a>(callback: retro_environment_t, key: &'a str) -> O
 /// Only strings are operated on. The possible values will
 /// generally be displayed and stored as-is by the frontend.
 #[proc::context(SetEnvironmentContext)]
-pub unsafe fn set_variables(callback: retro_environment_t, variables: &[retro_variable]) -> bool {
+pub unsafe fn set_variables(
+    callback: retro_environment_t,
+    variables: &[retro_variable],
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_variable *
-    set_ptr(
+    try_set_ptr(
         callback,
         RETRO_ENVIRONMENT_SET_VARIABLES,
         variables.as_ptr(),
     )
-    .unwrap_or(false)
 }
 
 /// Result is set to [`true`] if some variables are updated by
 /// frontend since last call to [`get_variable`].
 #[proc::context(GenericContext)]
+#[must_use]
 pub unsafe fn get_variable_update(callback: retro_environment_t) -> bool {
     // bool *
     get(callback, RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE)
@@ -366,22 +677,27 @@ pub unsafe fn get_variable_update(callback: retro_environment_t) -> bool {
 /// If true, the [`Core`] implementation supports calls to
 /// [`Core::on_load_game`] with [`None`] as argument.
 #[proc::context(SetEnvironmentContext)]
-pub unsafe fn set_support_no_game(callback: retro_environment_t, value: bool) -> bool {
+pub unsafe fn set_support_no_game(
+    callback: retro_environment_t,
+    value: bool,
+) -> Result<(), EnvironmentCallError> {
     // const bool *
-    set(callback, RETRO_ENVIRONMENT_SET_SUPPORT_NO_GAME, value).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_SUPPORT_NO_GAME, value)
 }
 
 /// Retrieves the absolute path from where this libretro
 /// implementation was loaded.
-/// [`None`] is returned if the libretro was loaded statically
+/// An error is returned if the libretro was loaded statically
 /// (i.e. linked statically to frontend), or if the path cannot be
 /// determined.
 /// Mostly useful in cooperation with [`set_support_no_game`] as assets can
 /// be loaded without ugly hacks.
 #[proc::context(GenericContext)]
-pub unsafe fn get_libretro_path<'a>(callback: retro_environment_t) -> Option<&'a Path> {
+pub unsafe fn get_libretro_path<'a>(
+    callback: retro_environment_t,
+) -> Result<&'a Path, EnvironmentCallError> {
     // const char **
-    get_path(callback, RETRO_ENVIRONMENT_GET_LIBRETRO_PATH)
+    try_get_path(callback, RETRO_ENVIRONMENT_GET_LIBRETRO_PATH)
 }
 
 /// Lets the core know how much time has passed since last
@@ -395,9 +711,9 @@ pub unsafe fn get_libretro_path<'a>(callback: retro_environment_t) -> Option<&'a
 pub unsafe fn set_frame_time_callback(
     callback: retro_environment_t,
     data: retro_frame_time_callback,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_frame_time_callback *
-    set(callback, RETRO_ENVIRONMENT_SET_FRAME_TIME_CALLBACK, data).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_FRAME_TIME_CALLBACK, data)
 }
 
 /// Sets an interface which is used to notify a libretro core about audio
@@ -428,9 +744,9 @@ pub unsafe fn set_frame_time_callback(
 pub unsafe fn set_audio_callback(
     callback: retro_environment_t,
     data: retro_audio_callback,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_audio_callback *
-    set(callback, RETRO_ENVIRONMENT_SET_AUDIO_CALLBACK, data).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_AUDIO_CALLBACK, data)
 }
 
 /// Gets an interface which is used by a libretro core to set
@@ -439,14 +755,14 @@ pub unsafe fn set_audio_callback(
 /// controlled indepedently.
 /// Should be called from either [`Core::on_init`] or [`Core::on_load_game`].
 /// Should not be called from [`Core::on_set_environment`].
-/// Returns false if rumble functionality is unavailable.
+/// Fails if rumble functionality is unavailable.
 #[proc::context(InitContext)]
 #[proc::context(LoadGameContext)]
 pub unsafe fn get_rumble_interface(
     callback: retro_environment_t,
-) -> Option<retro_rumble_interface> {
+) -> Result<retro_rumble_interface, EnvironmentCallError> {
     // struct retro_rumble_interface *
-    get_unchecked(callback, RETRO_ENVIRONMENT_GET_RUMBLE_INTERFACE).map(|(v, _)| v)
+    try_get_unchecked(callback, RETRO_ENVIRONMENT_GET_RUMBLE_INTERFACE)
 }
 
 /// Gets a bitmask telling which device type are expected to be
@@ -454,7 +770,11 @@ pub unsafe fn get_rumble_interface(
 /// Devices which are not handled or recognized always return
 /// 0 in [`retro_input_state_t`].
 /// Example bitmask: `RetroDevice::JOYPAD | RetroDevice::ANALOG`.
-#[proc::context(RunContext)]
+///
+/// Available from every context, so cores can hide control schemes
+/// the frontend can’t provide from their options before loading a game.
+#[proc::context(GenericContext)]
+#[must_use]
 pub unsafe fn get_input_device_capabilities(callback: retro_environment_t) -> RetroDevice {
     // I’m not entirely sure why this call returns a 64 bit value when the `RETRO_DEVICE_MASK` allows only eight distinct types.
     // uint64_t *
@@ -475,9 +795,9 @@ pub unsafe fn get_input_device_capabilities(callback: retro_environment_t) -> Re
 #[proc::unstable(feature = "env-commands")]
 pub unsafe fn get_sensor_interface(
     callback: retro_environment_t,
-) -> Option<retro_sensor_interface> {
+) -> Result<retro_sensor_interface, EnvironmentCallError> {
     // const struct retro_sensor_interface *
-    get_unchecked(callback, RETRO_ENVIRONMENT_GET_SENSOR_INTERFACE).map(|(v, _)| v)
+    try_get_unchecked(callback, RETRO_ENVIRONMENT_GET_SENSOR_INTERFACE)
 }
 
 /// Gets an interface to a video camera driver.
@@ -506,9 +826,9 @@ pub unsafe fn get_sensor_interface(
 pub unsafe fn get_camera_interface(
     callback: retro_environment_t,
     data: retro_camera_callback,
-) -> Option<retro_camera_callback> {
+) -> Result<retro_camera_callback, EnvironmentCallError> {
     // struct retro_camera_callback *
-    get_mut(callback, RETRO_ENVIRONMENT_GET_CAMERA_INTERFACE, data).map(|(v, _)| v)
+    try_get_mut(callback, RETRO_ENVIRONMENT_GET_CAMERA_INTERFACE, data)
 }
 
 /// Gets an interface for logging. This is useful for
@@ -521,21 +841,20 @@ pub unsafe fn get_camera_interface(
 #[proc::context(GenericContext)]
 pub unsafe fn get_log_callback(
     callback: retro_environment_t,
-) -> Result<Option<retro_log_callback>, Box<dyn std::error::Error>> {
+) -> Result<retro_log_callback, EnvironmentCallError> {
     // struct retro_log_callback *
-    match get_unchecked(callback, RETRO_ENVIRONMENT_GET_LOG_INTERFACE) {
-        Some((callback, true)) => Ok(Some(callback)),
-        _ => Err("Failed to query log callback".into()),
-    }
+    try_get_unchecked(callback, RETRO_ENVIRONMENT_GET_LOG_INTERFACE)
 }
 
 /// Gets an interface for performance counters. This is useful
 /// for performance logging in a cross-platform way and for detecting
 /// architecture-specific features, such as SIMD support.
 #[proc::context(GenericContext)]
-pub unsafe fn get_perf_interface(callback: retro_environment_t) -> Option<retro_perf_callback> {
+pub unsafe fn get_perf_interface(
+    callback: retro_environment_t,
+) -> Result<retro_perf_callback, EnvironmentCallError> {
     // struct retro_perf_callback *
-    get_unchecked(callback, RETRO_ENVIRONMENT_GET_PERF_INTERFACE).map(|(v, _)| v)
+    try_get_unchecked(callback, RETRO_ENVIRONMENT_GET_PERF_INTERFACE)
 }
 
 /// Gets access to the location interface.
@@ -545,22 +864,24 @@ pub unsafe fn get_perf_interface(callback: retro_environment_t) -> Option<retro_
 #[proc::context(GenericContext)]
 pub unsafe fn get_location_callback(
     callback: retro_environment_t,
-) -> Option<retro_location_callback> {
+) -> Result<retro_location_callback, EnvironmentCallError> {
     // struct retro_location_callback *
-    get_unchecked(callback, RETRO_ENVIRONMENT_GET_LOCATION_INTERFACE).map(|(v, _)| v)
+    try_get_unchecked(callback, RETRO_ENVIRONMENT_GET_LOCATION_INTERFACE)
 }
 
 /// Returns the "core assets" directory of the frontend.
 /// This directory can be used to store specific assets that the
 /// core relies upon, such as art assets,
 /// input data, etc etc.
-/// The returned value can be [`None`].
-/// If so, no such directory is defined,
+/// Fails with [`EnvironmentCallError::Unavailable`] if
+/// no such directory is defined,
 /// and it's up to the implementation to find a suitable directory.
 #[proc::context(GenericContext)]
-pub unsafe fn get_core_assets_directory<'a>(callback: retro_environment_t) -> Option<&'a Path> {
+pub unsafe fn get_core_assets_directory<'a>(
+    callback: retro_environment_t,
+) -> Result<&'a Path, EnvironmentCallError> {
     // const char **
-    get_path(callback, RETRO_ENVIRONMENT_GET_CORE_ASSETS_DIRECTORY)
+    try_get_path(callback, RETRO_ENVIRONMENT_GET_CORE_ASSETS_DIRECTORY)
 }
 
 /// Returns the "save" directory of the frontend, unless there is no
@@ -569,16 +890,18 @@ pub unsafe fn get_core_assets_directory<'a>(callback: retro_environment_t) -> Op
 /// cannot use the regular memory interface ([`Core::get_memory_data`]).
 ///
 /// If the frontend cannot designate a save directory, it will return
-/// [`None`] to indicate that the core should attempt to operate without a
+/// an error to indicate that the core should attempt to operate without a
 /// save directory set.
 ///
 /// NOTE: early libretro cores used the system directory for save
 /// files. Cores that need to be backwards-compatible can still check
 /// [`get_system_directory`].
 #[proc::context(GenericContext)]
-pub unsafe fn get_save_directory<'a>(callback: retro_environment_t) -> Option<&'a Path> {
+pub unsafe fn get_save_directory<'a>(
+    callback: retro_environment_t,
+) -> Result<&'a Path, EnvironmentCallError> {
     // const char **
-    get_path(callback, RETRO_ENVIRONMENT_GET_SAVE_DIRECTORY)
+    try_get_path(callback, RETRO_ENVIRONMENT_GET_SAVE_DIRECTORY)
 }
 
 /// Sets a new av_info structure.
@@ -610,15 +933,15 @@ pub unsafe fn get_save_directory<'a>(callback: retro_environment_t) -> Option<&'
 /// use [`set_game_geometry`], which is a softer variant
 /// of [`set_system_av_info`].
 ///
-/// If this returns [`false`], the frontend does not acknowledge a
+/// If this fails, the frontend does not acknowledge a
 /// changed [`retro_system_av_info`] struct.
 #[proc::context(RunContext)]
 pub unsafe fn set_system_av_info(
     callback: retro_environment_t,
     av_info: retro_system_av_info,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_system_av_info *
-    set(callback, RETRO_ENVIRONMENT_SET_SYSTEM_AV_INFO, av_info).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_SYSTEM_AV_INFO, av_info)
 }
 
 /// Allows a libretro core to announce support for the
@@ -633,9 +956,9 @@ pub unsafe fn set_system_av_info(
 pub unsafe fn set_proc_address_callback(
     callback: retro_environment_t,
     data: retro_get_proc_address_interface,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_get_proc_address_interface *
-    set(callback, RETRO_ENVIRONMENT_SET_PROC_ADDRESS_CALLBACK, data).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_PROC_ADDRESS_CALLBACK, data)
 }
 
 /// This environment call introduces the concept of libretro "subsystems".
@@ -658,14 +981,13 @@ pub unsafe fn set_proc_address_callback(
 pub unsafe fn set_subsystem_info(
     callback: retro_environment_t,
     data: &[retro_subsystem_info],
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_subsystem_info *
-    set_ptr(
+    try_set_ptr(
         callback,
         RETRO_ENVIRONMENT_SET_SUBSYSTEM_INFO,
         data.as_ptr(),
     )
-    .unwrap_or(false)
 }
 
 /// This environment call lets a libretro core tell the frontend
@@ -708,14 +1030,13 @@ pub unsafe fn set_subsystem_info(
 pub unsafe fn set_controller_info(
     callback: retro_environment_t,
     data: &[retro_controller_info],
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_controller_info *
-    set_ptr(
+    try_set_ptr(
         callback,
         RETRO_ENVIRONMENT_SET_CONTROLLER_INFO,
         data.as_ptr(),
     )
-    .unwrap_or(false)
 }
 
 /// This environment call lets a libretro core tell the frontend
@@ -731,9 +1052,12 @@ pub unsafe fn set_controller_info(
 #[proc::context(InitContext)]
 #[proc::context(LoadGameContext)]
 #[proc::unstable(feature = "env-commands")]
-pub unsafe fn set_memory_maps(callback: retro_environment_t, data: retro_memory_map) -> bool {
+pub unsafe fn set_memory_maps(
+    callback: retro_environment_t,
+    data: retro_memory_map,
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_memory_map *
-    set(callback, RETRO_ENVIRONMENT_SET_MEMORY_MAPS, data).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_MEMORY_MAPS, data)
 }
 
 /// Sets a new game_geometry structure.
@@ -757,46 +1081,43 @@ pub unsafe fn set_memory_maps(callback: retro_environment_t, data: retro_memory_
 pub unsafe fn set_game_geometry(
     callback: retro_environment_t,
     geometry: retro_game_geometry,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_game_geometry *
-    set(callback, RETRO_ENVIRONMENT_SET_GEOMETRY, geometry).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_GEOMETRY, geometry)
 }
 
 /// Returns the specified username of the frontend, if specified by the user.
 /// This username can be used as a nickname for a core that has online facilities
 /// or any other mode where personalization of the user is desirable.
-/// The returned value can be [`None`].
+/// Fails with [`EnvironmentCallError::Unavailable`] if no username has been set.
 /// If this environment callback is used by a core that requires a valid username,
 /// a default username should be specified by the core.
 #[proc::context(GenericContext)]
-pub unsafe fn get_username<'a>(callback: retro_environment_t) -> Option<&'a str> {
-    let ptr: *mut c_void = std::ptr::null_mut();
+pub unsafe fn get_username<'a>(
+    callback: retro_environment_t,
+) -> Result<&'a str, EnvironmentCallError> {
+    let ptr: *const c_char = std::ptr::null();
 
     // const char **
-    if let Some((ptr, _)) = get_mut(callback, RETRO_ENVIRONMENT_GET_USERNAME, ptr) {
-        if ptr.is_null() {
-            return None;
-        }
-
-        return get_str_from_pointer(ptr as *const c_char);
-    }
+    let ptr = try_get_mut(callback, RETRO_ENVIRONMENT_GET_USERNAME, ptr)?;
 
-    None
+    get_str_from_pointer(ptr).ok_or(EnvironmentCallError::Unavailable {
+        command: RETRO_ENVIRONMENT_GET_USERNAME,
+    })
 }
 
 /// Returns the language of the frontend, if specified by the user.
 /// It can be used by the core for localization purposes.
 #[proc::context(GenericContext)]
-pub unsafe fn get_language(callback: retro_environment_t) -> Option<retro_language> {
+pub unsafe fn get_language(
+    callback: retro_environment_t,
+) -> Result<retro_language, EnvironmentCallError> {
     // unsigned *
-    if let Some((id, _)) = get::<u32>(callback, RETRO_ENVIRONMENT_GET_LANGUAGE) {
-        if id < retro_language::RETRO_LANGUAGE_LAST as u32 {
-            // This is safe because all values from 0 to RETRO_LANGUAGE_LAST have defined values
-            return Some(std::mem::transmute(id));
-        }
-    }
+    let id = try_get::<u32>(callback, RETRO_ENVIRONMENT_GET_LANGUAGE)?;
 
-    None
+    retro_language::from_repr(id).ok_or(EnvironmentCallError::Unavailable {
+        command: RETRO_ENVIRONMENT_GET_LANGUAGE,
+    })
 }
 
 /// Returns a preallocated framebuffer which the core can use for rendering
@@ -831,14 +1152,13 @@ pub unsafe fn get_language(callback: retro_environment_t) -> Option<retro_langua
 pub unsafe fn get_current_software_framebuffer(
     callback: retro_environment_t,
     data: retro_framebuffer,
-) -> Option<retro_framebuffer> {
+) -> Result<retro_framebuffer, EnvironmentCallError> {
     // struct retro_framebuffer *
-    get_mut(
+    try_get_mut(
         callback,
         RETRO_ENVIRONMENT_GET_CURRENT_SOFTWARE_FRAMEBUFFER,
         data,
     )
-    .map(|(v, _)| v)
 }
 
 /// Returns an API specific rendering interface for accessing API specific data.
@@ -855,9 +1175,9 @@ pub unsafe fn get_current_software_framebuffer(
 #[proc::unstable(feature = "env-commands")]
 pub unsafe fn get_hw_render_interface(
     callback: retro_environment_t,
-) -> Option<retro_hw_render_interface> {
+) -> Result<retro_hw_render_interface, EnvironmentCallError> {
     // const struct retro_hw_render_interface **
-    get_unchecked(callback, RETRO_ENVIRONMENT_GET_HW_RENDER_INTERFACE).map(|(v, _)| v)
+    try_get_unchecked(callback, RETRO_ENVIRONMENT_GET_HW_RENDER_INTERFACE)
 }
 
 /// If true, the Core implementation supports achievements.
@@ -866,9 +1186,12 @@ pub unsafe fn get_hw_render_interface(
 /// or via [`Core::get_memory_data`] / [`Core::get_memory_size`].
 #[proc::context(InitContext)]
 #[proc::unstable(feature = "env-commands")]
-pub unsafe fn set_support_achievements(callback: retro_environment_t, value: bool) -> bool {
+pub unsafe fn set_support_achievements(
+    callback: retro_environment_t,
+    value: bool,
+) -> Result<(), EnvironmentCallError> {
     // const bool *
-    set(callback, RETRO_ENVIRONMENT_SET_SUPPORT_ACHIEVEMENTS, value).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_SUPPORT_ACHIEVEMENTS, value)
 }
 
 /// Sets an interface which lets the libretro core negotiate with frontend how a context is created.
@@ -881,19 +1204,18 @@ pub unsafe fn set_hw_render_context_negotiation_interface(
     callback: retro_environment_t,
     interface_type: retro_hw_render_context_negotiation_interface_type,
     interface_version: ::std::os::raw::c_uint,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     let data = retro_hw_render_context_negotiation_interface {
         interface_type,
         interface_version,
     };
 
     // const struct retro_hw_render_context_negotiation_interface *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_HW_RENDER_CONTEXT_NEGOTIATION_INTERFACE,
         data,
     )
-    .unwrap_or(false)
 }
 
 /// Sets quirk flags associated with serialization.
@@ -905,34 +1227,34 @@ pub unsafe fn set_hw_render_context_negotiation_interface(
 pub unsafe fn set_serialization_quirks(
     callback: retro_environment_t,
     quirks: SerializationQuirks,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // uint64_t *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_SERIALIZATION_QUIRKS,
         quirks.bits() as u64,
     )
-    .unwrap_or(false)
 }
 
 /// The frontend will try to use a 'shared' hardware context (mostly applicable
 /// to OpenGL) when a hardware context is being set up.
 ///
-/// Returns [`true`] if the frontend supports shared hardware contexts and [`false`]
+/// Succeeds if the frontend supports shared hardware contexts and fails
 /// if the frontend does not support shared hardware contexts.
 ///
 /// This will do nothing on its own until `SET_HW_RENDER` environment callbacks are
 /// being used.
 #[proc::context(GenericContext)]
 #[proc::unstable(feature = "env-commands")]
-pub unsafe fn set_hw_shared_context(callback: retro_environment_t) -> bool {
+pub unsafe fn set_hw_shared_context(
+    callback: retro_environment_t,
+) -> Result<(), EnvironmentCallError> {
     // N/A (null) *
-    set_ptr(
+    try_set_ptr(
         callback,
         RETRO_ENVIRONMENT_SET_HW_SHARED_CONTEXT,
         std::ptr::null() as *const c_void,
     )
-    .unwrap_or(false)
 }
 
 /// Gets access to the VFS interface.
@@ -945,17 +1267,19 @@ pub unsafe fn set_hw_shared_context(callback: retro_environment_t) -> bool {
 pub fn get_vfs_interface(
     callback: retro_environment_t,
     data: retro_vfs_interface_info,
-) -> Option<retro_vfs_interface_info> {
+) -> Result<retro_vfs_interface_info, EnvironmentCallError> {
     // struct retro_vfs_interface_info *
-    get_mut(callback, RETRO_ENVIRONMENT_GET_VFS_INTERFACE, data).map(|(v, _)| v)
+    try_get_mut(callback, RETRO_ENVIRONMENT_GET_VFS_INTERFACE, data)
 }
 
 /// Gets an interface which is used by a libretro core to set state of LEDs.
 #[proc::context(GenericContext)]
 #[proc::unstable(feature = "env-commands")]
-pub fn get_led_interface(callback: retro_environment_t) -> Option<retro_led_interface> {
+pub fn get_led_interface(
+    callback: retro_environment_t,
+) -> Result<retro_led_interface, EnvironmentCallError> {
     // struct retro_led_interface *
-    get_unchecked(callback, RETRO_ENVIRONMENT_GET_LED_INTERFACE).map(|(v, _)| v)
+    try_get_unchecked(callback, RETRO_ENVIRONMENT_GET_LED_INTERFACE)
 }
 
 /// Tells the core if the frontend wants audio or video.
@@ -966,6 +1290,7 @@ pub fn get_led_interface(callback: retro_environment_t) -> Option<retro_led_inte
 /// See [`AudioVideoEnable`] for descriptions of the flags.
 #[proc::context(GenericContext)]
 #[proc::unstable(feature = "env-commands")]
+#[must_use]
 pub unsafe fn get_audio_video_enable(callback: retro_environment_t) -> AudioVideoEnable {
     // int *
     if let Some((info, _)) = get(callback, RETRO_ENVIRONMENT_GET_AUDIO_VIDEO_ENABLE) {
@@ -978,14 +1303,17 @@ pub unsafe fn get_audio_video_enable(callback: retro_environment_t) -> AudioVide
 /// Returns a MIDI interface that can be used for raw data I/O.
 #[proc::context(GenericContext)]
 #[proc::unstable(feature = "env-commands")]
-pub fn get_midi_interface(callback: retro_environment_t) -> Option<retro_midi_interface> {
+pub fn get_midi_interface(
+    callback: retro_environment_t,
+) -> Result<retro_midi_interface, EnvironmentCallError> {
     // struct retro_midi_interface **
-    get_unchecked(callback, RETRO_ENVIRONMENT_GET_MIDI_INTERFACE).map(|(v, _)| v)
+    try_get_unchecked(callback, RETRO_ENVIRONMENT_GET_MIDI_INTERFACE)
 }
 
 /// Boolean value that indicates whether or not the frontend is in fastforwarding mode.
 #[proc::context(GenericContext)]
 #[proc::unstable(feature = "env-commands")]
+#[must_use]
 pub unsafe fn get_fastforwarding(callback: retro_environment_t) -> bool {
     // bool *
     get(callback, RETRO_ENVIRONMENT_GET_FASTFORWARDING)
@@ -1000,9 +1328,11 @@ pub unsafe fn get_fastforwarding(callback: retro_environment_t) -> bool {
 /// refresh rate/framerate.
 #[proc::context(GenericContext)]
 #[proc::unstable(feature = "env-commands")]
-pub unsafe fn get_target_refresh_rate(callback: retro_environment_t) -> Option<f32> {
+pub unsafe fn get_target_refresh_rate(
+    callback: retro_environment_t,
+) -> Result<f32, EnvironmentCallError> {
     // float *
-    get(callback, RETRO_ENVIRONMENT_GET_TARGET_REFRESH_RATE).map(|(v, _)| v)
+    try_get(callback, RETRO_ENVIRONMENT_GET_TARGET_REFRESH_RATE)
 }
 
 /// Boolean value that indicates whether or not the frontend supports
@@ -1015,6 +1345,7 @@ pub unsafe fn get_target_refresh_rate(callback: retro_environment_t) -> Option<f
 /// It will return a bitmask of all the digital buttons.
 #[proc::context(GenericContext)]
 #[proc::unstable(feature = "env-commands")]
+#[must_use]
 pub unsafe fn get_input_bitmasks(callback: retro_environment_t) -> bool {
     // bool *
     // get(callback, RETRO_ENVIRONMENT_GET_INPUT_BITMASKS).map(|(v, _)| v).unwrap_or(false)
@@ -1051,6 +1382,7 @@ pub unsafe fn get_input_bitmasks(callback: retro_environment_t) -> bool {
 /// to additionally set optional core option category information
 /// for frontends with core option category support.
 #[proc::context(GenericContext)]
+#[must_use]
 pub unsafe fn get_core_options_version(callback: retro_environment_t) -> u32 {
     // unsigned *
     get(callback, RETRO_ENVIRONMENT_GET_CORE_OPTIONS_VERSION)
@@ -1060,12 +1392,14 @@ pub unsafe fn get_core_options_version(callback: retro_environment_t) -> u32 {
 
 /// Checks whether the frontend supports the [`set_core_options`] interface.
 #[proc::context(SetEnvironmentContext)]
+#[must_use]
 pub unsafe fn supports_set_core_options(callback: retro_environment_t) -> bool {
     get_core_options_version(callback) >= 1
 }
 
 /// Checks whether the frontend supports the [`set_core_options_v2`] interface.
 #[proc::context(SetEnvironmentContext)]
+#[must_use]
 pub unsafe fn supports_set_core_options_v2(callback: retro_environment_t) -> bool {
     get_core_options_version(callback) >= 2
 }
@@ -1134,14 +1468,13 @@ pub unsafe fn supports_set_core_options_v2(callback: retro_environment_t) -> boo
 pub unsafe fn set_core_options(
     callback: retro_environment_t,
     options: &[retro_core_option_definition],
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_core_option_definition **
-    set_ptr(
+    try_set_ptr(
         callback,
         RETRO_ENVIRONMENT_SET_CORE_OPTIONS,
         options.as_ptr(),
     )
-    .unwrap_or(false)
 }
 
 /// Allows an implementation to signal the environment
@@ -1331,14 +1664,16 @@ pub unsafe fn set_core_options(
 pub unsafe fn set_core_options_v2(
     callback: retro_environment_t,
     options: &retro_core_options_v2,
-) -> bool {
+) -> Result<bool, EnvironmentCallError> {
     // const struct retro_core_options_v2 *
     set_ptr(
         callback,
         RETRO_ENVIRONMENT_SET_CORE_OPTIONS_V2,
         options as *const _,
     )
-    .unwrap_or(false)
+    .ok_or(EnvironmentCallError::NoCallback {
+        command: RETRO_ENVIRONMENT_SET_CORE_OPTIONS_V2,
+    })
 }
 
 /// Allows an implementation to signal the environment
@@ -1386,9 +1721,9 @@ pub unsafe fn set_core_options_v2(
 pub unsafe fn set_core_options_intl(
     callback: retro_environment_t,
     options: retro_core_options_intl,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_core_options_intl *
-    set(callback, RETRO_ENVIRONMENT_SET_CORE_OPTIONS_INTL, options).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_CORE_OPTIONS_INTL, options)
 }
 
 /// Allows an implementation to signal the environment
@@ -1452,14 +1787,16 @@ pub unsafe fn set_core_options_intl(
 pub unsafe fn set_core_options_v2_intl(
     callback: retro_environment_t,
     options: retro_core_options_v2_intl,
-) -> bool {
+) -> Result<bool, EnvironmentCallError> {
     // const struct retro_core_options_v2_intl *
     set(
         callback,
         RETRO_ENVIRONMENT_SET_CORE_OPTIONS_V2_INTL,
         options,
     )
-    .unwrap_or(false)
+    .ok_or(EnvironmentCallError::NoCallback {
+        command: RETRO_ENVIRONMENT_SET_CORE_OPTIONS_V2_INTL,
+    })
 }
 
 /// Allows an implementation to signal the environment to show
@@ -1481,14 +1818,13 @@ pub unsafe fn set_core_options_v2_intl(
 pub unsafe fn set_core_options_display(
     callback: retro_environment_t,
     options: retro_core_option_display,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // struct retro_core_option_display *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_CORE_OPTIONS_DISPLAY,
         options,
     )
-    .unwrap_or(false)
 }
 
 /// Allows an implementation to ask frontend preferred hardware
@@ -1497,6 +1833,7 @@ pub unsafe fn set_core_options_display(
 ///
 /// 'data' points to an unsigned variable
 #[proc::context(GenericContext)]
+#[must_use]
 pub unsafe fn get_preferred_hw_render(callback: retro_environment_t) -> u32 {
     // unsigned *
     get(callback, RETRO_ENVIRONMENT_GET_PREFERRED_HW_RENDER)
@@ -1522,6 +1859,7 @@ pub unsafe fn get_preferred_hw_render(callback: retro_environment_t) -> u32 {
 /// disk images to the frontend and/or enables extra
 /// disk control functionality by the frontend.
 #[proc::context(GenericContext)]
+#[must_use]
 pub unsafe fn get_disk_control_interface_version(callback: retro_environment_t) -> u32 {
     // unsigned *
     get(
@@ -1542,14 +1880,13 @@ pub unsafe fn get_disk_control_interface_version(callback: retro_environment_t)
 pub unsafe fn set_disk_control_ext_interface(
     callback: retro_environment_t,
     data: retro_disk_control_ext_callback,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_disk_control_ext_callback *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_DISK_CONTROL_EXT_INTERFACE,
         data,
     )
-    .unwrap_or(false)
 }
 
 /// The returned value is the API version number of the message
@@ -1567,6 +1904,7 @@ pub unsafe fn set_disk_control_ext_interface(
 /// This allows the core to specify message logging level, priority and
 /// destination (OSD, logging interface or both).
 #[proc::context(GenericContext)]
+#[must_use]
 pub unsafe fn get_message_interface_version(callback: retro_environment_t) -> u32 {
     // unsigned *
     get(callback, RETRO_ENVIRONMENT_GET_MESSAGE_INTERFACE_VERSION)
@@ -1591,11 +1929,11 @@ pub unsafe fn set_message_ext(
     target: retro_message_target,
     type_: retro_message_type,
     progress: MessageProgress,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     let msg = CString::new(message).unwrap();
 
     // const struct retro_message_ext *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_MESSAGE_EXT,
         retro_message_ext {
@@ -1608,7 +1946,6 @@ pub unsafe fn set_message_ext(
             progress: progress.as_i8(),
         },
     )
-    .unwrap_or(false)
 }
 
 /// The first returned value is the number of active input devices
@@ -1623,7 +1960,10 @@ pub unsafe fn set_message_ext(
 /// If the second return value is [`false`], the number of active input
 /// devices is unknown. In this case, all input devices
 /// should be considered active.
+///
+/// See also [`GenericContext::get_max_users`].
 #[proc::context(GenericContext)]
+#[must_use]
 pub unsafe fn get_input_max_users(callback: retro_environment_t) -> (u32, bool) {
     // unsigned *
     get(callback, RETRO_ENVIRONMENT_GET_INPUT_MAX_USERS).unwrap_or((0, false))
@@ -1638,14 +1978,13 @@ pub unsafe fn get_input_max_users(callback: retro_environment_t) -> (u32, bool)
 pub unsafe fn set_audio_buffer_status_callback(
     callback: retro_environment_t,
     data: retro_audio_buffer_status_callback,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_audio_buffer_status_callback *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_AUDIO_BUFFER_STATUS_CALLBACK,
         data,
     )
-    .unwrap_or(false)
 }
 
 /// Sets minimum frontend audio latency in milliseconds.
@@ -1674,18 +2013,21 @@ pub unsafe fn set_audio_buffer_status_callback(
 /// callbacks happening after this call within the same [`Core::on_run`]
 /// call will target the newly initialized driver.
 #[proc::context(RunContext)]
-pub unsafe fn set_minimum_audio_latency(callback: retro_environment_t, latency: u32) -> bool {
+pub unsafe fn set_minimum_audio_latency(
+    callback: retro_environment_t,
+    latency: u32,
+) -> Result<(), EnvironmentCallError> {
     // const unsigned *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_MINIMUM_AUDIO_LATENCY,
         latency,
     )
-    .unwrap_or(false)
 }
 
 /// Checks whether the frontend supports the [`set_fastforwarding_override`] interface.
 #[proc::context(GenericContext)]
+#[must_use]
 pub unsafe fn supports_fastforwarding_override(callback: retro_environment_t) -> bool {
     // const struct retro_fastforwarding_override *
     set_ptr(
@@ -1702,14 +2044,13 @@ pub unsafe fn supports_fastforwarding_override(callback: retro_environment_t) ->
 pub unsafe fn set_fastforwarding_override(
     callback: retro_environment_t,
     value: retro_fastforwarding_override,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_fastforwarding_override *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_FASTFORWARDING_OVERRIDE,
         value,
     )
-    .unwrap_or(false)
 }
 
 ///  Allows an implementation to override 'global' content
@@ -1786,9 +2127,9 @@ pub unsafe fn set_fastforwarding_override(
 pub unsafe fn set_content_info_override(
     callback: retro_environment_t,
     value: retro_system_content_info_override,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_system_content_info_override *
-    set(callback, RETRO_ENVIRONMENT_SET_CONTENT_INFO_OVERRIDE, value).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_CONTENT_INFO_OVERRIDE, value)
 }
 
 /// Allows an implementation to fetch extended game
@@ -1827,13 +2168,11 @@ pub unsafe fn set_content_info_override(
 ///   [`Core::on_load_game_special`]
 #[proc::context(LoadGameContext)]
 #[proc::context(LoadGameSpecialContext)]
-pub unsafe fn get_game_info_ext(callback: retro_environment_t) -> Option<retro_game_info_ext> {
+pub unsafe fn get_game_info_ext(
+    callback: retro_environment_t,
+) -> Result<retro_game_info_ext, EnvironmentCallError> {
     // const struct retro_game_info_ext **
-    if let Some((v, true)) = get_unchecked(callback, RETRO_ENVIRONMENT_GET_GAME_INFO_EXT) {
-        Some(v)
-    } else {
-        None
-    }
+    try_get_unchecked(callback, RETRO_ENVIRONMENT_GET_GAME_INFO_EXT)
 }
 
 /// Allows a frontend to signal that a core must update
@@ -1847,14 +2186,13 @@ pub unsafe fn get_game_info_ext(callback: retro_environment_t) -> Option<retro_g
 pub unsafe fn set_core_options_update_display_callback(
     callback: retro_environment_t,
     data: retro_core_options_update_display_callback,
-) -> bool {
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_core_options_update_display_callback *
-    set(
+    try_set(
         callback,
         RETRO_ENVIRONMENT_SET_CORE_OPTIONS_UPDATE_DISPLAY_CALLBACK,
         data,
     )
-    .unwrap_or(false)
 }
 
 /// Allows an implementation to notify the frontend
@@ -1879,16 +2217,32 @@ pub unsafe fn set_core_options_update_display_callback(
 /// implementation may therefore pass `NULL` in order
 /// to test whether the callback is supported.
 #[proc::context(GenericContext)]
-pub unsafe fn set_variable(callback: retro_environment_t, value: retro_variable) -> bool {
+pub unsafe fn set_variable(
+    callback: retro_environment_t,
+    value: retro_variable,
+) -> Result<(), EnvironmentCallError> {
     // const struct retro_variable *
-    set(callback, RETRO_ENVIRONMENT_SET_VARIABLE, value).unwrap_or(false)
+    try_set(callback, RETRO_ENVIRONMENT_SET_VARIABLE, value)
 }
 
 /// Allows an implementation to get details on the actual rate
 /// the frontend is attempting to call [`Core::on_run`].
 #[proc::context(GenericContext)]
 #[proc::unstable(feature = "env-commands")]
-pub unsafe fn get_throttle_state(callback: retro_environment_t) -> Option<retro_throttle_state> {
+pub unsafe fn get_throttle_state(
+    callback: retro_environment_t,
+) -> Result<retro_throttle_state, EnvironmentCallError> {
     // struct retro_throttle_state *
-    get_unchecked(callback, RETRO_ENVIRONMENT_GET_THROTTLE_STATE).map(|(v, _)| v)
+    try_get_unchecked(callback, RETRO_ENVIRONMENT_GET_THROTTLE_STATE)
+}
+
+/// Whether the frontend allows just-in-time compilation, e.g. for dynamic recompilers.
+///
+/// Frontends on platforms that restrict JIT (like iOS and tvOS) verify whether it
+/// can be used, on other platforms the result is [`true`].
+/// See [`GenericContext::is_jit_capable`] for a version that handles unsupporting frontends.
+#[proc::context(GenericContext)]
+pub unsafe fn get_jit_capable(callback: retro_environment_t) -> Result<bool, EnvironmentCallError> {
+    // bool *
+    try_get(callback, RETRO_ENVIRONMENT_GET_JIT_CAPABLE)
 }
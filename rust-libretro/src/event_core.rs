@@ -0,0 +1,562 @@
+//! An alternative to implementing the many `on_*` methods of [`Core`].
+//!
+//! An [`EventDrivenCore`] handles every notification of the frontend in a single
+//! [`EventDrivenCore::handle_event`], which makes it easy to log, record or filter events
+//! in one place. [`EventDriven`] adapts it to [`Core`]:
+//!
+//! ```rust
+//! # use rust_libretro::{contexts::*, core::CoreOptions, event_core::*, proc::c_str, retro_core, sys::*, types::*};
+//! struct ExampleCore {
+//!     frame: u64,
+//! }
+//!
+//! retro_core!(EventDriven(ExampleCore { frame: 0 }));
+//!
+//! impl CoreOptions for ExampleCore {}
+//! impl EventDrivenCore for ExampleCore {
+//!     fn get_info(&self) -> SystemInfo {
+//!         SystemInfo {
+//!             library_name: c_str!("ExampleCore").into(),
+//!             library_version: c_str!("1.0.0").into(),
+//!             valid_extensions: c_str!("").into(),
+//!             need_fullpath: false,
+//!             block_extract: false,
+//!         }
+//!     }
+//!
+//!     fn on_get_av_info(&mut self, _ctx: &mut GetAvInfoContext) -> retro_system_av_info {
+//!         retro_system_av_info {
+//!             geometry: retro_game_geometry {
+//!                 base_width: 320,
+//!                 base_height: 240,
+//!                 max_width: 320,
+//!                 max_height: 240,
+//!                 aspect_ratio: 0.0,
+//!             },
+//!             timing: retro_system_timing {
+//!                 fps: 60.0,
+//!                 sample_rate: 48000.0,
+//!             },
+//!         }
+//!     }
+//!
+//!     fn handle_event(&mut self, event: Event) -> Result<(), Box<dyn std::error::Error>> {
+//!         match event {
+//!             Event::Run { ctx, .. } => {
+//!                 self.frame += 1;
+//!                 ctx.dupe_frame();
+//!             }
+//!             Event::Reset(_) => self.frame = 0,
+//!             _ => (),
+//!         }
+//!
+//!         Ok(())
+//!     }
+//! }
+//! ```
+//!
+//! Requests that need an answer, like the AV info or save states, stay trait methods.
+//! Handlers registered with [`GenericContext::on_option_change`] get passed the
+//! [`EventDriven`] wrapper, not the core itself.
+use crate::{
+    core::{Core, CoreOptions},
+    core_info::CoreInfo,
+    *,
+};
+use std::{error::Error, time::Duration};
+
+/// A notification of the frontend, carrying the context that is safe to use while handling it.
+///
+/// The variants correspond to the `on_*` methods of [`Core`] with the same name.
+#[non_exhaustive]
+pub enum Event<'a, 'b> {
+    /// See [`Core::on_set_environment`].
+    SetEnvironment {
+        initial: bool,
+        ctx: &'a mut SetEnvironmentContext<'b>,
+    },
+    /// See [`Core::on_before_set_core_options`].
+    BeforeSetCoreOptions(&'a mut SetEnvironmentContext<'b>),
+    /// See [`Core::on_init`].
+    Init(&'a mut InitContext<'b>),
+    /// See [`Core::on_deinit`].
+    Deinit(&'a mut DeinitContext<'b>),
+    /// See [`Core::on_set_controller_port_device`].
+    ControllerPortChanged {
+        port: u32,
+        device: u32,
+        ctx: &'a mut GenericContext<'b>,
+    },
+    /// See [`Core::on_max_users_changed`].
+    MaxUsersChanged {
+        max_users: Option<u32>,
+        ctx: &'a mut GenericContext<'b>,
+    },
+    /// See [`Core::on_reset`].
+    Reset(&'a mut ResetContext<'b>),
+    /// See [`Core::on_run_timed`].
+    Run {
+        frame_time: Option<timing::FrameTime>,
+        ctx: &'a mut RunContext<'b>,
+    },
+    /// See [`Core::on_load_game`]. An error fails loading the game.
+    LoadGame {
+        game: Option<retro_game_info>,
+        ctx: &'a mut LoadGameContext<'b>,
+    },
+    /// See [`Core::on_load_game_special`]. An error fails loading the game.
+    LoadGameSpecial {
+        game_type: std::os::raw::c_uint,
+        info: *const retro_game_info,
+        num_info: size_t,
+        ctx: &'a mut LoadGameSpecialContext<'b>,
+    },
+    /// See [`Core::on_unload_game`].
+    UnloadGame(&'a mut UnloadGameContext<'b>),
+    /// See [`Core::on_cheat_reset`].
+    CheatReset(&'a mut CheatResetContext<'b>),
+    /// See [`Core::on_cheat_set`].
+    CheatSet {
+        index: std::os::raw::c_uint,
+        enabled: bool,
+        code: &'a CStr,
+        ctx: &'a mut CheatSetContext<'b>,
+    },
+    /// See [`Core::on_options_changed`].
+    OptionsChanged(&'a mut OptionsChangedContext<'b>),
+    /// See [`Core::on_sram_dirty`].
+    SramDirty(&'a mut GenericContext<'b>),
+    /// See [`Core::on_fastforward_changed`].
+    FastForwardChanged {
+        fastforwarding: bool,
+        ctx: &'a mut GenericContext<'b>,
+    },
+    /// See [`Core::on_frame_overrun`].
+    FrameOverrun {
+        duration: Duration,
+        ctx: &'a mut GenericContext<'b>,
+    },
+    /// See [`Core::on_keyboard_event`].
+    Keyboard {
+        down: bool,
+        keycode: retro_key,
+        character: u32,
+        key_modifiers: retro_mod,
+    },
+    /// See [`Core::on_write_audio`].
+    WriteAudio(&'a mut AudioContext<'b>),
+    /// See [`Core::on_audio_set_state`].
+    AudioSetState { enabled: bool },
+    /// See [`Core::on_audio_buffer_status`].
+    AudioBufferStatus {
+        active: bool,
+        occupancy: u32,
+        underrun_likely: bool,
+    },
+    /// See [`Core::on_hw_context_reset`].
+    HwContextReset,
+    /// See [`Core::on_hw_context_destroyed`].
+    HwContextDestroyed,
+    /// See [`Core::on_hw_frame_presented`].
+    HwFramePresented(&'a mut GenericContext<'b>),
+    /// See [`Core::on_location_lifetime_status_initialized`].
+    LocationInitialized(&'a mut GenericContext<'b>),
+    /// See [`Core::on_location_lifetime_status_deinitialized`].
+    LocationDeinitialized(&'a mut GenericContext<'b>),
+    /// See [`Core::on_camera_initialized`].
+    CameraInitialized(&'a mut GenericContext<'b>),
+    /// See [`Core::on_camera_deinitialized`].
+    CameraDeinitialized(&'a mut GenericContext<'b>),
+}
+
+impl Event<'_, '_> {
+    /// The name of the variant, e.g. for logging.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SetEnvironment { .. } => "SetEnvironment",
+            Self::BeforeSetCoreOptions(_) => "BeforeSetCoreOptions",
+            Self::Init(_) => "Init",
+            Self::Deinit(_) => "Deinit",
+            Self::ControllerPortChanged { .. } => "ControllerPortChanged",
+            Self::MaxUsersChanged { .. } => "MaxUsersChanged",
+            Self::Reset(_) => "Reset",
+            Self::Run { .. } => "Run",
+            Self::LoadGame { .. } => "LoadGame",
+            Self::LoadGameSpecial { .. } => "LoadGameSpecial",
+            Self::UnloadGame(_) => "UnloadGame",
+            Self::CheatReset(_) => "CheatReset",
+            Self::CheatSet { .. } => "CheatSet",
+            Self::OptionsChanged(_) => "OptionsChanged",
+            Self::SramDirty(_) => "SramDirty",
+            Self::FastForwardChanged { .. } => "FastForwardChanged",
+            Self::FrameOverrun { .. } => "FrameOverrun",
+            Self::Keyboard { .. } => "Keyboard",
+            Self::WriteAudio(_) => "WriteAudio",
+            Self::AudioSetState { .. } => "AudioSetState",
+            Self::AudioBufferStatus { .. } => "AudioBufferStatus",
+            Self::HwContextReset => "HwContextReset",
+            Self::HwContextDestroyed => "HwContextDestroyed",
+            Self::HwFramePresented(_) => "HwFramePresented",
+            Self::LocationInitialized(_) => "LocationInitialized",
+            Self::LocationDeinitialized(_) => "LocationDeinitialized",
+            Self::CameraInitialized(_) => "CameraInitialized",
+            Self::CameraDeinitialized(_) => "CameraDeinitialized",
+        }
+    }
+}
+
+/// A core that handles all [`Event`]s in one method, see the [module documentation](self).
+pub trait EventDrivenCore: CoreOptions {
+    /// See [`Core::get_info`].
+    fn get_info(&self) -> SystemInfo;
+
+    /// See [`Core::supports_no_game`].
+    fn supports_no_game(&self) -> bool {
+        false
+    }
+
+    /// See [`Core::core_info`].
+    fn core_info(&self) -> CoreInfo {
+        CoreInfo::new(&self.get_info())
+    }
+
+    /// See [`Core::on_get_av_info`].
+    fn on_get_av_info(&mut self, ctx: &mut GetAvInfoContext) -> retro_system_av_info;
+
+    /// Handles a notification of the frontend.
+    ///
+    /// Errors fail loading the game for [`Event::LoadGame`] and [`Event::LoadGameSpecial`],
+    /// for all other events they only get logged with the `log` feature.
+    fn handle_event(&mut self, event: Event) -> Result<(), Box<dyn Error>>;
+
+    /// See [`Core::get_serialize_size`].
+    fn get_serialize_size(&mut self, _ctx: &mut GetSerializeSizeContext) -> size_t {
+        0
+    }
+
+    /// See [`Core::on_serialize`].
+    fn on_serialize(&mut self, _slice: &mut [u8], _ctx: &mut SerializeContext) -> bool {
+        false
+    }
+
+    /// See [`Core::on_unserialize`].
+    fn on_unserialize(&mut self, _slice: &mut [u8], _ctx: &mut UnserializeContext) -> bool {
+        false
+    }
+
+    /// See [`Core::on_get_region`].
+    fn on_get_region(&mut self, ctx: &mut GetRegionContext) -> std::os::raw::c_uint {
+        ctx.timing_region().unwrap_or(Region::NTSC).into()
+    }
+
+    /// See [`Core::get_memory_data`].
+    fn get_memory_data(
+        &mut self,
+        _id: std::os::raw::c_uint,
+        _ctx: &mut GetMemoryDataContext,
+    ) -> *mut std::os::raw::c_void {
+        std::ptr::null_mut()
+    }
+
+    /// See [`Core::get_memory_size`].
+    fn get_memory_size(
+        &mut self,
+        _id: std::os::raw::c_uint,
+        _ctx: &mut GetMemorySizeContext,
+    ) -> size_t {
+        0
+    }
+}
+
+/// Implements [`Core`] for an [`EventDrivenCore`], pass it to [`retro_core!()`].
+pub struct EventDriven<C>(pub C);
+
+impl<C: EventDrivenCore> EventDriven<C> {
+    fn dispatch(&mut self, event: Event) {
+        let _name = event.name();
+
+        if let Err(_err) = self.0.handle_event(event) {
+            #[cfg(feature = "log")]
+            log::error!("Failed to handle the {_name} event: {_err}");
+        }
+    }
+}
+
+impl<C: EventDrivenCore> CoreOptions for EventDriven<C> {
+    fn set_core_options(&self, ctx: &SetEnvironmentContext) -> bool {
+        self.0.set_core_options(ctx)
+    }
+
+    fn option_definitions() -> CoreOptionDefinitions {
+        C::option_definitions()
+    }
+
+    fn option_visibility_rules(&self) -> &'static [OptionVisibilityRule] {
+        self.0.option_visibility_rules()
+    }
+}
+
+impl<C: EventDrivenCore> Core for EventDriven<C> {
+    fn get_info(&self) -> SystemInfo {
+        self.0.get_info()
+    }
+
+    fn supports_no_game(&self) -> bool {
+        self.0.supports_no_game()
+    }
+
+    fn core_info(&self) -> CoreInfo {
+        self.0.core_info()
+    }
+
+    fn on_get_av_info(&mut self, ctx: &mut GetAvInfoContext) -> retro_system_av_info {
+        self.0.on_get_av_info(ctx)
+    }
+
+    fn on_set_environment(&mut self, initial: bool, ctx: &mut SetEnvironmentContext) {
+        self.dispatch(Event::SetEnvironment { initial, ctx })
+    }
+
+    fn on_before_set_core_options(&mut self, ctx: &mut SetEnvironmentContext) {
+        self.dispatch(Event::BeforeSetCoreOptions(ctx))
+    }
+
+    fn on_init(&mut self, ctx: &mut InitContext) {
+        self.dispatch(Event::Init(ctx))
+    }
+
+    fn on_deinit(&mut self, ctx: &mut DeinitContext) {
+        self.dispatch(Event::Deinit(ctx))
+    }
+
+    fn on_set_controller_port_device(&mut self, port: u32, device: u32, ctx: &mut GenericContext) {
+        self.dispatch(Event::ControllerPortChanged { port, device, ctx })
+    }
+
+    fn on_max_users_changed(&mut self, max_users: Option<u32>, ctx: &mut GenericContext) {
+        self.dispatch(Event::MaxUsersChanged { max_users, ctx })
+    }
+
+    fn on_reset(&mut self, ctx: &mut ResetContext) {
+        self.dispatch(Event::Reset(ctx))
+    }
+
+    fn on_run_timed(&mut self, ctx: &mut RunContext, frame_time: Option<timing::FrameTime>) {
+        self.dispatch(Event::Run { frame_time, ctx })
+    }
+
+    fn get_serialize_size(&mut self, ctx: &mut GetSerializeSizeContext) -> size_t {
+        self.0.get_serialize_size(ctx)
+    }
+
+    fn on_serialize(&mut self, slice: &mut [u8], ctx: &mut SerializeContext) -> bool {
+        self.0.on_serialize(slice, ctx)
+    }
+
+    fn on_unserialize(&mut self, slice: &mut [u8], ctx: &mut UnserializeContext) -> bool {
+        self.0.on_unserialize(slice, ctx)
+    }
+
+    fn on_load_game(
+        &mut self,
+        game: Option<retro_game_info>,
+        ctx: &mut LoadGameContext,
+    ) -> Result<(), Box<dyn Error>> {
+        self.0.handle_event(Event::LoadGame { game, ctx })
+    }
+
+    fn on_load_game_special(
+        &mut self,
+        game_type: std::os::raw::c_uint,
+        info: *const retro_game_info,
+        num_info: size_t,
+        ctx: &mut LoadGameSpecialContext,
+    ) -> Result<(), Box<dyn Error>> {
+        self.0.handle_event(Event::LoadGameSpecial {
+            game_type,
+            info,
+            num_info,
+            ctx,
+        })
+    }
+
+    fn on_unload_game(&mut self, ctx: &mut UnloadGameContext) {
+        self.dispatch(Event::UnloadGame(ctx))
+    }
+
+    fn on_cheat_reset(&mut self, ctx: &mut CheatResetContext) {
+        self.dispatch(Event::CheatReset(ctx))
+    }
+
+    fn on_cheat_set(
+        &mut self,
+        index: std::os::raw::c_uint,
+        enabled: bool,
+        code: &CStr,
+        ctx: &mut CheatSetContext,
+    ) {
+        self.dispatch(Event::CheatSet {
+            index,
+            enabled,
+            code,
+            ctx,
+        })
+    }
+
+    fn on_get_region(&mut self, ctx: &mut GetRegionContext) -> std::os::raw::c_uint {
+        self.0.on_get_region(ctx)
+    }
+
+    fn get_memory_data(
+        &mut self,
+        id: std::os::raw::c_uint,
+        ctx: &mut GetMemoryDataContext,
+    ) -> *mut std::os::raw::c_void {
+        self.0.get_memory_data(id, ctx)
+    }
+
+    fn get_memory_size(
+        &mut self,
+        id: std::os::raw::c_uint,
+        ctx: &mut GetMemorySizeContext,
+    ) -> size_t {
+        self.0.get_memory_size(id, ctx)
+    }
+
+    fn on_sram_dirty(&mut self, ctx: &mut GenericContext) {
+        self.dispatch(Event::SramDirty(ctx))
+    }
+
+    fn on_fastforward_changed(&mut self, fastforwarding: bool, ctx: &mut GenericContext) {
+        self.dispatch(Event::FastForwardChanged {
+            fastforwarding,
+            ctx,
+        })
+    }
+
+    fn on_frame_overrun(&mut self, duration: Duration, ctx: &mut GenericContext) {
+        self.dispatch(Event::FrameOverrun { duration, ctx })
+    }
+
+    fn on_options_changed(&mut self, ctx: &mut OptionsChangedContext) {
+        self.dispatch(Event::OptionsChanged(ctx))
+    }
+
+    fn on_keyboard_event(
+        &mut self,
+        down: bool,
+        keycode: retro_key,
+        character: u32,
+        key_modifiers: retro_mod,
+    ) {
+        self.dispatch(Event::Keyboard {
+            down,
+            keycode,
+            character,
+            key_modifiers,
+        })
+    }
+
+    #[allow(deprecated)]
+    fn on_write_audio(&mut self, ctx: &mut AudioContext) {
+        self.dispatch(Event::WriteAudio(ctx))
+    }
+
+    #[allow(deprecated)]
+    fn on_audio_set_state(&mut self, enabled: bool) {
+        self.dispatch(Event::AudioSetState { enabled })
+    }
+
+    fn on_audio_buffer_status(&mut self, active: bool, occupancy: u32, underrun_likely: bool) {
+        self.dispatch(Event::AudioBufferStatus {
+            active,
+            occupancy,
+            underrun_likely,
+        })
+    }
+
+    fn on_hw_context_reset(&mut self) {
+        self.dispatch(Event::HwContextReset)
+    }
+
+    fn on_hw_context_destroyed(&mut self) {
+        self.dispatch(Event::HwContextDestroyed)
+    }
+
+    fn on_hw_frame_presented(&mut self, ctx: &mut GenericContext) {
+        self.dispatch(Event::HwFramePresented(ctx))
+    }
+
+    fn on_location_lifetime_status_initialized(&mut self, ctx: &mut GenericContext) {
+        self.dispatch(Event::LocationInitialized(ctx))
+    }
+
+    fn on_location_lifetime_status_deinitialized(&mut self, ctx: &mut GenericContext) {
+        self.dispatch(Event::LocationDeinitialized(ctx))
+    }
+
+    fn on_camera_initialized(&mut self, ctx: &mut GenericContext) {
+        self.dispatch(Event::CameraInitialized(ctx))
+    }
+
+    fn on_camera_deinitialized(&mut self, ctx: &mut GenericContext) {
+        self.dispatch(Event::CameraDeinitialized(ctx))
+    }
+}
+
+#[test]
+fn event_driven_core() {
+    use crate::mock_frontend::StubCore;
+
+    struct Recorder(Vec<&'static str>);
+
+    impl CoreOptions for Recorder {}
+    impl EventDrivenCore for Recorder {
+        fn get_info(&self) -> SystemInfo {
+            StubCore.get_info()
+        }
+
+        fn on_get_av_info(&mut self, ctx: &mut GetAvInfoContext) -> retro_system_av_info {
+            StubCore.on_get_av_info(ctx)
+        }
+
+        fn handle_event(&mut self, event: Event) -> Result<(), Box<dyn Error>> {
+            self.0.push(event.name());
+
+            match event {
+                Event::LoadGame { game: None, .. } => Err("no content".into()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    let environment_callback: retro_environment_t = None;
+    let interfaces = core_wrapper::Interfaces::default();
+    let mut ctx = GenericContext::new(&environment_callback, &interfaces);
+    let mut load_ctx = LoadGameContext::new(&environment_callback, &interfaces);
+
+    let mut core = EventDriven(Recorder(Vec::new()));
+    core.on_reset(&mut ctx);
+    core.on_hw_context_reset();
+    assert!(core.on_load_game(None, &mut load_ctx).is_err());
+    core.on_set_controller_port_device(0, RETRO_DEVICE_JOYPAD, &mut ctx);
+
+    assert_eq!(
+        core.0 .0,
+        [
+            "Reset",
+            "HwContextReset",
+            "LoadGame",
+            "ControllerPortChanged"
+        ]
+    );
+
+    // Queries get forwarded without an event
+    let mut av_ctx = GetAvInfoContext::new(&environment_callback, &interfaces);
+    assert_eq!(
+        core.get_info().library_name,
+        StubCore.get_info().library_name
+    );
+    assert_eq!(core.on_get_av_info(&mut av_ctx).geometry.base_width, 320);
+    assert_eq!(core.0 .0.len(), 4);
+}
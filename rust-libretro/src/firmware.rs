@@ -0,0 +1,236 @@
+//! Checking the BIOS and firmware files a core needs from the system directory.
+//!
+//! Declare the files in a [`FirmwareSet`] and register it with
+//! [`GenericContext::set_firmware`]. Before [`Core::on_load_game`] and
+//! [`Core::on_load_game_special`] get called, every file gets looked up relative to
+//! [`GenericContext::get_system_directory`] and its MD5 compared with the known hashes.
+//! If a required file is missing or unknown, loading fails and the frontend shows a
+//! message listing all problems. Otherwise the resolved paths are available through
+//! [`GenericContext::get_firmware_path`].
+//!
+//! [`GenericContext::set_firmware`]: crate::contexts::GenericContext::set_firmware
+//! [`GenericContext::get_system_directory`]: crate::contexts::GenericContext::get_system_directory
+//! [`GenericContext::get_firmware_path`]: crate::contexts::GenericContext::get_firmware_path
+//! [`Core::on_load_game`]: crate::core::Core::on_load_game
+//! [`Core::on_load_game_special`]: crate::core::Core::on_load_game_special
+use crate::util::hash::ContentHashes;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// A single firmware file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Firmware {
+    /// The name shown to users, e.g. `"PlayStation BIOS (US)"`.
+    pub name: String,
+
+    /// The path relative to the system directory, e.g. `"scph5501.bin"`.
+    pub path: PathBuf,
+
+    /// The MD5 hashes of all known good dumps as hex strings.
+    /// If empty, any file at [`Firmware::path`] is accepted.
+    pub md5: Vec<String>,
+
+    /// Whether the core works without this file.
+    pub optional: bool,
+}
+
+impl Firmware {
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            md5: Vec::new(),
+            optional: false,
+        }
+    }
+
+    /// Adds the MD5 hash of a known good dump.
+    pub fn md5(mut self, md5: impl Into<String>) -> Self {
+        self.md5.push(md5.into().to_ascii_lowercase());
+        self
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    fn check(&self, system_directory: &Path) -> Result<PathBuf, FirmwareProblem> {
+        let path = system_directory.join(&self.path);
+
+        let file = std::fs::File::open(&path).map_err(|_| FirmwareProblem::Missing {
+            name: self.name.clone(),
+            path: path.clone(),
+        })?;
+
+        if self.md5.is_empty() {
+            return Ok(path);
+        }
+
+        let md5 = ContentHashes::from_reader(file)
+            .map_err(|_| FirmwareProblem::Missing {
+                name: self.name.clone(),
+                path: path.clone(),
+            })?
+            .md5_hex();
+
+        if !self.md5.contains(&md5) {
+            return Err(FirmwareProblem::Mismatch {
+                name: self.name.clone(),
+                path,
+                md5,
+            });
+        }
+
+        Ok(path)
+    }
+}
+
+/// All firmware files of a core.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FirmwareSet {
+    files: Vec<Firmware>,
+}
+
+impl FirmwareSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, firmware: Firmware) -> Self {
+        self.files.push(firmware);
+        self
+    }
+
+    pub fn files(&self) -> &[Firmware] {
+        &self.files
+    }
+
+    /// Looks up every file in `system_directory`.
+    ///
+    /// Problems with optional files don’t fail the check, but they are left out of the result.
+    pub fn check(&self, system_directory: &Path) -> Result<FirmwarePaths, FirmwareError> {
+        let mut paths = FirmwarePaths::default();
+        let mut problems = Vec::new();
+
+        for firmware in &self.files {
+            match firmware.check(system_directory) {
+                Ok(path) => {
+                    paths.paths.insert(firmware.name.clone(), path);
+                }
+                Err(_problem) if firmware.optional => {
+                    #[cfg(feature = "log")]
+                    log::info!("Optional firmware unavailable: {_problem}");
+                }
+                Err(problem) => problems.push(problem),
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(paths)
+        } else {
+            Err(FirmwareError { problems })
+        }
+    }
+}
+
+/// The paths of the firmware files that passed [`FirmwareSet::check`], by [`Firmware::name`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FirmwarePaths {
+    paths: HashMap<String, PathBuf>,
+}
+
+impl FirmwarePaths {
+    pub fn get(&self, name: &str) -> Option<&Path> {
+        self.paths.get(name).map(PathBuf::as_path)
+    }
+}
+
+/// Why a single firmware file has been rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FirmwareProblem {
+    /// The frontend did not report a system directory.
+    NoSystemDirectory,
+
+    /// The file does not exist or can’t be read.
+    Missing { name: String, path: PathBuf },
+
+    /// The file does not match any of the known hashes.
+    Mismatch {
+        name: String,
+        path: PathBuf,
+        md5: String,
+    },
+}
+
+impl std::fmt::Display for FirmwareProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoSystemDirectory => write!(f, "The system directory is unknown"),
+            Self::Missing { name, path } => write!(f, "{name} is missing ({})", path.display()),
+            Self::Mismatch { name, path, md5 } => {
+                write!(
+                    f,
+                    "{name} is an unknown dump ({}, MD5 {md5})",
+                    path.display()
+                )
+            }
+        }
+    }
+}
+
+/// Returned by [`FirmwareSet::check`] if a required file has been rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareError {
+    pub problems: Vec<FirmwareProblem>,
+}
+
+impl std::fmt::Display for FirmwareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, problem) in self.problems.iter().enumerate() {
+            if index > 0 {
+                write!(f, "; ")?;
+            }
+
+            write!(f, "{problem}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for FirmwareError {}
+
+#[test]
+fn firmware_set() {
+    let directory =
+        std::env::temp_dir().join(format!("rust-libretro-firmware-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("bios.bin"), b"bios").unwrap();
+
+    let md5 = ContentHashes::from_bytes(b"bios").md5_hex();
+    let set = FirmwareSet::new()
+        .with(Firmware::new("BIOS", "bios.bin").md5(md5.to_ascii_uppercase()))
+        .with(Firmware::new("Extra", "extra.bin").optional());
+
+    let paths = set.check(&directory).unwrap();
+    assert_eq!(
+        paths.get("BIOS"),
+        Some(directory.join("bios.bin").as_path())
+    );
+    assert_eq!(paths.get("Extra"), None);
+
+    let set = set
+        .with(Firmware::new("Bad", "bios.bin").md5("00"))
+        .with(Firmware::new("Gone", "gone.bin"));
+
+    let err = set.check(&directory).unwrap_err();
+    assert!(
+        matches!(&err.problems[0], FirmwareProblem::Mismatch { md5: found, .. } if *found == md5)
+    );
+    assert!(matches!(err.problems[1], FirmwareProblem::Missing { .. }));
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,148 @@
+//! Synchronizing OpenGL rendering with the frontend.
+//!
+//! OpenGL commands run asynchronously, so a frame handed to the frontend with
+//! [`RunContext::draw_hardware_frame`] may still be rendering when the frontend reads it,
+//! which shows up as flickering or torn frames. [`RunContext::draw_hardware_frame_synced`]
+//! inserts a fence after the core’s rendering commands and waits for it before passing the
+//! frame on. Afterwards [`Core::on_hw_frame_presented`] gets called, e.g. to switch to the
+//! next render target.
+//!
+//! [`RunContext::draw_hardware_frame`]: crate::contexts::RunContext::draw_hardware_frame
+//! [`RunContext::draw_hardware_frame_synced`]: crate::contexts::RunContext::draw_hardware_frame_synced
+//! [`Core::on_hw_frame_presented`]: crate::core::Core::on_hw_frame_presented
+use crate::contexts::GenericContext;
+use std::{os::raw::c_void, time::Duration};
+
+type GLsync = *const c_void;
+
+const GL_SYNC_GPU_COMMANDS_COMPLETE: u32 = 0x9117;
+const GL_SYNC_FLUSH_COMMANDS_BIT: u32 = 0x0000_0001;
+const GL_ALREADY_SIGNALED: u32 = 0x911A;
+const GL_TIMEOUT_EXPIRED: u32 = 0x911B;
+const GL_CONDITION_SATISFIED: u32 = 0x911C;
+
+/// How [`GlSync::wait`] finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlSyncStatus {
+    /// All commands before the fence have completed, or there was no fence.
+    Complete,
+
+    /// The timeout expired first.
+    TimedOut,
+
+    /// The driver reported an error.
+    Failed,
+}
+
+/// Inserts and waits on OpenGL fences, see the [module documentation](self).
+///
+/// The OpenGL functions are loaded through the frontend’s `get_proc_address` callback,
+/// which requires OpenGL 3.2, OpenGL ES 3.0 or `ARB_sync`.
+pub struct GlSync {
+    fence: Option<GLsync>,
+    timeout: Duration,
+
+    fence_sync: unsafe extern "C" fn(u32, u32) -> GLsync,
+    client_wait_sync: unsafe extern "C" fn(GLsync, u32, u64) -> u32,
+    delete_sync: unsafe extern "C" fn(GLsync),
+    flush: unsafe extern "C" fn(),
+}
+
+impl std::fmt::Debug for GlSync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GlSync")
+            .field("pending", &self.fence.is_some())
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl GlSync {
+    /// Loads the fence functions. The hardware context must have been reset.
+    pub fn new(ctx: &GenericContext) -> Result<Self, Box<dyn std::error::Error>> {
+        macro_rules! load {
+            ($name:literal) => {
+                match ctx.get_hw_proc_address($name) {
+                    Some(function) => function,
+                    None => return Err(concat!("Failed to load ", $name).into()),
+                }
+            };
+            ($name:literal as $type:ty) => {
+                // Function pointers of all types have the same representation
+                unsafe { std::mem::transmute::<unsafe extern "C" fn(), $type>(load!($name)) }
+            };
+        }
+
+        Ok(Self {
+            fence: None,
+            timeout: Duration::from_millis(100),
+
+            fence_sync: load!("glFenceSync" as unsafe extern "C" fn(u32, u32) -> GLsync),
+            client_wait_sync: load!(
+                "glClientWaitSync" as unsafe extern "C" fn(GLsync, u32, u64) -> u32
+            ),
+            delete_sync: load!("glDeleteSync" as unsafe extern "C" fn(GLsync)),
+            flush: load!("glFlush"),
+        })
+    }
+
+    /// Sets how long [`GlSync::wait`] waits at most, 100 ms by default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Whether a fence has been inserted but not waited on.
+    pub fn is_pending(&self) -> bool {
+        self.fence.is_some()
+    }
+
+    /// Inserts a fence after all commands issued so far and flushes them to the GPU.
+    ///
+    /// A fence that has not been waited on yet gets replaced.
+    pub fn fence(&mut self) {
+        self.delete();
+
+        unsafe {
+            let fence = (self.fence_sync)(GL_SYNC_GPU_COMMANDS_COMPLETE, 0);
+            self.fence = (!fence.is_null()).then_some(fence);
+            (self.flush)();
+        }
+    }
+
+    /// Blocks until the commands before the last fence have completed.
+    pub fn wait(&mut self) -> GlSyncStatus {
+        let fence = match self.fence {
+            Some(fence) => fence,
+            None => return GlSyncStatus::Complete,
+        };
+
+        let timeout = self.timeout.as_nanos().min(u64::MAX as u128) as u64;
+        let status = unsafe { (self.client_wait_sync)(fence, GL_SYNC_FLUSH_COMMANDS_BIT, timeout) };
+
+        match status {
+            GL_ALREADY_SIGNALED | GL_CONDITION_SATISFIED => {
+                self.delete();
+                GlSyncStatus::Complete
+            }
+            // Keeps the fence, so waiting can be retried
+            GL_TIMEOUT_EXPIRED => GlSyncStatus::TimedOut,
+            _ => {
+                self.delete();
+                GlSyncStatus::Failed
+            }
+        }
+    }
+
+    /// Forgets the pending fence without calling OpenGL,
+    /// call it in [`Core::on_hw_context_destroyed`](crate::core::Core::on_hw_context_destroyed).
+    pub fn context_lost(&mut self) {
+        self.fence = None;
+    }
+
+    fn delete(&mut self) {
+        if let Some(fence) = self.fence.take() {
+            unsafe { (self.delete_sync)(fence) };
+        }
+    }
+}
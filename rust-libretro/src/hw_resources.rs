@@ -0,0 +1,350 @@
+//! Recreating hardware rendering resources when the frontend resets the context.
+//!
+//! The frontend may destroy and recreate the hardware context at any time, e.g. when
+//! the video driver gets reinitialized, which invalidates all resources of the core.
+//! Instead of tearing everything down in [`Core::on_hw_context_destroyed`] and rebuilding it
+//! in [`Core::on_hw_context_reset`], resources can be registered with
+//! [`GenericContext::register_hw_resource`] and get recreated when needed:
+//!
+//! - When the context gets reset, all resources get created, [`HwResourceLevel::Device`] first.
+//!   This happens before [`Core::on_hw_context_reset`] gets called.
+//! - When the context gets destroyed, all resources get destroyed in reverse order,
+//!   after [`Core::on_hw_context_destroyed`] has been called.
+//! - When the base size changes through [`RunContext::try_set_game_geometry`] or
+//!   [`RunContext::try_set_system_av_info`], the [`HwResourceLevel::Swapchain`] resources
+//!   get recreated.
+//!
+//! [`Core::on_hw_context_destroyed`]: crate::core::Core::on_hw_context_destroyed
+//! [`Core::on_hw_context_reset`]: crate::core::Core::on_hw_context_reset
+//! [`GenericContext::register_hw_resource`]: crate::contexts::GenericContext::register_hw_resource
+//! [`RunContext::try_set_game_geometry`]: crate::contexts::RunContext::try_set_game_geometry
+//! [`RunContext::try_set_system_av_info`]: crate::contexts::RunContext::try_set_system_av_info
+
+/// What a [`HwResource`] depends on, which decides when it gets recreated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HwResourceLevel {
+    /// Depends on the hardware context only, e.g. shaders and static buffers.
+    Device,
+
+    /// Also depends on the video size, e.g. render targets.
+    Swapchain,
+}
+
+/// The state passed to [`HwResource::create`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HwResourceInfo {
+    /// The base width of the last reported geometry.
+    pub width: u32,
+
+    /// The base height of the last reported geometry.
+    pub height: u32,
+}
+
+/// A resource that lives in the hardware context.
+///
+/// The manager owns the resource, so state that the core needs
+/// to access while rendering has to be shared, e.g. with an [`Rc`](std::rc::Rc).
+pub trait HwResource {
+    fn create(&mut self, info: &HwResourceInfo) -> Result<(), Box<dyn std::error::Error>>;
+    fn destroy(&mut self);
+}
+
+struct Entry {
+    level: HwResourceLevel,
+    resource: Box<dyn HwResource>,
+    alive: bool,
+}
+
+/// Keeps track of the registered [`HwResource`]s and whether the hardware context is available.
+#[derive(Default)]
+pub struct HwResourceManager {
+    entries: Vec<Entry>,
+    info: HwResourceInfo,
+    context_alive: bool,
+}
+
+impl std::fmt::Debug for HwResourceManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HwResourceManager")
+            .field("resources", &self.entries.len())
+            .field("info", &self.info)
+            .field("context_alive", &self.context_alive)
+            .finish()
+    }
+}
+
+impl HwResourceManager {
+    /// Adds a resource, which gets created right away if the context is available.
+    pub fn register<R: HwResource + 'static>(&mut self, level: HwResourceLevel, resource: R) {
+        self.entries.push(Entry {
+            level,
+            resource: Box::new(resource),
+            alive: false,
+        });
+
+        if self.context_alive {
+            self.create_level(level);
+        }
+    }
+
+    /// Whether the hardware context is currently available.
+    pub fn is_context_alive(&self) -> bool {
+        self.context_alive
+    }
+
+    pub fn info(&self) -> HwResourceInfo {
+        self.info
+    }
+
+    /// Creates all resources for a new context.
+    pub(crate) fn reset(&mut self, info: HwResourceInfo) {
+        if self.context_alive {
+            self.destroy();
+        }
+
+        self.info = info;
+        self.context_alive = true;
+
+        self.create_level(HwResourceLevel::Device);
+        self.create_level(HwResourceLevel::Swapchain);
+    }
+
+    /// Destroys all resources, as the context is going away.
+    pub(crate) fn destroy(&mut self) {
+        self.destroy_level(HwResourceLevel::Swapchain);
+        self.destroy_level(HwResourceLevel::Device);
+
+        self.context_alive = false;
+    }
+
+    /// Recreates the [`HwResourceLevel::Swapchain`] resources if the size changed.
+    pub(crate) fn resize(&mut self, width: u32, height: u32) {
+        if self.info.width == width && self.info.height == height {
+            return;
+        }
+
+        self.info.width = width;
+        self.info.height = height;
+
+        if self.context_alive {
+            self.destroy_level(HwResourceLevel::Swapchain);
+            self.create_level(HwResourceLevel::Swapchain);
+        }
+    }
+
+    fn create_level(&mut self, level: HwResourceLevel) {
+        let info = self.info;
+
+        for entry in self.entries.iter_mut() {
+            if entry.level != level || entry.alive {
+                continue;
+            }
+
+            let result = entry.resource.create(&info);
+            entry.alive = result.is_ok();
+
+            #[cfg(feature = "log")]
+            if let Err(err) = result {
+                log::warn!("Failed to create a hardware resource: {err}");
+            }
+        }
+    }
+
+    fn destroy_level(&mut self, level: HwResourceLevel) {
+        for entry in self.entries.iter_mut().rev() {
+            if entry.level == level && entry.alive {
+                entry.resource.destroy();
+                entry.alive = false;
+            }
+        }
+    }
+}
+
+/// One resource per sync index of the frontend, e.g. the command buffers of a Vulkan core.
+///
+/// The sync index mask has a bit set for every index the frontend may use, the resources
+/// get (re)created whenever it changes. Vulkan cores take the mask and the current index
+/// from `VulkanInterface::sync_index_mask` and `VulkanInterface::sync_index` of the
+/// `vulkan` module, which requires the `unstable-env-commands` feature.
+#[derive(Debug, Clone)]
+pub struct FrameResources<T> {
+    mask: u32,
+    resources: Vec<T>,
+}
+
+impl<T> Default for FrameResources<T> {
+    fn default() -> Self {
+        Self {
+            mask: 0,
+            resources: Vec::new(),
+        }
+    }
+}
+
+impl<T> FrameResources<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Recreates the resources if `mask` differs from the last one.
+    ///
+    /// `destroy` gets called for every existing resource before `create`
+    /// gets called for every sync index. Returns [`true`] if the resources have been recreated.
+    pub fn update(
+        &mut self,
+        mask: u32,
+        create: impl FnMut(u32) -> T,
+        destroy: impl FnMut(T),
+    ) -> bool {
+        if mask == self.mask && !self.resources.is_empty() {
+            return false;
+        }
+
+        self.resources.drain(..).for_each(destroy);
+        self.mask = mask;
+
+        let count = u32::BITS - mask.leading_zeros();
+        self.resources.extend((0..count).map(create));
+
+        true
+    }
+
+    /// Like [`FrameResources::update`], for resources whose creation can fail.
+    ///
+    /// If `create` fails, the resources created so far get destroyed and the
+    /// next call tries again.
+    pub fn try_update<E>(
+        &mut self,
+        mask: u32,
+        mut create: impl FnMut(u32) -> Result<T, E>,
+        mut destroy: impl FnMut(T),
+    ) -> Result<bool, E> {
+        if mask == self.mask && !self.resources.is_empty() {
+            return Ok(false);
+        }
+
+        self.resources.drain(..).for_each(&mut destroy);
+        self.mask = mask;
+
+        let count = u32::BITS - mask.leading_zeros();
+        for index in 0..count {
+            match create(index) {
+                Ok(resource) => self.resources.push(resource),
+                Err(err) => {
+                    self.clear(destroy);
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Destroys all resources, e.g. when the hardware context gets destroyed.
+    pub fn clear(&mut self, destroy: impl FnMut(T)) {
+        self.resources.drain(..).for_each(destroy);
+        self.mask = 0;
+    }
+
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// The resource of the sync index `index`.
+    pub fn get(&self, index: u32) -> Option<&T> {
+        self.resources.get(index as usize)
+    }
+
+    pub fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        self.resources.get_mut(index as usize)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.resources.iter()
+    }
+}
+
+#[test]
+fn frame_resources() {
+    let mut resources = FrameResources::new();
+    let mut destroyed = Vec::new();
+
+    assert!(resources.update(0b11, |index| index * 10, |_| ()));
+    assert!(!resources.update(0b11, |_| unreachable!(), |_| ()));
+    assert_eq!(resources.get(1), Some(&10));
+
+    assert!(resources.update(0b111, |index| index * 100, |old| destroyed.push(old)));
+    assert_eq!(destroyed, [0, 10]);
+    assert_eq!(resources.iter().copied().collect::<Vec<_>>(), [0, 100, 200]);
+
+    resources.clear(|old| destroyed.push(old));
+    assert!(resources.is_empty());
+    assert_eq!(destroyed, [0, 10, 0, 100, 200]);
+
+    // A failed creation leaves nothing behind and gets retried
+    destroyed.clear();
+    let failing = |index| if index < 2 { Ok(index) } else { Err(index) };
+    assert_eq!(
+        resources.try_update(0b111, failing, |old| destroyed.push(old)),
+        Err(2)
+    );
+    assert!(resources.is_empty());
+    assert_eq!(destroyed, [0, 1]);
+    assert_eq!(resources.try_update(0b111, Ok::<_, ()>, |_| ()), Ok(true));
+    assert_eq!(resources.get(2), Some(&2));
+}
+
+#[test]
+fn hw_resource_manager() {
+    use std::{cell::RefCell, rc::Rc};
+
+    struct Logged(&'static str, Rc<RefCell<Vec<String>>>);
+
+    impl HwResource for Logged {
+        fn create(&mut self, info: &HwResourceInfo) -> Result<(), Box<dyn std::error::Error>> {
+            self.1
+                .borrow_mut()
+                .push(format!("+{} {}", self.0, info.width));
+            Ok(())
+        }
+
+        fn destroy(&mut self) {
+            self.1.borrow_mut().push(format!("-{}", self.0));
+        }
+    }
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut manager = HwResourceManager::default();
+
+    manager.register(HwResourceLevel::Swapchain, Logged("target", log.clone()));
+    manager.register(HwResourceLevel::Device, Logged("shader", log.clone()));
+    assert!(log.borrow().is_empty());
+
+    manager.reset(HwResourceInfo {
+        width: 320,
+        height: 240,
+    });
+    manager.resize(320, 240);
+    manager.resize(640, 480);
+    manager.destroy();
+
+    assert_eq!(
+        *log.borrow(),
+        [
+            "+shader 320",
+            "+target 320",
+            "-target",
+            "+target 640",
+            "-target",
+            "-shader"
+        ]
+    );
+}
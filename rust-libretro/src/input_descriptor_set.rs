@@ -0,0 +1,162 @@
+//! Input descriptors grouped by port, submitted only when they changed.
+//!
+//! Cores that describe the buttons of each port separately, e.g. depending on the device
+//! set in [`Core::on_set_controller_port_device`], can pass the descriptors of a single
+//! port to [`GenericContext::update_input_descriptors`]. The wrapper merges them with the
+//! other ports and only submits the table if it differs from the last one,
+//! as every submission makes the frontend rebuild its input menus.
+//!
+//! [`Core::on_set_controller_port_device`]: crate::core::Core::on_set_controller_port_device
+//! [`GenericContext::update_input_descriptors`]: crate::contexts::GenericContext::update_input_descriptors
+use crate::{sys::retro_input_descriptor, util::get_str_from_pointer};
+use std::{collections::BTreeMap, ffi::CString};
+
+/// An owned [`retro_input_descriptor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputDescriptor {
+    pub port: u32,
+    pub device: u32,
+    pub index: u32,
+    pub id: u32,
+    pub description: String,
+}
+
+impl InputDescriptor {
+    /// Returns [`None`] for the terminating entry, i.e. a `NULL` or empty description.
+    pub fn from_raw(descriptor: &retro_input_descriptor) -> Option<Self> {
+        let description = get_str_from_pointer(descriptor.description)?;
+
+        if description.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            port: descriptor.port,
+            device: descriptor.device,
+            index: descriptor.index,
+            id: descriptor.id,
+            description: description.to_owned(),
+        })
+    }
+}
+
+/// The table last submitted to the frontend, in the layout it expects.
+#[derive(Debug)]
+pub struct SubmittedDescriptors {
+    descriptors: Vec<InputDescriptor>,
+
+    /// Borrowed by `raw`.
+    _descriptions: Vec<CString>,
+    raw: Vec<retro_input_descriptor>,
+}
+
+impl SubmittedDescriptors {
+    fn new(descriptors: Vec<InputDescriptor>) -> Self {
+        // Descriptions with a nul byte are cut off there
+        let descriptions: Vec<CString> = descriptors
+            .iter()
+            .map(|descriptor| {
+                let description = descriptor.description.split('\0').next().unwrap_or("");
+                CString::new(description).unwrap()
+            })
+            .collect();
+
+        let raw = descriptors
+            .iter()
+            .zip(&descriptions)
+            .map(|(descriptor, description)| retro_input_descriptor {
+                port: descriptor.port,
+                device: descriptor.device,
+                index: descriptor.index,
+                id: descriptor.id,
+                description: description.as_ptr(),
+            })
+            .chain(std::iter::once(retro_input_descriptor {
+                port: 0,
+                device: 0,
+                index: 0,
+                id: 0,
+                description: std::ptr::null(),
+            }))
+            .collect();
+
+        Self {
+            descriptors,
+            _descriptions: descriptions,
+            raw,
+        }
+    }
+
+    /// The descriptors, terminated by an entry with a `NULL` description.
+    pub fn as_raw(&self) -> &[retro_input_descriptor] {
+        &self.raw
+    }
+}
+
+/// See the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct InputDescriptorSet {
+    ports: BTreeMap<u32, Vec<InputDescriptor>>,
+    submitted: Option<SubmittedDescriptors>,
+}
+
+impl InputDescriptorSet {
+    /// Replaces the descriptors of `port`.
+    ///
+    /// Returns the merged table if it differs from the last submitted one.
+    pub(crate) fn update(
+        &mut self,
+        port: u32,
+        descriptors: Vec<InputDescriptor>,
+    ) -> Option<SubmittedDescriptors> {
+        if descriptors.is_empty() {
+            self.ports.remove(&port);
+        } else {
+            self.ports.insert(port, descriptors);
+        }
+
+        let merged: Vec<InputDescriptor> = self.ports.values().flatten().cloned().collect();
+
+        match &self.submitted {
+            Some(submitted) if submitted.descriptors == merged => None,
+            None if merged.is_empty() => None,
+            _ => Some(SubmittedDescriptors::new(merged)),
+        }
+    }
+
+    /// Keeps the table the frontend accepted, to compare the next update against.
+    pub(crate) fn set_submitted(&mut self, submitted: SubmittedDescriptors) {
+        self.submitted = Some(submitted);
+    }
+}
+
+#[test]
+fn input_descriptor_merging() {
+    let descriptor = |port, id, description: &str| InputDescriptor {
+        port,
+        device: crate::sys::RETRO_DEVICE_JOYPAD,
+        index: 0,
+        id,
+        description: description.to_owned(),
+    };
+
+    let mut set = InputDescriptorSet::default();
+    assert!(set.update(1, vec![]).is_none());
+
+    let submitted = set.update(1, vec![descriptor(1, 0, "B")]).unwrap();
+    set.set_submitted(submitted);
+    assert!(set.update(1, vec![descriptor(1, 0, "B")]).is_none());
+
+    // Ports are merged in order, no matter which one got updated last
+    let submitted = set.update(0, vec![descriptor(0, 8, "A")]).unwrap();
+    assert_eq!(
+        submitted.descriptors,
+        [descriptor(0, 8, "A"), descriptor(1, 0, "B")]
+    );
+    assert_eq!(submitted.as_raw().len(), 3);
+    assert!(submitted.as_raw()[2].description.is_null());
+    set.set_submitted(submitted);
+
+    let submitted = set.update(1, vec![]).unwrap();
+    assert_eq!(submitted.descriptors, [descriptor(0, 8, "A")]);
+}
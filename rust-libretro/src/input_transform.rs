@@ -0,0 +1,248 @@
+//! Mapping the left analog stick to the D-Pad and vice versa.
+//!
+//! Many games only support one of the two, so cores commonly offer a core option to play
+//! them with the other. Keep an [`InputTransformer`] per port, change its mode with
+//! [`InputTransformer::apply_option`] and read the input through
+//! [`RunContext::get_transformed_input`].
+//!
+//! [`RunContext::get_transformed_input`]: crate::contexts::RunContext::get_transformed_input
+use crate::types::JoypadState;
+use std::str::FromStr;
+
+/// The largest deflection of an analog axis.
+const AXIS_MAX: i16 = 0x7FFF;
+
+/// How [`InputTransformer`] changes the input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransformMode {
+    /// The input is passed through unchanged.
+    #[default]
+    Disabled,
+
+    /// Deflecting the left stick presses the D-Pad, with hysteresis.
+    AnalogToDpad,
+
+    /// Pressing the D-Pad deflects the left stick, ramping up over a few frames.
+    DpadToAnalog,
+}
+
+impl TransformMode {
+    /// The values accepted by [`TransformMode::from_str`], for use as core option values.
+    pub const OPTION_VALUES: [&'static str; 3] = ["disabled", "analog_to_dpad", "dpad_to_analog"];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Disabled => Self::OPTION_VALUES[0],
+            Self::AnalogToDpad => Self::OPTION_VALUES[1],
+            Self::DpadToAnalog => Self::OPTION_VALUES[2],
+        }
+    }
+}
+
+impl FromStr for TransformMode {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "disabled" => Ok(Self::Disabled),
+            "analog_to_dpad" => Ok(Self::AnalogToDpad),
+            "dpad_to_analog" => Ok(Self::DpadToAnalog),
+            _ => Err(format!("Unknown input transform mode: {value}").into()),
+        }
+    }
+}
+
+/// The joypad buttons and the left stick after [`InputTransformer::transform`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransformedInput {
+    pub buttons: JoypadState,
+    pub x: i16,
+    pub y: i16,
+}
+
+/// Transforms the input of a single port, see the [module documentation](self).
+#[derive(Debug, Clone, Copy)]
+pub struct InputTransformer {
+    mode: TransformMode,
+
+    /// The deflection at which a direction gets pressed.
+    press_threshold: i16,
+
+    /// The deflection below which a pressed direction gets released.
+    release_threshold: i16,
+
+    /// How far the emulated stick moves per frame.
+    ramp_step: i16,
+
+    /// The directions pressed through the stick in the last frame.
+    directions: JoypadState,
+
+    /// The emulated stick position of the last frame.
+    x: i16,
+    y: i16,
+}
+
+impl Default for InputTransformer {
+    fn default() -> Self {
+        Self::new(TransformMode::Disabled)
+    }
+}
+
+impl InputTransformer {
+    /// Presses directions at half deflection and reaches full deflection within four frames.
+    pub fn new(mode: TransformMode) -> Self {
+        Self {
+            mode,
+            press_threshold: 0x4000,
+            release_threshold: 0x3000,
+            ramp_step: 0x2000,
+            directions: JoypadState::empty(),
+            x: 0,
+            y: 0,
+        }
+    }
+
+    /// Sets the deflections at which directions get pressed and released again.
+    ///
+    /// `release` gets clamped to `press`, so directions don’t flicker around the threshold.
+    pub fn with_thresholds(mut self, press: i16, release: i16) -> Self {
+        self.press_threshold = press.max(1);
+        self.release_threshold = release.clamp(0, self.press_threshold);
+        self
+    }
+
+    /// Sets how far the emulated stick moves per frame, [`i16::MAX`] disables the ramping.
+    pub fn with_ramp_step(mut self, step: i16) -> Self {
+        self.ramp_step = step.max(1);
+        self
+    }
+
+    pub fn mode(&self) -> TransformMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: TransformMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.directions = JoypadState::empty();
+            self.x = 0;
+            self.y = 0;
+        }
+    }
+
+    /// Changes the mode to the value of a core option, e.g. in [`Core::on_options_changed`].
+    ///
+    /// [`Core::on_options_changed`]: crate::core::Core::on_options_changed
+    pub fn apply_option(&mut self, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_mode(value.parse()?);
+        Ok(())
+    }
+
+    /// Transforms the input of one frame, call it exactly once per frame.
+    pub fn transform(&mut self, buttons: JoypadState, x: i16, y: i16) -> TransformedInput {
+        match self.mode {
+            TransformMode::Disabled => TransformedInput { buttons, x, y },
+            TransformMode::AnalogToDpad => {
+                self.directions = self.stick_directions(x, y);
+
+                TransformedInput {
+                    buttons: buttons | self.directions,
+                    x,
+                    y,
+                }
+            }
+            TransformMode::DpadToAnalog => {
+                self.x = self.ramp(self.x, x, buttons, JoypadState::LEFT, JoypadState::RIGHT);
+                self.y = self.ramp(self.y, y, buttons, JoypadState::UP, JoypadState::DOWN);
+
+                TransformedInput {
+                    buttons,
+                    x: self.x,
+                    y: self.y,
+                }
+            }
+        }
+    }
+
+    fn stick_directions(&self, x: i16, y: i16) -> JoypadState {
+        let mut directions = JoypadState::empty();
+
+        for (value, negative, positive) in [
+            (x, JoypadState::LEFT, JoypadState::RIGHT),
+            (y, JoypadState::UP, JoypadState::DOWN),
+        ] {
+            for (direction, deflection) in [(negative, -(value as i32)), (positive, value as i32)] {
+                let threshold = if self.directions.contains(direction) {
+                    self.release_threshold
+                } else {
+                    self.press_threshold
+                };
+
+                if deflection >= threshold as i32 {
+                    directions |= direction;
+                }
+            }
+        }
+
+        directions
+    }
+
+    /// Moves the emulated axis toward the pressed direction,
+    /// or follows the real stick if neither direction is pressed.
+    fn ramp(
+        &self,
+        current: i16,
+        stick: i16,
+        buttons: JoypadState,
+        negative: JoypadState,
+        positive: JoypadState,
+    ) -> i16 {
+        let target = match (buttons.contains(negative), buttons.contains(positive)) {
+            (true, false) => -AXIS_MAX,
+            (false, true) => AXIS_MAX,
+            _ => return stick,
+        };
+
+        let step = self.ramp_step as i32;
+        let current = current as i32;
+        let target = target as i32;
+
+        let next = if current < target {
+            (current + step).min(target)
+        } else {
+            (current - step).max(target)
+        };
+
+        next as i16
+    }
+}
+
+#[test]
+fn input_transformer() {
+    let mut transformer = InputTransformer::new(TransformMode::AnalogToDpad);
+
+    let input = transformer.transform(JoypadState::A, 0x5000, -0x1000);
+    assert_eq!(input.buttons, JoypadState::A | JoypadState::RIGHT);
+    assert_eq!((input.x, input.y), (0x5000, -0x1000));
+
+    // Stays pressed above the release threshold
+    let input = transformer.transform(JoypadState::empty(), 0x3800, -0x3800);
+    assert_eq!(input.buttons, JoypadState::RIGHT);
+    let input = transformer.transform(JoypadState::empty(), 0x2000, 0);
+    assert_eq!(input.buttons, JoypadState::empty());
+
+    transformer.apply_option("dpad_to_analog").unwrap();
+    let ramped = [0u8; 5].map(|_| transformer.transform(JoypadState::LEFT, 0, 0x1234));
+    assert_eq!(
+        ramped.map(|input| input.x),
+        [-0x2000, -0x4000, -0x6000, -0x7FFF, -0x7FFF]
+    );
+    assert_eq!(ramped[0].y, 0x1234);
+
+    let input = transformer.transform(JoypadState::RIGHT, 0, 0);
+    assert_eq!(input.x, -0x5FFF);
+    assert_eq!(transformer.transform(JoypadState::empty(), 5, 0).x, 5);
+
+    assert!(transformer.apply_option("sideways").is_err());
+    assert_eq!(transformer.mode(), TransformMode::DpadToAnalog);
+}
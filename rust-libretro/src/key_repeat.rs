@@ -0,0 +1,161 @@
+//! Synthesizing key repeats for the keyboard callback.
+//!
+//! Frontends report a single key press no matter how long a key is held down.
+//! Cores for machines with text input usually expect the keyboard to repeat a held key
+//! like the real hardware would. [`KeyRepeat`] does that based on the number of frames
+//! run, so repeats stop while the frontend is paused.
+//!
+//! Register it with [`GenericContext::set_key_repeat`]; repeated presses get passed to
+//! [`Core::on_keyboard_event`] at the start of [`retro_run`](crate::retro_run),
+//! just like the presses reported by the frontend.
+//!
+//! [`GenericContext::set_key_repeat`]: crate::contexts::GenericContext::set_key_repeat
+//! [`Core::on_keyboard_event`]: crate::core::Core::on_keyboard_event
+use crate::sys::{retro_key, retro_mod};
+
+/// A key press reported by the keyboard callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub keycode: retro_key,
+    pub character: u32,
+    pub key_modifiers: retro_mod,
+}
+
+/// Repeats the last held key after `delay` frames, every `interval` frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRepeat {
+    delay: u32,
+    interval: u32,
+    held: Option<KeyEvent>,
+    frames: u32,
+}
+
+impl KeyRepeat {
+    pub fn new(delay: u32, interval: u32) -> Self {
+        Self {
+            delay: delay.max(1),
+            interval: interval.max(1),
+            held: None,
+            frames: 0,
+        }
+    }
+
+    /// Converts a delay and an interval in milliseconds to frames at `fps`.
+    pub fn from_millis(delay: u32, interval: u32, fps: f64) -> Self {
+        let frames = |millis: u32| (millis as f64 * fps / 1000.0).round() as u32;
+
+        Self::new(frames(delay), frames(interval))
+    }
+
+    pub fn delay(&self) -> u32 {
+        self.delay
+    }
+
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// The key that is currently being held, if it repeats.
+    pub fn held(&self) -> Option<KeyEvent> {
+        self.held
+    }
+
+    /// Records a key press or release reported by the frontend.
+    ///
+    /// Pressing a key stops the repeats of the previously held one, except for modifier keys.
+    pub fn handle_key(&mut self, down: bool, event: KeyEvent) {
+        if is_modifier(event.keycode) {
+            return;
+        }
+
+        if down {
+            self.held = Some(event);
+            self.frames = 0;
+        } else if self.held.map(|held| held.keycode) == Some(event.keycode) {
+            self.held = None;
+        }
+    }
+
+    /// Stops repeating the held key, e.g. when the core loses keyboard focus.
+    pub fn release_all(&mut self) {
+        self.held = None;
+    }
+
+    /// Advances the repeat clock by a frame and returns the press to repeat, if any.
+    pub fn tick(&mut self) -> Option<KeyEvent> {
+        let held = self.held?;
+
+        self.frames += 1;
+
+        if self.frames < self.delay {
+            return None;
+        }
+
+        if (self.frames - self.delay).is_multiple_of(self.interval) {
+            if self.frames >= self.delay + self.interval {
+                // Keep the counter bounded while a key is held for a long time
+                self.frames = self.delay;
+            }
+
+            return Some(held);
+        }
+
+        None
+    }
+}
+
+fn is_modifier(keycode: retro_key) -> bool {
+    matches!(
+        keycode,
+        retro_key::RETROK_LSHIFT
+            | retro_key::RETROK_RSHIFT
+            | retro_key::RETROK_LCTRL
+            | retro_key::RETROK_RCTRL
+            | retro_key::RETROK_LALT
+            | retro_key::RETROK_RALT
+            | retro_key::RETROK_LMETA
+            | retro_key::RETROK_RMETA
+            | retro_key::RETROK_LSUPER
+            | retro_key::RETROK_RSUPER
+            | retro_key::RETROK_MODE
+            | retro_key::RETROK_COMPOSE
+            | retro_key::RETROK_CAPSLOCK
+            | retro_key::RETROK_NUMLOCK
+            | retro_key::RETROK_SCROLLOCK
+    )
+}
+
+#[test]
+fn key_repeat() {
+    let a = KeyEvent {
+        keycode: retro_key::RETROK_a,
+        character: 'a' as u32,
+        key_modifiers: retro_mod::RETROKMOD_NONE,
+    };
+    let shift = KeyEvent {
+        keycode: retro_key::RETROK_LSHIFT,
+        character: 0,
+        key_modifiers: retro_mod::RETROKMOD_SHIFT,
+    };
+
+    let mut repeat = KeyRepeat::new(3, 2);
+    assert_eq!(repeat.tick(), None);
+
+    repeat.handle_key(true, a);
+    repeat.handle_key(true, shift);
+
+    let repeats = (0..9).map(|_| repeat.tick().is_some()).collect::<Vec<_>>();
+    assert_eq!(
+        repeats,
+        [false, false, true, false, true, false, true, false, true]
+    );
+
+    repeat.handle_key(false, shift);
+    assert_eq!(repeat.held(), Some(a));
+
+    repeat.handle_key(false, a);
+    assert_eq!(repeat.held(), None);
+    assert_eq!(repeat.tick(), None);
+
+    assert_eq!(KeyRepeat::from_millis(500, 50, 60.0), KeyRepeat::new(30, 3));
+}
@@ -0,0 +1,464 @@
+//! Middleware that can be stacked around any [`Core`] without modifying it.
+//!
+//! A [`CoreLayer`] gets notified around the calls of the core, e.g. to record input,
+//! collect statistics or hash save states:
+//!
+//! ```rust,ignore
+//! retro_core!(Layers::new(MyCore::default())
+//!     .with(InputRecorder::default())
+//!     .with(SaveStateHasher::default()));
+//! ```
+//!
+//! Layers see the calls in the order they were added to [`Layers`], and the
+//! `after_*` hooks in reverse order, so the first layer wraps all later ones.
+//! Everything a layer doesn’t hook into gets passed to the core unchanged.
+//!
+//! Handlers registered with [`GenericContext::on_option_change`] get passed the
+//! [`Layers`], not the core itself.
+use crate::{
+    core::{Core, CoreOptions},
+    core_info::CoreInfo,
+    disk_control::DiskControl,
+    *,
+};
+use std::error::Error;
+
+/// Hooks that run around the calls of a [`Core`], see the [module documentation](self).
+///
+/// All hooks do nothing by default.
+#[allow(unused_variables)]
+pub trait CoreLayer {
+    /// Called after [`Core::on_init`].
+    fn after_init(&mut self, ctx: &mut InitContext) {}
+
+    /// Called before [`Core::on_deinit`].
+    fn before_deinit(&mut self, ctx: &mut DeinitContext) {}
+
+    /// Called after [`Core::on_load_game`] succeeded.
+    fn after_load_game(&mut self, game: Option<&retro_game_info>, ctx: &mut LoadGameContext) {}
+
+    /// Called before [`Core::on_unload_game`].
+    fn before_unload_game(&mut self, ctx: &mut UnloadGameContext) {}
+
+    /// Called after [`Core::on_reset`].
+    fn after_reset(&mut self, ctx: &mut ResetContext) {}
+
+    /// Called before [`Core::on_run`], e.g. to poll or record input.
+    fn before_run(&mut self, ctx: &mut RunContext, delta_us: Option<i64>) {}
+
+    /// Called after [`Core::on_run`].
+    fn after_run(&mut self, ctx: &mut RunContext) {}
+
+    /// Called after [`Core::on_serialize`] succeeded, with the state written by the core.
+    fn after_serialize(&mut self, state: &[u8]) {}
+
+    /// Called after [`Core::on_unserialize`] succeeded, with the state loaded by the core.
+    fn after_unserialize(&mut self, state: &[u8]) {}
+
+    /// Called after [`Core::on_options_changed`].
+    fn after_options_changed(&mut self, ctx: &mut OptionsChangedContext) {}
+}
+
+/// A [`Core`] wrapped in [`CoreLayer`]s, pass it to [`retro_core!()`].
+pub struct Layers<C> {
+    core: C,
+    layers: Vec<Box<dyn CoreLayer>>,
+}
+
+impl<C: Core> Layers<C> {
+    pub fn new(core: C) -> Self {
+        Self {
+            core,
+            layers: Vec::new(),
+        }
+    }
+
+    /// Adds `layer` inside of the layers added so far.
+    pub fn with(mut self, layer: impl CoreLayer + 'static) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    pub fn core(&self) -> &C {
+        &self.core
+    }
+
+    pub fn core_mut(&mut self) -> &mut C {
+        &mut self.core
+    }
+
+    fn before(&mut self, mut hook: impl FnMut(&mut dyn CoreLayer)) {
+        for layer in self.layers.iter_mut() {
+            hook(layer.as_mut());
+        }
+    }
+
+    fn after(&mut self, mut hook: impl FnMut(&mut dyn CoreLayer)) {
+        for layer in self.layers.iter_mut().rev() {
+            hook(layer.as_mut());
+        }
+    }
+}
+
+impl<C: Core> CoreOptions for Layers<C> {
+    fn set_core_options(&self, ctx: &SetEnvironmentContext) -> bool {
+        self.core.set_core_options(ctx)
+    }
+
+    fn option_definitions() -> CoreOptionDefinitions {
+        C::option_definitions()
+    }
+
+    fn option_visibility_rules(&self) -> &'static [OptionVisibilityRule] {
+        self.core.option_visibility_rules()
+    }
+}
+
+impl<C: Core> Core for Layers<C> {
+    fn get_info(&self) -> SystemInfo {
+        self.core.get_info()
+    }
+
+    fn supports_no_game(&self) -> bool {
+        self.core.supports_no_game()
+    }
+
+    fn core_info(&self) -> CoreInfo {
+        self.core.core_info()
+    }
+
+    fn on_get_av_info(&mut self, ctx: &mut GetAvInfoContext) -> retro_system_av_info {
+        self.core.on_get_av_info(ctx)
+    }
+
+    fn on_set_environment(&mut self, initial: bool, ctx: &mut SetEnvironmentContext) {
+        self.core.on_set_environment(initial, ctx)
+    }
+
+    fn on_before_set_core_options(&mut self, ctx: &mut SetEnvironmentContext) {
+        self.core.on_before_set_core_options(ctx)
+    }
+
+    fn on_init(&mut self, ctx: &mut InitContext) {
+        self.core.on_init(ctx);
+        self.after(|layer| layer.after_init(ctx));
+    }
+
+    fn on_deinit(&mut self, ctx: &mut DeinitContext) {
+        self.before(|layer| layer.before_deinit(ctx));
+        self.core.on_deinit(ctx)
+    }
+
+    fn on_set_controller_port_device(&mut self, port: u32, device: u32, ctx: &mut GenericContext) {
+        self.core.on_set_controller_port_device(port, device, ctx)
+    }
+
+    fn on_max_users_changed(&mut self, max_users: Option<u32>, ctx: &mut GenericContext) {
+        self.core.on_max_users_changed(max_users, ctx)
+    }
+
+    fn on_reset(&mut self, ctx: &mut ResetContext) {
+        self.core.on_reset(ctx);
+        self.after(|layer| layer.after_reset(ctx));
+    }
+
+    fn on_run(&mut self, ctx: &mut RunContext, delta_us: Option<i64>) {
+        self.core.on_run(ctx, delta_us)
+    }
+
+    fn on_run_timed(&mut self, ctx: &mut RunContext, frame_time: Option<timing::FrameTime>) {
+        let delta_us = frame_time.map(|frame_time| frame_time.usec());
+
+        self.before(|layer| layer.before_run(ctx, delta_us));
+        self.core.on_run_timed(ctx, frame_time);
+        self.after(|layer| layer.after_run(ctx));
+    }
+
+    fn get_serialize_size(&mut self, ctx: &mut GetSerializeSizeContext) -> size_t {
+        self.core.get_serialize_size(ctx)
+    }
+
+    fn on_serialize(&mut self, slice: &mut [u8], ctx: &mut SerializeContext) -> bool {
+        let success = self.core.on_serialize(slice, ctx);
+
+        if success {
+            self.after(|layer| layer.after_serialize(slice));
+        }
+
+        success
+    }
+
+    fn on_unserialize(&mut self, slice: &mut [u8], ctx: &mut UnserializeContext) -> bool {
+        let success = self.core.on_unserialize(slice, ctx);
+
+        if success {
+            self.after(|layer| layer.after_unserialize(slice));
+        }
+
+        success
+    }
+
+    fn on_load_game(
+        &mut self,
+        game: Option<retro_game_info>,
+        ctx: &mut LoadGameContext,
+    ) -> Result<(), Box<dyn Error>> {
+        self.core.on_load_game(game, ctx)?;
+        self.after(|layer| layer.after_load_game(game.as_ref(), ctx));
+
+        Ok(())
+    }
+
+    fn on_load_game_special(
+        &mut self,
+        game_type: std::os::raw::c_uint,
+        info: *const retro_game_info,
+        num_info: size_t,
+        ctx: &mut LoadGameSpecialContext,
+    ) -> Result<(), Box<dyn Error>> {
+        self.core
+            .on_load_game_special(game_type, info, num_info, ctx)
+    }
+
+    fn on_unload_game(&mut self, ctx: &mut UnloadGameContext) {
+        self.before(|layer| layer.before_unload_game(ctx));
+        self.core.on_unload_game(ctx)
+    }
+
+    fn on_cheat_reset(&mut self, ctx: &mut CheatResetContext) {
+        self.core.on_cheat_reset(ctx)
+    }
+
+    fn on_cheat_set(
+        &mut self,
+        index: std::os::raw::c_uint,
+        enabled: bool,
+        code: &CStr,
+        ctx: &mut CheatSetContext,
+    ) {
+        self.core.on_cheat_set(index, enabled, code, ctx)
+    }
+
+    fn on_get_region(&mut self, ctx: &mut GetRegionContext) -> std::os::raw::c_uint {
+        self.core.on_get_region(ctx)
+    }
+
+    fn get_memory_data(
+        &mut self,
+        id: std::os::raw::c_uint,
+        ctx: &mut GetMemoryDataContext,
+    ) -> *mut std::os::raw::c_void {
+        self.core.get_memory_data(id, ctx)
+    }
+
+    fn get_memory_size(
+        &mut self,
+        id: std::os::raw::c_uint,
+        ctx: &mut GetMemorySizeContext,
+    ) -> size_t {
+        self.core.get_memory_size(id, ctx)
+    }
+
+    fn on_sram_dirty(&mut self, ctx: &mut GenericContext) {
+        self.core.on_sram_dirty(ctx)
+    }
+
+    fn on_fastforward_changed(&mut self, fastforwarding: bool, ctx: &mut GenericContext) {
+        self.core.on_fastforward_changed(fastforwarding, ctx)
+    }
+
+    fn on_frame_overrun(&mut self, duration: std::time::Duration, ctx: &mut GenericContext) {
+        self.core.on_frame_overrun(duration, ctx)
+    }
+
+    fn on_options_changed(&mut self, ctx: &mut OptionsChangedContext) {
+        self.core.on_options_changed(ctx);
+        self.after(|layer| layer.after_options_changed(ctx));
+    }
+
+    fn on_keyboard_event(
+        &mut self,
+        down: bool,
+        keycode: retro_key,
+        character: u32,
+        key_modifiers: retro_mod,
+    ) {
+        self.core
+            .on_keyboard_event(down, keycode, character, key_modifiers)
+    }
+
+    #[allow(deprecated)]
+    fn on_write_audio(&mut self, ctx: &mut AudioContext) {
+        self.core.on_write_audio(ctx)
+    }
+
+    #[allow(deprecated)]
+    fn on_audio_set_state(&mut self, enabled: bool) {
+        self.core.on_audio_set_state(enabled)
+    }
+
+    fn on_audio_buffer_status(&mut self, active: bool, occupancy: u32, underrun_likely: bool) {
+        self.core
+            .on_audio_buffer_status(active, occupancy, underrun_likely)
+    }
+
+    fn on_hw_context_reset(&mut self) {
+        self.core.on_hw_context_reset()
+    }
+
+    fn on_hw_context_destroyed(&mut self) {
+        self.core.on_hw_context_destroyed()
+    }
+
+    fn on_hw_frame_presented(&mut self, ctx: &mut GenericContext) {
+        self.core.on_hw_frame_presented(ctx)
+    }
+
+    fn on_get_proc_address(&mut self, symbol_name: &CStr) -> retro_proc_address_t {
+        self.core.on_get_proc_address(symbol_name)
+    }
+
+    fn on_location_lifetime_status_initialized(&mut self, ctx: &mut GenericContext) {
+        self.core.on_location_lifetime_status_initialized(ctx)
+    }
+
+    fn on_location_lifetime_status_deinitialized(&mut self, ctx: &mut GenericContext) {
+        self.core.on_location_lifetime_status_deinitialized(ctx)
+    }
+
+    fn on_camera_initialized(&mut self, ctx: &mut GenericContext) {
+        self.core.on_camera_initialized(ctx)
+    }
+
+    fn on_camera_deinitialized(&mut self, ctx: &mut GenericContext) {
+        self.core.on_camera_deinitialized(ctx)
+    }
+
+    fn on_camera_raw_framebuffer(&mut self, buffer: &[u32], width: u32, height: u32, pitch: usize) {
+        self.core
+            .on_camera_raw_framebuffer(buffer, width, height, pitch)
+    }
+
+    fn on_camera_gl_texture(
+        &mut self,
+        texture_id: u32,
+        texture_target: u32,
+        affine_matrix: &[f32; 3 * 3],
+    ) {
+        self.core
+            .on_camera_gl_texture(texture_id, texture_target, affine_matrix)
+    }
+
+    fn disk_control(&mut self) -> Option<&mut DiskControl> {
+        self.core.disk_control()
+    }
+
+    fn on_set_eject_state(&mut self, ejected: bool) -> bool {
+        self.core.on_set_eject_state(ejected)
+    }
+
+    fn on_get_eject_state(&mut self) -> bool {
+        self.core.on_get_eject_state()
+    }
+
+    fn on_get_image_index(&mut self) -> u32 {
+        self.core.on_get_image_index()
+    }
+
+    fn on_set_image_index(&mut self, index: u32) -> bool {
+        self.core.on_set_image_index(index)
+    }
+
+    fn on_get_num_images(&mut self) -> u32 {
+        self.core.on_get_num_images()
+    }
+
+    fn on_replace_image_index(&mut self, index: u32, info: *const retro_game_info) -> bool {
+        self.core.on_replace_image_index(index, info)
+    }
+
+    fn on_add_image_index(&mut self) -> bool {
+        self.core.on_add_image_index()
+    }
+
+    fn on_set_initial_image(&mut self, index: u32, path: &CStr) -> bool {
+        self.core.on_set_initial_image(index, path)
+    }
+
+    fn on_get_image_path(&mut self, index: u32) -> Option<CString> {
+        self.core.on_get_image_path(index)
+    }
+
+    fn on_get_image_label(&mut self, index: u32) -> Option<CString> {
+        self.core.on_get_image_label(index)
+    }
+
+    fn on_core_options_update_display(&mut self) -> bool {
+        self.core.on_core_options_update_display()
+    }
+}
+
+#[test]
+fn layer_order() {
+    use crate::mock_frontend::StubCore;
+    use std::{cell::RefCell, rc::Rc};
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    struct TestCore(Log);
+
+    impl CoreOptions for TestCore {}
+    impl Core for TestCore {
+        fn get_info(&self) -> SystemInfo {
+            StubCore.get_info()
+        }
+
+        fn on_get_av_info(&mut self, ctx: &mut GetAvInfoContext) -> retro_system_av_info {
+            StubCore.on_get_av_info(ctx)
+        }
+
+        fn on_reset(&mut self, _ctx: &mut ResetContext) {
+            self.0.borrow_mut().push("core reset".into());
+        }
+
+        fn on_serialize(&mut self, slice: &mut [u8], _ctx: &mut SerializeContext) -> bool {
+            slice.fill(7);
+            true
+        }
+    }
+
+    struct TestLayer(&'static str, Log);
+
+    impl CoreLayer for TestLayer {
+        fn after_reset(&mut self, _ctx: &mut ResetContext) {
+            self.1.borrow_mut().push(format!("{} reset", self.0));
+        }
+
+        fn after_serialize(&mut self, state: &[u8]) {
+            self.1.borrow_mut().push(format!("{} {:?}", self.0, state));
+        }
+    }
+
+    let log = Log::default();
+    let mut core = Layers::new(TestCore(log.clone()))
+        .with(TestLayer("outer", log.clone()))
+        .with(TestLayer("inner", log.clone()));
+
+    let environment_callback: retro_environment_t = None;
+    let interfaces = core_wrapper::Interfaces::default();
+    let mut ctx = GenericContext::new(&environment_callback, &interfaces);
+
+    core.on_reset(&mut ctx);
+    assert!(core.on_serialize(&mut [0; 2], &mut ctx));
+
+    assert_eq!(
+        *log.borrow(),
+        [
+            "core reset",
+            "inner reset",
+            "outer reset",
+            "inner [7, 7]",
+            "outer [7, 7]"
+        ]
+    );
+}
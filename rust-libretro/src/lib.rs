@@ -8,34 +8,99 @@ mod core_wrapper;
 #[cfg(feature = "log")]
 mod logger;
 mod macros;
+#[cfg(test)]
+mod mock_frontend;
 
+#[cfg(feature = "log")]
+pub mod api_trace;
+#[cfg(all(feature = "tokio", not(target_os = "emscripten")))]
+pub mod async_runtime;
+pub mod audio_pacing;
+pub mod audio_state;
+#[cfg(feature = "av-dump")]
+pub mod av_dump;
+pub mod content;
 pub mod contexts;
 pub mod core;
+#[cfg(feature = "core-config")]
+pub mod core_config;
+pub mod core_info;
+#[cfg(feature = "debug-widgets")]
+pub mod debug;
+pub mod disk_control;
 pub mod environment;
+pub mod event_core;
+pub mod firmware;
+pub mod gl_sync;
+pub mod hw_resources;
+pub mod input_descriptor_set;
+pub mod input_transform;
+pub mod key_repeat;
+pub mod layers;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod options;
+pub mod ports;
+pub mod rumble;
+pub mod sample_scheduler;
+pub mod savestate;
+pub mod screen_layout;
+#[cfg(feature = "unstable-env-commands")]
+pub mod split_run;
+pub mod sram;
+#[cfg(feature = "state-format")]
+pub mod state_format;
+pub mod text_input;
+pub mod timing;
 pub mod types;
 pub mod util;
+#[cfg(feature = "unstable-env-commands")]
+pub mod vfs;
+#[cfg(feature = "unstable-env-commands")]
+pub mod vulkan;
+#[cfg(feature = "wgpu")]
+pub mod wgpu_device;
+
+// Lets the procedural macros refer to `::rust_libretro` inside of this crate as well
+#[cfg(feature = "debug-widgets")]
+extern crate self as rust_libretro;
 
 pub use macros::*;
 pub use rust_libretro_proc as proc;
 pub use rust_libretro_sys as sys;
 
-use crate::{contexts::*, core::Core, core_wrapper::CoreWrapper, sys::*, types::*, util::*};
+use crate::{
+    contexts::*,
+    core::{AnyCore, Core},
+    core_wrapper::{ApiCall, CoreState, CoreWrapper},
+    sys::*,
+    types::*,
+    util::*,
+};
 use std::{
     ffi::*,
     os::raw::c_char,
     path::{Path, PathBuf},
-    sync::Arc,
 };
 
 #[doc(hidden)]
 static mut RETRO_INSTANCE: Option<CoreWrapper> = None;
 
+/// Returns the core instance, if it has been created.
+///
+/// # Safety
+///
+/// The frontend calls the libretro API from a single thread, so only one
+/// of the returned references is in use at a time.
+unsafe fn instance() -> Option<&'static mut CoreWrapper> {
+    (*std::ptr::addr_of_mut!(RETRO_INSTANCE)).as_mut()
+}
+
 /// This macro must be used to initialize your [`Core`].
 ///
 /// # Examples
 /// ```rust
-/// # use rust_libretro::{contexts::*, core::{Core, CoreOptions}, sys::*, types::*, retro_core};
-/// # use std::ffi::CString;
+/// # use rust_libretro::{contexts::*, core::{Core, CoreOptions}, proc::c_str, sys::*, types::*, retro_core};
 /// struct ExampleCore {
 ///     option_1: bool,
 ///     option_2: bool,
@@ -58,9 +123,9 @@ static mut RETRO_INSTANCE: Option<CoreWrapper> = None;
 /// impl Core for ExampleCore {
 ///     fn get_info(&self) -> SystemInfo {
 ///         SystemInfo {
-///             library_name: CString::new("ExampleCore").unwrap(),
-///             library_version: CString::new("1.0.0").unwrap(),
-///             valid_extensions: CString::new("").unwrap(),
+///             library_name: c_str!("ExampleCore").into(),
+///             library_version: c_str!("1.0.0").into(),
+///             valid_extensions: c_str!("").into(),
 ///             need_fullpath: false,
 ///             block_extract: false,
 ///         }
@@ -97,12 +162,18 @@ macro_rules! retro_core {
 
 #[doc(hidden)]
 macro_rules! forward {
-    ($(#[doc = $doc:tt ], )* $wrapper:ident, $name:ident, $handler:ident $(-> $return_type:ty)?, $($context:tt)+) => {
+    ($(#[doc = $doc:tt ], )* $wrapper:ident, $name:ident, $handler:ident $(-> $return_type:ty)? $([$call:ident])?, $($context:tt)+) => {
         #[no_mangle]
         $(#[doc = $doc])*
         pub unsafe extern "C" fn $name() $(-> $return_type)? {
             // Check that the instance has been created
-            if let Some($wrapper) = RETRO_INSTANCE.as_mut() {
+            if let Some($wrapper) = instance() {
+                $(
+                    if !$wrapper.check_call(ApiCall::$call) {
+                        return Default::default();
+                    }
+                )?
+
                 // Forward to the Core implementation
                 let mut ctx = $($context)+;
                 return $wrapper.core.$handler(&mut ctx);
@@ -113,6 +184,21 @@ macro_rules! forward {
     };
 }
 
+/// Logs a call of the libretro API at the rate configured in [`api_trace`].
+///
+/// The message has to start with the name of the entry point, e.g. `"retro_run()"`.
+#[cfg(feature = "log")]
+#[doc(hidden)]
+macro_rules! trace_api {
+    ($group:ident, $fmt:literal $($args:tt)*) => {{
+        static CALLS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+        if let Some(number) = api_trace::sample(api_trace::ApiGroup::$group, $fmt, &CALLS) {
+            log::trace!("{}{number}", format_args!($fmt $($args)*));
+        }
+    }};
+}
+
 #[doc(hidden)]
 macro_rules! callback {
     ($(#[doc = $doc:tt ], )* $name:ident, $arg:ident, $handler:ident) => {
@@ -120,7 +206,7 @@ macro_rules! callback {
         $(#[doc = $doc])*
         pub unsafe extern "C" fn $name(arg1: $arg) {
             // Check that the instance has been created
-            if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
+            if let Some(wrapper) = instance() {
                 if arg1.is_some() {
                     // We were given a callback, make sure that it’s not a NULL pointer
                     if (arg1.unwrap() as *const c_void).is_null() {
@@ -147,32 +233,53 @@ macro_rules! callback {
 #[doc(hidden)]
 pub fn set_core<C: 'static + Core>(core: C) {
     unsafe {
-        if RETRO_INSTANCE.is_some() {
-            let core = &RETRO_INSTANCE.as_ref().unwrap().core;
-            let info = core.get_info();
-            let name = info.library_name.into_string().unwrap();
-            let version = info.library_version.into_string().unwrap();
+        if let Some(wrapper) = instance() {
+            let info = wrapper.core.get_info();
+            let name = info.library_name.to_string_lossy();
+            let version = info.library_version.to_string_lossy();
 
             panic!("Attempted to set a core after the system was already initialized.\nAlready registered core: {} {}", name, version)
         }
 
-        RETRO_INSTANCE.replace(CoreWrapper::new(core));
+        *std::ptr::addr_of_mut!(RETRO_INSTANCE) = Some(CoreWrapper::new(core));
     }
 }
 
+#[cfg(feature = "log")]
+static LOGGER: once_cell::sync::OnceCell<logger::RetroLogger> = once_cell::sync::OnceCell::new();
+
+/// Installs the logger and hands it the frontend’s logging interface once available.
+///
+/// Records logged before that are buffered and replayed (see [`logger::RetroLogger`]).
 #[cfg(feature = "log")]
 #[doc(hidden)]
 fn init_log(env_callback: retro_environment_t) {
-    let retro_logger = unsafe { environment::get_log_callback(env_callback) };
+    let mut installed = false;
+    let retro_logger = LOGGER.get_or_init(|| {
+        installed = true;
+        logger::RetroLogger::new()
+    });
+
+    if installed {
+        log::set_max_level(log::LevelFilter::Trace);
+        log::set_logger(retro_logger).expect("could not set logger");
+    }
 
-    let retro_logger = if let Ok(Some(log_callback)) = retro_logger {
-        logger::RetroLogger::new(log_callback)
-    } else {
-        logger::RetroLogger::new(retro_log_callback { log: None })
-    };
+    if !retro_logger.has_callback() {
+        if let Ok(log_callback) = unsafe { environment::get_log_callback(env_callback) } {
+            if log_callback.log.is_some() {
+                retro_logger.set_callback(log_callback);
+            }
+        }
+    }
+}
 
-    log::set_max_level(log::LevelFilter::Trace);
-    log::set_boxed_logger(Box::new(retro_logger)).expect("could not set logger");
+/// Gives up on the logging interface, the frontend would have provided it by now.
+fn stop_log_buffering() {
+    #[cfg(feature = "log")]
+    if let Some(retro_logger) = LOGGER.get() {
+        retro_logger.stop_buffering();
+    }
 }
 
 /*****************************************************************************\
@@ -183,15 +290,8 @@ forward!(
     #[doc = "Notifies the [`Core`] when all cheats should be unapplied."],
     wrapper,
     retro_cheat_reset,
-    on_cheat_reset,
-    GenericContext::new(&wrapper.environment_callback, Arc::clone(&wrapper.interfaces))
-);
-forward!(
-    #[doc = "Notifies the [`Core`] when it is being closed and its resources should be freed."],
-    wrapper,
-    retro_deinit,
-    on_deinit,
-    GenericContext::new(&wrapper.environment_callback, Arc::clone(&wrapper.interfaces))
+    on_cheat_reset [CheatReset],
+    GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces)
 );
 forward!(
     #[doc = "Called when the frontend needs region information from the [`Core`]."],
@@ -201,30 +301,14 @@ forward!(
     wrapper,
     retro_get_region,
     on_get_region -> std::os::raw::c_uint,
-    GenericContext::new(&wrapper.environment_callback, Arc::clone(&wrapper.interfaces))
+    GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces)
 );
 forward!(
     #[doc = "Notifies the [`Core`] when the current game should be reset."],
     wrapper,
     retro_reset,
-    on_reset,
-    GenericContext::new(&wrapper.environment_callback, Arc::clone(&wrapper.interfaces))
-);
-forward!(
-    #[doc = "Called when the frontend needs to know how large a buffer to allocate for save states."],
-    #[doc = ""],
-    #[doc = "See also [`rust_libretro_sys::retro_serialize_size`]."],
-    wrapper,
-    retro_serialize_size,
-    get_serialize_size -> size_t,
-    GenericContext::new(&wrapper.environment_callback, Arc::clone(&wrapper.interfaces))
-);
-forward!(
-    #[doc = "Notifies the [`Core`] when the currently loaded game should be unloaded. Called before [`retro_deinit`]."],
-    wrapper,
-    retro_unload_game,
-    on_unload_game,
-    GenericContext::new(&wrapper.environment_callback, Arc::clone(&wrapper.interfaces))
+    on_reset [Reset],
+    GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces)
 );
 
 callback!(
@@ -272,7 +356,7 @@ callback!(
 #[no_mangle]
 pub unsafe extern "C" fn retro_api_version() -> std::os::raw::c_uint {
     #[cfg(feature = "log")]
-    log::trace!("retro_api_version()");
+    trace_api!(Lifecycle, "retro_api_version()");
 
     RETRO_API_VERSION
 }
@@ -283,15 +367,16 @@ pub unsafe extern "C" fn retro_api_version() -> std::os::raw::c_uint {
 #[no_mangle]
 pub unsafe extern "C" fn retro_init() {
     #[cfg(feature = "log")]
-    log::trace!("retro_init()");
+    trace_api!(Lifecycle, "retro_init()");
+
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::Init) {
+            return;
+        }
 
-    if let Some(mut wrapper) = RETRO_INSTANCE.as_mut() {
-        wrapper.can_dupe = environment::can_dupe(wrapper.environment_callback);
+        wrapper.state = CoreState::Initialized;
 
-        let mut ctx = InitContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+        let mut ctx = InitContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         return wrapper.core.on_init(&mut ctx);
     }
@@ -299,13 +384,102 @@ pub unsafe extern "C" fn retro_init() {
     panic!("retro_init: Core has not been initialized yet!");
 }
 
+/// Notifies the [`Core`] when it is being closed and its resources should be freed.
+///
+/// If the frontend did not unload the game first, [`Core::on_unload_game`] gets called before.
+#[no_mangle]
+pub unsafe extern "C" fn retro_deinit() {
+    #[cfg(feature = "log")]
+    trace_api!(Lifecycle, "retro_deinit()");
+
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::Deinit) {
+            return;
+        }
+
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
+
+        if wrapper.state == CoreState::GameLoaded {
+            #[cfg(feature = "log")]
+            log::warn!("retro_deinit called without retro_unload_game, unloading the game first");
+
+            wrapper.core.on_unload_game(&mut ctx);
+        }
+
+        wrapper.state = CoreState::Uninitialized;
+
+        wrapper.core.on_deinit(&mut ctx);
+
+        // The frontend doesn’t read the perf counters anymore
+        wrapper.interfaces.write().unwrap().perf_interface.clear();
+
+        audio_state::AudioThread::disable();
+
+        #[cfg(all(feature = "tokio", not(target_os = "emscripten")))]
+        async_runtime::AsyncRuntime::shutdown();
+
+        return;
+    }
+
+    panic!("retro_deinit: Core has not been initialized yet!");
+}
+
+/// Creates the core instance and queries its [`SystemInfo`] on the first call.
+///
+/// `retro_get_system_info` requires statically allocated data, which lives in a
+/// [`OnceCell`](once_cell::sync::OnceCell) instead of being leaked.
+/// Concurrent callers block until the first one is done, so the core gets created
+/// exactly once, regardless of which thread calls [`retro_get_system_info`] or
+/// [`retro_set_environment`] first.
+fn system_info() -> &'static SystemInfo {
+    static SYS_INFO: once_cell::sync::OnceCell<SystemInfo> = once_cell::sync::OnceCell::new();
+
+    SYS_INFO.get_or_init(|| unsafe {
+        extern "Rust" {
+            fn __retro_init_core();
+        }
+        __retro_init_core();
+
+        match instance() {
+            Some(wrapper) => wrapper.core.get_info(),
+            None => panic!("No core instance found!"),
+        }
+    })
+}
+
+/// Returns the `.info` file of the [`Core`], see [`core_info`](mod@core_info).
+///
+/// Exported as [`core_info::CORE_INFO_SYMBOL`] for `cargo retro-info`, frontends don’t call it.
+/// The returned string stays valid until the core gets unloaded, `NULL` gets returned
+/// if the core instance couldn’t be created.
+#[no_mangle]
+pub unsafe extern "C" fn rust_libretro_core_info() -> *const c_char {
+    static CORE_INFO: once_cell::sync::OnceCell<CString> = once_cell::sync::OnceCell::new();
+
+    let info = CORE_INFO.get_or_try_init(|| {
+        // Creates the core instance
+        system_info();
+
+        let Some(wrapper) = (*std::ptr::addr_of!(RETRO_INSTANCE)).as_ref() else {
+            return Err(());
+        };
+
+        let mut info = wrapper.core.core_info();
+        info.supports_no_game = wrapper.core.supports_no_game();
+
+        Ok(CString::new(info.to_string()).unwrap_or_default())
+    });
+
+    info.map_or(std::ptr::null(), |info| info.as_ptr())
+}
+
 /// Provides _statically known_ system info to the frontend.
 ///
 /// See also [`rust_libretro_sys::retro_get_system_info`].
 #[no_mangle]
 pub unsafe extern "C" fn retro_get_system_info(info: *mut retro_system_info) {
     #[cfg(feature = "log")]
-    log::trace!("retro_get_system_info(info = {info:#?})");
+    trace_api!(Lifecycle, "retro_get_system_info(info = {info:#?})");
 
     // Make sure that the pointer we got is plausible
     if info.is_null() {
@@ -315,28 +489,7 @@ pub unsafe extern "C" fn retro_get_system_info(info: *mut retro_system_info) {
     // We didn’t get a NULL pointer, so this should be safe
     let info = &mut *info;
 
-    // retro_get_system_info requires statically allocated data
-    // This is unsafe because we mutate a static value.
-    //
-    // TODO: Should this be put behind an Arc<Mutex> or Arc<RwLock>?
-    static mut SYS_INFO: Option<*const SystemInfo> = None;
-
-    let sys_info = {
-        if SYS_INFO.is_none() {
-            extern "Rust" {
-                fn __retro_init_core();
-            }
-            __retro_init_core();
-
-            if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-                SYS_INFO = Some(Box::into_raw(Box::new(wrapper.core.get_info())));
-            } else {
-                panic!("No core instance found!");
-            }
-        }
-
-        &*SYS_INFO.unwrap()
-    };
+    let sys_info = system_info();
 
     info.library_name = sys_info.library_name.as_ptr();
     info.library_version = sys_info.library_version.as_ptr();
@@ -353,9 +506,9 @@ pub unsafe extern "C" fn retro_get_system_info(info: *mut retro_system_info) {
 #[no_mangle]
 pub unsafe extern "C" fn retro_get_system_av_info(info: *mut retro_system_av_info) {
     #[cfg(feature = "log")]
-    log::trace!("retro_get_system_av_info(info = {info:#?})");
+    trace_api!(Lifecycle, "retro_get_system_av_info(info = {info:#?})");
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
+    if let Some(wrapper) = instance() {
         // Make sure that the pointer we got is plausible
         if info.is_null() {
             panic!("Expected retro_system_av_info, got NULL pointer instead!");
@@ -364,16 +517,31 @@ pub unsafe extern "C" fn retro_get_system_av_info(info: *mut retro_system_av_inf
         // We didn’t get a NULL pointer, so this should be safe
         let info = &mut *info;
 
-        let mut ctx = GetAvInfoContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+        let mut ctx = GetAvInfoContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         let av_info = wrapper.core.on_get_av_info(&mut ctx);
 
-        info.geometry = av_info.geometry;
+        let mut interfaces = wrapper.interfaces.write().unwrap();
+
+        info.geometry = match interfaces.software_rotation {
+            Some(rotation) => util::rotate::rotate_geometry(av_info.geometry, rotation),
+            None => av_info.geometry,
+        };
         info.timing = av_info.timing;
 
+        interfaces.av_info = Some(av_info);
+
+        #[cfg(feature = "av-dump")]
+        if interfaces.av_dump.is_none() {
+            match av_dump::AvDump::from_env(&av_info) {
+                Ok(dump) => interfaces.av_dump = dump,
+                Err(_err) => {
+                    #[cfg(feature = "log")]
+                    log::warn!("Failed to start dumping audio and video: {_err}");
+                }
+            }
+        }
+
         return;
     }
 
@@ -388,9 +556,15 @@ pub unsafe extern "C" fn retro_get_system_av_info(info: *mut retro_system_av_inf
 #[no_mangle]
 pub unsafe extern "C" fn retro_set_environment(environment: retro_environment_t) {
     #[cfg(feature = "log")]
-    log::trace!("retro_set_environment(environment = {environment:#?})");
+    trace_api!(
+        Lifecycle,
+        "retro_set_environment(environment = {environment:#?})"
+    );
+
+    // Frontends may call us before `retro_get_system_info`, possibly from another thread
+    system_info();
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
+    if let Some(wrapper) = instance() {
         let mut initial = false;
 
         if let Some(callback) = environment {
@@ -398,24 +572,27 @@ pub unsafe extern "C" fn retro_set_environment(environment: retro_environment_t)
                 initial = true;
                 wrapper.environment_set = true;
 
-                #[cfg(feature = "log")]
-                init_log(Some(callback));
-
-                #[cfg(feature = "unstable-env-commands")]
-                {
-                    wrapper.supports_bitmasks = environment::get_input_bitmasks(Some(callback));
-                }
+                wrapper.supports_bitmasks = environment::get_input_bitmasks(Some(callback));
             }
 
+            // The logging interface might only become available on a later call
+            #[cfg(feature = "log")]
+            init_log(Some(callback));
+
             wrapper.environment_callback.replace(callback);
         } else {
             wrapper.environment_callback.take();
         }
 
-        let mut ctx = SetEnvironmentContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+        wrapper.invalidate_environment_cache();
+
+        let mut ctx =
+            SetEnvironmentContext::new(&wrapper.environment_callback, &wrapper.interfaces);
+
+        if initial {
+            // Lets cores change the `OptionsCompat` mode before the options get registered
+            wrapper.core.on_before_set_core_options(&mut ctx);
+        }
 
         // Our default implementation of `set_core_options` uses `RETRO_ENVIRONMENT_GET_CORE_OPTIONS_VERSION`,
         // which seems to only work on the first call to `retro_set_environment`.
@@ -424,7 +601,26 @@ pub unsafe extern "C" fn retro_set_environment(environment: retro_environment_t)
             log::warn!("Failed to set core options");
         }
 
-        return wrapper.core.on_set_environment(initial, &mut ctx);
+        if initial && wrapper.core.supports_no_game() && ctx.set_support_no_game(true).is_err() {
+            #[cfg(feature = "log")]
+            log::warn!("Failed to enable support for running without content");
+        }
+
+        wrapper.core.on_set_environment(initial, &mut ctx);
+
+        // Exports `metrics::METRICS_SYMBOL`
+        #[cfg(feature = "metrics")]
+        if initial && ctx.enable_proc_address_interface().is_err() {
+            #[cfg(feature = "log")]
+            log::debug!("The frontend does not support the proc address interface");
+        }
+
+        if initial {
+            // Options are visible by default, hide those that depend on other devices
+            wrapper.update_option_visibility();
+        }
+
+        return;
     }
 
     panic!("retro_set_environment: Core has not been initialized yet!");
@@ -439,17 +635,26 @@ pub unsafe extern "C" fn retro_set_controller_port_device(
     device: std::os::raw::c_uint,
 ) {
     #[cfg(feature = "log")]
-    log::trace!("retro_set_controller_port_device(port = {port}, device = {device})");
+    trace_api!(
+        Lifecycle,
+        "retro_set_controller_port_device(port = {port}, device = {device})"
+    );
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = GenericContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+    if let Some(wrapper) = instance() {
+        let index = port as usize;
+        if wrapper.port_devices.len() <= index {
+            wrapper.port_devices.resize(index + 1, RETRO_DEVICE_JOYPAD);
+        }
+        wrapper.port_devices[index] = device;
 
-        return wrapper
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
+
+        wrapper
             .core
             .on_set_controller_port_device(port, device, &mut ctx);
+
+        wrapper.update_option_visibility();
+        return;
     }
 
     panic!("retro_set_controller_port_device: Core has not been initialized yet!");
@@ -461,25 +666,61 @@ pub unsafe extern "C" fn retro_set_controller_port_device(
 #[no_mangle]
 pub unsafe extern "C" fn retro_run() {
     #[cfg(feature = "log")]
-    log::trace!("retro_run()");
+    trace_api!(Frame, "retro_run()");
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        if environment::get_variable_update(wrapper.environment_callback) {
-            let mut ctx = OptionsChangedContext::new(
-                &wrapper.environment_callback,
-                Arc::clone(&wrapper.interfaces),
-            );
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::Run) {
+            return;
+        }
 
-            wrapper.core.on_options_changed(&mut ctx);
+        #[cfg(feature = "metrics")]
+        let frame_start = std::time::Instant::now();
+
+        for transitions in wrapper.joypad_transitions.iter_mut() {
+            transitions.polled = false;
         }
 
         if let Some(callback) = wrapper.input_poll_callback {
             (callback)();
         }
 
+        // Everything else this frame needs from the interfaces
+        let snapshot = wrapper.begin_frame();
+
+        if environment::get_variable_update(wrapper.environment_callback) {
+            wrapper.notify_options_changed();
+        } else if snapshot.options_pending {
+            wrapper.dispatch_option_handlers();
+        }
+
+        if snapshot.options_resubmitted {
+            // The frontend made all options visible again
+            wrapper.option_visibility.clear();
+            wrapper.update_option_visibility();
+        }
+
+        if let Some((text, frames)) = &snapshot.message {
+            wrapper.show_queued_message(text, *frames);
+        }
+
+        if let Some(event) = snapshot.key_repeat {
+            wrapper.dispatch_keyboard_event(true, event);
+        }
+
+        #[cfg(all(feature = "tokio", not(target_os = "emscripten")))]
+        if let Some(runtime) = async_runtime::AsyncRuntime::get() {
+            runtime.poll();
+        }
+
+        if snapshot.sram_due {
+            wrapper.check_sram();
+        }
+
+        wrapper.update_frame_hints();
+
         let mut ctx = RunContext {
             environment_callback: &wrapper.environment_callback,
-            interfaces: Arc::clone(&wrapper.interfaces),
+            interfaces: &wrapper.interfaces,
 
             video_refresh_callback: &wrapper.video_refresh_callback,
             audio_sample_callback: &wrapper.audio_sample_callback,
@@ -487,21 +728,87 @@ pub unsafe extern "C" fn retro_run() {
             input_poll_callback: &wrapper.input_poll_callback,
             input_state_callback: &wrapper.input_state_callback,
 
-            can_dupe: wrapper.can_dupe,
+            hints: wrapper.hints,
+            software_rotation: wrapper.software_rotation,
             had_frame: &mut wrapper.had_frame,
             last_width: &mut wrapper.last_width,
             last_height: &mut wrapper.last_height,
             last_pitch: &mut wrapper.last_pitch,
 
+            capture_frames: &mut wrapper.capture_frames,
+            captured_frame: &mut wrapper.captured_frame,
+            hw_frame_presented: &mut wrapper.hw_frame_presented,
+            frame_dedup: &mut wrapper.frame_dedup,
+
             supports_bitmasks: wrapper.supports_bitmasks,
+            joypad_transitions: &mut wrapper.joypad_transitions,
+            port_devices: &wrapper.port_devices,
         };
 
-        return wrapper.core.on_run(&mut ctx, wrapper.frame_delta.take());
+        let start = snapshot.watchdog.then(std::time::Instant::now);
+
+        let reference = snapshot.frame_time_reference;
+        let frame_time = wrapper.frame_delta.take().map(|usec| {
+            let now = std::time::Instant::now();
+            let since_last_run = wrapper.last_run.replace(now).map(|last_run| now - last_run);
+
+            timing::FrameTime::classify(usec, reference, wrapper.throttle_mode, since_last_run)
+        });
+
+        wrapper.core.on_run_timed(&mut ctx, frame_time);
+
+        if std::mem::take(&mut wrapper.hw_frame_presented) {
+            let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
+            wrapper.core.on_hw_frame_presented(&mut ctx);
+        }
+
+        if snapshot.text_input {
+            wrapper.dispatch_text_input();
+        }
+
+        if let Some(start) = start {
+            wrapper.update_frame_watchdog(start.elapsed());
+        }
+
+        #[cfg(feature = "metrics")]
+        metrics::record_frame(frame_start.elapsed());
+
+        return;
     }
 
     panic!("retro_run: Core has not been initialized yet!");
 }
 
+/// Called when the frontend needs to know how large a buffer to allocate for save states.
+///
+/// See also [`rust_libretro_sys::retro_serialize_size`].
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize_size() -> size_t {
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::SerializeSize) {
+            return 0;
+        }
+
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
+
+        let size = wrapper.core.get_serialize_size(&mut ctx);
+        let versioned = wrapper
+            .interfaces
+            .read()
+            .unwrap()
+            .state_migrations
+            .is_some();
+
+        if versioned && size > 0 {
+            return size + savestate::StateMigrations::HEADER_SIZE as size_t;
+        }
+
+        return size;
+    }
+
+    panic!("retro_serialize_size: Core has not been initialized yet!");
+}
+
 /// Called by the frontend when the [`Core`]s state should be serialized (“save state”).
 /// This function should return [`false`] on error.
 ///
@@ -509,7 +816,7 @@ pub unsafe extern "C" fn retro_run() {
 #[no_mangle]
 pub unsafe extern "C" fn retro_serialize(data: *mut std::os::raw::c_void, size: size_t) -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_serialize(data = {data:#?}, size = {size})");
+    trace_api!(State, "retro_serialize(data = {data:#?}, size = {size})");
 
     if data.is_null() {
         #[cfg(feature = "log")]
@@ -518,14 +825,23 @@ pub unsafe extern "C" fn retro_serialize(data: *mut std::os::raw::c_void, size:
         return false;
     }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = GenericContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::Serialize) {
+            return false;
+        }
+
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         // Convert the given buffer into a proper slice
-        let slice = std::slice::from_raw_parts_mut(data as *mut u8, size as usize);
+        let mut slice = std::slice::from_raw_parts_mut(data as *mut u8, size as usize);
+
+        if let Some(migrations) = wrapper.interfaces.read().unwrap().state_migrations.as_ref() {
+            if !migrations.write_header(slice) {
+                return false;
+            }
+
+            slice = &mut slice[savestate::StateMigrations::HEADER_SIZE..];
+        }
 
         return wrapper.core.on_serialize(slice, &mut ctx);
     }
@@ -543,7 +859,7 @@ pub unsafe extern "C" fn retro_unserialize(
     size: size_t,
 ) -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_unserialize(data = {data:#?}, size = {size})");
+    trace_api!(State, "retro_unserialize(data = {data:#?}, size = {size})");
 
     if data.is_null() {
         #[cfg(feature = "log")]
@@ -552,16 +868,32 @@ pub unsafe extern "C" fn retro_unserialize(
         return false;
     }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = GenericContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::Unserialize) {
+            return false;
+        }
+
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         // Convert the given buffer into a proper slice
         let slice = std::slice::from_raw_parts_mut(data as *mut u8, size as usize);
 
-        return wrapper.core.on_unserialize(slice, &mut ctx);
+        // The core may use the interfaces while loading the state
+        let migrations = wrapper.interfaces.write().unwrap().state_migrations.take();
+
+        let loaded = match migrations.as_ref() {
+            Some(migrations) => {
+                migrations.load(slice, |state| wrapper.core.on_unserialize(state, &mut ctx))
+            }
+            None => wrapper.core.on_unserialize(slice, &mut ctx),
+        };
+
+        let mut interfaces = wrapper.interfaces.write().unwrap();
+        if interfaces.state_migrations.is_none() {
+            interfaces.state_migrations = migrations;
+        }
+
+        return loaded;
     }
 
     panic!("retro_unserialize: Core has not been initialized yet!");
@@ -578,7 +910,10 @@ pub unsafe extern "C" fn retro_cheat_set(
     code: *const std::os::raw::c_char,
 ) {
     #[cfg(feature = "log")]
-    log::trace!("retro_cheat_set(index = {index}, enabled = {enabled}, code = {code:#?})");
+    trace_api!(
+        State,
+        "retro_cheat_set(index = {index}, enabled = {enabled}, code = {code:#?})"
+    );
 
     if code.is_null() {
         #[cfg(feature = "log")]
@@ -587,11 +922,12 @@ pub unsafe extern "C" fn retro_cheat_set(
         return;
     }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = GenericContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::CheatSet) {
+            return;
+        }
+
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         // Wrap the pointer into a `CStr`.
         // This assumes the pointer is valid and ends on a null byte.
@@ -613,20 +949,25 @@ pub unsafe extern "C" fn retro_cheat_set(
 #[no_mangle]
 pub unsafe extern "C" fn retro_load_game(game: *const retro_game_info) -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_load_game(game_type = {game:#?})");
+    trace_api!(Lifecycle, "retro_load_game(game_type = {game:#?})");
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = OptionsChangedContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+    stop_log_buffering();
 
-        wrapper.core.on_options_changed(&mut ctx);
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::LoadGame) {
+            return false;
+        }
 
-        let mut ctx = LoadGameContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+        wrapper.notify_options_changed();
+
+        if let Err(_err) = wrapper.check_firmware() {
+            #[cfg(feature = "log")]
+            log::error!("Failed to load game: {_err}");
+
+            return false;
+        }
+
+        let mut ctx = LoadGameContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         let status = if game.is_null() {
             wrapper.core.on_load_game(None, &mut ctx)
@@ -634,6 +975,12 @@ pub unsafe extern "C" fn retro_load_game(game: *const retro_game_info) -> bool {
             wrapper.core.on_load_game(Some(*game), &mut ctx)
         };
 
+        if status.is_ok() {
+            wrapper.state = CoreState::GameLoaded;
+        }
+
+        wrapper.software_rotation = wrapper.interfaces.read().unwrap().software_rotation;
+
         cfg_if::cfg_if! {
             if #[cfg(feature = "log")] {
                 match status {
@@ -661,7 +1008,8 @@ pub unsafe extern "C" fn retro_load_game_special(
     num_info: size_t,
 ) -> bool {
     #[cfg(feature = "log")]
-    log::trace!(
+    trace_api!(
+        Lifecycle,
         "retro_load_game_special(game_type = {game_type}, info = {info:#?}, num_info = {num_info})"
     );
 
@@ -672,23 +1020,35 @@ pub unsafe extern "C" fn retro_load_game_special(
         return false;
     }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = OptionsChangedContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+    stop_log_buffering();
+
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::LoadGame) {
+            return false;
+        }
+
+        wrapper.notify_options_changed();
 
-        wrapper.core.on_options_changed(&mut ctx);
+        if let Err(_err) = wrapper.check_firmware() {
+            #[cfg(feature = "log")]
+            log::error!("Failed to load game: {_err}");
+
+            return false;
+        }
 
-        let mut ctx = LoadGameSpecialContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+        let mut ctx =
+            LoadGameSpecialContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         let status = wrapper
             .core
             .on_load_game_special(game_type, info, num_info, &mut ctx);
 
+        if status.is_ok() {
+            wrapper.state = CoreState::GameLoaded;
+        }
+
+        wrapper.software_rotation = wrapper.interfaces.read().unwrap().software_rotation;
+
         cfg_if::cfg_if! {
             if #[cfg(feature = "log")] {
                 match status {
@@ -708,6 +1068,37 @@ pub unsafe extern "C" fn retro_load_game_special(
     panic!("retro_load_game_special: Core has not been initialized yet!");
 }
 
+/// Notifies the [`Core`] when the currently loaded game should be unloaded. Called before [`retro_deinit`].
+#[no_mangle]
+pub unsafe extern "C" fn retro_unload_game() {
+    #[cfg(feature = "log")]
+    trace_api!(Lifecycle, "retro_unload_game()");
+
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::UnloadGame) {
+            return;
+        }
+
+        wrapper.state = CoreState::Initialized;
+
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
+
+        wrapper.core.on_unload_game(&mut ctx);
+
+        // The next content starts with freshly registered perf counters
+        wrapper
+            .interfaces
+            .write()
+            .unwrap()
+            .perf_interface
+            .unregister_all();
+
+        return;
+    }
+
+    panic!("retro_unload_game: Core has not been initialized yet!");
+}
+
 /// Returns a mutable pointer to queried memory type.
 /// Return [`std::ptr::null()`] in case this doesn’t apply to your [`Core`].
 ///
@@ -717,13 +1108,14 @@ pub unsafe extern "C" fn retro_get_memory_data(
     id: std::os::raw::c_uint,
 ) -> *mut std::os::raw::c_void {
     #[cfg(feature = "log")]
-    log::trace!("retro_get_memory_data(id = {id})");
+    trace_api!(State, "retro_get_memory_data(id = {id})");
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = GenericContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::GetMemory) {
+            return std::ptr::null_mut();
+        }
+
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         return wrapper.core.get_memory_data(id, &mut ctx);
     }
@@ -738,13 +1130,14 @@ pub unsafe extern "C" fn retro_get_memory_data(
 #[no_mangle]
 pub unsafe extern "C" fn retro_get_memory_size(id: std::os::raw::c_uint) -> size_t {
     #[cfg(feature = "log")]
-    log::trace!("retro_get_memory_size(id = {id})");
+    trace_api!(State, "retro_get_memory_size(id = {id})");
+
+    if let Some(wrapper) = instance() {
+        if !wrapper.check_call(ApiCall::GetMemory) {
+            return 0;
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = GenericContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         return wrapper.core.get_memory_size(id, &mut ctx);
     }
@@ -771,9 +1164,9 @@ pub unsafe extern "C" fn retro_keyboard_callback_fn(
     key_modifiers: u16,
 ) {
     #[cfg(feature = "log")]
-    log::trace!("retro_keyboard_callback_fn(down = {down}, keycode = {keycode}, character = {character}, key_modifiers = {key_modifiers})");
+    trace_api!(Callback, "retro_keyboard_callback_fn(down = {down}, keycode = {keycode}, character = {character}, key_modifiers = {key_modifiers})");
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
+    if let Some(wrapper) = instance() {
         // Not sure why bindgen uses `c_int32` as value type
         // for the newtype enum on Windows but `c_uint32` on Unix.
         cfg_if::cfg_if! {
@@ -782,12 +1175,17 @@ pub unsafe extern "C" fn retro_keyboard_callback_fn(
             }
         };
 
-        return wrapper.core.on_keyboard_event(
-            down,
-            retro_key(keycode),
+        let event = key_repeat::KeyEvent {
+            keycode: retro_key(keycode),
             character,
-            retro_mod(key_modifiers.into()),
-        );
+            key_modifiers: retro_mod(key_modifiers.into()),
+        };
+
+        if let Some(key_repeat) = wrapper.interfaces.write().unwrap().key_repeat.as_mut() {
+            key_repeat.handle_key(down, event);
+        }
+
+        return wrapper.dispatch_keyboard_event(down, event);
     }
 
     panic!("retro_keyboard_callback_fn: Core has not been initialized yet!");
@@ -797,9 +1195,23 @@ pub unsafe extern "C" fn retro_keyboard_callback_fn(
 #[no_mangle]
 pub unsafe extern "C" fn retro_hw_context_reset_callback() {
     #[cfg(feature = "log")]
-    log::trace!("retro_hw_context_reset_callback()");
+    trace_api!(Callback, "retro_hw_context_reset_callback()");
+
+    if let Some(wrapper) = instance() {
+        {
+            let mut interfaces = wrapper.interfaces.write().unwrap();
+
+            let info = interfaces
+                .av_info
+                .map(|av_info| hw_resources::HwResourceInfo {
+                    width: av_info.geometry.base_width,
+                    height: av_info.geometry.base_height,
+                })
+                .unwrap_or_default();
+
+            interfaces.hw_resources.reset(info);
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
         return wrapper.core.on_hw_context_reset();
     }
 
@@ -810,10 +1222,17 @@ pub unsafe extern "C" fn retro_hw_context_reset_callback() {
 #[no_mangle]
 pub unsafe extern "C" fn retro_hw_context_destroyed_callback() {
     #[cfg(feature = "log")]
-    log::trace!("retro_hw_context_destroyed_callback()");
+    trace_api!(Callback, "retro_hw_context_destroyed_callback()");
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        return wrapper.core.on_hw_context_destroyed();
+    if let Some(wrapper) = instance() {
+        wrapper.core.on_hw_context_destroyed();
+        wrapper.interfaces.write().unwrap().hw_resources.destroy();
+
+        // The frontend frees the interface along with the context
+        #[cfg(feature = "unstable-env-commands")]
+        vulkan::invalidate_interfaces();
+
+        return;
     }
 
     panic!("retro_hw_context_destroyed_callback: Core has not been initialized yet!");
@@ -823,9 +1242,16 @@ pub unsafe extern "C" fn retro_hw_context_destroyed_callback() {
 #[no_mangle]
 pub unsafe extern "C" fn retro_set_eject_state_callback(ejected: bool) -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_set_eject_state_callback(ejected = {ejected})");
+    trace_api!(
+        Callback,
+        "retro_set_eject_state_callback(ejected = {ejected})"
+    );
+
+    if let Some(wrapper) = instance() {
+        if let Some(disk_control) = wrapper.core.disk_control() {
+            return disk_control.set_eject_state(ejected);
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
         return wrapper.core.on_set_eject_state(ejected);
     }
 
@@ -836,9 +1262,13 @@ pub unsafe extern "C" fn retro_set_eject_state_callback(ejected: bool) -> bool {
 #[no_mangle]
 pub unsafe extern "C" fn retro_get_eject_state_callback() -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_get_eject_state_callback()");
+    trace_api!(Callback, "retro_get_eject_state_callback()");
+
+    if let Some(wrapper) = instance() {
+        if let Some(disk_control) = wrapper.core.disk_control() {
+            return disk_control.is_ejected();
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
         return wrapper.core.on_get_eject_state();
     }
 
@@ -849,9 +1279,13 @@ pub unsafe extern "C" fn retro_get_eject_state_callback() -> bool {
 #[no_mangle]
 pub unsafe extern "C" fn retro_get_image_index_callback() -> ::std::os::raw::c_uint {
     #[cfg(feature = "log")]
-    log::trace!("retro_get_image_index_callback()");
+    trace_api!(Callback, "retro_get_image_index_callback()");
+
+    if let Some(wrapper) = instance() {
+        if let Some(disk_control) = wrapper.core.disk_control() {
+            return disk_control.image_index();
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
         return wrapper.core.on_get_image_index();
     }
 
@@ -862,9 +1296,13 @@ pub unsafe extern "C" fn retro_get_image_index_callback() -> ::std::os::raw::c_u
 #[no_mangle]
 pub unsafe extern "C" fn retro_set_image_index_callback(index: ::std::os::raw::c_uint) -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_set_image_index_callback()");
+    trace_api!(Callback, "retro_set_image_index_callback()");
+
+    if let Some(wrapper) = instance() {
+        if let Some(disk_control) = wrapper.core.disk_control() {
+            return disk_control.set_image_index(index);
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
         return wrapper.core.on_set_image_index(index);
     }
 
@@ -875,9 +1313,13 @@ pub unsafe extern "C" fn retro_set_image_index_callback(index: ::std::os::raw::c
 #[no_mangle]
 pub unsafe extern "C" fn retro_get_num_images_callback() -> ::std::os::raw::c_uint {
     #[cfg(feature = "log")]
-    log::trace!("retro_get_num_images_callback()");
+    trace_api!(Callback, "retro_get_num_images_callback()");
+
+    if let Some(wrapper) = instance() {
+        if let Some(disk_control) = wrapper.core.disk_control() {
+            return disk_control.num_images();
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
         return wrapper.core.on_get_num_images();
     }
 
@@ -891,9 +1333,16 @@ pub unsafe extern "C" fn retro_replace_image_index_callback(
     info: *const retro_game_info,
 ) -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_replace_image_index_callback(index = {index}, info = {info:#?})");
+    trace_api!(
+        Callback,
+        "retro_replace_image_index_callback(index = {index}, info = {info:#?})"
+    );
+
+    if let Some(wrapper) = instance() {
+        if let Some(disk_control) = wrapper.core.disk_control() {
+            return disk_control.replace_image_index(index, info);
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
         return wrapper.core.on_replace_image_index(index, info);
     }
 
@@ -904,9 +1353,13 @@ pub unsafe extern "C" fn retro_replace_image_index_callback(
 #[no_mangle]
 pub unsafe extern "C" fn retro_add_image_index_callback() -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_add_image_index_callback()");
+    trace_api!(Callback, "retro_add_image_index_callback()");
+
+    if let Some(wrapper) = instance() {
+        if let Some(disk_control) = wrapper.core.disk_control() {
+            return disk_control.add_image_index();
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
         return wrapper.core.on_add_image_index();
     }
 
@@ -920,9 +1373,16 @@ pub unsafe extern "C" fn retro_set_initial_image_callback(
     path: *const ::std::os::raw::c_char,
 ) -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_set_initial_image_callback(index = {index}, path = {path:#?})");
+    trace_api!(
+        Callback,
+        "retro_set_initial_image_callback(index = {index}, path = {path:#?})"
+    );
+
+    if let Some(wrapper) = instance() {
+        if let Some(disk_control) = wrapper.core.disk_control() {
+            return disk_control.set_initial_image(index, CStr::from_ptr(path));
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
         return wrapper
             .core
             .on_set_initial_image(index, CStr::from_ptr(path));
@@ -939,10 +1399,18 @@ pub unsafe extern "C" fn retro_get_image_path_callback(
     len: size_t,
 ) -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_get_image_path_callback(index = {index}, path = {path:#?}, len = {len})");
+    trace_api!(
+        Callback,
+        "retro_get_image_path_callback(index = {index}, path = {path:#?}, len = {len})"
+    );
+
+    if let Some(wrapper) = instance() {
+        let image_path = match wrapper.core.disk_control() {
+            Some(disk_control) => disk_control.image_path(index),
+            None => wrapper.core.on_get_image_path(index),
+        };
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        match wrapper.core.on_get_image_path(index) {
+        match image_path {
             Some(image_path) => {
                 let image_path = image_path.as_bytes();
                 let buf = std::slice::from_raw_parts_mut(path as *mut u8, len as usize);
@@ -966,10 +1434,18 @@ pub unsafe extern "C" fn retro_get_image_label_callback(
     len: size_t,
 ) -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_get_image_label_callback(index = {index}, label = {label:#?}, len = {len})");
+    trace_api!(
+        Callback,
+        "retro_get_image_label_callback(index = {index}, label = {label:#?}, len = {len})"
+    );
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        match wrapper.core.on_get_image_label(index) {
+    if let Some(wrapper) = instance() {
+        let image_label = match wrapper.core.disk_control() {
+            Some(disk_control) => disk_control.image_label(index),
+            None => wrapper.core.on_get_image_label(index),
+        };
+
+        match image_label {
             Some(image_label) => {
                 let image_label = image_label.as_bytes();
                 let buf = std::slice::from_raw_parts_mut(label as *mut u8, len as usize);
@@ -989,9 +1465,9 @@ pub unsafe extern "C" fn retro_get_image_label_callback(
 #[no_mangle]
 pub unsafe extern "C" fn retro_frame_time_callback_fn(usec: retro_usec_t) {
     #[cfg(feature = "log")]
-    log::trace!("retro_frame_time_callback_fn(usec = {usec})");
+    trace_api!(Frame, "retro_frame_time_callback_fn(usec = {usec})");
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
+    if let Some(wrapper) = instance() {
         wrapper.frame_delta = Some(usec);
         return;
     }
@@ -999,29 +1475,40 @@ pub unsafe extern "C" fn retro_frame_time_callback_fn(usec: retro_usec_t) {
     panic!("retro_frame_time_callback_fn: Core has not been initialized yet!");
 }
 
-/// Notifies the [`Core`] when audio data should be written.
+/// Notifies the [`AudioState`](audio_state::AudioState), or the [`Core`] if there is none,
+/// when audio data should be written.
 #[no_mangle]
 pub unsafe extern "C" fn retro_audio_callback_fn() {
-    // This is just too noisy, even for trace logging
-    // #[cfg(feature = "log")]
-    // log::trace!("retro_audio_callback_fn()");
+    #[cfg(feature = "log")]
+    trace_api!(Frame, "retro_audio_callback_fn()");
+
+    // Doesn’t touch the wrapper, which `retro_run` might be using on another thread
+    if audio_state::AudioThread::lock().write_audio() {
+        return;
+    }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
+    if let Some(wrapper) = instance() {
         let mut ctx = AudioContext {
             environment_callback: &wrapper.environment_callback,
-            interfaces: Arc::clone(&wrapper.interfaces),
+            interfaces: &wrapper.interfaces,
 
             audio_sample_callback: &wrapper.audio_sample_callback,
             audio_sample_batch_callback: &wrapper.audio_sample_batch_callback,
+
+            in_audio_callback: true,
         };
 
+        ctx.flush_queued_audio();
+
+        #[allow(deprecated)]
         return wrapper.core.on_write_audio(&mut ctx);
     }
 
     panic!("retro_audio_callback_fn: Core has not been initialized yet!");
 }
 
-/// Notifies the [`Core`] about the state of the frontend’s audio system.
+/// Notifies the [`AudioState`](audio_state::AudioState), or the [`Core`] if there is none,
+/// about the state of the frontend’s audio system.
 ///
 /// [`true`]: Audio driver in frontend is active, and callback is
 /// expected to be called regularily.
@@ -1035,9 +1522,22 @@ pub unsafe extern "C" fn retro_audio_callback_fn() {
 #[no_mangle]
 pub unsafe extern "C" fn retro_audio_set_state_callback_fn(enabled: bool) {
     #[cfg(feature = "log")]
-    log::trace!("retro_audio_set_state_callback_fn(enabled = {enabled})");
+    trace_api!(
+        Callback,
+        "retro_audio_set_state_callback_fn(enabled = {enabled})"
+    );
+
+    if audio_state::AudioThread::lock().set_state(enabled) {
+        return;
+    }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
+    if let Some(wrapper) = instance() {
+        if !enabled {
+            // Nobody is going to pick up audio queued while the frontend is paused
+            wrapper.interfaces.write().unwrap().queued_audio.clear();
+        }
+
+        #[allow(deprecated)]
         return wrapper.core.on_audio_set_state(enabled);
     }
 
@@ -1056,9 +1556,9 @@ pub unsafe extern "C" fn retro_camera_frame_raw_framebuffer_callback(
     let buffer = std::slice::from_raw_parts(buffer, buffer_size);
 
     #[cfg(feature = "log")]
-    log::trace!("retro_camera_frame_raw_framebuffer_callback(buffer = &[u32; {}], width = {width}, height = {height}, pitch = {pitch})", buffer.len());
+    trace_api!(Frame, "retro_camera_frame_raw_framebuffer_callback(buffer = &[u32; {}], width = {width}, height = {height}, pitch = {pitch})", buffer.len());
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
+    if let Some(wrapper) = instance() {
         return wrapper
             .core
             .on_camera_raw_framebuffer(buffer, width, height, pitch as usize);
@@ -1075,9 +1575,9 @@ pub unsafe extern "C" fn retro_camera_frame_opengl_texture_callback(
     affine: *const f32,
 ) {
     #[cfg(feature = "log")]
-    log::trace!("retro_camera_frame_opengl_texture_callback(texture_id = {texture_id}, texture_target = {texture_target}, affine = {:#?})", std::slice::from_raw_parts(affine, 3 * 3));
+    trace_api!(Frame, "retro_camera_frame_opengl_texture_callback(texture_id = {texture_id}, texture_target = {texture_target}, affine = {:#?})", std::slice::from_raw_parts(affine, 3 * 3));
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
+    if let Some(wrapper) = instance() {
         // Packed 3x3 column-major matrix
         let matrix = std::slice::from_raw_parts(affine, 3 * 3);
         // Convert to fixed size array; we know it contains 9 elements
@@ -1095,13 +1595,12 @@ pub unsafe extern "C" fn retro_camera_frame_opengl_texture_callback(
 #[no_mangle]
 pub unsafe extern "C" fn retro_camera_initialized_callback() {
     #[cfg(feature = "log")]
-    log::trace!("retro_camera_initialized_callback()");
+    trace_api!(Callback, "retro_camera_initialized_callback()");
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = GenericContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+    if let Some(wrapper) = instance() {
+        wrapper.interfaces.write().unwrap().camera_state = CameraState::Initialized;
+
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         return wrapper.core.on_camera_initialized(&mut ctx);
     }
@@ -1113,13 +1612,12 @@ pub unsafe extern "C" fn retro_camera_initialized_callback() {
 #[no_mangle]
 pub unsafe extern "C" fn retro_camera_deinitialized_callback() {
     #[cfg(feature = "log")]
-    log::trace!("retro_camera_deinitialized_callback()");
+    trace_api!(Callback, "retro_camera_deinitialized_callback()");
+
+    if let Some(wrapper) = instance() {
+        wrapper.interfaces.write().unwrap().camera_state = CameraState::Deinitialized;
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = GenericContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         return wrapper.core.on_camera_deinitialized(&mut ctx);
     }
@@ -1131,13 +1629,13 @@ pub unsafe extern "C" fn retro_camera_deinitialized_callback() {
 #[no_mangle]
 pub unsafe extern "C" fn retro_location_lifetime_status_initialized_callback() {
     #[cfg(feature = "log")]
-    log::trace!("retro_location_lifetime_status_initialized_callback()");
+    trace_api!(
+        Callback,
+        "retro_location_lifetime_status_initialized_callback()"
+    );
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = GenericContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+    if let Some(wrapper) = instance() {
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         return wrapper
             .core
@@ -1153,13 +1651,13 @@ pub unsafe extern "C" fn retro_location_lifetime_status_initialized_callback() {
 #[no_mangle]
 pub unsafe extern "C" fn retro_location_lifetime_status_deinitialized_callback() {
     #[cfg(feature = "log")]
-    log::trace!("retro_location_lifetime_status_deinitialized_callback()");
+    trace_api!(
+        Callback,
+        "retro_location_lifetime_status_deinitialized_callback()"
+    );
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        let mut ctx = GenericContext::new(
-            &wrapper.environment_callback,
-            Arc::clone(&wrapper.interfaces),
-        );
+    if let Some(wrapper) = instance() {
+        let mut ctx = GenericContext::new(&wrapper.environment_callback, &wrapper.interfaces);
 
         return wrapper
             .core
@@ -1177,10 +1675,16 @@ pub unsafe extern "C" fn retro_get_proc_address_callback(
     sym: *const ::std::os::raw::c_char,
 ) -> retro_proc_address_t {
     #[cfg(feature = "log")]
-    log::trace!("retro_get_proc_address_callback({sym:#?})");
+    trace_api!(Callback, "retro_get_proc_address_callback({sym:#?})");
+
+    if let Some(wrapper) = instance() {
+        let sym = CStr::from_ptr(sym);
+
+        if let Some(function) = wrapper.interfaces.read().unwrap().proc_addresses.get(sym) {
+            return *function;
+        }
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
-        return wrapper.core.on_get_proc_address(CStr::from_ptr(sym));
+        return wrapper.core.on_get_proc_address(sym);
     }
 
     panic!("retro_get_proc_address_callback: Core has not been initialized yet!");
@@ -1194,9 +1698,25 @@ pub unsafe extern "C" fn retro_audio_buffer_status_callback_fn(
     underrun_likely: bool,
 ) {
     #[cfg(feature = "log")]
-    log::trace!("retro_audio_buffer_status_callback_fn(active = {active}, occupancy = {occupancy}, underrun_likely = {underrun_likely})");
+    trace_api!(Frame, "retro_audio_buffer_status_callback_fn(active = {active}, occupancy = {occupancy}, underrun_likely = {underrun_likely})");
+
+    #[cfg(feature = "metrics")]
+    if underrun_likely {
+        metrics::record_audio_underrun();
+    }
+
+    if let Some(wrapper) = instance() {
+        wrapper
+            .interfaces
+            .write()
+            .unwrap()
+            .audio_pacer
+            .set_buffer_status(audio_pacing::AudioBufferStatus {
+                active,
+                occupancy,
+                underrun_likely,
+            });
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
         return wrapper
             .core
             .on_audio_buffer_status(active, occupancy, underrun_likely);
@@ -1209,9 +1729,9 @@ pub unsafe extern "C" fn retro_audio_buffer_status_callback_fn(
 #[no_mangle]
 pub unsafe extern "C" fn retro_core_options_update_display_callback_fn() -> bool {
     #[cfg(feature = "log")]
-    log::trace!("retro_core_options_update_display_callback_fn()");
+    trace_api!(Callback, "retro_core_options_update_display_callback_fn()");
 
-    if let Some(wrapper) = RETRO_INSTANCE.as_mut() {
+    if let Some(wrapper) = instance() {
         return wrapper.core.on_core_options_update_display();
     }
 
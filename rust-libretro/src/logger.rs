@@ -1,15 +1,56 @@
 //! [`log::Log`] implementation using the libretro logging interface.
 use super::*;
 use log::{Level, Metadata, Record};
-use std::io::Write;
+use std::{
+    collections::VecDeque,
+    io::Write,
+    sync::{Mutex, RwLock},
+};
+
+/// The number of records kept until the logging interface becomes available.
+const MAX_PENDING_RECORDS: usize = 256;
+
+/// A record waiting for the logging interface.
+struct PendingRecord {
+    level: Level,
+    target: String,
+    message: String,
+}
 
 pub struct RetroLogger {
-    callback: retro_log_callback,
+    callback: RwLock<retro_log_callback>,
+
+    /// Records logged before the frontend provided its logging interface.
+    /// [`None`] once the records have been replayed or written to stderr.
+    pending: Mutex<Option<VecDeque<PendingRecord>>>,
 }
 
 impl RetroLogger {
-    pub fn new(callback: retro_log_callback) -> Self {
-        Self { callback }
+    pub fn new() -> Self {
+        Self {
+            callback: RwLock::new(retro_log_callback { log: None }),
+            pending: Mutex::new(Some(VecDeque::new())),
+        }
+    }
+
+    /// Sets the logging interface and replays the records logged so far.
+    pub fn set_callback(&self, callback: retro_log_callback) {
+        *self.callback.write().unwrap() = callback;
+        self.stop_buffering();
+    }
+
+    pub fn has_callback(&self) -> bool {
+        self.callback.read().unwrap().log.is_some()
+    }
+
+    /// Stops waiting for the logging interface and writes the records logged so far
+    /// to the logging interface if available, or to stderr otherwise.
+    pub fn stop_buffering(&self) {
+        if let Some(pending) = self.pending.lock().unwrap().take() {
+            for record in pending {
+                self.write(record.level, &record.target, &record.message);
+            }
+        }
     }
 
     fn get_retro_log_level(level: Level) -> retro_log_level {
@@ -21,29 +62,13 @@ impl RetroLogger {
             Level::Trace => retro_log_level::RETRO_LOG_DEBUG,
         }
     }
-}
-
-impl log::Log for RetroLogger {
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= log::max_level()
-    }
-
-    fn log(&self, record: &Record) {
-        if !self.enabled(record.metadata()) {
-            return;
-        }
 
-        let target = if !record.target().is_empty() {
-            record.target()
-        } else {
-            record.module_path().unwrap_or_default()
-        };
-
-        if let Some(cb) = self.callback.log {
+    fn write(&self, level: Level, target: &str, message: &str) {
+        if let Some(cb) = self.callback.read().unwrap().log {
             let mut args: Vec<u8> = Vec::new();
 
-            if writeln!(args, "{}\0", record.args()).is_ok() {
-                let level = Self::get_retro_log_level(record.level());
+            if writeln!(args, "{}\0", message).is_ok() {
+                let level = Self::get_retro_log_level(level);
                 let target = CString::new(target).unwrap();
 
                 unsafe {
@@ -59,7 +84,7 @@ impl log::Log for RetroLogger {
                 }
             }
         } else {
-            let level = match record.level() {
+            let level = match level {
                 Level::Debug => "DEBUG",
                 Level::Info => "INFO",
                 Level::Warn => "WARN",
@@ -70,14 +95,44 @@ impl log::Log for RetroLogger {
             let stderr = std::io::stderr();
             let mut stderr_lock = stderr.lock();
 
-            let _ = writeln!(
-                stderr_lock,
-                "[libretro {}] [{}] {}",
-                level,
-                target,
-                record.args()
-            );
+            let _ = writeln!(stderr_lock, "[libretro {}] [{}] {}", level, target, message);
+        }
+    }
+}
+
+impl log::Log for RetroLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
         }
+
+        let target = if !record.target().is_empty() {
+            record.target()
+        } else {
+            record.module_path().unwrap_or_default()
+        };
+
+        if !self.has_callback() {
+            if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+                if pending.len() == MAX_PENDING_RECORDS {
+                    pending.pop_front();
+                }
+
+                pending.push_back(PendingRecord {
+                    level: record.level(),
+                    target: target.to_owned(),
+                    message: record.args().to_string(),
+                });
+
+                return;
+            }
+        }
+
+        self.write(record.level(), target, &record.args().to_string());
     }
 
     fn flush(&self) {
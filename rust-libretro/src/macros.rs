@@ -21,3 +21,22 @@ macro_rules! input_descriptors {
         $crate::input_descriptor!(0, 0, 0, 0, "")
     ] }
 }
+
+/// Creates a [`CoreInfo`](crate::core_info::CoreInfo) from a [`SystemInfo`](crate::types::SystemInfo)
+/// and the authors, license and description in the `Cargo.toml` of the crate using the macro.
+#[macro_export]
+macro_rules! core_info {
+    ( $system_info:expr $(,)? ) => {{
+        let mut info = $crate::core_info::CoreInfo::new(&$system_info);
+
+        info.authors = env!("CARGO_PKG_AUTHORS")
+            .split(':')
+            .filter(|author| !author.is_empty())
+            .map(str::to_owned)
+            .collect();
+        info.license = env!("CARGO_PKG_LICENSE").to_owned();
+        info.description = env!("CARGO_PKG_DESCRIPTION").to_owned();
+
+        info
+    }};
+}
@@ -0,0 +1,244 @@
+//! Statistics about the health of a core, exported for external tools.
+//!
+//! Requires the `metrics` feature, without it nothing gets counted.
+//!
+//! The wrapper counts frames, the time spent in `retro_run`, likely audio underruns
+//! reported through [`GenericContext::enable_audio_buffer_status_callback`] and the
+//! environment calls made by the core and the wrapper.
+//!
+//! Besides reading them with [`snapshot`], frontends and tools can look up the
+//! [`METRICS_SYMBOL`] through the `get_proc_address` interface, which the wrapper
+//! enables by itself. It resolves to a function of type
+//! `const char *(*)(void)` that returns the metrics as a JSON object:
+//!
+//! ```json
+//! {
+//!   "frames": 3600,
+//!   "frame_time_us": { "last": 812, "average": 790, "max": 4211 },
+//!   "audio_underruns": 0,
+//!   "environment_calls": { "RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE": 3600 },
+//!   "other_environment_calls": 0
+//! }
+//! ```
+//!
+//! The returned string stays valid until the function gets called again.
+//! Cores that don’t want to export it can remove it with [`GenericContext::unregister_proc`].
+//!
+//! [`GenericContext::enable_audio_buffer_status_callback`]: crate::contexts::GenericContext::enable_audio_buffer_status_callback
+//! [`GenericContext::unregister_proc`]: crate::contexts::GenericContext::unregister_proc
+use crate::{environment::command_name, sys::retro_proc_address_t};
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    os::raw::c_char,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// The symbol [`metrics_json`] gets exported as.
+pub const METRICS_SYMBOL: &str = "rust_libretro_metrics_json";
+
+/// Environment commands are counted by their number without the experimental flag,
+/// private ones and those above the last slot in [`OTHER_ENVIRONMENT_CALLS`].
+const COMMAND_SLOTS: usize = 128;
+
+static FRAMES: AtomicU64 = AtomicU64::new(0);
+static FRAME_TIME_LAST: AtomicU64 = AtomicU64::new(0);
+static FRAME_TIME_TOTAL: AtomicU64 = AtomicU64::new(0);
+static FRAME_TIME_MAX: AtomicU64 = AtomicU64::new(0);
+static AUDIO_UNDERRUNS: AtomicU64 = AtomicU64::new(0);
+
+static ENVIRONMENT_CALLS: [AtomicU64; COMMAND_SLOTS] = [const { AtomicU64::new(0) }; COMMAND_SLOTS];
+
+/// The command last counted in each slot, including its flags.
+static ENVIRONMENT_COMMANDS: [AtomicU32; COMMAND_SLOTS] =
+    [const { AtomicU32::new(0) }; COMMAND_SLOTS];
+
+static OTHER_ENVIRONMENT_CALLS: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps the string returned by [`metrics_json`] alive.
+static JSON: Mutex<Option<CString>> = Mutex::new(None);
+
+/// The metrics collected since the core has been loaded by the frontend or [`reset`] got called.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    pub frames: u64,
+    pub frame_time_last: Duration,
+    pub frame_time_max: Duration,
+    pub frame_time_total: Duration,
+    pub audio_underruns: u64,
+
+    /// How often each `RETRO_ENVIRONMENT_*` command has been called, sorted by command.
+    pub environment_calls: Vec<(u32, u64)>,
+
+    /// The calls of private commands and of commands too new to have a slot.
+    pub other_environment_calls: u64,
+}
+
+impl Metrics {
+    pub fn frame_time_average(&self) -> Duration {
+        if self.frames == 0 {
+            return Duration::ZERO;
+        }
+
+        self.frame_time_total / self.frames.min(u32::MAX as u64) as u32
+    }
+
+    /// Formats the metrics as described in the [module documentation](self).
+    pub fn to_json(&self) -> String {
+        let calls = self
+            .environment_calls
+            .iter()
+            .map(|(command, count)| format!("\"{}\":{count}", json_command_name(*command)))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            "{{\"frames\":{},\"frame_time_us\":{{\"last\":{},\"average\":{},\"max\":{}}},\"audio_underruns\":{},\"environment_calls\":{{{calls}}},\"other_environment_calls\":{}}}",
+            self.frames,
+            self.frame_time_last.as_micros(),
+            self.frame_time_average().as_micros(),
+            self.frame_time_max.as_micros(),
+            self.audio_underruns,
+            self.other_environment_calls,
+        )
+    }
+}
+
+/// Unknown commands would all be called `RETRO_ENVIRONMENT_UNKNOWN`, which makes
+/// for duplicate keys, so they get their number appended.
+fn json_command_name(command: u32) -> String {
+    let name = command_name(command);
+
+    if name == "RETRO_ENVIRONMENT_UNKNOWN" {
+        format!("{name}_{command:#x}")
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Returns [`None`] for commands counted in [`OTHER_ENVIRONMENT_CALLS`].
+fn slot(command: u32) -> Option<usize> {
+    if command & crate::sys::RETRO_ENVIRONMENT_PRIVATE != 0 {
+        return None;
+    }
+
+    let slot = (command & !crate::sys::RETRO_ENVIRONMENT_EXPERIMENTAL) as usize;
+    (slot < COMMAND_SLOTS).then_some(slot)
+}
+
+pub(crate) fn record_environment_call(command: u32) {
+    let Some(slot) = slot(command) else {
+        OTHER_ENVIRONMENT_CALLS.fetch_add(1, Ordering::Relaxed);
+        return;
+    };
+
+    ENVIRONMENT_CALLS[slot].fetch_add(1, Ordering::Relaxed);
+    ENVIRONMENT_COMMANDS[slot].store(command, Ordering::Relaxed);
+}
+
+pub(crate) fn record_frame(elapsed: Duration) {
+    let nanos = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+
+    FRAMES.fetch_add(1, Ordering::Relaxed);
+    FRAME_TIME_LAST.store(nanos, Ordering::Relaxed);
+    FRAME_TIME_TOTAL.fetch_add(nanos, Ordering::Relaxed);
+    FRAME_TIME_MAX.fetch_max(nanos, Ordering::Relaxed);
+}
+
+pub(crate) fn record_audio_underrun() {
+    AUDIO_UNDERRUNS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the current metrics.
+pub fn snapshot() -> Metrics {
+    let environment_calls = ENVIRONMENT_CALLS
+        .iter()
+        .zip(&ENVIRONMENT_COMMANDS)
+        .filter_map(|(count, command)| {
+            let count = count.load(Ordering::Relaxed);
+            (count > 0).then(|| (command.load(Ordering::Relaxed), count))
+        })
+        .collect();
+
+    Metrics {
+        frames: FRAMES.load(Ordering::Relaxed),
+        frame_time_last: Duration::from_nanos(FRAME_TIME_LAST.load(Ordering::Relaxed)),
+        frame_time_max: Duration::from_nanos(FRAME_TIME_MAX.load(Ordering::Relaxed)),
+        frame_time_total: Duration::from_nanos(FRAME_TIME_TOTAL.load(Ordering::Relaxed)),
+        audio_underruns: AUDIO_UNDERRUNS.load(Ordering::Relaxed),
+        environment_calls,
+        other_environment_calls: OTHER_ENVIRONMENT_CALLS.load(Ordering::Relaxed),
+    }
+}
+
+/// Sets all metrics back to zero.
+pub fn reset() {
+    for counter in [
+        &FRAMES,
+        &FRAME_TIME_LAST,
+        &FRAME_TIME_TOTAL,
+        &FRAME_TIME_MAX,
+        &AUDIO_UNDERRUNS,
+        &OTHER_ENVIRONMENT_CALLS,
+    ]
+    .into_iter()
+    .chain(&ENVIRONMENT_CALLS)
+    {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Returns the [`snapshot`] as JSON, exported as [`METRICS_SYMBOL`].
+pub extern "C" fn metrics_json() -> *const c_char {
+    // The JSON never contains a nul byte
+    let json = CString::new(snapshot().to_json()).unwrap_or_default();
+
+    let mut stored = JSON.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    stored.insert(json).as_ptr()
+}
+
+/// The procs every core exports, see [`InterfaceList::proc_addresses`](crate::core_wrapper::InterfaceList::proc_addresses).
+pub(crate) fn proc_addresses() -> HashMap<CString, retro_proc_address_t> {
+    // The proc address interface only passes functions without a signature
+    let function = unsafe {
+        std::mem::transmute::<extern "C" fn() -> *const c_char, unsafe extern "C" fn()>(
+            metrics_json,
+        )
+    };
+
+    let symbol = CString::new(METRICS_SYMBOL).unwrap();
+    HashMap::from([(symbol, Some(function))])
+}
+
+#[test]
+fn metrics_json_format() {
+    use crate::sys::*;
+
+    let metrics = Metrics {
+        frames: 2,
+        frame_time_last: Duration::from_micros(900),
+        frame_time_max: Duration::from_micros(1100),
+        frame_time_total: Duration::from_micros(2000),
+        audio_underruns: 1,
+        environment_calls: vec![
+            (RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE, 2),
+            (RETRO_ENVIRONMENT_EXPERIMENTAL | 0x7e, 1),
+        ],
+        other_environment_calls: 3,
+    };
+
+    assert_eq!(
+        metrics.to_json(),
+        "{\"frames\":2,\"frame_time_us\":{\"last\":900,\"average\":1000,\"max\":1100},\
+         \"audio_underruns\":1,\"environment_calls\":{\"RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE\":2,\
+         \"RETRO_ENVIRONMENT_UNKNOWN_0x1007e\":1},\"other_environment_calls\":3}"
+    );
+    assert_eq!(slot(RETRO_ENVIRONMENT_GET_VFS_INTERFACE), Some(45));
+    assert_eq!(slot(RETRO_ENVIRONMENT_EXPERIMENTAL | 127), Some(127));
+    assert_eq!(slot(128), None);
+    assert_eq!(slot(RETRO_ENVIRONMENT_PRIVATE | 3), None);
+}
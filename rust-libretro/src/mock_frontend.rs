@@ -0,0 +1,228 @@
+//! A minimal frontend for exercising the environment wrappers in tests.
+//!
+//! [`environment`] handles the commands a test enabled with [`MockFrontend::support`]
+//! and rejects all others, just like a frontend that doesn't know them.
+//! The state is thread local, so tests running in parallel don't see each other's calls.
+//!
+//! [`StubCore`] is the matching core for tests that only need some [`Core`](core::Core).
+use crate::{
+    core::{Core, CoreOptions},
+    proc::c_str,
+    *,
+};
+use std::{cell::RefCell, collections::HashSet, ffi::CString};
+
+#[derive(Debug, Default)]
+pub(crate) struct MockFrontend {
+    supported: HashSet<u32>,
+
+    /// The last value passed with `RETRO_ENVIRONMENT_SET_SUPPORT_ACHIEVEMENTS`.
+    pub support_achievements: Option<bool>,
+
+    /// Returned for `RETRO_ENVIRONMENT_GET_CORE_ASSETS_DIRECTORY`, `None` returns `NULL`.
+    pub core_assets_directory: Option<CString>,
+
+    /// Returned for `RETRO_ENVIRONMENT_GET_JIT_CAPABLE`.
+    pub jit_capable: bool,
+
+    /// Returned for `RETRO_ENVIRONMENT_GET_AUDIO_VIDEO_ENABLE`.
+    pub audio_video_enable: u32,
+}
+
+thread_local! {
+    static FRONTEND: RefCell<MockFrontend> = RefCell::new(MockFrontend::default());
+}
+
+impl MockFrontend {
+    /// Resets the frontend of the current thread and passes it to `f` for setting it up.
+    pub fn install(f: impl FnOnce(&mut MockFrontend)) {
+        FRONTEND.with(|frontend| {
+            let mut frontend = frontend.borrow_mut();
+            *frontend = MockFrontend::default();
+            f(&mut frontend);
+        });
+    }
+
+    pub fn with<R>(f: impl FnOnce(&MockFrontend) -> R) -> R {
+        FRONTEND.with(|frontend| f(&frontend.borrow()))
+    }
+
+    pub fn support(&mut self, command: u32) -> &mut Self {
+        self.supported.insert(command);
+        self
+    }
+}
+
+/// A core that does nothing but describe itself, test cores forward
+/// [`Core::get_info`] and [`Core::on_get_av_info`] to it.
+#[derive(Debug, Default)]
+pub(crate) struct StubCore;
+
+impl CoreOptions for StubCore {}
+
+impl Core for StubCore {
+    fn get_info(&self) -> SystemInfo {
+        SystemInfo {
+            library_name: c_str!("Stub Core").into(),
+            library_version: c_str!("0.1.0").into(),
+            valid_extensions: c_str!("").into(),
+
+            need_fullpath: false,
+            block_extract: false,
+        }
+    }
+
+    fn supports_no_game(&self) -> bool {
+        true
+    }
+
+    fn on_get_av_info(&mut self, _ctx: &mut GetAvInfoContext) -> retro_system_av_info {
+        retro_system_av_info {
+            geometry: retro_game_geometry {
+                base_width: 320,
+                base_height: 240,
+                max_width: 320,
+                max_height: 240,
+                aspect_ratio: 0.0,
+            },
+            timing: retro_system_timing {
+                fps: 60.0,
+                sample_rate: 48000.0,
+            },
+        }
+    }
+}
+
+/// The environment callback of the [`MockFrontend`] of the current thread.
+pub(crate) unsafe extern "C" fn environment(cmd: u32, data: *mut c_void) -> bool {
+    FRONTEND.with(|frontend| {
+        let mut frontend = frontend.borrow_mut();
+
+        if !frontend.supported.contains(&cmd) {
+            return false;
+        }
+
+        match cmd {
+            RETRO_ENVIRONMENT_SET_SUPPORT_ACHIEVEMENTS => {
+                frontend.support_achievements = Some(*(data as *const bool));
+                true
+            }
+            RETRO_ENVIRONMENT_GET_CORE_ASSETS_DIRECTORY => {
+                *(data as *mut *const c_char) = frontend
+                    .core_assets_directory
+                    .as_ref()
+                    .map_or(std::ptr::null(), |path| path.as_ptr());
+                true
+            }
+            RETRO_ENVIRONMENT_GET_JIT_CAPABLE => {
+                *(data as *mut bool) = frontend.jit_capable;
+                true
+            }
+            RETRO_ENVIRONMENT_GET_AUDIO_VIDEO_ENABLE => {
+                *(data as *mut u32) = frontend.audio_video_enable;
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+#[test]
+fn support_achievements() {
+    use environment::EnvironmentCallError;
+
+    let interfaces = core_wrapper::Interfaces::default();
+    let environment_callback: retro_environment_t = Some(environment);
+    let ctx = contexts::InitContext::new(&environment_callback, &interfaces);
+    let set = |value| unsafe { ctx.set_support_achievements(value) };
+
+    MockFrontend::install(|_| ());
+    assert_eq!(
+        set(true),
+        Err(EnvironmentCallError::Unsupported {
+            command: RETRO_ENVIRONMENT_SET_SUPPORT_ACHIEVEMENTS
+        })
+    );
+    assert_eq!(
+        MockFrontend::with(|frontend| frontend.support_achievements),
+        None
+    );
+
+    MockFrontend::install(|frontend| {
+        frontend.support(RETRO_ENVIRONMENT_SET_SUPPORT_ACHIEVEMENTS);
+    });
+    assert_eq!(set(true), Ok(()));
+    assert_eq!(
+        MockFrontend::with(|frontend| frontend.support_achievements),
+        Some(true)
+    );
+    assert_eq!(set(false), Ok(()));
+    assert_eq!(
+        MockFrontend::with(|frontend| frontend.support_achievements),
+        Some(false)
+    );
+
+    let no_callback: retro_environment_t = None;
+    let ctx = contexts::InitContext::new(&no_callback, &interfaces);
+    assert_eq!(
+        unsafe { ctx.set_support_achievements(true) },
+        Err(EnvironmentCallError::NoCallback {
+            command: RETRO_ENVIRONMENT_SET_SUPPORT_ACHIEVEMENTS
+        })
+    );
+}
+
+#[test]
+fn core_assets_directory() {
+    use environment::EnvironmentCallError;
+    const COMMAND: u32 = RETRO_ENVIRONMENT_GET_CORE_ASSETS_DIRECTORY;
+
+    let interfaces = core_wrapper::Interfaces::default();
+    let environment_callback: retro_environment_t = Some(environment);
+    let ctx = contexts::GenericContext::new(&environment_callback, &interfaces);
+
+    MockFrontend::install(|_| ());
+    assert_eq!(
+        ctx.get_core_assets_directory(),
+        Err(EnvironmentCallError::Unsupported { command: COMMAND })
+    );
+
+    // Handling the command without a directory leaves it up to the core
+    MockFrontend::install(|frontend| {
+        frontend.support(COMMAND);
+    });
+    assert_eq!(
+        ctx.get_core_assets_directory(),
+        Err(EnvironmentCallError::Unavailable { command: COMMAND })
+    );
+
+    MockFrontend::install(|frontend| {
+        frontend.support(COMMAND).core_assets_directory =
+            Some(CString::new("/frontend/assets").unwrap());
+    });
+    assert_eq!(
+        ctx.get_core_assets_directory(),
+        Ok(Path::new("/frontend/assets"))
+    );
+}
+
+#[test]
+fn jit_capable() {
+    let interfaces = core_wrapper::Interfaces::default();
+    let environment_callback: retro_environment_t = Some(environment);
+    let ctx = contexts::GenericContext::new(&environment_callback, &interfaces);
+
+    // The command number libretro.h defines, without the experimental flag
+    assert_eq!(RETRO_ENVIRONMENT_GET_JIT_CAPABLE, 74);
+
+    MockFrontend::install(|frontend| {
+        frontend.support(74).jit_capable = false;
+    });
+    assert!(!ctx.is_jit_capable());
+
+    MockFrontend::install(|_| ());
+    assert_eq!(
+        ctx.is_jit_capable(),
+        !cfg!(any(target_os = "ios", target_os = "tvos"))
+    );
+}
@@ -0,0 +1,357 @@
+//! Reacting to changes of individual core options.
+//!
+//! Instead of re-reading every option in [`Core::on_options_changed`], a core can register
+//! a handler per option with [`GenericContext::on_option_change`]. The handler gets called
+//! with the current value the first time options get checked after its registration,
+//! and afterwards only when the frontend reports a different value.
+//!
+//! The values of an option can be replaced at runtime with
+//! [`GenericContext::set_option_values`], e.g. with a list of detected resolutions.
+//!
+//! [`Core::on_options_changed`]: crate::core::Core::on_options_changed
+//! [`GenericContext::on_option_change`]: crate::contexts::GenericContext::on_option_change
+//! [`GenericContext::set_option_values`]: crate::contexts::GenericContext::set_option_values
+use crate::{sys::*, types::CoreOptionDefinitions};
+use std::{any::Any, collections::HashMap, ffi::CString, os::raw::c_char};
+
+type Callback = Box<dyn FnMut(Option<&str>, &mut dyn Any)>;
+
+struct Handler {
+    key: String,
+    /// The value passed to the last call, [`None`] if the handler has not been called yet.
+    value: Option<Option<String>>,
+    callback: Callback,
+}
+
+/// The handlers registered with [`GenericContext::on_option_change`].
+///
+/// [`GenericContext::on_option_change`]: crate::contexts::GenericContext::on_option_change
+#[derive(Default)]
+pub struct OptionHandlers {
+    handlers: Vec<Handler>,
+}
+
+impl std::fmt::Debug for OptionHandlers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.handlers.iter().map(|handler| &handler.key))
+            .finish()
+    }
+}
+
+impl OptionHandlers {
+    pub(crate) fn register<C: 'static>(
+        &mut self,
+        key: &str,
+        mut handler: impl FnMut(Option<&str>, &mut C) + 'static,
+    ) {
+        let callback = move |value: Option<&str>, core: &mut dyn Any| {
+            if let Some(core) = core.downcast_mut::<C>() {
+                handler(value, core);
+            } else {
+                #[cfg(feature = "log")]
+                log::warn!(
+                    "Option handler expects a core of type {}",
+                    std::any::type_name::<C>()
+                );
+            }
+        };
+
+        self.handlers.push(Handler {
+            key: key.to_owned(),
+            value: None,
+            callback: Box::new(callback),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// Whether a handler has not been called since its registration.
+    pub(crate) fn has_pending(&self) -> bool {
+        self.handlers.iter().any(|handler| handler.value.is_none())
+    }
+
+    /// Calls every handler whose option changed since its last call.
+    ///
+    /// `get` returns the current value of an option.
+    pub(crate) fn dispatch(
+        &mut self,
+        core: &mut dyn Any,
+        mut get: impl FnMut(&str) -> Option<String>,
+    ) {
+        for handler in self.handlers.iter_mut() {
+            let value = get(&handler.key);
+
+            if handler.value.as_ref() == Some(&value) {
+                continue;
+            }
+
+            (handler.callback)(value.as_deref(), core);
+            handler.value = Some(value);
+        }
+    }
+}
+
+/// The values replacing the declared ones of an option, pairs of value and label.
+pub(crate) type OptionValues = Vec<(String, Option<String>)>;
+
+/// The declared core options with some of their values replaced,
+/// in the layouts passed to the frontend.
+#[derive(Debug, Default)]
+pub struct RuntimeOptions {
+    /// Owns the strings the definitions point to.
+    strings: Vec<CString>,
+
+    categories: Vec<retro_core_option_v2_category>,
+    definitions_v2: Vec<retro_core_option_v2_definition>,
+    definitions: Vec<retro_core_option_definition>,
+    variables: Vec<retro_variable>,
+}
+
+impl RuntimeOptions {
+    pub(crate) fn new(
+        declared: &CoreOptionDefinitions,
+        replaced: &HashMap<String, OptionValues>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        const MAX_VALUES: usize = RETRO_NUM_CORE_OPTION_VALUES_MAX as usize - 1;
+        const NO_VALUE: retro_core_option_value = retro_core_option_value {
+            value: std::ptr::null(),
+            label: std::ptr::null(),
+        };
+
+        let mut options = Self::default();
+
+        // Moving a `CString` into the vector keeps its heap allocation, so the pointers stay valid
+        let mut string = |value: &str| -> Result<*const c_char, Box<dyn std::error::Error>> {
+            let value = CString::new(value)?;
+            let ptr = value.as_ptr();
+            options.strings.push(value);
+            Ok(ptr)
+        };
+
+        let mut categories = Vec::new();
+        for category in declared.categories {
+            categories.push(retro_core_option_v2_category {
+                key: string(category.key)?,
+                desc: string(category.desc)?,
+                info: string(category.info)?,
+            });
+        }
+
+        let mut definitions_v2 = Vec::new();
+        let mut definitions = Vec::new();
+        let mut variables = Vec::new();
+
+        for option in declared.definitions {
+            let declared_values = option
+                .values
+                .iter()
+                .map(|value| (value.value, value.label))
+                .collect::<Vec<_>>();
+            let values = match replaced.get(option.key) {
+                Some(values) => values
+                    .iter()
+                    .map(|(value, label)| (value.as_str(), label.as_deref()))
+                    .collect(),
+                None => declared_values,
+            };
+
+            if values.is_empty() || values.len() > MAX_VALUES {
+                return Err(format!(
+                    "Core option {} needs between 1 and {MAX_VALUES} values, got {}",
+                    option.key,
+                    values.len()
+                )
+                .into());
+            }
+
+            // Replaced values might not contain the declared default
+            let default_value = match option.default_value {
+                Some(default) if values.iter().any(|(value, _)| *value == default) => {
+                    string(default)?
+                }
+                Some(_) => string(values[0].0)?,
+                None => std::ptr::null(),
+            };
+
+            let mut value_definitions = [NO_VALUE; RETRO_NUM_CORE_OPTION_VALUES_MAX as usize];
+            for (definition, (value, label)) in value_definitions.iter_mut().zip(&values) {
+                *definition = retro_core_option_value {
+                    value: string(value)?,
+                    label: match label {
+                        Some(label) => string(label)?,
+                        None => std::ptr::null(),
+                    },
+                };
+            }
+
+            let key = string(option.key)?;
+            let desc = string(option.desc)?;
+            let info = string(option.info)?;
+
+            definitions_v2.push(retro_core_option_v2_definition {
+                key,
+                desc,
+                desc_categorized: string(option.desc_categorized.unwrap_or(""))?,
+                info,
+                info_categorized: string(option.info_categorized.unwrap_or(""))?,
+                category_key: string(option.category_key.unwrap_or(""))?,
+                values: value_definitions,
+                default_value,
+            });
+            definitions.push(retro_core_option_definition {
+                key,
+                desc,
+                info,
+                values: value_definitions,
+                default_value,
+            });
+
+            let list = values
+                .iter()
+                .map(|(value, _)| *value)
+                .collect::<Vec<_>>()
+                .join("|");
+            variables.push(retro_variable {
+                key,
+                value: string(&format!("{}; {list}", option.desc))?,
+            });
+        }
+
+        // List terminators
+        categories.push(retro_core_option_v2_category {
+            key: std::ptr::null(),
+            desc: std::ptr::null(),
+            info: std::ptr::null(),
+        });
+        definitions_v2.push(retro_core_option_v2_definition {
+            key: std::ptr::null(),
+            desc: std::ptr::null(),
+            desc_categorized: std::ptr::null(),
+            info: std::ptr::null(),
+            info_categorized: std::ptr::null(),
+            category_key: std::ptr::null(),
+            values: [NO_VALUE; RETRO_NUM_CORE_OPTION_VALUES_MAX as usize],
+            default_value: std::ptr::null(),
+        });
+        definitions.push(retro_core_option_definition {
+            key: std::ptr::null(),
+            desc: std::ptr::null(),
+            info: std::ptr::null(),
+            values: [NO_VALUE; RETRO_NUM_CORE_OPTION_VALUES_MAX as usize],
+            default_value: std::ptr::null(),
+        });
+        variables.push(retro_variable {
+            key: std::ptr::null(),
+            value: std::ptr::null(),
+        });
+
+        options.categories = categories;
+        options.definitions_v2 = definitions_v2;
+        options.definitions = definitions;
+        options.variables = variables;
+
+        Ok(options)
+    }
+
+    /// Points into `self`, which must not be dropped while the frontend uses the result.
+    pub(crate) fn options_v2(&self) -> retro_core_options_v2 {
+        retro_core_options_v2 {
+            categories: self.categories.as_ptr() as *mut _,
+            definitions: self.definitions_v2.as_ptr() as *mut _,
+        }
+    }
+
+    pub(crate) fn definitions(&self) -> &[retro_core_option_definition] {
+        &self.definitions
+    }
+
+    pub(crate) fn variables(&self) -> &[retro_variable] {
+        &self.variables
+    }
+}
+
+#[test]
+fn option_handlers() {
+    #[derive(Default)]
+    struct TestCore {
+        aspect: Vec<Option<String>>,
+        scale: u32,
+    }
+
+    let mut handlers = OptionHandlers::default();
+    handlers.register("aspect", |value, core: &mut TestCore| {
+        core.aspect.push(value.map(ToOwned::to_owned))
+    });
+    handlers.register("scale", |value, core: &mut TestCore| {
+        core.scale = value.and_then(|value| value.parse().ok()).unwrap_or(1)
+    });
+    assert!(handlers.has_pending());
+
+    let mut core = TestCore::default();
+    let mut options = std::collections::HashMap::from([("aspect", "4:3"), ("scale", "2")]);
+
+    let get = |options: &std::collections::HashMap<_, &str>, key: &str| {
+        options.get(key).map(|value| value.to_string())
+    };
+
+    handlers.dispatch(&mut core, |key| get(&options, key));
+    assert!(!handlers.has_pending());
+    assert_eq!(core.scale, 2);
+
+    options.insert("scale", "3");
+    handlers.dispatch(&mut core, |key| get(&options, key));
+    assert_eq!(core.scale, 3);
+
+    options.remove("aspect");
+    handlers.dispatch(&mut core, |key| get(&options, key));
+    assert_eq!(core.aspect, [Some("4:3".to_owned()), None]);
+}
+
+#[test]
+fn runtime_options() {
+    use crate::types::{CoreOptionDefinition, CoreOptionValueDefinition};
+    use std::ffi::CStr;
+
+    let declared = CoreOptionDefinitions {
+        categories: &[],
+        definitions: &[CoreOptionDefinition {
+            key: "disc",
+            desc: "Disc",
+            desc_categorized: None,
+            info: "",
+            info_categorized: None,
+            category_key: None,
+            values: &[CoreOptionValueDefinition {
+                value: "none",
+                label: None,
+            }],
+            default_value: Some("none"),
+        }],
+    };
+    let string = |ptr: *const c_char| unsafe { CStr::from_ptr(ptr) }.to_str().unwrap();
+
+    let options = RuntimeOptions::new(&declared, &HashMap::new()).unwrap();
+    assert_eq!(string(options.definitions()[0].default_value), "none");
+    assert!(options.definitions()[1].key.is_null());
+
+    let replaced = HashMap::from([(
+        "disc".to_owned(),
+        vec![
+            ("0".to_owned(), Some("Disc 1".to_owned())),
+            ("1".to_owned(), None),
+        ],
+    )]);
+    let options = RuntimeOptions::new(&declared, &replaced).unwrap();
+    let definition = &options.definitions()[0];
+    assert_eq!(string(definition.default_value), "0");
+    assert_eq!(string(definition.values[0].label), "Disc 1");
+    assert!(definition.values[1].label.is_null());
+    assert!(definition.values[2].value.is_null());
+    assert_eq!(string(options.variables()[0].value), "Disc; 0|1");
+
+    let empty = HashMap::from([("disc".to_owned(), Vec::new())]);
+    assert!(RuntimeOptions::new(&declared, &empty).is_err());
+}
@@ -0,0 +1,158 @@
+//! Per-port state that follows the number of ports the frontend provides.
+//!
+//! Frontends differ in how many controller ports they offer, RetroArch e.g. lets users
+//! configure up to 16. Instead of a fixed size array, keep the state of every port in a
+//! [`Ports`] collection and resize it in [`Core::on_max_users_changed`]. Lookups of ports
+//! outside of the collection return [`None`] instead of panicking.
+//!
+//! ```ignore
+//! fn on_max_users_changed(&mut self, max_users: Option<u32>, _ctx: &mut GenericContext) {
+//!     self.players.resize(max_users.unwrap_or(2));
+//! }
+//!
+//! fn on_set_controller_port_device(&mut self, port: u32, device: u32, _ctx: &mut GenericContext) {
+//!     self.players.set_device(port, device);
+//! }
+//!
+//! fn on_run(&mut self, ctx: &mut RunContext, _delta_us: Option<i64>) {
+//!     for port in self.players.connected_mut() {
+//!         port.state.update(ctx.get_joypad_bitmask(port.index, 0));
+//!     }
+//! }
+//! ```
+//!
+//! [`Core::on_max_users_changed`]: crate::core::Core::on_max_users_changed
+use crate::sys::{retro_controller_info, RETRO_DEVICE_JOYPAD, RETRO_DEVICE_NONE};
+
+/// A single port and the core’s state for it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Port<T> {
+    pub index: u32,
+
+    /// The device type set via [`Core::on_set_controller_port_device`](crate::core::Core::on_set_controller_port_device),
+    /// [`RETRO_DEVICE_JOYPAD`] until the frontend sets one.
+    pub device: u32,
+
+    pub state: T,
+}
+
+impl<T> Port<T> {
+    /// Whether a device other than [`RETRO_DEVICE_NONE`] is plugged into the port.
+    pub fn is_connected(&self) -> bool {
+        self.device != RETRO_DEVICE_NONE
+    }
+}
+
+/// The state of every port, see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Ports<T> {
+    ports: Vec<Port<T>>,
+}
+
+impl<T: Default> Ports<T> {
+    pub fn new(len: u32) -> Self {
+        let mut ports = Self { ports: Vec::new() };
+        ports.resize(len);
+        ports
+    }
+
+    /// Creates one port for every entry passed to
+    /// [`GenericContext::set_controller_info`](crate::contexts::GenericContext::set_controller_info).
+    pub fn with_controller_info(info: &[retro_controller_info]) -> Self {
+        Self::new(info.len() as u32)
+    }
+
+    /// Adds or removes ports at the end, returns whether the number of ports changed.
+    ///
+    /// New ports start with the default state and [`RETRO_DEVICE_JOYPAD`].
+    pub fn resize(&mut self, len: u32) -> bool {
+        let old_len = self.ports.len();
+
+        self.ports.truncate(len as usize);
+        self.ports.extend((old_len as u32..len).map(|index| Port {
+            index,
+            device: RETRO_DEVICE_JOYPAD,
+            state: T::default(),
+        }));
+
+        self.ports.len() != old_len
+    }
+
+    /// Records the device type of `port`, adding ports if the frontend uses more than expected.
+    pub fn set_device(&mut self, port: u32, device: u32) {
+        if port as usize >= self.ports.len() {
+            self.resize(port + 1);
+        }
+
+        self.ports[port as usize].device = device;
+    }
+}
+
+impl<T> Ports<T> {
+    pub fn len(&self) -> u32 {
+        self.ports.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ports.is_empty()
+    }
+
+    pub fn get(&self, port: u32) -> Option<&Port<T>> {
+        self.ports.get(port as usize)
+    }
+
+    pub fn get_mut(&mut self, port: u32) -> Option<&mut Port<T>> {
+        self.ports.get_mut(port as usize)
+    }
+
+    /// The device type of `port`, [`RETRO_DEVICE_NONE`] for ports outside of the collection.
+    pub fn device(&self, port: u32) -> u32 {
+        self.get(port).map_or(RETRO_DEVICE_NONE, |port| port.device)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Port<T>> {
+        self.ports.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Port<T>> {
+        self.ports.iter_mut()
+    }
+
+    /// The ports with a device plugged in, see [`Port::is_connected`].
+    pub fn connected(&self) -> impl Iterator<Item = &Port<T>> {
+        self.iter().filter(|port| port.is_connected())
+    }
+
+    pub fn connected_mut(&mut self) -> impl Iterator<Item = &mut Port<T>> {
+        self.iter_mut().filter(|port| port.is_connected())
+    }
+}
+
+#[test]
+fn ports() {
+    let mut ports = Ports::<u8>::new(2);
+    assert_eq!(ports.len(), 2);
+    assert!(!ports.resize(2));
+
+    ports.set_device(1, RETRO_DEVICE_NONE);
+    ports.set_device(4, RETRO_DEVICE_JOYPAD);
+    assert_eq!(ports.len(), 5);
+    assert_eq!(ports.device(1), RETRO_DEVICE_NONE);
+    assert_eq!(ports.device(8), RETRO_DEVICE_NONE);
+    assert!(ports.get(8).is_none());
+
+    for port in ports.connected_mut() {
+        port.state = port.index as u8 + 1;
+    }
+
+    assert_eq!(
+        ports.iter().map(|port| port.state).collect::<Vec<_>>(),
+        [1, 0, 3, 4, 5]
+    );
+
+    assert!(ports.resize(3));
+    assert_eq!(
+        ports.connected().map(|port| port.index).collect::<Vec<_>>(),
+        [0, 2]
+    );
+}
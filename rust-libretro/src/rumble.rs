@@ -0,0 +1,176 @@
+//! Mixing the rumble requests of multiple sources within a core.
+//!
+//! [`GenericContext::set_rumble_state`] replaces the strength of a motor, so if e.g. the
+//! emulated controller and a rumble pak both drive the same port, the last one wins.
+//! [`GenericContext::set_rumble`] instead remembers the strength requested by every source
+//! and sends the mix of all of them to the frontend, see [`RumbleMix`].
+//!
+//! [`GenericContext::set_rumble_state`]: crate::contexts::GenericContext::set_rumble_state
+//! [`GenericContext::set_rumble`]: crate::contexts::GenericContext::set_rumble
+use crate::sys::retro_rumble_effect;
+use std::collections::HashMap;
+
+/// How the strengths requested by multiple sources get combined.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RumbleMix {
+    /// The strongest request wins.
+    #[default]
+    Max,
+
+    /// The requests add up, limited to [`u16::MAX`].
+    Sum,
+}
+
+/// The requested strengths of the strong and the weak motor.
+type Strengths = [u16; 2];
+
+fn motor(effect: retro_rumble_effect) -> Option<usize> {
+    match effect {
+        retro_rumble_effect::RETRO_RUMBLE_STRONG => Some(0),
+        retro_rumble_effect::RETRO_RUMBLE_WEAK => Some(1),
+        _ => None,
+    }
+}
+
+/// Tracks the rumble requests of all sources, see the [module documentation](self).
+#[derive(Debug, Default, Clone)]
+pub struct RumbleMixer {
+    mix: RumbleMix,
+    sources: HashMap<(u32, &'static str), Strengths>,
+
+    /// The strengths last sent to the frontend, by port.
+    applied: HashMap<u32, Strengths>,
+}
+
+impl RumbleMixer {
+    pub fn new(mix: RumbleMix) -> Self {
+        Self {
+            mix,
+            ..Default::default()
+        }
+    }
+
+    pub fn mix(&self) -> RumbleMix {
+        self.mix
+    }
+
+    pub fn set_mix(&mut self, mix: RumbleMix) {
+        self.mix = mix;
+    }
+
+    /// Records the strength `source` requests for a motor of `port`.
+    pub fn set(
+        &mut self,
+        port: u32,
+        source: &'static str,
+        effect: retro_rumble_effect,
+        strength: u16,
+    ) {
+        if let Some(motor) = motor(effect) {
+            self.sources.entry((port, source)).or_default()[motor] = strength;
+        }
+    }
+
+    /// Cancels both effects `source` requested on `port`.
+    pub fn stop(&mut self, port: u32, source: &'static str) {
+        self.sources.remove(&(port, source));
+    }
+
+    /// Cancels the effects of every source.
+    pub fn stop_all(&mut self) {
+        self.sources.clear();
+    }
+
+    /// The mixed strength of a motor of `port`.
+    pub fn strength(&self, port: u32, effect: retro_rumble_effect) -> u16 {
+        match motor(effect) {
+            Some(motor) => self.mixed(port)[motor],
+            None => 0,
+        }
+    }
+
+    fn mixed(&self, port: u32) -> Strengths {
+        let mut mixed = [0u16; 2];
+
+        for (_, strengths) in self.sources.iter().filter(|((p, _), _)| *p == port) {
+            for (mixed, &strength) in mixed.iter_mut().zip(strengths) {
+                *mixed = match self.mix {
+                    RumbleMix::Max => (*mixed).max(strength),
+                    RumbleMix::Sum => mixed.saturating_add(strength),
+                };
+            }
+        }
+
+        mixed
+    }
+
+    /// Returns the motors whose mixed strength changed since the last call,
+    /// assuming the frontend gets told about all of them.
+    pub fn take_changes(&mut self) -> Vec<(u32, retro_rumble_effect, u16)> {
+        let mut ports = self
+            .sources
+            .keys()
+            .map(|(port, _)| *port)
+            .chain(self.applied.keys().copied())
+            .collect::<Vec<_>>();
+        ports.sort_unstable();
+        ports.dedup();
+
+        let mut changes = Vec::new();
+
+        for port in ports {
+            let mixed = self.mixed(port);
+            let applied = self.applied.get(&port).copied().unwrap_or_default();
+
+            for (effect, motor) in [
+                (retro_rumble_effect::RETRO_RUMBLE_STRONG, 0),
+                (retro_rumble_effect::RETRO_RUMBLE_WEAK, 1),
+            ] {
+                if mixed[motor] != applied[motor] {
+                    changes.push((port, effect, mixed[motor]));
+                }
+            }
+
+            if mixed == [0, 0] {
+                self.applied.remove(&port);
+            } else {
+                self.applied.insert(port, mixed);
+            }
+        }
+
+        changes
+    }
+}
+
+#[test]
+fn rumble_mixer() {
+    use retro_rumble_effect::*;
+
+    let mut mixer = RumbleMixer::new(RumbleMix::Max);
+    mixer.set(0, "pak", RETRO_RUMBLE_STRONG, 0x8000);
+    mixer.set(0, "controller", RETRO_RUMBLE_STRONG, 0x4000);
+    mixer.set(0, "controller", RETRO_RUMBLE_WEAK, 0x1000);
+
+    assert_eq!(
+        mixer.take_changes(),
+        [
+            (0, RETRO_RUMBLE_STRONG, 0x8000),
+            (0, RETRO_RUMBLE_WEAK, 0x1000)
+        ]
+    );
+    assert!(mixer.take_changes().is_empty());
+
+    mixer.set_mix(RumbleMix::Sum);
+    mixer.set(0, "pak", RETRO_RUMBLE_STRONG, 0xC000);
+    assert_eq!(mixer.strength(0, RETRO_RUMBLE_STRONG), u16::MAX);
+
+    mixer.stop(0, "pak");
+    assert_eq!(mixer.take_changes(), [(0, RETRO_RUMBLE_STRONG, 0x4000)]);
+
+    mixer.stop_all();
+    assert_eq!(
+        mixer.take_changes(),
+        [(0, RETRO_RUMBLE_STRONG, 0), (0, RETRO_RUMBLE_WEAK, 0)]
+    );
+    assert!(mixer.take_changes().is_empty());
+}
@@ -0,0 +1,190 @@
+//! Scheduling sound chip events with sample accuracy.
+//!
+//! Sound chips change their registers in the middle of a frame, e.g. when the emulated CPU
+//! writes to them. Instead of generating the whole frame of audio at once, a core schedules
+//! those changes with a [`SampleScheduler`] and lets [`SampleScheduler::run`] generate the
+//! samples in between, so every change takes effect at the right sample.
+//!
+//! Timestamps count audio frames, i.e. one left and one right sample, since the scheduler
+//! has been created. They keep increasing across frames of the core.
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+/// Generates the audio and handles the events driven by [`SampleScheduler::run`].
+pub trait SampleHandler<E> {
+    /// Generates the next `frames` audio frames. No event is due until all of them are done.
+    fn render(&mut self, frames: u64);
+
+    /// Handles an event that became due. It may schedule further events.
+    fn handle(&mut self, event: E, scheduler: &mut SampleScheduler<E>);
+}
+
+struct Entry<E> {
+    time: u64,
+    /// Keeps events with the same timestamp in the order they were scheduled in.
+    sequence: u64,
+    event: E,
+}
+
+impl<E> PartialEq for Entry<E> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.time, self.sequence) == (other.time, other.sequence)
+    }
+}
+
+impl<E> Eq for Entry<E> {}
+
+impl<E> PartialOrd for Entry<E> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<E> Ord for Entry<E> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, so the `BinaryHeap` pops the earliest event first
+        (other.time, other.sequence).cmp(&(self.time, self.sequence))
+    }
+}
+
+/// Events of type `E` ordered by their timestamp, see the [module documentation](self).
+pub struct SampleScheduler<E> {
+    now: u64,
+    sequence: u64,
+    events: BinaryHeap<Entry<E>>,
+}
+
+impl<E> Default for SampleScheduler<E> {
+    fn default() -> Self {
+        Self {
+            now: 0,
+            sequence: 0,
+            events: BinaryHeap::new(),
+        }
+    }
+}
+
+impl<E> std::fmt::Debug for SampleScheduler<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SampleScheduler")
+            .field("now", &self.now)
+            .field("events", &self.events.len())
+            .finish()
+    }
+}
+
+impl<E> SampleScheduler<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The timestamp of the next audio frame that gets generated.
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Schedules `event` for the audio frame at `time`.
+    ///
+    /// Events in the past become due right away.
+    pub fn schedule_at(&mut self, time: u64, event: E) {
+        self.events.push(Entry {
+            time,
+            sequence: self.sequence,
+            event,
+        });
+        self.sequence += 1;
+    }
+
+    /// Schedules `event` for `delay` audio frames from now.
+    pub fn schedule_in(&mut self, delay: u64, event: E) {
+        self.schedule_at(self.now.saturating_add(delay), event);
+    }
+
+    /// The timestamp of the earliest pending event.
+    pub fn next_event_time(&self) -> Option<u64> {
+        self.events.peek().map(|entry| entry.time)
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Cancels every pending event for which `keep` returns [`false`].
+    pub fn retain(&mut self, mut keep: impl FnMut(&E) -> bool) {
+        self.events.retain(|entry| keep(&entry.event));
+    }
+
+    /// Cancels all pending events.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Generates `frames` audio frames with `handler`, handling every event whose
+    /// timestamp falls into them right before the first frame it applies to.
+    ///
+    /// Events due right after the last frame are left for the next call.
+    pub fn run(&mut self, frames: u64, handler: &mut impl SampleHandler<E>) {
+        let end = self.now.saturating_add(frames);
+
+        while self.now < end {
+            while let Some(time) = self.next_event_time() {
+                if time > self.now {
+                    break;
+                }
+
+                let entry = self.events.pop().unwrap();
+                handler.handle(entry.event, self);
+            }
+
+            let next = self.next_event_time().map_or(end, |time| time.min(end));
+            handler.render(next - self.now);
+            self.now = next;
+        }
+    }
+}
+
+#[test]
+fn sample_scheduler() {
+    #[derive(Default)]
+    struct Chip {
+        log: Vec<String>,
+    }
+
+    impl SampleHandler<&'static str> for Chip {
+        fn render(&mut self, frames: u64) {
+            self.log.push(frames.to_string());
+        }
+
+        fn handle(&mut self, event: &'static str, scheduler: &mut SampleScheduler<&'static str>) {
+            self.log.push(format!("{event}@{}", scheduler.now()));
+
+            if event == "note" {
+                scheduler.schedule_in(3, "release");
+            }
+        }
+    }
+
+    let mut scheduler = SampleScheduler::new();
+    let mut chip = Chip::default();
+
+    scheduler.schedule_at(4, "note");
+    scheduler.schedule_at(0, "volume");
+    scheduler.schedule_at(4, "pan");
+    scheduler.schedule_at(10, "end");
+    scheduler.schedule_at(11, "cancelled");
+    scheduler.retain(|event| *event != "cancelled");
+
+    scheduler.run(5, &mut chip);
+    assert_eq!(chip.log, ["volume@0", "4", "note@4", "pan@4", "1"]);
+
+    chip.log.clear();
+    scheduler.run(5, &mut chip);
+    assert_eq!(chip.log, ["2", "release@7", "3"]);
+
+    assert_eq!(scheduler.now(), 10);
+    assert_eq!(scheduler.next_event_time(), Some(10));
+    assert_eq!(scheduler.len(), 1);
+}
@@ -0,0 +1,185 @@
+//! Versioned savestates that keep loading after the layout of the core’s state changed.
+//!
+//! Register [`StateMigrations`] with [`GenericContext::set_state_migrations`] and the
+//! wrapper prefixes every serialized state with a small header containing the current
+//! version. When an older state gets loaded, the migrations run one after another before
+//! the upgraded data gets passed to [`Core::on_unserialize`].
+//!
+//! States without a header, i.e. the ones created before migrations have been registered,
+//! are treated as version 1. [`Core::get_serialize_size`] and [`Core::on_serialize`]
+//! don’t need to account for the header.
+//!
+//! States that are large but compress well can be stored with a [`CompressedState`],
+//! which requires the `lz4` or `zstd` feature.
+//!
+//! [`GenericContext::set_state_migrations`]: crate::contexts::GenericContext::set_state_migrations
+//! [`Core::on_unserialize`]: crate::core::Core::on_unserialize
+//! [`Core::get_serialize_size`]: crate::core::Core::get_serialize_size
+//! [`Core::on_serialize`]: crate::core::Core::on_serialize
+
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+mod compressed;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+pub use compressed::*;
+
+/// Marks states that start with a version header.
+const MAGIC: [u8; 4] = *b"RLSV";
+
+type Migration = Box<dyn Fn(Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>>>;
+
+/// The state versions of a core and the functions that convert between them.
+///
+/// ```ignore
+/// let migrations = StateMigrations::new()
+///     // v1 → v2: a `u32` counter got appended
+///     .migration(|mut state| {
+///         state.extend_from_slice(&0u32.to_le_bytes());
+///         Ok(state)
+///     });
+/// ```
+#[derive(Default)]
+pub struct StateMigrations {
+    migrations: Vec<Migration>,
+}
+
+impl std::fmt::Debug for StateMigrations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StateMigrations")
+            .field("version", &self.version())
+            .finish()
+    }
+}
+
+impl StateMigrations {
+    /// The size of the header the wrapper adds to serialized states.
+    pub const HEADER_SIZE: usize = 8;
+
+    /// Starts at version 1, without any migrations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a migration from the current version to the next one.
+    pub fn migration(
+        mut self,
+        migrate: impl Fn(Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>> + 'static,
+    ) -> Self {
+        self.migrations.push(Box::new(migrate));
+        self
+    }
+
+    /// The version of newly serialized states.
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32 + 1
+    }
+
+    /// Returns the version of a serialized state and the length of its header.
+    pub fn split_header(data: &[u8]) -> (u32, usize) {
+        if data.len() >= Self::HEADER_SIZE && data[..4] == MAGIC {
+            let version = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+            (version, Self::HEADER_SIZE)
+        } else {
+            (1, 0)
+        }
+    }
+
+    /// Writes the header of the current version, returns [`false`] if `data` is too small.
+    pub(crate) fn write_header(&self, data: &mut [u8]) -> bool {
+        if data.len() < Self::HEADER_SIZE {
+            return false;
+        }
+
+        data[..4].copy_from_slice(&MAGIC);
+        data[4..Self::HEADER_SIZE].copy_from_slice(&self.version().to_le_bytes());
+        true
+    }
+
+    /// Upgrades the state data of `version`, without its header, to the current version.
+    pub fn migrate(
+        &self,
+        version: u32,
+        mut data: Vec<u8>,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if version == 0 || version > self.version() {
+            return Err(format!(
+                "Unsupported savestate version {version}, expected at most {}",
+                self.version()
+            )
+            .into());
+        }
+
+        for (index, migrate) in self.migrations.iter().enumerate() {
+            let from = index as u32 + 1;
+
+            if from >= version {
+                data = migrate(data)
+                    .map_err(|err| format!("Migrating savestate version {from} failed: {err}"))?;
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Passes the state data, upgraded to the current version if needed, to `unserialize`.
+    pub(crate) fn load(
+        &self,
+        data: &mut [u8],
+        unserialize: impl FnOnce(&mut [u8]) -> bool,
+    ) -> bool {
+        let (version, header) = Self::split_header(data);
+        let data = &mut data[header..];
+
+        if version == self.version() {
+            return unserialize(data);
+        }
+
+        match self.migrate(version, data.to_vec()) {
+            Ok(mut migrated) => unserialize(&mut migrated),
+            Err(_err) => {
+                #[cfg(feature = "log")]
+                log::warn!("Failed to load savestate: {_err}");
+
+                false
+            }
+        }
+    }
+}
+
+#[test]
+fn state_migrations() {
+    let migrations = StateMigrations::new()
+        .migration(|mut state| {
+            state.push(2);
+            Ok(state)
+        })
+        .migration(|state| match state.len() {
+            2 => Ok(state.into_iter().map(|byte| byte * 10).collect()),
+            _ => Err("broken state".into()),
+        });
+    assert_eq!(migrations.version(), 3);
+
+    let mut state = [0; StateMigrations::HEADER_SIZE + 2];
+    assert!(migrations.write_header(&mut state));
+    state[StateMigrations::HEADER_SIZE..].copy_from_slice(&[5, 6]);
+    assert_eq!(
+        StateMigrations::split_header(&state),
+        (3, StateMigrations::HEADER_SIZE)
+    );
+
+    let mut loaded = Vec::new();
+    assert!(migrations.load(&mut state, |data| {
+        loaded = data.to_vec();
+        true
+    }));
+    assert_eq!(loaded, [5, 6]);
+
+    // Without a header the state is of version 1
+    assert!(migrations.load(&mut [1], |data| {
+        loaded = data.to_vec();
+        true
+    }));
+    assert_eq!(loaded, [10, 20]);
+
+    assert!(migrations.migrate(2, vec![1, 2, 3]).is_err());
+    assert!(migrations.migrate(4, Vec::new()).is_err());
+}
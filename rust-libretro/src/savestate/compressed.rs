@@ -0,0 +1,299 @@
+/// Marks states written by [`CompressedState`].
+const MAGIC: [u8; 4] = *b"RLCS";
+
+const METHOD_RAW: u8 = 0;
+#[cfg(feature = "lz4")]
+const METHOD_LZ4: u8 = 1;
+#[cfg(feature = "zstd")]
+const METHOD_ZSTD: u8 = 2;
+
+/// The algorithm [`CompressedState`] compresses states with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Fast enough to compress a state every frame, e.g. for rewinding or runahead.
+    #[cfg(feature = "lz4")]
+    Lz4,
+
+    /// Compresses better than LZ4, at the given level from 1 to 22. zstd’s default is 3.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl Compression {
+    fn method(self) -> u8 {
+        match self {
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => METHOD_LZ4,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(_) => METHOD_ZSTD,
+        }
+    }
+}
+
+/// Compresses the states of a core with a large but compressible state,
+/// e.g. one that contains the emulated RAM.
+///
+/// The core serializes its state into a buffer owned by the [`CompressedState`],
+/// which then gets compressed into the frontend’s buffer. If the compressed state
+/// is not smaller than the raw one, the raw state gets stored instead.
+///
+/// ```ignore
+/// fn get_serialize_size(&mut self, _ctx: &mut GetSerializeSizeContext) -> size_t {
+///     self.compressed.serialize_size(self.machine.state_size()) as size_t
+/// }
+///
+/// fn on_serialize(&mut self, slice: &mut [u8], _ctx: &mut SerializeContext) -> bool {
+///     let size = self.machine.state_size();
+///     self.compressed.serialize(slice, size, |state| self.machine.save(state))
+/// }
+///
+/// fn on_unserialize(&mut self, slice: &mut [u8], _ctx: &mut UnserializeContext) -> bool {
+///     self.compressed.unserialize(slice, |state| self.machine.load(state))
+/// }
+/// ```
+///
+/// Requires the `lz4` or `zstd` feature.
+#[derive(Debug, Clone)]
+pub struct CompressedState {
+    compression: Compression,
+
+    /// The size reported by the first call of [`CompressedState::serialize_size`].
+    bound: Option<usize>,
+
+    raw: Vec<u8>,
+    compressed: Vec<u8>,
+}
+
+impl CompressedState {
+    /// The size of the header in front of the compressed state.
+    pub const HEADER_SIZE: usize = 16;
+
+    pub fn new(compression: Compression) -> Self {
+        Self {
+            compression,
+            bound: None,
+            raw: Vec::new(),
+            compressed: Vec::new(),
+        }
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Returns the size to report in [`Core::get_serialize_size`] for a raw state of
+    /// `raw_size` bytes: enough for the header and the state stored uncompressed.
+    ///
+    /// The size must not grow while a game is loaded, so the size of the first call is
+    /// returned until [`CompressedState::reset`]. Larger states still get saved as long
+    /// as they compress well enough to fit.
+    ///
+    /// [`Core::get_serialize_size`]: crate::core::Core::get_serialize_size
+    pub fn serialize_size(&mut self, raw_size: usize) -> usize {
+        *self.bound.get_or_insert(Self::HEADER_SIZE + raw_size)
+    }
+
+    /// Forgets the reported size and frees the buffers, call it in [`Core::on_unload_game`].
+    ///
+    /// [`Core::on_unload_game`]: crate::core::Core::on_unload_game
+    pub fn reset(&mut self) {
+        self.bound = None;
+        self.raw = Vec::new();
+        self.compressed = Vec::new();
+    }
+
+    /// Lets `serialize` write a raw state of `raw_size` bytes and stores it compressed in `data`.
+    ///
+    /// Returns [`false`] if `serialize` failed or the state doesn’t fit into `data`.
+    pub fn serialize(
+        &mut self,
+        data: &mut [u8],
+        raw_size: usize,
+        serialize: impl FnOnce(&mut [u8]) -> bool,
+    ) -> bool {
+        if data.len() < Self::HEADER_SIZE {
+            return false;
+        }
+
+        self.raw.clear();
+        self.raw.resize(raw_size, 0);
+
+        if !serialize(&mut self.raw) {
+            return false;
+        }
+
+        let (header, payload) = data.split_at_mut(Self::HEADER_SIZE);
+        let (method, length) = match self.compress() {
+            Some(length) if length < raw_size && length <= payload.len() => {
+                payload[..length].copy_from_slice(&self.compressed[..length]);
+                (self.compression.method(), length)
+            }
+            _ if raw_size <= payload.len() => {
+                payload[..raw_size].copy_from_slice(&self.raw);
+                (METHOD_RAW, raw_size)
+            }
+            _ => {
+                #[cfg(feature = "log")]
+                log::warn!(
+                    "The savestate needs {} bytes, the frontend provided {}",
+                    Self::HEADER_SIZE + raw_size,
+                    Self::HEADER_SIZE + payload.len()
+                );
+
+                return false;
+            }
+        };
+
+        header[..4].copy_from_slice(&MAGIC);
+        header[4] = method;
+        header[5..8].fill(0);
+        header[8..12].copy_from_slice(&(raw_size as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&(length as u32).to_le_bytes());
+
+        true
+    }
+
+    /// Decompresses the state in `data` and passes it to `unserialize`.
+    ///
+    /// States without a header, i.e. the ones saved before compression was used,
+    /// get passed on as they are.
+    pub fn unserialize(
+        &mut self,
+        data: &mut [u8],
+        unserialize: impl FnOnce(&mut [u8]) -> bool,
+    ) -> bool {
+        if data.len() < Self::HEADER_SIZE || data[..4] != MAGIC {
+            return unserialize(data);
+        }
+
+        let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap()) as usize;
+        let method = data[4];
+        let raw_size = read_u32(&data[8..12]);
+        let length = read_u32(&data[12..16]);
+
+        let payload = match data[Self::HEADER_SIZE..].get_mut(..length) {
+            Some(payload) => payload,
+            None => return false,
+        };
+
+        if method == METHOD_RAW {
+            return length == raw_size && unserialize(payload);
+        }
+
+        self.raw.clear();
+        self.raw.resize(raw_size, 0);
+
+        match decompress(method, payload, &mut self.raw) {
+            Ok(size) if size == raw_size => unserialize(&mut self.raw),
+            Ok(_size) => {
+                #[cfg(feature = "log")]
+                log::warn!("The savestate decompressed to {_size} bytes, expected {raw_size}");
+
+                false
+            }
+            Err(_err) => {
+                #[cfg(feature = "log")]
+                log::warn!("Failed to decompress the savestate: {_err}");
+
+                false
+            }
+        }
+    }
+
+    /// Compresses the raw state into `self.compressed`, returns the compressed size.
+    fn compress(&mut self) -> Option<usize> {
+        match self.compression {
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                self.compressed
+                    .resize(lz4_flex::block::get_maximum_output_size(self.raw.len()), 0);
+
+                lz4_flex::block::compress_into(&self.raw, &mut self.compressed).ok()
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => {
+                // Only compressed states smaller than the raw one get stored
+                self.compressed.resize(self.raw.len(), 0);
+
+                zstd::bulk::compress_to_buffer(&self.raw, &mut self.compressed, level).ok()
+            }
+        }
+    }
+}
+
+/// Decompresses `data` into `raw`, returns the decompressed size.
+fn decompress(
+    method: u8,
+    data: &[u8],
+    raw: &mut [u8],
+) -> Result<usize, Box<dyn std::error::Error>> {
+    match method {
+        #[cfg(feature = "lz4")]
+        METHOD_LZ4 => Ok(lz4_flex::block::decompress_into(data, raw)?),
+        #[cfg(feature = "zstd")]
+        METHOD_ZSTD => Ok(zstd::bulk::decompress_to_buffer(data, raw)?),
+        _ => Err(format!("Unsupported savestate compression method {method}").into()),
+    }
+}
+
+#[test]
+fn compressed_state() {
+    #[cfg(feature = "lz4")]
+    let compression = Compression::Lz4;
+    #[cfg(not(feature = "lz4"))]
+    let compression = Compression::Zstd(3);
+
+    let mut compressed = CompressedState::new(compression);
+    assert_eq!(compressed.serialize_size(1000), 1016);
+    assert_eq!(compressed.serialize_size(2000), 1016);
+
+    let state: Vec<u8> = (0..1000).map(|index| (index / 100) as u8).collect();
+    let mut data = [0u8; 1016];
+    assert!(compressed.serialize(&mut data, 1000, |raw| {
+        raw.copy_from_slice(&state);
+        true
+    }));
+    assert_eq!(data[4], compression.method());
+    assert!(u32::from_le_bytes(data[12..16].try_into().unwrap()) < 100);
+
+    let mut loaded = Vec::new();
+    assert!(compressed.unserialize(&mut data, |raw| {
+        loaded = raw.to_vec();
+        true
+    }));
+    assert_eq!(loaded, state);
+
+    // Incompressible states are stored raw
+    let noise = |len: usize| -> Vec<u8> {
+        let mut x = 0x2545_f491u32;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    };
+    let small = noise(1000);
+    assert!(compressed.serialize(&mut data, 1000, |raw| {
+        raw.copy_from_slice(&small);
+        true
+    }));
+    assert_eq!(data[4], METHOD_RAW);
+    assert!(compressed.unserialize(&mut data, |raw| raw == small.as_slice()));
+
+    // A state larger than the bound only fits if it compresses
+    assert!(compressed.serialize(&mut data, 2000, |raw| {
+        raw.fill(7);
+        true
+    }));
+    let large = noise(2000);
+    assert!(!compressed.serialize(&mut data, 2000, |raw| {
+        raw.copy_from_slice(&large);
+        true
+    }));
+
+    // States without a header are passed on unchanged
+    assert!(compressed.unserialize(&mut [1, 2, 3], |raw| raw == [1, 2, 3]));
+}
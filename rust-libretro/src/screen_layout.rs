@@ -0,0 +1,270 @@
+//! Arranging the two screens of handheld systems in a single framebuffer.
+//!
+//! The frontend only knows about one framebuffer, so cores emulating systems with
+//! two screens combine them. [`ScreenLayout`] computes the combined geometry and
+//! where each screen gets drawn, and pushes geometry updates when the layout changes.
+use super::*;
+use std::str::FromStr;
+
+/// How the two screens are placed next to each other.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenArrangement {
+    /// The top screen above the bottom screen.
+    #[default]
+    Vertical,
+
+    /// The top screen left of the bottom screen.
+    Horizontal,
+
+    /// Only the top screen.
+    TopOnly,
+
+    /// Only the bottom screen.
+    BottomOnly,
+}
+
+impl ScreenArrangement {
+    /// The values accepted by [`ScreenArrangement::from_str`], for use as core option values.
+    pub const OPTION_VALUES: [&'static str; 4] = ["vertical", "horizontal", "top", "bottom"];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Vertical => Self::OPTION_VALUES[0],
+            Self::Horizontal => Self::OPTION_VALUES[1],
+            Self::TopOnly => Self::OPTION_VALUES[2],
+            Self::BottomOnly => Self::OPTION_VALUES[3],
+        }
+    }
+}
+
+impl FromStr for ScreenArrangement {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "vertical" => Ok(Self::Vertical),
+            "horizontal" => Ok(Self::Horizontal),
+            "top" => Ok(Self::TopOnly),
+            "bottom" => Ok(Self::BottomOnly),
+            _ => Err(format!("Unknown screen arrangement: {value}").into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Screen {
+    Top,
+    Bottom,
+}
+
+/// A rectangle inside of the combined framebuffer, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Combines two equally sized screens into one framebuffer.
+///
+/// The maximum size of [`ScreenLayout::geometry`] fits every arrangement, so returning it
+/// from [`Core::on_get_av_info`] allows switching arrangements without reinitializing
+/// the frontend’s drivers.
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenLayout {
+    screen_width: u32,
+    screen_height: u32,
+    gap: u32,
+    arrangement: ScreenArrangement,
+
+    /// Set when the geometry has not been sent to the frontend yet.
+    dirty: bool,
+}
+
+impl ScreenLayout {
+    pub fn new(screen_width: u32, screen_height: u32, arrangement: ScreenArrangement) -> Self {
+        Self {
+            screen_width,
+            screen_height,
+            gap: 0,
+            arrangement,
+            dirty: false,
+        }
+    }
+
+    /// Leaves a gap of `gap` pixels between the screens if both are visible.
+    pub fn with_gap(mut self, gap: u32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn arrangement(&self) -> ScreenArrangement {
+        self.arrangement
+    }
+
+    /// Changes the arrangement.
+    /// The new geometry gets sent to the frontend by [`ScreenLayout::update_geometry`].
+    pub fn set_arrangement(&mut self, arrangement: ScreenArrangement) {
+        if arrangement != self.arrangement {
+            self.arrangement = arrangement;
+            self.dirty = true;
+        }
+    }
+
+    /// Changes the arrangement to the value of a core option,
+    /// e.g. in [`Core::on_options_changed`].
+    pub fn apply_option(&mut self, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.set_arrangement(value.parse()?);
+        Ok(())
+    }
+
+    /// The size of the combined framebuffer.
+    pub fn size(&self) -> (u32, u32) {
+        Self::size_of(
+            self.arrangement,
+            self.screen_width,
+            self.screen_height,
+            self.gap,
+        )
+    }
+
+    fn size_of(arrangement: ScreenArrangement, width: u32, height: u32, gap: u32) -> (u32, u32) {
+        match arrangement {
+            ScreenArrangement::Vertical => (width, height * 2 + gap),
+            ScreenArrangement::Horizontal => (width * 2 + gap, height),
+            ScreenArrangement::TopOnly | ScreenArrangement::BottomOnly => (width, height),
+        }
+    }
+
+    /// Where to draw `screen`, or [`None`] if it is hidden.
+    pub fn screen_rect(&self, screen: Screen) -> Option<Rect> {
+        let (width, height) = (self.screen_width, self.screen_height);
+        let rect = |x, y| {
+            Some(Rect {
+                x,
+                y,
+                width,
+                height,
+            })
+        };
+
+        match (self.arrangement, screen) {
+            (ScreenArrangement::Vertical, Screen::Top)
+            | (ScreenArrangement::Horizontal, Screen::Top)
+            | (ScreenArrangement::TopOnly, Screen::Top)
+            | (ScreenArrangement::BottomOnly, Screen::Bottom) => rect(0, 0),
+            (ScreenArrangement::Vertical, Screen::Bottom) => rect(0, height + self.gap),
+            (ScreenArrangement::Horizontal, Screen::Bottom) => rect(width + self.gap, 0),
+            _ => None,
+        }
+    }
+
+    fn geometry_of(
+        width: u32,
+        height: u32,
+        max_width: u32,
+        max_height: u32,
+    ) -> retro_game_geometry {
+        retro_game_geometry {
+            base_width: width,
+            base_height: height,
+            max_width,
+            max_height,
+            aspect_ratio: width as f32 / height as f32,
+        }
+    }
+
+    /// The geometry of the current arrangement, with a maximum size of [`ScreenLayout::max_size`].
+    pub fn geometry(&self) -> retro_game_geometry {
+        let (width, height) = self.size();
+        let (max_width, max_height) = self.max_size();
+
+        Self::geometry_of(width, height, max_width, max_height)
+    }
+
+    /// The largest size of all arrangements.
+    pub fn max_size(&self) -> (u32, u32) {
+        [ScreenArrangement::Vertical, ScreenArrangement::Horizontal]
+            .into_iter()
+            .map(|arrangement| {
+                Self::size_of(arrangement, self.screen_width, self.screen_height, self.gap)
+            })
+            .fold((0, 0), |(max_width, max_height), (width, height)| {
+                (max_width.max(width), max_height.max(height))
+            })
+    }
+
+    /// Copies the pixels of `screen` into the combined `framebuffer`.
+    ///
+    /// `pixels` holds `screen_width * screen_height` tightly packed pixels,
+    /// `framebuffer` is laid out as described by [`ScreenLayout::size`].
+    /// Hidden screens are skipped.
+    pub fn blit<P: Copy>(&self, framebuffer: &mut [P], screen: Screen, pixels: &[P]) {
+        let rect = match self.screen_rect(screen) {
+            Some(rect) => rect,
+            None => return,
+        };
+
+        let (stride, _) = self.size();
+        let width = rect.width as usize;
+
+        for (row, src) in pixels
+            .chunks_exact(width)
+            .take(rect.height as usize)
+            .enumerate()
+        {
+            let start = (rect.y as usize + row) * stride as usize + rect.x as usize;
+
+            if let Some(dst) = framebuffer.get_mut(start..start + width) {
+                dst.copy_from_slice(src);
+            }
+        }
+    }
+
+    /// Sends the geometry to the frontend if the arrangement changed since the last call.
+    pub fn update_geometry(&mut self, ctx: &RunContext) -> Result<bool, AvInfoError> {
+        if !self.dirty {
+            return Ok(false);
+        }
+
+        ctx.try_set_game_geometry(self.geometry())?;
+        self.dirty = false;
+
+        Ok(true)
+    }
+}
+
+#[test]
+fn screen_layouts() {
+    let mut layout = ScreenLayout::new(256, 192, ScreenArrangement::Vertical).with_gap(8);
+
+    assert_eq!(layout.size(), (256, 392));
+    assert_eq!(layout.max_size(), (520, 392));
+    assert_eq!(
+        layout.screen_rect(Screen::Bottom),
+        Some(Rect {
+            x: 0,
+            y: 200,
+            width: 256,
+            height: 192
+        })
+    );
+
+    layout.apply_option("horizontal").unwrap();
+    assert!(layout.dirty);
+    assert_eq!(layout.size(), (520, 192));
+    assert_eq!(layout.screen_rect(Screen::Bottom).unwrap().x, 264);
+
+    layout.apply_option("bottom").unwrap();
+    assert_eq!(layout.size(), (256, 192));
+    assert_eq!(layout.screen_rect(Screen::Top), None);
+    assert_eq!(layout.screen_rect(Screen::Bottom).unwrap().x, 0);
+    assert!(layout.apply_option("diagonal").is_err());
+
+    let layout = ScreenLayout::new(2, 1, ScreenArrangement::Horizontal).with_gap(1);
+    let mut framebuffer = [0u8; 5];
+    layout.blit(&mut framebuffer, Screen::Top, &[1, 1]);
+    layout.blit(&mut framebuffer, Screen::Bottom, &[2, 2]);
+    assert_eq!(framebuffer, [1, 1, 0, 2, 2]);
+}
@@ -0,0 +1,215 @@
+//! Runs a frame in separate update and render steps, so that rendering can be skipped.
+//!
+//! Frontends don’t always show the frames a core produces: with run-ahead the frames that
+//! get rolled back are never shown and the frontend disables video for them via
+//! [`AudioVideoEnable`], and a frame that looks exactly like the previous one can be duped
+//! instead of being drawn again. A [`SplitRunCore`] advances the emulation in
+//! [`SplitRunCore::update`] and draws into a [`Framebuffer`] in [`SplitRunCore::render`],
+//! which only gets called if the frame is going to be shown.
+//!
+//! Return the core from [`Core::as_split_run`] and leave [`Core::on_run`] alone,
+//! the default implementation of [`Core::on_run_timed`] takes care of the rest:
+//!
+//! ```ignore
+//! impl SplitRunCore for MyCore {
+//!     fn update(&mut self, ctx: &mut RunContext, _delta_us: Option<i64>) {
+//!         self.dirty |= self.machine.step(ctx.get_joypad_bitmask(0, 0));
+//!     }
+//!
+//!     fn render(&mut self, framebuffer: &mut Framebuffer) {
+//!         self.machine.draw(framebuffer.borrow_slice_mut(), framebuffer.pitch);
+//!         self.dirty = false;
+//!     }
+//!
+//!     fn frame_size(&self) -> (u32, u32) {
+//!         (WIDTH, HEIGHT)
+//!     }
+//!
+//!     fn pixel_format(&self) -> PixelFormat {
+//!         PixelFormat::XRGB8888
+//!     }
+//!
+//!     fn frame_changed(&self) -> bool {
+//!         self.dirty
+//!     }
+//! }
+//!
+//! impl Core for MyCore {
+//!     fn as_split_run(&mut self) -> Option<&mut dyn SplitRunCore> {
+//!         Some(self)
+//!     }
+//!
+//!     // …
+//! }
+//! ```
+//!
+//! [`Core::as_split_run`]: crate::core::Core::as_split_run
+//! [`Core::on_run`]: crate::core::Core::on_run
+//! [`Core::on_run_timed`]: crate::core::Core::on_run_timed
+use crate::{contexts::RunContext, types::*};
+
+/// A core that updates and renders a frame in separate steps, see the [module documentation](self).
+pub trait SplitRunCore {
+    /// Advances the emulation by one frame, called once per [`Core::on_run`](crate::core::Core::on_run).
+    ///
+    /// Audio gets rendered here as well, rendering can be skipped but updating can’t.
+    fn update(&mut self, ctx: &mut RunContext, delta_us: Option<i64>);
+
+    /// Draws the current frame, only called if the frontend is going to show it.
+    ///
+    /// `framebuffer` is either the frontend’s own memory or a fallback buffer,
+    /// its size and format are the ones returned by [`SplitRunCore::frame_size`]
+    /// and [`SplitRunCore::pixel_format`].
+    fn render(&mut self, framebuffer: &mut Framebuffer);
+
+    /// The width and height of the frame drawn by [`SplitRunCore::render`].
+    fn frame_size(&self) -> (u32, u32);
+
+    /// The pixel format set with [`LoadGameContext::set_pixel_format`](crate::contexts::LoadGameContext::set_pixel_format).
+    fn pixel_format(&self) -> PixelFormat;
+
+    /// Whether the frame differs from the last rendered one.
+    ///
+    /// If [`false`] and the frontend supports it, the previous frame gets duped
+    /// instead of calling [`SplitRunCore::render`].
+    fn frame_changed(&self) -> bool {
+        true
+    }
+}
+
+/// What to do with the video output of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Present {
+    /// The frontend discards the frame anyway.
+    Skip,
+    Dupe,
+    Render,
+}
+
+impl Present {
+    fn choose(video_enabled: bool, frame_changed: bool, can_dupe: bool, had_frame: bool) -> Self {
+        if !video_enabled {
+            Self::Skip
+        } else if !frame_changed && can_dupe && had_frame {
+            Self::Dupe
+        } else {
+            Self::Render
+        }
+    }
+}
+
+/// Runs one frame of `core`, called by the default [`Core::on_run_timed`](crate::core::Core::on_run_timed).
+pub(crate) fn run(core: &mut dyn SplitRunCore, ctx: &mut RunContext, delta_us: Option<i64>) {
+    core.update(ctx, delta_us);
+
+    match Present::choose(
+        ctx.hints.video_enabled,
+        core.frame_changed(),
+        ctx.can_dupe(),
+        *ctx.had_frame,
+    ) {
+        Present::Skip => (),
+        Present::Dupe => ctx.dupe_frame(),
+        Present::Render => {
+            let (width, height) = core.frame_size();
+            let mut framebuffer = unsafe {
+                ctx.get_current_framebuffer_or_fallback(
+                    width,
+                    height,
+                    MemoryAccess::WRITE,
+                    core.pixel_format(),
+                )
+            };
+
+            core.render(&mut framebuffer);
+
+            // The framebuffer borrows `ctx`, but the memory it points to doesn’t belong to it
+            let data = unsafe { framebuffer.as_slice() };
+            let (width, height, pitch) = (framebuffer.width, framebuffer.height, framebuffer.pitch);
+
+            ctx.draw_frame(data, width, height, pitch as u64);
+        }
+    }
+}
+
+#[test]
+fn choose_presentation() {
+    assert_eq!(Present::choose(false, true, true, true), Present::Skip);
+    assert_eq!(Present::choose(false, false, false, false), Present::Skip);
+    assert_eq!(Present::choose(true, false, true, true), Present::Dupe);
+    assert_eq!(Present::choose(true, true, true, true), Present::Render);
+
+    // Without frontend support or a previous frame there is nothing to dupe
+    assert_eq!(Present::choose(true, false, false, true), Present::Render);
+    assert_eq!(Present::choose(true, false, true, false), Present::Render);
+}
+
+#[test]
+fn video_enabled_hint() {
+    use crate::{
+        core::{Core, CoreOptions},
+        core_wrapper::CoreWrapper,
+        mock_frontend::*,
+    };
+
+    struct TestCore;
+
+    impl SplitRunCore for TestCore {
+        fn update(&mut self, _ctx: &mut RunContext, _delta_us: Option<i64>) {}
+
+        fn render(&mut self, _framebuffer: &mut Framebuffer) {}
+
+        fn frame_size(&self) -> (u32, u32) {
+            (1, 1)
+        }
+
+        fn pixel_format(&self) -> PixelFormat {
+            PixelFormat::XRGB8888
+        }
+    }
+
+    impl CoreOptions for TestCore {}
+    impl Core for TestCore {
+        fn get_info(&self) -> SystemInfo {
+            StubCore.get_info()
+        }
+
+        fn on_get_av_info(
+            &mut self,
+            ctx: &mut crate::contexts::GetAvInfoContext,
+        ) -> crate::sys::retro_system_av_info {
+            StubCore.on_get_av_info(ctx)
+        }
+
+        fn as_split_run(&mut self) -> Option<&mut dyn SplitRunCore> {
+            Some(self)
+        }
+    }
+
+    let install = |bits: Option<u32>| {
+        MockFrontend::install(|frontend| {
+            if let Some(bits) = bits {
+                frontend.support(crate::sys::RETRO_ENVIRONMENT_GET_AUDIO_VIDEO_ENABLE);
+                frontend.audio_video_enable = bits;
+            }
+        })
+    };
+
+    let mut wrapper = CoreWrapper::new(TestCore);
+    wrapper.environment_callback = Some(environment);
+
+    // Run-ahead disables video for single frames, so it gets fetched every frame
+    install(Some(AudioVideoEnable::ENABLE_AUDIO.bits()));
+    wrapper.update_frame_hints();
+    assert!(!wrapper.hints.video_enabled);
+
+    install(Some(AudioVideoEnable::all().bits()));
+    wrapper.update_frame_hints();
+    assert!(wrapper.hints.video_enabled);
+
+    // Frontends without support show every frame and don’t get asked again
+    install(None);
+    wrapper.update_frame_hints();
+    assert!(wrapper.hints.video_enabled);
+    assert!(!wrapper.fetch_video_enabled);
+}
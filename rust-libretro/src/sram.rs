@@ -0,0 +1,92 @@
+//! Detecting changes of the save RAM for cores that persist it themselves.
+//!
+//! Frontends usually flush [`RETRO_MEMORY_SAVE_RAM`] when the game gets unloaded,
+//! some also periodically. Under frontends that don’t, a crash loses all progress.
+//! A [`SramTracker`] hashes the save RAM every few frames and calls [`Core::on_sram_dirty`]
+//! when it changed, so that the core can write it to disk, e.g. through the VFS interface.
+//!
+//! [`RETRO_MEMORY_SAVE_RAM`]: crate::sys::RETRO_MEMORY_SAVE_RAM
+//! [`Core::on_sram_dirty`]: crate::core::Core::on_sram_dirty
+use crate::util::hash::crc32;
+
+/// Periodically checks the save RAM for changes.
+///
+/// Register it with [`GenericContext::set_sram_tracker`], usually in [`Core::on_load_game`].
+/// The save RAM is taken from [`Core::get_memory_data`] and [`Core::get_memory_size`]
+/// with the id [`RETRO_MEMORY_SAVE_RAM`].
+///
+/// [`GenericContext::set_sram_tracker`]: crate::contexts::GenericContext::set_sram_tracker
+/// [`Core::on_load_game`]: crate::core::Core::on_load_game
+/// [`Core::get_memory_data`]: crate::core::Core::get_memory_data
+/// [`Core::get_memory_size`]: crate::core::Core::get_memory_size
+/// [`RETRO_MEMORY_SAVE_RAM`]: crate::sys::RETRO_MEMORY_SAVE_RAM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SramTracker {
+    interval: u32,
+    frames_left: u32,
+    hash: Option<u32>,
+}
+
+impl SramTracker {
+    /// Checks the save RAM every `interval_frames` frames, starting with the first frame.
+    pub fn new(interval_frames: u32) -> Self {
+        Self {
+            interval: interval_frames.max(1),
+            frames_left: 0,
+            hash: None,
+        }
+    }
+
+    /// The number of frames between two checks.
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Forgets the last known contents, so that the next check only records them.
+    pub fn reset(&mut self) {
+        self.frames_left = 0;
+        self.hash = None;
+    }
+
+    /// Advances by one frame and returns [`true`] if the save RAM should be checked.
+    pub fn tick(&mut self) -> bool {
+        if self.frames_left == 0 {
+            self.frames_left = self.interval - 1;
+            true
+        } else {
+            self.frames_left -= 1;
+            false
+        }
+    }
+
+    /// Hashes `sram` and returns [`true`] if it changed since the last check.
+    ///
+    /// The first check only records the contents.
+    pub fn check(&mut self, sram: &[u8]) -> bool {
+        let hash = crc32(sram);
+
+        match self.hash.replace(hash) {
+            Some(previous) => previous != hash,
+            None => false,
+        }
+    }
+}
+
+#[test]
+fn sram_tracker() {
+    let mut tracker = SramTracker::new(3);
+    let due = (0..6).map(|_| tracker.tick()).collect::<Vec<_>>();
+    assert_eq!(due, [true, false, false, true, false, false]);
+
+    let mut sram = [0u8; 16];
+    assert!(!tracker.check(&sram));
+    assert!(!tracker.check(&sram));
+
+    sram[4] = 1;
+    assert!(tracker.check(&sram));
+    assert!(!tracker.check(&sram));
+
+    tracker.reset();
+    sram[4] = 2;
+    assert!(!tracker.check(&sram));
+}
@@ -0,0 +1,196 @@
+//! Serializing the state of a core into savestates.
+//!
+//! Implement [`SerdeState`] for the type holding the core’s state and pick a
+//! [`StateFormat`] for it:
+//!
+//! - [`Bincode`] is compact and fast, but the layout of the state must not change
+//!   without a [`StateMigrations`] entry.
+//! - [`Cbor`] stores field names, so fields can be added or removed as long as they
+//!   have `#[serde(default)]`.
+//! - [`Raw`] copies the memory of a [`bytemuck::Pod`] type, which is as fast as it gets
+//!   and useful if the frontend serializes every frame, e.g. for runahead.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct State { /* … */ }
+//!
+//! impl SerdeState for State {
+//!     type Format = Cbor;
+//! }
+//!
+//! fn on_serialize(&mut self, slice: &mut [u8], _ctx: &mut SerializeContext) -> bool {
+//!     self.state.save_state(slice).is_ok()
+//! }
+//! ```
+//!
+//! The size of [`Bincode`] and [`Cbor`] states depends on their content, so a core whose
+//! state contains e.g. a [`Vec`] should report a fixed upper bound in
+//! [`Core::get_serialize_size`]. Trailing bytes are ignored when loading.
+//!
+//! This module requires the `state-format` feature.
+//!
+//! [`StateMigrations`]: crate::savestate::StateMigrations
+//! [`Core::get_serialize_size`]: crate::core::Core::get_serialize_size
+use serde::{de::DeserializeOwned, Serialize};
+use std::error::Error;
+
+/// Converts a state of type `T` from and to bytes.
+pub trait StateFormat<T> {
+    /// The number of bytes [`StateFormat::write`] needs for `state`.
+    fn size(state: &T) -> Result<usize, Box<dyn Error>>;
+
+    /// Writes `state` to the start of `data`, returns the number of bytes written.
+    fn write(state: &T, data: &mut [u8]) -> Result<usize, Box<dyn Error>>;
+
+    fn read(data: &[u8]) -> Result<T, Box<dyn Error>>;
+}
+
+/// Uses the same options as [`bincode::DefaultOptions`] with trailing bytes allowed.
+#[derive(Debug, Clone, Copy)]
+pub struct Bincode;
+
+/// Encodes the state as CBOR, see [RFC 8949](https://www.rfc-editor.org/rfc/rfc8949).
+#[derive(Debug, Clone, Copy)]
+pub struct Cbor;
+
+/// Copies the bytes of the state as they are in memory.
+///
+/// The states are only compatible between builds for the same endianness.
+#[derive(Debug, Clone, Copy)]
+pub struct Raw;
+
+fn check_size(size: usize, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    if data.len() < size {
+        return Err(format!("The state needs {size} bytes, got {}", data.len()).into());
+    }
+
+    Ok(())
+}
+
+impl<T: Serialize + DeserializeOwned> StateFormat<T> for Bincode {
+    fn size(state: &T) -> Result<usize, Box<dyn Error>> {
+        use bincode::Options;
+
+        let size = bincode::DefaultOptions::new()
+            .allow_trailing_bytes()
+            .serialized_size(state)?;
+
+        Ok(size as usize)
+    }
+
+    fn write(state: &T, data: &mut [u8]) -> Result<usize, Box<dyn Error>> {
+        use bincode::Options;
+
+        let size = Self::size(state)?;
+        check_size(size, data)?;
+
+        bincode::DefaultOptions::new()
+            .allow_trailing_bytes()
+            .serialize_into(&mut data[..size], state)?;
+
+        Ok(size)
+    }
+
+    fn read(data: &[u8]) -> Result<T, Box<dyn Error>> {
+        use bincode::Options;
+
+        Ok(bincode::DefaultOptions::new()
+            .allow_trailing_bytes()
+            .deserialize(data)?)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> StateFormat<T> for Cbor {
+    fn size(state: &T) -> Result<usize, Box<dyn Error>> {
+        // CBOR has no way to compute the size without encoding
+        let mut buffer = Vec::new();
+        ciborium::ser::into_writer(state, &mut buffer)?;
+
+        Ok(buffer.len())
+    }
+
+    fn write(state: &T, data: &mut [u8]) -> Result<usize, Box<dyn Error>> {
+        let mut buffer = Vec::new();
+        ciborium::ser::into_writer(state, &mut buffer)?;
+
+        check_size(buffer.len(), data)?;
+        data[..buffer.len()].copy_from_slice(&buffer);
+
+        Ok(buffer.len())
+    }
+
+    fn read(data: &[u8]) -> Result<T, Box<dyn Error>> {
+        Ok(ciborium::de::from_reader(data)?)
+    }
+}
+
+impl<T: bytemuck::Pod> StateFormat<T> for Raw {
+    fn size(_state: &T) -> Result<usize, Box<dyn Error>> {
+        Ok(std::mem::size_of::<T>())
+    }
+
+    fn write(state: &T, data: &mut [u8]) -> Result<usize, Box<dyn Error>> {
+        let bytes = bytemuck::bytes_of(state);
+        check_size(bytes.len(), data)?;
+        data[..bytes.len()].copy_from_slice(bytes);
+
+        Ok(bytes.len())
+    }
+
+    fn read(data: &[u8]) -> Result<T, Box<dyn Error>> {
+        let size = std::mem::size_of::<T>();
+        check_size(size, data)?;
+
+        Ok(bytemuck::pod_read_unaligned(&data[..size]))
+    }
+}
+
+/// A core state that gets saved in the [`StateFormat`] `Format`,
+/// see the [module documentation](self).
+pub trait SerdeState: Sized {
+    type Format: StateFormat<Self>;
+
+    /// The number of bytes [`SerdeState::save_state`] needs, `0` if the state can’t be encoded.
+    fn state_size(&self) -> usize {
+        Self::Format::size(self).unwrap_or(0)
+    }
+
+    /// Writes the state to the start of `data`, returns the number of bytes written.
+    fn save_state(&self, data: &mut [u8]) -> Result<usize, Box<dyn Error>> {
+        Self::Format::write(self, data)
+    }
+
+    fn load_state(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Self::Format::read(data)
+    }
+}
+
+#[test]
+fn raw_state() {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[repr(C)]
+    struct State {
+        frame: u32,
+        registers: [u32; 3],
+    }
+
+    unsafe impl bytemuck::Zeroable for State {}
+    unsafe impl bytemuck::Pod for State {}
+
+    impl SerdeState for State {
+        type Format = Raw;
+    }
+
+    let state = State {
+        frame: 1,
+        registers: [2, 3, 0xDEAD_BEEF],
+    };
+    assert_eq!(state.state_size(), 16);
+
+    let mut data = [0u8; 20];
+    assert_eq!(state.save_state(&mut data).unwrap(), 16);
+    assert_eq!(State::load_state(&data).unwrap(), state);
+
+    assert!(state.save_state(&mut data[..8]).is_err());
+    assert!(State::load_state(&data[..15]).is_err());
+}
@@ -0,0 +1,449 @@
+//! Asking the player to enter text, e.g. a name for a high score table.
+//!
+//! libretro has no environment command to open the frontend’s on-screen keyboard, but
+//! frontends deliver keys typed on their on-screen keyboard or a physical keyboard through
+//! the keyboard callback, see [`GenericContext::enable_keyboard_callback`].
+//! [`GenericContext::request_text_input`] accepts those and additionally offers a
+//! [`TextInput`] keyboard the core draws with [`RunContext::draw_text_input`], so
+//! players with nothing but a gamepad can enter text as well.
+//!
+//! While a request is active, keyboard events are not passed to [`Core::on_keyboard_event`].
+//! The callback gets called after [`Core::on_run`] once the text has been confirmed or
+//! the request has been cancelled.
+//!
+//! [`GenericContext::enable_keyboard_callback`]: crate::contexts::GenericContext::enable_keyboard_callback
+//! [`GenericContext::request_text_input`]: crate::contexts::GenericContext::request_text_input
+//! [`RunContext::draw_text_input`]: crate::contexts::RunContext::draw_text_input
+//! [`Core::on_keyboard_event`]: crate::core::Core::on_keyboard_event
+//! [`Core::on_run`]: crate::core::Core::on_run
+use crate::{sys::retro_key, types::JoypadState};
+use std::any::Any;
+
+/// The characters of the on-screen keyboard, followed by a row of [`Key::Space`],
+/// [`Key::Delete`] and [`Key::Done`].
+const ROWS: [&str; 4] = ["1234567890", "QWERTYUIOP", "ASDFGHJKL'", "ZXCVBNM,.-"];
+const COLUMNS: usize = 10;
+
+/// The size of a key in pixels before scaling.
+const CELL: usize = 12;
+
+const BACKGROUND: u32 = 0x0020_2020;
+const KEY: u32 = 0x0050_5050;
+const SELECTED: u32 = 0x0030_70D0;
+const TEXT: u32 = 0x00FF_FFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    Space,
+    Delete,
+    Done,
+}
+
+/// Where a [`TextInput`] is at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextInputStatus {
+    Active,
+    Done(String),
+    Cancelled,
+}
+
+/// A text field with an on-screen keyboard navigated with the D-Pad.
+///
+/// `A` types the selected key, `B` deletes the last character, `Start` confirms
+/// and `Select` cancels. Keyboard input works as well.
+#[derive(Debug, Clone)]
+pub struct TextInput {
+    prompt: String,
+    text: String,
+    max_len: usize,
+    row: usize,
+    column: usize,
+    status: TextInputStatus,
+}
+
+impl TextInput {
+    /// Accepts up to `max_len` characters.
+    pub fn new(prompt: &str, max_len: usize) -> Self {
+        Self {
+            prompt: prompt.to_owned(),
+            text: String::new(),
+            max_len,
+            row: 0,
+            column: 0,
+            status: TextInputStatus::Active,
+        }
+    }
+
+    /// Prefills the text field.
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = text.chars().take(self.max_len).collect();
+        self
+    }
+
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn status(&self) -> &TextInputStatus {
+        &self.status
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == TextInputStatus::Active
+    }
+
+    fn key_at(row: usize, column: usize) -> Key {
+        match ROWS.get(row) {
+            Some(row) => Key::Char(row.as_bytes()[column] as char),
+            None => match column {
+                0..=5 => Key::Space,
+                6..=7 => Key::Delete,
+                _ => Key::Done,
+            },
+        }
+    }
+
+    fn press(&mut self, key: Key) {
+        match key {
+            Key::Char(c) => self.insert(c),
+            Key::Space => self.insert(' '),
+            Key::Delete => {
+                self.text.pop();
+            }
+            Key::Done => self.status = TextInputStatus::Done(self.text.clone()),
+        }
+    }
+
+    fn insert(&mut self, c: char) {
+        if self.text.chars().count() < self.max_len {
+            self.text.push(c);
+        }
+    }
+
+    /// Handles the buttons that have been pressed in this frame,
+    /// see [`JoypadTransitions::pressed`](crate::types::JoypadTransitions::pressed).
+    pub fn handle_buttons(&mut self, pressed: JoypadState) {
+        if !self.is_active() {
+            return;
+        }
+
+        let rows = ROWS.len() + 1;
+
+        if pressed.contains(JoypadState::UP) {
+            self.row = (self.row + rows - 1) % rows;
+        }
+        if pressed.contains(JoypadState::DOWN) {
+            self.row = (self.row + 1) % rows;
+        }
+        if pressed.contains(JoypadState::LEFT) {
+            // Skip over the columns of the wider keys in the last row
+            let key = Self::key_at(self.row, self.column);
+            while Self::key_at(self.row, self.column) == key {
+                self.column = (self.column + COLUMNS - 1) % COLUMNS;
+            }
+        }
+        if pressed.contains(JoypadState::RIGHT) {
+            let key = Self::key_at(self.row, self.column);
+            while Self::key_at(self.row, self.column) == key {
+                self.column = (self.column + 1) % COLUMNS;
+            }
+        }
+
+        if pressed.contains(JoypadState::A) {
+            self.press(Self::key_at(self.row, self.column));
+        }
+        if pressed.contains(JoypadState::B) {
+            self.press(Key::Delete);
+        }
+        if pressed.contains(JoypadState::START) {
+            self.press(Key::Done);
+        }
+        if pressed.contains(JoypadState::SELECT) {
+            self.status = TextInputStatus::Cancelled;
+        }
+    }
+
+    /// Handles a key press reported by the keyboard callback.
+    pub fn handle_key(&mut self, keycode: retro_key, character: u32) {
+        if !self.is_active() {
+            return;
+        }
+
+        match keycode {
+            retro_key::RETROK_RETURN | retro_key::RETROK_KP_ENTER => self.press(Key::Done),
+            retro_key::RETROK_BACKSPACE => self.press(Key::Delete),
+            retro_key::RETROK_ESCAPE => self.status = TextInputStatus::Cancelled,
+            _ => {
+                if let Some(c) = char::from_u32(character).filter(|c| !c.is_control()) {
+                    self.insert(c);
+                }
+            }
+        }
+    }
+
+    /// Draws the prompt, the text and the keyboard centered onto an XRGB8888 frame.
+    ///
+    /// `pitch` is the distance between two lines in pixels.
+    pub fn draw(&self, frame: &mut [u32], width: usize, height: usize, pitch: usize) {
+        let base_width = COLUMNS * CELL;
+        let base_height = (ROWS.len() + 3) * CELL;
+        let scale = (width / base_width).min(height / base_height).max(1);
+        let cell = CELL * scale;
+
+        let mut canvas = Canvas {
+            frame,
+            width: width.min(pitch),
+            height,
+            pitch,
+            scale,
+        };
+
+        let left = width.saturating_sub(base_width * scale) / 2;
+        let top = height.saturating_sub(base_height * scale) / 2;
+
+        canvas.fill(
+            left,
+            top,
+            base_width * scale,
+            base_height * scale,
+            BACKGROUND,
+        );
+
+        let max_chars = base_width / 6 - 1;
+        let text = format!("{}_", self.text);
+        let skip = text.chars().count().saturating_sub(max_chars);
+        canvas.text(left + 3 * scale, top + 2 * scale, &self.prompt, TEXT);
+        canvas.text(
+            left + 3 * scale,
+            top + cell + 2 * scale,
+            &text[text.char_indices().nth(skip).map_or(0, |(i, _)| i)..],
+            TEXT,
+        );
+
+        for row in 0..=ROWS.len() {
+            let mut column = 0;
+
+            while column < COLUMNS {
+                let key = Self::key_at(row, column);
+                let mut span = 1;
+                while column + span < COLUMNS && Self::key_at(row, column + span) == key {
+                    span += 1;
+                }
+
+                let selected = row == self.row && (column..column + span).contains(&self.column);
+                let x = left + column * cell;
+                let y = top + (row + 2) * cell;
+
+                canvas.fill(
+                    x + scale,
+                    y + scale,
+                    span * cell - 2 * scale,
+                    cell - 2 * scale,
+                    if selected { SELECTED } else { KEY },
+                );
+
+                let label = match key {
+                    Key::Char(c) => c.to_string(),
+                    Key::Space => "SPACE".to_owned(),
+                    Key::Delete => "DEL".to_owned(),
+                    Key::Done => "OK".to_owned(),
+                };
+                let label_width = label.len() * 6 * scale - scale;
+                canvas.text(
+                    x + (span * cell).saturating_sub(label_width) / 2,
+                    y + (cell - 7 * scale) / 2,
+                    &label,
+                    TEXT,
+                );
+
+                column += span;
+            }
+        }
+    }
+}
+
+struct Canvas<'a> {
+    frame: &'a mut [u32],
+    width: usize,
+    height: usize,
+    pitch: usize,
+    scale: usize,
+}
+
+impl Canvas<'_> {
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        for y in y..(y + height).min(self.height) {
+            let line = y * self.pitch;
+
+            if let Some(pixels) = self
+                .frame
+                .get_mut(line + x.min(self.width)..line + (x + width).min(self.width))
+            {
+                pixels.fill(color);
+            }
+        }
+    }
+
+    fn text(&mut self, x: usize, y: usize, text: &str, color: u32) {
+        let scale = self.scale;
+
+        for (index, c) in text.chars().enumerate() {
+            let left = x + index * 6 * scale;
+
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..5 {
+                    if bits & (0x10 >> column) != 0 {
+                        self.fill(left + column * scale, y + row * scale, scale, scale, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A 5×7 pixel glyph, one byte per line with the leftmost pixel in bit 4.
+/// Lowercase letters are drawn as uppercase ones, unknown characters as `?`.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ' ' => [0x00; 7],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '\'' => [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+type Callback = Box<dyn FnOnce(Option<String>, &mut dyn Any)>;
+
+/// A request made with [`GenericContext::request_text_input`].
+///
+/// [`GenericContext::request_text_input`]: crate::contexts::GenericContext::request_text_input
+pub struct TextInputRequest {
+    pub(crate) input: TextInput,
+    callback: Callback,
+}
+
+impl std::fmt::Debug for TextInputRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TextInputRequest")
+            .field("input", &self.input)
+            .finish()
+    }
+}
+
+impl TextInputRequest {
+    pub(crate) fn new<C: 'static>(
+        input: TextInput,
+        callback: impl FnOnce(Option<String>, &mut C) + 'static,
+    ) -> Self {
+        let callback = move |text: Option<String>, core: &mut dyn Any| {
+            if let Some(core) = core.downcast_mut::<C>() {
+                callback(text, core);
+            } else {
+                #[cfg(feature = "log")]
+                log::warn!(
+                    "Text input callback expects a core of type {}",
+                    std::any::type_name::<C>()
+                );
+            }
+        };
+
+        Self {
+            input,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Calls the callback with the entered text, or [`None`] if the request has been cancelled.
+    pub(crate) fn finish(self, core: &mut dyn Any) {
+        let text = match self.input.status {
+            TextInputStatus::Done(text) => Some(text),
+            _ => None,
+        };
+
+        (self.callback)(text, core);
+    }
+}
+
+#[test]
+fn text_input() {
+    let mut input = TextInput::new("NAME", 4);
+
+    // Down to `Q`, right to `W`, type it
+    input.handle_buttons(JoypadState::DOWN);
+    input.handle_buttons(JoypadState::RIGHT);
+    input.handle_buttons(JoypadState::A);
+    assert_eq!(input.text(), "W");
+
+    // Up twice wraps around to the last row, `OK` is left of `SPACE`
+    input.handle_buttons(JoypadState::UP);
+    input.handle_buttons(JoypadState::UP);
+    input.handle_buttons(JoypadState::A);
+    assert_eq!(input.text(), "W ");
+    input.handle_buttons(JoypadState::LEFT);
+    assert_eq!(TextInput::key_at(input.row, input.column), Key::Done);
+
+    input.handle_key(retro_key::RETROK_a, 'a' as u32);
+    input.handle_key(retro_key::RETROK_b, 'b' as u32);
+    input.handle_key(retro_key::RETROK_c, 'c' as u32);
+    assert_eq!(input.text(), "W ab");
+    input.handle_key(retro_key::RETROK_BACKSPACE, 8);
+    assert_eq!(input.text(), "W a");
+
+    input.handle_buttons(JoypadState::A);
+    assert_eq!(input.status(), &TextInputStatus::Done("W a".to_owned()));
+
+    let mut frame = vec![0u32; 320 * 240];
+    input.draw(&mut frame, 320, 240, 320);
+    assert!(frame.contains(&SELECTED));
+    assert!(frame.contains(&TEXT));
+
+    let mut input = TextInput::new("", 8).with_text("X");
+    input.handle_buttons(JoypadState::SELECT);
+    assert_eq!(input.status(), &TextInputStatus::Cancelled);
+}
@@ -0,0 +1,327 @@
+//! Switching between PAL and NTSC timings at runtime.
+//!
+//! Changing the frame rate or the sample rate requires [`RunContext::set_system_av_info`],
+//! which may reinitialize the frontend’s drivers, while geometry-only changes can use the
+//! cheaper [`RunContext::set_game_geometry`]. [`TimingManager`] picks the right call for you.
+//!
+//! A [`FrameWatchdog`] reports when [`Core::on_run`] repeatedly takes longer than a frame.
+//!
+//! [`FrameTime`] tells apart the frame times that follow the wall clock from the ones
+//! the frontend made up while throttling or after a pause, see [`Core::on_run_timed`].
+use super::*;
+use std::time::Duration;
+
+/// The AV info of a single region, as well as the rate at which the core generates audio
+/// samples if it differs from [`retro_system_timing::sample_rate`].
+#[derive(Debug, Clone, Copy)]
+pub struct RegionTiming {
+    pub av_info: retro_system_av_info,
+    pub source_sample_rate: Option<f64>,
+}
+
+impl RegionTiming {
+    pub fn new(av_info: retro_system_av_info) -> Self {
+        Self {
+            av_info,
+            source_sample_rate: None,
+        }
+    }
+
+    pub fn with_source_sample_rate(av_info: retro_system_av_info, sample_rate: f64) -> Self {
+        Self {
+            av_info,
+            source_sample_rate: Some(sample_rate),
+        }
+    }
+}
+
+/// Keeps track of the active [`Region`] and its timings.
+///
+/// Return [`TimingManager::av_info`] from [`Core::on_get_av_info`] and
+/// [`TimingManager::region`] from [`Core::on_get_region`].
+#[derive(Debug, Clone, Copy)]
+pub struct TimingManager {
+    region: Region,
+    ntsc: RegionTiming,
+    pal: RegionTiming,
+}
+
+impl TimingManager {
+    pub fn new(region: Region, ntsc: RegionTiming, pal: RegionTiming) -> Self {
+        Self { region, ntsc, pal }
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn timing(&self, region: Region) -> &RegionTiming {
+        match region {
+            Region::NTSC => &self.ntsc,
+            Region::PAL => &self.pal,
+        }
+    }
+
+    /// The AV info of the active region.
+    pub fn av_info(&self) -> retro_system_av_info {
+        self.timing(self.region).av_info
+    }
+
+    /// The factor to resample the generated audio with before
+    /// handing it to the frontend, `1.0` if no resampling is needed.
+    pub fn resample_ratio(&self) -> f64 {
+        let timing = self.timing(self.region);
+
+        match timing.source_sample_rate {
+            Some(source) if source > 0.0 => timing.av_info.timing.sample_rate / source,
+            _ => 1.0,
+        }
+    }
+
+    /// The number of audio frames the frontend expects per video frame.
+    pub fn samples_per_frame(&self) -> f64 {
+        let timing = self.av_info().timing;
+
+        if timing.fps > 0.0 {
+            timing.sample_rate / timing.fps
+        } else {
+            0.0
+        }
+    }
+
+    /// Switches to the timings of another region.
+    ///
+    /// Uses [`RunContext::try_set_game_geometry`] if only the geometry changes
+    /// and [`RunContext::try_set_system_av_info`] otherwise.
+    /// The active region stays the same if the frontend rejects the change.
+    pub fn switch_region(
+        &mut self,
+        ctx: &RunContext,
+        region: Region,
+    ) -> Result<AvInfoDiff, AvInfoError> {
+        if region == self.region {
+            return Ok(AvInfoDiff::default());
+        }
+
+        let old = self.av_info();
+        let new = self.timing(region).av_info;
+        let diff = AvInfoDiff::new(&old, &new);
+
+        if diff.requires_reinit() {
+            ctx.try_set_system_av_info(new)?;
+        } else if !diff.is_empty() {
+            ctx.try_set_game_geometry(new.geometry)?;
+        }
+
+        self.region = region;
+
+        Ok(diff)
+    }
+}
+
+/// Measures the wall time of [`Core::on_run`] against the frame budget.
+///
+/// The budget is one frame at the frame rate last reported to the frontend, unless set with
+/// [`FrameWatchdog::with_budget`]. After `consecutive` overruns in a row a warning gets logged
+/// and [`Core::on_frame_overrun`] gets called, e.g. to enable frame skipping.
+/// Register it with [`GenericContext::set_frame_watchdog`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameWatchdog {
+    consecutive: u32,
+    budget: Option<Duration>,
+    overruns: u32,
+    total_overruns: u64,
+}
+
+impl FrameWatchdog {
+    pub fn new(consecutive: u32) -> Self {
+        Self {
+            consecutive: consecutive.max(1),
+            budget: None,
+            overruns: 0,
+            total_overruns: 0,
+        }
+    }
+
+    /// Uses a fixed budget instead of the one derived from the frame rate.
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// The total number of frames that took longer than the budget.
+    pub fn total_overruns(&self) -> u64 {
+        self.total_overruns
+    }
+
+    /// Records the duration of a frame and returns [`true`] if the
+    /// budget has been exceeded `consecutive` times in a row.
+    ///
+    /// `fps` is used if no fixed budget has been set, frames are never
+    /// considered overrun without a positive frame rate.
+    pub fn record(&mut self, elapsed: Duration, fps: f64) -> bool {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None if fps > 0.0 => Duration::from_secs_f64(1.0 / fps),
+            None => return false,
+        };
+
+        if elapsed <= budget {
+            self.overruns = 0;
+            return false;
+        }
+
+        self.overruns += 1;
+        self.total_overruns += 1;
+
+        if self.overruns < self.consecutive {
+            return false;
+        }
+
+        self.overruns = 0;
+        true
+    }
+}
+
+/// The time passed since the previous frame, as reported by the frame time callback
+/// enabled with [`LoadGameContext::enable_frame_time_callback`].
+///
+/// While fast-forwarding, in slow motion or after a pause, frontends pass the reference
+/// frame time instead of the real time, so cores that advance real-time clocks by the raw
+/// value drift or jump. All variants hold the microseconds reported by the frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameTime {
+    /// The frontend runs in real time, the value follows the wall clock.
+    Normal(i64),
+
+    /// The frontend fast-forwards, runs in slow motion, rewinds, steps frames
+    /// or runs unthrottled, the value doesn’t follow the wall clock.
+    Throttled(i64),
+
+    /// The first frame after the frontend paused, the value is the reference
+    /// and doesn’t include the time spent paused.
+    Resumed(i64),
+}
+
+impl FrameTime {
+    /// The shortest gap between two frames that counts as a pause.
+    pub const PAUSE_THRESHOLD: Duration = Duration::from_millis(250);
+
+    /// Classifies the microseconds passed to the frame time callback.
+    ///
+    /// `reference` is the value passed to [`LoadGameContext::enable_frame_time_callback`],
+    /// `throttle_mode` the `RETRO_THROTTLE_*` mode reported for this frame and
+    /// `since_last_run` the wall time since the previous frame started.
+    pub fn classify(
+        usec: i64,
+        reference: Option<i64>,
+        throttle_mode: Option<u32>,
+        since_last_run: Option<Duration>,
+    ) -> Self {
+        match throttle_mode {
+            Some(RETRO_THROTTLE_NONE | RETRO_THROTTLE_VSYNC) | None => (),
+            Some(_) => return Self::Throttled(usec),
+        }
+
+        if let (Some(reference), Some(since_last_run)) = (reference, since_last_run) {
+            let frame = Duration::from_micros(reference.max(0) as u64);
+
+            if usec == reference && since_last_run >= Self::PAUSE_THRESHOLD.max(frame * 4) {
+                return Self::Resumed(usec);
+            }
+        }
+
+        Self::Normal(usec)
+    }
+
+    /// The microseconds reported by the frontend.
+    pub fn usec(&self) -> i64 {
+        match *self {
+            Self::Normal(usec) | Self::Throttled(usec) | Self::Resumed(usec) => usec,
+        }
+    }
+
+    /// The microseconds real-time clocks should advance by, zero after a pause.
+    pub fn real_time_usec(&self) -> i64 {
+        match *self {
+            Self::Normal(usec) | Self::Throttled(usec) => usec,
+            Self::Resumed(_) => 0,
+        }
+    }
+}
+
+#[test]
+fn frame_watchdog() {
+    let frame = Duration::from_millis(10);
+    let mut watchdog = FrameWatchdog::new(2);
+
+    assert!(!watchdog.record(frame * 2, 0.0));
+    assert!(!watchdog.record(frame * 2, 60.0));
+    assert!(!watchdog.record(frame, 60.0));
+    assert!(!watchdog.record(frame * 2, 60.0));
+    assert!(watchdog.record(frame * 2, 60.0));
+    assert!(!watchdog.record(frame * 2, 60.0));
+    assert_eq!(watchdog.total_overruns(), 4);
+
+    let mut watchdog = FrameWatchdog::new(1).with_budget(frame * 3);
+    assert!(!watchdog.record(frame * 2, 60.0));
+    assert!(watchdog.record(frame * 4, 60.0));
+}
+
+#[test]
+fn timing_manager_ratios() {
+    let av_info = |fps, sample_rate| retro_system_av_info {
+        geometry: retro_game_geometry {
+            base_width: 256,
+            base_height: 240,
+            max_width: 256,
+            max_height: 288,
+            aspect_ratio: 4.0 / 3.0,
+        },
+        timing: retro_system_timing { fps, sample_rate },
+    };
+
+    let mut timing = TimingManager::new(
+        Region::NTSC,
+        RegionTiming::with_source_sample_rate(av_info(60.0, 48000.0), 32000.0),
+        RegionTiming::new(av_info(50.0, 48000.0)),
+    );
+
+    assert_eq!(timing.resample_ratio(), 1.5);
+    assert_eq!(timing.samples_per_frame(), 800.0);
+
+    timing.region = Region::PAL;
+    assert_eq!(timing.resample_ratio(), 1.0);
+    assert_eq!(timing.samples_per_frame(), 960.0);
+}
+
+#[test]
+fn frame_time() {
+    let reference = Some(16_667);
+    let frame = Some(Duration::from_micros(16_700));
+    let paused = Some(Duration::from_secs(3));
+
+    assert_eq!(
+        FrameTime::classify(16_800, reference, Some(RETRO_THROTTLE_VSYNC), frame),
+        FrameTime::Normal(16_800)
+    );
+    assert_eq!(
+        FrameTime::classify(16_667, reference, Some(RETRO_THROTTLE_FAST_FORWARD), frame),
+        FrameTime::Throttled(16_667)
+    );
+    assert_eq!(
+        FrameTime::classify(16_667, reference, None, paused),
+        FrameTime::Resumed(16_667)
+    );
+    assert_eq!(FrameTime::Resumed(16_667).real_time_usec(), 0);
+
+    // A long frame without the reference value is a slow frame, not a pause
+    assert_eq!(
+        FrameTime::classify(3_000_000, reference, None, paused),
+        FrameTime::Normal(3_000_000)
+    );
+    assert_eq!(
+        FrameTime::classify(16_667, reference, None, None),
+        FrameTime::Normal(16_667)
+    );
+}
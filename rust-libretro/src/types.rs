@@ -1,21 +1,27 @@
 //! Rust versions of libretro data structures.
 use super::*;
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+};
 
 /// Static information about the [`Core`] implementation.
 #[derive(Debug, Default)]
 pub struct SystemInfo {
     /// Descriptive name of library. Should not
     /// contain any version numbers, etc.
-    pub library_name: CString,
+    ///
+    /// Use [`c_str!`](crate::proc::c_str) to declare the strings at compile time,
+    /// e.g. `c_str!("ExampleCore").into()`.
+    pub library_name: Cow<'static, CStr>,
 
     /// Descriptive version of the core.
-    pub library_version: CString,
+    pub library_version: Cow<'static, CStr>,
 
     /// A string listing probably content extensions the core will be able to
     /// load, separated with pipe. I.e. "bin|rom|iso".
     /// Typically used for a GUI to filter out extensions.
-    pub valid_extensions: CString,
+    pub valid_extensions: Cow<'static, CStr>,
 
     /// libretro cores that need to have direct access to their content
     /// files, including cores which use the path of the content files to
@@ -62,6 +68,10 @@ bitflags::bitflags! {
         /// pointer is supposed to be on the screen.
         /// The frontend must make sure not to interfere with its own hardware
         /// mouse pointer.
+        ///
+        /// libretro has no environment command to grab the mouse or the keyboard,
+        /// as the reported motion is relative anyway. Capturing the host’s
+        /// pointer is up to the user, e.g. with RetroArch’s “Grab Mouse” hotkey.
         const MOUSE = (1 << RETRO_DEVICE_MOUSE);
 
         /// KEYBOARD device lets one poll for raw key pressed.
@@ -225,6 +235,7 @@ impl MessageProgress {
 }
 
 /// Screen rotation in degrees
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Rotation {
     None,
 
@@ -251,6 +262,22 @@ impl Rotation {
             Rotation::CounterClockwise270 => 3,
         }
     }
+
+    /// Whether the rotation swaps the width and height of the screen, e.g. for vertical (TATE) games.
+    pub fn is_vertical(&self) -> bool {
+        self.get_env_value() % 2 == 1
+    }
+}
+
+/// Who rotates the screen after [`LoadGameContext::set_rotation`](crate::contexts::LoadGameContext::set_rotation).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RotationMode {
+    /// The frontend accepted the rotation.
+    Frontend,
+
+    /// The frontend refused the rotation, so [`RunContext::draw_frame`](crate::contexts::RunContext::draw_frame)
+    /// rotates the frames instead.
+    Software,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -295,6 +322,503 @@ impl From<PixelFormat> for retro_pixel_format {
     }
 }
 
+/// A copy of a software rendered frame, see [`RunContext::set_frame_capture`].
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub pitch: usize,
+    pub format: PixelFormat,
+}
+
+impl CapturedFrame {
+    /// Converts the frame to tightly packed 8 bit `R, G, B` pixels.
+    pub fn to_rgb8(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let bytes_per_pixel = self.format.bit_per_pixel();
+        let row_length = self.width as usize * bytes_per_pixel;
+
+        if bytes_per_pixel == 0 {
+            return Err("Unknown pixel format".into());
+        } else if self.pitch < row_length {
+            return Err(format!(
+                "Pitch {} is too small for {} pixels",
+                self.pitch, self.width
+            )
+            .into());
+        }
+
+        let mut rgb = Vec::with_capacity(self.width as usize * self.height as usize * 3);
+        let mut pixels = vec![0u32; self.width as usize];
+
+        for y in 0..self.height as usize {
+            let row = self
+                .data
+                .get(y * self.pitch..y * self.pitch + row_length)
+                .ok_or("The frame data is truncated")?;
+
+            if let PixelFormat::XRGB8888 = self.format {
+                for (pixel, bytes) in pixels.iter_mut().zip(row.chunks_exact(4)) {
+                    *pixel = u32::from_ne_bytes(bytes.try_into().unwrap());
+                }
+            } else {
+                let row: Vec<u16> = row
+                    .chunks_exact(2)
+                    .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+                    .collect();
+
+                match self.format {
+                    PixelFormat::RGB565 => util::convert::rgb565_to_xrgb8888(&row, &mut pixels),
+                    _ => util::convert::xrgb1555_to_xrgb8888(&row, &mut pixels),
+                }
+            }
+
+            for pixel in &pixels {
+                rgb.extend_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8]);
+            }
+        }
+
+        Ok(rgb)
+    }
+
+    /// Encodes the frame as an uncompressed PNG image.
+    pub fn to_png(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        util::png::encode_rgb8(self.width, self.height, &self.to_rgb8()?)
+    }
+}
+
+/// Detects software rendered frames that are identical to the previous one,
+/// see [`RunContext::set_frame_dedup`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameDeduplicator {
+    /// The hash, width, height and pitch of the previous frame.
+    last: Option<(u64, u32, u32, usize)>,
+    duplicates: u64,
+}
+
+impl FrameDeduplicator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers the frame and returns whether it equals the previous one.
+    pub fn is_duplicate(&mut self, data: &[u8], width: u32, height: u32, pitch: usize) -> bool {
+        // The last row does not need to be padded to the full pitch
+        let length = data.len().min(pitch * height as usize);
+        let frame = (util::hash::xxh64(&data[..length], 0), width, height, pitch);
+
+        let duplicate = self.last == Some(frame);
+        self.last = Some(frame);
+
+        if duplicate {
+            self.duplicates += 1;
+        }
+
+        duplicate
+    }
+
+    /// Forgets the previous frame, e.g. because the frontend got a frame from elsewhere.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// The number of frames detected as duplicates.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+}
+
+#[test]
+fn frame_deduplicator() {
+    let mut dedup = FrameDeduplicator::new();
+    let frame = [1u8, 2, 3, 4, 0xFF, 0xFF, 5, 6, 7, 8, 0xFF, 0xFF];
+
+    assert!(!dedup.is_duplicate(&frame, 1, 2, 6));
+    assert!(dedup.is_duplicate(&frame, 1, 2, 6));
+
+    // Data past the last row is ignored
+    assert!(dedup.is_duplicate(&[frame.as_slice(), &[9, 9]].concat(), 1, 2, 6));
+    assert!(!dedup.is_duplicate(&frame, 2, 1, 6));
+
+    dedup.reset();
+    assert!(!dedup.is_duplicate(&frame, 2, 1, 6));
+    assert_eq!(dedup.duplicates(), 2);
+}
+
+/// An image embedded with [`include_image_xrgb8888!`](crate::proc::include_image_xrgb8888).
+///
+/// The pixels are tightly packed [`PixelFormat::XRGB8888`] values.
+/// Their otherwise unused top byte holds the alpha channel of the source image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: &'static [u32],
+}
+
+impl StaticImage {
+    /// The length of a row in bytes, as expected by [`RunContext::draw_frame`].
+    pub const fn pitch(&self) -> u64 {
+        self.width as u64 * 4
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<u32> {
+        if x >= self.width {
+            return None;
+        }
+
+        self.pixels
+            .get(y as usize * self.width as usize + x as usize)
+            .copied()
+    }
+
+    /// The pixels as bytes, e.g. for [`RunContext::draw_frame`].
+    pub fn as_bytes(&self) -> &'static [u8] {
+        // SAFETY: `u8` has no alignment requirements and every bit pattern is valid
+        unsafe {
+            std::slice::from_raw_parts(
+                self.pixels.as_ptr() as *const u8,
+                std::mem::size_of_val(self.pixels),
+            )
+        }
+    }
+
+    /// Blends the image onto an `XRGB8888` framebuffer of `dst_width` pixels per row,
+    /// with its top left corner at `x`, `y`. Parts outside of the framebuffer get clipped.
+    pub fn blit(&self, dst: &mut [u32], dst_width: u32, x: i32, y: i32) {
+        let dst_width = dst_width as i64;
+        if dst_width == 0 {
+            return;
+        }
+        let dst_height = dst.len() as i64 / dst_width;
+
+        for (row, pixels) in self
+            .pixels
+            .chunks_exact(self.width.max(1) as usize)
+            .enumerate()
+        {
+            let dst_y = y as i64 + row as i64;
+            if !(0..dst_height).contains(&dst_y) {
+                continue;
+            }
+
+            for (column, &pixel) in pixels.iter().enumerate() {
+                let dst_x = x as i64 + column as i64;
+                if !(0..dst_width).contains(&dst_x) {
+                    continue;
+                }
+
+                let target = &mut dst[(dst_y * dst_width + dst_x) as usize];
+                *target = blend(*target, pixel);
+            }
+        }
+    }
+}
+
+/// Draws `src` over `dst`, weighted by the alpha value in the top byte of `src`.
+pub(crate) fn blend(dst: u32, src: u32) -> u32 {
+    let alpha = src >> 24;
+    match alpha {
+        0 => dst,
+        0xFF => src & 0x00FF_FFFF,
+        _ => {
+            // Maps the alpha value to `0..=256`, so the division becomes a shift
+            let weight = alpha + (alpha >> 7);
+            let inverse = 256 - weight;
+            let rb = ((src & 0x00FF_00FF) * weight + (dst & 0x00FF_00FF) * inverse) >> 8;
+            let g = ((src & 0x0000_FF00) * weight + (dst & 0x0000_FF00) * inverse) >> 8;
+            (rb & 0x00FF_00FF) | (g & 0x0000_FF00)
+        }
+    }
+}
+
+/// The video standard of the loaded content, see [`Core::on_get_region`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Region {
+    NTSC = RETRO_REGION_NTSC as isize,
+    PAL = RETRO_REGION_PAL as isize,
+}
+
+impl Region {
+    /// The region of a video standard running at `fps`:
+    /// PAL for the 50 Hz family, NTSC for everything else.
+    pub fn from_fps(fps: f64) -> Self {
+        // Halfway between 50 and 60 Hz, also covers rates like 49.70 or 59.94 Hz
+        if fps > 0.0 && fps < 55.0 {
+            Self::PAL
+        } else {
+            Self::NTSC
+        }
+    }
+}
+
+impl From<Region> for std::os::raw::c_uint {
+    fn from(other: Region) -> Self {
+        other as std::os::raw::c_uint
+    }
+}
+
+#[test]
+fn region_from_fps() {
+    assert_eq!(Region::from_fps(50.0), Region::PAL);
+    assert_eq!(Region::from_fps(49.701459), Region::PAL);
+    assert_eq!(Region::from_fps(59.94), Region::NTSC);
+    assert_eq!(Region::from_fps(60.098814), Region::NTSC);
+    assert_eq!(Region::from_fps(75.0), Region::NTSC);
+    assert_eq!(Region::from_fps(0.0), Region::NTSC);
+}
+
+/// Hints of the frontend about the current frame, see [`RunContext::hints`].
+///
+/// Fetched by the wrapper before [`Core::on_run`], so reading them doesn’t call into the
+/// frontend. They get refreshed about once per second, so changes made in the frontend’s
+/// menu or by its fast-forward hotkey show up with a delay of up to a second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHints {
+    /// Whether the frontend wants the overscan area to be shown instead of cropped.
+    ///
+    /// Deprecated by libretro in favor of core options.
+    pub overscan: bool,
+
+    /// Whether the frontend can dupe frames, see [`RunContext::dupe_frame`].
+    pub can_dupe: bool,
+
+    /// Whether the frontend is fast-forwarding, see [`Core::on_fastforward_changed`].
+    pub fastforwarding: bool,
+
+    /// The number of ports the frontend provides, see [`Core::on_max_users_changed`].
+    pub max_users: Option<u32>,
+
+    /// Whether the frontend is going to show this frame, [`false`] e.g. for the frames
+    /// run-ahead rolls back.
+    ///
+    /// Unlike the other hints, this one gets fetched every frame, but only for cores
+    /// that return themselves from `Core::as_split_run`. Always [`true`] otherwise
+    /// and if the frontend doesn’t support `RETRO_ENVIRONMENT_GET_AUDIO_VIDEO_ENABLE`.
+    pub video_enabled: bool,
+}
+
+impl Default for FrameHints {
+    fn default() -> Self {
+        Self {
+            overscan: false,
+            can_dupe: false,
+            fastforwarding: false,
+            max_users: None,
+            video_enabled: true,
+        }
+    }
+}
+
+/// The language of the frontend, see [`GenericContext::get_user_info`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Language {
+    #[default]
+    English,
+    Japanese,
+    French,
+    Spanish,
+    German,
+    Italian,
+    Dutch,
+    PortugueseBrazil,
+    PortuguesePortugal,
+    Russian,
+    Korean,
+    ChineseTraditional,
+    ChineseSimplified,
+    Esperanto,
+    Polish,
+    Vietnamese,
+    Arabic,
+    Greek,
+    Turkish,
+    Slovak,
+    Persian,
+    Hebrew,
+    Asturian,
+    Finnish,
+    Indonesian,
+    Swedish,
+    Ukrainian,
+}
+
+impl Language {
+    /// The IETF language tag, e.g. `"pt-BR"`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::Japanese => "ja",
+            Self::French => "fr",
+            Self::Spanish => "es",
+            Self::German => "de",
+            Self::Italian => "it",
+            Self::Dutch => "nl",
+            Self::PortugueseBrazil => "pt-BR",
+            Self::PortuguesePortugal => "pt-PT",
+            Self::Russian => "ru",
+            Self::Korean => "ko",
+            Self::ChineseTraditional => "zh-Hant",
+            Self::ChineseSimplified => "zh-Hans",
+            Self::Esperanto => "eo",
+            Self::Polish => "pl",
+            Self::Vietnamese => "vi",
+            Self::Arabic => "ar",
+            Self::Greek => "el",
+            Self::Turkish => "tr",
+            Self::Slovak => "sk",
+            Self::Persian => "fa",
+            Self::Hebrew => "he",
+            Self::Asturian => "ast",
+            Self::Finnish => "fi",
+            Self::Indonesian => "id",
+            Self::Swedish => "sv",
+            Self::Ukrainian => "uk",
+        }
+    }
+}
+
+impl From<retro_language> for Language {
+    /// Unknown languages fall back to English, like in the libretro API.
+    fn from(other: retro_language) -> Self {
+        match other {
+            retro_language::RETRO_LANGUAGE_ENGLISH => Self::English,
+            retro_language::RETRO_LANGUAGE_JAPANESE => Self::Japanese,
+            retro_language::RETRO_LANGUAGE_FRENCH => Self::French,
+            retro_language::RETRO_LANGUAGE_SPANISH => Self::Spanish,
+            retro_language::RETRO_LANGUAGE_GERMAN => Self::German,
+            retro_language::RETRO_LANGUAGE_ITALIAN => Self::Italian,
+            retro_language::RETRO_LANGUAGE_DUTCH => Self::Dutch,
+            retro_language::RETRO_LANGUAGE_PORTUGUESE_BRAZIL => Self::PortugueseBrazil,
+            retro_language::RETRO_LANGUAGE_PORTUGUESE_PORTUGAL => Self::PortuguesePortugal,
+            retro_language::RETRO_LANGUAGE_RUSSIAN => Self::Russian,
+            retro_language::RETRO_LANGUAGE_KOREAN => Self::Korean,
+            retro_language::RETRO_LANGUAGE_CHINESE_TRADITIONAL => Self::ChineseTraditional,
+            retro_language::RETRO_LANGUAGE_CHINESE_SIMPLIFIED => Self::ChineseSimplified,
+            retro_language::RETRO_LANGUAGE_ESPERANTO => Self::Esperanto,
+            retro_language::RETRO_LANGUAGE_POLISH => Self::Polish,
+            retro_language::RETRO_LANGUAGE_VIETNAMESE => Self::Vietnamese,
+            retro_language::RETRO_LANGUAGE_ARABIC => Self::Arabic,
+            retro_language::RETRO_LANGUAGE_GREEK => Self::Greek,
+            retro_language::RETRO_LANGUAGE_TURKISH => Self::Turkish,
+            retro_language::RETRO_LANGUAGE_SLOVAK => Self::Slovak,
+            retro_language::RETRO_LANGUAGE_PERSIAN => Self::Persian,
+            retro_language::RETRO_LANGUAGE_HEBREW => Self::Hebrew,
+            retro_language::RETRO_LANGUAGE_ASTURIAN => Self::Asturian,
+            retro_language::RETRO_LANGUAGE_FINNISH => Self::Finnish,
+            retro_language::RETRO_LANGUAGE_INDONESIAN => Self::Indonesian,
+            retro_language::RETRO_LANGUAGE_SWEDISH => Self::Swedish,
+            retro_language::RETRO_LANGUAGE_UKRAINIAN => Self::Ukrainian,
+            _ => Self::English,
+        }
+    }
+}
+
+impl From<Language> for retro_language {
+    fn from(other: Language) -> Self {
+        match other {
+            Language::English => Self::RETRO_LANGUAGE_ENGLISH,
+            Language::Japanese => Self::RETRO_LANGUAGE_JAPANESE,
+            Language::French => Self::RETRO_LANGUAGE_FRENCH,
+            Language::Spanish => Self::RETRO_LANGUAGE_SPANISH,
+            Language::German => Self::RETRO_LANGUAGE_GERMAN,
+            Language::Italian => Self::RETRO_LANGUAGE_ITALIAN,
+            Language::Dutch => Self::RETRO_LANGUAGE_DUTCH,
+            Language::PortugueseBrazil => Self::RETRO_LANGUAGE_PORTUGUESE_BRAZIL,
+            Language::PortuguesePortugal => Self::RETRO_LANGUAGE_PORTUGUESE_PORTUGAL,
+            Language::Russian => Self::RETRO_LANGUAGE_RUSSIAN,
+            Language::Korean => Self::RETRO_LANGUAGE_KOREAN,
+            Language::ChineseTraditional => Self::RETRO_LANGUAGE_CHINESE_TRADITIONAL,
+            Language::ChineseSimplified => Self::RETRO_LANGUAGE_CHINESE_SIMPLIFIED,
+            Language::Esperanto => Self::RETRO_LANGUAGE_ESPERANTO,
+            Language::Polish => Self::RETRO_LANGUAGE_POLISH,
+            Language::Vietnamese => Self::RETRO_LANGUAGE_VIETNAMESE,
+            Language::Arabic => Self::RETRO_LANGUAGE_ARABIC,
+            Language::Greek => Self::RETRO_LANGUAGE_GREEK,
+            Language::Turkish => Self::RETRO_LANGUAGE_TURKISH,
+            Language::Slovak => Self::RETRO_LANGUAGE_SLOVAK,
+            Language::Persian => Self::RETRO_LANGUAGE_PERSIAN,
+            Language::Hebrew => Self::RETRO_LANGUAGE_HEBREW,
+            Language::Asturian => Self::RETRO_LANGUAGE_ASTURIAN,
+            Language::Finnish => Self::RETRO_LANGUAGE_FINNISH,
+            Language::Indonesian => Self::RETRO_LANGUAGE_INDONESIAN,
+            Language::Swedish => Self::RETRO_LANGUAGE_SWEDISH,
+            Language::Ukrainian => Self::RETRO_LANGUAGE_UKRAINIAN,
+        }
+    }
+}
+
+/// Information about the user of the frontend.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserInfo {
+    pub username: Option<String>,
+    pub language: Language,
+}
+
+/// The frontend as guessed by [`GenericContext::get_frontend_info`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum FrontendKind {
+    RetroArch,
+    Ludo,
+    #[default]
+    Other,
+}
+
+impl FrontendKind {
+    /// Guesses the frontend from the directories it reports and the version of its message interface.
+    pub(crate) fn guess<'a>(
+        paths: impl IntoIterator<Item = &'a Path>,
+        message_interface_version: u32,
+    ) -> Self {
+        let mut retroarch = false;
+
+        for component in paths.into_iter().flat_map(Path::components) {
+            let component = component.as_os_str().to_string_lossy().to_lowercase();
+
+            if component == "ludo" || component == ".ludo" {
+                return Self::Ludo;
+            }
+
+            retroarch |= component.contains("retroarch");
+        }
+
+        // The message interface originates from RetroArch
+        if retroarch || message_interface_version >= 1 {
+            Self::RetroArch
+        } else {
+            Self::Other
+        }
+    }
+}
+
+#[test]
+fn frontend_kind_guess() {
+    let paths = |paths: &'static [&'static str]| paths.iter().map(Path::new);
+
+    assert_eq!(
+        FrontendKind::guess(paths(&["/home/user/.ludo/system"]), 0),
+        FrontendKind::Ludo
+    );
+    assert_eq!(
+        FrontendKind::guess(paths(&["/home/user/.config/retroarch/system"]), 0),
+        FrontendKind::RetroArch
+    );
+    assert_eq!(FrontendKind::guess(paths(&[]), 1), FrontendKind::RetroArch);
+    assert_eq!(
+        FrontendKind::guess(paths(&["/srv/preludo/system"]), 0),
+        FrontendKind::Other
+    );
+}
+
+/// Best-effort information about the frontend, see [`GenericContext::get_frontend_info`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrontendInfo {
+    pub kind: FrontendKind,
+    pub core_options_version: u32,
+    pub message_interface_version: u32,
+    pub disk_control_interface_version: u32,
+}
+
 #[derive(Debug)]
 pub struct PerfCounter {
     #[allow(unused)]
@@ -303,10 +827,118 @@ pub struct PerfCounter {
     pub(crate) counter: retro_perf_counter,
 }
 
+/// The counters of [`GenericContext::start_perf_counter`].
+///
+/// Frontends keep pointers to the registered counters, so they are boxed to stay in place.
 #[derive(Debug, Default)]
 pub struct PerfCounters {
     pub interface: Option<retro_perf_callback>,
-    pub counters: HashMap<&'static str, PerfCounter>,
+    pub counters: HashMap<&'static str, Box<PerfCounter>>,
+
+    /// Counters registered for previously loaded content.
+    /// libretro has no way to unregister a counter, so they are kept alive until
+    /// [`retro_deinit`] in case the frontend still reads them.
+    pub retired: Vec<Box<PerfCounter>>,
+}
+
+impl PerfCounters {
+    /// Stops using the registered counters, they get registered again on their next start.
+    pub(crate) fn unregister_all(&mut self) {
+        self.retired
+            .extend(self.counters.drain().map(|(_, counter)| counter));
+    }
+
+    /// Frees all counters, once the frontend is done with the core.
+    pub(crate) fn clear(&mut self) {
+        self.counters.clear();
+        self.retired.clear();
+    }
+}
+
+#[test]
+fn perf_counters_lifecycle() {
+    let mut counters = PerfCounters::default();
+
+    let ident = CString::new("frame").unwrap();
+    let counter = retro_perf_counter {
+        ident: ident.as_ptr(),
+        start: 0,
+        total: 0,
+        call_cnt: 0,
+        registered: true,
+    };
+    counters
+        .counters
+        .insert("frame", Box::new(PerfCounter { ident, counter }));
+    let address = &counters.counters["frame"].counter as *const retro_perf_counter;
+
+    counters.unregister_all();
+    assert!(counters.counters.is_empty());
+    assert_eq!(&counters.retired[0].counter as *const _, address);
+
+    counters.clear();
+    assert!(counters.retired.is_empty());
+}
+
+/// Messages waiting to be displayed by [`GenericContext::show_simple_message`].
+///
+/// The legacy message interface replaces whatever message is currently on screen,
+/// so messages get displayed one after another instead.
+#[derive(Debug, Default)]
+pub struct MessageQueue {
+    frames_left: u32,
+    pending: VecDeque<(String, u32)>,
+}
+
+impl MessageQueue {
+    /// Queues a message and returns it if it should be displayed right away.
+    pub(crate) fn push(&mut self, text: &str, frames: u32) -> Option<(String, u32)> {
+        self.pending.push_back((text.to_owned(), frames));
+
+        if self.frames_left == 0 {
+            return self.next();
+        }
+
+        None
+    }
+
+    /// Advances the queue by one frame and returns the message that should
+    /// be displayed next, if the current one has expired.
+    pub(crate) fn tick(&mut self) -> Option<(String, u32)> {
+        self.frames_left = self.frames_left.saturating_sub(1);
+
+        if self.frames_left == 0 {
+            return self.next();
+        }
+
+        None
+    }
+
+    /// Drops all pending messages.
+    pub(crate) fn clear(&mut self) {
+        self.frames_left = 0;
+        self.pending.clear();
+    }
+
+    fn next(&mut self) -> Option<(String, u32)> {
+        let (text, frames) = self.pending.pop_front()?;
+        self.frames_left = frames;
+
+        Some((text, frames))
+    }
+}
+
+#[test]
+fn message_queue_order() {
+    let mut queue = MessageQueue::default();
+
+    assert_eq!(queue.push("first", 2), Some(("first".to_owned(), 2)));
+    assert_eq!(queue.push("second", 1), None);
+
+    assert_eq!(queue.tick(), None);
+    assert_eq!(queue.tick(), Some(("second".to_owned(), 1)));
+    assert_eq!(queue.tick(), None);
+    assert_eq!(queue.push("third", 1), Some(("third".to_owned(), 1)));
 }
 
 #[derive(Debug, Default)]
@@ -317,6 +949,883 @@ pub struct Position {
     pub vert_accuracy: f64,
 }
 
+bitflags::bitflags! {
+    /// Joypad button mask
+    pub struct JoypadState: u16 {
+        const B      = 0b0000_0000_0000_0001;
+        const Y      = 0b0000_0000_0000_0010;
+        const SELECT = 0b0000_0000_0000_0100;
+        const START  = 0b0000_0000_0000_1000;
+
+        const UP     = 0b0000_0000_0001_0000;
+        const DOWN   = 0b0000_0000_0010_0000;
+        const LEFT   = 0b0000_0000_0100_0000;
+        const RIGHT  = 0b0000_0000_1000_0000;
+
+        const A      = 0b0000_0001_0000_0000;
+        const X      = 0b0000_0010_0000_0000;
+        const L      = 0b0000_0100_0000_0000;
+        const R      = 0b0000_1000_0000_0000;
+
+        const L2     = 0b0001_0000_0000_0000;
+        const R2     = 0b0010_0000_0000_0000;
+        const L3     = 0b0100_0000_0000_0000;
+        const R3     = 0b1000_0000_0000_0000;
+    }
+}
+
+impl From<sys::input::JoypadButton> for JoypadState {
+    fn from(button: sys::input::JoypadButton) -> Self {
+        Self::from_bits_truncate(1 << button as u32)
+    }
+}
+
+/// Tracks the joypad state of one port across frames to detect
+/// buttons that have just been pressed or released.
+///
+/// See [`RunContext::get_joypad_transitions`].
+#[derive(Debug, Clone, Copy)]
+pub struct JoypadTransitions {
+    previous: JoypadState,
+    current: JoypadState,
+    pub(crate) polled: bool,
+}
+
+impl Default for JoypadTransitions {
+    fn default() -> Self {
+        Self {
+            previous: JoypadState::empty(),
+            current: JoypadState::empty(),
+            polled: false,
+        }
+    }
+}
+
+impl JoypadTransitions {
+    /// Records the state of the current frame.
+    pub fn update(&mut self, state: JoypadState) {
+        self.previous = self.current;
+        self.current = state;
+    }
+
+    /// The buttons held down in the current frame.
+    pub fn current(&self) -> JoypadState {
+        self.current
+    }
+
+    /// The buttons held down in the previous frame.
+    pub fn previous(&self) -> JoypadState {
+        self.previous
+    }
+
+    /// The buttons that are held down now but weren’t in the previous frame.
+    pub fn pressed(&self) -> JoypadState {
+        self.current & !self.previous
+    }
+
+    /// The buttons that were held down in the previous frame but aren’t anymore.
+    pub fn released(&self) -> JoypadState {
+        self.previous & !self.current
+    }
+
+    /// Returns [`true`] if all of the given `buttons` have been pressed in this frame.
+    pub fn just_pressed(&self, buttons: JoypadState) -> bool {
+        self.pressed().contains(buttons)
+    }
+
+    /// Returns [`true`] if all of the given `buttons` have been released in this frame.
+    pub fn just_released(&self, buttons: JoypadState) -> bool {
+        self.released().contains(buttons)
+    }
+}
+
+bitflags::bitflags! {
+    /// The buttons of a [`RETRO_DEVICE_LIGHTGUN`].
+    #[derive(Default)]
+    pub struct LightgunButtons: u16 {
+        const TRIGGER = 1 << 0;
+        const RELOAD = 1 << 1;
+        const AUX_A = 1 << 2;
+        const AUX_B = 1 << 3;
+        const AUX_C = 1 << 4;
+        const START = 1 << 5;
+        const SELECT = 1 << 6;
+        const DPAD_UP = 1 << 7;
+        const DPAD_DOWN = 1 << 8;
+        const DPAD_LEFT = 1 << 9;
+        const DPAD_RIGHT = 1 << 10;
+    }
+}
+
+impl LightgunButtons {
+    /// The device ids of the buttons, in the order of their bits.
+    pub(crate) const IDS: [u32; 11] = [
+        RETRO_DEVICE_ID_LIGHTGUN_TRIGGER,
+        RETRO_DEVICE_ID_LIGHTGUN_RELOAD,
+        RETRO_DEVICE_ID_LIGHTGUN_AUX_A,
+        RETRO_DEVICE_ID_LIGHTGUN_AUX_B,
+        RETRO_DEVICE_ID_LIGHTGUN_AUX_C,
+        RETRO_DEVICE_ID_LIGHTGUN_START,
+        RETRO_DEVICE_ID_LIGHTGUN_SELECT,
+        RETRO_DEVICE_ID_LIGHTGUN_DPAD_UP,
+        RETRO_DEVICE_ID_LIGHTGUN_DPAD_DOWN,
+        RETRO_DEVICE_ID_LIGHTGUN_DPAD_LEFT,
+        RETRO_DEVICE_ID_LIGHTGUN_DPAD_RIGHT,
+    ];
+}
+
+/// The state of a lightgun in one frame, see [`RunContext::get_lightgun_state`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LightgunState {
+    /// The horizontal position in framebuffer pixels.
+    pub x: u32,
+
+    /// The vertical position in framebuffer pixels.
+    pub y: u32,
+
+    /// Whether the lightgun points outside of the screen, [`LightgunState::x`]
+    /// and [`LightgunState::y`] are clamped to the screen edges in that case.
+    pub offscreen: bool,
+
+    pub buttons: LightgunButtons,
+}
+
+impl LightgunState {
+    /// Maps a screen-space coordinate in `[-0x7fff, 0x7fff]` to `[0, size)`.
+    pub fn scale_coordinate(value: i16, size: u32) -> u32 {
+        if size == 0 {
+            return 0;
+        }
+
+        let value = value.clamp(-0x7fff, 0x7fff) as i64 + 0x7fff;
+        ((value * size as i64) / 0xffff) as u32
+    }
+}
+
+/// Tracks the state of a lightgun across frames to detect shots and reloads.
+///
+/// Many games reload when the trigger gets pulled while pointing offscreen,
+/// so both that and the dedicated reload button count as a reload.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lightgun {
+    previous: LightgunState,
+    current: LightgunState,
+}
+
+impl Lightgun {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the state of the current frame.
+    pub fn update(&mut self, state: LightgunState) {
+        self.previous = self.current;
+        self.current = state;
+    }
+
+    pub fn current(&self) -> &LightgunState {
+        &self.current
+    }
+
+    pub fn previous(&self) -> &LightgunState {
+        &self.previous
+    }
+
+    /// The buttons that are held down now but weren’t in the previous frame.
+    pub fn pressed(&self) -> LightgunButtons {
+        self.current.buttons & !self.previous.buttons
+    }
+
+    /// The buttons that were held down in the previous frame but aren’t anymore.
+    pub fn released(&self) -> LightgunButtons {
+        self.previous.buttons & !self.current.buttons
+    }
+
+    /// Returns [`true`] if the trigger has been pulled while pointing at the screen.
+    pub fn just_fired(&self) -> bool {
+        self.pressed().contains(LightgunButtons::TRIGGER) && !self.current.offscreen
+    }
+
+    /// Returns [`true`] if the reload button has been pressed
+    /// or the trigger has been pulled while pointing offscreen.
+    pub fn just_reloaded(&self) -> bool {
+        let pressed = self.pressed();
+
+        pressed.contains(LightgunButtons::RELOAD)
+            || (pressed.contains(LightgunButtons::TRIGGER) && self.current.offscreen)
+    }
+}
+
+#[test]
+fn lightgun() {
+    assert_eq!(LightgunState::scale_coordinate(-0x8000, 320), 0);
+    assert_eq!(LightgunState::scale_coordinate(0, 320), 159);
+    assert_eq!(LightgunState::scale_coordinate(0x7fff, 320), 319);
+    assert_eq!(LightgunState::scale_coordinate(0x7fff, 0), 0);
+
+    let mut lightgun = Lightgun::new();
+    let trigger = LightgunState {
+        buttons: LightgunButtons::TRIGGER,
+        ..LightgunState::default()
+    };
+
+    lightgun.update(trigger);
+    assert!(lightgun.just_fired());
+    assert!(!lightgun.just_reloaded());
+
+    lightgun.update(trigger);
+    assert!(!lightgun.just_fired());
+
+    lightgun.update(LightgunState::default());
+    assert_eq!(lightgun.released(), LightgunButtons::TRIGGER);
+
+    lightgun.update(LightgunState {
+        offscreen: true,
+        ..trigger
+    });
+    assert!(!lightgun.just_fired());
+    assert!(lightgun.just_reloaded());
+}
+
+#[test]
+fn joypad_transitions() {
+    let mut transitions = JoypadTransitions::default();
+
+    transitions.update(JoypadState::START);
+    assert!(transitions.just_pressed(JoypadState::START));
+
+    transitions.update(JoypadState::START | JoypadState::SELECT);
+    assert!(!transitions.just_pressed(JoypadState::START));
+    assert!(transitions.just_pressed(JoypadState::SELECT));
+
+    transitions.update(JoypadState::SELECT);
+    assert!(transitions.just_released(JoypadState::START));
+    assert_eq!(transitions.released(), JoypadState::START);
+}
+
+#[test]
+fn joypad_buttons() {
+    use sys::input::{Device, JoypadButton};
+
+    assert_eq!(JoypadState::from(JoypadButton::B), JoypadState::B);
+    assert_eq!(JoypadState::from(JoypadButton::R3), JoypadState::R3);
+
+    for &button in JoypadButton::ALL {
+        assert_eq!(JoypadButton::try_from(u32::from(button)), Ok(button));
+    }
+
+    assert_eq!(
+        JoypadButton::try_from(RETRO_DEVICE_ID_JOYPAD_MASK),
+        Err(RETRO_DEVICE_ID_JOYPAD_MASK)
+    );
+    assert_eq!(
+        Device::from_subclass(RETRO_DEVICE_SUBCLASS!(RETRO_DEVICE_ANALOG, 1)),
+        Some(Device::Analog)
+    );
+}
+
+/// Describes what changed between two [`retro_system_av_info`]s.
+///
+/// Returned by [`RunContext::try_set_system_av_info`] and [`RunContext::try_set_game_geometry`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AvInfoDiff {
+    pub base_size: bool,
+    pub max_size: bool,
+    pub aspect_ratio: bool,
+    pub fps: bool,
+    pub sample_rate: bool,
+}
+
+impl AvInfoDiff {
+    pub fn new(old: &retro_system_av_info, new: &retro_system_av_info) -> Self {
+        Self {
+            base_size: old.geometry.base_width != new.geometry.base_width
+                || old.geometry.base_height != new.geometry.base_height,
+            max_size: old.geometry.max_width != new.geometry.max_width
+                || old.geometry.max_height != new.geometry.max_height,
+            aspect_ratio: old.geometry.aspect_ratio.to_bits()
+                != new.geometry.aspect_ratio.to_bits(),
+            fps: old.timing.fps.to_bits() != new.timing.fps.to_bits(),
+            sample_rate: old.timing.sample_rate.to_bits() != new.timing.sample_rate.to_bits(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether the change makes the frontend reinitialize its video or audio driver.
+    pub fn requires_reinit(&self) -> bool {
+        self.max_size || self.fps || self.sample_rate
+    }
+}
+
+/// The state of the camera, see [`GenericContext::camera_state`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CameraState {
+    /// The frontend provides no camera interface,
+    /// or [`LoadGameContext::enable_camera_interface`] has not been called.
+    #[default]
+    Unavailable,
+
+    /// The frontend provides a camera interface but has not initialized its camera driver yet.
+    Uninitialized,
+
+    /// The camera driver has been initialized, the camera can be started.
+    Initialized,
+
+    /// The camera has been started and the frontend sends frames.
+    Running,
+
+    /// The frontend failed to start the camera,
+    /// usually because the user denied the camera permission.
+    Denied,
+
+    /// The frontend shut down its camera driver.
+    Deinitialized,
+}
+
+/// Errors returned by [`RunContext::camera_start`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraError {
+    /// The frontend provides no camera interface.
+    Unavailable,
+
+    /// The frontend failed to start the camera before initializing its camera driver,
+    /// or after shutting it down.
+    NotInitialized,
+
+    /// The frontend failed to start an initialized camera,
+    /// usually because the user denied the camera permission.
+    Denied,
+}
+
+impl std::fmt::Display for CameraError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unavailable => write!(f, "The frontend provides no camera interface"),
+            Self::NotInitialized => write!(f, "The camera driver has not been initialized"),
+            Self::Denied => write!(
+                f,
+                "The frontend failed to start the camera, was the permission denied?"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CameraError {}
+
+/// Errors returned by [`RunContext::try_set_system_av_info`], [`RunContext::try_set_game_geometry`]
+/// and [`AvInfo::build`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvInfoError {
+    /// The base size exceeds the maximum size,
+    /// which is the one declared at load time for [`RunContext::try_set_game_geometry`].
+    ExceedsMaxSize {
+        width: u32,
+        height: u32,
+        max_width: u32,
+        max_height: u32,
+    },
+
+    /// The frontend did not accept the change.
+    Rejected,
+
+    /// The base or maximum width or height is zero.
+    ZeroSize,
+
+    /// The frame rate is not a positive number below 1000.
+    InvalidFps,
+
+    /// The sample rate is negative or not a number.
+    InvalidSampleRate,
+
+    /// The aspect ratio is negative or not a number.
+    InvalidAspectRatio,
+}
+
+impl std::fmt::Display for AvInfoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ExceedsMaxSize {
+                width,
+                height,
+                max_width,
+                max_height,
+            } => write!(
+                f,
+                "Geometry {width}x{height} exceeds the maximum size of {max_width}x{max_height}"
+            ),
+            Self::Rejected => write!(f, "The frontend rejected the new AV info"),
+            Self::ZeroSize => write!(f, "The geometry has a width or height of zero"),
+            Self::InvalidFps => write!(f, "The frame rate is out of range"),
+            Self::InvalidSampleRate => write!(f, "The sample rate is out of range"),
+            Self::InvalidAspectRatio => write!(f, "The aspect ratio is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for AvInfoError {}
+
+pub(crate) fn check_geometry(
+    geometry: &retro_game_geometry,
+    max_width: u32,
+    max_height: u32,
+) -> Result<(), AvInfoError> {
+    if geometry.base_width > max_width || geometry.base_height > max_height {
+        return Err(AvInfoError::ExceedsMaxSize {
+            width: geometry.base_width,
+            height: geometry.base_height,
+            max_width,
+            max_height,
+        });
+    }
+
+    Ok(())
+}
+
+/// Builds a validated [`retro_system_av_info`], e.g. for [`Core::on_get_av_info`]:
+///
+/// ```rust
+/// # use rust_libretro::types::AvInfo;
+/// let av_info = AvInfo::ntsc(256, 224, 48000.0)
+///     .with_max(512, 448)
+///     .with_aspect_ratio(4.0 / 3.0)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(av_info.geometry.max_width, 512);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct AvInfo(retro_system_av_info);
+
+impl AvInfo {
+    /// Frames of `width` × `height` pixels, which is also the maximum size.
+    /// The aspect ratio defaults to square pixels.
+    pub fn progressive(width: u32, height: u32, fps: f64, sample_rate: f64) -> Self {
+        Self(retro_system_av_info {
+            geometry: retro_game_geometry {
+                base_width: width,
+                base_height: height,
+                max_width: width,
+                max_height: height,
+                aspect_ratio: 0.0,
+            },
+            timing: retro_system_timing { fps, sample_rate },
+        })
+    }
+
+    /// Runs at the NTSC frame rate of 60000/1001 fps.
+    pub fn ntsc(width: u32, height: u32, sample_rate: f64) -> Self {
+        Self::progressive(width, height, 60000.0 / 1001.0, sample_rate)
+    }
+
+    /// Runs at the PAL frame rate of 50 fps.
+    pub fn pal(width: u32, height: u32, sample_rate: f64) -> Self {
+        Self::progressive(width, height, 50.0, sample_rate)
+    }
+
+    /// Sets the maximum size, for cores that change their geometry while running.
+    pub fn with_max(mut self, max_width: u32, max_height: u32) -> Self {
+        self.0.geometry.max_width = max_width;
+        self.0.geometry.max_height = max_height;
+        self
+    }
+
+    /// Sets the display aspect ratio, `0.0` for square pixels.
+    pub fn with_aspect_ratio(mut self, aspect_ratio: f32) -> Self {
+        self.0.geometry.aspect_ratio = aspect_ratio;
+        self
+    }
+
+    /// Checks for zero sizes, a base size beyond the maximum size and implausible rates.
+    pub fn build(self) -> Result<retro_system_av_info, AvInfoError> {
+        let retro_system_av_info { geometry, timing } = self.0;
+
+        if [
+            geometry.base_width,
+            geometry.base_height,
+            geometry.max_width,
+            geometry.max_height,
+        ]
+        .contains(&0)
+        {
+            return Err(AvInfoError::ZeroSize);
+        }
+
+        check_geometry(&geometry, geometry.max_width, geometry.max_height)?;
+
+        if !(timing.fps > 0.0 && timing.fps < 1000.0) {
+            return Err(AvInfoError::InvalidFps);
+        }
+
+        if !(timing.sample_rate >= 0.0 && timing.sample_rate.is_finite()) {
+            return Err(AvInfoError::InvalidSampleRate);
+        }
+
+        if !(geometry.aspect_ratio >= 0.0 && geometry.aspect_ratio.is_finite()) {
+            return Err(AvInfoError::InvalidAspectRatio);
+        }
+
+        Ok(self.0)
+    }
+}
+
+#[test]
+fn av_info_builder() {
+    let av_info = AvInfo::progressive(320, 240, 60.0, 44100.0)
+        .build()
+        .unwrap();
+    assert_eq!(
+        (av_info.geometry.max_width, av_info.geometry.max_height),
+        (320, 240)
+    );
+    assert_eq!(av_info.timing.sample_rate, 44100.0);
+
+    assert_eq!(AvInfo::pal(320, 240, 0.0).build().unwrap().timing.fps, 50.0);
+
+    let build = |av_info: AvInfo| av_info.build().unwrap_err();
+    assert_eq!(
+        build(AvInfo::ntsc(320, 240, 48000.0).with_max(0, 0)),
+        AvInfoError::ZeroSize
+    );
+    assert!(matches!(
+        build(AvInfo::ntsc(640, 480, 48000.0).with_max(320, 240)),
+        AvInfoError::ExceedsMaxSize { .. }
+    ));
+    assert_eq!(
+        build(AvInfo::progressive(320, 240, 0.0, 48000.0)),
+        AvInfoError::InvalidFps
+    );
+    assert_eq!(
+        build(AvInfo::progressive(320, 240, 60.0, f64::NAN)),
+        AvInfoError::InvalidSampleRate
+    );
+    assert_eq!(
+        build(AvInfo::pal(320, 240, 48000.0).with_aspect_ratio(-1.0)),
+        AvInfoError::InvalidAspectRatio
+    );
+}
+
+#[test]
+fn av_info_diff() {
+    let old = retro_system_av_info {
+        geometry: retro_game_geometry {
+            base_width: 320,
+            base_height: 240,
+            max_width: 640,
+            max_height: 480,
+            aspect_ratio: 4.0 / 3.0,
+        },
+        timing: retro_system_timing {
+            fps: 60.0,
+            sample_rate: 48000.0,
+        },
+    };
+
+    let mut new = old;
+    assert!(AvInfoDiff::new(&old, &new).is_empty());
+
+    new.geometry.base_width = 640;
+    let diff = AvInfoDiff::new(&old, &new);
+    assert!(diff.base_size && !diff.requires_reinit());
+
+    new.timing.sample_rate = 44100.0;
+    assert!(AvInfoDiff::new(&old, &new).requires_reinit());
+
+    new.geometry.base_height = 960;
+    assert!(check_geometry(&new.geometry, 640, 480).is_err());
+}
+
+/// Selects the interface `#[derive(CoreOptions)]` registers the core options with,
+/// see [`SetEnvironmentContext::set_options_compat_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OptionsCompat {
+    /// Uses the newest interface the frontend reports via
+    /// [`GenericContext::get_core_options_version`].
+    ///
+    /// Frontends that accept options v2 but do not support categories show the
+    /// options without them. Quirks of frontends can be handled by choosing a
+    /// mode based on [`GenericContext::get_frontend_info`].
+    #[default]
+    Auto,
+
+    /// Never uses options v2, even if the frontend supports it.
+    ForceV1,
+
+    /// Only uses the legacy [`SetEnvironmentContext::set_variables`] interface.
+    ForceVariables,
+}
+
+/// The value of an enabled toggle declared with `#[options(bool …)]`.
+pub const OPTION_ENABLED: &str = "enabled";
+
+/// The value of a disabled toggle declared with `#[options(bool …)]`.
+pub const OPTION_DISABLED: &str = "disabled";
+
+/// Parses the value of a toggle, see [`GenericContext::get_bool_variable`].
+///
+/// Besides [`OPTION_ENABLED`] and [`OPTION_DISABLED`] the values
+/// `true`/`false`, `on`/`off` and `1`/`0` are understood, ignoring case.
+pub fn parse_bool_option(value: &str) -> Option<bool> {
+    const ENABLED: [&str; 4] = [OPTION_ENABLED, "true", "on", "1"];
+    const DISABLED: [&str; 4] = [OPTION_DISABLED, "false", "off", "0"];
+
+    if ENABLED
+        .iter()
+        .any(|enabled| value.eq_ignore_ascii_case(enabled))
+    {
+        Some(true)
+    } else if DISABLED
+        .iter()
+        .any(|disabled| value.eq_ignore_ascii_case(disabled))
+    {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn bool_option_values() {
+    assert_eq!(parse_bool_option(OPTION_ENABLED), Some(true));
+    assert_eq!(parse_bool_option("True"), Some(true));
+    assert_eq!(parse_bool_option("1"), Some(true));
+    assert_eq!(parse_bool_option(OPTION_DISABLED), Some(false));
+    assert_eq!(parse_bool_option("OFF"), Some(false));
+    assert_eq!(parse_bool_option("unstable"), None);
+}
+
+/// A value of a [`CoreOptionDefinition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoreOptionValueDefinition {
+    pub value: &'static str,
+    pub label: Option<&'static str>,
+}
+
+/// A core option as declared with `#[derive(CoreOptions)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoreOptionDefinition {
+    pub key: &'static str,
+    pub desc: &'static str,
+    pub desc_categorized: Option<&'static str>,
+    pub info: &'static str,
+    pub info_categorized: Option<&'static str>,
+    pub category_key: Option<&'static str>,
+    pub values: &'static [CoreOptionValueDefinition],
+    pub default_value: Option<&'static str>,
+}
+
+/// Shows a core option only while a device is connected, declared with
+/// `#[visible_when("key", device = "RETRO_DEVICE_ANALOG")]` on a `#[derive(CoreOptions)]`.
+///
+/// The visibility gets updated whenever the frontend changes the device of a port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionVisibilityRule {
+    pub key: &'static str,
+
+    /// The device, either a base type matching all of its subclasses or a subclass.
+    pub device: u32,
+
+    /// The port the device has to be connected to, [`None`] for any port.
+    pub port: Option<u32>,
+}
+
+impl OptionVisibilityRule {
+    /// Whether the rule matches the devices by port, unset ports count as [`RETRO_DEVICE_JOYPAD`].
+    pub fn matches(&self, port_devices: &[u32]) -> bool {
+        let matches = |device: u32| {
+            if self.device > RETRO_DEVICE_MASK {
+                device == self.device
+            } else {
+                device & RETRO_DEVICE_MASK == self.device
+            }
+        };
+
+        match self.port {
+            Some(port) => matches(
+                port_devices
+                    .get(port as usize)
+                    .copied()
+                    .unwrap_or(RETRO_DEVICE_JOYPAD),
+            ),
+            None if port_devices.is_empty() => matches(RETRO_DEVICE_JOYPAD),
+            None => port_devices.iter().any(|&device| matches(device)),
+        }
+    }
+}
+
+#[test]
+fn option_visibility_rule() {
+    let analog = OptionVisibilityRule {
+        key: "analog",
+        device: RETRO_DEVICE_ANALOG,
+        port: None,
+    };
+    let dual_shock = RETRO_DEVICE_SUBCLASS!(RETRO_DEVICE_ANALOG, 1);
+
+    assert!(!analog.matches(&[]));
+    assert!(analog.matches(&[RETRO_DEVICE_JOYPAD, dual_shock]));
+
+    let second_port = OptionVisibilityRule {
+        port: Some(1),
+        ..analog
+    };
+    assert!(!second_port.matches(&[dual_shock]));
+
+    let subclass = OptionVisibilityRule {
+        device: dual_shock,
+        ..analog
+    };
+    assert!(subclass.matches(&[dual_shock]));
+    assert!(!subclass.matches(&[RETRO_DEVICE_ANALOG]));
+
+    let joypad = OptionVisibilityRule {
+        device: RETRO_DEVICE_JOYPAD,
+        port: Some(3),
+        ..analog
+    };
+    assert!(joypad.matches(&[RETRO_DEVICE_ANALOG]));
+}
+
+/// A core option category as declared with `#[derive(CoreOptions)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoreOptionCategoryDefinition {
+    pub key: &'static str,
+    pub desc: &'static str,
+    pub info: &'static str,
+}
+
+/// All core options and categories of a core, see [`CoreOptions::option_definitions`](crate::core::CoreOptions::option_definitions).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoreOptionDefinitions {
+    pub categories: &'static [CoreOptionCategoryDefinition],
+    pub definitions: &'static [CoreOptionDefinition],
+}
+
+impl CoreOptionDefinitions {
+    /// Serializes the definitions into the layout of `retro_core_options_v2`,
+    /// which is what `libretro_core_options.h` files declare.
+    ///
+    /// Missing optional strings are written as `null`.
+    pub fn to_json(&self) -> String {
+        fn string(value: &str) -> String {
+            let mut out = String::with_capacity(value.len() + 2);
+            out.push('"');
+
+            for c in value.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                    c => out.push(c),
+                }
+            }
+
+            out.push('"');
+            out
+        }
+
+        fn optional(value: Option<&str>) -> String {
+            value.map_or_else(|| "null".to_owned(), string)
+        }
+
+        let categories = self
+            .categories
+            .iter()
+            .map(|category| {
+                format!(
+                    "{{\"key\":{},\"desc\":{},\"info\":{}}}",
+                    string(category.key),
+                    string(category.desc),
+                    string(category.info)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let definitions = self
+            .definitions
+            .iter()
+            .map(|option| {
+                let values = option
+                    .values
+                    .iter()
+                    .map(|value| {
+                        format!(
+                            "{{\"value\":{},\"label\":{}}}",
+                            string(value.value),
+                            optional(value.label)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+
+                format!(
+                    "{{\"key\":{},\"desc\":{},\"desc_categorized\":{},\"info\":{},\"info_categorized\":{},\"category_key\":{},\"values\":[{}],\"default_value\":{}}}",
+                    string(option.key),
+                    string(option.desc),
+                    optional(option.desc_categorized),
+                    string(option.info),
+                    optional(option.info_categorized),
+                    optional(option.category_key),
+                    values,
+                    optional(option.default_value)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            "{{\"categories\":[{}],\"definitions\":[{}]}}",
+            categories, definitions
+        )
+    }
+}
+
+#[test]
+fn core_option_definitions_json() {
+    let definitions = CoreOptionDefinitions {
+        categories: &[CoreOptionCategoryDefinition {
+            key: "video",
+            desc: "Video",
+            info: "Options affecting the \"video\" output.",
+        }],
+        definitions: &[CoreOptionDefinition {
+            key: "scale",
+            desc: "Scale",
+            desc_categorized: None,
+            info: "Upscaling factor",
+            info_categorized: None,
+            category_key: Some("video"),
+            values: &[
+                CoreOptionValueDefinition {
+                    value: "1",
+                    label: None,
+                },
+                CoreOptionValueDefinition {
+                    value: "2",
+                    label: Some("2x"),
+                },
+            ],
+            default_value: Some("1"),
+        }],
+    };
+
+    assert_eq!(
+        definitions.to_json(),
+        concat!(
+            r#"{"categories":[{"key":"video","desc":"Video","info":"Options affecting the \"video\" output."}],"#,
+            r#""definitions":[{"key":"scale","desc":"Scale","desc_categorized":null,"info":"Upscaling factor","#,
+            r#""info_categorized":null,"category_key":"video","values":[{"value":"1","label":null},"#,
+            r#"{"value":"2","label":"2x"}],"default_value":"1"}]}"#
+        )
+    );
+}
+
 /// Data structures used by experimental libretro environment function calls
 #[proc::unstable(feature = "env-commands")]
 pub mod unstable {
@@ -366,37 +1875,130 @@ pub mod unstable {
         }
     }
 
-    bitflags::bitflags! {
-        /// Joypad button mask
-        pub struct JoypadState: u16 {
-            const B      = 0b0000_0000_0000_0001;
-            const Y      = 0b0000_0000_0000_0010;
-            const SELECT = 0b0000_0000_0000_0100;
-            const START  = 0b0000_0000_0000_1000;
+    #[derive(Debug, Default)]
+    pub struct VfsInterfaceInfo {
+        pub(crate) supported_version: u32,
+        pub(crate) interface: Option<retro_vfs_interface>,
+    }
 
-            const UP     = 0b0000_0000_0001_0000;
-            const DOWN   = 0b0000_0000_0010_0000;
-            const LEFT   = 0b0000_0000_0100_0000;
-            const RIGHT  = 0b0000_0000_1000_0000;
+    impl VfsInterfaceInfo {
+        /// Looks up a function of the interface, which the frontend only has to
+        /// provide if it supports at least `version`.
+        pub(crate) fn function<F>(
+            &self,
+            function: &'static str,
+            version: u32,
+            get: impl FnOnce(&retro_vfs_interface) -> Option<F>,
+        ) -> Result<F, VfsError> {
+            let interface = self.interface.as_ref().ok_or(VfsError::NotEnabled)?;
 
-            const A      = 0b0000_0001_0000_0000;
-            const X      = 0b0000_0010_0000_0000;
-            const L      = 0b0000_0100_0000_0000;
-            const R      = 0b0000_1000_0000_0000;
+            let unsupported = VfsError::Unsupported {
+                function,
+                required_version: version,
+                supported_version: self.supported_version,
+            };
 
-            const L2     = 0b0001_0000_0000_0000;
-            const R2     = 0b0010_0000_0000_0000;
-            const L3     = 0b0100_0000_0000_0000;
-            const R3     = 0b1000_0000_0000_0000;
+            if self.supported_version < version {
+                return Err(unsupported);
+            }
+
+            get(interface).ok_or(unsupported)
+        }
+
+        pub(crate) fn capabilities(&self) -> VfsCapabilities {
+            let interface = match self.interface.as_ref() {
+                Some(interface) => interface,
+                None => return VfsCapabilities::default(),
+            };
+            let version = self.supported_version;
+
+            VfsCapabilities {
+                version,
+                files: interface.open.is_some()
+                    && interface.close.is_some()
+                    && interface.read.is_some()
+                    && interface.write.is_some()
+                    && interface.seek.is_some(),
+                truncate: version >= 2 && interface.truncate.is_some(),
+                stat: version >= 3 && interface.stat.is_some(),
+                mkdir: version >= 3 && interface.mkdir.is_some(),
+                directories: version >= 3
+                    && interface.opendir.is_some()
+                    && interface.readdir.is_some()
+                    && interface.dirent_get_name.is_some()
+                    && interface.dirent_is_dir.is_some()
+                    && interface.closedir.is_some(),
+            }
         }
     }
 
-    #[derive(Debug, Default)]
-    pub struct VfsInterfaceInfo {
-        pub(crate) supported_version: u32,
-        pub(crate) interface: Option<retro_vfs_interface>,
+    /// The parts of the VFS interface provided by the frontend,
+    /// see [`GenericContext::vfs_capabilities`](crate::contexts::GenericContext::vfs_capabilities).
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct VfsCapabilities {
+        /// The interface version, `0` if the interface has not been enabled.
+        pub version: u32,
+
+        /// Opening, closing, reading, writing and seeking in files.
+        pub files: bool,
+
+        /// `vfs_truncate`, since version 2.
+        pub truncate: bool,
+
+        /// `vfs_stat`, since version 3.
+        pub stat: bool,
+
+        /// `vfs_mkdir`, since version 3.
+        pub mkdir: bool,
+
+        /// `vfs_opendir`, `vfs_readdir`, `vfs_dirent_*` and `vfs_closedir`, since version 3.
+        pub directories: bool,
     }
 
+    /// Returned by the `vfs_*` functions of [`GenericContext`](crate::contexts::GenericContext).
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum VfsError {
+        /// The VFS interface has not been enabled.
+        NotEnabled,
+
+        /// The frontend does not provide `function`, e.g. because it implements an older version.
+        Unsupported {
+            function: &'static str,
+            required_version: u32,
+            supported_version: u32,
+        },
+
+        /// The path contains a NUL byte.
+        InvalidPath,
+
+        /// The directory exists already.
+        Exists,
+
+        /// The frontend reported an error.
+        Failed { function: &'static str },
+    }
+
+    impl std::fmt::Display for VfsError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::NotEnabled => write!(f, "The VFS interface has not been enabled"),
+                Self::Unsupported {
+                    function,
+                    required_version,
+                    supported_version,
+                } => write!(
+                    f,
+                    "VFS function {function} is not supported (requires version {required_version}, the frontend supports version {supported_version})"
+                ),
+                Self::InvalidPath => write!(f, "VFS paths must not contain NUL bytes"),
+                Self::Exists => write!(f, "The directory exists already"),
+                Self::Failed { function } => write!(f, "VFS function {function} failed"),
+            }
+        }
+    }
+
+    impl std::error::Error for VfsError {}
+
     bitflags::bitflags! {
         pub struct VfsFileOpenFlags: u32 {
             const READ = RETRO_VFS_FILE_ACCESS_READ;
@@ -475,3 +2077,20 @@ pub mod unstable {
     }
 }
 pub use unstable::*;
+
+#[test]
+fn static_image() {
+    const IMAGE: StaticImage = StaticImage {
+        width: 2,
+        height: 2,
+        pixels: &[0xFF_11_22_33, 0x00_FF_FF_FF, 0x80_FF_FF_FF, 0xFF_44_55_66],
+    };
+    assert_eq!(IMAGE.pitch(), 8);
+    assert_eq!(IMAGE.get(1, 1), Some(0xFF_44_55_66));
+    assert_eq!(IMAGE.get(2, 0), None);
+    assert_eq!(IMAGE.as_bytes().len(), 16);
+
+    let mut dst = [0u32; 3 * 2];
+    IMAGE.blit(&mut dst, 3, 1, -1);
+    assert_eq!(dst, [0, 0x80_80_80, 0x44_55_66, 0, 0, 0]);
+}
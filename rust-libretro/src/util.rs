@@ -1,6 +1,20 @@
 //! Utility functions
 use super::*;
 
+pub mod audio;
+pub mod convert;
+pub mod hash;
+mod instant;
+pub mod png;
+mod rng;
+pub mod rotate;
+pub mod scale;
+pub mod spsc;
+
+pub(crate) use instant::set_time_source;
+pub use instant::RetroInstant;
+pub use rng::DeterministicRng;
+
 /// Tries to convert a pointer to a [`CString`] into a Rust [`str`]
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub fn get_str_from_pointer<'a>(ptr: *const c_char) -> Option<&'a str> {
@@ -45,3 +59,53 @@ pub fn get_path_from_pointer<'a>(ptr: *const c_char) -> Option<&'a Path> {
 pub fn get_path_buf_from_pointer(ptr: *mut c_char) -> Option<PathBuf> {
     get_str_from_pointer(ptr).map(PathBuf::from)
 }
+
+/// Picks the frame rate out of `candidates` that shows the least judder on a display
+/// running at `refresh_rate` Hz, e.g. 59.94 instead of 60 on a 59.94 Hz display.
+///
+/// A frame rate fits best if the refresh rate is a whole multiple of it, or the other
+/// way around, so 30 fps is as good as 60 fps at 120 Hz. The earlier candidate wins ties.
+/// Without a valid refresh rate the first valid candidate gets returned.
+///
+/// See [`GetAvInfoContext::choose_fps`](crate::contexts::GetAvInfoContext::choose_fps).
+pub fn choose_fps(refresh_rate: f64, candidates: &[f64]) -> Option<f64> {
+    let mut candidates = candidates
+        .iter()
+        .copied()
+        .filter(|fps| fps.is_finite() && *fps > 0.0);
+
+    if !(refresh_rate.is_finite() && refresh_rate > 0.0) {
+        return candidates.next();
+    }
+
+    let judder = |fps: f64| {
+        let ratio = refresh_rate.max(fps) / refresh_rate.min(fps);
+        let multiple = ratio.round();
+
+        (ratio - multiple).abs() / multiple
+    };
+
+    candidates
+        .map(|fps| (fps, judder(fps)))
+        .fold(None, |best: Option<(f64, f64)>, (fps, judder)| match best {
+            Some((_, best_judder)) if best_judder <= judder => best,
+            _ => Some((fps, judder)),
+        })
+        .map(|(fps, _)| fps)
+}
+
+#[test]
+fn choose_fps_candidates() {
+    let ntsc = 60000.0 / 1001.0;
+
+    assert_eq!(choose_fps(60.0, &[ntsc, 60.0]), Some(60.0));
+    assert_eq!(choose_fps(ntsc, &[60.0, ntsc]), Some(ntsc));
+    assert_eq!(choose_fps(120.0, &[ntsc, 60.0]), Some(60.0));
+    assert_eq!(choose_fps(144.0, &[60.0, 72.0]), Some(72.0));
+    assert_eq!(choose_fps(60.0, &[30.0, 60.0]), Some(30.0));
+    assert_eq!(choose_fps(50.0, &[ntsc, 50.0]), Some(50.0));
+
+    assert_eq!(choose_fps(0.0, &[ntsc, 60.0]), Some(ntsc));
+    assert_eq!(choose_fps(60.0, &[f64::NAN, 0.0, 50.0]), Some(50.0));
+    assert_eq!(choose_fps(60.0, &[]), None);
+}
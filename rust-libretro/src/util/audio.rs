@@ -0,0 +1,79 @@
+//! Conversions of interleaved 16 bit audio to the stereo frames libretro expects.
+//!
+//! All functions convert as many frames as fit into both `src` and `dst`
+//! and return the number of converted frames.
+//! [`AudioContext::batch_mono`] and [`AudioContext::batch_quad`] convert and render in one go.
+//!
+//! [`AudioContext::batch_mono`]: crate::contexts::AudioContext::batch_mono
+//! [`AudioContext::batch_quad`]: crate::contexts::AudioContext::batch_quad
+
+/// Duplicates every mono sample into a stereo frame.
+pub fn mono_to_stereo(src: &[i16], dst: &mut [i16]) -> usize {
+    let frames = src.len().min(dst.len() / 2);
+
+    for (sample, frame) in src.iter().zip(dst.chunks_exact_mut(2)) {
+        frame[0] = *sample;
+        frame[1] = *sample;
+    }
+
+    frames
+}
+
+/// Mixes quadraphonic (4.0) audio down to stereo.
+///
+/// The samples of a 4.0 frame are ordered front left, front right, rear left, rear right.
+/// Each output channel is the sum of its front and rear channel, scaled by the gains
+/// and clamped to the range of [`i16`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuadDownmix {
+    pub front_gain: f32,
+    pub rear_gain: f32,
+}
+
+impl Default for QuadDownmix {
+    /// Mixes the rear channels in at −3 dB.
+    fn default() -> Self {
+        Self {
+            front_gain: 1.0,
+            rear_gain: std::f32::consts::FRAC_1_SQRT_2,
+        }
+    }
+}
+
+impl QuadDownmix {
+    pub fn downmix(&self, src: &[i16], dst: &mut [i16]) -> usize {
+        let frames = (src.len() / 4).min(dst.len() / 2);
+
+        let mix = |front: i16, rear: i16| {
+            let sample = front as f32 * self.front_gain + rear as f32 * self.rear_gain;
+            sample.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+        };
+
+        for (quad, stereo) in src.chunks_exact(4).zip(dst.chunks_exact_mut(2)) {
+            stereo[0] = mix(quad[0], quad[2]);
+            stereo[1] = mix(quad[1], quad[3]);
+        }
+
+        frames
+    }
+}
+
+#[test]
+fn channel_conversions() {
+    let mut stereo = [0; 6];
+    assert_eq!(mono_to_stereo(&[1, -2, 3, 4], &mut stereo), 3);
+    assert_eq!(stereo, [1, 1, -2, -2, 3, 3]);
+
+    let downmix = QuadDownmix {
+        front_gain: 1.0,
+        rear_gain: 0.5,
+    };
+
+    let mut stereo = [0; 4];
+    let quad = [100, -100, 50, 20, i16::MAX, i16::MIN, i16::MAX, i16::MIN];
+    assert_eq!(downmix.downmix(&quad, &mut stereo), 2);
+    assert_eq!(stereo, [125, -90, i16::MAX, i16::MIN]);
+
+    // Incomplete frames are ignored
+    assert_eq!(downmix.downmix(&[1, 2, 3], &mut stereo), 0);
+}
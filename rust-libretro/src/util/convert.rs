@@ -0,0 +1,291 @@
+//! Conversions between the pixel formats supported by libretro.
+//!
+//! [`XRGB8888`](crate::types::PixelFormat::XRGB8888) pixels are stored as native endian [`u32`]s,
+//! [`RGB565`](crate::types::PixelFormat::RGB565) and [`XRGB1555`](crate::types::PixelFormat::XRGB1555)
+//! pixels as native endian [`u16`]s.
+//! RGBA8888 buffers, as used by most image crates, are plain bytes in `R, G, B, A` order.
+//!
+//! Converting from XRGB8888 to the 16 bit formats uses SSE2 or NEON when available.
+//!
+//! All functions convert `min(src.len(), dst.len())` pixels.
+//! Use [`as_bytes`] to pass the converted buffer to [`RunContext::draw_frame`](crate::contexts::RunContext::draw_frame).
+
+/// Pixel types that can be viewed as raw bytes.
+pub trait Pixel: private::Sealed + Copy {}
+
+impl Pixel for u16 {}
+impl Pixel for u32 {}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// Returns the native endian bytes of a pixel buffer.
+pub fn as_bytes<P: Pixel>(pixels: &[P]) -> &[u8] {
+    // SAFETY: `u16` and `u32` have no padding and every bit pattern is a valid `u8`
+    unsafe {
+        std::slice::from_raw_parts(pixels.as_ptr() as *const u8, std::mem::size_of_val(pixels))
+    }
+}
+
+/// Bit layout of a 16 bit pixel format.
+struct Layout16 {
+    r_shift: u32,
+    g_shift: u32,
+    b_shift: u32,
+    r_mask: u32,
+    g_mask: u32,
+    b_mask: u32,
+}
+
+const RGB565: Layout16 = Layout16 {
+    r_shift: 8,
+    g_shift: 5,
+    b_shift: 3,
+    r_mask: 0xF800,
+    g_mask: 0x07E0,
+    b_mask: 0x001F,
+};
+
+const XRGB1555: Layout16 = Layout16 {
+    r_shift: 9,
+    g_shift: 6,
+    b_shift: 3,
+    r_mask: 0x7C00,
+    g_mask: 0x03E0,
+    b_mask: 0x001F,
+};
+
+impl Layout16 {
+    #[inline(always)]
+    fn pack(&self, pixel: u32) -> u16 {
+        (((pixel >> self.r_shift) & self.r_mask)
+            | ((pixel >> self.g_shift) & self.g_mask)
+            | ((pixel >> self.b_shift) & self.b_mask)) as u16
+    }
+}
+
+#[inline(always)]
+fn expand5(value: u16) -> u32 {
+    let value = (value & 0x1F) as u32;
+    (value << 3) | (value >> 2)
+}
+
+#[inline(always)]
+fn expand6(value: u16) -> u32 {
+    let value = (value & 0x3F) as u32;
+    (value << 2) | (value >> 4)
+}
+
+fn pack_xrgb8888(src: &[u32], dst: &mut [u16], layout: &Layout16) {
+    let done = simd::pack_xrgb8888(src, dst, layout);
+
+    for (src, dst) in src[done..].iter().zip(&mut dst[done..]) {
+        *dst = layout.pack(*src);
+    }
+}
+
+pub fn xrgb8888_to_rgb565(src: &[u32], dst: &mut [u16]) {
+    pack_xrgb8888(src, dst, &RGB565);
+}
+
+pub fn xrgb8888_to_xrgb1555(src: &[u32], dst: &mut [u16]) {
+    pack_xrgb8888(src, dst, &XRGB1555);
+}
+
+pub fn rgb565_to_xrgb8888(src: &[u16], dst: &mut [u32]) {
+    for (src, dst) in src.iter().zip(dst) {
+        let r = expand5(src >> 11);
+        let g = expand6(src >> 5);
+        let b = expand5(*src);
+
+        *dst = (r << 16) | (g << 8) | b;
+    }
+}
+
+pub fn xrgb1555_to_xrgb8888(src: &[u16], dst: &mut [u32]) {
+    for (src, dst) in src.iter().zip(dst) {
+        let r = expand5(src >> 10);
+        let g = expand5(src >> 5);
+        let b = expand5(*src);
+
+        *dst = (r << 16) | (g << 8) | b;
+    }
+}
+
+pub fn rgb565_to_xrgb1555(src: &[u16], dst: &mut [u16]) {
+    for (src, dst) in src.iter().zip(dst) {
+        // Drops the lowest green bit
+        *dst = ((src >> 1) & 0x7FE0) | (src & 0x001F);
+    }
+}
+
+pub fn xrgb1555_to_rgb565(src: &[u16], dst: &mut [u16]) {
+    for (src, dst) in src.iter().zip(dst) {
+        // Replicates the highest green bit into the new lowest one
+        let green_low = (src >> 4) & 0x0020;
+        *dst = ((src << 1) & 0xFFC0) | green_low | (src & 0x001F);
+    }
+}
+
+/// Converts `RGBA` bytes, ignoring the alpha channel.
+pub fn rgba8888_to_xrgb8888(src: &[u8], dst: &mut [u32]) {
+    for (src, dst) in src.chunks_exact(4).zip(dst) {
+        *dst = ((src[0] as u32) << 16) | ((src[1] as u32) << 8) | src[2] as u32;
+    }
+}
+
+/// Converts to `RGBA` bytes with an opaque alpha channel.
+pub fn xrgb8888_to_rgba8888(src: &[u32], dst: &mut [u8]) {
+    for (src, dst) in src.iter().zip(dst.chunks_exact_mut(4)) {
+        dst.copy_from_slice(&[(src >> 16) as u8, (src >> 8) as u8, *src as u8, 0xFF]);
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod simd {
+    use super::Layout16;
+
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    /// Returns the number of converted pixels.
+    pub(super) fn pack_xrgb8888(src: &[u32], dst: &mut [u16], layout: &Layout16) -> usize {
+        if is_x86_feature_detected!("sse2") {
+            unsafe { pack_xrgb8888_sse2(src, dst, layout) }
+        } else {
+            0
+        }
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn pack_channels(
+        pixels: __m128i,
+        shifts: &[__m128i; 3],
+        masks: &[__m128i; 3],
+    ) -> __m128i {
+        let r = _mm_and_si128(_mm_srl_epi32(pixels, shifts[0]), masks[0]);
+        let g = _mm_and_si128(_mm_srl_epi32(pixels, shifts[1]), masks[1]);
+        let b = _mm_and_si128(_mm_srl_epi32(pixels, shifts[2]), masks[2]);
+
+        _mm_or_si128(_mm_or_si128(r, g), b)
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn pack_xrgb8888_sse2(src: &[u32], dst: &mut [u16], layout: &Layout16) -> usize {
+        let count = src.len().min(dst.len()) / 8 * 8;
+
+        let shifts = [
+            _mm_cvtsi32_si128(layout.r_shift as i32),
+            _mm_cvtsi32_si128(layout.g_shift as i32),
+            _mm_cvtsi32_si128(layout.b_shift as i32),
+        ];
+        let masks = [
+            _mm_set1_epi32(layout.r_mask as i32),
+            _mm_set1_epi32(layout.g_mask as i32),
+            _mm_set1_epi32(layout.b_mask as i32),
+        ];
+
+        // SSE2 can only pack with signed saturation,
+        // so move the values into the signed range and back.
+        let bias = _mm_set1_epi32(0x8000);
+        let unbias = _mm_set1_epi16(0x8000u16 as i16);
+
+        for offset in (0..count).step_by(8) {
+            let low = _mm_loadu_si128(src.as_ptr().add(offset) as *const __m128i);
+            let high = _mm_loadu_si128(src.as_ptr().add(offset + 4) as *const __m128i);
+
+            let low = _mm_sub_epi32(pack_channels(low, &shifts, &masks), bias);
+            let high = _mm_sub_epi32(pack_channels(high, &shifts, &masks), bias);
+            let packed = _mm_xor_si128(_mm_packs_epi32(low, high), unbias);
+
+            _mm_storeu_si128(dst.as_mut_ptr().add(offset) as *mut __m128i, packed);
+        }
+
+        count
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+mod simd {
+    use super::Layout16;
+    use std::arch::aarch64::*;
+
+    /// Returns the number of converted pixels.
+    pub(super) fn pack_xrgb8888(src: &[u32], dst: &mut [u16], layout: &Layout16) -> usize {
+        let count = src.len().min(dst.len()) / 4 * 4;
+
+        unsafe {
+            // Negative shifts shift to the right
+            let r_shift = vdupq_n_s32(-(layout.r_shift as i32));
+            let g_shift = vdupq_n_s32(-(layout.g_shift as i32));
+            let b_shift = vdupq_n_s32(-(layout.b_shift as i32));
+            let r_mask = vdupq_n_u32(layout.r_mask);
+            let g_mask = vdupq_n_u32(layout.g_mask);
+            let b_mask = vdupq_n_u32(layout.b_mask);
+
+            for offset in (0..count).step_by(4) {
+                let pixels = vld1q_u32(src.as_ptr().add(offset));
+
+                let r = vandq_u32(vshlq_u32(pixels, r_shift), r_mask);
+                let g = vandq_u32(vshlq_u32(pixels, g_shift), g_mask);
+                let b = vandq_u32(vshlq_u32(pixels, b_shift), b_mask);
+
+                let packed = vmovn_u32(vorrq_u32(vorrq_u32(r, g), b));
+                vst1_u16(dst.as_mut_ptr().add(offset), packed);
+            }
+        }
+
+        count
+    }
+}
+
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    all(target_arch = "aarch64", target_feature = "neon")
+)))]
+mod simd {
+    use super::Layout16;
+
+    pub(super) fn pack_xrgb8888(_src: &[u32], _dst: &mut [u16], _layout: &Layout16) -> usize {
+        0
+    }
+}
+
+#[test]
+fn pixel_format_conversions() {
+    let src: Vec<u32> = (0..19u32).map(|i| i.wrapping_mul(0x9E37_79B9)).collect();
+
+    let mut rgb565 = vec![0; src.len()];
+    xrgb8888_to_rgb565(&src, &mut rgb565);
+
+    let mut xrgb1555 = vec![0; src.len()];
+    xrgb8888_to_xrgb1555(&src, &mut xrgb1555);
+
+    for (i, pixel) in src.iter().enumerate() {
+        assert_eq!(rgb565[i], RGB565.pack(*pixel));
+        assert_eq!(xrgb1555[i], XRGB1555.pack(*pixel));
+    }
+
+    let mut converted = [0; 1];
+    xrgb1555_to_rgb565(&[0x7FFF], &mut converted);
+    assert_eq!(converted[0], 0xFFFF);
+    rgb565_to_xrgb1555(&[0xFFFF], &mut converted);
+    assert_eq!(converted[0], 0x7FFF);
+
+    let mut xrgb8888 = [0; 1];
+    rgb565_to_xrgb8888(&[0xF800], &mut xrgb8888);
+    assert_eq!(xrgb8888[0], 0x00FF_0000);
+
+    let mut rgba = [0; 4];
+    xrgb8888_to_rgba8888(&[0x0012_3456], &mut rgba);
+    assert_eq!(rgba, [0x12, 0x34, 0x56, 0xFF]);
+    rgba8888_to_xrgb8888(&rgba, &mut xrgb8888);
+    assert_eq!(xrgb8888[0], 0x0012_3456);
+}
@@ -0,0 +1,598 @@
+//! Hash functions used to identify content.
+//!
+//! Frontends and the libretro databases identify content by its CRC-32, MD5 or SHA-1.
+//! [`ContentHashes`] computes all three in a single pass, streaming files through the
+//! VFS interface instead of loading them into memory when possible.
+use crate::{content::*, contexts::*, util::*};
+use std::{fmt::Write, io::Read};
+
+/// The size of the chunks files are read in.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Lookup table for the reflected CRC-32 polynomial `0xEDB88320`.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut index = 0;
+
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+
+        table[index] = crc;
+        index += 1;
+    }
+
+    table
+};
+
+/// Computes the CRC-32 checksum (as used by zip archives and the libretro databases) of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+/// Computes a CRC-32 checksum over multiple chunks of data.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { crc: !0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.crc = (self.crc >> 8) ^ CRC32_TABLE[((self.crc ^ *byte as u32) & 0xFF) as usize];
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.crc
+    }
+}
+
+const XXH64_PRIME_1: u64 = 0x9E37_79B1_85EB_CA87;
+const XXH64_PRIME_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const XXH64_PRIME_3: u64 = 0x1656_67B1_9E37_79F9;
+const XXH64_PRIME_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const XXH64_PRIME_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+fn xxh64_round(acc: u64, lane: u64) -> u64 {
+    acc.wrapping_add(lane.wrapping_mul(XXH64_PRIME_2))
+        .rotate_left(31)
+        .wrapping_mul(XXH64_PRIME_1)
+}
+
+fn xxh64_merge(acc: u64, lane: u64) -> u64 {
+    (acc ^ xxh64_round(0, lane))
+        .wrapping_mul(XXH64_PRIME_1)
+        .wrapping_add(XXH64_PRIME_4)
+}
+
+/// Computes the 64 bit xxHash of `data`, a fast non-cryptographic hash,
+/// e.g. to tell whether a frame changed.
+pub fn xxh64(data: &[u8], seed: u64) -> u64 {
+    let read_u64 = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().unwrap());
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap()) as u64;
+
+    let mut stripes = data.chunks_exact(32);
+    let mut hash = if data.len() >= 32 {
+        let mut acc = [
+            seed.wrapping_add(XXH64_PRIME_1).wrapping_add(XXH64_PRIME_2),
+            seed.wrapping_add(XXH64_PRIME_2),
+            seed,
+            seed.wrapping_sub(XXH64_PRIME_1),
+        ];
+
+        for stripe in stripes.by_ref() {
+            for (acc, lane) in acc.iter_mut().zip(stripe.chunks_exact(8)) {
+                *acc = xxh64_round(*acc, read_u64(lane));
+            }
+        }
+
+        let hash = acc[0]
+            .rotate_left(1)
+            .wrapping_add(acc[1].rotate_left(7))
+            .wrapping_add(acc[2].rotate_left(12))
+            .wrapping_add(acc[3].rotate_left(18));
+
+        acc.iter().fold(hash, |hash, &acc| xxh64_merge(hash, acc))
+    } else {
+        seed.wrapping_add(XXH64_PRIME_5)
+    };
+
+    hash = hash.wrapping_add(data.len() as u64);
+
+    let mut rest = stripes.remainder();
+
+    while rest.len() >= 8 {
+        hash ^= xxh64_round(0, read_u64(&rest[..8]));
+        hash = hash
+            .rotate_left(27)
+            .wrapping_mul(XXH64_PRIME_1)
+            .wrapping_add(XXH64_PRIME_4);
+        rest = &rest[8..];
+    }
+
+    if rest.len() >= 4 {
+        hash ^= read_u32(&rest[..4]).wrapping_mul(XXH64_PRIME_1);
+        hash = hash
+            .rotate_left(23)
+            .wrapping_mul(XXH64_PRIME_2)
+            .wrapping_add(XXH64_PRIME_3);
+        rest = &rest[4..];
+    }
+
+    for &byte in rest {
+        hash ^= (byte as u64).wrapping_mul(XXH64_PRIME_5);
+        hash = hash.rotate_left(11).wrapping_mul(XXH64_PRIME_1);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(XXH64_PRIME_2);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(XXH64_PRIME_3);
+    hash ^ (hash >> 32)
+}
+
+/// Buffers input until full 64 byte blocks are available, as needed by MD5 and SHA-1.
+#[derive(Debug, Clone, Copy)]
+struct BlockBuffer {
+    block: [u8; 64],
+    filled: usize,
+    length: u64,
+}
+
+impl BlockBuffer {
+    fn new() -> Self {
+        Self {
+            block: [0; 64],
+            filled: 0,
+            length: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8], mut process: impl FnMut(&[u8; 64])) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+
+        while !data.is_empty() {
+            let count = (64 - self.filled).min(data.len());
+            self.block[self.filled..self.filled + count].copy_from_slice(&data[..count]);
+            self.filled += count;
+            data = &data[count..];
+
+            if self.filled == 64 {
+                process(&self.block);
+                self.filled = 0;
+            }
+        }
+    }
+
+    /// Appends the padding and the message length in bits, encoded by `encode_length`.
+    fn finish(
+        mut self,
+        encode_length: impl Fn(u64) -> [u8; 8],
+        mut process: impl FnMut(&[u8; 64]),
+    ) {
+        let length = encode_length(self.length.wrapping_mul(8));
+
+        self.block[self.filled] = 0x80;
+        self.block[self.filled + 1..].fill(0);
+
+        if self.filled >= 56 {
+            process(&self.block);
+            self.block = [0; 64];
+        }
+
+        self.block[56..].copy_from_slice(&length);
+        process(&self.block);
+    }
+}
+
+/// Computes an MD5 digest over multiple chunks of data.
+#[derive(Debug, Clone, Copy)]
+pub struct Md5 {
+    state: [u32; 4],
+    buffer: BlockBuffer,
+}
+
+impl Default for Md5 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Md5 {
+    const SHIFTS: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+
+    /// `floor(abs(sin(i + 1)) * 2^32)`
+    const CONSTANTS: [u32; 64] = [
+        0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613,
+        0xfd469501, 0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193,
+        0xa679438e, 0x49b40821, 0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d,
+        0x02441453, 0xd8a1e681, 0xe7d3fbc8, 0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed,
+        0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a, 0xfffa3942, 0x8771f681, 0x6d9d6122,
+        0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70, 0x289b7ec6, 0xeaa127fa,
+        0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665, 0xf4292244,
+        0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+        0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb,
+        0xeb86d391,
+    ];
+
+    pub fn new() -> Self {
+        Self {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            buffer: BlockBuffer::new(),
+        }
+    }
+
+    fn process(state: &mut [u32; 4], block: &[u8; 64]) {
+        let mut words = [0u32; 16];
+        for (word, chunk) in words.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+
+        let [mut a, mut b, mut c, mut d] = *state;
+
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(Self::CONSTANTS[i])
+                .wrapping_add(words[g]);
+
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(Self::SHIFTS[i]));
+        }
+
+        for (value, result) in state.iter_mut().zip([a, b, c, d]) {
+            *value = value.wrapping_add(result);
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.buffer
+            .update(data, |block| Self::process(state, block));
+    }
+
+    pub fn finish(&self) -> [u8; 16] {
+        let mut state = self.state;
+        self.buffer
+            .finish(u64::to_le_bytes, |block| Self::process(&mut state, block));
+
+        let mut digest = [0; 16];
+        for (chunk, value) in digest.chunks_exact_mut(4).zip(state) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+
+        digest
+    }
+}
+
+/// Computes a SHA-1 digest over multiple chunks of data.
+#[derive(Debug, Clone, Copy)]
+pub struct Sha1 {
+    state: [u32; 5],
+    buffer: BlockBuffer,
+}
+
+impl Default for Sha1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sha1 {
+    pub fn new() -> Self {
+        Self {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0],
+            buffer: BlockBuffer::new(),
+        }
+    }
+
+    fn process(state: &mut [u32; 5], block: &[u8; 64]) {
+        let mut words = [0u32; 80];
+        for (word, chunk) in words.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes(chunk.try_into().unwrap());
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = *state;
+
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i / 20 {
+                0 => ((b & c) | (!b & d), 0x5a827999),
+                1 => (b ^ c ^ d, 0x6ed9eba1),
+                2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (value, result) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(result);
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.buffer
+            .update(data, |block| Self::process(state, block));
+    }
+
+    pub fn finish(&self) -> [u8; 20] {
+        let mut state = self.state;
+        self.buffer
+            .finish(u64::to_be_bytes, |block| Self::process(&mut state, block));
+
+        let mut digest = [0; 20];
+        for (chunk, value) in digest.chunks_exact_mut(4).zip(state) {
+            chunk.copy_from_slice(&value.to_be_bytes());
+        }
+
+        digest
+    }
+}
+
+/// The CRC-32, MD5 and SHA-1 of a piece of content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentHashes {
+    pub crc32: u32,
+    pub md5: [u8; 16],
+    pub sha1: [u8; 20],
+}
+
+impl ContentHashes {
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let mut hasher = ContentHasher::new();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    /// Hashes everything `reader` returns, without keeping it in memory.
+    pub fn from_reader(mut reader: impl Read) -> std::io::Result<Self> {
+        let mut hasher = ContentHasher::new();
+        let mut buffer = vec![0; CHUNK_SIZE];
+
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => return Ok(hasher.finish()),
+                Ok(length) => hasher.update(&buffer[..length]),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Hashes the file at `path` through the frontend’s VFS interface.
+    ///
+    /// Falls back to the local file system if the VFS interface has not been
+    /// enabled by [`SetEnvironmentContext::enable_vfs_interface`].
+    #[proc::unstable(feature = "env-commands")]
+    pub fn from_vfs(ctx: &GenericContext, path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        match hash_file_vfs(ctx, path)? {
+            Some(hashes) => Ok(hashes),
+            None => Ok(Self::from_reader(std::fs::File::open(path)?)?),
+        }
+    }
+
+    /// Hashes the content passed to [`Core::on_load_game`].
+    ///
+    /// In-memory content gets hashed directly, otherwise the file at
+    /// [`retro_game_info::path`] gets streamed from the VFS interface if available.
+    /// For `archive#entry` style paths the hashes of the selected entry are returned.
+    pub fn from_game_info(
+        game: &retro_game_info,
+        ctx: &GenericContext,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if !game.data.is_null() {
+            let data =
+                unsafe { std::slice::from_raw_parts(game.data as *const u8, game.size as usize) };
+
+            return Ok(Self::from_bytes(data));
+        }
+
+        let path = get_str_from_pointer(game.path)
+            .ok_or("The frontend provided neither content data nor a path")?;
+
+        if let (_, Some(_)) = split_archive_path(path) {
+            let mut reader = ArchiveReader::from_game_info(game, ctx)?;
+            return Ok(Self::from_bytes(&reader.read_default_entry()?));
+        }
+
+        unsafe { Self::from_vfs(ctx, path) }
+    }
+
+    /// The MD5 as a lowercase hex string, as used by the libretro databases.
+    pub fn md5_hex(&self) -> String {
+        to_hex(&self.md5)
+    }
+
+    /// The SHA-1 as a lowercase hex string, as used by the libretro databases.
+    pub fn sha1_hex(&self) -> String {
+        to_hex(&self.sha1)
+    }
+}
+
+/// Computes all [`ContentHashes`] over multiple chunks of data.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ContentHasher {
+    crc32: Crc32,
+    md5: Md5,
+    sha1: Sha1,
+}
+
+impl ContentHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.crc32.update(data);
+        self.md5.update(data);
+        self.sha1.update(data);
+    }
+
+    pub fn finish(&self) -> ContentHashes {
+        ContentHashes {
+            crc32: self.crc32.finish(),
+            md5: self.md5.finish(),
+            sha1: self.sha1.finish(),
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Streams a file through the VFS interface.
+///
+/// Returns `Ok(None)` if the VFS interface is unavailable.
+fn hash_file_vfs(
+    ctx: &GenericContext,
+    path: &str,
+) -> Result<Option<ContentHashes>, Box<dyn std::error::Error>> {
+    let interfaces = ctx.interfaces.read().unwrap();
+
+    let interface = match interfaces.vfs_interface_info.interface {
+        Some(interface) => interface,
+        None => return Ok(None),
+    };
+
+    if let (Some(open), Some(read), Some(close)) = (interface.open, interface.read, interface.close)
+    {
+        let path = CString::new(path)?;
+
+        unsafe {
+            let handle = open(
+                path.as_ptr(),
+                RETRO_VFS_FILE_ACCESS_READ,
+                RETRO_VFS_FILE_ACCESS_HINT_NONE,
+            );
+            if handle.is_null() {
+                return Err(format!("Failed to open {path:?}").into());
+            }
+
+            let mut hasher = ContentHasher::new();
+            let mut buffer = vec![0u8; CHUNK_SIZE];
+
+            let result = loop {
+                let length = read(handle, buffer.as_mut_ptr() as *mut _, buffer.len() as u64);
+
+                match length {
+                    0 => break Ok(Some(hasher.finish())),
+                    length if length > 0 => hasher.update(&buffer[..length as usize]),
+                    _ => break Err(format!("Failed to read {path:?}").into()),
+                }
+            };
+
+            close(handle);
+
+            return result;
+        }
+    }
+
+    Ok(None)
+}
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+    let mut crc = Crc32::new();
+    crc.update(b"1234");
+    crc.update(b"56789");
+    assert_eq!(crc.finish(), 0xCBF4_3926);
+}
+
+#[test]
+fn content_hashes() {
+    let hashes = ContentHashes::from_bytes(b"abc");
+    assert_eq!(hashes.crc32, 0x3524_41C2);
+    assert_eq!(hashes.md5_hex(), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(
+        hashes.sha1_hex(),
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+
+    // Spans multiple blocks and needs an extra padding block
+    let data = [b'a'; 119];
+    let mut hasher = ContentHasher::new();
+    for chunk in data.chunks(7) {
+        hasher.update(chunk);
+    }
+    let hashes = hasher.finish();
+    assert_eq!(hashes.md5_hex(), "8a7bd0732ed6a28ce75f6dabc90e1613");
+    assert_eq!(
+        hashes.sha1_hex(),
+        "ee971065aaa017e0632a8ca6c77bb3bf8b1dfc56"
+    );
+    assert_eq!(hashes, ContentHashes::from_bytes(&data));
+    assert_eq!(
+        ContentHashes::from_reader(&data[..]).unwrap(),
+        ContentHashes::from_bytes(&data)
+    );
+
+    let empty = ContentHashes::from_bytes(&[]);
+    assert_eq!(empty.md5_hex(), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(empty.sha1_hex(), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+}
+
+#[test]
+fn xxh64_check_values() {
+    assert_eq!(xxh64(b"", 0), 0xEF46_DB37_51D8_E999);
+    assert_eq!(xxh64(b"a", 0), 0xD24E_C4F1_A98C_6E5B);
+    assert_eq!(xxh64(b"abc", 0), 0x44BC_2CF5_AD77_0999);
+
+    let data = (0..100).collect::<Vec<u8>>();
+    assert_eq!(xxh64(&data, 0), 0x6AC1_E580_3216_6597);
+}
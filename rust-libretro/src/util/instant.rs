@@ -0,0 +1,140 @@
+//! A monotonic clock that prefers the frontend’s perf interface.
+use crate::sys::retro_perf_get_time_usec_t;
+use std::{
+    ops::{Add, Sub},
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+/// The `get_time_usec` callback of the perf interface, if it has been enabled.
+static GET_TIME_USEC: RwLock<retro_perf_get_time_usec_t> = RwLock::new(None);
+
+pub(crate) fn set_time_source(get_time_usec: retro_perf_get_time_usec_t) {
+    *GET_TIME_USEC
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = get_time_usec;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Source {
+    /// Microseconds returned by `get_time_usec`.
+    Perf(i64),
+    Std(Instant),
+}
+
+/// A point in time like [`std::time::Instant`].
+///
+/// Once [`LoadGameContext::enable_perf_interface`] succeeded, the time is taken from the
+/// frontend’s `get_time_usec`, otherwise from [`Instant`], so timing code behaves the same
+/// whether or not the frontend provides the perf interface.
+///
+/// Instants taken before and after enabling the perf interface come from different
+/// clocks and can’t be compared, the duration between them is zero.
+///
+/// [`LoadGameContext::enable_perf_interface`]: crate::contexts::LoadGameContext::enable_perf_interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RetroInstant(Source);
+
+impl RetroInstant {
+    pub fn now() -> Self {
+        let get_time_usec = *GET_TIME_USEC
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        match get_time_usec {
+            Some(get_time_usec) => Self(Source::Perf(unsafe { get_time_usec() })),
+            None => Self(Source::Std(Instant::now())),
+        }
+    }
+
+    /// Returns the time passed since `earlier`, or `None` if `earlier` is later than `self`
+    /// or was taken from a different clock.
+    pub fn checked_duration_since(&self, earlier: RetroInstant) -> Option<Duration> {
+        match (self.0, earlier.0) {
+            (Source::Perf(now), Source::Perf(earlier)) => {
+                let usec = now.checked_sub(earlier)?;
+                u64::try_from(usec).ok().map(Duration::from_micros)
+            }
+            (Source::Std(now), Source::Std(earlier)) => now.checked_duration_since(earlier),
+            _ => None,
+        }
+    }
+
+    /// Returns the time passed since `earlier`, zero if `earlier` is later than `self`.
+    pub fn duration_since(&self, earlier: RetroInstant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    /// Returns the time passed since this instant was created.
+    pub fn elapsed(&self) -> Duration {
+        Self::now().duration_since(*self)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<RetroInstant> {
+        match self.0 {
+            Source::Perf(usec) => {
+                let duration = i64::try_from(duration.as_micros()).ok()?;
+                usec.checked_add(duration)
+                    .map(|usec| Self(Source::Perf(usec)))
+            }
+            Source::Std(instant) => instant.checked_add(duration).map(|i| Self(Source::Std(i))),
+        }
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<RetroInstant> {
+        match self.0 {
+            Source::Perf(usec) => {
+                let duration = i64::try_from(duration.as_micros()).ok()?;
+                usec.checked_sub(duration)
+                    .map(|usec| Self(Source::Perf(usec)))
+            }
+            Source::Std(instant) => instant.checked_sub(duration).map(|i| Self(Source::Std(i))),
+        }
+    }
+}
+
+impl Add<Duration> for RetroInstant {
+    type Output = RetroInstant;
+
+    /// Panics on overflow, like [`Instant`].
+    fn add(self, duration: Duration) -> RetroInstant {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl Sub<Duration> for RetroInstant {
+    type Output = RetroInstant;
+
+    fn sub(self, duration: Duration) -> RetroInstant {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl Sub<RetroInstant> for RetroInstant {
+    type Output = Duration;
+
+    fn sub(self, earlier: RetroInstant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+#[test]
+fn retro_instant() {
+    let earlier = RetroInstant(Source::Perf(1_000));
+    let later = earlier + Duration::from_millis(2);
+
+    assert_eq!(later, RetroInstant(Source::Perf(3_000)));
+    assert_eq!(later - earlier, Duration::from_millis(2));
+    assert_eq!(earlier.duration_since(later), Duration::ZERO);
+    assert_eq!(earlier.checked_duration_since(later), None);
+    assert_eq!(
+        later - Duration::from_micros(500),
+        RetroInstant(Source::Perf(2_500))
+    );
+
+    let other_clock = RetroInstant(Source::Std(Instant::now()));
+    assert_eq!(other_clock.checked_duration_since(earlier), None);
+    assert_eq!(other_clock.duration_since(earlier), Duration::ZERO);
+}
@@ -0,0 +1,123 @@
+//! A minimal PNG encoder for screenshots.
+//!
+//! The image data is stored without compression, which keeps the encoder tiny
+//! at the cost of larger files. Re-encode the images if their size matters.
+use super::hash::Crc32;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The maximum length of an uncompressed deflate block.
+const MAX_STORED_BLOCK: usize = u16::MAX as usize;
+
+/// Encodes an image of tightly packed 8 bit `R, G, B` pixels.
+///
+/// Returns an error if `rgb` holds less than `width * height` pixels.
+pub fn encode_rgb8(
+    width: u32,
+    height: u32,
+    rgb: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let row_length = width as usize * 3;
+
+    if width == 0 || height == 0 {
+        return Err("Images must not be empty".into());
+    } else if rgb.len() < row_length * height as usize {
+        return Err(format!(
+            "Expected {} bytes of image data, got {}",
+            row_length * height as usize,
+            rgb.len()
+        )
+        .into());
+    }
+
+    // Every row starts with its filter type, `0` meaning “none”
+    let mut scanlines = Vec::with_capacity((row_length + 1) * height as usize);
+    for row in rgb.chunks_exact(row_length).take(height as usize) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, color type 2 (RGB), deflate compression, adaptive filtering, no interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    Ok(png)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc.finish().to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let block_count = (data.len() / MAX_STORED_BLOCK) + 1;
+    let mut stream = Vec::with_capacity(data.len() + block_count * 5 + 6);
+
+    // 32K window, no preset dictionary, lowest compression level
+    stream.extend_from_slice(&[0x78, 0x01]);
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let length = block.len() as u16;
+
+        stream.push(is_final as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    // The largest number of bytes that can be summed up before `b` could overflow
+    const CHUNK: usize = 5552;
+
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for chunk in data.chunks(CHUNK) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+
+        a %= MOD;
+        b %= MOD;
+    }
+
+    (b << 16) | a
+}
+
+#[test]
+fn png_encoding() {
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+
+    let png = encode_rgb8(2, 2, &[0xFF; 12]).unwrap();
+    assert!(png.starts_with(SIGNATURE));
+    assert_eq!(&png[12..16], b"IHDR");
+    // IEND and its (constant) checksum
+    assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+
+    assert!(encode_rgb8(2, 2, &[0xFF; 11]).is_err());
+}
@@ -0,0 +1,117 @@
+//! A small, reproducible random number generator.
+use super::hash::crc32;
+
+/// A `xoshiro256**` random number generator.
+///
+/// Its output only depends on the seed, which makes it suitable for netplay and runahead,
+/// where every instance of the core needs to produce the same values.
+/// Store [`DeterministicRng::to_bytes`] in your savestates to restore the exact
+/// stream of numbers when loading a state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeterministicRng {
+    state: [u64; 4],
+}
+
+impl DeterministicRng {
+    /// The size of the serialized state in bytes.
+    pub const STATE_SIZE: usize = 32;
+
+    pub fn from_seed(seed: u64) -> Self {
+        // Expand the seed with SplitMix64, as recommended by the xoshiro authors
+        let mut seed = seed;
+        let mut next = || {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+
+        Self {
+            state: [next(), next(), next(), next()],
+        }
+    }
+
+    /// Seeds the generator with the CRC-32 of the content,
+    /// so that every instance running the same content starts with the same state.
+    pub fn from_content(data: &[u8]) -> Self {
+        Self::from_seed(crc32(data) as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a value in `0.0..1.0`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a value in `0..bound`, or `0` if `bound` is `0`.
+    pub fn below(&mut self, bound: u32) -> u32 {
+        // Lemire's multiply-shift method; the bias is negligible for 64 bit inputs
+        ((self.next_u32() as u64 * bound as u64) >> 32) as u32
+    }
+
+    pub fn fill_bytes(&mut self, dst: &mut [u8]) {
+        for chunk in dst.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    /// Serializes the state of the generator.
+    pub fn to_bytes(&self) -> [u8; Self::STATE_SIZE] {
+        let mut bytes = [0; Self::STATE_SIZE];
+
+        for (chunk, value) in bytes.chunks_exact_mut(8).zip(self.state) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Restores a state created by [`DeterministicRng::to_bytes`].
+    pub fn from_bytes(bytes: [u8; Self::STATE_SIZE]) -> Self {
+        let mut state = [0; 4];
+
+        for (value, chunk) in state.iter_mut().zip(bytes.chunks_exact(8)) {
+            *value = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+
+        Self { state }
+    }
+}
+
+#[test]
+fn deterministic_rng_state() {
+    let mut a = DeterministicRng::from_content(b"content");
+    let mut b = DeterministicRng::from_content(b"content");
+    assert_eq!(a.next_u64(), b.next_u64());
+
+    let saved = a.to_bytes();
+    let expected = (a.next_u64(), a.below(6), a.next_f64());
+
+    let mut restored = DeterministicRng::from_bytes(saved);
+    assert_eq!(
+        (restored.next_u64(), restored.below(6), restored.next_f64()),
+        expected
+    );
+    assert!(expected.1 < 6);
+}
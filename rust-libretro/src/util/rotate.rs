@@ -0,0 +1,325 @@
+//! Rotating and flipping software rendered frames, e.g. the fallback of
+//! [`LoadGameContext::set_rotation`](crate::contexts::LoadGameContext::set_rotation)
+//! for frontends that refuse to rotate the screen.
+//!
+//! Rotating by a quarter turn reads the rows of the source and writes the columns of the
+//! output, which thrashes the cache for large frames. The pixels get copied in tiles of
+//! [`TILE_SIZE`] × [`TILE_SIZE`] instead, so both stay in the cache while a tile is copied.
+use crate::{sys::retro_game_geometry, types::Rotation};
+
+/// The width and height in pixels of the tiles copied at once.
+pub const TILE_SIZE: usize = 32;
+
+/// A frame produced by [`RotatedSurface::rotate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RotatedFrame<'a> {
+    pub data: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    pub pitch: usize,
+}
+
+/// Rotates and flips frames into a buffer that is kept around between frames.
+///
+/// The frame gets flipped first and then turned like the frontend would turn it,
+/// e.g. [`Rotation::CounterClockwise90`] moves the top right corner to the top left.
+/// Pass the result to [`RunContext::draw_frame`](crate::contexts::RunContext::draw_frame),
+/// or use [`RunContext::draw_rotated_frame`](crate::contexts::RunContext::draw_rotated_frame).
+#[derive(Debug, Clone)]
+pub struct RotatedSurface {
+    rotation: Rotation,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+    buffer: Vec<u8>,
+}
+
+impl Default for RotatedSurface {
+    fn default() -> Self {
+        Self::new(Rotation::None)
+    }
+}
+
+impl RotatedSurface {
+    pub fn new(rotation: Rotation) -> Self {
+        Self {
+            rotation,
+            flip_horizontal: false,
+            flip_vertical: false,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Mirrors the frames before rotating them.
+    pub fn with_flip(mut self, horizontal: bool, vertical: bool) -> Self {
+        self.set_flip(horizontal, vertical);
+        self
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    pub fn set_flip(&mut self, horizontal: bool, vertical: bool) {
+        self.flip_horizontal = horizontal;
+        self.flip_vertical = vertical;
+    }
+
+    /// Returns the geometry of the frames this surface turns `geometry` into.
+    pub fn geometry(&self, geometry: retro_game_geometry) -> retro_game_geometry {
+        rotate_geometry(geometry, self.rotation)
+    }
+
+    /// Rotates a frame of `width` × `height` pixels of `bytes_per_pixel` bytes each.
+    /// The result is tightly packed.
+    ///
+    /// Panics if `src` is too small for the frame, or `bytes_per_pixel` is not 2 or 4.
+    pub fn rotate(
+        &mut self,
+        src: &[u8],
+        width: u32,
+        height: u32,
+        pitch: usize,
+        bytes_per_pixel: usize,
+    ) -> RotatedFrame<'_> {
+        let (width, height, pitch) = transform(
+            src,
+            width,
+            height,
+            pitch,
+            bytes_per_pixel,
+            self.rotation,
+            (self.flip_horizontal, self.flip_vertical),
+            &mut self.buffer,
+        );
+
+        RotatedFrame {
+            data: &self.buffer,
+            width,
+            height,
+            pitch,
+        }
+    }
+}
+
+/// Rotates a frame into `dst` and returns its width, height and pitch,
+/// see [`RotatedSurface::rotate`].
+pub fn rotate_frame(
+    src: &[u8],
+    width: u32,
+    height: u32,
+    pitch: usize,
+    bytes_per_pixel: usize,
+    rotation: Rotation,
+    dst: &mut Vec<u8>,
+) -> (u32, u32, usize) {
+    transform(
+        src,
+        width,
+        height,
+        pitch,
+        bytes_per_pixel,
+        rotation,
+        (false, false),
+        dst,
+    )
+}
+
+/// Returns the geometry of the frames [`rotate_frame`] turns `geometry` into.
+///
+/// Quarter turns swap the width and height and invert the aspect ratio.
+pub fn rotate_geometry(geometry: retro_game_geometry, rotation: Rotation) -> retro_game_geometry {
+    if !rotation.is_vertical() {
+        return geometry;
+    }
+
+    retro_game_geometry {
+        base_width: geometry.base_height,
+        base_height: geometry.base_width,
+        max_width: geometry.max_height,
+        max_height: geometry.max_width,
+        aspect_ratio: if geometry.aspect_ratio > 0.0 {
+            1.0 / geometry.aspect_ratio
+        } else {
+            geometry.aspect_ratio
+        },
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn transform(
+    src: &[u8],
+    width: u32,
+    height: u32,
+    pitch: usize,
+    bytes_per_pixel: usize,
+    rotation: Rotation,
+    (flip_horizontal, flip_vertical): (bool, bool),
+    dst: &mut Vec<u8>,
+) -> (u32, u32, usize) {
+    let (w, h) = (width as usize, height as usize);
+    if w == 0 || h == 0 {
+        dst.clear();
+        return (0, 0, 0);
+    }
+
+    assert!(src.len() >= pitch * (h - 1) + w * bytes_per_pixel);
+
+    let (dst_width, dst_height) = if rotation.is_vertical() {
+        (h, w)
+    } else {
+        (w, h)
+    };
+    let dst_pitch = dst_width * bytes_per_pixel;
+
+    dst.clear();
+    dst.resize(dst_pitch * dst_height, 0);
+
+    // Where the source pixel (0, 0) ends up and how far the next source pixel of a row
+    // and of a column is away from it in the output, in bytes
+    let (bpp, dp) = (bytes_per_pixel as isize, dst_pitch as isize);
+    let (w1, h1) = (w as isize - 1, h as isize - 1);
+    let (mut origin, mut dx, mut dy) = match rotation.get_env_value() {
+        1 => (w1 * dp, -dp, bpp),
+        2 => (h1 * dp + w1 * bpp, -bpp, -dp),
+        3 => (h1 * bpp, dp, -bpp),
+        _ => (0, bpp, dp),
+    };
+
+    if flip_horizontal {
+        origin += w1 * dx;
+        dx = -dx;
+    }
+    if flip_vertical {
+        origin += h1 * dy;
+        dy = -dy;
+    }
+
+    if (dx, dy) == (bpp, dp) {
+        for (row, dst_row) in src.chunks(pitch).zip(dst.chunks_exact_mut(dst_pitch)) {
+            dst_row.copy_from_slice(&row[..dst_pitch]);
+        }
+    } else {
+        match bytes_per_pixel {
+            2 => copy_tiled::<2>(src, w, h, pitch, dst, origin, dx, dy),
+            4 => copy_tiled::<4>(src, w, h, pitch, dst, origin, dx, dy),
+            _ => panic!("unsupported pixel size of {bytes_per_pixel} bytes"),
+        }
+    }
+
+    (dst_width as u32, dst_height as u32, dst_pitch)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[inline(always)]
+fn copy_tiled<const BPP: usize>(
+    src: &[u8],
+    w: usize,
+    h: usize,
+    pitch: usize,
+    dst: &mut [u8],
+    origin: isize,
+    dx: isize,
+    dy: isize,
+) {
+    for tile_y in (0..h).step_by(TILE_SIZE) {
+        for tile_x in (0..w).step_by(TILE_SIZE) {
+            let tile_width = TILE_SIZE.min(w - tile_x);
+
+            for y in tile_y..(tile_y + TILE_SIZE).min(h) {
+                let row = &src[y * pitch + tile_x * BPP..][..tile_width * BPP];
+                let mut offset = origin + tile_x as isize * dx + y as isize * dy;
+
+                for pixel in row.chunks_exact(BPP) {
+                    let offset_usize = offset as usize;
+                    dst[offset_usize..offset_usize + BPP].copy_from_slice(pixel);
+                    offset += dx;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn rotations() {
+    // 1 2 3
+    // 4 5 6
+    let src: Vec<u8> = [1u16, 2, 3, 0, 4, 5, 6, 0]
+        .iter()
+        .flat_map(|pixel| pixel.to_ne_bytes())
+        .collect();
+    let pixels = |bytes: &[u8]| -> Vec<u16> {
+        bytes
+            .chunks_exact(2)
+            .map(|pixel| u16::from_ne_bytes([pixel[0], pixel[1]]))
+            .collect()
+    };
+
+    let mut dst = Vec::new();
+    let mut rotate = |rotation| {
+        let size = rotate_frame(&src, 3, 2, 8, 2, rotation, &mut dst);
+        (size, pixels(&dst))
+    };
+
+    assert_eq!(rotate(Rotation::None), ((3, 2, 6), vec![1, 2, 3, 4, 5, 6]));
+    assert_eq!(
+        rotate(Rotation::CounterClockwise90),
+        ((2, 3, 4), vec![3, 6, 2, 5, 1, 4])
+    );
+    assert_eq!(
+        rotate(Rotation::Clockwise180),
+        ((3, 2, 6), vec![6, 5, 4, 3, 2, 1])
+    );
+    assert_eq!(
+        rotate(Rotation::Clockwise90),
+        ((2, 3, 4), vec![4, 1, 5, 2, 6, 3])
+    );
+
+    let mut surface = RotatedSurface::new(Rotation::None).with_flip(true, false);
+    assert_eq!(
+        pixels(surface.rotate(&src, 3, 2, 8, 2).data),
+        [3, 2, 1, 6, 5, 4]
+    );
+
+    surface.set_flip(false, true);
+    surface.set_rotation(Rotation::Clockwise90);
+    assert_eq!(
+        pixels(surface.rotate(&src, 3, 2, 8, 2).data),
+        [1, 4, 2, 5, 3, 6]
+    );
+
+    // Frames larger than a tile
+    let (width, height) = (TILE_SIZE as u32 + 5, TILE_SIZE as u32 * 2 + 3);
+    let src: Vec<u8> = (0..width * height).flat_map(|i| i.to_ne_bytes()).collect();
+    let mut surface = RotatedSurface::new(Rotation::CounterClockwise90);
+    let frame = surface.rotate(&src, width, height, width as usize * 4, 4);
+    assert_eq!((frame.width, frame.height), (height, width));
+
+    for y in 0..height {
+        for x in 0..width {
+            let offset = ((width - 1 - x) * height + y) as usize * 4;
+            let pixel = u32::from_ne_bytes(frame.data[offset..offset + 4].try_into().unwrap());
+            assert_eq!(pixel, y * width + x);
+        }
+    }
+
+    let geometry = RotatedSurface::new(Rotation::Clockwise270).geometry(retro_game_geometry {
+        base_width: 320,
+        base_height: 240,
+        max_width: 640,
+        max_height: 480,
+        aspect_ratio: 4.0 / 3.0,
+    });
+    assert_eq!(
+        (
+            geometry.base_width,
+            geometry.base_height,
+            geometry.max_width
+        ),
+        (240, 320, 480)
+    );
+    assert_eq!(geometry.aspect_ratio, 0.75);
+}
@@ -0,0 +1,265 @@
+//! Scaling software rendered frames, e.g. to offer internal resolution options.
+//!
+//! A core renders at its internal resolution and scales the result to the base size
+//! of the geometry it reported, so the frontend always gets frames of the same size.
+//! [`Scaler`] keeps the output buffer around between frames and
+//! [`RunContext::draw_scaled_frame`](crate::contexts::RunContext::draw_scaled_frame)
+//! combines it with [`RunContext::draw_frame`](crate::contexts::RunContext::draw_frame).
+//!
+//! All buffers are tightly packed, i.e. their pitch equals their width.
+//! The functions panic if a buffer is smaller than `width * height` pixels.
+use super::convert::Pixel;
+
+/// How [`Scaler`] maps the source pixels to the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ScaleFilter {
+    /// See [`scale_nearest`].
+    #[default]
+    Nearest,
+
+    /// See [`scale_bilinear`].
+    Bilinear,
+
+    /// See [`scale_integer`].
+    Integer,
+}
+
+/// Scales [`XRGB8888`](crate::types::PixelFormat::XRGB8888) frames to a fixed output size.
+#[derive(Debug, Clone, Default)]
+pub struct Scaler {
+    filter: ScaleFilter,
+    buffer: Vec<u32>,
+}
+
+impl Scaler {
+    pub fn new(filter: ScaleFilter) -> Self {
+        Self {
+            filter,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn filter(&self) -> ScaleFilter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: ScaleFilter) {
+        self.filter = filter;
+    }
+
+    /// Scales `src` to `dst_width * dst_height` pixels and returns the scaled frame.
+    ///
+    /// If the sizes match, the frame only gets copied.
+    pub fn scale(
+        &mut self,
+        src: &[u32],
+        src_width: u32,
+        src_height: u32,
+        dst_width: u32,
+        dst_height: u32,
+    ) -> &[u32] {
+        let len = dst_width as usize * dst_height as usize;
+        self.buffer.clear();
+        self.buffer.resize(len, 0);
+
+        if src_width == dst_width && src_height == dst_height {
+            self.buffer.copy_from_slice(&src[..len]);
+            return &self.buffer;
+        }
+
+        let dst = &mut self.buffer;
+        match self.filter {
+            ScaleFilter::Nearest => {
+                scale_nearest(src, src_width, src_height, dst, dst_width, dst_height)
+            }
+            ScaleFilter::Bilinear => {
+                scale_bilinear(src, src_width, src_height, dst, dst_width, dst_height)
+            }
+            ScaleFilter::Integer => {
+                scale_integer(src, src_width, src_height, dst, dst_width, dst_height)
+            }
+        }
+
+        &self.buffer
+    }
+}
+
+/// Maps every output coordinate to the source coordinate of the pixel center, in 16.16 fixed point.
+#[inline(always)]
+fn positions(src_len: u32, dst_len: u32) -> impl Iterator<Item = u64> {
+    let step = ((src_len as u64) << 16) / dst_len.max(1) as u64;
+    (0..dst_len as u64).map(move |i| i * step + step / 2)
+}
+
+/// Picks the closest source pixel for every output pixel.
+pub fn scale_nearest<P: Pixel>(
+    src: &[P],
+    src_width: u32,
+    src_height: u32,
+    dst: &mut [P],
+    dst_width: u32,
+    dst_height: u32,
+) {
+    let (src_width, dst_width_usize) = (src_width as usize, dst_width as usize);
+    assert!(src.len() >= src_width * src_height as usize);
+    assert!(dst.len() >= dst_width_usize * dst_height as usize);
+
+    if src_width == 0 || src_height == 0 {
+        return;
+    }
+
+    let columns = positions(src_width as u32, dst_width)
+        .map(|x| (x >> 16) as usize)
+        .collect::<Vec<_>>();
+
+    let mut previous: Option<(usize, usize)> = None;
+    for (y, sy) in positions(src_height, dst_height).enumerate() {
+        let sy = (sy >> 16) as usize;
+        let start = y * dst_width_usize;
+
+        // Repeated source rows only get copied
+        if let Some((previous_sy, previous_start)) = previous {
+            if previous_sy == sy {
+                dst.copy_within(previous_start..start, start);
+                continue;
+            }
+        }
+
+        let src_row = &src[sy * src_width..][..src_width];
+        for (dst, x) in dst[start..][..dst_width_usize].iter_mut().zip(&columns) {
+            *dst = src_row[*x];
+        }
+
+        previous = Some((sy, start));
+    }
+}
+
+/// Scales by the largest integer factor that fits and centers the result on black borders.
+///
+/// Falls back to [`scale_nearest`] if the source is larger than the output.
+pub fn scale_integer<P: Pixel + Default>(
+    src: &[P],
+    src_width: u32,
+    src_height: u32,
+    dst: &mut [P],
+    dst_width: u32,
+    dst_height: u32,
+) {
+    let factor = (dst_width / src_width.max(1)).min(dst_height / src_height.max(1));
+    if factor == 0 {
+        scale_nearest(src, src_width, src_height, dst, dst_width, dst_height);
+        return;
+    }
+
+    let (src_width, src_height) = (src_width as usize, src_height as usize);
+    let (dst_width, dst_height) = (dst_width as usize, dst_height as usize);
+    let factor = factor as usize;
+    assert!(src.len() >= src_width * src_height);
+    assert!(dst.len() >= dst_width * dst_height);
+
+    let dst = &mut dst[..dst_width * dst_height];
+    dst.fill(P::default());
+
+    if src_width == 0 || src_height == 0 {
+        return;
+    }
+
+    let offset_x = (dst_width - src_width * factor) / 2;
+    let offset_y = (dst_height - src_height * factor) / 2;
+
+    for (sy, src_row) in src.chunks_exact(src_width).take(src_height).enumerate() {
+        let start = (offset_y + sy * factor) * dst_width + offset_x;
+
+        let row = &mut dst[start..][..src_width * factor];
+        for (pixels, src) in row.chunks_exact_mut(factor).zip(src_row) {
+            pixels.fill(*src);
+        }
+
+        for i in 1..factor {
+            dst.copy_within(start..start + src_width * factor, start + i * dst_width);
+        }
+    }
+}
+
+/// Interpolates between the channels of two `XRGB8888` pixels, `weight` is in `0..=256`.
+#[inline(always)]
+fn lerp(a: u32, b: u32, weight: u32) -> u32 {
+    let inverse = 256 - weight;
+    let rb = (((a & 0x00FF_00FF) * inverse + (b & 0x00FF_00FF) * weight) >> 8) & 0x00FF_00FF;
+    let g = (((a & 0x0000_FF00) * inverse + (b & 0x0000_FF00) * weight) >> 8) & 0x0000_FF00;
+    rb | g
+}
+
+/// Splits a 16.16 source position into the two neighbouring pixels and the weight of the second.
+#[inline(always)]
+fn neighbours(position: u64, len: usize) -> (usize, usize, u32) {
+    let position = position.saturating_sub(0x8000);
+    let first = ((position >> 16) as usize).min(len - 1);
+    let second = (first + 1).min(len - 1);
+    (first, second, ((position >> 8) & 0xFF) as u32)
+}
+
+/// Interpolates between the four closest source pixels, for
+/// [`XRGB8888`](crate::types::PixelFormat::XRGB8888) frames only.
+pub fn scale_bilinear(
+    src: &[u32],
+    src_width: u32,
+    src_height: u32,
+    dst: &mut [u32],
+    dst_width: u32,
+    dst_height: u32,
+) {
+    let (src_width, src_height) = (src_width as usize, src_height as usize);
+    assert!(src.len() >= src_width * src_height);
+    assert!(dst.len() >= dst_width as usize * dst_height as usize);
+
+    if src_width == 0 || src_height == 0 {
+        return;
+    }
+
+    let columns = positions(src_width as u32, dst_width)
+        .map(|x| neighbours(x, src_width))
+        .collect::<Vec<_>>();
+
+    let rows = dst.chunks_exact_mut(dst_width.max(1) as usize);
+    for (dst_row, sy) in rows.zip(positions(src_height as u32, dst_height)) {
+        let (top, bottom, weight_y) = neighbours(sy, src_height);
+        let top = &src[top * src_width..][..src_width];
+        let bottom = &src[bottom * src_width..][..src_width];
+
+        for (dst, (left, right, weight_x)) in dst_row.iter_mut().zip(&columns) {
+            let upper = lerp(top[*left], top[*right], *weight_x);
+            let lower = lerp(bottom[*left], bottom[*right], *weight_x);
+            *dst = lerp(upper, lower, weight_y);
+        }
+    }
+}
+
+#[test]
+fn scale_filters() {
+    let src = [0x00_00_00_00, 0x00_FF_FF_FF, 0x00_00_00_FF, 0x00_FF_00_00];
+
+    let mut nearest = [0u32; 16];
+    scale_nearest(&src, 2, 2, &mut nearest, 4, 4);
+    assert_eq!(nearest[..4], [src[0], src[0], src[1], src[1]]);
+    assert_eq!(nearest[4..8], nearest[..4]);
+    assert_eq!(nearest[12..], [src[2], src[2], src[3], src[3]]);
+
+    let mut halved = [0u32; 1];
+    scale_nearest(&nearest, 4, 4, &mut halved, 1, 1);
+    assert_eq!(halved, [src[3]]);
+
+    let mut integer = [1u16; 3 * 5];
+    scale_integer(&[7u16], 1, 1, &mut integer, 3, 5);
+    assert_eq!(integer, [0, 0, 0, 7, 7, 7, 7, 7, 7, 7, 7, 7, 0, 0, 0]);
+
+    let mut bilinear = [0u32; 3];
+    scale_bilinear(&[0x00_00_00_00, 0x00_FF_FF_FF], 2, 1, &mut bilinear, 3, 1);
+    assert_eq!(bilinear[0], 0);
+    assert_eq!(bilinear[1], 0x00_7E_7E_7E);
+    assert_eq!(bilinear[2], 0x00_FF_FF_FF);
+
+    let mut scaler = Scaler::new(ScaleFilter::Integer);
+    assert_eq!(scaler.scale(&src, 2, 2, 2, 2), src);
+    assert_eq!(scaler.scale(&src, 2, 2, 4, 4), nearest);
+}
@@ -0,0 +1,396 @@
+//! A lock-free, fixed-capacity single-producer single-consumer queue.
+//!
+//! Audio workers, input recorders and event queues hand values from one thread to
+//! exactly one other thread. [`channel`] splits a [`RingBuffer`] into a [`Producer`]
+//! and a [`Consumer`] which can be moved to different threads. Neither side ever
+//! blocks or allocates after creation: pushing into a full queue returns the value
+//! and popping from an empty queue returns [`None`].
+//!
+//! ```
+//! use rust_libretro::util::spsc;
+//!
+//! let (mut producer, mut consumer) = spsc::channel::<i16>(4);
+//!
+//! std::thread::spawn(move || {
+//!     for sample in 0..4 {
+//!         producer.push(sample).unwrap();
+//!     }
+//! })
+//! .join()
+//! .unwrap();
+//!
+//! let mut samples = [0; 8];
+//! assert_eq!(consumer.pop_slice(&mut samples), 4);
+//! assert_eq!(samples[..4], [0, 1, 2, 3]);
+//! ```
+//!
+//! The synchronization is checked with [loom](https://docs.rs/loom):
+//! `RUSTFLAGS="--cfg loom" cargo test --release --lib util::spsc`
+use std::mem::MaybeUninit;
+
+#[cfg(loom)]
+use loom::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+#[cfg(not(loom))]
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Mirrors the closure based API of `loom::cell::UnsafeCell`.
+#[cfg(not(loom))]
+struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    fn new(value: T) -> Self {
+        Self(std::cell::UnsafeCell::new(value))
+    }
+
+    fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
+
+/// Creates a queue holding up to `capacity` values and splits it.
+pub fn channel<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    RingBuffer::new(capacity).split()
+}
+
+/// The storage shared by a [`Producer`] and a [`Consumer`].
+///
+/// One slot more than the capacity is allocated so that a full queue can be told
+/// apart from an empty one without a shared counter.
+pub struct RingBuffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+
+    /// The slot the consumer reads next, only written by the consumer.
+    head: AtomicUsize,
+
+    /// The slot the producer writes next, only written by the producer.
+    tail: AtomicUsize,
+}
+
+// Values are moved from the producer’s thread to the consumer’s thread,
+// every slot is only accessed by one side at a time.
+unsafe impl<T: Send> Send for RingBuffer<T> {}
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T> RingBuffer<T> {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "The capacity of a RingBuffer must not be zero"
+        );
+
+        Self {
+            slots: (0..=capacity)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len() - 1
+    }
+
+    pub fn split(self) -> (Producer<T>, Consumer<T>) {
+        let buffer = Arc::new(self);
+
+        (
+            Producer {
+                buffer: Arc::clone(&buffer),
+            },
+            Consumer { buffer },
+        )
+    }
+
+    fn next(&self, index: usize) -> usize {
+        if index + 1 == self.slots.len() {
+            0
+        } else {
+            index + 1
+        }
+    }
+
+    fn len(&self, head: usize, tail: usize) -> usize {
+        if tail >= head {
+            tail - head
+        } else {
+            self.slots.len() - head + tail
+        }
+    }
+}
+
+impl<T> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        let mut head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+
+        while head != tail {
+            self.slots[head].with_mut(|slot| unsafe { (*slot).assume_init_drop() });
+            head = self.next(head);
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for RingBuffer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RingBuffer")
+            .field("capacity", &self.capacity())
+            .field("head", &self.head)
+            .field("tail", &self.tail)
+            .finish()
+    }
+}
+
+/// The writing half of a [`RingBuffer`].
+#[derive(Debug)]
+pub struct Producer<T> {
+    buffer: Arc<RingBuffer<T>>,
+}
+
+impl<T> Producer<T> {
+    /// Appends `value`, returns it back if the queue is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let tail = self.buffer.tail.load(Ordering::Relaxed);
+        let next = self.buffer.next(tail);
+
+        if next == self.buffer.head.load(Ordering::Acquire) {
+            return Err(value);
+        }
+
+        self.buffer.slots[tail].with_mut(|slot| unsafe { (*slot).write(value) });
+        self.buffer.tail.store(next, Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Appends as many values of `values` as fit, returns the number of appended values.
+    pub fn push_slice(&mut self, values: &[T]) -> usize
+    where
+        T: Copy,
+    {
+        values
+            .iter()
+            .take_while(|value| self.push(**value).is_ok())
+            .count()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// The number of values waiting to be popped.
+    ///
+    /// The consumer might pop values concurrently, so this is an upper bound.
+    pub fn len(&self) -> usize {
+        let head = self.buffer.head.load(Ordering::Acquire);
+        let tail = self.buffer.tail.load(Ordering::Relaxed);
+
+        self.buffer.len(head, tail)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// The number of values that can be pushed without failing.
+    pub fn free(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Whether the [`Consumer`] has been dropped, pushed values will never be read.
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.buffer) < 2
+    }
+}
+
+/// The reading half of a [`RingBuffer`].
+#[derive(Debug)]
+pub struct Consumer<T> {
+    buffer: Arc<RingBuffer<T>>,
+}
+
+impl<T> Consumer<T> {
+    /// Removes the oldest value.
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.buffer.head.load(Ordering::Relaxed);
+
+        if head == self.buffer.tail.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let value = self.buffer.slots[head].with(|slot| unsafe { (*slot).assume_init_read() });
+        self.buffer
+            .head
+            .store(self.buffer.next(head), Ordering::Release);
+
+        Some(value)
+    }
+
+    /// Returns the oldest value without removing it.
+    pub fn peek(&self) -> Option<&T> {
+        let head = self.buffer.head.load(Ordering::Relaxed);
+
+        if head == self.buffer.tail.load(Ordering::Acquire) {
+            return None;
+        }
+
+        // The producer doesn’t touch the slot until `head` moves on, which requires `&mut self`.
+        Some(self.buffer.slots[head].with(|slot| unsafe { (*slot).assume_init_ref() }))
+    }
+
+    /// Moves the oldest values into `values`, returns the number of moved values.
+    pub fn pop_slice(&mut self, values: &mut [T]) -> usize {
+        let mut count = 0;
+
+        for value in values.iter_mut() {
+            match self.pop() {
+                Some(next) => *value = next,
+                None => break,
+            }
+
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Pops values until the queue is empty.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.pop())
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// The number of values that can be popped.
+    ///
+    /// The producer might push values concurrently, so this is a lower bound.
+    pub fn len(&self) -> usize {
+        let head = self.buffer.head.load(Ordering::Relaxed);
+        let tail = self.buffer.tail.load(Ordering::Acquire);
+
+        self.buffer.len(head, tail)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the [`Producer`] has been dropped, no more values will arrive.
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.buffer) < 2
+    }
+}
+
+#[cfg(not(loom))]
+#[test]
+fn spsc_ring_buffer() {
+    let (mut producer, mut consumer) = channel(3);
+    assert_eq!(producer.capacity(), 3);
+    assert!(consumer.is_empty());
+    assert_eq!(consumer.pop(), None);
+
+    assert_eq!(producer.push_slice(&[1, 2, 3, 4]), 3);
+    assert!(producer.is_full());
+    assert_eq!(producer.push(5), Err(5));
+    assert_eq!(consumer.peek(), Some(&1));
+
+    let mut values = [0; 2];
+    assert_eq!(consumer.pop_slice(&mut values), 2);
+    assert_eq!(values, [1, 2]);
+    assert_eq!(producer.free(), 2);
+
+    // Wrap around the end of the slots
+    for value in 4..=9 {
+        assert_eq!(producer.push(value), Ok(()));
+        assert_eq!(consumer.pop(), Some(value - 1));
+    }
+
+    assert_eq!(consumer.drain().collect::<Vec<_>>(), [9]);
+    assert!(!producer.is_abandoned());
+
+    // Values left in the queue are dropped with it
+    let value = Arc::new(());
+    let (mut producer, consumer) = channel(2);
+    producer.push(Arc::clone(&value)).unwrap();
+    drop(consumer);
+    assert!(producer.is_abandoned());
+    drop(producer);
+    assert_eq!(Arc::strong_count(&value), 1);
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::thread;
+
+    #[test]
+    fn spsc_transfer() {
+        loom::model(|| {
+            let (mut producer, mut consumer) = channel(2);
+
+            let thread = thread::spawn(move || {
+                for value in 0..3 {
+                    while producer.push(value).is_err() {
+                        thread::yield_now();
+                    }
+                }
+            });
+
+            let mut expected = 0;
+            while expected < 3 {
+                match consumer.pop() {
+                    Some(value) => {
+                        assert_eq!(value, expected);
+                        expected += 1;
+                    }
+                    None => thread::yield_now(),
+                }
+            }
+
+            thread.join().unwrap();
+            assert!(consumer.is_empty());
+        });
+    }
+
+    #[test]
+    fn spsc_drop_pending() {
+        loom::model(|| {
+            let value = Arc::new(());
+            let (mut producer, mut consumer) = channel(1);
+
+            let pushed = Arc::clone(&value);
+            let thread = thread::spawn(move || {
+                let _ = producer.push(pushed);
+            });
+
+            drop(consumer.pop());
+            thread.join().unwrap();
+            drop(consumer);
+
+            assert_eq!(Arc::strong_count(&value), 1);
+        });
+    }
+}
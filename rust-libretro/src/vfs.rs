@@ -0,0 +1,225 @@
+//! Walking directories through the frontend’s VFS interface.
+//!
+//! [`VfsDir::walk`] lists a directory and all of its subdirectories, e.g. to find the
+//! files of multi-file content or an asset folder, optionally filtered by a glob pattern.
+//! It requires the `unstable-env-commands` feature and version 3 of the VFS interface,
+//! see [`SetEnvironmentContext::enable_vfs_interface`].
+//!
+//! [`SetEnvironmentContext::enable_vfs_interface`]: crate::contexts::SetEnvironmentContext::enable_vfs_interface
+use crate::{contexts::GenericContext, sys::*, types::VfsError};
+use std::ffi::{CStr, CString};
+
+/// An entry found by [`VfsDir::walk`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VfsEntry {
+    /// The path of the entry, starting with the path passed to [`VfsDir::walk`].
+    pub path: String,
+
+    /// The file name of the entry, decoded lossily if it is not valid UTF-8.
+    pub name: String,
+
+    pub is_dir: bool,
+}
+
+struct OpenDir {
+    path: String,
+    handle: *mut retro_vfs_dir_handle,
+}
+
+/// Iterates over a directory tree, depth first, see [`VfsDir::walk`].
+///
+/// The directories get closed when the iterator is dropped.
+pub struct VfsDir {
+    pattern: Option<String>,
+    include_hidden: bool,
+    stack: Vec<OpenDir>,
+
+    opendir: unsafe extern "C" fn(*const std::os::raw::c_char, bool) -> *mut retro_vfs_dir_handle,
+    readdir: unsafe extern "C" fn(*mut retro_vfs_dir_handle) -> bool,
+    dirent_get_name: unsafe extern "C" fn(*mut retro_vfs_dir_handle) -> *const std::os::raw::c_char,
+    dirent_is_dir: unsafe extern "C" fn(*mut retro_vfs_dir_handle) -> bool,
+    closedir: unsafe extern "C" fn(*mut retro_vfs_dir_handle) -> std::os::raw::c_int,
+}
+
+impl std::fmt::Debug for VfsDir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VfsDir")
+            .field("pattern", &self.pattern)
+            .field(
+                "open",
+                &self.stack.iter().map(|dir| &dir.path).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl VfsDir {
+    /// Lists `path` recursively, yielding the entries whose name matches `pattern`.
+    ///
+    /// Subdirectories get walked whether they match or not. See [`glob_match`] for
+    /// the supported patterns, an empty pattern matches every entry.
+    pub fn walk(ctx: &GenericContext, path: &str, pattern: &str) -> Result<Self, VfsError> {
+        let interfaces = ctx.interfaces.read().unwrap();
+        let info = &interfaces.vfs_interface_info;
+
+        let mut dir = Self {
+            pattern: (!pattern.is_empty()).then(|| pattern.to_owned()),
+            include_hidden: false,
+            stack: Vec::new(),
+
+            opendir: info.function("opendir", 3, |vfs| vfs.opendir)?,
+            readdir: info.function("readdir", 3, |vfs| vfs.readdir)?,
+            dirent_get_name: info.function("dirent_get_name", 3, |vfs| vfs.dirent_get_name)?,
+            dirent_is_dir: info.function("dirent_is_dir", 3, |vfs| vfs.dirent_is_dir)?,
+            closedir: info.function("closedir", 3, |vfs| vfs.closedir)?,
+        };
+
+        dir.open(path.to_owned())?;
+        Ok(dir)
+    }
+
+    /// Also lists hidden entries, only affects directories opened afterwards.
+    pub fn include_hidden(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    fn open(&mut self, path: String) -> Result<(), VfsError> {
+        let c_path = CString::new(path.as_str()).map_err(|_| VfsError::InvalidPath)?;
+
+        let handle = unsafe { (self.opendir)(c_path.as_ptr(), self.include_hidden) };
+        if handle.is_null() {
+            return Err(VfsError::Failed {
+                function: "opendir",
+            });
+        }
+
+        self.stack.push(OpenDir { path, handle });
+        Ok(())
+    }
+
+    fn close_top(&mut self) {
+        if let Some(dir) = self.stack.pop() {
+            unsafe { (self.closedir)(dir.handle) };
+        }
+    }
+}
+
+impl Iterator for VfsDir {
+    type Item = Result<VfsEntry, VfsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let dir = self.stack.last()?;
+            let handle = dir.handle;
+
+            if !unsafe { (self.readdir)(handle) } {
+                self.close_top();
+                continue;
+            }
+
+            let name = unsafe { (self.dirent_get_name)(handle) };
+            if name.is_null() {
+                return Some(Err(VfsError::Failed {
+                    function: "dirent_get_name",
+                }));
+            }
+
+            let name = unsafe { CStr::from_ptr(name) }
+                .to_string_lossy()
+                .into_owned();
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            let entry = VfsEntry {
+                path: join(&dir.path, &name),
+                is_dir: unsafe { (self.dirent_is_dir)(handle) },
+                name,
+            };
+
+            if entry.is_dir {
+                if let Err(err) = self.open(entry.path.clone()) {
+                    return Some(Err(err));
+                }
+            }
+
+            let matches = match self.pattern.as_deref() {
+                Some(pattern) => glob_match(pattern, &entry.name),
+                None => true,
+            };
+
+            if matches {
+                return Some(Ok(entry));
+            }
+        }
+    }
+}
+
+impl Drop for VfsDir {
+    fn drop(&mut self) {
+        while !self.stack.is_empty() {
+            self.close_top();
+        }
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() || dir.ends_with('/') || dir.ends_with('\\') {
+        format!("{dir}{name}")
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+/// Matches a file name against a glob pattern, ignoring ASCII case.
+///
+/// `*` matches any number of characters and `?` exactly one, e.g. `*.bin` or `disk?.img`.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    let (mut p, mut n) = (0, 0);
+    // The position after the last `*` and the name position it has been matched up to
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, n));
+            }
+            Some(&c) if c == '?' || c.eq_ignore_ascii_case(&name[n]) => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` match one more character
+                Some((star, matched)) => {
+                    p = star;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[test]
+fn glob_patterns() {
+    assert!(glob_match("*.bin", "track01.BIN"));
+    assert!(!glob_match("*.bin", "track01.cue"));
+    assert!(glob_match("disk?.img", "disk2.img"));
+    assert!(!glob_match("disk?.img", "disk10.img"));
+    assert!(glob_match("*a*b*", "xxaxxbxx"));
+    assert!(!glob_match("*a*b", "xxbxxa"));
+    assert!(glob_match("*", ""));
+    assert!(!glob_match("?", ""));
+    assert!(glob_match("exact", "Exact"));
+
+    assert_eq!(join("roms", "a.bin"), "roms/a.bin");
+    assert_eq!(join("roms/", "a.bin"), "roms/a.bin");
+}
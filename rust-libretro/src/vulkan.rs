@@ -0,0 +1,417 @@
+//! Presenting frames through the frontend’s Vulkan interface.
+//!
+//! `libretro_vulkan.h` is not bound by rust-libretro-sys, this module mirrors the parts of
+//! it a core needs to present frames, using plain pointers and integers for the Vulkan
+//! handles, so no Vulkan crate is required. The handles are ABI compatible with the ones
+//! of `ash` and can be converted with e.g. `vk::Semaphore::from_raw`.
+//!
+//! After [`Core::on_hw_context_reset`], get the interface with [`VulkanInterface::get`].
+//! A frame gets handed to the frontend with [`RunContext::draw_vulkan_frame`], in one of
+//! two [`SubmissionMode`]s:
+//!
+//! - [`SubmissionMode::CommandBuffers`]: the core records command buffers and the frontend
+//!   submits them on its queue before it uses the image.
+//! - [`SubmissionMode::External`]: the core submits its work itself, e.g. because a
+//!   library like wgpu owns the submission, and passes the semaphores it signals.
+//!   The frontend waits for them before it uses the image. The queue is shared with the
+//!   frontend, so every submission has to happen while holding a [`QueueLock`].
+//!
+//! ```ignore
+//! let vulkan = VulkanInterface::get(&ctx.into())?;
+//!
+//! {
+//!     let queue = vulkan.lock_queue()?;
+//!     device.queue_submit(queue.queue(), &submits, fence)?;
+//! }
+//!
+//! ctx.draw_vulkan_frame(&vulkan, &image, SubmissionMode::External(&[semaphore]), width, height)?;
+//! ```
+//!
+//! The interface is only valid until [`Core::on_hw_context_destroyed`] gets called,
+//! afterwards its methods return [`VulkanError::Destroyed`].
+//!
+//! [`Core::on_hw_context_reset`]: crate::core::Core::on_hw_context_reset
+//! [`Core::on_hw_context_destroyed`]: crate::core::Core::on_hw_context_destroyed
+//! [`RunContext::draw_vulkan_frame`]: crate::contexts::RunContext::draw_vulkan_frame
+#![allow(non_camel_case_types, non_snake_case)]
+
+use crate::{contexts::GenericContext, environment, sys::*};
+use std::{
+    ffi::c_void,
+    os::raw::c_char,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+pub type VkInstance = *mut c_void;
+pub type VkPhysicalDevice = *mut c_void;
+pub type VkDevice = *mut c_void;
+pub type VkQueue = *mut c_void;
+pub type VkCommandBuffer = *mut c_void;
+pub type VkImage = u64;
+pub type VkImageView = u64;
+pub type VkSemaphore = u64;
+pub type VkImageLayout = i32;
+
+pub type PFN_vkVoidFunction = Option<unsafe extern "system" fn()>;
+pub type PFN_vkGetInstanceProcAddr =
+    Option<unsafe extern "system" fn(VkInstance, *const c_char) -> PFN_vkVoidFunction>;
+pub type PFN_vkGetDeviceProcAddr =
+    Option<unsafe extern "system" fn(VkDevice, *const c_char) -> PFN_vkVoidFunction>;
+
+/// The version of `libretro_vulkan.h` this module mirrors.
+pub const RETRO_HW_RENDER_INTERFACE_VULKAN_VERSION: u32 = 5;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VkComponentMapping {
+    pub r: i32,
+    pub g: i32,
+    pub b: i32,
+    pub a: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VkImageSubresourceRange {
+    pub aspectMask: u32,
+    pub baseMipLevel: u32,
+    pub levelCount: u32,
+    pub baseArrayLayer: u32,
+    pub layerCount: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VkImageViewCreateInfo {
+    pub sType: i32,
+    pub pNext: *const c_void,
+    pub flags: u32,
+    pub image: VkImage,
+    pub viewType: i32,
+    pub format: i32,
+    pub components: VkComponentMapping,
+    pub subresourceRange: VkImageSubresourceRange,
+}
+
+/// The image the frontend presents, `create_info` describes how `image_view` has been created.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct retro_vulkan_image {
+    pub image_view: VkImageView,
+    pub image_layout: VkImageLayout,
+    pub create_info: VkImageViewCreateInfo,
+}
+
+pub type retro_vulkan_set_image_t = Option<
+    unsafe extern "C" fn(
+        handle: *mut c_void,
+        image: *const retro_vulkan_image,
+        num_semaphores: u32,
+        semaphores: *const VkSemaphore,
+        src_queue_family: u32,
+    ),
+>;
+pub type retro_vulkan_get_sync_index_t = Option<unsafe extern "C" fn(handle: *mut c_void) -> u32>;
+pub type retro_vulkan_get_sync_index_mask_t =
+    Option<unsafe extern "C" fn(handle: *mut c_void) -> u32>;
+pub type retro_vulkan_set_command_buffers_t =
+    Option<unsafe extern "C" fn(handle: *mut c_void, num_cmd: u32, cmd: *const VkCommandBuffer)>;
+pub type retro_vulkan_wait_sync_index_t = Option<unsafe extern "C" fn(handle: *mut c_void)>;
+pub type retro_vulkan_lock_queue_t = Option<unsafe extern "C" fn(handle: *mut c_void)>;
+pub type retro_vulkan_unlock_queue_t = Option<unsafe extern "C" fn(handle: *mut c_void)>;
+pub type retro_vulkan_set_signal_semaphore_t =
+    Option<unsafe extern "C" fn(handle: *mut c_void, semaphore: VkSemaphore)>;
+
+/// Returned by `RETRO_ENVIRONMENT_GET_HW_RENDER_INTERFACE` for [`retro_hw_context_type::RETRO_HW_CONTEXT_VULKAN`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct retro_hw_render_interface_vulkan {
+    pub interface_type: retro_hw_render_interface_type,
+    pub interface_version: u32,
+    pub handle: *mut c_void,
+    pub instance: VkInstance,
+    pub gpu: VkPhysicalDevice,
+    pub device: VkDevice,
+    pub get_device_proc_addr: PFN_vkGetDeviceProcAddr,
+    pub get_instance_proc_addr: PFN_vkGetInstanceProcAddr,
+    pub queue: VkQueue,
+    pub queue_index: u32,
+    pub set_image: retro_vulkan_set_image_t,
+    pub get_sync_index: retro_vulkan_get_sync_index_t,
+    pub get_sync_index_mask: retro_vulkan_get_sync_index_mask_t,
+    pub set_command_buffers: retro_vulkan_set_command_buffers_t,
+    pub wait_sync_index: retro_vulkan_wait_sync_index_t,
+    pub lock_queue: retro_vulkan_lock_queue_t,
+    pub unlock_queue: retro_vulkan_unlock_queue_t,
+    pub set_signal_semaphore: retro_vulkan_set_signal_semaphore_t,
+}
+
+/// Returned by [`VulkanInterface::get`] and by its other methods once the context has been destroyed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VulkanError {
+    /// The frontend did not provide a hardware render interface.
+    Unavailable,
+
+    /// The frontend provided the interface of another API.
+    WrongInterface(retro_hw_render_interface_type),
+
+    /// The frontend implements an older version of `libretro_vulkan.h`.
+    UnsupportedVersion(u32),
+
+    /// The hardware context the interface belongs to has been destroyed.
+    Destroyed,
+}
+
+impl std::fmt::Display for VulkanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unavailable => write!(f, "The hardware render interface is unavailable"),
+            Self::WrongInterface(interface) => {
+                write!(f, "Expected a Vulkan interface, got {interface:?}")
+            }
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "The Vulkan interface version {version} is older than {RETRO_HW_RENDER_INTERFACE_VULKAN_VERSION}"
+            ),
+            Self::Destroyed => write!(f, "The hardware context has been destroyed"),
+        }
+    }
+}
+
+impl std::error::Error for VulkanError {}
+
+/// How the work rendering a frame reaches the GPU, see the [module documentation](self).
+#[derive(Debug, Clone, Copy)]
+pub enum SubmissionMode<'a> {
+    /// The frontend submits these command buffers on its queue.
+    CommandBuffers(&'a [VkCommandBuffer]),
+
+    /// The core submitted its work while holding a [`QueueLock`] and signals these semaphores.
+    External(&'a [VkSemaphore]),
+}
+
+/// Counts the destroyed hardware contexts, the frontend frees the interface of each.
+static CONTEXT_GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// Invalidates all [`VulkanInterface`]s, called when the frontend destroys the hardware context.
+pub(crate) fn invalidate_interfaces() {
+    CONTEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The Vulkan interface of the current hardware context, see the [module documentation](self).
+///
+/// Copies outliving the context don’t point at freed memory, they return
+/// [`VulkanError::Destroyed`] instead.
+#[derive(Debug, Clone, Copy)]
+pub struct VulkanInterface {
+    interface: *const retro_hw_render_interface_vulkan,
+
+    /// The [`CONTEXT_GENERATION`] the interface belongs to.
+    generation: u32,
+}
+
+impl VulkanInterface {
+    /// Asks the frontend for the interface, which is only available between
+    /// [`Core::on_hw_context_reset`](crate::core::Core::on_hw_context_reset) and
+    /// [`Core::on_hw_context_destroyed`](crate::core::Core::on_hw_context_destroyed).
+    pub fn get(ctx: &GenericContext) -> Result<Self, VulkanError> {
+        let interface = unsafe {
+            environment::try_get_unchecked::<*const retro_hw_render_interface_vulkan>(
+                *ctx.environment_callback,
+                RETRO_ENVIRONMENT_GET_HW_RENDER_INTERFACE,
+            )
+        }
+        .map_err(|_| VulkanError::Unavailable)?;
+
+        // Check the common header before reading any Vulkan specific field
+        let header = match unsafe { (interface as *const retro_hw_render_interface).as_ref() } {
+            Some(header) => header,
+            None => return Err(VulkanError::Unavailable),
+        };
+
+        if header.interface_type != retro_hw_render_interface_type::RETRO_HW_RENDER_INTERFACE_VULKAN
+        {
+            return Err(VulkanError::WrongInterface(header.interface_type));
+        }
+
+        if header.interface_version < RETRO_HW_RENDER_INTERFACE_VULKAN_VERSION {
+            return Err(VulkanError::UnsupportedVersion(header.interface_version));
+        }
+
+        Ok(Self {
+            interface,
+            generation: CONTEXT_GENERATION.load(Ordering::Relaxed),
+        })
+    }
+
+    /// Whether the hardware context the interface belongs to still exists.
+    pub fn is_valid(&self) -> bool {
+        self.generation == CONTEXT_GENERATION.load(Ordering::Relaxed)
+    }
+
+    /// The raw interface, e.g. to create an `ash` device from its handles.
+    pub fn raw(&self) -> Result<&retro_hw_render_interface_vulkan, VulkanError> {
+        if !self.is_valid() {
+            return Err(VulkanError::Destroyed);
+        }
+
+        // The frontend keeps the interface alive until it destroys the context
+        Ok(unsafe { &*self.interface })
+    }
+
+    pub fn version(&self) -> Result<u32, VulkanError> {
+        Ok(self.raw()?.interface_version)
+    }
+
+    /// The index of the frame that is being rendered, see
+    /// [`FrameResources`](crate::hw_resources::FrameResources).
+    pub fn sync_index(&self) -> Result<u32, VulkanError> {
+        let raw = self.raw()?;
+
+        Ok(raw
+            .get_sync_index
+            .map_or(0, |get_sync_index| unsafe { get_sync_index(raw.handle) }))
+    }
+
+    /// Has a bit set for every sync index the frontend may use.
+    pub fn sync_index_mask(&self) -> Result<u32, VulkanError> {
+        let raw = self.raw()?;
+
+        Ok(raw
+            .get_sync_index_mask
+            .map_or(1, |get_sync_index_mask| unsafe {
+                get_sync_index_mask(raw.handle)
+            }))
+    }
+
+    /// Waits until the resources of the current sync index are no longer in use by the GPU.
+    pub fn wait_sync_index(&self) -> Result<(), VulkanError> {
+        let raw = self.raw()?;
+
+        if let Some(wait_sync_index) = raw.wait_sync_index {
+            unsafe { wait_sync_index(raw.handle) };
+        }
+
+        Ok(())
+    }
+
+    /// Locks the queue shared with the frontend until the returned guard gets dropped.
+    pub fn lock_queue(&self) -> Result<QueueLock<'_>, VulkanError> {
+        let raw = self.raw()?;
+
+        if let Some(lock_queue) = raw.lock_queue {
+            unsafe { lock_queue(raw.handle) };
+        }
+
+        Ok(QueueLock { interface: raw })
+    }
+
+    /// Has the frontend signal `semaphore` once it is done with the next presented image,
+    /// e.g. to reuse a single image. [`None`] stops signaling.
+    pub fn set_signal_semaphore(&self, semaphore: Option<VkSemaphore>) -> Result<(), VulkanError> {
+        let raw = self.raw()?;
+
+        if let Some(set_signal_semaphore) = raw.set_signal_semaphore {
+            unsafe { set_signal_semaphore(raw.handle, semaphore.unwrap_or(0)) };
+        }
+
+        Ok(())
+    }
+
+    /// Passes the image to present with the next frame and the work that renders it.
+    ///
+    /// `src_queue_family` is the queue family that owns the image, the frontend
+    /// takes care of the ownership transfer if it differs from its own.
+    pub fn set_image(
+        &self,
+        image: &retro_vulkan_image,
+        mode: SubmissionMode,
+        src_queue_family: u32,
+    ) -> Result<(), VulkanError> {
+        let raw = self.raw()?;
+
+        let semaphores: &[VkSemaphore] = match mode {
+            SubmissionMode::CommandBuffers(_) => &[],
+            SubmissionMode::External(semaphores) => semaphores,
+        };
+
+        if let Some(set_image) = raw.set_image {
+            unsafe {
+                set_image(
+                    raw.handle,
+                    image,
+                    semaphores.len() as u32,
+                    semaphores.as_ptr(),
+                    src_queue_family,
+                )
+            };
+        }
+
+        if let SubmissionMode::CommandBuffers(command_buffers) = mode {
+            if let Some(set_command_buffers) = raw.set_command_buffers {
+                unsafe {
+                    set_command_buffers(
+                        raw.handle,
+                        command_buffers.len() as u32,
+                        command_buffers.as_ptr(),
+                    )
+                };
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps the queue shared with the frontend locked, see [`VulkanInterface::lock_queue`].
+#[derive(Debug)]
+pub struct QueueLock<'a> {
+    interface: &'a retro_hw_render_interface_vulkan,
+}
+
+impl QueueLock<'_> {
+    /// The queue that may be used while the lock is held.
+    pub fn queue(&self) -> VkQueue {
+        self.interface.queue
+    }
+
+    /// The queue family of [`QueueLock::queue`].
+    pub fn queue_family(&self) -> u32 {
+        self.interface.queue_index
+    }
+}
+
+impl Drop for QueueLock<'_> {
+    fn drop(&mut self) {
+        if let Some(unlock_queue) = self.interface.unlock_queue {
+            unsafe { unlock_queue(self.interface.handle) };
+        }
+    }
+}
+
+#[test]
+fn destroyed_interface() {
+    // Enums and function pointers are valid when zeroed, the handles are only passed on
+    let raw = retro_hw_render_interface_vulkan {
+        interface_version: RETRO_HW_RENDER_INTERFACE_VULKAN_VERSION,
+        ..unsafe { std::mem::zeroed() }
+    };
+
+    let interface = VulkanInterface {
+        interface: &raw,
+        generation: CONTEXT_GENERATION.load(Ordering::Relaxed),
+    };
+    let copy = interface;
+
+    assert_eq!(
+        interface.version(),
+        Ok(RETRO_HW_RENDER_INTERFACE_VULKAN_VERSION)
+    );
+    assert_eq!(interface.sync_index_mask(), Ok(1));
+
+    invalidate_interfaces();
+
+    assert!(!copy.is_valid());
+    assert_eq!(copy.version(), Err(VulkanError::Destroyed));
+    assert!(copy.lock_queue().is_err());
+}
@@ -0,0 +1,434 @@
+//! Rendering with wgpu on the frontend’s Vulkan device.
+//!
+//! [`WgpuContext::new`] wraps the instance, physical device, device and queue of the
+//! [`VulkanInterface`] in a [`wgpu::Device`] and [`wgpu::Queue`], so a core can use wgpu
+//! without creating its own device. It keeps a [`RenderTarget`] for every sync index of
+//! the frontend:
+//!
+//! ```ignore
+//! fn on_run(&mut self, ctx: &mut RunContext, _delta_us: Option<i64>) {
+//!     let wgpu = self.wgpu.as_mut().unwrap();
+//!     let Ok(target) = wgpu.begin_frame() else {
+//!         return;
+//!     };
+//!
+//!     let mut encoder = wgpu.device().create_command_encoder(&Default::default());
+//!     // … render into `target.view`
+//!
+//!     if wgpu.submit([encoder.finish()]).is_ok() {
+//!         let _ = wgpu.present(ctx);
+//!     }
+//! }
+//! ```
+//!
+//! Create the context in [`Core::on_hw_context_reset`] and drop it in
+//! [`Core::on_hw_context_destroyed`]. The frontend has to create its device with the
+//! extensions wgpu needs, see [`wgpu_hal::vulkan::Adapter::required_device_extensions`].
+//!
+//! wgpu submits on the frontend’s queue, so [`WgpuContext::submit`] holds a
+//! [`QueueLock`](crate::vulkan::QueueLock) and the frontend sees the work in submission
+//! order without semaphores. The render targets are handed over in
+//! `VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL`, the layout wgpu leaves them in after a render pass.
+//!
+//! This module requires the `wgpu` feature.
+//!
+//! [`Core::on_hw_context_reset`]: crate::core::Core::on_hw_context_reset
+//! [`Core::on_hw_context_destroyed`]: crate::core::Core::on_hw_context_destroyed
+use crate::{
+    contexts::{GenericContext, RunContext},
+    hw_resources::FrameResources,
+    vulkan::{self, SubmissionMode, VulkanError, VulkanInterface},
+};
+use ash::vk::{self, Handle};
+use wgpu_hal::api::Vulkan;
+
+/// `VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL`
+const COLOR_ATTACHMENT_OPTIMAL: vulkan::VkImageLayout = 2;
+
+/// A texture the core renders a frame into and the view the frontend samples it with.
+#[derive(Debug)]
+pub struct RenderTarget {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    image: vulkan::retro_vulkan_image,
+}
+
+/// A wgpu device on top of the frontend’s Vulkan device, see the [module documentation](self).
+pub struct WgpuContext {
+    interface: VulkanInterface,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    adapter: wgpu::Adapter,
+    raw_device: ash::Device,
+
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    targets: FrameResources<RenderTarget>,
+}
+
+impl WgpuContext {
+    /// Creates the wgpu device with render targets of `width`×`height` pixels.
+    ///
+    /// `format` has to be one of [`wgpu::TextureFormat::Rgba8Unorm`],
+    /// [`wgpu::TextureFormat::Rgba8UnormSrgb`], [`wgpu::TextureFormat::Bgra8Unorm`]
+    /// and [`wgpu::TextureFormat::Bgra8UnormSrgb`].
+    pub fn new(
+        ctx: &GenericContext,
+        desc: &wgpu::DeviceDescriptor,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, WgpuError> {
+        vk_format(format).ok_or(WgpuError::UnsupportedFormat(format))?;
+
+        let interface = VulkanInterface::get(ctx).map_err(WgpuError::Vulkan)?;
+        let raw = *interface.raw().map_err(WgpuError::Vulkan)?;
+
+        unsafe {
+            // Both function pointer types only differ in their handle types
+            let get_instance_proc_addr = std::mem::transmute::<
+                vulkan::PFN_vkGetInstanceProcAddr,
+                Option<vk::PFN_vkGetInstanceProcAddr>,
+            >(raw.get_instance_proc_addr)
+            .ok_or(WgpuError::Vulkan(VulkanError::Unavailable))?;
+
+            let entry = ash::Entry::from_static_fn(vk::StaticFn {
+                get_instance_proc_addr,
+            });
+            let instance = ash::Instance::load(
+                entry.static_fn(),
+                vk::Instance::from_raw(raw.instance as u64),
+            );
+            let driver_api_version = entry
+                .try_enumerate_instance_version()
+                .ok()
+                .flatten()
+                .unwrap_or(vk::API_VERSION_1_0);
+
+            // The drop guard keeps wgpu from destroying the frontend’s instance
+            let hal_instance = <<Vulkan as wgpu_hal::Api>::Instance>::from_raw(
+                entry,
+                instance.clone(),
+                driver_api_version,
+                0,
+                Vec::new(),
+                wgpu_hal::InstanceFlags::empty(),
+                false,
+                Some(Box::new(())),
+            )
+            .map_err(WgpuError::Instance)?;
+
+            let exposed = hal_instance
+                .expose_adapter(vk::PhysicalDevice::from_raw(raw.gpu as u64))
+                .ok_or(WgpuError::NoAdapter)?;
+
+            let raw_device =
+                ash::Device::load(instance.fp_v1_0(), vk::Device::from_raw(raw.device as u64));
+            let extensions = exposed.adapter.required_device_extensions(desc.features);
+
+            // The frontend keeps owning the device, its queue is the first one of `queue_index`
+            let open_device = exposed
+                .adapter
+                .device_from_raw(
+                    raw_device.clone(),
+                    false,
+                    &extensions,
+                    desc.features,
+                    // No update-after-bind descriptors, the type isn’t exported by wgpu-hal
+                    std::iter::empty().collect(),
+                    raw.queue_index,
+                    0,
+                )
+                .map_err(WgpuError::Device)?;
+
+            let wgpu_instance = wgpu::Instance::from_hal::<Vulkan>(hal_instance);
+            let adapter = wgpu_instance.create_adapter_from_hal(exposed);
+            let (device, queue) = adapter
+                .create_device_from_hal(open_device, desc, None)
+                .map_err(WgpuError::RequestDevice)?;
+
+            Ok(Self {
+                interface,
+                device,
+                queue,
+                adapter,
+                raw_device,
+
+                format,
+                width,
+                height,
+                targets: FrameResources::new(),
+            })
+        }
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    pub fn adapter(&self) -> &wgpu::Adapter {
+        &self.adapter
+    }
+
+    pub fn interface(&self) -> &VulkanInterface {
+        &self.interface
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Changes the size of the render targets, they get recreated by the next [`WgpuContext::begin_frame`].
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+
+        let raw_device = &self.raw_device;
+        self.targets
+            .clear(|target| destroy_render_target(raw_device, target));
+    }
+
+    /// Waits until the frontend is done with the render target of the current sync index and returns it.
+    pub fn begin_frame(&mut self) -> Result<&RenderTarget, WgpuError> {
+        let (format, width, height) = (self.format, self.width, self.height);
+        let device = &self.device;
+        let raw_device = &self.raw_device;
+        let interface = &self.interface;
+
+        let update_targets = |targets: &mut FrameResources<RenderTarget>| {
+            targets.try_update(
+                interface.sync_index_mask().map_err(WgpuError::Vulkan)?,
+                |index| create_render_target(device, raw_device, format, width, height, index),
+                |target| destroy_render_target(raw_device, target),
+            )
+        };
+
+        update_targets(&mut self.targets)?;
+        interface.wait_sync_index().map_err(WgpuError::Vulkan)?;
+
+        let sync_index = interface.sync_index().map_err(WgpuError::Vulkan)?;
+        if self.targets.get(sync_index).is_none() {
+            // The frontend changed its sync index mask since it was fetched above
+            update_targets(&mut self.targets)?;
+        }
+
+        self.targets
+            .get(sync_index)
+            .ok_or(WgpuError::SyncIndex(sync_index))
+    }
+
+    /// Submits `command_buffers` on the queue shared with the frontend.
+    pub fn submit<I: IntoIterator<Item = wgpu::CommandBuffer>>(
+        &self,
+        command_buffers: I,
+    ) -> Result<(), WgpuError> {
+        let _lock = self.interface.lock_queue().map_err(WgpuError::Vulkan)?;
+        self.queue.submit(command_buffers);
+
+        Ok(())
+    }
+
+    /// Presents the render target of the current sync index.
+    pub fn present(&self, ctx: &mut RunContext) -> Result<(), WgpuError> {
+        let sync_index = self.interface.sync_index().map_err(WgpuError::Vulkan)?;
+
+        if let Some(target) = self.targets.get(sync_index) {
+            ctx.draw_vulkan_frame(
+                &self.interface,
+                &target.image,
+                SubmissionMode::External(&[]),
+                self.width,
+                self.height,
+            )
+            .map_err(WgpuError::Vulkan)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for WgpuContext {
+    fn drop(&mut self) {
+        let raw_device = &self.raw_device;
+        self.targets
+            .clear(|target| destroy_render_target(raw_device, target));
+    }
+}
+
+impl std::fmt::Debug for WgpuContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WgpuContext")
+            .field("format", &self.format)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("targets", &self.targets.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returned by [`WgpuContext::new`] and, once the hardware context has been destroyed,
+/// by the methods that use the [`VulkanInterface`].
+#[derive(Debug)]
+pub enum WgpuError {
+    Vulkan(VulkanError),
+
+    /// The render targets can’t use this format.
+    UnsupportedFormat(wgpu::TextureFormat),
+
+    Instance(wgpu_hal::InstanceError),
+
+    /// wgpu does not support the frontend’s physical device.
+    NoAdapter,
+
+    Device(wgpu_hal::DeviceError),
+    RequestDevice(wgpu::RequestDeviceError),
+
+    /// Creating the image view of a render target failed, e.g. because the device is out of memory.
+    ImageView(vk::Result),
+
+    /// The frontend’s sync index is outside of its sync index mask.
+    SyncIndex(u32),
+}
+
+impl std::fmt::Display for WgpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Vulkan(err) => write!(f, "{err}"),
+            Self::UnsupportedFormat(format) => {
+                write!(f, "{format:?} can’t be used for render targets")
+            }
+            Self::Instance(err) => write!(f, "Failed to wrap the Vulkan instance: {err}"),
+            Self::NoAdapter => write!(f, "wgpu does not support the physical device"),
+            Self::Device(err) => write!(f, "Failed to wrap the Vulkan device: {err}"),
+            Self::RequestDevice(err) => write!(f, "Failed to create the wgpu device: {err}"),
+            Self::ImageView(err) => {
+                write!(
+                    f,
+                    "Failed to create the image view of a render target: {err}"
+                )
+            }
+            Self::SyncIndex(index) => {
+                write!(
+                    f,
+                    "The sync index {index} is outside of the sync index mask"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for WgpuError {}
+
+fn vk_format(format: wgpu::TextureFormat) -> Option<vk::Format> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm => Some(vk::Format::R8G8B8A8_UNORM),
+        wgpu::TextureFormat::Rgba8UnormSrgb => Some(vk::Format::R8G8B8A8_SRGB),
+        wgpu::TextureFormat::Bgra8Unorm => Some(vk::Format::B8G8R8A8_UNORM),
+        wgpu::TextureFormat::Bgra8UnormSrgb => Some(vk::Format::B8G8R8A8_SRGB),
+        _ => None,
+    }
+}
+
+fn create_render_target(
+    device: &wgpu::Device,
+    raw_device: &ash::Device,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    index: u32,
+) -> Result<RenderTarget, WgpuError> {
+    let label = format!("libretro render target {index}");
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(&label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        // The frontend samples the image and may copy it, e.g. for screenshots
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut image = vk::Image::null();
+    unsafe {
+        texture.as_hal::<Vulkan, _>(|texture| {
+            if let Some(texture) = texture {
+                image = texture.raw_handle();
+            }
+        });
+    }
+
+    let create_info = vk::ImageViewCreateInfo::builder()
+        .image(image)
+        .view_type(vk::ImageViewType::TYPE_2D)
+        .format(vk_format(format).unwrap_or(vk::Format::UNDEFINED))
+        .subresource_range(vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        })
+        .build();
+
+    let image_view = unsafe { raw_device.create_image_view(&create_info, None) }
+        .map_err(WgpuError::ImageView)?;
+
+    let image = vulkan::retro_vulkan_image {
+        image_view: image_view.as_raw(),
+        image_layout: COLOR_ATTACHMENT_OPTIMAL,
+        create_info: vulkan::VkImageViewCreateInfo {
+            sType: create_info.s_type.as_raw(),
+            pNext: std::ptr::null(),
+            flags: create_info.flags.as_raw(),
+            image: create_info.image.as_raw(),
+            viewType: create_info.view_type.as_raw(),
+            format: create_info.format.as_raw(),
+            components: vulkan::VkComponentMapping {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            },
+            subresourceRange: vulkan::VkImageSubresourceRange {
+                aspectMask: vk::ImageAspectFlags::COLOR.as_raw(),
+                baseMipLevel: 0,
+                levelCount: 1,
+                baseArrayLayer: 0,
+                layerCount: 1,
+            },
+        },
+    };
+
+    Ok(RenderTarget {
+        texture,
+        view,
+        image,
+    })
+}
+
+fn destroy_render_target(raw_device: &ash::Device, target: RenderTarget) {
+    unsafe {
+        raw_device.destroy_image_view(vk::ImageView::from_raw(target.image.image_view), None);
+    }
+}